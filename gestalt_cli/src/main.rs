@@ -219,10 +219,19 @@ fn save_tasks(db_path: &str, tasks: &HashMap<String, Task>) -> Result<(), String
 }
 
 fn build_http_client() -> Result<reqwest::blocking::Client, String> {
-    reqwest::blocking::Client::builder()
-        .timeout(Duration::from_secs(20))
-        .build()
-        .map_err(|e| e.to_string())
+    let mut builder = reqwest::blocking::Client::builder().timeout(Duration::from_secs(20));
+    // `gestalt mcp-serve --http` refuses requests without the API token.
+    if let Ok(token) = std::env::var("GESTALT_API_TOKEN") {
+        if !token.is_empty() {
+            let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|e| e.to_string())?;
+            value.set_sensitive(true);
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+            builder = builder.default_headers(headers);
+        }
+    }
+    builder.build().map_err(|e| e.to_string())
 }
 
 fn call_mcp(
@@ -378,9 +387,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("📍 URL: http://{}:{}", host, port);
             println!();
 
-            // Run cargo
+            // The MCP server lives in the timeline binary (`gestalt mcp-serve`)
+            let port = port.to_string();
             let status = Command::new("cargo")
-                .args(["run", "-p", "gestalt_mcp", "--", "--http"])
+                .args([
+                    "run",
+                    "-p",
+                    "gestalt_timeline",
+                    "--bin",
+                    "gestalt",
+                    "--",
+                    "mcp-serve",
+                    "--http",
+                    "--host",
+                    &host,
+                    "--port",
+                    &port,
+                ])
                 .status()?;
 
             std::process::exit(status.code().unwrap_or(0));
@@ -402,7 +402,7 @@ mod tests {
     #[tokio::test]
     async fn test_echo_handler() {
         let mut handler = EchoHandler;
-        let result = handler.handle_input("test").await?;
+        let result = handler.handle_input("test").await.unwrap();
         assert_eq!(result, "Echo: test");
     }
 }
@@ -0,0 +1,229 @@
+//! Embeddings served over HTTP by OpenAI-compatible or Ollama servers.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::domain::rag::embeddings::EmbeddingModel;
+
+use super::DEFAULT_BATCH_SIZE;
+
+/// Wire format of the embeddings endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpEmbeddingApi {
+    /// `POST {base_url}/embeddings`, as served by OpenAI, vLLM and llama.cpp.
+    OpenAi,
+    /// `POST {base_url}/api/embed`.
+    Ollama,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// Embedding model behind an HTTP API.
+pub struct HttpEmbeddingModel {
+    client: reqwest::Client,
+    api: HttpEmbeddingApi,
+    base_url: String,
+    model: String,
+    model_id: String,
+    api_key: Option<String>,
+    dimension: usize,
+    batch_size: usize,
+}
+
+impl HttpEmbeddingModel {
+    pub fn new(
+        api: HttpEmbeddingApi,
+        base_url: impl Into<String>,
+        model: impl Into<String>,
+    ) -> Self {
+        let model = model.into();
+        let prefix = match api {
+            HttpEmbeddingApi::OpenAi => "openai",
+            HttpEmbeddingApi::Ollama => "ollama",
+        };
+        Self {
+            client: reqwest::Client::new(),
+            api,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model_id: format!("{}/{}", prefix, model),
+            model,
+            api_key: None,
+            dimension: 0,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        self.dimension = dimension;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Learn the vector length by embedding a probe text.
+    pub async fn probe_dimension(mut self) -> anyhow::Result<Self> {
+        let vectors = self.request(&["dimension probe".to_string()]).await?;
+        self.dimension = vectors.first().map(Vec::len).unwrap_or_default();
+        Ok(self)
+    }
+
+    async fn request(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let url = match self.api {
+            HttpEmbeddingApi::OpenAi => format!("{}/embeddings", self.base_url),
+            HttpEmbeddingApi::Ollama => format!("{}/api/embed", self.base_url),
+        };
+        let mut request = self
+            .client
+            .post(&url)
+            .json(&json!({ "model": self.model, "input": texts }));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Embedding request to {} failed: {} {}", url, status, body);
+        }
+        let vectors = match self.api {
+            HttpEmbeddingApi::OpenAi => {
+                let mut data = response.json::<OpenAiResponse>().await?.data;
+                data.sort_by_key(|d| d.index);
+                data.into_iter().map(|d| d.embedding).collect::<Vec<_>>()
+            }
+            HttpEmbeddingApi::Ollama => response.json::<OllamaResponse>().await?.embeddings,
+        };
+        if vectors.len() != texts.len() {
+            anyhow::bail!(
+                "Embedding server returned {} vectors for {} texts",
+                vectors.len(),
+                texts.len()
+            );
+        }
+        Ok(vectors)
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for HttpEmbeddingModel {
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let mut vectors = self.embed_batch(&[text.to_string()]).await?;
+        vectors
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Embedding server returned no vector"))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            for vector in self.request(batch).await? {
+                if self.dimension != 0 && vector.len() != self.dimension {
+                    anyhow::bail!(
+                        "{} returned a {}-dim vector, expected {}",
+                        self.model_id,
+                        vector.len(),
+                        self.dimension
+                    );
+                }
+                vectors.push(vector);
+            }
+        }
+        Ok(vectors)
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve `bodies` as JSON responses, one per connection, and return the base URL.
+    async fn serve(bodies: Vec<serde_json::Value>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for body in bodies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 64 * 1024];
+                let _ = socket.read(&mut buf).await.unwrap();
+                let body = body.to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_openai_and_ollama_embeddings() {
+        let base = serve(vec![
+            json!({"data": [{"index": 0, "embedding": [0.0, 1.0, 0.0]}]}),
+            json!({"data": [
+                {"index": 1, "embedding": [0.0, 0.0, 1.0]},
+                {"index": 0, "embedding": [1.0, 0.0, 0.0]}
+            ]}),
+        ])
+        .await;
+        let model = HttpEmbeddingModel::new(
+            HttpEmbeddingApi::OpenAi,
+            format!("{}/v1/", base),
+            "text-embedding-3-small",
+        )
+        .probe_dimension()
+        .await
+        .unwrap();
+        assert_eq!(model.model_id(), "openai/text-embedding-3-small");
+        assert_eq!(model.dimension(), 3);
+        let vectors = model
+            .embed_batch(&["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0]]);
+
+        let base = serve(vec![json!({"embeddings": [[0.5, 0.5]]})]).await;
+        let model = HttpEmbeddingModel::new(HttpEmbeddingApi::Ollama, base, "nomic-embed-text")
+            .with_dimension(3);
+        let err = model.embed("hi").await.unwrap_err().to_string();
+        assert!(
+            err.contains("returned a 2-dim vector, expected 3"),
+            "{}",
+            err
+        );
+    }
+}
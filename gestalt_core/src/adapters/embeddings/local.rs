@@ -0,0 +1,225 @@
+//! Sentence embeddings computed on the CPU from a local BERT-family model.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+use crate::domain::rag::embeddings::EmbeddingModel;
+
+use super::DEFAULT_BATCH_SIZE;
+
+struct Encoder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl Encoder {
+    /// Mean-pooled, L2-normalized embeddings of `texts`, as sentence-transformers computes them.
+    fn embed(&self, texts: Vec<String>) -> anyhow::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts, true)
+            .map_err(anyhow::Error::msg)?;
+        let rows = |field: fn(&tokenizers::Encoding) -> &[u32]| -> anyhow::Result<Tensor> {
+            let rows = encodings
+                .iter()
+                .map(|e| Tensor::new(field(e), &self.device))
+                .collect::<candle_core::Result<Vec<_>>>()?;
+            Ok(Tensor::stack(&rows, 0)?)
+        };
+        let input_ids = rows(tokenizers::Encoding::get_ids)?;
+        let type_ids = rows(tokenizers::Encoding::get_type_ids)?;
+        let mask = rows(tokenizers::Encoding::get_attention_mask)?;
+
+        let hidden = self.model.forward(&input_ids, &type_ids, Some(&mask))?;
+        let mask = mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+        let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+        let mean = summed.broadcast_div(&mask.sum(1)?)?;
+        let norm = mean.sqr()?.sum_keepdim(1)?.sqrt()?;
+        Ok(mean.broadcast_div(&norm)?.to_vec2::<f32>()?)
+    }
+}
+
+/// BERT-style sentence-embedding model (MiniLM, BGE, E5, ...) loaded from a
+/// directory holding `config.json`, `tokenizer.json` and `model.safetensors`.
+pub struct LocalEmbeddingModel {
+    encoder: Arc<Encoder>,
+    model_id: String,
+    dimension: usize,
+    batch_size: usize,
+}
+
+impl LocalEmbeddingModel {
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let config: Config = serde_json::from_str(
+            &std::fs::read_to_string(dir.join("config.json"))
+                .with_context(|| format!("Failed to read {}/config.json", dir.display()))?,
+        )
+        .context("Unsupported model config.json")?;
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).map_err(|e| {
+            anyhow::anyhow!("Failed to load {}/tokenizer.json: {}", dir.display(), e)
+        })?;
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            pad_id: config.pad_token_id as u32,
+            ..Default::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(anyhow::Error::msg)?;
+
+        let device = Device::Cpu;
+        let weights = dir.join("model.safetensors");
+        // SAFETY: the file is mapped read-only and must not be modified while loaded.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[&weights], DType::F32, &device) }
+            .with_context(|| format!("Failed to load {}", weights.display()))?;
+        let model = BertModel::load(vb, &config)?;
+
+        Ok(Self {
+            encoder: Arc::new(Encoder {
+                model,
+                tokenizer,
+                device,
+            }),
+            model_id: dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "local".to_string()),
+            dimension: config.hidden_size,
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    /// Record vectors under `model_id` instead of the directory name.
+    pub fn with_model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = model_id.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for LocalEmbeddingModel {
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let mut vectors = self.embed_batch(&[text.to_string()]).await?;
+        vectors
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Model returned no embedding"))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            let encoder = self.encoder.clone();
+            let batch = batch.to_vec();
+            vectors.extend(tokio::task::spawn_blocking(move || encoder.embed(batch)).await??);
+        }
+        Ok(vectors)
+    }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_nn::VarMap;
+
+    /// A randomly initialised two-layer BERT with a six-word vocabulary.
+    fn write_tiny_model(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        let config = serde_json::json!({
+            "vocab_size": 6,
+            "hidden_size": 8,
+            "num_hidden_layers": 2,
+            "num_attention_heads": 2,
+            "intermediate_size": 16,
+            "hidden_act": "gelu",
+            "hidden_dropout_prob": 0.0,
+            "max_position_embeddings": 16,
+            "type_vocab_size": 2,
+            "initializer_range": 0.02,
+            "layer_norm_eps": 1e-12,
+            "pad_token_id": 0,
+            "classifier_dropout": null,
+            "model_type": "bert"
+        });
+        std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+
+        let tokenizer = serde_json::json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": {"type": "Whitespace"},
+            "post_processor": null,
+            "decoder": null,
+            "model": {
+                "type": "WordLevel",
+                "vocab": {"[PAD]": 0, "[UNK]": 1, "hello": 2, "world": 3, "rust": 4, "code": 5},
+                "unk_token": "[UNK]"
+            }
+        });
+        std::fs::write(dir.join("tokenizer.json"), tokenizer.to_string()).unwrap();
+
+        let config: Config = serde_json::from_value(config).unwrap();
+        let varmap = VarMap::new();
+        let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+        BertModel::load(vb, &config).unwrap();
+        varmap.save(dir.join("model.safetensors")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_model_embeds_in_batches() {
+        let dir = std::env::temp_dir()
+            .join(format!("gestalt-embed-{}", std::process::id()))
+            .join("tiny-bert");
+        write_tiny_model(&dir);
+
+        let model = LocalEmbeddingModel::load(&dir).unwrap().with_batch_size(2);
+        assert_eq!(model.model_id(), "tiny-bert");
+        assert_eq!(model.dimension(), 8);
+
+        let texts = vec![
+            "hello world".to_string(),
+            "rust code rust code".to_string(),
+            "hello".to_string(),
+        ];
+        let batch = model.embed_batch(&texts).await.unwrap();
+        assert_eq!(batch.len(), 3);
+        for (text, vector) in texts.iter().zip(&batch) {
+            assert_eq!(vector.len(), 8);
+            let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-4);
+            // Padding inside a batch must not change a text's embedding.
+            let single = model.embed(text).await.unwrap();
+            assert!(single.iter().zip(vector).all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+        assert_ne!(batch[0], batch[1]);
+
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Embedding model backends: local BERT-family models and HTTP servers.
+
+mod http;
+mod local;
+
+pub use http::{HttpEmbeddingApi, HttpEmbeddingModel};
+pub use local::LocalEmbeddingModel;
+
+use std::sync::Arc;
+
+use crate::application::config::EmbeddingConfig;
+use crate::domain::rag::embeddings::{DummyEmbeddingModel, EmbeddingModel};
+
+/// Texts embedded per request or forward pass unless configured otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+
+/// Build the embedding model described by `config`.
+pub async fn from_config(config: &EmbeddingConfig) -> anyhow::Result<Arc<dyn EmbeddingModel>> {
+    let batch_size = config.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    let model: Arc<dyn EmbeddingModel> = match config.provider.to_lowercase().as_str() {
+        "local" => {
+            let path = config
+                .path
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Local embedding model needs a `path`"))?;
+            let mut model = LocalEmbeddingModel::load(path)?.with_batch_size(batch_size);
+            if let Some(id) = &config.model {
+                model = model.with_model_id(id);
+            }
+            Arc::new(model)
+        }
+        provider @ ("openai" | "ollama") => {
+            let (api, default_url, default_model) = if provider == "openai" {
+                (
+                    HttpEmbeddingApi::OpenAi,
+                    "https://api.openai.com/v1".to_string(),
+                    "text-embedding-3-small",
+                )
+            } else {
+                (
+                    HttpEmbeddingApi::Ollama,
+                    std::env::var("OLLAMA_HOST")
+                        .unwrap_or_else(|_| "http://localhost:11434".to_string()),
+                    "nomic-embed-text",
+                )
+            };
+            let mut model = HttpEmbeddingModel::new(
+                api,
+                config.base_url.clone().unwrap_or(default_url),
+                config.model.as_deref().unwrap_or(default_model),
+            )
+            .with_batch_size(batch_size);
+            let api_key = config.api_key.clone().or_else(|| {
+                (api == HttpEmbeddingApi::OpenAi)
+                    .then(|| std::env::var("OPENAI_API_KEY").ok())
+                    .flatten()
+            });
+            if let Some(key) = api_key {
+                model = model.with_api_key(key);
+            }
+            match config.dimension {
+                Some(dimension) => Arc::new(model.with_dimension(dimension)),
+                None => Arc::new(model.probe_dimension().await?),
+            }
+        }
+        "dummy" => Arc::new(DummyEmbeddingModel::new(config.dimension.unwrap_or(384))),
+        other => anyhow::bail!(
+            "Unknown embedding provider '{}' (expected local, openai, ollama or dummy)",
+            other
+        ),
+    };
+    tracing::info!(
+        "Embedding model: {} ({} dims)",
+        model.model_id(),
+        model.dimension()
+    );
+    Ok(model)
+}
@@ -1,3 +1,4 @@
 pub mod auth;
+pub mod embeddings;
 pub mod mcp;
 pub mod persistence;
@@ -0,0 +1,397 @@
+//! BM25 lexical search shared by the SurrealDB [`VectorDb`] adapters.
+//!
+//! Records keep the [`code_tokens`] of their `metadata.content` as a
+//! space-separated `search_text` field under a SurrealDB full-text index. The
+//! index finds the records holding any query term; SurrealDB's `@@` only
+//! returns records holding every term, so each term gets its own predicate.
+//! The matches are ranked with [`bm25_rank`]: SurrealDB's own BM25 scores a
+//! term found in half the records or more as zero or below. They only pick
+//! the [`MAX_CANDIDATES`] best matches to rank when there are more.
+//!
+//! Record count and total token count per collection are kept in
+//! [`STATS_TABLE`] by [`store`] and [`delete_where`], so searches do not scan
+//! the collection.
+//!
+//! [`VectorDb`]: crate::ports::outbound::repo_manager::VectorDb
+
+use crate::domain::rag::search::{
+    bm25_rank, bm25_rank_with_doc_freq, code_tokens, CorpusStats, BM25_B, BM25_K1,
+};
+use crate::ports::outbound::repo_manager::ScoredResult;
+use serde::Deserialize;
+use std::collections::HashMap;
+use surrealdb::{Connection, Surreal};
+
+/// Field holding the space-separated search tokens of a record.
+pub const SEARCH_FIELD: &str = "search_text";
+
+/// Table holding the [`CollectionStats`] of each collection, keyed by name.
+pub const STATS_TABLE: &str = "lexical_stats";
+
+/// Query terms beyond this many are ignored.
+const MAX_QUERY_TERMS: usize = 32;
+
+/// Matching records beyond this many are not ranked.
+pub const MAX_CANDIDATES: usize = 1000;
+
+/// Running totals a collection's [`CorpusStats`] are derived from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct CollectionStats {
+    pub doc_count: u64,
+    pub total_len: u64,
+}
+
+impl From<CollectionStats> for CorpusStats {
+    fn from(stats: CollectionStats) -> Self {
+        CorpusStats {
+            doc_count: stats.doc_count as usize,
+            avg_doc_len: if stats.doc_count == 0 {
+                0.0
+            } else {
+                stats.total_len as f32 / stats.doc_count as f32
+            },
+        }
+    }
+}
+
+/// A record to rank, with its search tokens.
+type Candidate = ((surrealdb::sql::Thing, serde_json::Value), Vec<String>);
+
+#[derive(Debug, Deserialize)]
+struct TermCount {
+    count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct LexicalHit {
+    id: surrealdb::sql::Thing,
+    metadata: serde_json::Value,
+    #[serde(default)]
+    search_text: String,
+}
+
+/// Check that `collection` can be spliced into SurrealQL as a table name.
+pub fn table_name(collection: &str) -> anyhow::Result<&str> {
+    if collection.is_empty() {
+        return Err(anyhow::anyhow!("Collection name cannot be empty"));
+    }
+    if !collection
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid collection name '{}': only [a-zA-Z0-9_] are allowed",
+            collection
+        ));
+    }
+    Ok(collection)
+}
+
+/// The [`SEARCH_FIELD`] value for a record whose content is `content`.
+pub fn search_text(content: &str) -> String {
+    code_tokens(content).join(" ")
+}
+
+/// Store `record` as `id` in `collection`, with its [`SEARCH_FIELD`] set from
+/// `content`, and update the collection's [`CollectionStats`] to match.
+pub async fn store<C: Connection>(
+    db: &Surreal<C>,
+    collection: &str,
+    id: &str,
+    content: &str,
+    mut record: serde_json::Value,
+) -> anyhow::Result<()> {
+    let table = table_name(collection)?;
+    let search_text = search_text(content);
+    let len = search_text.split_whitespace().count();
+    record[SEARCH_FIELD] = search_text.into();
+    // Stats only exist once a search seeded them from the whole collection;
+    // until then UPDATE leaves them missing.
+    db.query(format!(
+        "BEGIN TRANSACTION; \
+         LET $old = (SELECT {field} FROM type::thing($table, $id))[0]; \
+         LET $added = IF $old IS NONE {{ 1 }} ELSE {{ 0 }}; \
+         LET $old_len = array::len(string::words($old.{field} OR '')); \
+         UPSERT type::thing($table, $id) CONTENT $record RETURN NONE; \
+         UPDATE type::thing($stats, $table) \
+             SET doc_count += $added, total_len += $len - $old_len RETURN NONE; \
+         COMMIT TRANSACTION;",
+        field = SEARCH_FIELD,
+    ))
+    .bind(("table", table.to_string()))
+    .bind(("id", id.to_string()))
+    .bind(("record", record))
+    .bind(("stats", STATS_TABLE))
+    .bind(("len", len))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// Delete the records of `collection` whose top-level `field` equals `value`
+/// and take them out of the collection's [`CollectionStats`].
+pub async fn delete_where<C: Connection>(
+    db: &Surreal<C>,
+    collection: &str,
+    field: &str,
+    value: serde_json::Value,
+) -> anyhow::Result<()> {
+    let table = table_name(collection)?;
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(anyhow::anyhow!("Invalid field name '{}'", field));
+    }
+    db.query(format!(
+        "BEGIN TRANSACTION; \
+         LET $gone = (DELETE {table} WHERE {field} = $value RETURN BEFORE); \
+         LET $gone_len = math::sum((SELECT VALUE array::len(string::words({search} OR '')) FROM $gone)); \
+         UPDATE type::thing($stats, $table) \
+             SET doc_count -= array::len($gone), total_len -= $gone_len RETURN NONE; \
+         COMMIT TRANSACTION;",
+        table = table,
+        field = field,
+        search = SEARCH_FIELD,
+    ))
+    .bind(("table", table.to_string()))
+    .bind(("value", value))
+    .bind(("stats", STATS_TABLE))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// Define the analyzer and the BM25 index of `collection`; a no-op once they
+/// exist.
+pub async fn ensure_index<C: Connection>(db: &Surreal<C>, collection: &str) -> anyhow::Result<()> {
+    let table = table_name(collection)?;
+    db.query(format!(
+        "DEFINE ANALYZER IF NOT EXISTS code_tokens TOKENIZERS blank FILTERS lowercase; \
+         DEFINE INDEX IF NOT EXISTS {table}_search ON TABLE {table} FIELDS {field} \
+         SEARCH ANALYZER code_tokens BM25({k1},{b});",
+        table = table,
+        field = SEARCH_FIELD,
+        k1 = BM25_K1,
+        b = BM25_B,
+    ))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// BM25 search of `collection` for the [`code_tokens`] of `query`, best first.
+pub async fn search<C: Connection>(
+    db: &Surreal<C>,
+    collection: &str,
+    query: &str,
+    limit: usize,
+) -> anyhow::Result<Vec<ScoredResult>> {
+    let table = table_name(collection)?;
+    let mut terms = code_tokens(query);
+    terms.sort();
+    terms.dedup();
+    terms.truncate(MAX_QUERY_TERMS);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    ensure_index(db, table).await?;
+
+    let candidates = candidates(db, table, &terms, MAX_CANDIDATES).await?;
+    let stats = corpus_stats(db, table).await?;
+    let query = terms.join(" ");
+    let ranked = if candidates.len() < MAX_CANDIDATES {
+        bm25_rank(&query, candidates, &stats)
+    } else {
+        // Only some of the matches: count what the candidates cannot tell.
+        let doc_freq = doc_freq(db, table, &terms).await?;
+        bm25_rank_with_doc_freq(&query, candidates, &stats, &doc_freq)
+    };
+    Ok(ranked
+        .into_iter()
+        .take(limit)
+        .map(|((id, metadata), score)| ScoredResult {
+            id: id.to_string(),
+            score,
+            metadata,
+        })
+        .collect())
+}
+
+/// Up to `cap` records of `table` holding any of `terms` with their tokens,
+/// those SurrealDB scores highest first.
+async fn candidates<C: Connection>(
+    db: &Surreal<C>,
+    table: &str,
+    terms: &[String],
+    cap: usize,
+) -> anyhow::Result<Vec<Candidate>> {
+    let matches: Vec<String> = (0..terms.len())
+        .map(|i| format!("{} @{}@ $term{}", SEARCH_FIELD, i, i))
+        .collect();
+    let scores: Vec<String> = (0..terms.len())
+        .map(|i| format!("(search::score({}) OR 0)", i))
+        .collect();
+    let sql = format!(
+        "SELECT id, metadata, {}, {} AS score FROM {} WHERE {} ORDER BY score DESC LIMIT $cap",
+        SEARCH_FIELD,
+        scores.join(" + "),
+        table,
+        matches.join(" OR ")
+    );
+    let mut request = db.query(sql).bind(("cap", cap));
+    for (i, term) in terms.iter().enumerate() {
+        request = request.bind((format!("term{}", i), term.clone()));
+    }
+    let hits: Vec<LexicalHit> = request.await?.take(0)?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| {
+            let tokens = hit
+                .search_text
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            ((hit.id, hit.metadata), tokens)
+        })
+        .collect())
+}
+
+/// Number of records of `table` holding each of `terms`.
+async fn doc_freq<C: Connection>(
+    db: &Surreal<C>,
+    table: &str,
+    terms: &[String],
+) -> anyhow::Result<HashMap<String, usize>> {
+    let sql: String = (0..terms.len())
+        .map(|i| {
+            format!(
+                "SELECT count() FROM {} WHERE {} @0@ $term{} GROUP ALL;",
+                table, SEARCH_FIELD, i
+            )
+        })
+        .collect();
+    let mut request = db.query(sql);
+    for (i, term) in terms.iter().enumerate() {
+        request = request.bind((format!("term{}", i), term.clone()));
+    }
+    let mut response = request.await?;
+    let mut doc_freq = HashMap::new();
+    for (i, term) in terms.iter().enumerate() {
+        let count: Option<TermCount> = response.take(i)?;
+        doc_freq.insert(term.clone(), count.map_or(0, |c| c.count));
+    }
+    Ok(doc_freq)
+}
+
+/// Record count and average token count of `table`, from [`STATS_TABLE`].
+/// The first call for a collection counts it once and stores the result.
+pub async fn corpus_stats<C: Connection>(
+    db: &Surreal<C>,
+    table: &str,
+) -> anyhow::Result<CorpusStats> {
+    let stats: Option<CollectionStats> = db
+        .query("SELECT doc_count, total_len FROM ONLY type::thing($stats, $table)")
+        .bind(("stats", STATS_TABLE))
+        .bind(("table", table.to_string()))
+        .await?
+        .take(0)?;
+    if let Some(stats) = stats {
+        return Ok(stats.into());
+    }
+
+    let stats: Option<CollectionStats> = db
+        .query(format!(
+            "BEGIN TRANSACTION; \
+             LET $counted = (SELECT count() AS doc_count, \
+                 math::sum(array::len(string::words({} OR ''))) AS total_len \
+                 FROM {} GROUP ALL)[0]; \
+             UPSERT ONLY type::thing($stats, $table) CONTENT {{ \
+                 doc_count: $counted.doc_count OR 0, \
+                 total_len: $counted.total_len OR 0 \
+             }} RETURN doc_count, total_len; \
+             COMMIT TRANSACTION;",
+            SEARCH_FIELD, table
+        ))
+        .bind(("stats", STATS_TABLE))
+        .bind(("table", table.to_string()))
+        .await?
+        .take(1)?;
+    Ok(stats.unwrap_or_default().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use surrealdb::engine::local::{Db, Mem};
+
+    async fn db() -> Surreal<Db> {
+        let db = Surreal::new::<Mem>(()).await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        db
+    }
+
+    async fn put(db: &Surreal<Db>, id: &str, content: &str) {
+        let record = json!({ "metadata": { "content": content } });
+        store(db, "code", id, content, record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stats_are_seeded_once_and_kept_current() {
+        let db = db().await;
+        put(&db, "a", "alpha beta").await;
+        put(&db, "b", "gamma").await;
+
+        let stats = corpus_stats(&db, "code").await.unwrap();
+        assert_eq!(stats.doc_count, 2);
+        assert_eq!(stats.avg_doc_len, 1.5);
+
+        // A new record counts; a rewritten one only changes the length.
+        put(&db, "c", "delta epsilon zeta").await;
+        put(&db, "a", "alpha").await;
+        let stats = corpus_stats(&db, "code").await.unwrap();
+        assert_eq!(stats.doc_count, 3);
+        assert_eq!(stats.avg_doc_len, 5.0 / 3.0);
+    }
+
+    #[tokio::test]
+    async fn deleted_records_leave_the_stats() {
+        let db = db().await;
+        for (id, doc, content) in [
+            ("a", "d1", "alpha beta"),
+            ("b", "d1", "gamma"),
+            ("c", "d2", "delta"),
+        ] {
+            let record = json!({ "doc": doc, "metadata": { "content": content } });
+            store(&db, "code", id, content, record).await.unwrap();
+        }
+        corpus_stats(&db, "code").await.unwrap();
+
+        delete_where(&db, "code", "doc", json!("d1")).await.unwrap();
+        let stats = corpus_stats(&db, "code").await.unwrap();
+        assert_eq!(stats.doc_count, 1);
+        assert_eq!(stats.avg_doc_len, 1.0);
+        assert!(delete_where(&db, "code", "doc = 1 OR true", json!(1))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn candidates_are_capped_and_counted_in_full() {
+        let db = db().await;
+        ensure_index(&db, "code").await.unwrap();
+        for i in 0..5 {
+            put(&db, &format!("r{}", i), &format!("common word{}", i)).await;
+        }
+        put(&db, "rare", "common rare").await;
+        let terms = vec!["common".to_string(), "rare".to_string()];
+
+        let capped = candidates(&db, "code", &terms, 2).await.unwrap();
+        assert_eq!(capped.len(), 2);
+        let counts = doc_freq(&db, "code", &terms).await.unwrap();
+        assert_eq!(counts["common"], 6);
+        assert_eq!(counts["rare"], 1);
+
+        let results = search(&db, "code", "rare", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].metadata["content"], "common rare");
+    }
+}
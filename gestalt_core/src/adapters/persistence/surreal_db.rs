@@ -1,6 +1,7 @@
+use super::lexical;
 use crate::ports::outbound::repo_manager::{ScoredResult, VectorDb};
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use surrealdb::engine::local::Db;
 use surrealdb::Surreal;
 
@@ -14,30 +15,6 @@ impl SurrealDbAdapter {
         db.use_ns("neural").use_db("link").await?;
         Ok(Self { db })
     }
-
-    fn sanitize_table_name(collection: &str) -> anyhow::Result<String> {
-        if collection.is_empty() {
-            return Err(anyhow::anyhow!("Collection name cannot be empty"));
-        }
-
-        if !collection
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '_')
-        {
-            return Err(anyhow::anyhow!(
-                "Invalid collection name '{}': only [a-zA-Z0-9_] are allowed",
-                collection
-            ));
-        }
-
-        Ok(collection.to_string())
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct VectorRecord {
-    id: surrealdb::sql::Thing,
-    metadata: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,17 +33,9 @@ impl VectorDb for SurrealDbAdapter {
         vector: Vec<f32>,
         metadata: serde_json::Value,
     ) -> anyhow::Result<()> {
-        let table = Self::sanitize_table_name(collection)?;
-        self.db
-            .query(
-                "UPSERT type::thing($table, $id) CONTENT { embedding: $embedding, metadata: $metadata }",
-            )
-            .bind(("table", table))
-            .bind(("id", id.to_string()))
-            .bind(("embedding", vector))
-            .bind(("metadata", metadata))
-            .await?;
-        Ok(())
+        let content = metadata["content"].as_str().unwrap_or_default().to_string();
+        let record = serde_json::json!({ "embedding": vector, "metadata": metadata });
+        lexical::store(&self.db, collection, id, &content, record).await
     }
 
     async fn search_similar(
@@ -75,7 +44,7 @@ impl VectorDb for SurrealDbAdapter {
         vector: Vec<f32>,
         limit: usize,
     ) -> anyhow::Result<Vec<ScoredResult>> {
-        let table = Self::sanitize_table_name(collection)?;
+        let table = lexical::table_name(collection)?;
         let query = format!(
             "SELECT id, metadata, vector::similarity::cosine(embedding, $vector) AS score \
              FROM {} WHERE embedding IS NOT NONE ORDER BY score DESC LIMIT $limit",
@@ -91,22 +60,6 @@ impl VectorDb for SurrealDbAdapter {
 
         let results: Vec<SearchResult> = response.take(0)?;
 
-        if results.is_empty() {
-            // Simple lexical fallback: return existing records when similarity returns empty.
-            let fallback_query = format!("SELECT id, metadata FROM {} LIMIT $limit", table);
-
-            let mut response = self.db.query(fallback_query).bind(("limit", limit)).await?;
-            let fallback_results: Vec<VectorRecord> = response.take(0)?;
-            return Ok(fallback_results
-                .into_iter()
-                .map(|r| ScoredResult {
-                    id: r.id.to_string(),
-                    score: 0.0,
-                    metadata: r.metadata,
-                })
-                .collect());
-        }
-
         Ok(results
             .into_iter()
             .map(|r| ScoredResult {
@@ -116,4 +69,13 @@ impl VectorDb for SurrealDbAdapter {
             })
             .collect())
     }
+
+    async fn search_lexical(
+        &self,
+        collection: &str,
+        query: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScoredResult>> {
+        lexical::search(&self.db, collection, query, limit).await
+    }
 }
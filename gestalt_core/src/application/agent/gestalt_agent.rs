@@ -1,5 +1,8 @@
 use super::tools::create_gestalt_tools;
 use super::AgentMode;
+use crate::adapters::embeddings;
+use crate::application::config::GestaltConfig;
+use crate::domain::rag::embeddings::{DummyEmbeddingModel, EmbeddingModel};
 use crate::ports::outbound::repo_manager::{RepoManager, VectorDb};
 use serde_json::json;
 use std::sync::Arc;
@@ -22,19 +25,32 @@ pub struct GestaltAgent {
 }
 
 impl GestaltAgent {
+    /// Build the agent; code search embeds queries with the model from
+    /// `config.embedding` (the dummy model when unset), so it must match the
+    /// one `vector_db` was indexed with.
     pub async fn new(
+        config: &GestaltConfig,
         vector_db: Arc<dyn VectorDb>,
         repo_manager: Arc<dyn RepoManager>,
         llm_provider: Arc<dyn LLMProvider>,
-    ) -> Self {
-        let registry =
-            create_gestalt_tools(repo_manager.clone(), vector_db.clone(), llm_provider).await;
-        Self {
+    ) -> anyhow::Result<Self> {
+        let embedding_model: Arc<dyn EmbeddingModel> = match &config.embedding {
+            Some(embedding) => embeddings::from_config(embedding).await?,
+            None => Arc::new(DummyEmbeddingModel::new(384)),
+        };
+        let registry = create_gestalt_tools(
+            repo_manager.clone(),
+            vector_db.clone(),
+            embedding_model,
+            llm_provider,
+        )
+        .await;
+        Ok(Self {
             _vector_db: vector_db,
             _repo_manager: repo_manager,
             _mode: AgentMode::Build,
             registry,
-        }
+        })
     }
 }
 
@@ -58,7 +74,7 @@ impl GraphNode for GestaltNode {
     /// Primary execution loop for the Gestalt reasoning process.
     ///
     /// Reads previous reasoning history and critic feedback from the state,
-    /// then uses the `DecisionEngine` to decide on the next action (tool call or final answer).
+    /// then asks the `DecisionEngine` for the next tool calls or a final answer.
     async fn execute(&mut self, state: &mut ContextState) -> anyhow::Result<NodeResult> {
         let history = state.get_string("reasoning_history").unwrap_or_default();
         let critic_feedback = state.get_string("critic_feedback").unwrap_or_default();
@@ -87,57 +103,71 @@ impl GraphNode for GestaltNode {
             current_task.description
         );
 
-        // Build context with history, feedback, and CURRENT PLAN STEP
-        let mut context = DecisionContext::new(&self.question)
-            .with_metadata("repo_url", self.repo_url.clone())
-            .with_metadata("history", history.clone())
-            .with_metadata("current_step", current_task.description.clone())
-            .with_metadata("total_steps", plan.len().to_string())
-            .with_metadata("step_index", step_index.to_string());
-
+        // Build the request with history, feedback, and CURRENT PLAN STEP
+        let mut instructions = format!(
+            "Repository: {}\nStep {}/{}: {}\n\nHistory:\n{}",
+            self.repo_url,
+            step_index + 1,
+            plan.len(),
+            current_task.description,
+            history
+        );
         if !critic_feedback.is_empty() {
-            context = context.with_metadata("critic_feedback", critic_feedback);
+            instructions.push_str(&format!("\n\nCritic feedback:\n{}", critic_feedback));
         }
+        instructions.push_str(
+            "\n\nCall a tool to carry out this step, or reply in plain text with the final answer.",
+        );
 
-        let decision = self.engine.decide(&context).await?;
+        let request = ChatRequest::new(vec![
+            Message::new(MessageRole::System, instructions),
+            Message::new(MessageRole::User, self.question.clone()),
+        ])
+        .with_tools(self.registry.schemas().await);
+        let reply = self.engine.chat(&request).await?;
 
-        if let Some(tool_name) = decision.action.strip_prefix("call:") {
-            tracing::info!("Agent decided to call tool: {}", tool_name);
-            let args = decision.parameters.unwrap_or(json!({}));
+        if reply.tool_calls.is_empty() {
+            tracing::info!("Agent provided final answer.");
+            println!("🤖 {}", reply.content);
+            state.set_value("final_answer", json!(reply.content));
+            return Ok(NodeResult::Halt);
+        }
 
-            match self.registry.call(tool_name, &EmptyContext, args).await {
+        let mut updated_history = history;
+        for call in reply.tool_calls {
+            tracing::info!("Agent decided to call tool: {}", call.name);
+            match self
+                .registry
+                .call(&call.name, &EmptyContext, call.arguments)
+                .await
+            {
                 Ok(result) => {
-                    let mut updated_history = history;
                     updated_history.push_str(&format!(
                         "\nStep {}: {} -> Tool {}: {}\n",
                         step_index + 1,
                         current_task.description,
-                        tool_name,
+                        call.name,
                         result
                     ));
-                    state.set_value("reasoning_history", json!(updated_history));
-
-                    // Move to next step in the plan
-                    state.set_value("current_step_index", json!(step_index + 1));
-
-                    Ok(NodeResult::Continue(None))
                 }
                 Err(e) => {
+                    state.set_value("reasoning_history", json!(updated_history));
                     // Capture error for the ReflectionNode to handle
-                    Ok(NodeResult::Error(format!(
+                    return Ok(NodeResult::Error(format!(
                         "Step {} failed at tool {}: {}",
                         step_index + 1,
-                        tool_name,
+                        call.name,
                         e
-                    )))
+                    )));
                 }
             }
-        } else {
-            tracing::info!("Agent provided final answer.");
-            println!("🤖 {}", decision.action);
-            state.set_value("final_answer", json!(decision.action));
-            Ok(NodeResult::Halt)
         }
+        state.set_value("reasoning_history", json!(updated_history));
+
+        // Move to next step in the plan
+        state.set_value("current_step_index", json!(step_index + 1));
+
+        Ok(NodeResult::Continue(None))
     }
 }
 
@@ -1,31 +1,31 @@
+use crate::application::hybrid_search::{HybridSearch, Reranker};
 use crate::context::scanner;
-use crate::domain::rag::embeddings::{DummyEmbeddingModel, EmbeddingModel};
+use crate::domain::rag::embeddings::EmbeddingModel;
 use crate::ports::outbound::repo_manager::{RepoManager, VectorDb};
+use crate::ports::outbound::sandbox::{
+    default_sandbox_or_disabled, CommandSandbox, SandboxPolicy, SandboxRequest,
+};
+use crate::ports::outbound::vfs::{RealFileSystem, VirtualFileSystem};
 use serde_json::{json, Value};
 use std::path::Path;
 use std::sync::Arc;
 use synapse_agentic::prelude::*;
-use tokio::time;
 
+/// Tools of the Gestalt agent. `embedding_model` must be the model the
+/// repositories in `vector_db` were indexed with.
 pub async fn create_gestalt_tools(
     repo_manager: Arc<dyn RepoManager>,
     vector_db: Arc<dyn VectorDb>,
+    embedding_model: Arc<dyn EmbeddingModel>,
     llm_provider: Arc<dyn LLMProvider>,
 ) -> ToolRegistry {
     let registry = ToolRegistry::new();
     registry.register_tool(ScanWorkspaceTool).await;
 
-    // Use dummy embedding model for now as we don't have the model files easily accessible in all environments
-    // In a real scenario, we'd initialize BertEmbeddingModel here.
-    let embedding_model = Arc::new(DummyEmbeddingModel::new(384));
-
     registry
-        .register_tool(SearchCodeTool {
-            vector_db,
-            embedding_model,
-        })
+        .register_tool(SearchCodeTool::new(vector_db, embedding_model))
         .await;
-    registry.register_tool(ExecuteShellTool).await;
+    registry.register_tool(ExecuteShellTool::default()).await;
     registry.register_tool(ReadFileTool).await;
     registry.register_tool(WriteFileTool).await;
     registry.register_tool(GitStatusTool).await;
@@ -78,17 +78,21 @@ impl Tool for ScanWorkspaceTool {
 }
 
 pub struct SearchCodeTool {
-    vector_db: Arc<dyn VectorDb>,
-    embedding_model: Arc<dyn EmbeddingModel>,
+    search: HybridSearch,
 }
 
 impl SearchCodeTool {
     pub fn new(vector_db: Arc<dyn VectorDb>, embedding_model: Arc<dyn EmbeddingModel>) -> Self {
         Self {
-            vector_db,
-            embedding_model,
+            search: HybridSearch::new(vector_db, embedding_model),
         }
     }
+
+    /// Rerank fused results with `reranker` before returning them.
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.search = self.search.with_reranker(reranker);
+        self
+    }
 }
 
 #[async_trait]
@@ -97,7 +101,7 @@ impl Tool for SearchCodeTool {
         "search_code"
     }
     fn description(&self) -> &str {
-        "Search for similar code fragments in the vector database."
+        "Search indexed code by keywords, identifiers and meaning. Returns path, line range, scores and a snippet per match."
     }
     fn parameters(&self) -> Value {
         json!({
@@ -117,20 +121,27 @@ impl Tool for SearchCodeTool {
             .ok_or_else(|| anyhow::anyhow!("Missing 'query' parameter"))?;
         let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
 
-        let query_embedding = self.embedding_model.embed(query).await?;
-
         // Search in "chunks" collection which is used by IndexService
-        let similar = self
-            .vector_db
-            .search_similar("chunks", query_embedding, limit)
-            .await?;
-
-        Ok(json!({ "results": similar }))
+        let hits = self.search.search("chunks", query, limit).await?;
+        let results: Vec<Value> = hits
+            .into_iter()
+            .map(|hit| {
+                json!({
+                    "path": hit.path,
+                    "start_line": hit.start_line,
+                    "end_line": hit.end_line,
+                    "scores": hit.scores,
+                    "snippet": hit.snippet,
+                })
+            })
+            .collect();
+
+        Ok(json!({ "results": results }))
     }
 }
 
 fn validate_shell_command(command: &str) -> anyhow::Result<()> {
-    let forbidden = [";", "&&", "||", "|", "`", "$(&", "$(", "${", ">", "<", "\n", "\r", "#", "//", "/*", "*/"];
+    let forbidden = [";", "&&", "||", "|", "`", "$", ">", "<", "\n", "\r", "#", "//", "/*", "*/"];
     for token in forbidden {
         if command.contains(token) {
             anyhow::bail!("command contains forbidden shell metacharacter: {{}} ({})", token);
@@ -143,7 +154,28 @@ fn validate_shell_command(command: &str) -> anyhow::Result<()> {
 }
 
 
-pub struct ExecuteShellTool;
+/// Runs shell commands in a [`CommandSandbox`] over a materialized copy of
+/// the current directory; changes are written back through `vfs`.
+pub struct ExecuteShellTool {
+    sandbox: Arc<dyn CommandSandbox>,
+    vfs: Arc<dyn VirtualFileSystem>,
+}
+
+impl ExecuteShellTool {
+    pub fn new(sandbox: Arc<dyn CommandSandbox>, vfs: Arc<dyn VirtualFileSystem>) -> Self {
+        Self { sandbox, vfs }
+    }
+}
+
+impl Default for ExecuteShellTool {
+    /// Default sandbox, writing changes straight to disk.
+    fn default() -> Self {
+        Self::new(
+            default_sandbox_or_disabled(SandboxPolicy::from_env()),
+            Arc::new(RealFileSystem),
+        )
+    }
+}
 
 #[async_trait]
 impl Tool for ExecuteShellTool {
@@ -151,7 +183,7 @@ impl Tool for ExecuteShellTool {
         "execute_shell"
     }
     fn description(&self) -> &str {
-        "Execute a shell command on the host operating system."
+        "Execute a shell command in a sandbox with a writable copy of the workspace."
     }
     fn parameters(&self) -> Value {
         json!({
@@ -171,39 +203,14 @@ impl Tool for ExecuteShellTool {
 
         validate_shell_command(command)?;
 
-        #[cfg(target_os = "windows")]
-        let mut cmd = tokio::process::Command::new("powershell");
-        #[cfg(target_os = "windows")]
-        cmd.arg("-Command").arg(command);
-
-        #[cfg(not(target_os = "windows"))]
-        let mut cmd = tokio::process::Command::new("sh");
-        #[cfg(not(target_os = "windows"))]
-        cmd.arg("-c").arg(command);
-
-        cmd.kill_on_drop(true);
-        let output_res = time::timeout(
-            std::time::Duration::from_secs(30),
-            cmd.output(),
-        )
-        .await
-        .map_err(|_| anyhow::anyhow!("command timed out after 30 seconds"))
-        .and_then(|r| r.map_err(anyhow::Error::from));
-
-        match output_res {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
-
-                Ok(json!({
-                    "exit_code": exit_code,
-                    "stdout": stdout,
-                    "stderr": stderr
-                }))
-            }
-            Err(e) => Err(anyhow::anyhow!("Failed to execute '{}': {}", command, e)),
-        }
+        let request =
+            SandboxRequest::new(command).with_workspace(self.vfs.clone(), ".", "execute_shell");
+        let output = self
+            .sandbox
+            .run(request)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to execute '{}': {}", command, e))?;
+        Ok(serde_json::to_value(output)?)
     }
 }
 
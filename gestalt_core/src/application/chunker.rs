@@ -0,0 +1,490 @@
+//! Syntax-aware chunking: splits source files on item boundaries (functions,
+//! impls, classes, headings) using tree-sitter grammars.
+
+use tree_sitter::{Language, Node, Parser};
+
+/// Languages the syntax chunker understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLanguage {
+    Rust,
+    Python,
+    TypeScript,
+    Tsx,
+    Go,
+    Markdown,
+}
+
+impl SourceLanguage {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            "go" => Some(Self::Go),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+            Self::Markdown => tree_sitter_md::LANGUAGE.into(),
+        }
+    }
+
+    /// Joins a parent scope and a nested item name.
+    fn scope_separator(self) -> &'static str {
+        match self {
+            Self::Rust => "::",
+            Self::Markdown => " > ",
+            _ => ".",
+        }
+    }
+
+    /// Comments and attributes that belong to the item following them.
+    fn is_decoration(self, kind: &str) -> bool {
+        match self {
+            Self::Rust => matches!(kind, "line_comment" | "block_comment" | "attribute_item"),
+            Self::Python | Self::TypeScript | Self::Tsx | Self::Go => kind == "comment",
+            Self::Markdown => false,
+        }
+    }
+
+    fn item<'t>(self, node: Node<'t>, source: &str) -> Option<Item<'t>> {
+        let name = |field: &str| {
+            node.child_by_field_name(field)
+                .map(|n| text(n, source).trim().to_string())
+        };
+        let item = |kind, name| Some(Item::leaf(kind, name));
+        let container = |kind, name| {
+            Some(Item {
+                kind,
+                name,
+                scope: None,
+                body: node.child_by_field_name("body"),
+            })
+        };
+
+        match (self, node.kind()) {
+            (Self::Rust, "function_item" | "function_signature_item") => {
+                item("function", name("name"))
+            }
+            (Self::Rust, "struct_item") => item("struct", name("name")),
+            (Self::Rust, "enum_item") => item("enum", name("name")),
+            (Self::Rust, "union_item") => item("union", name("name")),
+            (Self::Rust, "type_item") => item("type", name("name")),
+            (Self::Rust, "const_item") => item("const", name("name")),
+            (Self::Rust, "static_item") => item("static", name("name")),
+            (Self::Rust, "macro_definition") => item("macro", name("name")),
+            (Self::Rust, "trait_item") => container("trait", name("name")),
+            (Self::Rust, "mod_item") => container("module", name("name")),
+            (Self::Rust, "impl_item") => {
+                let target = name("type");
+                let name = match name("trait") {
+                    Some(tr) => target.map(|ty| format!("{} for {}", tr, ty)),
+                    None => target,
+                };
+                container("impl", name)
+            }
+
+            (Self::Python, "function_definition") => item("function", name("name")),
+            (Self::Python, "class_definition") => container("class", name("name")),
+            (Self::Python, "decorated_definition") => node
+                .child_by_field_name("definition")
+                .and_then(|def| self.item(def, source)),
+
+            (Self::TypeScript | Self::Tsx, "function_declaration")
+            | (Self::TypeScript | Self::Tsx, "generator_function_declaration") => {
+                item("function", name("name"))
+            }
+            (Self::TypeScript | Self::Tsx, "class_declaration")
+            | (Self::TypeScript | Self::Tsx, "abstract_class_declaration") => {
+                container("class", name("name"))
+            }
+            (Self::TypeScript | Self::Tsx, "interface_declaration") => {
+                item("interface", name("name"))
+            }
+            (Self::TypeScript | Self::Tsx, "type_alias_declaration") => item("type", name("name")),
+            (Self::TypeScript | Self::Tsx, "enum_declaration") => item("enum", name("name")),
+            (Self::TypeScript | Self::Tsx, "internal_module" | "module") => {
+                container("namespace", name("name"))
+            }
+            (Self::TypeScript | Self::Tsx, "method_definition") => item("method", name("name")),
+            (Self::TypeScript | Self::Tsx, "export_statement") => node
+                .child_by_field_name("declaration")
+                .and_then(|decl| self.item(decl, source)),
+            (Self::TypeScript | Self::Tsx, "lexical_declaration" | "variable_declaration") => {
+                // `const handler = () => ...` is a function in all but syntax.
+                let mut cursor = node.walk();
+                let declarator = node
+                    .named_children(&mut cursor)
+                    .find(|c| c.kind() == "variable_declarator")?;
+                let value = declarator.child_by_field_name("value")?;
+                matches!(
+                    value.kind(),
+                    "arrow_function" | "function_expression" | "function"
+                )
+                .then(|| {
+                    Item::leaf(
+                        "function",
+                        declarator
+                            .child_by_field_name("name")
+                            .map(|n| text(n, source).to_string()),
+                    )
+                })
+            }
+
+            (Self::Go, "function_declaration") => item("function", name("name")),
+            (Self::Go, "method_declaration") => Some(Item {
+                scope: node
+                    .child_by_field_name("receiver")
+                    .and_then(|r| first_descendant(r, "type_identifier"))
+                    .map(|n| text(n, source).to_string()),
+                ..Item::leaf("method", name("name"))
+            }),
+            (Self::Go, "type_declaration") => item(
+                "type",
+                first_descendant(node, "type_spec")
+                    .or_else(|| first_descendant(node, "type_alias"))
+                    .and_then(|spec| spec.child_by_field_name("name"))
+                    .map(|n| text(n, source).to_string()),
+            ),
+
+            (Self::Markdown, "section") => {
+                let mut cursor = node.walk();
+                let heading = node
+                    .named_children(&mut cursor)
+                    .find(|c| matches!(c.kind(), "atx_heading" | "setext_heading"))?;
+                Some(Item {
+                    kind: "heading",
+                    name: heading
+                        .child_by_field_name("heading_content")
+                        .map(|n| text(n, source).trim().to_string()),
+                    scope: None,
+                    body: Some(node),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An item recognised in the syntax tree.
+struct Item<'t> {
+    kind: &'static str,
+    name: Option<String>,
+    /// Scope that overrides the enclosing one, e.g. a Go method's receiver type.
+    scope: Option<String>,
+    /// Node whose children are chunked separately when the item is too large.
+    body: Option<Node<'t>>,
+}
+
+impl Item<'_> {
+    fn leaf(kind: &'static str, name: Option<String>) -> Self {
+        Self {
+            kind,
+            name,
+            scope: None,
+            body: None,
+        }
+    }
+}
+
+/// A region of a source file: one item, or the code between items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxSpan {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    /// First line, 1-based.
+    pub start_line: usize,
+    /// Last line, inclusive.
+    pub end_line: usize,
+    pub symbol: Option<String>,
+    pub kind: Option<String>,
+    pub parent: Option<String>,
+}
+
+/// Split `source` into spans on item boundaries.
+///
+/// Containers (impls, traits, modules, classes, heading sections) longer than
+/// `max_len` bytes are split into their members; other items are returned
+/// whole even when they exceed `max_len`, for the caller to split further.
+/// Returns `None` if the source cannot be parsed.
+pub fn syntax_spans(
+    language: SourceLanguage,
+    source: &str,
+    max_len: usize,
+) -> Option<Vec<SyntaxSpan>> {
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(source, None)?;
+
+    let mut walker = Walker {
+        language,
+        source,
+        max_len,
+        line_starts: std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+        spans: Vec::new(),
+    };
+    walker.walk(tree.root_node(), None, None);
+    Some(walker.spans)
+}
+
+/// Symbol metadata attached to a span.
+#[derive(Clone)]
+struct Label {
+    symbol: Option<String>,
+    kind: Option<&'static str>,
+    parent: Option<String>,
+}
+
+struct Walker<'s> {
+    language: SourceLanguage,
+    source: &'s str,
+    max_len: usize,
+    line_starts: Vec<usize>,
+    spans: Vec<SyntaxSpan>,
+}
+
+impl Walker<'_> {
+    /// Chunk the children of `container`. Code before the first item is
+    /// labelled `intro` if given, e.g. a heading section's own text.
+    fn walk(&mut self, container: Node, parent: Option<&str>, mut intro: Option<Label>) {
+        // Decorations waiting for the next item, and non-item code between items.
+        let mut lead: Option<(usize, usize)> = None;
+        let mut group: Option<(usize, usize)> = None;
+        let plain = Label {
+            symbol: None,
+            kind: None,
+            parent: parent.map(str::to_string),
+        };
+
+        let mut cursor = container.walk();
+        let children: Vec<Node> = container.named_children(&mut cursor).collect();
+        for node in children {
+            if self.language.is_decoration(node.kind()) {
+                let start = lead.map_or(node.start_byte(), |(start, _)| start);
+                lead = Some((start, node.end_byte()));
+                continue;
+            }
+
+            let Some(item) = self.language.item(node, self.source) else {
+                let start = lead.take().map_or(node.start_byte(), |(start, _)| start);
+                group = match group {
+                    Some((group_start, _)) if node.end_byte() - group_start <= self.max_len => {
+                        Some((group_start, node.end_byte()))
+                    }
+                    Some((group_start, group_end)) => {
+                        let label = intro.take().unwrap_or_else(|| plain.clone());
+                        self.push(group_start, group_end, label);
+                        Some((start, node.end_byte()))
+                    }
+                    None => Some((start, node.end_byte())),
+                };
+                continue;
+            };
+
+            if let Some((start, end)) = group.take() {
+                let label = intro.take().unwrap_or_else(|| plain.clone());
+                self.push(start, end, label);
+            }
+            intro = None;
+            let start = lead.take().map_or(node.start_byte(), |(start, _)| start);
+            let label = Label {
+                symbol: item.name.clone(),
+                kind: Some(item.kind),
+                parent: item.scope.or_else(|| parent.map(str::to_string)),
+            };
+            match item.body {
+                Some(body) if node.end_byte() - start > self.max_len => {
+                    let nested = match (&label.parent, &item.name) {
+                        (Some(scope), Some(name)) => Some(format!(
+                            "{}{}{}",
+                            scope,
+                            self.language.scope_separator(),
+                            name
+                        )),
+                        (None, Some(name)) => Some(name.clone()),
+                        (scope, None) => scope.clone(),
+                    };
+                    if body.id() == node.id() {
+                        // The item's own text comes before its first member.
+                        self.walk(body, nested.as_deref(), Some(label));
+                    } else {
+                        // Keep the signature and its docs, then chunk the members.
+                        self.push(start, body.start_byte(), label);
+                        self.walk(body, nested.as_deref(), None);
+                    }
+                }
+                _ => self.push(start, node.end_byte(), label),
+            }
+        }
+
+        if let Some((lead_start, lead_end)) = lead {
+            group = Some(group.map_or((lead_start, lead_end), |(start, _)| (start, lead_end)));
+        }
+        if let Some((start, end)) = group {
+            self.push(start, end, intro.unwrap_or(plain));
+        }
+    }
+
+    fn push(&mut self, start: usize, end: usize, label: Label) {
+        // Start at the beginning of the line so indentation is kept.
+        let line_start = self.line_starts[self.line_of(start) - 1];
+        let start = if self.source[line_start..start].trim().is_empty() {
+            line_start
+        } else {
+            start
+        };
+        let text = self.source[start..end].trim_end();
+        if text.trim().is_empty() {
+            return;
+        }
+        let end = start + text.len();
+        self.spans.push(SyntaxSpan {
+            start_byte: start,
+            end_byte: end,
+            start_line: self.line_of(start),
+            end_line: self.line_of(end - 1),
+            symbol: label.symbol,
+            kind: label.kind.map(str::to_string),
+            parent: label.parent,
+        });
+    }
+
+    /// 1-based line containing byte `offset`.
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+}
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    &source[node.byte_range()]
+}
+
+fn first_descendant<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    let mut cursor = node.walk();
+    let children: Vec<Node<'t>> = node.named_children(&mut cursor).collect();
+    children.into_iter().find_map(|child| {
+        if child.kind() == kind {
+            Some(child)
+        } else {
+            first_descendant(child, kind)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(language: SourceLanguage, source: &str, max_len: usize) -> Vec<String> {
+        syntax_spans(language, source, max_len)
+            .unwrap()
+            .into_iter()
+            .map(|s| {
+                format!(
+                    "{}-{} {} {} {}",
+                    s.start_line,
+                    s.end_line,
+                    s.kind.as_deref().unwrap_or("-"),
+                    s.symbol.as_deref().unwrap_or("-"),
+                    s.parent.as_deref().unwrap_or("-")
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_items_and_oversized_impl() {
+        let source = r#"use std::fmt;
+
+/// A point.
+#[derive(Debug)]
+pub struct Point {
+    x: i32,
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.x)
+    }
+
+    // Doubles x.
+    fn double(&self) -> i32 {
+        self.x * 2
+    }
+}
+"#;
+        assert_eq!(
+            summary(SourceLanguage::Rust, source, 1000),
+            vec![
+                "1-1 - - -",
+                "3-7 struct Point -",
+                "9-18 impl fmt::Display for Point -"
+            ]
+        );
+        assert_eq!(
+            summary(SourceLanguage::Rust, source, 120),
+            vec![
+                "1-1 - - -",
+                "3-7 struct Point -",
+                "9-9 impl fmt::Display for Point -",
+                "10-12 function fmt fmt::Display for Point",
+                "14-17 function double fmt::Display for Point",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_typescript_go_and_markdown() {
+        let python = "import os\n\nclass Repo:\n    @property\n    def name(self):\n        return 'x'\n\n    def path(self):\n        return os.getcwd()\n";
+        assert_eq!(
+            summary(SourceLanguage::Python, python, 40),
+            vec![
+                "1-1 - - -",
+                "3-3 class Repo -",
+                "4-6 function name Repo",
+                "8-9 function path Repo"
+            ]
+        );
+
+        let typescript = "// Entry point.\nexport function main(): void {}\nexport const handler = async () => 1;\ninterface Options { verbose: boolean }\n";
+        assert_eq!(
+            summary(SourceLanguage::TypeScript, typescript, 1000),
+            vec![
+                "1-2 function main -",
+                "3-3 function handler -",
+                "4-4 interface Options -"
+            ]
+        );
+
+        let go = "package server\n\n// Run starts the server.\nfunc (s *Server) Run() error {\n\treturn nil\n}\n\ntype Server struct{}\n";
+        assert_eq!(
+            summary(SourceLanguage::Go, go, 1000),
+            vec!["1-1 - - -", "3-6 method Run Server", "8-8 type Server -"]
+        );
+
+        let markdown = "# Guide\n\nIntro.\n\n## Install\n\nRun it.\n\n## Usage\n\nUse it.\n";
+        assert_eq!(
+            summary(SourceLanguage::Markdown, markdown, 1000),
+            vec!["1-11 heading Guide -"]
+        );
+        assert_eq!(
+            summary(SourceLanguage::Markdown, markdown, 20),
+            vec![
+                "1-3 heading Guide -",
+                "5-7 heading Install Guide",
+                "9-11 heading Usage Guide"
+            ]
+        );
+    }
+}
@@ -82,6 +82,24 @@ pub struct LoggingConfig {
     pub file: Option<String>,
 }
 
+/// Embedding model configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingConfig {
+    /// "local", "openai", "ollama" or "dummy"
+    pub provider: String,
+    /// Model name sent to HTTP backends; for local models, the ID recorded
+    /// with each vector (defaults to the directory name)
+    pub model: Option<String>,
+    /// Local model directory with config.json, tokenizer.json and model.safetensors
+    pub path: Option<String>,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    /// Vector length; HTTP backends probe the server when unset
+    pub dimension: Option<usize>,
+    /// Texts embedded per request or forward pass
+    pub batch_size: Option<usize>,
+}
+
 /// Gestalt main configuration
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct GestaltConfig {
@@ -89,6 +107,7 @@ pub struct GestaltConfig {
     pub database: Option<DatabaseConfig>,
     pub mcp: Option<McpConfig>,
     pub logging: Option<LoggingConfig>,
+    pub embedding: Option<EmbeddingConfig>,
 }
 
 impl GestaltConfig {
@@ -174,6 +193,23 @@ impl GestaltConfig {
             });
         }
 
+        // Embedding configuration from env
+        if let Ok(provider) = env::var("GESTALT_EMBEDDING__PROVIDER") {
+            config.embedding = Some(EmbeddingConfig {
+                provider,
+                model: env::var("GESTALT_EMBEDDING__MODEL").ok(),
+                path: env::var("GESTALT_EMBEDDING__PATH").ok(),
+                base_url: env::var("GESTALT_EMBEDDING__BASE_URL").ok(),
+                api_key: env::var("GESTALT_EMBEDDING__API_KEY").ok(),
+                dimension: env::var("GESTALT_EMBEDDING__DIMENSION")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+                batch_size: env::var("GESTALT_EMBEDDING__BATCH_SIZE")
+                    .ok()
+                    .and_then(|v| v.parse().ok()),
+            });
+        }
+
         Ok(config)
     }
 
@@ -201,6 +237,15 @@ impl GestaltConfig {
                 format: Some("json".to_string()),
                 file: None,
             })),
+            embedding: self.embedding.or(Some(EmbeddingConfig {
+                provider: "dummy".to_string(),
+                model: None,
+                path: None,
+                base_url: None,
+                api_key: None,
+                dimension: Some(384),
+                batch_size: None,
+            })),
         }
     }
 }
@@ -0,0 +1,395 @@
+//! Hybrid code search: BM25 and vector retrieval fused with reciprocal rank
+//! fusion, optionally reordered by a reranker.
+
+use crate::domain::rag::embeddings::{EmbeddingModel, EmbeddingSpace};
+use crate::domain::rag::search::{reciprocal_rank_fusion, ScoreBreakdown};
+use crate::ports::outbound::repo_manager::{ScoredResult, VectorDb};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use synapse_agentic::prelude::LLMProvider;
+use tracing::warn;
+
+/// Lines of chunk content returned as a hit's snippet.
+const SNIPPET_LINES: usize = 12;
+/// Characters of each passage shown to an LLM reranker.
+const RERANK_PASSAGE_CHARS: usize = 1500;
+
+/// A code chunk matching a search query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeSearchHit {
+    pub id: String,
+    pub path: String,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+    pub scores: ScoreBreakdown,
+    pub snippet: String,
+}
+
+/// Scores candidate passages against a query after fusion.
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// Relevance of each passage to `query`, higher is better, in passage order.
+    async fn rerank(&self, query: &str, passages: &[String]) -> anyhow::Result<Vec<f32>>;
+}
+
+/// Reranker that asks an LLM to grade each passage from 0 to 10.
+pub struct LlmReranker {
+    provider: Arc<dyn LLMProvider>,
+}
+
+impl LlmReranker {
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait]
+impl Reranker for LlmReranker {
+    async fn rerank(&self, query: &str, passages: &[String]) -> anyhow::Result<Vec<f32>> {
+        let mut prompt = format!(
+            "Rate how relevant each code passage is to the query on a scale from 0 to 10.\n\
+             Reply with only a JSON array of {} numbers, one per passage, in order.\n\n\
+             Query: {}\n",
+            passages.len(),
+            query
+        );
+        for (i, passage) in passages.iter().enumerate() {
+            let passage: String = passage.chars().take(RERANK_PASSAGE_CHARS).collect();
+            prompt.push_str(&format!("\n[{}]\n```\n{}\n```\n", i, passage));
+        }
+
+        let reply = self.provider.generate(&prompt).await?;
+        let json = reply
+            .find('[')
+            .zip(reply.rfind(']'))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| &reply[start..=end])
+            .ok_or_else(|| anyhow::anyhow!("Reranker reply has no JSON array: {}", reply))?;
+        let scores: Vec<f32> = serde_json::from_str(json)?;
+        if scores.len() != passages.len() {
+            anyhow::bail!(
+                "Reranker returned {} scores for {} passages",
+                scores.len(),
+                passages.len()
+            );
+        }
+        Ok(scores)
+    }
+}
+
+/// Searches one collection lexically and by embedding and fuses the results.
+pub struct HybridSearch {
+    vector_db: Arc<dyn VectorDb>,
+    embedding_model: Arc<dyn EmbeddingModel>,
+    reranker: Option<Arc<dyn Reranker>>,
+}
+
+impl HybridSearch {
+    pub fn new(vector_db: Arc<dyn VectorDb>, embedding_model: Arc<dyn EmbeddingModel>) -> Self {
+        Self {
+            vector_db,
+            embedding_model,
+            reranker: None,
+        }
+    }
+
+    /// Reorder the fused candidates with `reranker` before truncating.
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    pub async fn search(
+        &self,
+        collection: &str,
+        query: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<CodeSearchHit>> {
+        let pool = (limit * 4).max(20);
+        let (vector, mismatch) = self.vector_candidates(collection, query, pool).await?;
+        let lexical = self
+            .vector_db
+            .search_lexical(collection, query, pool)
+            .await?;
+
+        let mut candidates: HashMap<String, (Value, ScoreBreakdown)> = HashMap::new();
+        for r in &lexical {
+            let entry = candidates
+                .entry(r.id.clone())
+                .or_insert_with(|| (r.metadata.clone(), ScoreBreakdown::default()));
+            entry.1.bm25 = Some(r.score);
+        }
+        for r in &vector {
+            let entry = candidates
+                .entry(r.id.clone())
+                .or_insert_with(|| (r.metadata.clone(), ScoreBreakdown::default()));
+            entry.1.vector = Some(r.score);
+        }
+
+        let rankings = [
+            lexical.iter().map(|r| r.id.clone()).collect(),
+            vector.iter().map(|r| r.id.clone()).collect(),
+        ];
+        let mut fused: Vec<(Value, ScoreBreakdown, String)> = reciprocal_rank_fusion(&rankings)
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let (metadata, mut scores) = candidates.remove(&id)?;
+                scores.fused = score;
+                Some((metadata, scores, id))
+            })
+            .collect();
+        if fused.is_empty() {
+            if let Some(message) = mismatch {
+                anyhow::bail!("{}", message);
+            }
+        }
+
+        if let Some(reranker) = &self.reranker {
+            fused.truncate(limit * 2);
+            let passages: Vec<String> = fused
+                .iter()
+                .map(|(metadata, _, _)| content_of(metadata).to_string())
+                .collect();
+            match reranker.rerank(query, &passages).await {
+                Ok(scores) => {
+                    for (hit, score) in fused.iter_mut().zip(scores) {
+                        hit.1.rerank = Some(score);
+                    }
+                    fused.sort_by(|a, b| {
+                        b.1.rerank
+                            .unwrap_or_default()
+                            .total_cmp(&a.1.rerank.unwrap_or_default())
+                            .then_with(|| b.1.fused.total_cmp(&a.1.fused))
+                    });
+                }
+                Err(e) => warn!("Reranking failed, keeping fused order: {}", e),
+            }
+        }
+
+        Ok(fused
+            .into_iter()
+            .take(limit)
+            .map(|(metadata, scores, id)| CodeSearchHit {
+                path: metadata["path"].as_str().unwrap_or_default().to_string(),
+                start_line: line_field(&metadata, "start_line"),
+                end_line: line_field(&metadata, "end_line"),
+                snippet: content_of(&metadata)
+                    .lines()
+                    .take(SNIPPET_LINES)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                scores,
+                id,
+            })
+            .collect())
+    }
+
+    /// Nearest chunks embedded by the configured model, plus an explanation
+    /// when matching chunks were embedded by another model.
+    async fn vector_candidates(
+        &self,
+        collection: &str,
+        query: &str,
+        pool: usize,
+    ) -> anyhow::Result<(Vec<ScoredResult>, Option<String>)> {
+        let query_embedding = match self.embedding_model.embed(query).await {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                warn!(
+                    "Embedding the query failed, searching lexically only: {}",
+                    e
+                );
+                return Ok((Vec::new(), None));
+            }
+        };
+        let current = EmbeddingSpace::of(self.embedding_model.as_ref());
+        let similar = self
+            .vector_db
+            .search_similar(collection, query_embedding, pool)
+            .await?;
+
+        // Scores against vectors from another model are meaningless.
+        let (results, mismatched): (Vec<_>, Vec<_>) =
+            similar
+                .into_iter()
+                .partition(|r| match stored_space(&r.metadata) {
+                    Some(stored) => stored == current,
+                    None => true,
+                });
+        let mismatch = mismatched
+            .first()
+            .and_then(|r| stored_space(&r.metadata))
+            .and_then(|stored| current.mismatch(&stored));
+        Ok((results, mismatch))
+    }
+}
+
+/// Embedding space recorded with a chunk by the indexer, if any.
+fn stored_space(metadata: &Value) -> Option<EmbeddingSpace> {
+    Some(EmbeddingSpace {
+        model_id: metadata.get("embedding_model")?.as_str()?.to_string(),
+        dimension: metadata.get("embedding_dim")?.as_u64()? as usize,
+    })
+}
+
+fn content_of(metadata: &Value) -> &str {
+    metadata["content"].as_str().unwrap_or_default()
+}
+
+fn line_field(metadata: &Value, field: &str) -> Option<usize> {
+    metadata.get(field)?.as_u64().map(|n| n as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::rag::embeddings::DummyEmbeddingModel;
+    use crate::domain::rag::search::{bm25_rank, code_tokens, CorpusStats};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// In-memory store whose vector search ranks by insertion order.
+    #[derive(Default)]
+    struct MemoryDb {
+        rows: Mutex<Vec<(String, Value)>>,
+    }
+
+    #[async_trait]
+    impl VectorDb for MemoryDb {
+        async fn store_embedding(
+            &self,
+            _collection: &str,
+            id: &str,
+            _vector: Vec<f32>,
+            metadata: Value,
+        ) -> anyhow::Result<()> {
+            self.rows.lock().unwrap().push((id.to_string(), metadata));
+            Ok(())
+        }
+
+        async fn search_similar(
+            &self,
+            _collection: &str,
+            _vector: Vec<f32>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<ScoredResult>> {
+            let rows = self.rows.lock().unwrap();
+            Ok(rows
+                .iter()
+                .take(limit)
+                .enumerate()
+                .map(|(i, (id, metadata))| ScoredResult {
+                    id: id.clone(),
+                    score: 1.0 - i as f32 / 10.0,
+                    metadata: metadata.clone(),
+                })
+                .collect())
+        }
+
+        async fn search_lexical(
+            &self,
+            _collection: &str,
+            query: &str,
+            limit: usize,
+        ) -> anyhow::Result<Vec<ScoredResult>> {
+            let rows = self.rows.lock().unwrap();
+            let candidates: Vec<_> = rows
+                .iter()
+                .map(|row| (row, code_tokens(content_of(&row.1))))
+                .collect();
+            let stats = CorpusStats {
+                doc_count: candidates.len(),
+                avg_doc_len: 8.0,
+            };
+            Ok(bm25_rank(query, candidates, &stats)
+                .into_iter()
+                .take(limit)
+                .map(|((id, metadata), score)| ScoredResult {
+                    id: id.clone(),
+                    score,
+                    metadata: metadata.clone(),
+                })
+                .collect())
+        }
+    }
+
+    struct ReverseReranker;
+
+    #[async_trait]
+    impl Reranker for ReverseReranker {
+        async fn rerank(&self, _query: &str, passages: &[String]) -> anyhow::Result<Vec<f32>> {
+            Ok((0..passages.len()).map(|i| i as f32).collect())
+        }
+    }
+
+    async fn seeded_db(model: &DummyEmbeddingModel) -> Arc<MemoryDb> {
+        let db = Arc::new(MemoryDb::default());
+        let chunks = [
+            ("src/http.rs", "struct HttpClient;\nimpl HttpClient {}"),
+            ("src/misc.rs", "fn unrelated() {}"),
+            (
+                "src/auth.rs",
+                "fn verify_token(token: &str) -> bool {\n    !token.is_empty()\n}",
+            ),
+        ];
+        for (i, (path, content)) in chunks.iter().enumerate() {
+            db.store_embedding(
+                "chunks",
+                &format!("chunks:{}", i),
+                vec![],
+                json!({
+                    "path": path,
+                    "content": content,
+                    "start_line": 10,
+                    "end_line": 12,
+                    "embedding_model": model.model_id(),
+                    "embedding_dim": model.dimension()
+                }),
+            )
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_fuses_and_reranks() {
+        let model = Arc::new(DummyEmbeddingModel::new(4));
+        let db = seeded_db(&model).await;
+
+        let search = HybridSearch::new(db.clone(), model.clone());
+        let hits = search.search("chunks", "verifyToken", 2).await.unwrap();
+        assert_eq!(hits[0].path, "src/auth.rs");
+        assert_eq!((hits[0].start_line, hits[0].end_line), (Some(10), Some(12)));
+        assert!(hits[0].scores.bm25.is_some() && hits[0].scores.vector.is_some());
+        assert!(hits[0].snippet.starts_with("fn verify_token"));
+        assert_eq!(hits[1].path, "src/http.rs");
+        assert_eq!(hits[1].scores.bm25, None);
+
+        let reranked = HybridSearch::new(db, model)
+            .with_reranker(Arc::new(ReverseReranker))
+            .search("chunks", "verifyToken", 3)
+            .await
+            .unwrap();
+        assert_eq!(reranked[0].path, "src/misc.rs");
+        assert_eq!(reranked[0].scores.rerank, Some(2.0));
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_reports_model_mismatch() {
+        let db = seeded_db(&DummyEmbeddingModel::new(4)).await;
+        let search = HybridSearch::new(db, Arc::new(DummyEmbeddingModel::new(8)));
+
+        let hits = search.search("chunks", "verifyToken", 2).await.unwrap();
+        assert!(hits.iter().all(|h| h.scores.vector.is_none()));
+
+        let err = search
+            .search("chunks", "nothingMatches", 2)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("re-index"), "{}", err);
+    }
+}
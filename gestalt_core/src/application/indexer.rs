@@ -1,3 +1,5 @@
+use crate::application::chunker::{syntax_spans, SourceLanguage};
+use crate::domain::rag::search::{bm25_rank, code_tokens, CorpusStats};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
@@ -23,6 +25,16 @@ pub struct FileMetadata {
 pub struct Chunk {
     pub content: String,
     pub index: usize,
+    /// First line of the chunk, 1-based.
+    pub start_line: usize,
+    /// Last line of the chunk, inclusive.
+    pub end_line: usize,
+    /// Name of the item the chunk belongs to, e.g. a function or heading.
+    pub symbol: Option<String>,
+    /// Kind of that item (`function`, `impl`, `class`, `heading`, ...).
+    pub kind: Option<String>,
+    /// Enclosing scope, e.g. the impl or class of a method.
+    pub parent: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +43,15 @@ pub struct DocumentRecord {
     pub chunks: Vec<Chunk>,
 }
 
+/// Files touched between two working-tree snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoChanges {
+    /// Indexable files to (re-)index, as absolute paths.
+    pub changed: Vec<PathBuf>,
+    /// Paths, relative to the repository root, whose documents should be dropped.
+    pub removed: Vec<PathBuf>,
+}
+
 #[derive(Debug, Error)]
 pub enum IndexerError {
     #[error("IO error: {0}")]
@@ -68,6 +89,8 @@ impl Default for Indexer {
                 "py".to_string(),
                 "js".to_string(),
                 "ts".to_string(),
+                "tsx".to_string(),
+                "go".to_string(),
                 "json".to_string(),
             ],
             max_file_size: 1024 * 1024, // 1MB
@@ -145,39 +168,108 @@ impl Indexer {
 
     /// Scan the repository for relevant files.
     pub fn scan(&self, root: &Path) -> Vec<PathBuf> {
-        let mut files = Vec::new();
-        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                let path = entry.path();
-                let relative = path.strip_prefix(root).unwrap_or(path);
-
-                // Skip hidden directories (like .git)
-                if relative
-                    .components()
-                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
-                {
-                    continue;
-                }
+        WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| self.is_indexable(root, path))
+            .collect()
+    }
+
+    /// Whether `path` under `root` is a file the indexer would pick up.
+    pub fn is_indexable(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+
+        // Skip hidden directories (like .git)
+        if relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return false;
+        }
+
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+            return false;
+        };
+        if !self.allowlist.contains(&ext.to_string()) {
+            return false;
+        }
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() <= self.max_file_size => true,
+            Ok(metadata) if metadata.is_file() => {
+                warn!(
+                    "Skipping file {:?} (size {} exceeds limit {})",
+                    path,
+                    metadata.len(),
+                    self.max_file_size
+                );
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Directories under `root` (itself included) that may hold indexable files.
+    pub fn watch_dirs(&self, root: &Path) -> Vec<PathBuf> {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            })
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_dir())
+            .map(|entry| entry.into_path())
+            .collect()
+    }
+
+    /// Tree object of the working tree at `root`, if it is the root of a git
+    /// repository: tracked and untracked files that git does not ignore, with
+    /// uncommitted edits. Blobs and trees go to the object database; the index
+    /// file on disk is not touched.
+    ///
+    /// What gets indexed is the working tree, so this, not `HEAD`, is what a
+    /// later [`Indexer::changes_between`] must start from.
+    pub fn worktree_snapshot(root: &Path) -> Option<String> {
+        let repo = git2::Repository::open(root).ok()?;
+        let mut index = repo.index().ok()?;
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .ok()?;
+        index.update_all(["*"], None).ok()?;
+        let tree = index.write_tree().ok()?;
+        Some(tree.to_string())
+    }
 
-                if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                    if self.allowlist.contains(&ext.to_string()) {
-                        if let Ok(metadata) = entry.metadata() {
-                            if metadata.len() <= self.max_file_size {
-                                files.push(path.to_path_buf());
-                            } else {
-                                warn!(
-                                    "Skipping file {:?} (size {} exceeds limit {})",
-                                    path,
-                                    metadata.len(),
-                                    self.max_file_size
-                                );
-                            }
-                        }
-                    }
+    /// Files under `root` that differ between two
+    /// [snapshots](Indexer::worktree_snapshot), with renames split into a
+    /// removal and a change.
+    pub fn changes_between(&self, root: &Path, old: &str, new: &str) -> Result<RepoChanges> {
+        let repo = git2::Repository::open(root)?;
+        let old = repo.find_tree(git2::Oid::from_str(old)?)?;
+        let new = repo.find_tree(git2::Oid::from_str(new)?)?;
+        let mut diff = repo.diff_tree_to_tree(Some(&old), Some(&new), None)?;
+        diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+
+        let mut changes = RepoChanges::default();
+        for delta in diff.deltas() {
+            let old_path = delta.old_file().path().map(Path::to_path_buf);
+            let new_path = delta.new_file().path().map(|p| root.join(p));
+            match delta.status() {
+                git2::Delta::Deleted => changes.removed.extend(old_path),
+                git2::Delta::Renamed => {
+                    changes.removed.extend(old_path);
+                    changes.changed.extend(new_path);
                 }
+                git2::Delta::Added
+                | git2::Delta::Modified
+                | git2::Delta::Copied
+                | git2::Delta::Typechange => changes.changed.extend(new_path),
+                _ => {}
             }
         }
-        files
+        changes.changed.retain(|path| self.is_indexable(root, path));
+        Ok(changes)
     }
 
     /// Process a file into chunks and metadata.
@@ -193,7 +285,7 @@ impl Indexer {
         hasher.update(content.as_bytes());
         let checksum = format!("{:x}", hasher.finalize());
 
-        let chunks = self.chunk_text(&content);
+        let chunks = self.chunk_file(file_path, &content);
 
         Ok(DocumentRecord {
             metadata: FileMetadata {
@@ -204,6 +296,50 @@ impl Indexer {
         })
     }
 
+    /// Chunk on item boundaries where the language is supported, splitting
+    /// oversized items and everything else with the line chunker.
+    fn chunk_file(&self, file_path: &Path, content: &str) -> Vec<Chunk> {
+        let spans = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(SourceLanguage::from_extension)
+            .and_then(|language| syntax_spans(language, content, self.chunk_size));
+        let Some(spans) = spans else {
+            return self.chunk_text(content);
+        };
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for span in spans {
+            let text = &content[span.start_byte..span.end_byte];
+            let pieces = if text.len() <= self.chunk_size {
+                vec![(text.to_string(), span.start_line, span.end_line)]
+            } else {
+                self.chunk_text(text)
+                    .into_iter()
+                    .map(|c| {
+                        (
+                            c.content,
+                            span.start_line + c.start_line - 1,
+                            span.start_line + c.end_line - 1,
+                        )
+                    })
+                    .collect()
+            };
+            for (content, start_line, end_line) in pieces {
+                chunks.push(Chunk {
+                    content,
+                    index: chunks.len(),
+                    start_line,
+                    end_line,
+                    symbol: span.symbol.clone(),
+                    kind: span.kind.clone(),
+                    parent: span.parent.clone(),
+                });
+            }
+        }
+        chunks
+    }
+
     fn chunk_text(&self, text: &str) -> Vec<Chunk> {
         let mut chunks = Vec::new();
         let lines: Vec<&str> = text.lines().collect();
@@ -228,6 +364,11 @@ impl Indexer {
             chunks.push(Chunk {
                 content: chunk_content.trim_end().to_string(),
                 index: chunks.len(),
+                start_line: start_line + 1,
+                end_line,
+                symbol: None,
+                kind: None,
+                parent: None,
             });
 
             if end_line == lines.len() {
@@ -271,6 +412,12 @@ pub struct VectorRecord {
     pub score: f32,
 }
 
+#[derive(Debug, Deserialize)]
+struct LexicalChunk {
+    metadata: serde_json::Value,
+    tokens: Vec<String>,
+}
+
 pub struct SurrealAdapter {
     client: crate::db::surreal::SurrealClient,
 }
@@ -315,6 +462,16 @@ impl VectorAdapter for SurrealAdapter {
                         "doc_id": doc_id,
                         "content": chunk.content,
                         "chunk_index": chunk.index,
+                        "tokens": code_tokens(&chunk.content),
+                        "metadata": {
+                            "path": doc.metadata.path,
+                            "content": chunk.content,
+                            "start_line": chunk.start_line,
+                            "end_line": chunk.end_line,
+                            "symbol": chunk.symbol,
+                            "kind": chunk.kind,
+                            "parent": chunk.parent,
+                        },
                         "created_at": created_at,
                     }),
                 )
@@ -330,21 +487,43 @@ impl VectorAdapter for SurrealAdapter {
         query: &str,
         limit: usize,
     ) -> anyhow::Result<Vec<VectorRecord>> {
-        // Simple keyword-based search in SurrealDB as a fallback for pure vector search
-        let sql = "SELECT path, content FROM chunks WHERE doc_id CONTAINS $repo_id AND content CONTAINS $query LIMIT $limit";
-        let results: Vec<VectorRecord> = self
+        let mut terms = code_tokens(query);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let scope = serde_json::json!({ "repo_id": repo_id, "terms": terms });
+        let candidates: Vec<LexicalChunk> = self
             .client
             .query_with(
-                sql,
-                serde_json::json!({
-                    "repo_id": repo_id,
-                    "query": query,
-                    "limit": limit,
-                }),
+                "SELECT metadata, tokens FROM chunks WHERE doc_id CONTAINS $repo_id AND tokens CONTAINSANY $terms",
+                &scope,
+            )
+            .await?;
+        let corpus: Vec<CorpusStats> = self
+            .client
+            .query_with(
+                "SELECT count() AS doc_count, math::mean(array::len(tokens)) AS avg_doc_len FROM chunks WHERE doc_id CONTAINS $repo_id AND tokens IS NOT NONE GROUP ALL",
+                &scope,
             )
             .await?;
 
-        Ok(results)
+        let stats = corpus.first().copied().unwrap_or_default();
+        let candidates = candidates
+            .into_iter()
+            .map(|c| (c.metadata, c.tokens))
+            .collect();
+        Ok(bm25_rank(query, candidates, &stats)
+            .into_iter()
+            .take(limit)
+            .map(|(metadata, score)| VectorRecord {
+                path: metadata["path"].as_str().unwrap_or_default().to_string(),
+                content: metadata["content"].as_str().unwrap_or_default().to_string(),
+                score,
+            })
+            .collect())
     }
 }
 
@@ -370,6 +549,93 @@ mod tests {
         assert!(chunks.len() >= 5);
         assert_eq!(chunks[0].content, "line1");
         assert_eq!(chunks[1].content, "line2");
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (2, 2));
+    }
+
+    #[test]
+    fn test_process_file_chunks_on_items() {
+        let dir = tempdir().expect("failed to create temp dir");
+        let file_path = dir.path().join("lib.rs");
+        let body: String = (0..8)
+            .map(|i| format!("    let v{} = {};\n", i, i))
+            .collect();
+        let source = format!(
+            "fn small() {{}}\n\nimpl Engine {{\n    fn run(&self) {{\n{}    }}\n}}\n",
+            body
+        );
+        std::fs::write(&file_path, source).expect("failed to write test file");
+
+        let indexer = Indexer::new(vec!["rs".to_string()], 1024, 80, 0);
+        let record = indexer
+            .process_file(dir.path(), &file_path)
+            .expect("failed to process file");
+        let chunks = &record.chunks;
+
+        assert_eq!(chunks[0].content, "fn small() {}");
+        assert_eq!(chunks[0].symbol.as_deref(), Some("small"));
+        assert_eq!(chunks[1].kind.as_deref(), Some("impl"));
+        // The oversized method falls back to line chunks that keep its symbol.
+        let run: Vec<&Chunk> = chunks
+            .iter()
+            .filter(|c| c.symbol.as_deref() == Some("run"))
+            .collect();
+        assert!(run.len() > 1);
+        assert!(run.iter().all(|c| c.parent.as_deref() == Some("Engine")));
+        assert_eq!(run[0].start_line, 4);
+        assert_eq!(run.last().unwrap().end_line, 13);
+    }
+
+    #[test]
+    fn test_changes_between_snapshots() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().canonicalize()?;
+        for (name, content) in [
+            ("a.rs", "fn a() {}"),
+            ("b.rs", "fn b() {}"),
+            (
+                "c.md",
+                "# Notes\n\nSome long enough text to be detected as a rename.\n",
+            ),
+        ] {
+            std::fs::write(root.join(name), content)?;
+        }
+        let repo = git2::Repository::init(&root)?;
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+        // Indexed with an uncommitted edit that is reverted afterwards: the
+        // file matches HEAD again but not what was indexed.
+        std::fs::write(root.join("a.rs"), "fn a() { todo!() }")?;
+        let before = Indexer::worktree_snapshot(&root).unwrap();
+        std::fs::write(root.join("a.rs"), "fn a() {}")?;
+        std::fs::remove_file(root.join("b.rs"))?;
+        std::fs::rename(root.join("c.md"), root.join("d.md"))?;
+        std::fs::write(root.join("e.py"), "def e(): pass")?;
+        std::fs::write(root.join("f.bin"), "skipped")?;
+        let after = Indexer::worktree_snapshot(&root).unwrap();
+        assert_eq!(Indexer::worktree_snapshot(&root), Some(after.clone()));
+        assert!(git2::Repository::open(&root)?
+            .index()?
+            .get_path(Path::new("e.py"), 0)
+            .is_none());
+
+        let indexer = Indexer::default();
+        let mut changes = indexer.changes_between(&root, &before, &after)?;
+        changes.changed.sort();
+        changes.removed.sort();
+        assert_eq!(
+            changes.changed,
+            vec![root.join("a.rs"), root.join("d.md"), root.join("e.py")]
+        );
+        assert_eq!(
+            changes.removed,
+            vec![PathBuf::from("b.rs"), PathBuf::from("c.md")]
+        );
+        Ok(())
     }
 
     #[test]
@@ -1,4 +1,6 @@
 pub mod agent;
+pub mod chunker;
 pub mod config;
+pub mod hybrid_search;
 pub mod indexer;
 pub mod mcp_service;
@@ -0,0 +1,433 @@
+//! Line diffs, unified-diff rendering and three-way merges.
+//!
+//! Lines keep their terminators, so a missing newline at end of file is a
+//! real difference and is rendered with git's `\ No newline at end of file`.
+
+use std::ops::Range;
+
+/// Lines surrounding each hunk in rendered diffs (git's default).
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// Edit distance beyond which [`diff_lines`] stops searching for a minimal
+/// diff and reports the differing middle as one replacement. Bounds the
+/// search to O(D²) memory for very different inputs.
+pub const MAX_EDIT_DISTANCE: usize = 1024;
+
+/// `old[old]` was replaced by `new[new]`; either range may be empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Split `text` into lines, keeping the `\n` of each line.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Minimal line changes turning `old` into `new` (Myers' O(ND) algorithm).
+///
+/// Past [`MAX_EDIT_DISTANCE`] edits, everything between the common prefix and
+/// suffix becomes a single change.
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let Some(edits) = myers_edits(a, b, MAX_EDIT_DISTANCE) else {
+        return vec![Change {
+            old: prefix..prefix + a.len(),
+            new: prefix..prefix + b.len(),
+        }];
+    };
+
+    let mut changes = Vec::new();
+    let mut pending: Option<Change> = None;
+    for (x, y, kind) in edits {
+        let (old_line, new_line) = (x + prefix, y + prefix);
+        let change = pending.get_or_insert(Change {
+            old: old_line..old_line,
+            new: new_line..new_line,
+        });
+        if change.old.end != old_line || change.new.end != new_line {
+            changes.push(pending.take().expect("pending change"));
+            pending = Some(Change {
+                old: old_line..old_line,
+                new: new_line..new_line,
+            });
+        }
+        let change = pending.as_mut().expect("pending change");
+        match kind {
+            Edit::Delete => change.old.end += 1,
+            Edit::Insert => change.new.end += 1,
+        }
+    }
+    changes.extend(pending);
+    changes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Delete,
+    Insert,
+}
+
+/// Deletions and insertions as `(old index, new index, edit)`, in order, or
+/// `None` when more than `max_d` edits are needed.
+///
+/// Round `d` only reaches diagonals `-d..=d`, so only that slice of `v` is
+/// kept for the backtrack.
+fn myers_edits<T: PartialEq>(a: &[T], b: &[T], max_d: usize) -> Option<Vec<(usize, usize, Edit)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // trace[d][k + d] is the furthest x on diagonal k before round d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        if d as usize > max_d {
+            return None;
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        if d == 0 {
+            break;
+        }
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            edits.push((prev_x as usize, prev_y as usize, Edit::Insert));
+        } else {
+            edits.push((prev_x as usize, prev_y as usize, Edit::Delete));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    Some(edits)
+}
+
+/// Unified-diff hunks (`@@ ... @@` sections only) turning `old` into `new`.
+///
+/// Returns an empty string when both texts are equal.
+pub fn unified_hunks(old: &str, new: &str, context: usize) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let changes = diff_lines(&old_lines, &new_lines);
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < changes.len() {
+        // Group changes whose context windows touch into one hunk.
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1].old.start - changes[j].old.end <= 2 * context
+        {
+            j += 1;
+        }
+        let old_start = changes[i].old.start.saturating_sub(context);
+        let old_end = (changes[j].old.end + context).min(old_lines.len());
+        let new_start = changes[i].new.start - (changes[i].old.start - old_start);
+        let new_end = changes[j].new.end + (old_end - changes[j].old.end);
+
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+        let mut pos = old_start;
+        for change in &changes[i..=j] {
+            for line in &old_lines[pos..change.old.start] {
+                push_line(&mut out, ' ', line);
+            }
+            for line in &old_lines[change.old.clone()] {
+                push_line(&mut out, '-', line);
+            }
+            for line in &new_lines[change.new.clone()] {
+                push_line(&mut out, '+', line);
+            }
+            pos = change.old.end;
+        }
+        for line in &old_lines[pos..old_end] {
+            push_line(&mut out, ' ', line);
+        }
+        i = j + 1;
+    }
+    out
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+    // An empty range names the line before it, as in `diff -u`.
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// A region both sides changed differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictHunk {
+    /// 1-based first base line of the region (the line before it when the region is empty).
+    pub base_start: usize,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// Merged text; conflicting regions carry diff3-style markers.
+    pub merged: String,
+    pub conflicts: Vec<ConflictHunk>,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Three-way merge of `ours` and `theirs`, both derived from `base`.
+///
+/// Changes touching the same (or adjacent) base lines merge cleanly only when
+/// both sides made the same edit; otherwise the region becomes a conflict.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_lines = split_lines(base);
+    let our_lines = split_lines(ours);
+    let their_lines = split_lines(theirs);
+    let our_changes = diff_lines(&base_lines, &our_lines);
+    let their_changes = diff_lines(&base_lines, &their_lines);
+
+    let mut merged = String::new();
+    let mut conflicts = Vec::new();
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+
+    while i < our_changes.len() || j < their_changes.len() {
+        let start = match (our_changes.get(i), their_changes.get(j)) {
+            (Some(a), Some(b)) => a.old.start.min(b.old.start),
+            (Some(a), None) => a.old.start,
+            (None, Some(b)) => b.old.start,
+            (None, None) => unreachable!(),
+        };
+        let mut end = start;
+        let (ours_from, theirs_from) = (i, j);
+        loop {
+            if let Some(change) = our_changes.get(i).filter(|c| c.old.start <= end) {
+                end = end.max(change.old.end);
+                i += 1;
+            } else if let Some(change) = their_changes.get(j).filter(|c| c.old.start <= end) {
+                end = end.max(change.old.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        merged.extend(base_lines[pos..start].iter().copied());
+        let ours_text = replay(
+            &base_lines,
+            &our_lines,
+            &our_changes[ours_from..i],
+            start..end,
+        );
+        let theirs_text = replay(
+            &base_lines,
+            &their_lines,
+            &their_changes[theirs_from..j],
+            start..end,
+        );
+        if theirs_from == j || ours_text == theirs_text {
+            merged.push_str(&ours_text);
+        } else if ours_from == i {
+            merged.push_str(&theirs_text);
+        } else {
+            let base_text: String = base_lines[start..end].concat();
+            push_marker_section(&mut merged, "<<<<<<< ours", &ours_text);
+            push_marker_section(&mut merged, "||||||| base", &base_text);
+            push_marker_section(&mut merged, "=======", &theirs_text);
+            merged.push_str(">>>>>>> theirs\n");
+            conflicts.push(ConflictHunk {
+                base_start: if start == end { start } else { start + 1 },
+                base: base_text,
+                ours: ours_text,
+                theirs: theirs_text,
+            });
+        }
+        pos = end;
+    }
+    merged.extend(base_lines[pos..].iter().copied());
+
+    MergeResult { merged, conflicts }
+}
+
+/// One side's text for `base[region]`, given its changes inside that region.
+fn replay(base: &[&str], side: &[&str], changes: &[Change], region: Range<usize>) -> String {
+    let mut out = String::new();
+    let mut pos = region.start;
+    for change in changes {
+        out.extend(base[pos..change.old.start].iter().copied());
+        out.extend(side[change.new.clone()].iter().copied());
+        pos = change.old.end;
+    }
+    out.extend(base[pos..region.end].iter().copied());
+    out
+}
+
+fn push_marker_section(out: &mut String, marker: &str, text: &str) {
+    out.push_str(marker);
+    out.push('\n');
+    out.push_str(text);
+    if !text.is_empty() && !text.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, merge3, myers_edits, split_lines, unified_hunks, Change};
+
+    #[test]
+    fn diff_lines_finds_minimal_changes() {
+        let old = split_lines("a\nb\nc\nd\n");
+        let new = split_lines("a\nx\nc\nd\ne\n");
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![
+                Change {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Change {
+                    old: 4..4,
+                    new: 4..5
+                },
+            ]
+        );
+        assert!(diff_lines(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diff_lines_caps_edit_distance() {
+        let old: Vec<usize> = (0..10).collect();
+        let new: Vec<usize> = (100..110).collect();
+        assert!(myers_edits(&old, &new, 19).is_none());
+        assert_eq!(myers_edits(&old, &new, 20).map(|e| e.len()), Some(20));
+
+        let old: Vec<usize> = (0..1000).collect();
+        let new: Vec<usize> = (0..1000).map(|i| i + 10_000).collect();
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![Change {
+                old: 0..1000,
+                new: 0..1000
+            }]
+        );
+    }
+
+    #[test]
+    fn unified_hunks_match_diff_u() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            unified_hunks(old, new, 3),
+            "@@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -8,3 +8,4 @@\n 8\n 9\n 10\n+11\n"
+        );
+    }
+
+    #[test]
+    fn unified_hunks_mark_missing_newline() {
+        assert_eq!(
+            unified_hunks("a\nb", "a\nb\n", 3),
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+        assert_eq!(unified_hunks("", "new\n", 3), "@@ -0,0 +1 @@\n+new\n");
+    }
+
+    #[test]
+    fn merge3_combines_disjoint_changes() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\n";
+        let result = merge3(base, ours, theirs);
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "A\nb\nc\nd\nE\n");
+    }
+
+    #[test]
+    fn merge3_accepts_identical_edits() {
+        let result = merge3("a\nb\n", "a\nB\n", "a\nB\n");
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "a\nB\n");
+    }
+
+    #[test]
+    fn merge3_reports_conflicting_hunks() {
+        let base = "a\nb\nc\nd\ne\nf\ng\n";
+        let ours = "a\nours\nc\nd\ne\nf\nG\n";
+        let theirs = "a\ntheirs\nc\nd\ne\nf\ng\n";
+        let result = merge3(base, ours, theirs);
+
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.base_start, 2);
+        assert_eq!(conflict.base, "b\n");
+        assert_eq!(conflict.ours, "ours\n");
+        assert_eq!(conflict.theirs, "theirs\n");
+        assert_eq!(
+            result.merged,
+            "a\n<<<<<<< ours\nours\n||||||| base\nb\n=======\ntheirs\n>>>>>>> theirs\n\
+             c\nd\ne\nf\nG\n"
+        );
+    }
+}
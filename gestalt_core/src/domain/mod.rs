@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+pub mod diff;
 pub mod genui;
 pub mod rag;
 
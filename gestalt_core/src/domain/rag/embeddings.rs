@@ -3,16 +3,62 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait EmbeddingModel: Send + Sync {
     async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>>;
+
+    /// Embed several texts, in order. Backends that can batch override this.
+    async fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for text in texts {
+            vectors.push(self.embed(text).await?);
+        }
+        Ok(vectors)
+    }
+
+    /// Identifies the model; stored with every vector it produces so that
+    /// vectors from different models are never compared.
+    fn model_id(&self) -> &str;
+
+    /// Length of the vectors this model produces.
+    fn dimension(&self) -> usize;
+}
+
+/// Model and vector length that produced a stored embedding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingSpace {
+    pub model_id: String,
+    pub dimension: usize,
+}
+
+impl EmbeddingSpace {
+    pub fn of(model: &dyn EmbeddingModel) -> Self {
+        Self {
+            model_id: model.model_id().to_string(),
+            dimension: model.dimension(),
+        }
+    }
+
+    /// Error explaining why vectors from `stored` cannot be searched with this model.
+    pub fn mismatch(&self, stored: &EmbeddingSpace) -> Option<String> {
+        (self != stored).then(|| {
+            format!(
+                "Index was embedded with {} ({} dims) but the configured embedding model is {} ({} dims); re-index to search it",
+                stored.model_id, stored.dimension, self.model_id, self.dimension
+            )
+        })
+    }
 }
 
 // Dummy implementation for when models are not available or for testing
 pub struct DummyEmbeddingModel {
     dim: usize,
+    model_id: String,
 }
 
 impl DummyEmbeddingModel {
     pub fn new(dim: usize) -> Self {
-        Self { dim }
+        Self {
+            dim,
+            model_id: format!("dummy-{}", dim),
+        }
     }
 }
 
@@ -33,4 +79,12 @@ impl EmbeddingModel for DummyEmbeddingModel {
         }
         Ok(vec)
     }
+
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
 }
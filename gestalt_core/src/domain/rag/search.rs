@@ -0,0 +1,217 @@
+//! Lexical scoring and rank fusion for hybrid code search.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// BM25 term-frequency saturation.
+pub const BM25_K1: f32 = 1.2;
+/// BM25 document-length normalization.
+pub const BM25_B: f32 = 0.75;
+/// Reciprocal rank fusion damping constant, as in the original RRF paper.
+pub const RRF_K: f32 = 60.0;
+
+/// Lowercase search tokens of `text` that understand code identifiers.
+///
+/// `parseHttpRequest` and `parse_http_request` both yield `parse`, `http` and
+/// `request`, plus the joined `parsehttprequest` so whole-identifier matches
+/// rank higher.
+pub fn code_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for ident in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|s| !s.is_empty())
+    {
+        let parts: Vec<String> = ident
+            .split('_')
+            .flat_map(split_camel_case)
+            .map(|p| p.to_lowercase())
+            .filter(|p| p.chars().count() > 1)
+            .collect();
+        if parts.len() > 1 {
+            tokens.push(parts.concat());
+        }
+        tokens.extend(parts);
+    }
+    tokens
+}
+
+/// Split `HTTPServerError2` into `HTTP`, `Server`, `Error`, `2`.
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 1..chars.len() {
+        let (idx, c) = chars[i];
+        let prev = chars[i - 1].1;
+        let next_is_lower = chars.get(i + 1).is_some_and(|(_, n)| n.is_lowercase());
+        let boundary = (prev.is_lowercase() && c.is_uppercase())
+            || (prev.is_uppercase() && c.is_uppercase() && next_is_lower)
+            || (prev.is_alphabetic() && c.is_numeric())
+            || (prev.is_numeric() && c.is_alphabetic());
+        if boundary {
+            parts.push(&word[start..idx]);
+            start = idx;
+        }
+    }
+    if start < word.len() {
+        parts.push(&word[start..]);
+    }
+    parts
+}
+
+/// Collection-wide statistics BM25 normalizes against.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CorpusStats {
+    pub doc_count: usize,
+    pub avg_doc_len: f32,
+}
+
+/// Rank `candidates` (item and its [`code_tokens`]) against `query` with BM25.
+///
+/// Document frequencies are counted over `candidates`, so they must include
+/// every document that contains a query term. Non-matching candidates are
+/// dropped; the rest are returned best first.
+pub fn bm25_rank<T>(
+    query: &str,
+    candidates: Vec<(T, Vec<String>)>,
+    stats: &CorpusStats,
+) -> Vec<(T, f32)> {
+    let terms: HashSet<String> = code_tokens(query).into_iter().collect();
+    let mut doc_freq: HashMap<String, usize> = HashMap::new();
+    for (_, tokens) in &candidates {
+        let present: HashSet<&str> = tokens.iter().map(String::as_str).collect();
+        for term in &terms {
+            if present.contains(term.as_str()) {
+                *doc_freq.entry(term.clone()).or_default() += 1;
+            }
+        }
+    }
+    bm25_rank_with_doc_freq(query, candidates, stats, &doc_freq)
+}
+
+/// [`bm25_rank`] with the document frequency of each query term given, for
+/// `candidates` that are only some of the documents containing them.
+pub fn bm25_rank_with_doc_freq<T>(
+    query: &str,
+    candidates: Vec<(T, Vec<String>)>,
+    stats: &CorpusStats,
+    doc_freq: &HashMap<String, usize>,
+) -> Vec<(T, f32)> {
+    let terms: HashSet<String> = code_tokens(query).into_iter().collect();
+    let n = stats.doc_count.max(candidates.len()) as f32;
+    let avg_len = if stats.avg_doc_len > 0.0 {
+        stats.avg_doc_len
+    } else {
+        1.0
+    };
+    let mut ranked: Vec<(T, f32)> = candidates
+        .into_iter()
+        .filter_map(|(item, tokens)| {
+            let mut tf: HashMap<&str, f32> = HashMap::new();
+            for token in &tokens {
+                if terms.contains(token) {
+                    *tf.entry(token.as_str()).or_default() += 1.0;
+                }
+            }
+            let len_norm = 1.0 - BM25_B + BM25_B * tokens.len() as f32 / avg_len;
+            let score: f32 = tf
+                .iter()
+                .map(|(term, &freq)| {
+                    let df = doc_freq.get(*term).copied().unwrap_or(0) as f32;
+                    let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * freq * (BM25_K1 + 1.0) / (freq + BM25_K1 * len_norm)
+                })
+                .sum();
+            (score > 0.0).then_some((item, score))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Fuse several best-first rankings of IDs with reciprocal rank fusion.
+pub fn reciprocal_rank_fusion(rankings: &[Vec<String>]) -> Vec<(String, f32)> {
+    let mut scores: HashMap<&str, f32> = HashMap::new();
+    for ranking in rankings {
+        for (rank, id) in ranking.iter().enumerate() {
+            *scores.entry(id.as_str()).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(String, f32)> = scores
+        .into_iter()
+        .map(|(id, score)| (id.to_string(), score))
+        .collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    fused
+}
+
+/// How a search hit was scored by each stage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector: Option<f32>,
+    pub fused: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_tokens_split_identifiers() {
+        assert_eq!(
+            code_tokens("fn parseHttpRequest(raw_body: &str)"),
+            vec![
+                "fn",
+                "parsehttprequest",
+                "parse",
+                "http",
+                "request",
+                "rawbody",
+                "raw",
+                "body",
+                "str"
+            ]
+        );
+        assert_eq!(
+            code_tokens("HTTPServerError2"),
+            vec!["httpservererror", "http", "server", "error"]
+        );
+    }
+
+    #[test]
+    fn test_bm25_and_fusion() {
+        let docs = [
+            (
+                "auth",
+                "fn verify_token(token: &str) -> bool { check_token(token) }",
+            ),
+            (
+                "http",
+                "struct HttpClient; impl HttpClient { fn send() {} }",
+            ),
+            ("misc", "fn tokenize(text: &str) {}"),
+        ];
+        let candidates: Vec<(&str, Vec<String>)> = docs
+            .iter()
+            .map(|(id, text)| (*id, code_tokens(text)))
+            .collect();
+        let stats = CorpusStats {
+            doc_count: 3,
+            avg_doc_len: candidates.iter().map(|(_, t)| t.len()).sum::<usize>() as f32 / 3.0,
+        };
+        let ranked = bm25_rank("verifyToken", candidates, &stats);
+        assert_eq!(ranked[0].0, "auth");
+        assert!(ranked.iter().all(|(id, _)| *id != "http"));
+
+        let lexical = vec!["a".to_string(), "b".to_string()];
+        let vector = vec!["c".to_string(), "b".to_string(), "a".to_string()];
+        let fused = reciprocal_rank_fusion(&[lexical, vector]);
+        let order: Vec<&str> = fused.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "c"]);
+    }
+}
@@ -1,14 +1,23 @@
 //! MCP Client Implementation
 //!
-//! Integration with MCP (Model Context Protocol) servers.
+//! Integration with MCP (Model Context Protocol) servers over stdio or
+//! streamable HTTP.
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock as StdRwLock};
 use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use super::protocol::{
+    error_codes, CallToolResult, InitializeResult, JsonRpcNotification, JsonRpcRequest,
+    ListToolsResult, RequestId, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use super::transport::{HttpTransport, McpTransport, StdioTransport};
 
 /// MCP Client errors
 #[derive(Debug, Error)]
@@ -27,6 +36,9 @@ pub enum McpError {
 
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+
+    #[error("Server error {code}: {message}")]
+    Rpc { code: i64, message: String },
 }
 
 /// Tool information from MCP server
@@ -73,44 +85,252 @@ pub trait McpClient: Send + Sync {
     ) -> Result<ToolResult, McpError>;
 }
 
+/// Where an MCP server lives.
+///
+/// `http://` and `https://` URLs use streamable HTTP; anything else is a
+/// command line (optionally prefixed with `stdio:`) spawned as a child process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpEndpoint {
+    Stdio { command: String, args: Vec<String> },
+    Http { url: String },
+}
+
+impl McpEndpoint {
+    /// Parse a server URL or command line
+    pub fn parse(server_url: &str) -> Result<Self, McpError> {
+        let trimmed = server_url.trim();
+        if trimmed.starts_with("http://") || trimmed.starts_with("https://") {
+            return Ok(McpEndpoint::Http {
+                url: trimmed.to_string(),
+            });
+        }
+
+        let command_line = trimmed
+            .strip_prefix("stdio://")
+            .or_else(|| trimmed.strip_prefix("stdio:"))
+            .unwrap_or(trimmed);
+        let mut parts = command_line.split_whitespace().map(ToOwned::to_owned);
+        let command = parts
+            .next()
+            .ok_or_else(|| McpError::Connection("empty MCP server command".to_string()))?;
+        Ok(McpEndpoint::Stdio {
+            command,
+            args: parts.collect(),
+        })
+    }
+}
+
+/// Sends `notifications/cancelled` if a request is abandoned before it completes
+struct CancelOnDrop {
+    transport: Arc<dyn McpTransport>,
+    id: RequestId,
+    armed: bool,
+}
+
+impl CancelOnDrop {
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let transport = self.transport.clone();
+        let id = self.id.clone();
+        handle.spawn(async move {
+            let notification = JsonRpcNotification::new(
+                "notifications/cancelled",
+                Some(json!({ "requestId": id, "reason": "Request cancelled by client" })),
+            );
+            if let Err(e) = transport.notify(notification).await {
+                debug!("Failed to send cancellation for request {}: {}", id, e);
+            }
+        });
+    }
+}
+
 /// MCP Client implementation
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct GestaltMcpClient {
     // Server URL
-    _server_url: Option<String>,
+    server_url: Option<String>,
     // Available tools cache
-    tools: Vec<ToolInfo>,
+    tools: Arc<StdRwLock<Vec<ToolInfo>>>,
     // Connection status
     connected: Arc<AtomicBool>,
+    // Active transport
+    transport: Arc<RwLock<Option<Arc<dyn McpTransport>>>>,
+    // Handshake result
+    server: Arc<StdRwLock<Option<InitializeResult>>>,
+    // JSON-RPC id sequence
+    next_id: Arc<AtomicU64>,
+    // Default per-request timeout
+    request_timeout: Duration,
+}
+
+impl std::fmt::Debug for GestaltMcpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GestaltMcpClient")
+            .field("server_url", &self.server_url)
+            .field("connected", &self.is_connected())
+            .field("request_timeout", &self.request_timeout)
+            .finish_non_exhaustive()
+    }
 }
 
 impl GestaltMcpClient {
     /// Create a new MCP client
     pub fn new() -> Self {
         Self {
-            _server_url: None,
-            tools: Vec::new(),
+            server_url: None,
+            tools: Arc::new(StdRwLock::new(Vec::new())),
             connected: Arc::new(AtomicBool::new(false)),
+            transport: Arc::new(RwLock::new(None)),
+            server: Arc::new(StdRwLock::new(None)),
+            next_id: Arc::new(AtomicU64::new(1)),
+            request_timeout: Duration::from_secs(30),
         }
     }
 
     /// Create with server URL
     pub fn with_server(server_url: &str) -> Self {
         Self {
-            _server_url: Some(server_url.to_string()),
-            tools: Vec::new(),
-            connected: Arc::new(AtomicBool::new(false)),
+            server_url: Some(server_url.to_string()),
+            ..Self::new()
         }
     }
 
+    /// Override the default per-request timeout
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Server URL this client was created for
+    pub fn server_url(&self) -> Option<&str> {
+        self.server_url.as_deref()
+    }
+
     /// Check if connected
     pub fn is_connected(&self) -> bool {
         self.connected.load(Ordering::SeqCst)
     }
 
     /// Get cached tools
-    pub fn cached_tools(&self) -> &[ToolInfo] {
-        &self.tools
+    pub fn cached_tools(&self) -> Vec<ToolInfo> {
+        self.tools.read().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    /// Handshake result from the connected server
+    pub fn server_info(&self) -> Option<InitializeResult> {
+        self.server.read().ok().and_then(|s| s.clone())
+    }
+
+    /// Connect over an already established transport and run the handshake
+    pub async fn connect_transport(
+        &self,
+        transport: Arc<dyn McpTransport>,
+    ) -> Result<(), McpError> {
+        if self.is_connected() {
+            self.disconnect().await?;
+        }
+
+        let init = match self.initialize(&transport).await {
+            Ok(init) => init,
+            Err(e) => {
+                let _ = transport.close().await;
+                return Err(e);
+            }
+        };
+
+        info!(
+            "Connected to MCP server {} {} (protocol {})",
+            init.server_info.name, init.server_info.version, init.protocol_version
+        );
+
+        if let Ok(mut server) = self.server.write() {
+            *server = Some(init);
+        }
+        *self.transport.write().await = Some(transport);
+        self.connected.store(true, Ordering::SeqCst);
+
+        // Prime the cache so callers can inspect tools right after connecting.
+        self.list_tools().await?;
+        Ok(())
+    }
+
+    async fn initialize(
+        &self,
+        transport: &Arc<dyn McpTransport>,
+    ) -> Result<InitializeResult, McpError> {
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {
+                "name": "gestalt",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        // `initialize` must not be cancelled, so it bypasses the cancel guard.
+        let request = JsonRpcRequest::new(self.next_request_id(), "initialize", Some(params));
+        let response = tokio::time::timeout(self.request_timeout, transport.request(request))
+            .await
+            .map_err(|_| McpError::Timeout)??;
+        let init: InitializeResult = serde_json::from_value(response.into_result()?)
+            .map_err(|e| McpError::InvalidResponse(format!("bad initialize result: {}", e)))?;
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&init.protocol_version.as_str()) {
+            return Err(McpError::Connection(format!(
+                "unsupported MCP protocol version '{}'",
+                init.protocol_version
+            )));
+        }
+        transport.set_protocol_version(&init.protocol_version);
+        transport
+            .notify(JsonRpcNotification::new("notifications/initialized", None))
+            .await?;
+        Ok(init)
+    }
+
+    fn next_request_id(&self) -> RequestId {
+        RequestId::Number(self.next_id.fetch_add(1, Ordering::SeqCst))
+    }
+
+    async fn active_transport(&self) -> Result<Arc<dyn McpTransport>, McpError> {
+        self.transport
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| McpError::Connection("not connected".to_string()))
+    }
+
+    /// Send a request; dropping the returned future (or timing out) cancels it on the server.
+    async fn request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value, McpError> {
+        let transport = self.active_transport().await?;
+        let id = self.next_request_id();
+        let guard = CancelOnDrop {
+            transport: transport.clone(),
+            id: id.clone(),
+            armed: true,
+        };
+
+        let request = JsonRpcRequest::new(id, method, params);
+        let response = tokio::time::timeout(timeout, transport.request(request))
+            .await
+            .map_err(|_| McpError::Timeout)??;
+        guard.disarm();
+        response.into_result()
     }
 }
 
@@ -122,67 +342,99 @@ impl Default for GestaltMcpClient {
 
 #[async_trait]
 impl McpClient for GestaltMcpClient {
-    async fn connect(&self, _server_url: &str) -> Result<(), McpError> {
-        // In production, would connect to actual MCP server
-        // For now, simulate connection
-        Ok(())
+    async fn connect(&self, server_url: &str) -> Result<(), McpError> {
+        let transport: Arc<dyn McpTransport> = match McpEndpoint::parse(server_url)? {
+            McpEndpoint::Http { url } => Arc::new(HttpTransport::new(&url)),
+            McpEndpoint::Stdio { command, args } => {
+                Arc::new(StdioTransport::spawn(&command, &args, None).await?)
+            }
+        };
+        self.connect_transport(transport).await
     }
 
     async fn disconnect(&self) -> Result<(), McpError> {
         self.connected.store(false, Ordering::SeqCst);
+        if let Ok(mut tools) = self.tools.write() {
+            tools.clear();
+        }
+        if let Some(transport) = self.transport.write().await.take() {
+            transport.close().await?;
+        }
         Ok(())
     }
 
     async fn list_tools(&self) -> Result<Vec<ToolInfo>, McpError> {
-        // In production, would fetch from MCP server
-        // Return sample tools
-        Ok(vec![
-            ToolInfo {
-                name: "shell_execute".to_string(),
-                description: "Execute shell commands".to_string(),
-                parameters: json!({
-                    "type": "object",
-                    "properties": {
-                        "command": {"type": "string"},
-                        "timeout": {"type": "number"}
-                    }
-                }),
-            },
-            ToolInfo {
-                name: "file_read".to_string(),
-                description: "Read file contents".to_string(),
-                parameters: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string"}
-                    }
-                }),
-            },
-            ToolInfo {
-                name: "file_write".to_string(),
-                description: "Write file contents".to_string(),
-                parameters: json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string"},
-                        "content": {"type": "string"}
-                    }
-                }),
-            },
-        ])
+        if let Some(server) = self.server_info() {
+            if !server.has_capability("tools") {
+                warn!(
+                    "MCP server {} did not advertise tools; listing anyway",
+                    server.server_info.name
+                );
+            }
+        }
+
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let result = self
+                .request("tools/list", params, self.request_timeout)
+                .await?;
+            let page: ListToolsResult = serde_json::from_value(result)
+                .map_err(|e| McpError::InvalidResponse(format!("bad tools/list result: {}", e)))?;
+            tools.extend(page.tools.into_iter().map(ToolInfo::from));
+
+            match page.next_cursor {
+                Some(next) if !next.is_empty() && cursor.as_deref() != Some(next.as_str()) => {
+                    cursor = Some(next)
+                }
+                _ => break,
+            }
+        }
+
+        if let Ok(mut cache) = self.tools.write() {
+            *cache = tools.clone();
+        }
+        Ok(tools)
     }
 
     async fn call_tool(
         &self,
-        _name: &str,
-        _arguments: Value,
-        _timeout: Option<Duration>,
+        name: &str,
+        arguments: Value,
+        timeout: Option<Duration>,
     ) -> Result<ToolResult, McpError> {
-        // In production, would call actual MCP tool
+        let params = json!({ "name": name, "arguments": arguments });
+        let result = match self
+            .request(
+                "tools/call",
+                Some(params),
+                timeout.unwrap_or(self.request_timeout),
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(McpError::Rpc { code, message })
+                if code == error_codes::INVALID_PARAMS
+                    && message.to_lowercase().contains("tool") =>
+            {
+                return Err(McpError::ToolNotFound(name.to_string()))
+            }
+            Err(e) => return Err(e),
+        };
+
+        let call: CallToolResult = serde_json::from_value(result)
+            .map_err(|e| McpError::InvalidResponse(format!("bad tools/call result: {}", e)))?;
+        let error = call.is_error.then(|| call.text());
+        let content = call
+            .structured_content
+            .clone()
+            .unwrap_or_else(|| Value::Array(call.content.clone()));
+
         Ok(ToolResult {
-            success: true,
-            content: json!({"result": "tool called"}),
-            error: None,
+            success: !call.is_error,
+            content,
+            error,
         })
     }
 }
@@ -202,23 +454,36 @@ mod tests {
     async fn test_client_with_server() {
         let client = GestaltMcpClient::with_server("http://localhost:3000");
         assert!(!client.is_connected());
+        assert_eq!(client.server_url(), Some("http://localhost:3000"));
     }
 
     #[tokio::test]
-    async fn test_list_tools() {
+    async fn test_list_tools_requires_connection() {
         let client = GestaltMcpClient::new();
-        let tools = client.list_tools().await.unwrap();
-        assert!(!tools.is_empty());
-        assert_eq!(tools.len(), 3);
+        assert!(matches!(
+            client.list_tools().await,
+            Err(McpError::Connection(_))
+        ));
     }
 
-    #[tokio::test]
-    async fn test_call_tool() {
-        let client = GestaltMcpClient::new();
-        let result = client
-            .call_tool("shell_execute", json!({"command": "echo test"}), None)
-            .await
-            .unwrap();
-        assert!(result.success);
+    #[test]
+    fn test_endpoint_parse() {
+        assert_eq!(
+            McpEndpoint::parse("https://mcp.example.com/mcp").unwrap(),
+            McpEndpoint::Http {
+                url: "https://mcp.example.com/mcp".to_string()
+            }
+        );
+        assert_eq!(
+            McpEndpoint::parse("stdio:npx -y server-filesystem /tmp").unwrap(),
+            McpEndpoint::Stdio {
+                command: "npx".to_string(),
+                args: vec!["-y", "server-filesystem", "/tmp"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            }
+        );
+        assert!(McpEndpoint::parse("   ").is_err());
     }
 }
@@ -1,10 +1,18 @@
 //! MCP (Model Context Protocol) Module
 //!
-//! Provides MCP client implementation and tool execution.
+//! Provides the MCP client (stdio and streamable HTTP transports), tool execution
+//! and an MCP server publishing a synapse `ToolRegistry`.
 
 pub mod client_impl;
+pub mod protocol;
 pub mod registry;
+pub mod server;
+pub mod transport;
 
-pub use client_impl::{GestaltMcpClient, McpClient, McpError, ToolCall, ToolInfo, ToolResult};
+pub use client_impl::{
+    GestaltMcpClient, McpClient, McpEndpoint, McpError, ToolCall, ToolInfo, ToolResult,
+};
+pub use server::{McpResourceProvider, McpServer};
+pub use transport::{HttpTransport, McpTransport, StdioTransport};
 
 pub use registry::{DefaultToolContext, McpRegistry, ToolContext};
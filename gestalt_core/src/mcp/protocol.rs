@@ -0,0 +1,316 @@
+//! MCP Wire Protocol
+//!
+//! JSON-RPC 2.0 message types and the MCP payloads shared by client and server.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::client_impl::{McpError, ToolInfo};
+
+/// JSON-RPC version string carried by every message.
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Protocol revision requested during `initialize`.
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Protocol revisions the client can talk to.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// JSON-RPC request identifier
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    String(String),
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestId::Number(n) => write!(f, "{}", n),
+            RequestId::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// JSON-RPC request (expects a response)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcRequest {
+    pub fn new(id: RequestId, method: &str, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+/// JSON-RPC notification (no response)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcNotification {
+    pub fn new(method: &str, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+/// JSON-RPC error object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// JSON-RPC response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    /// Successful response
+    pub fn success(id: RequestId, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// Error response
+    pub fn failure(id: RequestId, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+
+    /// Convert into the `result` payload or an RPC error
+    pub fn into_result(self) -> Result<Value, McpError> {
+        match (self.result, self.error) {
+            (_, Some(err)) => Err(McpError::Rpc {
+                code: err.code,
+                message: err.message,
+            }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null),
+        }
+    }
+}
+
+/// Any message that can appear on the wire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest),
+    Response(JsonRpcResponse),
+    Notification(JsonRpcNotification),
+}
+
+/// Standard JSON-RPC error codes
+pub mod error_codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// MCP-specific: `resources/read` for an unknown URI
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
+}
+
+/// Name and version of a client or server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Implementation {
+    pub name: String,
+    pub version: String,
+}
+
+/// Result of the `initialize` handshake
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: Value,
+    pub server_info: Implementation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+}
+
+impl InitializeResult {
+    /// Whether the server advertised a capability (e.g. `tools`)
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.get(name).is_some()
+    }
+}
+
+/// Tool definition as published by `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: Value,
+}
+
+impl From<McpTool> for ToolInfo {
+    fn from(tool: McpTool) -> Self {
+        ToolInfo {
+            name: tool.name,
+            description: tool.description.unwrap_or_default(),
+            parameters: tool.input_schema,
+        }
+    }
+}
+
+/// One page of `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListToolsResult {
+    #[serde(default)]
+    pub tools: Vec<McpTool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Result of `tools/call`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    #[serde(default)]
+    pub content: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+impl CallToolResult {
+    /// Concatenated text of all `text` content items
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter(|c| c.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|c| c.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Resource advertised by `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// One page of `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResourcesResult {
+    #[serde(default)]
+    pub resources: Vec<McpResource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Text contents of a resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl ResourceContents {
+    /// JSON document serialized as `application/json`
+    pub fn json(uri: &str, value: &Value) -> Self {
+        Self {
+            uri: uri.to_string(),
+            mime_type: Some("application/json".to_string()),
+            text: serde_json::to_string_pretty(value).unwrap_or_default(),
+        }
+    }
+}
+
+/// Result of `resources/read`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadResourceResult {
+    #[serde(default)]
+    pub contents: Vec<ResourceContents>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_classification() {
+        let req: JsonRpcMessage =
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).unwrap();
+        assert!(matches!(req, JsonRpcMessage::Request(_)));
+
+        let resp: JsonRpcMessage =
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": "a", "result": {}})).unwrap();
+        assert!(matches!(resp, JsonRpcMessage::Response(_)));
+
+        let note: JsonRpcMessage = serde_json::from_value(
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        )
+        .unwrap();
+        assert!(matches!(note, JsonRpcMessage::Notification(_)));
+    }
+
+    #[test]
+    fn test_error_response_into_result() {
+        let resp = JsonRpcResponse::failure(RequestId::Number(3), -32601, "nope");
+        match resp.into_result() {
+            Err(McpError::Rpc { code, .. }) => assert_eq!(code, -32601),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
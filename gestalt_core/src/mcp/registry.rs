@@ -43,8 +43,8 @@ impl ToolContext for DefaultToolContext {
 pub struct McpRegistry {
     /// Registered tools
     tools: Arc<Mutex<HashMap<String, ToolInfo>>>,
-    /// MCP client
-    client: Arc<Mutex<Option<GestaltMcpClient>>>,
+    /// Client serving each tool discovered on a live server
+    tool_clients: Arc<Mutex<HashMap<String, GestaltMcpClient>>>,
     /// Default timeout
     default_timeout: Duration,
 }
@@ -54,7 +54,7 @@ impl McpRegistry {
     pub fn new() -> Self {
         Self {
             tools: Arc::new(Mutex::new(HashMap::new())),
-            client: Arc::new(Mutex::new(None)),
+            tool_clients: Arc::new(Mutex::new(HashMap::new())),
             default_timeout: Duration::from_secs(30),
         }
     }
@@ -67,8 +67,8 @@ impl McpRegistry {
 
     /// Unregister a tool
     pub async fn unregister_tool(&self, name: &str) {
-        let mut tools = self.tools.lock().await;
-        tools.remove(name);
+        self.tools.lock().await.remove(name);
+        self.tool_clients.lock().await.remove(name);
     }
 
     /// List all tools
@@ -83,21 +83,39 @@ impl McpRegistry {
         tools.get(name).cloned()
     }
 
-    /// Connect to MCP server
-    pub async fn connect(&self, server_url: &str) -> Result<(), McpError> {
+    /// Connect to MCP server and register every tool it publishes.
+    ///
+    /// `server_url` is either an `http(s)://` endpoint or a stdio command line.
+    /// Returns the number of tools registered from the server.
+    pub async fn connect(&self, server_url: &str) -> Result<usize, McpError> {
         let client = GestaltMcpClient::with_server(server_url);
         client.connect(server_url).await?;
+        Ok(self.register_client(client).await)
+    }
 
-        // Fetch and register tools
-        let tools = client.list_tools().await?;
+    /// Register the tools of an already connected client.
+    pub async fn register_client(&self, client: GestaltMcpClient) -> usize {
+        let tools = client.cached_tools();
+        let count = tools.len();
+        let mut tool_clients = self.tool_clients.lock().await;
         for tool in tools {
+            tool_clients.insert(tool.name.clone(), client.clone());
             self.register_tool(tool).await;
         }
+        count
+    }
 
-        let mut client_ref = self.client.lock().await;
-        *client_ref = Some(client);
-
-        Ok(())
+    /// Disconnect every live server and drop the tools they provided.
+    pub async fn disconnect_all(&self) {
+        let clients: Vec<(String, GestaltMcpClient)> =
+            self.tool_clients.lock().await.drain().collect();
+        let mut tools = self.tools.lock().await;
+        for (name, client) in clients {
+            tools.remove(&name);
+            if client.is_connected() {
+                let _ = client.disconnect().await;
+            }
+        }
     }
 
     /// Execute a tool
@@ -121,9 +139,12 @@ impl McpRegistry {
             .unwrap_or(self.default_timeout);
 
         // Execute with timeout
-        let result = timeout(timeout_duration, self.execute_tool_inner(name, args, ctx))
-            .await
-            .map_err(|_| McpError::Timeout)?;
+        let result = timeout(
+            timeout_duration,
+            self.execute_tool_inner(name, args, ctx, timeout_duration),
+        )
+        .await
+        .map_err(|_| McpError::Timeout)?;
 
         result
     }
@@ -134,11 +155,13 @@ impl McpRegistry {
         name: &str,
         args: Value,
         _ctx: &dyn ToolContext,
+        timeout_duration: Duration,
     ) -> Result<ToolResult, McpError> {
-        let client_ref = self.client.lock().await;
+        // Clone the client out so concurrent calls do not serialize on the lock.
+        let client = self.tool_clients.lock().await.get(name).cloned();
 
-        if let Some(ref client) = *client_ref {
-            client.call_tool(name, args, None::<Duration>).await
+        if let Some(client) = client {
+            client.call_tool(name, args, Some(timeout_duration)).await
         } else {
             // Local execution for registered tools
             Ok(ToolResult {
@@ -0,0 +1,480 @@
+//! MCP Server
+//!
+//! Publishes a synapse `ToolRegistry` (and optional resources) over MCP.
+//! Transport-agnostic: [`McpServer::handle_value`] answers one JSON-RPC
+//! message, [`McpServer::serve`] drives a newline-delimited stdio session.
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use synapse_agentic::prelude::{EmptyContext, ToolRegistry};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+
+use super::client_impl::McpError;
+use super::protocol::{
+    error_codes, CallToolResult, Implementation, InitializeResult, JsonRpcMessage,
+    JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, ListResourcesResult, ListToolsResult,
+    McpResource, McpTool, ReadResourceResult, RequestId, ResourceContents, JSONRPC_VERSION,
+    PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
+};
+
+/// Source of MCP resources (e.g. VFS state, timeline events)
+#[async_trait]
+pub trait McpResourceProvider: Send + Sync {
+    /// Resources currently available from this provider
+    async fn list_resources(&self) -> Result<Vec<McpResource>, McpError>;
+
+    /// Whether `uri` belongs to this provider
+    fn handles(&self, uri: &str) -> bool;
+
+    /// Read the contents behind `uri`
+    async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, McpError>;
+}
+
+/// MCP server over a tool registry and a set of resource providers
+#[derive(Clone)]
+pub struct McpServer {
+    info: Implementation,
+    instructions: Option<String>,
+    tools: ToolRegistry,
+    resources: Vec<Arc<dyn McpResourceProvider>>,
+}
+
+impl std::fmt::Debug for McpServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpServer")
+            .field("info", &self.info)
+            .field("resources", &self.resources.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl McpServer {
+    /// Create a server publishing every tool in `tools`
+    pub fn new(name: &str, version: &str, tools: ToolRegistry) -> Self {
+        Self {
+            info: Implementation {
+                name: name.to_string(),
+                version: version.to_string(),
+            },
+            instructions: None,
+            tools,
+            resources: Vec::new(),
+        }
+    }
+
+    /// Set the instructions returned from `initialize`
+    pub fn with_instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    /// Add a resource provider
+    pub fn with_resource_provider(mut self, provider: Arc<dyn McpResourceProvider>) -> Self {
+        self.resources.push(provider);
+        self
+    }
+
+    /// Tool definitions as published by `tools/list`
+    pub async fn tool_definitions(&self) -> Vec<McpTool> {
+        self.tools
+            .tools()
+            .await
+            .into_iter()
+            .map(|tool| McpTool {
+                name: tool.name().to_string(),
+                description: Some(tool.description().to_string()),
+                input_schema: tool.parameters(),
+            })
+            .collect()
+    }
+
+    /// Answer one raw JSON-RPC message.
+    ///
+    /// Returns `None` for notifications and responses, which need no reply.
+    pub async fn handle_value(&self, value: Value) -> Option<Value> {
+        let message = match serde_json::from_value::<JsonRpcMessage>(value) {
+            Ok(message) => message,
+            Err(e) => return Some(invalid_request(format!("Invalid request: {}", e))),
+        };
+        let response = self.handle_message(message).await?;
+        serde_json::to_value(response).ok()
+    }
+
+    /// Answer one parsed JSON-RPC message
+    pub async fn handle_message(&self, message: JsonRpcMessage) -> Option<JsonRpcResponse> {
+        match message {
+            JsonRpcMessage::Request(request) => Some(self.handle_request(request).await),
+            JsonRpcMessage::Notification(notification) => {
+                debug!("MCP client notification: {}", notification.method);
+                None
+            }
+            JsonRpcMessage::Response(response) => {
+                debug!("Ignoring client response {}", response.id);
+                None
+            }
+        }
+    }
+
+    /// Dispatch a request to the matching MCP method
+    pub async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let id = request.id.clone();
+        let params = request.params.unwrap_or(Value::Null);
+        let result = match request.method.as_str() {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools().await),
+            "tools/call" => self.call_tool(&params).await,
+            "resources/list" => self.list_resources().await,
+            "resources/read" => self.read_resource(&params).await,
+            "resources/templates/list" => Ok(json!({ "resourceTemplates": [] })),
+            other => Err((
+                error_codes::METHOD_NOT_FOUND,
+                format!("Method not found: {}", other),
+            )),
+        };
+
+        match result {
+            Ok(value) => JsonRpcResponse::success(id, value),
+            Err((code, message)) => JsonRpcResponse::failure(id, code, message),
+        }
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params
+            .get("protocolVersion")
+            .and_then(Value::as_str)
+            .unwrap_or(PROTOCOL_VERSION);
+        let protocol_version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+            requested
+        } else {
+            PROTOCOL_VERSION
+        };
+
+        let mut capabilities = json!({ "tools": { "listChanged": false } });
+        if !self.resources.is_empty() {
+            capabilities["resources"] = json!({ "subscribe": false, "listChanged": false });
+        }
+
+        let result = InitializeResult {
+            protocol_version: protocol_version.to_string(),
+            capabilities,
+            server_info: self.info.clone(),
+            instructions: self.instructions.clone(),
+        };
+        serde_json::to_value(result).unwrap_or(Value::Null)
+    }
+
+    async fn list_tools(&self) -> Value {
+        let result = ListToolsResult {
+            tools: self.tool_definitions().await,
+            next_cursor: None,
+        };
+        serde_json::to_value(result).unwrap_or(Value::Null)
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((error_codes::INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let tool = self.tools.get(name).await.ok_or((
+            error_codes::INVALID_PARAMS,
+            format!("Unknown tool: {}", name),
+        ))?;
+        let args = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        let result = match tool.call(&EmptyContext, args).await {
+            Ok(value) => tool_output(value),
+            Err(e) => CallToolResult {
+                content: vec![json!({ "type": "text", "text": e.to_string() })],
+                structured_content: None,
+                is_error: true,
+            },
+        };
+        serde_json::to_value(result).map_err(|e| (error_codes::INTERNAL_ERROR, format!("{}", e)))
+    }
+
+    async fn list_resources(&self) -> Result<Value, (i64, String)> {
+        let mut resources = Vec::new();
+        for provider in &self.resources {
+            resources.extend(provider.list_resources().await.map_err(internal)?);
+        }
+        serde_json::to_value(ListResourcesResult {
+            resources,
+            next_cursor: None,
+        })
+        .map_err(|e| (error_codes::INTERNAL_ERROR, e.to_string()))
+    }
+
+    async fn read_resource(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params.get("uri").and_then(Value::as_str).ok_or((
+            error_codes::INVALID_PARAMS,
+            "Missing resource uri".to_string(),
+        ))?;
+        let provider = self
+            .resources
+            .iter()
+            .find(|provider| provider.handles(uri))
+            .ok_or((
+                error_codes::RESOURCE_NOT_FOUND,
+                format!("Resource not found: {}", uri),
+            ))?;
+        let contents = provider.read_resource(uri).await.map_err(internal)?;
+        serde_json::to_value(ReadResourceResult { contents })
+            .map_err(|e| (error_codes::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Serve newline-delimited JSON-RPC until `reader` reaches EOF.
+    ///
+    /// Requests run concurrently; `notifications/cancelled` aborts the
+    /// matching in-flight request.
+    pub async fn serve<R, W>(self: Arc<Self>, reader: R, writer: W) -> Result<(), McpError>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer = Arc::new(Mutex::new(writer));
+        let in_flight: Arc<StdMutex<HashMap<RequestId, AbortHandle>>> = Arc::default();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| McpError::Connection(format!("read failed: {}", e)))?
+        {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let message = match serde_json::from_str::<Value>(line) {
+                Ok(value) => serde_json::from_value::<JsonRpcMessage>(value)
+                    .map_err(|e| invalid_request(format!("Invalid request: {}", e))),
+                Err(e) => Err(json!({
+                    "jsonrpc": JSONRPC_VERSION,
+                    "id": null,
+                    "error": { "code": error_codes::PARSE_ERROR, "message": e.to_string() }
+                })),
+            };
+
+            let request = match message {
+                Ok(JsonRpcMessage::Request(request)) => request,
+                Ok(JsonRpcMessage::Notification(notification)) => {
+                    cancel_in_flight(&in_flight, &notification);
+                    continue;
+                }
+                Ok(JsonRpcMessage::Response(_)) => continue,
+                Err(error) => {
+                    write_line(&writer, &error).await?;
+                    continue;
+                }
+            };
+
+            let id = request.id.clone();
+            let server = self.clone();
+            let task_writer = writer.clone();
+            let task_in_flight = in_flight.clone();
+            let (registered_tx, registered_rx) = oneshot::channel::<()>();
+            let handle = tokio::spawn(async move {
+                // Wait until the abort handle is registered so cleanup below always finds it.
+                let _ = registered_rx.await;
+                let task_id = request.id.clone();
+                let response = server.handle_request(request).await;
+                if let Ok(mut map) = task_in_flight.lock() {
+                    map.remove(&task_id);
+                }
+                if let Err(e) = write_line(&task_writer, &response).await {
+                    warn!("Failed to write MCP response {}: {}", task_id, e);
+                }
+            });
+            if let Ok(mut map) = in_flight.lock() {
+                map.insert(id, handle.abort_handle());
+            }
+            let _ = registered_tx.send(());
+        }
+
+        Ok(())
+    }
+
+    /// Serve over this process' stdin/stdout
+    pub async fn serve_stdio(self: Arc<Self>) -> Result<(), McpError> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+}
+
+/// Map a tool's JSON output to MCP content
+fn tool_output(value: Value) -> CallToolResult {
+    match value {
+        Value::String(text) => CallToolResult {
+            content: vec![json!({ "type": "text", "text": text })],
+            structured_content: None,
+            is_error: false,
+        },
+        other => CallToolResult {
+            content: vec![json!({
+                "type": "text",
+                "text": serde_json::to_string_pretty(&other).unwrap_or_default()
+            })],
+            structured_content: other.is_object().then_some(other),
+            is_error: false,
+        },
+    }
+}
+
+fn internal(e: McpError) -> (i64, String) {
+    (error_codes::INTERNAL_ERROR, e.to_string())
+}
+
+fn invalid_request(message: String) -> Value {
+    json!({
+        "jsonrpc": JSONRPC_VERSION,
+        "id": null,
+        "error": { "code": error_codes::INVALID_REQUEST, "message": message }
+    })
+}
+
+fn cancel_in_flight(
+    in_flight: &StdMutex<HashMap<RequestId, AbortHandle>>,
+    notification: &JsonRpcNotification,
+) {
+    if notification.method != "notifications/cancelled" {
+        debug!("MCP client notification: {}", notification.method);
+        return;
+    }
+    let request_id = notification
+        .params
+        .as_ref()
+        .and_then(|p| p.get("requestId"))
+        .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+    if let Some(id) = request_id {
+        if let Some(handle) = in_flight.lock().ok().and_then(|mut map| map.remove(&id)) {
+            debug!("Cancelling MCP request {}", id);
+            handle.abort();
+        }
+    }
+}
+
+async fn write_line<W>(writer: &Mutex<W>, message: &impl serde::Serialize) -> Result<(), McpError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut line = serde_json::to_string(message)
+        .map_err(|e| McpError::InvalidResponse(format!("failed to encode message: {}", e)))?;
+    line.push('\n');
+
+    let mut writer = writer.lock().await;
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| McpError::Connection(format!("write failed: {}", e)))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| McpError::Connection(format!("flush failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synapse_agentic::prelude::{Tool, ToolContext};
+
+    struct UpperTool;
+
+    #[async_trait]
+    impl Tool for UpperTool {
+        fn name(&self) -> &str {
+            "upper"
+        }
+        fn description(&self) -> &str {
+            "Uppercase text"
+        }
+        fn parameters(&self) -> Value {
+            json!({ "type": "object", "properties": { "text": { "type": "string" } } })
+        }
+        async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+            match args.get("text").and_then(Value::as_str) {
+                Some(text) => Ok(json!({ "text": text.to_uppercase() })),
+                None => anyhow::bail!("Missing 'text' parameter"),
+            }
+        }
+    }
+
+    async fn server() -> McpServer {
+        let registry = ToolRegistry::new();
+        registry.register_tool(UpperTool).await;
+        McpServer::new("test", "0.0.0", registry)
+    }
+
+    #[tokio::test]
+    async fn test_tools_call_maps_errors_to_is_error() {
+        let server = server().await;
+
+        let ok = server
+            .handle_value(json!({
+                "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                "params": { "name": "upper", "arguments": { "text": "hi" } }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(ok["result"]["structuredContent"]["text"], "HI");
+        assert_eq!(ok["result"]["isError"], false);
+
+        let failed = server
+            .handle_value(json!({
+                "jsonrpc": "2.0", "id": 2, "method": "tools/call",
+                "params": { "name": "upper", "arguments": {} }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(failed["result"]["isError"], true);
+
+        let unknown = server
+            .handle_value(json!({
+                "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                "params": { "name": "nope" }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(unknown["error"]["code"], error_codes::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_get_no_reply() {
+        let server = server().await;
+        let reply = server
+            .handle_value(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        assert!(reply.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resources_without_providers() {
+        let server = server().await;
+        let init = server
+            .handle_value(json!({
+                "jsonrpc": "2.0", "id": 1, "method": "initialize",
+                "params": { "protocolVersion": "1999-01-01" }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(init["result"]["capabilities"].get("resources").is_none());
+
+        let read = server
+            .handle_value(json!({
+                "jsonrpc": "2.0", "id": 2, "method": "resources/read",
+                "params": { "uri": "gestalt://missing" }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(read["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+    }
+}
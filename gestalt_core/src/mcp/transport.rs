@@ -0,0 +1,408 @@
+//! MCP Transports
+//!
+//! Stdio (newline-delimited JSON-RPC) and streamable HTTP (JSON or SSE replies).
+
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use synapse_agentic::stream::SseDecoder;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::client_impl::McpError;
+use super::protocol::{
+    error_codes, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, RequestId,
+};
+
+/// A bidirectional JSON-RPC channel to an MCP server
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// Send a request and wait for the matching response
+    async fn request(&self, request: JsonRpcRequest) -> Result<JsonRpcResponse, McpError>;
+
+    /// Send a notification
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<(), McpError>;
+
+    /// Close the channel and release its resources
+    async fn close(&self) -> Result<(), McpError>;
+
+    /// Record the protocol revision agreed during `initialize`
+    fn set_protocol_version(&self, _version: &str) {}
+}
+
+type PendingMap = Arc<StdMutex<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>>;
+type SharedWriter = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// Removes a pending request slot if the waiting future is dropped
+struct PendingGuard {
+    pending: PendingMap,
+    id: RequestId,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+/// Newline-delimited JSON-RPC over a child process' stdin/stdout
+pub struct StdioTransport {
+    writer: SharedWriter,
+    pending: PendingMap,
+    closed: Arc<AtomicBool>,
+    reader_task: JoinHandle<()>,
+    child: Mutex<Option<Child>>,
+}
+
+impl StdioTransport {
+    /// Spawn an MCP server process and talk to it over stdio
+    pub async fn spawn(
+        command: &str,
+        args: &[String],
+        env: Option<&HashMap<String, String>>,
+    ) -> Result<Self, McpError> {
+        let mut cmd = tokio::process::Command::new(command);
+        cmd.args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(env) = env {
+            cmd.envs(env);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| McpError::Connection(format!("failed to spawn '{}': {}", command, e)))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| McpError::Connection("child stdin unavailable".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| McpError::Connection("child stdout unavailable".to_string()))?;
+
+        // Servers log on stderr; keep it out of the terminal but visible in traces.
+        if let Some(stderr) = child.stderr.take() {
+            let name = command.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("[mcp:{}] {}", name, line);
+                }
+            });
+        }
+
+        let transport = Self::from_streams(stdout, stdin);
+        *transport.child.lock().await = Some(child);
+        Ok(transport)
+    }
+
+    /// Build a transport over arbitrary streams (used for in-process servers and tests)
+    pub fn from_streams<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(writer)));
+        let pending: PendingMap = Arc::new(StdMutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let reader_task = tokio::spawn(read_loop(
+            reader,
+            writer.clone(),
+            pending.clone(),
+            closed.clone(),
+        ));
+
+        Self {
+            writer,
+            pending,
+            closed,
+            reader_task,
+            child: Mutex::new(None),
+        }
+    }
+
+    async fn write_message(&self, message: &impl serde::Serialize) -> Result<(), McpError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(McpError::Connection("transport closed".to_string()));
+        }
+        write_line(&self.writer, message).await
+    }
+}
+
+async fn write_line(
+    writer: &SharedWriter,
+    message: &impl serde::Serialize,
+) -> Result<(), McpError> {
+    let mut line = serde_json::to_string(message)
+        .map_err(|e| McpError::InvalidResponse(format!("failed to encode message: {}", e)))?;
+    line.push('\n');
+
+    let mut writer = writer.lock().await;
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| McpError::Connection(format!("write failed: {}", e)))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| McpError::Connection(format!("flush failed: {}", e)))
+}
+
+async fn read_loop<R>(reader: R, writer: SharedWriter, pending: PendingMap, closed: Arc<AtomicBool>)
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("MCP stdio read failed: {}", e);
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let message: JsonRpcMessage = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => {
+                debug!("Ignoring non JSON-RPC line from MCP server: {}", line);
+                continue;
+            }
+        };
+
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let sender = pending.lock().ok().and_then(|mut p| p.remove(&response.id));
+                match sender {
+                    Some(tx) => {
+                        let _ = tx.send(response);
+                    }
+                    None => debug!("Dropping response for unknown request {}", response.id),
+                }
+            }
+            JsonRpcMessage::Request(request) => {
+                let reply = server_request_reply(&request);
+                if let Err(e) = write_line(&writer, &reply).await {
+                    warn!(
+                        "Failed to answer server request '{}': {}",
+                        request.method, e
+                    );
+                }
+            }
+            JsonRpcMessage::Notification(notification) => {
+                debug!("MCP server notification: {}", notification.method);
+            }
+        }
+    }
+
+    closed.store(true, Ordering::SeqCst);
+    // Dropping the senders wakes every waiter with a closed-channel error.
+    if let Ok(mut pending) = pending.lock() {
+        pending.clear();
+    }
+}
+
+/// Answer requests the server sends to the client.
+fn server_request_reply(request: &JsonRpcRequest) -> JsonRpcResponse {
+    match request.method.as_str() {
+        "ping" => JsonRpcResponse::success(request.id.clone(), Value::Object(Default::default())),
+        other => JsonRpcResponse::failure(
+            request.id.clone(),
+            error_codes::METHOD_NOT_FOUND,
+            format!("Client does not support '{}'", other),
+        ),
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, request: JsonRpcRequest) -> Result<JsonRpcResponse, McpError> {
+        let (tx, rx) = oneshot::channel();
+        let guard = PendingGuard {
+            pending: self.pending.clone(),
+            id: request.id.clone(),
+        };
+        self.pending
+            .lock()
+            .map_err(|_| McpError::Connection("pending map poisoned".to_string()))?
+            .insert(request.id.clone(), tx);
+
+        self.write_message(&request).await?;
+
+        let response = rx
+            .await
+            .map_err(|_| McpError::Connection("server closed the connection".to_string()));
+        drop(guard);
+        response
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<(), McpError> {
+        self.write_message(&notification).await
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.closed.store(true, Ordering::SeqCst);
+        self.reader_task.abort();
+        let _ = self.writer.lock().await.shutdown().await;
+
+        if let Some(mut child) = self.child.lock().await.take() {
+            let _ = child.start_kill();
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(2), child.wait()).await;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StdioTransport {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+/// Streamable HTTP transport: every message is POSTed to a single endpoint and
+/// the server answers with JSON or an SSE stream carrying the response.
+pub struct HttpTransport {
+    client: reqwest::Client,
+    endpoint: String,
+    session_id: StdMutex<Option<String>>,
+    protocol_version: StdMutex<Option<String>>,
+}
+
+impl HttpTransport {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.to_string(),
+            session_id: StdMutex::new(None),
+            protocol_version: StdMutex::new(None),
+        }
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id.lock().ok().and_then(|s| s.clone())
+    }
+
+    async fn post(&self, body: &impl serde::Serialize) -> Result<reqwest::Response, McpError> {
+        let mut req = self
+            .client
+            .post(&self.endpoint)
+            .header("Accept", "application/json, text/event-stream")
+            .json(body);
+        if let Some(session) = self.session_id() {
+            req = req.header("Mcp-Session-Id", session);
+        }
+        if let Some(version) = self.protocol_version.lock().ok().and_then(|v| v.clone()) {
+            req = req.header("MCP-Protocol-Version", version);
+        }
+
+        let response = req
+            .send()
+            .await
+            .map_err(|e| McpError::Connection(format!("HTTP request failed: {}", e)))?;
+
+        if let Some(session) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            if let Ok(mut slot) = self.session_id.lock() {
+                *slot = Some(session.to_string());
+            }
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(McpError::Connection(format!(
+                "MCP server returned HTTP {}: {}",
+                status, text
+            )));
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, request: JsonRpcRequest) -> Result<JsonRpcResponse, McpError> {
+        let mut response = self.post(&request).await?;
+
+        let is_sse = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.starts_with("text/event-stream"))
+            .unwrap_or(false);
+
+        if !is_sse {
+            return response
+                .json::<JsonRpcResponse>()
+                .await
+                .map_err(|e| McpError::InvalidResponse(format!("invalid JSON-RPC body: {}", e)));
+        }
+
+        let mut decoder = SseDecoder::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| McpError::Connection(format!("SSE stream failed: {}", e)))?
+        {
+            for event in decoder.push(&chunk) {
+                match serde_json::from_str::<JsonRpcMessage>(&event.data) {
+                    Ok(JsonRpcMessage::Response(resp)) if resp.id == request.id => return Ok(resp),
+                    Ok(JsonRpcMessage::Notification(n)) => {
+                        debug!("MCP server notification: {}", n.method)
+                    }
+                    Ok(_) => {}
+                    Err(_) => debug!("Ignoring SSE event: {}", event.data),
+                }
+            }
+        }
+
+        Err(McpError::InvalidResponse(format!(
+            "SSE stream ended without a response to request {}",
+            request.id
+        )))
+    }
+
+    async fn notify(&self, notification: JsonRpcNotification) -> Result<(), McpError> {
+        self.post(&notification).await.map(|_| ())
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        if let Some(session) = self.session_id() {
+            // Best effort: servers may not support explicit session termination.
+            let _ = self
+                .client
+                .delete(&self.endpoint)
+                .header("Mcp-Session-Id", session)
+                .send()
+                .await;
+        }
+        Ok(())
+    }
+
+    fn set_protocol_version(&self, version: &str) {
+        if let Ok(mut slot) = self.protocol_version.lock() {
+            *slot = Some(version.to_string());
+        }
+    }
+}
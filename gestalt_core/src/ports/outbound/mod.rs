@@ -1,3 +1,6 @@
 pub mod mcp_client;
 pub mod repo_manager;
+pub mod sandbox;
 pub mod vfs;
+pub mod vfs_journal;
+pub mod vfs_layer;
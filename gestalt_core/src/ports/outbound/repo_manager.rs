@@ -37,4 +37,11 @@ pub trait VectorDb: Send + Sync {
         vector: Vec<f32>,
         limit: usize,
     ) -> anyhow::Result<Vec<ScoredResult>>;
+    /// BM25 search over the identifier-aware tokens of `metadata.content`.
+    async fn search_lexical(
+        &self,
+        collection: &str,
+        query: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScoredResult>>;
 }
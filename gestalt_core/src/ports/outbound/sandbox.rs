@@ -0,0 +1,1636 @@
+//! Sandboxed command execution.
+//!
+//! A [`CommandSandbox`] runs agent-issued shell commands under a
+//! [`SandboxPolicy`]: an allow-list of binaries, CPU, memory and wall-clock
+//! limits, a scrubbed environment and, on Linux, private user, mount and
+//! network namespaces with a read-only root file system.
+//!
+//! When a request carries a [`SandboxWorkspace`], the workspace is
+//! materialized from the VFS into a scratch directory the command may write
+//! to (mounted over the workspace path by the namespace sandbox). After the
+//! command exits, everything it created, changed or removed is written back
+//! through the VFS, so commands see pending overlay changes and their own
+//! changes stay pending until the overlay is flushed.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::vfs::{rebase_path, tree, EntryKind, PendingChange, VirtualFileSystem};
+
+/// Programs allowed by [`SandboxPolicy::default`].
+///
+/// Only tools that cannot start other programs: the allow-list checks the
+/// programs the shell starts, so anything able to run code (`find -exec`,
+/// `awk` and `sed` scripts, `rg --pre`, `sort --compress-program`,
+/// interpreters, build tools and test runners) would bypass it. Allow those
+/// explicitly with `GESTALT_SANDBOX_ALLOW`.
+pub const DEFAULT_ALLOWED_BINARIES: &[&str] = &[
+    "basename", "cat", "cp", "cut", "date", "diff", "dirname", "du", "file", "grep", "head", "ls",
+    "mkdir", "mv", "pwd", "rm", "rmdir", "rustfmt", "stat", "tail", "touch", "tr", "tree", "uniq",
+    "wc", "which",
+];
+
+/// Shell builtins and keywords that are always allowed.
+const SHELL_BUILTINS: &[&str] = &[
+    ":", "[", "cd", "echo", "exit", "export", "false", "printf", "pwd", "read", "return", "set",
+    "shift", "test", "true", "unset", "wait",
+];
+
+/// Words after which the next word is still in command position.
+const COMMAND_PREFIXES: &[&str] = &[
+    "!", "{", "}", "if", "then", "else", "elif", "fi", "while", "until", "do", "done", "esac",
+    "exec", "command", "time",
+];
+
+/// Environment variables passed through to sandboxed commands.
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+];
+
+/// Workspace paths that are not materialized by default.
+const DEFAULT_EXCLUDED: &[&str] = &[".git", ".gestalt", "target", "node_modules"];
+
+/// What a sandboxed command may do.
+#[derive(Debug, Clone)]
+pub struct SandboxPolicy {
+    /// Programs a command line may start; `None` allows any program.
+    pub allowed_binaries: Option<Vec<String>>,
+    /// Keep the host network; otherwise commands only see a down loopback.
+    pub network: bool,
+    /// Wall-clock limit for foreground commands.
+    pub timeout: Duration,
+    /// CPU time limit (`RLIMIT_CPU`).
+    pub cpu_time: Option<Duration>,
+    /// Data segment limit in bytes (`RLIMIT_DATA`, `RLIMIT_AS` off Linux).
+    pub memory_bytes: Option<u64>,
+    /// Captured bytes per output stream; the rest is dropped.
+    pub max_output_bytes: usize,
+    /// Host paths mounted writable into the namespace sandbox.
+    pub writable_paths: Vec<PathBuf>,
+    /// Workspace-relative paths that are not materialized.
+    pub excluded: Vec<PathBuf>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_binaries: Some(
+                DEFAULT_ALLOWED_BINARIES
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect(),
+            ),
+            network: false,
+            timeout: Duration::from_secs(120),
+            cpu_time: Some(Duration::from_secs(120)),
+            memory_bytes: Some(4 << 30),
+            max_output_bytes: 1 << 20,
+            writable_paths: Vec::new(),
+            excluded: DEFAULT_EXCLUDED.iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Default policy adjusted by `GESTALT_SANDBOX_*` environment variables:
+    /// `ALLOW` (comma separated, `*` for any), `NETWORK`, `TIMEOUT_SECS`,
+    /// `CPU_SECS` and `MEMORY_MB` (0 disables) and `WRITABLE` (a path list).
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(format!("GESTALT_SANDBOX_{}", name)).ok();
+        let number = |name: &str| var(name).and_then(|v| v.trim().parse::<u64>().ok());
+        let mut policy = Self::default();
+        if let Some(allow) = var("ALLOW") {
+            policy.allowed_binaries = (allow.trim() != "*").then(|| {
+                allow
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|b| !b.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            });
+        }
+        if let Some(network) = var("NETWORK") {
+            policy.network = matches!(network.trim(), "1" | "true" | "yes");
+        }
+        if let Some(secs) = number("TIMEOUT_SECS") {
+            policy.timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = number("CPU_SECS") {
+            policy.cpu_time = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        if let Some(mb) = number("MEMORY_MB") {
+            policy.memory_bytes = (mb > 0).then_some(mb << 20);
+        }
+        if let Some(paths) = var("WRITABLE") {
+            policy.writable_paths = std::env::split_paths(&paths).collect();
+        }
+        policy
+    }
+
+    /// Rejects command lines that start a program outside the allow-list.
+    ///
+    /// Only the programs the shell itself starts are checked; command
+    /// substitution is refused because it would hide them.
+    ///
+    /// ```
+    /// # use gestalt_core::ports::outbound::sandbox::SandboxPolicy;
+    /// let policy = SandboxPolicy::default();
+    /// assert!(policy.check_command("cargo test && git status").is_err());
+    /// assert!(policy.check_command("cd src && grep -rn todo . 2>&1 | tail -n 5").is_ok());
+    /// ```
+    pub fn check_command(&self, command: &str) -> Result<()> {
+        let programs = command_programs(command)?;
+        let Some(allowed) = &self.allowed_binaries else {
+            return Ok(());
+        };
+        for program in programs {
+            if !SHELL_BUILTINS.contains(&program.as_str()) && !allowed.contains(&program) {
+                bail!("'{}' is not in the sandbox allow-list", program);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Programs started by a shell command line, in order.
+pub fn command_programs(command: &str) -> Result<Vec<String>> {
+    if command.contains('`') || command.contains("$(") {
+        bail!("command substitution is not allowed in sandboxed commands");
+    }
+    let mut programs = Vec::new();
+    for words in simple_commands(command)? {
+        let program = words
+            .into_iter()
+            .find(|w| !is_assignment(w) && !COMMAND_PREFIXES.contains(&w.as_str()));
+        if let Some(program) = program {
+            if !matches!(program.as_str(), "for" | "case" | "select") {
+                programs.push(program);
+            }
+        }
+    }
+    Ok(programs)
+}
+
+/// Splits a shell command line into simple commands (at `;`, `&`, `|`,
+/// newlines, parentheses and backticks) and those into words with quotes and
+/// escapes removed; redirections like `2>&1` stay one word. Good enough to
+/// find programs and their arguments, not a shell parser.
+///
+/// ```
+/// # use gestalt_core::ports::outbound::sandbox::simple_commands;
+/// let commands = simple_commands("FOO=1 cargo test 2>&1 | grep 'a b'").unwrap();
+/// assert_eq!(commands, [vec!["FOO=1", "cargo", "test", "2>&1"], vec!["grep", "a b"]]);
+/// ```
+pub fn simple_commands(command: &str) -> Result<Vec<Vec<String>>> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    // `None` between words, so that `''` still makes an (empty) word.
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                word.get_or_insert_with(String::new).extend(chars.next())
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                word.get_or_insert_with(String::new);
+                quote = Some(c);
+            }
+            (None, ' ' | '\t') => words.extend(word.take()),
+            (None, '&')
+                if word.as_deref().is_some_and(|w| w.ends_with(['>', '<']))
+                    || chars.peek() == Some(&'>') =>
+            {
+                word.get_or_insert_with(String::new).push(c)
+            }
+            (None, ';' | '&' | '|' | '\n' | '(' | ')' | '`') => {
+                words.extend(word.take());
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        bail!("unterminated quote in command");
+    }
+    words.extend(word.take());
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    Ok(commands)
+}
+
+/// Whether `word` is a `NAME=value` assignment, which sets the environment of
+/// the program that follows it.
+pub fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Workspace a command runs against, read from and written back to `vfs`.
+#[derive(Clone)]
+pub struct SandboxWorkspace {
+    pub vfs: Arc<dyn VirtualFileSystem>,
+    /// Absolute workspace root.
+    pub root: PathBuf,
+    /// Owner recorded for the changes written back.
+    pub owner: String,
+}
+
+/// A shell command to run in a sandbox.
+#[derive(Clone)]
+pub struct SandboxRequest {
+    pub command: String,
+    /// Working directory; defaults to the workspace root or the current directory.
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub workspace: Option<SandboxWorkspace>,
+    /// Background jobs are not bound by the policy's wall-clock timeout.
+    pub background: bool,
+}
+
+impl SandboxRequest {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            cwd: None,
+            env: Vec::new(),
+            workspace: None,
+            background: false,
+        }
+    }
+
+    pub fn in_dir(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Materialize `root` from `vfs` for the command and write its changes back as `owner`.
+    pub fn with_workspace(
+        mut self,
+        vfs: Arc<dyn VirtualFileSystem>,
+        root: impl AsRef<Path>,
+        owner: impl Into<String>,
+    ) -> Self {
+        let root = root.as_ref();
+        let root = match std::env::current_dir() {
+            Ok(cwd) if root.is_relative() => cwd.join(root),
+            _ => root.to_path_buf(),
+        };
+        self.workspace = Some(SandboxWorkspace {
+            vfs,
+            root,
+            owner: owner.into(),
+        });
+        self
+    }
+
+    pub fn background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+}
+
+/// Resource usage of a finished command and its waited-for children.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ResourceUsage {
+    pub wall_ms: u64,
+    pub user_ms: u64,
+    pub system_ms: u64,
+    pub max_rss_kib: u64,
+}
+
+/// Result of a sandboxed command.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SandboxOutput {
+    pub exit_code: i32,
+    /// Signal that terminated the command, if any.
+    pub signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Output beyond the policy's limit was dropped.
+    pub truncated: bool,
+    pub timed_out: bool,
+    pub killed: bool,
+    pub usage: ResourceUsage,
+    /// Workspace files the command created or changed, now pending in the VFS.
+    pub changed_files: Vec<PathBuf>,
+    /// Workspace paths the command removed, now pending in the VFS.
+    pub removed_files: Vec<PathBuf>,
+    /// Changes that could not be written back to the VFS.
+    pub sync_errors: Vec<String>,
+}
+
+impl SandboxOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0 && !self.timed_out && !self.killed && self.sync_errors.is_empty()
+    }
+}
+
+/// A running sandboxed command.
+///
+/// Dropping it without waiting kills the command and discards its changes,
+/// so an abandoned command never syncs back into the workspace.
+pub struct SandboxedProcess {
+    id: Option<u32>,
+    kill: Option<oneshot::Sender<()>>,
+    done: Option<JoinHandle<Result<SandboxOutput>>>,
+}
+
+impl SandboxedProcess {
+    pub fn id(&self) -> Option<u32> {
+        self.id
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Waits for the command; changes are written back to the workspace first.
+    ///
+    /// `self` lives in the returned future, so dropping the future before the
+    /// command exits (e.g. on cancellation) kills it.
+    pub async fn wait(mut self) -> Result<SandboxOutput> {
+        let done = self
+            .done
+            .take()
+            .context("sandboxed command already waited for")?;
+        done.await.context("sandbox waiter panicked")?
+    }
+
+    /// Kills the command (and its process group); its changes are discarded.
+    pub async fn kill(mut self) -> Result<SandboxOutput> {
+        if let Some(kill) = self.kill.take() {
+            let _ = kill.send(());
+        }
+        self.wait().await
+    }
+}
+
+impl Drop for SandboxedProcess {
+    fn drop(&mut self) {
+        if let Some(kill) = self.kill.take() {
+            let _ = kill.send(());
+        }
+    }
+}
+
+/// Kind of isolation a sandbox provides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxKind {
+    /// Private user, mount and network namespaces with a read-only root.
+    Namespace,
+    /// Limits and allow-list only; the command runs on the host.
+    Host,
+}
+
+/// Port for running shell commands in isolation.
+#[async_trait]
+pub trait CommandSandbox: Send + Sync {
+    fn kind(&self) -> SandboxKind;
+    fn policy(&self) -> &SandboxPolicy;
+
+    /// Starts a command without waiting for it.
+    async fn spawn(&self, request: SandboxRequest) -> Result<SandboxedProcess>;
+
+    /// Runs a command to completion.
+    async fn run(&self, request: SandboxRequest) -> Result<SandboxOutput> {
+        self.spawn(request).await?.wait().await
+    }
+}
+
+/// Runs commands directly on the host, with limits but without isolation.
+///
+/// Workspace commands run inside the materialized copy, but absolute paths
+/// still reach the real file system.
+#[derive(Debug, Clone, Default)]
+pub struct HostSandbox {
+    policy: SandboxPolicy,
+}
+
+impl HostSandbox {
+    pub fn new(policy: SandboxPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+#[async_trait]
+impl CommandSandbox for HostSandbox {
+    fn kind(&self) -> SandboxKind {
+        SandboxKind::Host
+    }
+
+    fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+
+    async fn spawn(&self, request: SandboxRequest) -> Result<SandboxedProcess> {
+        spawn_shell(&self.policy, request, false).await
+    }
+}
+
+/// Runs commands in Linux user, mount and network namespaces, bubblewrap
+/// style: the root file system is remounted read-only, `/tmp` is a private
+/// tmpfs and only the materialized workspace and the policy's writable paths
+/// can be written.
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceSandbox {
+    policy: SandboxPolicy,
+}
+
+impl NamespaceSandbox {
+    pub fn new(policy: SandboxPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Whether unprivileged user namespaces can be created here.
+    pub fn is_supported() -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            static SUPPORTED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+            *SUPPORTED.get_or_init(linux::probe)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            false
+        }
+    }
+}
+
+#[async_trait]
+impl CommandSandbox for NamespaceSandbox {
+    fn kind(&self) -> SandboxKind {
+        SandboxKind::Namespace
+    }
+
+    fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+
+    async fn spawn(&self, request: SandboxRequest) -> Result<SandboxedProcess> {
+        spawn_shell(&self.policy, request, true).await
+    }
+}
+
+/// Sandbox selected by `GESTALT_SANDBOX` (`namespace` or `host`); defaults
+/// to namespaces. Fails when namespaces are unavailable, unless the host
+/// sandbox was requested explicitly.
+pub fn default_sandbox(policy: SandboxPolicy) -> Result<Arc<dyn CommandSandbox>> {
+    let requested = std::env::var("GESTALT_SANDBOX").unwrap_or_default();
+    match requested.trim() {
+        "host" => Ok(Arc::new(HostSandbox::new(policy))),
+        "" | "namespace" if NamespaceSandbox::is_supported() => {
+            Ok(Arc::new(NamespaceSandbox::new(policy)))
+        }
+        "" | "namespace" => bail!(
+            "user namespaces are unavailable; set GESTALT_SANDBOX=host to run commands \
+             without isolation"
+        ),
+        other => bail!(
+            "unknown GESTALT_SANDBOX '{}' (expected namespace or host)",
+            other
+        ),
+    }
+}
+
+/// [`default_sandbox`], or a [`DisabledSandbox`] refusing every command if
+/// it is unavailable.
+pub fn default_sandbox_or_disabled(policy: SandboxPolicy) -> Arc<dyn CommandSandbox> {
+    default_sandbox(policy.clone()).unwrap_or_else(|e| {
+        warn!("Sandbox unavailable, shell commands are disabled: {:#}", e);
+        Arc::new(DisabledSandbox::new(policy, format!("{:#}", e)))
+    })
+}
+
+/// Refuses every command; stands in when the requested isolation is unavailable.
+#[derive(Debug, Clone)]
+pub struct DisabledSandbox {
+    policy: SandboxPolicy,
+    reason: String,
+}
+
+impl DisabledSandbox {
+    pub fn new(policy: SandboxPolicy, reason: impl Into<String>) -> Self {
+        Self {
+            policy,
+            reason: reason.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandSandbox for DisabledSandbox {
+    /// The isolation it stands in for.
+    fn kind(&self) -> SandboxKind {
+        SandboxKind::Namespace
+    }
+
+    fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+
+    async fn spawn(&self, _request: SandboxRequest) -> Result<SandboxedProcess> {
+        bail!("sandboxed commands are disabled: {}", self.reason)
+    }
+}
+
+async fn spawn_shell(
+    policy: &SandboxPolicy,
+    request: SandboxRequest,
+    isolate: bool,
+) -> Result<SandboxedProcess> {
+    policy.check_command(&request.command)?;
+    let staged = match request.workspace {
+        Some(workspace) => Some(Staged::materialize(workspace, &policy.excluded).await?),
+        None => None,
+    };
+    let cwd = match (request.cwd, &staged) {
+        (Some(cwd), _) => cwd,
+        (None, Some(staged)) => staged.workspace.root.clone(),
+        (None, None) => std::env::current_dir()?,
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("powershell");
+        command.arg("-Command").arg(&request.command);
+        command
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut command = {
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(&request.command);
+        command
+    };
+    command.env_clear();
+    for key in PASSTHROUGH_ENV {
+        if let Ok(value) = std::env::var(key) {
+            command.env(key, value);
+        }
+    }
+    command.envs(request.env);
+    command.stdin(std::process::Stdio::null());
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::piped());
+
+    let timeout = (!request.background).then_some(policy.timeout);
+    if isolate {
+        #[cfg(target_os = "linux")]
+        {
+            let mut binds = Vec::new();
+            if let Some(staged) = &staged {
+                binds.push((
+                    staged.dir.path().to_path_buf(),
+                    staged.workspace.root.clone(),
+                ));
+            }
+            binds.extend(policy.writable_paths.iter().map(|p| (p.clone(), p.clone())));
+            let namespace = linux::Namespace::new(&binds, policy.network, &cwd)?;
+            let setup = unix::ChildSetup::new(policy).with_namespace(namespace);
+            return unix::launch(command, setup, timeout, policy.max_output_bytes, staged);
+        }
+        #[cfg(not(target_os = "linux"))]
+        bail!("the namespace sandbox is only available on Linux");
+    }
+
+    let cwd = match &staged {
+        Some(staged) => rebase_path(&cwd, &staged.workspace.root, staged.dir.path()),
+        None => cwd,
+    };
+    command.current_dir(cwd);
+    #[cfg(unix)]
+    {
+        let setup = unix::ChildSetup::new(policy);
+        unix::launch(command, setup, timeout, policy.max_output_bytes, staged)
+    }
+    #[cfg(not(unix))]
+    {
+        launch_plain(command, timeout, policy.max_output_bytes, staged)
+    }
+}
+
+/// Output of one stream, truncated to `cap` bytes.
+fn read_capped(
+    stream: Option<impl std::io::Read + Send + 'static>,
+    cap: usize,
+) -> JoinHandle<(String, bool)> {
+    tokio::task::spawn_blocking(move || {
+        let Some(mut stream) = stream else {
+            return (String::new(), false);
+        };
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = stream.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let room = cap.saturating_sub(kept.len());
+            kept.extend_from_slice(&buf[..n.min(room)]);
+            truncated |= n > room;
+        }
+        (String::from_utf8_lossy(&kept).into_owned(), truncated)
+    })
+}
+
+/// Fills in the captured output and writes workspace changes back.
+async fn finish(
+    mut output: SandboxOutput,
+    stdout: JoinHandle<(String, bool)>,
+    stderr: JoinHandle<(String, bool)>,
+    staged: Option<Staged>,
+) -> SandboxOutput {
+    let (out, out_truncated) = stdout.await.unwrap_or_default();
+    let (err, err_truncated) = stderr.await.unwrap_or_default();
+    output.stdout = out;
+    output.stderr = err;
+    output.truncated = out_truncated || err_truncated;
+    // A killed command may have left files half-written; its changes are dropped.
+    if let Some(staged) = staged.filter(|_| !output.timed_out && !output.killed) {
+        staged.sync_back(&mut output).await;
+    }
+    output
+}
+
+/// Polling launcher for platforms without `wait4`; no resource usage.
+#[cfg(not(unix))]
+fn launch_plain(
+    mut command: std::process::Command,
+    timeout: Option<Duration>,
+    max_output: usize,
+    staged: Option<Staged>,
+) -> Result<SandboxedProcess> {
+    let mut child = command
+        .spawn()
+        .context("failed to start sandboxed command")?;
+    let id = child.id();
+    let stdout = read_capped(child.stdout.take(), max_output);
+    let stderr = read_capped(child.stderr.take(), max_output);
+    let (kill_tx, mut kill_rx) = oneshot::channel::<()>();
+    let done = tokio::spawn(async move {
+        let started = std::time::Instant::now();
+        let (mut timed_out, mut killed) = (false, false);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if !timed_out && timeout.is_some_and(|t| started.elapsed() >= t) {
+                timed_out = true;
+                let _ = child.kill();
+            }
+            if !killed && kill_rx.try_recv().is_ok() {
+                killed = true;
+                let _ = child.kill();
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        let output = SandboxOutput {
+            exit_code: status.code().unwrap_or(-1),
+            timed_out,
+            killed,
+            usage: ResourceUsage {
+                wall_ms: started.elapsed().as_millis() as u64,
+                ..ResourceUsage::default()
+            },
+            ..SandboxOutput::default()
+        };
+        Ok(finish(output, stdout, stderr, staged).await)
+    });
+    Ok(SandboxedProcess {
+        id: Some(id),
+        kill: Some(kill_tx),
+        done: Some(done),
+    })
+}
+
+/// Workspace copy a command runs in.
+struct Staged {
+    dir: tempfile::TempDir,
+    workspace: SandboxWorkspace,
+    excluded: Vec<PathBuf>,
+    /// VFS key of each materialized file or directory, by relative path.
+    keys: HashMap<PathBuf, PathBuf>,
+    files: HashMap<PathBuf, [u8; 32]>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Staged {
+    async fn materialize(workspace: SandboxWorkspace, excluded: &[PathBuf]) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("gestalt-sandbox-")
+            .tempdir()
+            .context("failed to create the sandbox workspace")?;
+        let mut staged = Self {
+            dir,
+            workspace,
+            excluded: excluded.to_vec(),
+            keys: HashMap::new(),
+            files: HashMap::new(),
+            dirs: HashSet::new(),
+        };
+
+        let root = staged.workspace.root.clone();
+        let vfs = staged.workspace.vfs.clone();
+        let mut entries: HashMap<PathBuf, (EntryKind, PathBuf)> = HashMap::new();
+        let mut stack = vec![root.clone()];
+        while let Some(dir) = stack.pop() {
+            for path in vfs.list(&dir).await? {
+                let Some(rel) = staged.relative(&path) else {
+                    continue;
+                };
+                match vfs.entry_kind(&path).await {
+                    Some(EntryKind::Dir) => {
+                        entries.insert(rel, (EntryKind::Dir, path.clone()));
+                        stack.push(path);
+                    }
+                    Some(EntryKind::File) => {
+                        entries.insert(rel, (EntryKind::File, path));
+                    }
+                    None => {}
+                }
+            }
+        }
+        // Pending changes may be keyed by paths the walk above cannot see,
+        // e.g. relative to the current directory.
+        let cwd = std::env::current_dir()?;
+        for change in vfs.pending_changes().await {
+            let rel_of = |path: &Path| staged.relative(&cwd.join(path));
+            match &change {
+                PendingChange::CreateDir { path } => {
+                    if let Some(rel) = rel_of(path) {
+                        entries.insert(rel, (EntryKind::Dir, path.clone()));
+                    }
+                }
+                PendingChange::WriteFile { path, .. } => {
+                    if let Some(rel) = rel_of(path) {
+                        entries.insert(rel, (EntryKind::File, path.clone()));
+                    }
+                }
+                PendingChange::RemoveFile { path } | PendingChange::RemoveDir { path } => {
+                    if let Some(rel) = rel_of(path) {
+                        entries.retain(|p, _| !p.starts_with(&rel));
+                    }
+                }
+                PendingChange::Rename { from, to } => {
+                    if let Some(rel) = rel_of(from) {
+                        entries.retain(|p, _| !p.starts_with(&rel));
+                    }
+                    let Some(rel) = rel_of(to) else {
+                        continue;
+                    };
+                    match vfs.entry_kind(to).await {
+                        Some(EntryKind::Dir) => {
+                            let (dirs, files) = tree(vfs.as_ref(), to).await?;
+                            let below = dirs
+                                .into_iter()
+                                .map(|dir| (dir, EntryKind::Dir))
+                                .chain(files.into_iter().map(|file| (file, EntryKind::File)));
+                            for (path, kind) in below {
+                                if let Some(rel) = rel_of(&path) {
+                                    entries.insert(rel, (kind, path));
+                                }
+                            }
+                            entries.insert(rel, (EntryKind::Dir, to.clone()));
+                        }
+                        Some(EntryKind::File) => {
+                            entries.insert(rel, (EntryKind::File, to.clone()));
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+
+        for (rel, (kind, key)) in entries {
+            let target = staged.dir.path().join(&rel);
+            match kind {
+                EntryKind::Dir => {
+                    tokio::fs::create_dir_all(&target).await?;
+                    staged.dirs.insert(rel.clone());
+                }
+                EntryKind::File => {
+                    let bytes = vfs.read(&key).await?;
+                    if let Some(parent) = target.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(&target, &bytes).await?;
+                    // Keep executable bits of files that exist on disk.
+                    if let Ok(meta) = tokio::fs::metadata(root.join(&rel)).await {
+                        tokio::fs::set_permissions(&target, meta.permissions()).await?;
+                    }
+                    staged
+                        .files
+                        .insert(rel.clone(), Sha256::digest(&bytes).into());
+                }
+            }
+            staged.keys.insert(rel, key);
+        }
+        Ok(staged)
+    }
+
+    /// Workspace-relative path of `path`, unless it is outside or excluded.
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        let rel = path.strip_prefix(&self.workspace.root).ok()?;
+        let excluded = self.excluded.iter().any(|e| rel.starts_with(e));
+        (!rel.as_os_str().is_empty() && !excluded).then(|| rel.to_path_buf())
+    }
+
+    fn key(&self, rel: &Path) -> PathBuf {
+        self.keys
+            .get(rel)
+            .cloned()
+            .unwrap_or_else(|| self.workspace.root.join(rel))
+    }
+
+    /// Writes what the command changed in the copy back through the VFS.
+    async fn sync_back(self, output: &mut SandboxOutput) {
+        let vfs = self.workspace.vfs.clone();
+        let owner = self.workspace.owner.clone();
+        let mut seen_files = HashSet::new();
+        let mut seen_dirs = HashSet::new();
+
+        for entry in walkdir::WalkDir::new(self.dir.path()).min_depth(1) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    output.sync_errors.push(e.to_string());
+                    continue;
+                }
+            };
+            let Ok(rel) = entry.path().strip_prefix(self.dir.path()) else {
+                continue;
+            };
+            if self.excluded.iter().any(|e| rel.starts_with(e)) {
+                continue;
+            }
+            let rel = rel.to_path_buf();
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                if !self.dirs.contains(&rel) {
+                    if let Err(e) = vfs.create_dir_all(&self.key(&rel)).await {
+                        output.sync_errors.push(format!("{}: {}", rel.display(), e));
+                    }
+                }
+                seen_dirs.insert(rel);
+            } else if file_type.is_file() {
+                let bytes = match tokio::fs::read(entry.path()).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        output.sync_errors.push(format!("{}: {}", rel.display(), e));
+                        continue;
+                    }
+                };
+                let hash: [u8; 32] = Sha256::digest(&bytes).into();
+                if self.files.get(&rel) != Some(&hash) {
+                    let key = self.key(&rel);
+                    match vfs.write(&key, bytes, &owner).await {
+                        Ok(()) => output.changed_files.push(key),
+                        Err(e) => output.sync_errors.push(format!("{}: {}", rel.display(), e)),
+                    }
+                }
+                seen_files.insert(rel);
+            } else {
+                output.sync_errors.push(format!(
+                    "{}: only regular files and directories are kept",
+                    rel.display()
+                ));
+            }
+        }
+
+        let removed_dirs: BTreeSet<_> = self.dirs.difference(&seen_dirs).collect();
+        let under_removed = |rel: &Path| {
+            rel.ancestors()
+                .skip(1)
+                .any(|a| removed_dirs.contains(&a.to_path_buf()))
+        };
+        let mut removed_files: Vec<_> = self
+            .files
+            .keys()
+            .filter(|rel| !seen_files.contains(*rel) && !under_removed(rel))
+            .collect();
+        removed_files.sort();
+        for rel in removed_files {
+            let key = self.key(rel);
+            match vfs.remove_file(&key, &owner).await {
+                Ok(()) => output.removed_files.push(key),
+                Err(e) => output.sync_errors.push(format!("{}: {}", rel.display(), e)),
+            }
+        }
+        for rel in removed_dirs.iter().filter(|rel| !under_removed(rel)) {
+            let key = self.key(rel);
+            match vfs.remove_dir_all(&key, &owner).await {
+                Ok(()) => output.removed_files.push(key),
+                Err(e) => output.sync_errors.push(format!("{}: {}", rel.display(), e)),
+            }
+        }
+        output.changed_files.sort();
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::time::{Duration, Instant};
+
+    use anyhow::{Context, Result};
+    use tokio::sync::oneshot;
+
+    #[cfg(not(target_os = "linux"))]
+    use libc::RLIMIT_AS as RLIMIT_MEMORY;
+    #[cfg(target_os = "linux")]
+    use libc::RLIMIT_DATA as RLIMIT_MEMORY;
+
+    use super::{
+        finish, read_capped, ResourceUsage, SandboxOutput, SandboxPolicy, SandboxedProcess, Staged,
+    };
+
+    pub(super) fn check(ret: libc::c_int) -> io::Result<()> {
+        if ret == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Everything the child does between fork and exec; nothing here allocates.
+    pub(super) struct ChildSetup {
+        cpu: Option<libc::rlimit>,
+        memory: Option<libc::rlimit>,
+        #[cfg(target_os = "linux")]
+        namespace: Option<super::linux::Namespace>,
+    }
+
+    impl ChildSetup {
+        pub(super) fn new(policy: &SandboxPolicy) -> Self {
+            let hard_limit = |resource| {
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: libc::RLIM_INFINITY,
+                };
+                unsafe { libc::getrlimit(resource, &mut current) };
+                current.rlim_max
+            };
+            let limit = |resource, value: u64, slack: u64| {
+                let max = hard_limit(resource);
+                let clamp = |v: u64| {
+                    let v = v as libc::rlim_t;
+                    if max == libc::RLIM_INFINITY {
+                        v
+                    } else {
+                        v.min(max)
+                    }
+                };
+                libc::rlimit {
+                    rlim_cur: clamp(value),
+                    rlim_max: clamp(value.saturating_add(slack)),
+                }
+            };
+            Self {
+                // SIGXCPU at the soft limit, SIGKILL a second later.
+                cpu: policy
+                    .cpu_time
+                    .map(|t| limit(libc::RLIMIT_CPU, t.as_secs().max(1), 1)),
+                memory: policy.memory_bytes.map(|m| limit(RLIMIT_MEMORY, m, 0)),
+                #[cfg(target_os = "linux")]
+                namespace: None,
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        pub(super) fn with_namespace(mut self, namespace: super::linux::Namespace) -> Self {
+            self.namespace = Some(namespace);
+            self
+        }
+
+        fn apply(&self) -> io::Result<()> {
+            // Own process group, so timeouts and kills reach every descendant.
+            check(unsafe { libc::setpgid(0, 0) })?;
+            if let Some(cpu) = &self.cpu {
+                check(unsafe { libc::setrlimit(libc::RLIMIT_CPU, cpu) })?;
+            }
+            if let Some(memory) = &self.memory {
+                check(unsafe { libc::setrlimit(RLIMIT_MEMORY, memory) })?;
+            }
+            #[cfg(target_os = "linux")]
+            if let Some(namespace) = &self.namespace {
+                namespace.enter()?;
+            }
+            Ok(())
+        }
+    }
+
+    pub(super) fn launch(
+        mut command: std::process::Command,
+        setup: ChildSetup,
+        timeout: Option<Duration>,
+        max_output: usize,
+        staged: Option<Staged>,
+    ) -> Result<SandboxedProcess> {
+        unsafe {
+            command.pre_exec(move || setup.apply());
+        }
+        let mut child = command
+            .spawn()
+            .context("failed to start sandboxed command")?;
+        let pid = child.id();
+        let stdout = read_capped(child.stdout.take(), max_output);
+        let stderr = read_capped(child.stderr.take(), max_output);
+        // Reaped by `wait_exit` below, never by `Child`.
+        drop(child);
+
+        let (kill_tx, mut kill_rx) = oneshot::channel::<()>();
+        let done = tokio::spawn(async move {
+            let started = Instant::now();
+            let mut exit = tokio::task::spawn_blocking(move || wait_exit(pid as libc::pid_t));
+            let deadline = async {
+                match timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(deadline);
+            let (mut timed_out, mut killed) = (false, false);
+            let exited = tokio::select! {
+                exited = &mut exit => exited,
+                _ = &mut deadline => {
+                    timed_out = true;
+                    kill_group(pid);
+                    exit.await
+                }
+                Ok(()) = &mut kill_rx => {
+                    killed = true;
+                    kill_group(pid);
+                    exit.await
+                }
+            };
+            let (status, usage) = exited.context("sandbox waiter panicked")??;
+            let signal = libc::WIFSIGNALED(status).then(|| libc::WTERMSIG(status));
+            let output = SandboxOutput {
+                exit_code: match signal {
+                    Some(signal) => 128 + signal,
+                    None => libc::WEXITSTATUS(status),
+                },
+                signal,
+                timed_out,
+                killed,
+                usage: ResourceUsage {
+                    wall_ms: started.elapsed().as_millis() as u64,
+                    user_ms: timeval_ms(usage.ru_utime),
+                    system_ms: timeval_ms(usage.ru_stime),
+                    max_rss_kib: max_rss_kib(usage.ru_maxrss),
+                },
+                ..SandboxOutput::default()
+            };
+            Ok(finish(output, stdout, stderr, staged).await)
+        });
+
+        Ok(SandboxedProcess {
+            id: Some(pid),
+            kill: Some(kill_tx),
+            done: Some(done),
+        })
+    }
+
+    fn kill_group(pid: u32) {
+        unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+    }
+
+    /// Waits for `pid` to exit, kills what is left of its process group, then
+    /// reaps it with its resource usage.
+    fn wait_exit(pid: libc::pid_t) -> io::Result<(libc::c_int, libc::rusage)> {
+        loop {
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let ret = unsafe {
+                libc::waitid(
+                    libc::P_PID,
+                    pid as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOWAIT,
+                )
+            };
+            match check(ret) {
+                Ok(()) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        // Stragglers would keep the output pipes open.
+        kill_group(pid as u32);
+        loop {
+            let mut status = 0;
+            let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+            let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+            match check(ret) {
+                Ok(()) => return Ok((status, usage)),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn timeval_ms(tv: libc::timeval) -> u64 {
+        tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000
+    }
+
+    fn max_rss_kib(max_rss: libc::c_long) -> u64 {
+        // macOS reports bytes, everything else kibibytes.
+        if cfg!(target_os = "macos") {
+            max_rss as u64 / 1024
+        } else {
+            max_rss as u64
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    use anyhow::{Context, Result};
+
+    use super::unix::check;
+
+    /// Mount points that keep their own (pseudo) file systems.
+    const PSEUDO_FS: &[&str] = &["/proc", "/sys", "/dev"];
+    /// Directories replaced by a private tmpfs.
+    const TMPFS: &[&str] = &["/tmp"];
+    /// Bind mounts a namespace supports; their sources are opened in the child.
+    const MAX_BINDS: usize = 16;
+
+    /// Prepared namespace setup; `enter` runs in the forked child.
+    pub(super) struct Namespace {
+        flags: libc::c_int,
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        binds: Vec<(CString, CString)>,
+        tmpfs: Vec<CString>,
+        /// Bind targets (and their parents) to create inside a tmpfs; `true` for directories.
+        targets: Vec<(CString, bool)>,
+        readonly: Vec<(CString, libc::c_ulong)>,
+        cwd: CString,
+    }
+
+    fn cstring(path: &Path) -> Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .with_context(|| format!("path contains a NUL byte: {}", path.display()))
+    }
+
+    impl Namespace {
+        pub(super) fn new(binds: &[(PathBuf, PathBuf)], network: bool, cwd: &Path) -> Result<Self> {
+            let mut flags =
+                libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS;
+            if !network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+            if binds.len() > MAX_BINDS {
+                anyhow::bail!("at most {} writable paths can be mounted", MAX_BINDS);
+            }
+            let tmpfs: Vec<&Path> = TMPFS.iter().map(Path::new).collect();
+            let mut targets = Vec::new();
+            for (src, dst) in binds {
+                let Some(tmp) = tmpfs.iter().find(|t| dst.starts_with(t)) else {
+                    continue;
+                };
+                let mut parents: Vec<_> = dst
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|a| a.starts_with(tmp) && a != tmp)
+                    .collect();
+                parents.reverse();
+                for parent in parents {
+                    targets.push((cstring(parent)?, true));
+                }
+                targets.push((cstring(dst)?, src.is_dir()));
+            }
+            let mut readonly = Vec::new();
+            let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+                .context("failed to read /proc/self/mountinfo")?;
+            for mount_point in mountinfo.lines().filter_map(|l| l.split(' ').nth(4)) {
+                let mount_point = PathBuf::from(unescape(mount_point));
+                let under = |parent: &Path| mount_point.starts_with(parent);
+                if PSEUDO_FS.iter().map(Path::new).any(under)
+                    || binds.iter().any(|(_, dst)| under(dst))
+                    || tmpfs.iter().copied().any(under)
+                {
+                    continue;
+                }
+                let path = cstring(&mount_point)?;
+                let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+                if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+                    continue;
+                }
+                readonly.push((path, locked_flags(stat.f_flag)));
+            }
+            readonly.sort();
+            readonly.dedup_by(|a, b| a.0 == b.0);
+
+            Ok(Self {
+                flags,
+                uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+                gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+                binds: binds
+                    .iter()
+                    .map(|(src, dst)| Ok((cstring(src)?, cstring(dst)?)))
+                    .collect::<Result<_>>()?,
+                tmpfs: tmpfs.into_iter().map(cstring).collect::<Result<_>>()?,
+                targets,
+                readonly,
+                cwd: cstring(cwd)?,
+            })
+        }
+
+        pub(super) fn enter(&self) -> io::Result<()> {
+            check(unsafe { libc::unshare(self.flags) })?;
+            match write_file(c"/proc/self/setgroups", b"deny") {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                other => other?,
+            }
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+            mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+            // Sources may live below a tmpfs mount point (e.g. /tmp), so hold
+            // them open and bind them through /proc/self/fd afterwards.
+            let mut sources = [-1; MAX_BINDS];
+            for (fd, (src, _)) in sources.iter_mut().zip(&self.binds) {
+                *fd = unsafe { libc::open(src.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+                check(*fd)?;
+            }
+            for path in &self.tmpfs {
+                mount(
+                    Some(c"tmpfs"),
+                    path,
+                    Some(c"tmpfs"),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                )?;
+            }
+            for (path, dir) in &self.targets {
+                let ret = if *dir {
+                    unsafe { libc::mkdir(path.as_ptr(), 0o755) }
+                } else {
+                    let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC;
+                    let fd = unsafe { libc::open(path.as_ptr(), flags, 0o644) };
+                    if fd >= 0 {
+                        unsafe { libc::close(fd) };
+                    }
+                    fd
+                };
+                match check(ret) {
+                    Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+                    _ => {}
+                }
+            }
+            for (fd, (_, dst)) in sources.iter().zip(&self.binds) {
+                let mut buf = [0; 32];
+                mount(
+                    Some(fd_path(*fd, &mut buf)?),
+                    dst,
+                    None,
+                    libc::MS_BIND | libc::MS_REC,
+                )?;
+                unsafe { libc::close(*fd) };
+            }
+            for (path, flags) in &self.readonly {
+                let flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | flags;
+                match mount(None, path, None, flags) {
+                    // Mount points hidden below other mounts cannot be reached.
+                    Err(e)
+                        if path.as_bytes() != b"/"
+                            && matches!(e.raw_os_error(), Some(libc::ENOENT | libc::EACCES)) => {}
+                    other => other?,
+                }
+            }
+            check(unsafe { libc::chdir(self.cwd.as_ptr()) })
+        }
+    }
+
+    /// `/proc/self/fd/<fd>`, formatted without allocating.
+    fn fd_path(fd: libc::c_int, buf: &mut [u8; 32]) -> io::Result<&CStr> {
+        const PREFIX: &[u8] = b"/proc/self/fd/";
+        buf[..PREFIX.len()].copy_from_slice(PREFIX);
+        let mut digits = [0u8; 10];
+        let (mut n, mut len) = (fd.unsigned_abs(), 0);
+        loop {
+            digits[len] = b'0' + (n % 10) as u8;
+            len += 1;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        let mut end = PREFIX.len();
+        for digit in digits[..len].iter().rev() {
+            buf[end] = *digit;
+            end += 1;
+        }
+        buf[end] = 0;
+        CStr::from_bytes_with_nul(&buf[..=end]).map_err(|_| io::ErrorKind::InvalidInput.into())
+    }
+
+    /// Mount flags a remount inside a user namespace has to preserve.
+    fn locked_flags(st_flags: libc::c_ulong) -> libc::c_ulong {
+        [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ]
+        .into_iter()
+        .filter(|(st, _)| st_flags & st != 0)
+        .fold(0, |flags, (_, ms)| flags | ms)
+    }
+
+    /// Undoes the octal escapes (`\040`) of /proc/self/mountinfo.
+    fn unescape(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let octal = bytes
+                .get(i + 1..i + 4)
+                .filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+            match (bytes[i], octal) {
+                (b'\\', Some(d)) => {
+                    out.push((d[0] - b'0') * 64 + (d[1] - b'0') * 8 + (d[2] - b'0'));
+                    i += 4;
+                }
+                (b, _) => {
+                    out.push(b);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    fn mount(
+        source: Option<&CStr>,
+        target: &CStr,
+        fstype: Option<&CStr>,
+        flags: libc::c_ulong,
+    ) -> io::Result<()> {
+        let ptr = |s: Option<&CStr>| s.map_or(std::ptr::null(), CStr::as_ptr);
+        check(unsafe {
+            libc::mount(
+                ptr(source),
+                target.as_ptr(),
+                ptr(fstype),
+                flags,
+                std::ptr::null(),
+            )
+        })
+    }
+
+    fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        check(fd)?;
+        let written = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+        unsafe { libc::close(fd) };
+        if written == contents.len() as isize {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Whether a child can enter fresh user and mount namespaces.
+    pub(super) fn probe() -> bool {
+        use std::os::unix::process::CommandExt;
+        let mut command = std::process::Command::new("/bin/sh");
+        command
+            .args(["-c", ":"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        unsafe {
+            command.pre_exec(|| check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS)));
+        }
+        command.status().is_ok_and(|status| status.success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::outbound::vfs::OverlayFs;
+    use std::time::Instant;
+
+    fn policy() -> SandboxPolicy {
+        SandboxPolicy {
+            allowed_binaries: None,
+            timeout: Duration::from_secs(10),
+            ..SandboxPolicy::default()
+        }
+    }
+
+    #[test]
+    fn allow_list_checks_every_started_program() {
+        let programs =
+            command_programs("FOO=1 cargo test -q 2>&1 | tail -n 3; if true; then git log; fi")
+                .unwrap();
+        assert_eq!(programs, ["cargo", "tail", "true", "git"]);
+        assert_eq!(
+            command_programs("for f in a 'b c'; do echo \"$f | x\"; done").unwrap(),
+            ["echo"]
+        );
+        assert!(command_programs("echo $(rm -rf /)").is_err());
+        assert!(command_programs("echo 'open").is_err());
+
+        let policy = SandboxPolicy::default();
+        assert!(policy
+            .check_command("cd src && grep -rn todo . > log.txt")
+            .is_ok());
+        for runs_code in [
+            "cargo build",
+            "find . -exec sh {} ;",
+            "awk 'BEGIN{}'",
+            "rg --pre sh pattern",
+            "sort --compress-program=sh big.txt",
+            "python3 x.py",
+        ] {
+            assert!(policy.check_command(runs_code).is_err(), "{}", runs_code);
+        }
+        let err = policy.check_command("ls; curl evil.sh").unwrap_err();
+        assert!(err.to_string().contains("'curl'"));
+        assert!(policy.check_command("exec bash").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn host_sandbox_reports_output_usage_and_limits() -> Result<()> {
+        let sandbox = HostSandbox::new(policy());
+        let output = sandbox
+            .run(SandboxRequest::new("echo out; echo err >&2; exit 3").with_env("X", "1"))
+            .await?;
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        assert!(!output.success());
+
+        let sandbox = HostSandbox::new(SandboxPolicy {
+            timeout: Duration::from_millis(200),
+            ..policy()
+        });
+        let started = Instant::now();
+        let output = sandbox.run(SandboxRequest::new("sleep 5")).await?;
+        assert!(output.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let sandbox = HostSandbox::new(SandboxPolicy {
+            cpu_time: Some(Duration::from_secs(1)),
+            ..policy()
+        });
+        let output = sandbox
+            .run(SandboxRequest::new("while :; do :; done"))
+            .await?;
+        assert_eq!(output.signal, Some(libc::SIGXCPU));
+        assert!(output.usage.user_ms + output.usage.system_ms >= 900);
+
+        let job = sandbox
+            .spawn(SandboxRequest::new("sleep 5").background())
+            .await?;
+        assert!(job.id().is_some());
+        let output = job.kill().await?;
+        assert!(output.killed);
+
+        // Dropping a job, or a wait on it, kills the command.
+        let dir = tempfile::tempdir()?;
+        let marker = dir.path().join("ran");
+        let command = format!("sleep 1; touch {}", marker.display());
+        let job = sandbox
+            .spawn(SandboxRequest::new(command.clone()).background())
+            .await?;
+        drop(job);
+        let job = sandbox
+            .spawn(SandboxRequest::new(command).background())
+            .await?;
+        let _ = tokio::time::timeout(Duration::from_millis(100), job.wait()).await;
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn workspace_changes_flow_back_into_the_vfs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().to_path_buf();
+        std::fs::write(root.join("keep.txt"), "disk\n")?;
+        std::fs::write(root.join("gone.txt"), "bye\n")?;
+        std::fs::create_dir_all(root.join("target"))?;
+        std::fs::write(root.join("target/big.bin"), "skip")?;
+
+        let vfs: Arc<dyn VirtualFileSystem> = Arc::new(OverlayFs::new());
+        vfs.write(&root.join("keep.txt"), b"overlay\n".to_vec(), "agent")
+            .await?;
+
+        let sandbox = HostSandbox::new(policy());
+        let request = SandboxRequest::new(
+            "cat keep.txt; ls target 2>&1; mkdir -p out && echo new > out/new.txt && rm gone.txt",
+        )
+        .with_workspace(vfs.clone(), &root, "agent");
+        let output = sandbox.run(request).await?;
+        assert!(output.success(), "{:?}", output);
+        assert!(output.stdout.starts_with("overlay\n"));
+        assert!(output.stdout.contains("No such file"));
+        assert_eq!(output.changed_files, [root.join("out/new.txt")]);
+        assert_eq!(output.removed_files, [root.join("gone.txt")]);
+
+        assert_eq!(vfs.read(&root.join("out/new.txt")).await?, b"new\n");
+        assert!(!vfs.exists(&root.join("gone.txt")).await?);
+        assert!(root.join("gone.txt").exists());
+        assert!(!root.join("out").exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn renamed_directories_are_materialized_as_directories() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().to_path_buf();
+        std::fs::create_dir_all(root.join("src/nested"))?;
+        std::fs::write(root.join("src/nested/lib.rs"), "lib\n")?;
+
+        let vfs: Arc<dyn VirtualFileSystem> = Arc::new(OverlayFs::new());
+        vfs.rename(&root.join("src"), &root.join("moved"), "agent")
+            .await?;
+
+        let sandbox = HostSandbox::new(policy());
+        let request = SandboxRequest::new("test -d moved && cat moved/nested/lib.rs")
+            .with_workspace(vfs.clone(), &root, "agent");
+        let output = sandbox.run(request).await?;
+        assert!(output.success(), "{:?}", output);
+        assert_eq!(output.stdout, "lib\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn namespace_sandbox_isolates_the_host() -> Result<()> {
+        if !NamespaceSandbox::is_supported() {
+            eprintln!("skipping: user namespaces are unavailable");
+            return Ok(());
+        }
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().to_path_buf();
+        std::fs::write(root.join("a.txt"), "disk\n")?;
+        let vfs: Arc<dyn VirtualFileSystem> = Arc::new(OverlayFs::new());
+
+        let sandbox = NamespaceSandbox::new(policy());
+        let request = SandboxRequest::new(
+            "cat a.txt; echo changed > a.txt; touch /etc/gestalt-escape 2>/dev/null || echo ro; \
+             touch /tmp/scratch && echo tmp; cat /proc/net/dev | wc -l",
+        )
+        .with_workspace(vfs.clone(), &root, "agent");
+        let output = sandbox.run(request).await?;
+        assert!(output.success(), "{:?}", output);
+        let lines: Vec<_> = output.stdout.lines().collect();
+        assert_eq!(lines[..3], ["disk", "ro", "tmp"]);
+        // Header lines plus loopback only.
+        assert_eq!(lines[3].trim(), "3");
+
+        assert!(!Path::new("/etc/gestalt-escape").exists());
+        assert_eq!(std::fs::read_to_string(root.join("a.txt"))?, "disk\n");
+        assert_eq!(vfs.read(&root.join("a.txt")).await?, b"changed\n");
+        Ok(())
+    }
+}
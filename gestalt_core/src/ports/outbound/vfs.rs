@@ -1,18 +1,51 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex;
-use tokio::task::JoinSet;
 use tokio::time::Duration;
+use tracing::{info, warn};
+
+use super::vfs_journal::{JournalEntry, VfsJournal};
+use crate::domain::diff::{merge3, unified_hunks, ConflictHunk, DEFAULT_CONTEXT};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PendingChange {
-    CreateDir { path: PathBuf },
-    WriteFile { path: PathBuf, bytes: usize },
+    CreateDir {
+        path: PathBuf,
+    },
+    WriteFile {
+        path: PathBuf,
+        bytes: usize,
+    },
+    RemoveFile {
+        path: PathBuf,
+    },
+    RemoveDir {
+        path: PathBuf,
+    },
+    /// `to` was renamed from `from`; realized by the matching write and removal.
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+impl PendingChange {
+    /// Path affected by the change (the destination for renames).
+    pub fn path(&self) -> &Path {
+        match self {
+            PendingChange::CreateDir { path }
+            | PendingChange::WriteFile { path, .. }
+            | PendingChange::RemoveFile { path }
+            | PendingChange::RemoveDir { path } => path,
+            PendingChange::Rename { to, .. } => to,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,10 +62,22 @@ pub struct FlushError {
     pub error: String,
 }
 
+/// Pending change to `path` that clashes with what changed on disk since its base.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConflict {
+    pub path: PathBuf,
+    /// Conflicting hunks; a whole-file conflict (binary content, modify/delete) has one hunk.
+    pub hunks: Vec<ConflictHunk>,
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct FlushReport {
     pub created_dirs: Vec<PathBuf>,
     pub written_files: Vec<PathBuf>,
+    pub removed_paths: Vec<PathBuf>,
+    /// Writes merged with concurrent disk changes.
+    pub merged_files: Vec<PathBuf>,
+    pub conflicts: Vec<FileConflict>,
     pub errors: Vec<FlushError>,
 }
 
@@ -40,6 +85,26 @@ impl FlushReport {
     pub fn has_errors(&self) -> bool {
         !self.errors.is_empty()
     }
+
+    /// Report of a flush refused because of merge conflicts; nothing was applied.
+    pub fn from_conflicts(conflicts: Vec<FileConflict>) -> Self {
+        let errors = conflicts
+            .iter()
+            .map(|conflict| FlushError {
+                path: conflict.path.clone(),
+                operation: "merge",
+                error: format!(
+                    "{} conflicting hunk(s) with changes on disk; write the file again to resolve",
+                    conflict.hunks.len()
+                ),
+            })
+            .collect();
+        Self {
+            conflicts,
+            errors,
+            ..Self::default()
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -55,6 +120,719 @@ pub struct FileWatchEvent {
     pub event_type: FileEventType,
 }
 
+/// Kind of an entry on the real filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+/// Deletions and renames recorded by an overlay.
+///
+/// Whited-out paths (and everything below a whited-out directory) are hidden
+/// from the real filesystem until `flush` deletes them. Renames are realized
+/// as a write of the destination plus a whiteout of the source; the rename
+/// itself is only kept for reporting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Whiteouts {
+    files: BTreeSet<PathBuf>,
+    dirs: BTreeSet<PathBuf>,
+    renames: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Whiteouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.dirs.is_empty() && self.renames.is_empty()
+    }
+
+    /// Whether the on-disk entry at `path` is hidden by a whiteout.
+    pub fn hides(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Hide the on-disk file at `path`.
+    pub fn remove_file(&mut self, path: &Path) {
+        self.files.insert(path.to_path_buf());
+        self.renames.remove(path);
+    }
+
+    /// Hide the on-disk directory at `path` and everything below it.
+    pub fn remove_dir(&mut self, path: &Path) {
+        self.files.retain(|file| !file.starts_with(path));
+        self.dirs.retain(|dir| !dir.starts_with(path));
+        self.renames.retain(|to, _| !to.starts_with(path));
+        self.dirs.insert(path.to_path_buf());
+    }
+
+    /// `path` was written again: drop its file whiteout and rename record.
+    ///
+    /// Directory whiteouts stay so the old on-disk contents are still removed.
+    pub fn restore(&mut self, path: &Path) {
+        self.files.remove(path);
+        self.renames.remove(path);
+    }
+
+    /// An overlay-only entry at or below `path` was dropped: forget its records.
+    pub fn forget(&mut self, path: &Path) {
+        self.files.retain(|file| !file.starts_with(path));
+        self.renames.retain(|to, _| !to.starts_with(path));
+    }
+
+    /// Record that `from` was renamed to `to`.
+    ///
+    /// `on_disk` tells whether `from` exists on the real filesystem and needs a whiteout.
+    pub fn rename(&mut self, from: &Path, to: &Path, kind: EntryKind, on_disk: bool) {
+        // Re-key renames recorded below a renamed directory.
+        let moved: Vec<(PathBuf, PathBuf)> = self
+            .renames
+            .iter()
+            .filter(|(dest, _)| dest.starts_with(from))
+            .map(|(dest, origin)| (dest.clone(), origin.clone()))
+            .collect();
+        let origin = self
+            .renames
+            .get(from)
+            .cloned()
+            .unwrap_or_else(|| from.to_path_buf());
+        for (dest, source) in moved {
+            self.renames.remove(&dest);
+            if dest != from {
+                if let Ok(rel) = dest.strip_prefix(from) {
+                    self.renames.insert(to.join(rel), source);
+                }
+            }
+        }
+
+        if on_disk {
+            match kind {
+                EntryKind::File => self.remove_file(from),
+                EntryKind::Dir => self.remove_dir(from),
+            }
+        }
+        self.files.remove(to);
+        if origin != to {
+            self.renames.insert(to.to_path_buf(), origin);
+        }
+    }
+
+    /// Outermost whited-out paths, i.e. what `flush` must delete.
+    pub fn removals(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.dirs.iter().cloned().collect();
+        paths.extend(
+            self.files
+                .iter()
+                .filter(|file| !self.dirs.iter().any(|dir| file.starts_with(dir)))
+                .cloned(),
+        );
+        paths.sort();
+        paths
+    }
+
+    pub fn pending_changes(&self) -> Vec<PendingChange> {
+        let mut pending = Vec::new();
+        for path in &self.dirs {
+            pending.push(PendingChange::RemoveDir { path: path.clone() });
+        }
+        for path in &self.files {
+            pending.push(PendingChange::RemoveFile { path: path.clone() });
+        }
+        for (to, from) in &self.renames {
+            pending.push(PendingChange::Rename {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+        pending
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.dirs.clear();
+        self.renames.clear();
+    }
+
+    /// Kind of the visible on-disk entry at `path`, if any.
+    pub async fn disk_kind(&self, path: &Path) -> Option<EntryKind> {
+        if self.hides(path) {
+            return None;
+        }
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        Some(if meta.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        })
+    }
+
+    /// Visible on-disk directories and files below `root` (recursive, `root` excluded).
+    pub async fn disk_tree(&self, root: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if self.hides(&path) {
+                    continue;
+                }
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path.clone());
+                    stack.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+        dirs.sort();
+        files.sort();
+        Ok((dirs, files))
+    }
+}
+
+/// Disk content each pending entry was derived from.
+///
+/// Recorded the first time an overlay reads, writes or removes a path. At
+/// flush, a path whose disk content moved away from its base is three-way
+/// merged instead of overwritten; conflicting paths block the flush until
+/// they are written again, which takes the disk content seen at the
+/// conflict as the new base.
+#[derive(Debug, Clone, Default)]
+pub struct MergeBases {
+    bases: HashMap<PathBuf, Option<Vec<u8>>>,
+    conflicted: HashMap<PathBuf, Option<Vec<u8>>>,
+}
+
+impl MergeBases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `content` (read from disk) as the base of `path` unless one is known.
+    pub fn observe(&mut self, path: &Path, content: &[u8]) {
+        if !self.conflicted.contains_key(path) {
+            self.bases
+                .entry(path.to_path_buf())
+                .or_insert_with(|| Some(content.to_vec()));
+        }
+    }
+
+    /// Snapshot the disk content of `path` before it is modified.
+    ///
+    /// A conflicted path is resolved: its base becomes the disk content seen at the conflict.
+    pub async fn track(&mut self, path: &Path) {
+        self.track_in(path, &RealFileSystem).await
+    }
+
+    /// Like [`MergeBases::track`], with `lower` standing in for the disk.
+    pub async fn track_in(&mut self, path: &Path, lower: &dyn VirtualFileSystem) {
+        if let Some(seen) = self.conflicted.remove(path) {
+            self.bases.insert(path.to_path_buf(), seen);
+        } else if !self.bases.contains_key(path) {
+            let content = lower.read(path).await.ok();
+            self.bases.insert(path.to_path_buf(), content);
+        }
+    }
+
+    /// Recorded base of `path`: `Some(None)` if it did not exist on disk.
+    pub fn base(&self, path: &Path) -> Option<Option<&[u8]>> {
+        self.bases.get(path).map(Option::as_deref)
+    }
+
+    /// Restore a base recorded earlier, e.g. from a journal.
+    pub fn set_base(&mut self, path: &Path, content: Option<Vec<u8>>) {
+        self.bases.insert(path.to_path_buf(), content);
+    }
+
+    pub fn clear(&mut self) {
+        self.bases.clear();
+        self.conflicted.clear();
+    }
+
+    /// Three-way merge `plan` against the current disk.
+    ///
+    /// Clean merges replace the planned content in place and are returned
+    /// first. When conflicts are returned the plan must not be applied.
+    pub async fn reconcile(&mut self, plan: &mut FlushPlan) -> (Vec<PathBuf>, Vec<FileConflict>) {
+        self.reconcile_in(plan, &RealFileSystem).await
+    }
+
+    /// Like [`MergeBases::reconcile`], merging against `lower` instead of the disk.
+    pub async fn reconcile_in(
+        &mut self,
+        plan: &mut FlushPlan,
+        lower: &dyn VirtualFileSystem,
+    ) -> (Vec<PathBuf>, Vec<FileConflict>) {
+        let mut merged_files = Vec::new();
+        let mut conflicts = Vec::new();
+
+        for (path, ours) in &mut plan.writes {
+            if let Some(conflict) = self.unresolved(path) {
+                conflicts.push(conflict);
+                continue;
+            }
+            let Some(base) = self.bases.get(path.as_path()).cloned() else {
+                continue;
+            };
+            let theirs = lower.read(path).await.ok();
+            if theirs == base || theirs.as_deref() == Some(ours.as_slice()) {
+                continue;
+            }
+
+            let merge = match (&base, std::str::from_utf8(ours), &theirs) {
+                (base, Ok(ours_text), Some(theirs)) => {
+                    let base_text = match base {
+                        Some(base) => std::str::from_utf8(base).ok(),
+                        None => Some(""),
+                    };
+                    match (base_text, std::str::from_utf8(theirs)) {
+                        (Some(base_text), Ok(theirs_text)) => {
+                            Some(merge3(base_text, ours_text, theirs_text))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match merge {
+                Some(result) if result.is_clean() => {
+                    *ours = result.merged.into_bytes();
+                    merged_files.push(path.clone());
+                }
+                Some(result) => {
+                    conflicts.push(FileConflict {
+                        path: path.clone(),
+                        hunks: result.conflicts,
+                    });
+                    self.conflicted.insert(path.clone(), theirs);
+                }
+                None => {
+                    conflicts.push(whole_file_conflict(path, &base, Some(ours), &theirs));
+                    self.conflicted.insert(path.clone(), theirs);
+                }
+            }
+        }
+
+        for path in &plan.removals {
+            if let Some(conflict) = self.unresolved(path) {
+                conflicts.push(conflict);
+                continue;
+            }
+            let Some(base) = self.bases.get(path).cloned() else {
+                continue;
+            };
+            let theirs = lower.read(path).await.ok();
+            if theirs.is_some() && theirs != base {
+                conflicts.push(whole_file_conflict(path, &base, None, &theirs));
+                self.conflicted.insert(path.clone(), theirs);
+            }
+        }
+
+        (merged_files, conflicts)
+    }
+
+    /// A conflict from an earlier flush that was not resolved by a new write.
+    fn unresolved(&self, path: &Path) -> Option<FileConflict> {
+        let seen = self.conflicted.get(path)?;
+        let base = self.bases.get(path).cloned().flatten();
+        Some(whole_file_conflict(path, &base, None, seen))
+    }
+}
+
+fn whole_file_conflict(
+    path: &Path,
+    base: &Option<Vec<u8>>,
+    ours: Option<&[u8]>,
+    theirs: &Option<Vec<u8>>,
+) -> FileConflict {
+    let lossy =
+        |bytes: Option<&[u8]>| String::from_utf8_lossy(bytes.unwrap_or_default()).into_owned();
+    FileConflict {
+        path: path.to_path_buf(),
+        hunks: vec![ConflictHunk {
+            base_start: 1,
+            base: lossy(base.as_deref()),
+            ours: lossy(ours),
+            theirs: lossy(theirs.as_deref()),
+        }],
+    }
+}
+
+/// Unified diff (git-apply compatible) of the pending changes of `vfs` against the real disk.
+///
+/// Paths are rendered relative to `root`. Renames are emitted as git renames
+/// when the source still exists on disk; directory creations are not
+/// representable and are skipped.
+pub async fn overlay_diff<V: VirtualFileSystem + ?Sized>(vfs: &V, root: &Path) -> Result<String> {
+    overlay_diff_against(vfs, &RealFileSystem, root).await
+}
+
+/// Like [`overlay_diff`], with `lower` as the old side instead of the real disk.
+pub async fn overlay_diff_against<V: VirtualFileSystem + ?Sized>(
+    vfs: &V,
+    lower: &dyn VirtualFileSystem,
+    root: &Path,
+) -> Result<String> {
+    let mut writes = Vec::new();
+    let mut renames = Vec::new();
+    let mut removals = Vec::new();
+    for change in vfs.pending_changes().await {
+        match change {
+            PendingChange::WriteFile { path, .. } => writes.push(path),
+            PendingChange::Rename { from, to } => renames.push((to, from)),
+            PendingChange::RemoveFile { path } | PendingChange::RemoveDir { path } => {
+                removals.push(path)
+            }
+            PendingChange::CreateDir { .. } => {}
+        }
+    }
+    // Innermost rename first, so nested renames win over their parent's.
+    renames.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let written: HashSet<PathBuf> = writes.iter().cloned().collect();
+    let mut renamed_sources = HashSet::new();
+    let mut patches: Vec<(PathBuf, String)> = Vec::new();
+
+    for path in &writes {
+        let new = vfs.read(path).await?;
+        let old = lower.read(path).await.ok();
+        let origin = renames
+            .iter()
+            .find(|(to, _)| path.starts_with(to))
+            .map(|(to, from)| rebase_path(path, to, from))
+            .filter(|origin| origin != path && !written.contains(origin));
+
+        if let (None, Some(origin)) = (&old, origin) {
+            if let Ok(source) = lower.read(&origin).await {
+                patches.push((
+                    path.clone(),
+                    file_patch(root, Some((&origin, &source)), Some((path, &new))),
+                ));
+                renamed_sources.insert(origin);
+                continue;
+            }
+        }
+        if old.as_deref() != Some(new.as_slice()) {
+            let old = old.as_deref().map(|old| (path.as_path(), old));
+            patches.push((path.clone(), file_patch(root, old, Some((path, &new)))));
+        }
+    }
+
+    let mut removed_files = Vec::new();
+    for path in removals {
+        match lower.entry_kind(&path).await {
+            Some(EntryKind::Dir) => {
+                let (_, files) = tree(lower, &path).await?;
+                removed_files.extend(files);
+            }
+            Some(EntryKind::File) => removed_files.push(path),
+            None => {}
+        }
+    }
+    for path in removed_files {
+        if written.contains(&path) || renamed_sources.contains(&path) {
+            continue;
+        }
+        if let Ok(old) = lower.read(&path).await {
+            patches.push((path.clone(), file_patch(root, Some((&path, &old)), None)));
+        }
+    }
+
+    patches.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(patches.into_iter().map(|(_, patch)| patch).collect())
+}
+
+/// Directories and files of `vfs` below `root` (recursive, `root` excluded).
+pub async fn tree<V: VirtualFileSystem + ?Sized>(
+    vfs: &V,
+    root: &Path,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for path in vfs.list(&dir).await? {
+            match vfs.entry_kind(&path).await {
+                Some(EntryKind::Dir) => {
+                    dirs.push(path.clone());
+                    stack.push(path);
+                }
+                Some(EntryKind::File) => files.push(path),
+                None => {}
+            }
+        }
+    }
+    dirs.sort();
+    files.sort();
+    Ok((dirs, files))
+}
+
+/// git-style path of `path` relative to `root`.
+fn diff_path(root: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    rel.components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// One `diff --git` section; `None` on a side means the file is absent there.
+fn file_patch(root: &Path, old: Option<(&Path, &[u8])>, new: Option<(&Path, &[u8])>) -> String {
+    let (a, b) = match (old, new) {
+        (Some((from, _)), Some((to, _))) => (diff_path(root, from), diff_path(root, to)),
+        (Some((path, _)), None) | (None, Some((path, _))) => {
+            (diff_path(root, path), diff_path(root, path))
+        }
+        (None, None) => return String::new(),
+    };
+    let old_data = old.map(|(_, data)| data);
+    let new_data = new.map(|(_, data)| data);
+
+    let mut out = format!("diff --git a/{} b/{}\n", a, b);
+    let index = format!("index {}..{}", blob_id(old_data), blob_id(new_data));
+    if old.is_none() {
+        out.push_str(&format!("new file mode 100644\n{}\n", index));
+    } else if new.is_none() {
+        out.push_str(&format!("deleted file mode 100644\n{}\n", index));
+    } else if a != b {
+        let identical = old_data == new_data;
+        out.push_str(&format!(
+            "similarity index {}%\nrename from {}\nrename to {}\n",
+            if identical { 100 } else { 50 },
+            a,
+            b
+        ));
+        if identical {
+            return out;
+        }
+    }
+    if old.is_some() && new.is_some() {
+        out.push_str(&format!("{} 100644\n", index));
+    }
+
+    let old_name = old.map_or("/dev/null".to_string(), |_| format!("a/{}", a));
+    let new_name = new.map_or("/dev/null".to_string(), |_| format!("b/{}", b));
+    let (Ok(old_text), Ok(new_text)) = (
+        std::str::from_utf8(old_data.unwrap_or_default()),
+        std::str::from_utf8(new_data.unwrap_or_default()),
+    ) else {
+        out.push_str(&format!(
+            "Binary files {} and {} differ\n",
+            old_name, new_name
+        ));
+        return out;
+    };
+
+    // Like git, an empty file created or deleted gets no `---`/`+++` lines.
+    let hunks = unified_hunks(old_text, new_text, DEFAULT_CONTEXT);
+    if !hunks.is_empty() {
+        out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        out.push_str(&hunks);
+    }
+    out
+}
+
+/// Abbreviated blob id of `data` as on git's `index` lines; zeros when absent.
+fn blob_id(data: Option<&[u8]>) -> String {
+    match data.map(|data| git2::Oid::hash_object(git2::ObjectType::Blob, data)) {
+        Some(Ok(oid)) => oid.to_string()[..7].to_string(),
+        _ => "0".repeat(7),
+    }
+}
+
+/// Everything one `flush` applies to the real filesystem.
+#[derive(Debug, Default)]
+pub struct FlushPlan {
+    pub removals: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+    pub writes: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// A step already applied by `FlushPlan::apply`, kept for rollback.
+enum FlushStep {
+    /// `original` was moved aside to `trash`.
+    Trashed {
+        original: PathBuf,
+        trash: PathBuf,
+    },
+    CreatedDir(PathBuf),
+    Wrote(PathBuf),
+}
+
+static FLUSH_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Hidden sibling of `path` used for staging and trash during a flush.
+fn flush_sibling(path: &Path, tag: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let seq = FLUSH_SEQ.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(
+        ".{}.gestalt-{}-{}-{}",
+        name,
+        tag,
+        std::process::id(),
+        seq
+    ))
+}
+
+impl FlushPlan {
+    pub fn is_empty(&self) -> bool {
+        self.removals.is_empty() && self.dirs.is_empty() && self.writes.is_empty()
+    }
+
+    /// Apply the plan all-or-nothing.
+    ///
+    /// Removals are moved aside first, then directories are created and files
+    /// are staged next to their target and renamed into place. On the first
+    /// failure every applied step is rolled back and the report only carries
+    /// the error.
+    pub async fn apply(self) -> FlushReport {
+        let mut report = FlushReport::default();
+        let mut steps = Vec::new();
+
+        match self.apply_steps(&mut steps, &mut report).await {
+            Ok(()) => {
+                for step in steps {
+                    if let FlushStep::Trashed { trash, .. } = step {
+                        let _ = remove_any(&trash).await;
+                    }
+                }
+            }
+            Err(error) => {
+                for step in steps.into_iter().rev() {
+                    match step {
+                        FlushStep::Trashed { original, trash } => {
+                            let _ = tokio::fs::rename(&trash, &original).await;
+                        }
+                        FlushStep::CreatedDir(dir) => {
+                            let _ = tokio::fs::remove_dir(&dir).await;
+                        }
+                        FlushStep::Wrote(path) => {
+                            let _ = tokio::fs::remove_file(&path).await;
+                        }
+                    }
+                }
+                report = FlushReport {
+                    errors: vec![error],
+                    ..FlushReport::default()
+                };
+            }
+        }
+        report
+    }
+
+    async fn apply_steps(
+        self,
+        steps: &mut Vec<FlushStep>,
+        report: &mut FlushReport,
+    ) -> std::result::Result<(), FlushError> {
+        let fail = |path: &Path, operation: &'static str, err: std::io::Error| FlushError {
+            path: path.to_path_buf(),
+            operation,
+            error: err.to_string(),
+        };
+
+        for path in self.removals {
+            if tokio::fs::symlink_metadata(&path).await.is_err() {
+                continue;
+            }
+            let trash = flush_sibling(&path, "trash");
+            tokio::fs::rename(&path, &trash)
+                .await
+                .map_err(|e| fail(&path, "remove", e))?;
+            steps.push(FlushStep::Trashed {
+                original: path.clone(),
+                trash,
+            });
+            report.removed_paths.push(path);
+        }
+
+        for dir in self.dirs {
+            create_dirs(&dir, steps)
+                .await
+                .map_err(|e| fail(&dir, "create_dir_all", e))?;
+            report.created_dirs.push(dir);
+        }
+
+        for (path, data) in self.writes {
+            if let Some(parent) = path.parent() {
+                create_dirs(parent, steps)
+                    .await
+                    .map_err(|e| fail(&path, "create_dir_all_parent", e))?;
+            }
+            let staged = flush_sibling(&path, "tmp");
+            if let Err(e) = tokio::fs::write(&staged, data).await {
+                let _ = tokio::fs::remove_file(&staged).await;
+                return Err(fail(&path, "write", e));
+            }
+            if tokio::fs::symlink_metadata(&path).await.is_ok() {
+                let trash = flush_sibling(&path, "trash");
+                if let Err(e) = tokio::fs::rename(&path, &trash).await {
+                    let _ = tokio::fs::remove_file(&staged).await;
+                    return Err(fail(&path, "write", e));
+                }
+                steps.push(FlushStep::Trashed {
+                    original: path.clone(),
+                    trash,
+                });
+            }
+            if let Err(e) = tokio::fs::rename(&staged, &path).await {
+                let _ = tokio::fs::remove_file(&staged).await;
+                return Err(fail(&path, "write", e));
+            }
+            steps.push(FlushStep::Wrote(path.clone()));
+            report.written_files.push(path);
+        }
+
+        Ok(())
+    }
+}
+
+/// `create_dir_all` that records every directory it actually creates.
+async fn create_dirs(path: &Path, steps: &mut Vec<FlushStep>) -> std::io::Result<()> {
+    let mut missing = Vec::new();
+    let mut current = Some(path);
+    while let Some(dir) = current {
+        if dir.as_os_str().is_empty() || tokio::fs::metadata(dir).await.is_ok() {
+            break;
+        }
+        missing.push(dir.to_path_buf());
+        current = dir.parent();
+    }
+    for dir in missing.into_iter().rev() {
+        match tokio::fs::create_dir(&dir).await {
+            Ok(()) => steps.push(FlushStep::CreatedDir(dir)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+async fn remove_any(path: &Path) -> std::io::Result<()> {
+    if tokio::fs::symlink_metadata(path).await?.is_dir() {
+        tokio::fs::remove_dir_all(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+/// `NotFound` I/O error for a path hidden or missing in the overlay.
+pub fn not_found(path: &Path) -> anyhow::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("'{}' does not exist", path.display()),
+    )
+    .into()
+}
+
 #[async_trait]
 pub trait VirtualFileSystem: Send + Sync {
     /// Reads the content of a file at the given path.
@@ -64,7 +842,7 @@ pub trait VirtualFileSystem: Send + Sync {
     /// ```
     /// # use std::path::Path;
     /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
-    /// # tokio_test::block_on(async {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// let vfs = OverlayFs::new();
     /// let data = vfs.read(Path::new("hello.txt")).await;
     /// # });
@@ -78,7 +856,7 @@ pub trait VirtualFileSystem: Send + Sync {
     /// ```
     /// # use std::path::Path;
     /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
-    /// # tokio_test::block_on(async {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// let vfs = OverlayFs::new();
     /// vfs.write(Path::new("hello.txt"), b"world".to_vec(), "agent-1").await.unwrap();
     /// # });
@@ -92,7 +870,7 @@ pub trait VirtualFileSystem: Send + Sync {
     /// ```
     /// # use std::path::Path;
     /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
-    /// # tokio_test::block_on(async {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// let vfs = OverlayFs::new();
     /// let entries = vfs.list(Path::new(".")).await.unwrap();
     /// # });
@@ -102,6 +880,21 @@ pub trait VirtualFileSystem: Send + Sync {
     /// Checks if a file or directory exists at the given path.
     async fn exists(&self, path: &Path) -> Result<bool>;
 
+    /// Kind of the entry visible at `path`, `None` if there is none.
+    ///
+    /// The default probes with `exists` and `read`; implementations that know
+    /// their entries should override it.
+    async fn entry_kind(&self, path: &Path) -> Option<EntryKind> {
+        if !self.exists(path).await.unwrap_or(false) {
+            return None;
+        }
+        Some(if self.read(path).await.is_ok() {
+            EntryKind::File
+        } else {
+            EntryKind::Dir
+        })
+    }
+
     // Extended/Compatibility methods
     async fn read_to_string(&self, path: &Path) -> Result<String> {
         let bytes = self.read(path).await?;
@@ -121,9 +914,67 @@ pub trait VirtualFileSystem: Send + Sync {
     }
 
     async fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Removes a file, recording a whiteout until the next flush.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::Path;
+    /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let vfs = OverlayFs::new();
+    /// vfs.write(Path::new("old.txt"), b"bye".to_vec(), "agent-1").await.unwrap();
+    /// vfs.remove_file(Path::new("old.txt"), "agent-1").await.unwrap();
+    /// assert!(!vfs.exists(Path::new("old.txt")).await.unwrap());
+    /// # });
+    /// ```
+    async fn remove_file(&self, path: &Path, owner: &str) -> Result<()>;
+
+    /// Removes a directory and everything below it.
+    async fn remove_dir_all(&self, path: &Path, owner: &str) -> Result<()>;
+
+    /// Renames (moves) a file or directory, replacing an existing destination file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::Path;
+    /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let vfs = OverlayFs::new();
+    /// vfs.write(Path::new("a.rs"), b"mod a;".to_vec(), "agent-1").await.unwrap();
+    /// vfs.rename(Path::new("a.rs"), Path::new("b.rs"), "agent-1").await.unwrap();
+    /// assert_eq!(vfs.read(Path::new("b.rs")).await.unwrap(), b"mod a;");
+    /// # });
+    /// ```
+    async fn rename(&self, from: &Path, to: &Path, owner: &str) -> Result<()>;
+
     async fn flush(&self) -> Result<FlushReport>;
     async fn pending_changes(&self) -> Vec<PendingChange>;
+
+    /// Unified diff (git-apply compatible) of all pending changes against the
+    /// real disk, with paths relative to the current directory.
+    ///
+    /// ```
+    /// # use gestalt_core::ports::outbound::vfs::{VirtualFileSystem, OverlayFs};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let dir = tempfile::tempdir_in(".").unwrap();
+    /// let file = std::env::current_dir().unwrap().join(dir.path()).join("new.txt");
+    /// let vfs = OverlayFs::new();
+    /// vfs.write(&file, b"hello\n".to_vec(), "agent-1").await.unwrap();
+    /// let diff = vfs.diff().await.unwrap();
+    /// assert!(diff.contains("new file mode 100644\nindex 0000000..ce01362\n--- /dev/null\n"));
+    /// assert!(diff.ends_with("/new.txt\n@@ -0,0 +1 @@\n+hello\n"));
+    /// # });
+    /// ```
+    async fn diff(&self) -> Result<String> {
+        overlay_diff(self, &std::env::current_dir()?).await
+    }
+
     async fn acquire_lock(&self, path: &Path, owner: &str) -> Result<LockStatus>;
+    /// Release the lock `owner` holds on `path`, if any.
+    async fn release_lock(&self, path: &Path, owner: &str);
     async fn release_locks(&self, owner: &str);
     async fn discard(&self);
     async fn version(&self) -> u64;
@@ -133,88 +984,296 @@ pub trait FileWatcher: Send + Sync {
     fn watch(&self, path: PathBuf, interval: Duration) -> mpsc::Receiver<FileWatchEvent>;
 }
 
+/// Pending content of a text file and its version, bumped by every write.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileState {
+    pub content: Arc<String>,
+    pub version: u64,
+}
+
+/// Changes staged over the real filesystem: file contents, created
+/// directories, whiteouts, merge bases and locks, optionally journaled.
+///
+/// Shared by [`OverlayFs`] and actor-based file managers. Every change is
+/// validated, journaled and only then applied, so memory never holds a change
+/// the journal lacks.
 #[derive(Debug, Default)]
-struct OverlayState {
-    text_files: HashMap<PathBuf, String>,
+pub struct OverlayState {
+    text_files: HashMap<PathBuf, FileState>,
     binary_files: HashMap<PathBuf, Vec<u8>>,
     dirs: HashSet<PathBuf>,
+    whiteouts: Whiteouts,
+    bases: MergeBases,
     locks: HashMap<PathBuf, String>,
     version: u64,
+    journal: Option<VfsJournal>,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct OverlayFs {
-    state: Arc<Mutex<OverlayState>>,
-}
-
-impl OverlayFs {
+impl OverlayState {
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-#[async_trait]
-impl VirtualFileSystem for OverlayFs {
-    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
-        let state = self.state.lock().await;
-        if let Some(content) = state.binary_files.get(path) {
-            return Ok(content.clone());
+    /// State rebuilt from `journal`, which then records every further change.
+    ///
+    /// Entries that no longer apply (e.g. removing a file deleted from disk
+    /// meanwhile) are skipped with a warning.
+    pub async fn recover(mut journal: VfsJournal) -> Result<Self> {
+        let entries = journal.load().await?;
+        if !entries.is_empty() {
+            info!(
+                "Recovering {} VFS journal entries from {}",
+                entries.len(),
+                journal.path().display()
+            );
         }
-        if let Some(content) = state.text_files.get(path) {
-            return Ok(content.as_bytes().to_vec());
+        let mut state = Self::replayed(entries, journal.path()).await;
+        state.journal = Some(journal);
+        Ok(state)
+    }
+
+    /// Unjournaled state rebuilt from `journal` without modifying its file.
+    pub async fn inspect(journal: &VfsJournal) -> Result<Self> {
+        let entries = journal.read().await?;
+        Ok(Self::replayed(entries, journal.path()).await)
+    }
+
+    async fn replayed(entries: Vec<JournalEntry>, source: &Path) -> Self {
+        let mut state = Self::new();
+        for entry in entries {
+            if let Err(e) = state.replay(entry).await {
+                warn!("Skipping VFS journal entry of {}: {}", source.display(), e);
+            }
         }
-        drop(state);
-        Ok(tokio::fs::read(path).await?)
+        state
     }
 
-    async fn write(&self, path: &Path, data: Vec<u8>, owner: &str) -> Result<()> {
-        let mut state = self.state.lock().await;
-        match state.locks.get(path) {
-            Some(current_owner) if current_owner != owner => {
-                anyhow::bail!(
-                    "lock conflict for '{}': held by '{}'",
-                    path.display(),
-                    current_owner
-                );
+    /// Apply a journaled change; with no journal attached, nothing is re-recorded.
+    pub async fn replay(&mut self, entry: JournalEntry) -> Result<()> {
+        match entry {
+            JournalEntry::Checkpoint { version, locks } => {
+                self.version = version;
+                self.locks = locks.into_iter().collect();
+            }
+            JournalEntry::WriteText {
+                path,
+                owner,
+                content,
+            }
+            | JournalEntry::Patch {
+                path,
+                owner,
+                content,
+                ..
+            } => {
+                self.write_text(&path, content, &owner).await?;
             }
-            _ => {
-                state.locks.insert(path.to_path_buf(), owner.to_string());
+            JournalEntry::WriteBytes { path, owner, data } => {
+                self.write_bytes(&path, data, &owner).await?
             }
+            JournalEntry::CreateDir { path } => self.create_dir_all(&path).await?,
+            JournalEntry::RemoveFile { path, owner } => self.remove_file(&path, &owner).await?,
+            JournalEntry::RemoveDir { path, owner } => self.remove_dir_all(&path, &owner).await?,
+            JournalEntry::Rename { from, to, owner } => self.rename(&from, &to, &owner).await?,
+            JournalEntry::Lock { path, owner } => {
+                self.acquire_lock(&path, &owner).await?;
+            }
+            JournalEntry::ReleaseLocks { owner } => self.release_locks(&owner).await,
+            JournalEntry::Unlock { path, owner } => self.release_lock(&path, &owner).await,
+            JournalEntry::Base { path, content } => self.bases.set_base(&path, content),
         }
-        state.text_files.remove(path);
-        state.binary_files.insert(path.to_path_buf(), data);
         Ok(())
     }
 
-    async fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
-        let state = self.state.lock().await;
-        let mut entries = HashSet::new();
-
-        // Overlay files
-        for p in state.text_files.keys().chain(state.binary_files.keys()) {
-            if let Some(parent) = p.parent() {
-                if parent == path {
-                    entries.insert(p.clone());
-                }
-            }
+    /// Journal the change built by `entry`, plus the merge bases of `touched`.
+    ///
+    /// Called before a change is applied, so acknowledged changes survive a restart.
+    async fn journal(
+        &mut self,
+        touched: &[&Path],
+        entry: impl FnOnce() -> JournalEntry,
+    ) -> Result<()> {
+        let Some(journal) = self.journal.as_mut() else {
+            return Ok(());
+        };
+        journal.append(&entry()).await?;
+        for path in touched {
+            journal.append_base(&self.bases, path).await?;
         }
+        Ok(())
+    }
 
-        // Overlay dirs
-        for p in &state.dirs {
-            if let Some(parent) = p.parent() {
-                if parent == path {
-                    entries.insert(p.clone());
-                }
+    /// Compact the journal after the pending state was flushed or discarded.
+    async fn checkpoint(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            if let Err(e) = journal.checkpoint(self.version, &self.locks).await {
+                warn!(
+                    "Failed to compact VFS journal {}: {}",
+                    journal.path().display(),
+                    e
+                );
             }
         }
+    }
+
+    fn has_file(&self, path: &Path) -> bool {
+        self.text_files.contains_key(path) || self.binary_files.contains_key(path)
+    }
+
+    /// Whether the overlay holds `path` as a directory, explicitly or through entries below it.
+    fn has_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+            || self
+                .text_files
+                .keys()
+                .chain(self.binary_files.keys())
+                .chain(self.dirs.iter())
+                .any(|p| p != path && p.starts_with(path))
+    }
+
+    /// Fails if another owner holds a lock on `path` or anything below it.
+    fn check_locks(&self, path: &Path, owner: &str) -> Result<()> {
+        if let Some((locked, current_owner)) = self
+            .locks
+            .iter()
+            .find(|(locked, current_owner)| locked.starts_with(path) && *current_owner != owner)
+        {
+            anyhow::bail!(
+                "lock conflict for '{}': held by '{}'",
+                locked.display(),
+                current_owner
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails if another owner holds the lock on `path` itself.
+    fn check_write_lock(&self, path: &Path, owner: &str) -> Result<()> {
+        match self.locks.get(path) {
+            Some(current_owner) if current_owner != owner => anyhow::bail!(
+                "lock conflict for '{}': held by '{}'",
+                path.display(),
+                current_owner
+            ),
+            _ => Ok(()),
+        }
+    }
 
-        drop(state);
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 
-        // Real filesystem
-        if tokio::fs::metadata(path).await.is_ok() {
+    /// Content of `path`: the pending one, else the disk's unless whited out.
+    pub async fn read(&mut self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(data) = self.binary_files.get(path) {
+            return Ok(data.clone());
+        }
+        if let Some(file) = self.text_files.get(path) {
+            return Ok(file.content.as_bytes().to_vec());
+        }
+        if self.whiteouts.hides(path) {
+            return Err(not_found(path));
+        }
+        let data = tokio::fs::read(path).await?;
+        self.bases.observe(path, &data);
+        Ok(data)
+    }
+
+    /// Text of `path` with its version; disk content is cached as version 0.
+    pub async fn read_text(&mut self, path: &Path) -> Result<FileState> {
+        if let Some(file) = self.text_files.get(path) {
+            return Ok(file.clone());
+        }
+        let content = match self.binary_files.get(path) {
+            Some(data) => String::from_utf8(data.clone())?,
+            None if self.whiteouts.hides(path) => return Err(not_found(path)),
+            None => {
+                let content = tokio::fs::read_to_string(path).await?;
+                self.bases.observe(path, content.as_bytes());
+                content
+            }
+        };
+        let file = FileState {
+            content: Arc::new(content),
+            version: 0,
+        };
+        self.text_files.insert(path.to_path_buf(), file.clone());
+        Ok(file)
+    }
+
+    /// Store `content` as the next version of `path`.
+    pub async fn write_text(
+        &mut self,
+        path: &Path,
+        content: String,
+        owner: &str,
+    ) -> Result<FileState> {
+        let entry = || JournalEntry::WriteText {
+            path: path.to_path_buf(),
+            owner: owner.to_string(),
+            content: content.clone(),
+        };
+        self.write_text_as(path, content.clone(), owner, entry)
+            .await
+    }
+
+    /// Like [`OverlayState::write_text`], journaled as `entry` (e.g. a patch).
+    pub async fn write_text_as(
+        &mut self,
+        path: &Path,
+        content: String,
+        owner: &str,
+        entry: impl FnOnce() -> JournalEntry,
+    ) -> Result<FileState> {
+        self.check_write_lock(path, owner)?;
+        self.bases.track(path).await;
+        self.journal(&[path], entry).await?;
+
+        let file = FileState {
+            content: Arc::new(content),
+            version: self.text_files.get(path).map_or(0, |f| f.version) + 1,
+        };
+        self.locks.insert(path.to_path_buf(), owner.to_string());
+        self.text_files.insert(path.to_path_buf(), file.clone());
+        self.binary_files.remove(path);
+        self.whiteouts.restore(path);
+        Ok(file)
+    }
+
+    pub async fn write_bytes(&mut self, path: &Path, data: Vec<u8>, owner: &str) -> Result<()> {
+        self.check_write_lock(path, owner)?;
+        self.bases.track(path).await;
+        self.journal(&[path], || JournalEntry::WriteBytes {
+            path: path.to_path_buf(),
+            owner: owner.to_string(),
+            data: data.clone(),
+        })
+        .await?;
+
+        self.locks.insert(path.to_path_buf(), owner.to_string());
+        self.text_files.remove(path);
+        self.binary_files.insert(path.to_path_buf(), data);
+        self.whiteouts.restore(path);
+        Ok(())
+    }
+
+    /// Entries directly below `path`: pending ones plus the visible disk ones.
+    pub async fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries: HashSet<PathBuf> = self
+            .text_files
+            .keys()
+            .chain(self.binary_files.keys())
+            .chain(self.dirs.iter())
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect();
+
+        if !self.whiteouts.hides(path) && tokio::fs::metadata(path).await.is_ok() {
             let mut dir = tokio::fs::read_dir(path).await?;
             while let Some(entry) = dir.next_entry().await? {
-                entries.insert(entry.path());
+                if !self.whiteouts.hides(&entry.path()) {
+                    entries.insert(entry.path());
+                }
             }
         }
 
@@ -223,171 +1282,278 @@ impl VirtualFileSystem for OverlayFs {
         Ok(result)
     }
 
-    async fn exists(&self, path: &Path) -> Result<bool> {
-        let state = self.state.lock().await;
-        if state.text_files.contains_key(path)
-            || state.binary_files.contains_key(path)
-            || state.dirs.contains(path)
-        {
-            return Ok(true);
+    pub async fn entry_kind(&self, path: &Path) -> Option<EntryKind> {
+        if self.has_file(path) {
+            Some(EntryKind::File)
+        } else if self.has_dir(path) {
+            Some(EntryKind::Dir)
+        } else {
+            self.whiteouts.disk_kind(path).await
         }
-        drop(state);
-        Ok(tokio::fs::metadata(path).await.is_ok())
     }
 
-    async fn create_dir_all(&self, path: &Path) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.dirs.insert(path.to_path_buf());
+    pub async fn create_dir_all(&mut self, path: &Path) -> Result<()> {
+        self.journal(&[], || JournalEntry::CreateDir {
+            path: path.to_path_buf(),
+        })
+        .await?;
+        self.dirs.insert(path.to_path_buf());
         Ok(())
     }
 
-    async fn flush(&self) -> Result<FlushReport> {
-        let (pending_dirs, pending_files, pending_bytes) = {
-            let state = self.state.lock().await;
-            (
-                state.dirs.iter().cloned().collect::<Vec<_>>(),
-                state
-                    .text_files
-                    .iter()
-                    .map(|(path, content)| (path.clone(), content.clone()))
-                    .collect::<Vec<_>>(),
-                state
-                    .binary_files
-                    .iter()
-                    .map(|(path, content)| (path.clone(), content.clone()))
-                    .collect::<Vec<_>>(),
-            )
-        };
+    pub async fn remove_file(&mut self, path: &Path, owner: &str) -> Result<()> {
+        self.check_locks(path, owner)?;
+        let disk = self.whiteouts.disk_kind(path).await;
+        if self.has_dir(path) || disk == Some(EntryKind::Dir) {
+            anyhow::bail!("'{}' is a directory", path.display());
+        }
+        if disk.is_none() && !self.has_file(path) {
+            return Err(not_found(path));
+        }
+        if disk.is_some() {
+            self.bases.track(path).await;
+        }
+        self.journal(&[path], || JournalEntry::RemoveFile {
+            path: path.to_path_buf(),
+            owner: owner.to_string(),
+        })
+        .await?;
+
+        self.text_files.remove(path);
+        self.binary_files.remove(path);
+        if disk.is_some() {
+            self.whiteouts.remove_file(path);
+        } else {
+            self.whiteouts.forget(path);
+        }
+        self.locks.insert(path.to_path_buf(), owner.to_string());
+        Ok(())
+    }
 
-        let mut report = FlushReport::default();
-        let mut set = JoinSet::new();
-
-        enum FlushOp {
-            Dir(PathBuf),
-            File(PathBuf),
-            Error(FlushError),
-        }
-
-        for dir in pending_dirs {
-            set.spawn(async move {
-                if let Err(err) = tokio::fs::create_dir_all(&dir).await {
-                    FlushOp::Error(FlushError {
-                        path: dir,
-                        operation: "create_dir_all",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::Dir(dir)
-                }
-            });
+    pub async fn remove_dir_all(&mut self, path: &Path, owner: &str) -> Result<()> {
+        self.check_locks(path, owner)?;
+        let disk = self.whiteouts.disk_kind(path).await;
+        if self.has_file(path) || disk == Some(EntryKind::File) {
+            anyhow::bail!("'{}' is not a directory", path.display());
+        }
+        if disk.is_none() && !self.has_dir(path) {
+            return Err(not_found(path));
         }
+        self.journal(&[], || JournalEntry::RemoveDir {
+            path: path.to_path_buf(),
+            owner: owner.to_string(),
+        })
+        .await?;
+
+        self.text_files.retain(|p, _| !p.starts_with(path));
+        self.binary_files.retain(|p, _| !p.starts_with(path));
+        self.dirs.retain(|p| !p.starts_with(path));
+        if disk.is_some() {
+            self.whiteouts.remove_dir(path);
+        } else {
+            self.whiteouts.forget(path);
+        }
+        self.locks.insert(path.to_path_buf(), owner.to_string());
+        Ok(())
+    }
 
-        for (path, content) in pending_files {
-            set.spawn(async move {
-                if let Some(parent) = path.parent() {
-                    if let Err(err) = tokio::fs::create_dir_all(parent).await {
-                        return FlushOp::Error(FlushError {
-                            path,
-                            operation: "create_dir_all_parent",
-                            error: err.to_string(),
-                        });
-                    }
-                }
-                if let Err(err) = tokio::fs::write(&path, content).await {
-                    FlushOp::Error(FlushError {
-                        path,
-                        operation: "write",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::File(path)
-                }
-            });
+    /// Move `from` to `to`; on-disk sources are loaded and whited out.
+    pub async fn rename(&mut self, from: &Path, to: &Path, owner: &str) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        if to.starts_with(from) {
+            anyhow::bail!(
+                "cannot move '{}' into itself ('{}')",
+                from.display(),
+                to.display()
+            );
+        }
+        self.check_locks(from, owner)?;
+        self.check_locks(to, owner)?;
+
+        let disk = self.whiteouts.disk_kind(from).await;
+        let kind = if self.has_file(from) {
+            EntryKind::File
+        } else if self.has_dir(from) {
+            EntryKind::Dir
+        } else {
+            disk.ok_or_else(|| not_found(from))?
+        };
+        let dest_is_dir = self.has_dir(to)
+            || (!self.has_file(to) && self.whiteouts.disk_kind(to).await == Some(EntryKind::Dir));
+        if dest_is_dir {
+            anyhow::bail!("destination '{}' is a directory", to.display());
         }
 
-        for (path, content) in pending_bytes {
-            set.spawn(async move {
-                if let Some(parent) = path.parent() {
-                    if let Err(err) = tokio::fs::create_dir_all(parent).await {
-                        return FlushOp::Error(FlushError {
-                            path,
-                            operation: "create_dir_all_parent",
-                            error: err.to_string(),
-                        });
+        // Read everything the move needs from disk before journaling it.
+        let mut disk_files = Vec::new();
+        let mut disk_dirs = Vec::new();
+        match kind {
+            EntryKind::File => {
+                if !self.has_file(from) {
+                    disk_files.push((from.to_path_buf(), tokio::fs::read(from).await?));
+                }
+                if disk.is_some() {
+                    self.bases.track(from).await;
+                }
+                self.bases.track(to).await;
+            }
+            EntryKind::Dir => {
+                if self.has_file(to) || self.whiteouts.disk_kind(to).await.is_some() {
+                    anyhow::bail!("destination '{}' already exists", to.display());
+                }
+                if disk == Some(EntryKind::Dir) {
+                    let (dirs, files) = self.whiteouts.disk_tree(from).await?;
+                    for file in files {
+                        if !self.has_file(&file) {
+                            let data = tokio::fs::read(&file).await?;
+                            disk_files.push((file, data));
+                        }
                     }
+                    disk_dirs = dirs;
                 }
-                if let Err(err) = tokio::fs::write(&path, content).await {
-                    FlushOp::Error(FlushError {
-                        path,
-                        operation: "write",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::File(path)
+            }
+        }
+        self.journal(&[from, to], || JournalEntry::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            owner: owner.to_string(),
+        })
+        .await?;
+
+        // Pull the on-disk entries into the overlay, then re-root everything.
+        self.binary_files.extend(disk_files);
+        self.dirs.extend(disk_dirs);
+        match kind {
+            EntryKind::File => {
+                self.text_files.remove(to);
+                self.binary_files.remove(to);
+                if let Some(file) = self.text_files.remove(from) {
+                    self.text_files.insert(to.to_path_buf(), file);
+                } else if let Some(data) = self.binary_files.remove(from) {
+                    self.binary_files.insert(to.to_path_buf(), data);
                 }
-            });
+            }
+            EntryKind::Dir => {
+                self.dirs.insert(from.to_path_buf());
+                rebase_keys(&mut self.text_files, from, to);
+                rebase_keys(&mut self.binary_files, from, to);
+                self.dirs = self
+                    .dirs
+                    .drain()
+                    .map(|dir| rebase_path(&dir, from, to))
+                    .collect();
+            }
         }
+        rebase_keys(&mut self.locks, from, to);
+        self.locks.insert(to.to_path_buf(), owner.to_string());
+        self.whiteouts.rename(from, to, kind, disk.is_some());
+        Ok(())
+    }
+
+    /// Apply the pending state to disk, merging disk changes made since each
+    /// file's base. Conflicts or errors leave the state untouched for a retry.
+    pub async fn flush(&mut self) -> Result<FlushReport> {
+        let mut dirs = self.dirs.iter().cloned().collect::<Vec<_>>();
+        dirs.sort();
+        let mut writes = self
+            .text_files
+            .iter()
+            .map(|(path, file)| (path.clone(), file.content.as_bytes().to_vec()))
+            .chain(
+                self.binary_files
+                    .iter()
+                    .map(|(path, data)| (path.clone(), data.clone())),
+            )
+            .collect::<Vec<_>>();
+        writes.sort_by(|a, b| a.0.cmp(&b.0));
 
-        while let Some(res) = set.join_next().await {
-            match res? {
-                FlushOp::Dir(p) => report.created_dirs.push(p),
-                FlushOp::File(p) => report.written_files.push(p),
-                FlushOp::Error(e) => report.errors.push(e),
-            }
+        let mut plan = FlushPlan {
+            removals: self.whiteouts.removals(),
+            dirs,
+            writes,
+        };
+        let (merged_files, conflicts) = self.bases.reconcile(&mut plan).await;
+        if !conflicts.is_empty() {
+            warn!(
+                "VFS flush blocked by {} merge conflict(s).",
+                conflicts.len()
+            );
+            return Ok(FlushReport::from_conflicts(conflicts));
         }
-
-        let mut state = self.state.lock().await;
-        for created in &report.created_dirs {
-            state.dirs.remove(created);
+        // All-or-nothing: on error nothing was applied and the state is kept for a retry.
+        let mut report = plan.apply().await;
+        if report.has_errors() {
+            return Ok(report);
         }
+        report.merged_files = merged_files;
+
         for written in &report.written_files {
-            state.text_files.remove(written);
-            state.binary_files.remove(written);
-            state.locks.remove(written);
+            self.locks.remove(written);
         }
+        for removed in &report.removed_paths {
+            self.locks.retain(|locked, _| !locked.starts_with(removed));
+        }
+        self.clear_pending();
 
-        if !report.written_files.is_empty() || !report.created_dirs.is_empty() {
-            state.version += 1;
+        if !report.written_files.is_empty()
+            || !report.created_dirs.is_empty()
+            || !report.removed_paths.is_empty()
+        {
+            self.version += 1;
         }
+        self.checkpoint().await;
 
         Ok(report)
     }
 
-    async fn pending_changes(&self) -> Vec<PendingChange> {
-        let state = self.state.lock().await;
-        let total_files = state.text_files.len() + state.binary_files.len();
-        let mut pending = Vec::with_capacity(state.dirs.len() + total_files);
+    fn clear_pending(&mut self) {
+        self.text_files.clear();
+        self.binary_files.clear();
+        self.dirs.clear();
+        self.whiteouts.clear();
+        self.bases.clear();
+    }
 
-        let mut dirs = state.dirs.iter().cloned().collect::<Vec<_>>();
+    pub fn pending_changes(&self) -> Vec<PendingChange> {
+        let total_files = self.text_files.len() + self.binary_files.len();
+        let mut pending = Vec::with_capacity(self.dirs.len() + total_files);
+
+        let mut dirs = self.dirs.iter().cloned().collect::<Vec<_>>();
         dirs.sort();
         for path in dirs {
             pending.push(PendingChange::CreateDir { path });
         }
 
-        let mut files = state
+        let mut files = self
             .text_files
             .iter()
-            .map(|(path, content)| (path.clone(), content.len()))
+            .map(|(path, file)| (path.clone(), file.content.len()))
             .collect::<Vec<_>>();
         files.extend(
-            state
-                .binary_files
+            self.binary_files
                 .iter()
-                .map(|(path, content)| (path.clone(), content.len())),
+                .map(|(path, data)| (path.clone(), data.len())),
         );
         files.sort_by(|a, b| a.0.cmp(&b.0));
         for (path, bytes) in files {
             pending.push(PendingChange::WriteFile { path, bytes });
         }
 
+        pending.extend(self.whiteouts.pending_changes());
         pending
     }
 
-    async fn acquire_lock(&self, path: &Path, owner: &str) -> Result<LockStatus> {
-        let mut state = self.state.lock().await;
-        let status = match state.locks.get(path) {
+    pub async fn acquire_lock(&mut self, path: &Path, owner: &str) -> Result<LockStatus> {
+        let status = match self.locks.get(path) {
             None => {
-                state.locks.insert(path.to_path_buf(), owner.to_string());
+                self.journal(&[], || JournalEntry::Lock {
+                    path: path.to_path_buf(),
+                    owner: owner.to_string(),
+                })
+                .await?;
+                self.locks.insert(path.to_path_buf(), owner.to_string());
                 LockStatus::Acquired
             }
             Some(current_owner) if current_owner == owner => LockStatus::AlreadyHeldByOwner,
@@ -398,24 +1564,165 @@ impl VirtualFileSystem for OverlayFs {
         Ok(status)
     }
 
+    pub async fn release_lock(&mut self, path: &Path, owner: &str) {
+        if self.locks.get(path).map(String::as_str) != Some(owner) {
+            return;
+        }
+        if let Err(e) = self
+            .journal(&[], || JournalEntry::Unlock {
+                path: path.to_path_buf(),
+                owner: owner.to_string(),
+            })
+            .await
+        {
+            warn!(
+                "Failed to journal release of '{}' for '{}': {}",
+                path.display(),
+                owner,
+                e
+            );
+        }
+        self.locks.remove(path);
+    }
+
+    pub async fn release_locks(&mut self, owner: &str) {
+        if let Err(e) = self
+            .journal(&[], || JournalEntry::ReleaseLocks {
+                owner: owner.to_string(),
+            })
+            .await
+        {
+            warn!("Failed to journal lock release for '{}': {}", owner, e);
+        }
+        self.locks.retain(|_, current_owner| current_owner != owner);
+    }
+
+    /// Drop every pending change and lock.
+    pub async fn discard(&mut self) {
+        self.clear_pending();
+        self.locks.clear();
+        self.checkpoint().await;
+    }
+}
+
+/// `path` re-rooted from `from` to `to`.
+pub fn rebase_path(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(rel) if rel.as_os_str().is_empty() => to.to_path_buf(),
+        Ok(rel) => to.join(rel),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Re-root every key of `map` at or below `from` onto `to`.
+pub fn rebase_keys<V>(map: &mut HashMap<PathBuf, V>, from: &Path, to: &Path) {
+    let keys: Vec<PathBuf> = map
+        .keys()
+        .filter(|p| p.starts_with(from))
+        .cloned()
+        .collect();
+    for key in keys {
+        if let Some(value) = map.remove(&key) {
+            map.insert(rebase_path(&key, from, to), value);
+        }
+    }
+}
+
+/// Journaled overlay shared behind a mutex; see [`OverlayState`].
+#[derive(Debug, Clone, Default)]
+pub struct OverlayFs {
+    state: Arc<Mutex<OverlayState>>,
+}
+
+impl OverlayFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overlay rebuilt from `journal`, which then records every further change.
+    ///
+    /// Entries that no longer apply (e.g. removing a file deleted from disk
+    /// meanwhile) are skipped with a warning.
+    pub async fn recover(journal: VfsJournal) -> Result<Self> {
+        Ok(Self {
+            state: Arc::new(Mutex::new(OverlayState::recover(journal).await?)),
+        })
+    }
+
+    /// Read-only view of the overlay journaled in `journal`: the file is
+    /// neither repaired nor appended to, so this is safe while its agent runs.
+    pub async fn inspect(journal: &VfsJournal) -> Result<Self> {
+        Ok(Self {
+            state: Arc::new(Mutex::new(OverlayState::inspect(journal).await?)),
+        })
+    }
+}
+
+#[async_trait]
+impl VirtualFileSystem for OverlayFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.state.lock().await.read(path).await
+    }
+
+    async fn write(&self, path: &Path, data: Vec<u8>, owner: &str) -> Result<()> {
+        self.state.lock().await.write_bytes(path, data, owner).await
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        self.state.lock().await.list(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        Ok(self.entry_kind(path).await.is_some())
+    }
+
+    async fn entry_kind(&self, path: &Path) -> Option<EntryKind> {
+        self.state.lock().await.entry_kind(path).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.state.lock().await.create_dir_all(path).await
+    }
+
+    async fn remove_file(&self, path: &Path, owner: &str) -> Result<()> {
+        self.state.lock().await.remove_file(path, owner).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path, owner: &str) -> Result<()> {
+        self.state.lock().await.remove_dir_all(path, owner).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, owner: &str) -> Result<()> {
+        self.state.lock().await.rename(from, to, owner).await
+    }
+
+    async fn flush(&self) -> Result<FlushReport> {
+        // Held for the whole flush so the plan and the state stay consistent.
+        self.state.lock().await.flush().await
+    }
+
+    async fn pending_changes(&self) -> Vec<PendingChange> {
+        self.state.lock().await.pending_changes()
+    }
+
+    async fn acquire_lock(&self, path: &Path, owner: &str) -> Result<LockStatus> {
+        self.state.lock().await.acquire_lock(path, owner).await
+    }
+
+    async fn release_lock(&self, path: &Path, owner: &str) {
+        self.state.lock().await.release_lock(path, owner).await
+    }
+
     async fn release_locks(&self, owner: &str) {
-        let mut state = self.state.lock().await;
-        state
-            .locks
-            .retain(|_, current_owner| current_owner != owner);
+        self.state.lock().await.release_locks(owner).await
     }
 
     async fn discard(&self) {
-        let mut state = self.state.lock().await;
-        state.text_files.clear();
-        state.binary_files.clear();
-        state.dirs.clear();
-        state.locks.clear();
+        self.state.lock().await.discard().await
     }
 
     async fn version(&self) -> u64 {
-        let state = self.state.lock().await;
-        state.version
+        self.state.lock().await.version()
     }
 }
 
@@ -448,10 +1755,29 @@ impl VirtualFileSystem for RealFileSystem {
         Ok(tokio::fs::metadata(path).await.is_ok())
     }
 
+    async fn entry_kind(&self, path: &Path) -> Option<EntryKind> {
+        Whiteouts::new().disk_kind(path).await
+    }
+
     async fn create_dir_all(&self, path: &Path) -> Result<()> {
         Ok(tokio::fs::create_dir_all(path).await?)
     }
 
+    async fn remove_file(&self, path: &Path, _owner: &str) -> Result<()> {
+        Ok(tokio::fs::remove_file(path).await?)
+    }
+
+    async fn remove_dir_all(&self, path: &Path, _owner: &str) -> Result<()> {
+        Ok(tokio::fs::remove_dir_all(path).await?)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, _owner: &str) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        Ok(tokio::fs::rename(from, to).await?)
+    }
+
     async fn flush(&self) -> Result<FlushReport> {
         Ok(FlushReport::default())
     }
@@ -464,6 +1790,8 @@ impl VirtualFileSystem for RealFileSystem {
         Ok(LockStatus::Acquired)
     }
 
+    async fn release_lock(&self, _path: &Path, _owner: &str) {}
+
     async fn release_locks(&self, _owner: &str) {}
 
     async fn discard(&self) {}
@@ -562,9 +1890,10 @@ impl FileWatcher for OverlayFs {
 #[cfg(test)]
 mod tests {
     use super::{
-        FileEventType, FileWatcher, LockStatus, OverlayFs, PendingChange, RealFileSystem,
-        VirtualFileSystem,
+        overlay_diff, FileEventType, FileWatcher, LockStatus, OverlayFs, PendingChange,
+        RealFileSystem, VirtualFileSystem,
     };
+    use crate::ports::outbound::vfs_journal::{JournalEntry, VfsJournal};
     use anyhow::Result;
     use tempfile::tempdir;
     use tokio::time::Duration;
@@ -789,4 +2118,374 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn remove_file_hides_disk_entry_until_flush() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("old.txt");
+        tokio::fs::write(&file, "stale").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.remove_file(&file, "agent-a").await?;
+
+        assert!(!vfs.exists(&file).await?);
+        assert!(vfs.read(&file).await.is_err());
+        assert!(!vfs.list(tmp.path()).await?.contains(&file));
+        assert!(file.exists());
+        assert_eq!(
+            vfs.pending_changes().await,
+            vec![PendingChange::RemoveFile { path: file.clone() }]
+        );
+
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.removed_paths, vec![file.clone()]);
+        assert!(!file.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_missing_file_is_not_found() -> Result<()> {
+        let tmp = tempdir()?;
+        let vfs = OverlayFs::new();
+        let err = vfs
+            .remove_file(&tmp.path().join("ghost.txt"), "agent-a")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+            Some(std::io::ErrorKind::NotFound)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_dir_all_drops_overlay_and_disk_entries() -> Result<()> {
+        let tmp = tempdir()?;
+        let dir = tmp.path().join("build");
+        tokio::fs::create_dir_all(dir.join("out")).await?;
+        tokio::fs::write(dir.join("out").join("a.o"), "obj").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.write_string(&dir.join("new.txt"), "x".to_string(), "agent-a")
+            .await?;
+        vfs.remove_dir_all(&dir, "agent-a").await?;
+
+        assert!(!vfs.exists(&dir).await?);
+        assert!(!vfs.exists(&dir.join("out").join("a.o")).await?);
+        assert_eq!(
+            vfs.pending_changes().await,
+            vec![PendingChange::RemoveDir { path: dir.clone() }]
+        );
+
+        // Writing below a removed directory recreates it from scratch.
+        vfs.write_string(&dir.join("fresh.txt"), "y".to_string(), "agent-a")
+            .await?;
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert!(!dir.join("out").exists());
+        assert_eq!(tokio::fs::read_to_string(dir.join("fresh.txt")).await?, "y");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_moves_disk_file() -> Result<()> {
+        let tmp = tempdir()?;
+        let from = tmp.path().join("a.rs");
+        let to = tmp.path().join("src").join("b.rs");
+        tokio::fs::write(&from, "fn a() {}").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.rename(&from, &to, "agent-a").await?;
+
+        assert!(!vfs.exists(&from).await?);
+        assert_eq!(vfs.read_to_string(&to).await?, "fn a() {}");
+        let pending = vfs.pending_changes().await;
+        assert!(pending.contains(&PendingChange::Rename {
+            from: from.clone(),
+            to: to.clone()
+        }));
+
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert!(!from.exists());
+        assert_eq!(tokio::fs::read_to_string(&to).await?, "fn a() {}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_moves_directory_with_overlay_changes() -> Result<()> {
+        let tmp = tempdir()?;
+        let from = tmp.path().join("pkg");
+        let to = tmp.path().join("lib");
+        tokio::fs::create_dir_all(from.join("nested")).await?;
+        tokio::fs::write(from.join("nested").join("disk.txt"), "disk").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.write_string(&from.join("overlay.txt"), "overlay".to_string(), "agent-a")
+            .await?;
+        vfs.rename(&from, &to, "agent-a").await?;
+
+        assert!(!vfs.exists(&from).await?);
+        assert_eq!(
+            vfs.read_to_string(&to.join("nested").join("disk.txt"))
+                .await?,
+            "disk"
+        );
+        assert_eq!(
+            vfs.read_to_string(&to.join("overlay.txt")).await?,
+            "overlay"
+        );
+
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert!(!from.exists());
+        assert_eq!(
+            tokio::fs::read_to_string(to.join("nested").join("disk.txt")).await?,
+            "disk"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(to.join("overlay.txt")).await?,
+            "overlay"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_and_rename_respect_locks() -> Result<()> {
+        let tmp = tempdir()?;
+        let dir = tmp.path().join("shared");
+        let file = dir.join("locked.txt");
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&file, "v1").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.acquire_lock(&file, "agent-a").await?;
+
+        for err in [
+            vfs.remove_file(&file, "agent-b").await.unwrap_err(),
+            vfs.remove_dir_all(&dir, "agent-b").await.unwrap_err(),
+            vfs.rename(&file, &tmp.path().join("x.txt"), "agent-b")
+                .await
+                .unwrap_err(),
+        ] {
+            assert!(err.to_string().contains("lock conflict"));
+        }
+        assert!(vfs.pending_changes().await.is_empty());
+
+        vfs.remove_file(&file, "agent-a").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_flush_rolls_back_every_change() -> Result<()> {
+        let tmp = tempdir()?;
+        let removed = tmp.path().join("removed.txt");
+        let blocker = tmp.path().join("blocker");
+        tokio::fs::write(&removed, "keep me").await?;
+        tokio::fs::write(&blocker, "a file, not a dir").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.remove_file(&removed, "agent-a").await?;
+        vfs.write_string(&tmp.path().join("a-new.txt"), "new".to_string(), "agent-a")
+            .await?;
+        // Cannot be written: its parent is a regular file.
+        vfs.write_string(&blocker.join("child.txt"), "x".to_string(), "agent-a")
+            .await?;
+
+        let report = vfs.flush().await?;
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.written_files.is_empty());
+        assert_eq!(tokio::fs::read_to_string(&removed).await?, "keep me");
+        assert!(!tmp.path().join("a-new.txt").exists());
+        assert_eq!(vfs.pending_changes().await.len(), 3);
+        assert_eq!(vfs.version().await, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn real_fs_remove_and_rename() -> Result<()> {
+        let tmp = tempdir()?;
+        let from = tmp.path().join("a.txt");
+        let to = tmp.path().join("moved").join("b.txt");
+        let vfs = RealFileSystem;
+
+        vfs.write(&from, b"data".to_vec(), "owner").await?;
+        vfs.rename(&from, &to, "owner").await?;
+        assert!(!from.exists());
+        assert_eq!(vfs.read(&to).await?, b"data");
+
+        vfs.remove_file(&to, "owner").await?;
+        assert!(!to.exists());
+        vfs.remove_dir_all(&tmp.path().join("moved"), "owner")
+            .await?;
+        assert!(!tmp.path().join("moved").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn diff_renders_git_style_patches() -> Result<()> {
+        let tmp = tempdir()?;
+        let root = tmp.path();
+        tokio::fs::write(root.join("edit.txt"), "a\nb\nc\n").await?;
+        tokio::fs::write(root.join("gone.txt"), "bye\n").await?;
+        tokio::fs::write(root.join("old.rs"), "fn f() {}\n").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.write_string(&root.join("edit.txt"), "a\nB\nc\n".to_string(), "agent-a")
+            .await?;
+        vfs.remove_file(&root.join("gone.txt"), "agent-a").await?;
+        vfs.rename(&root.join("old.rs"), &root.join("src/new.rs"), "agent-a")
+            .await?;
+
+        let diff = overlay_diff(&vfs, root).await?;
+        assert_eq!(
+            diff,
+            "diff --git a/edit.txt b/edit.txt\nindex de98044..7be73ce 100644\n\
+             --- a/edit.txt\n+++ b/edit.txt\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n\
+             diff --git a/gone.txt b/gone.txt\ndeleted file mode 100644\n\
+             index b023018..0000000\n--- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n\
+             diff --git a/old.rs b/src/new.rs\nsimilarity index 100%\n\
+             rename from old.rs\nrename to src/new.rs\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn diff_renders_empty_and_binary_files_like_git() -> Result<()> {
+        let tmp = tempdir()?;
+        let root = tmp.path();
+        tokio::fs::write(root.join("image.bin"), [0u8, 159, 146, 150]).await?;
+
+        let vfs = OverlayFs::new();
+        vfs.write(&root.join("empty.txt"), Vec::new(), "agent-a")
+            .await?;
+        vfs.write(&root.join("image.bin"), vec![0, 255], "agent-a")
+            .await?;
+
+        let diff = overlay_diff(&vfs, root).await?;
+        let blob = |data: &[u8]| {
+            git2::Oid::hash_object(git2::ObjectType::Blob, data)
+                .unwrap()
+                .to_string()[..7]
+                .to_string()
+        };
+        assert_eq!(
+            diff,
+            format!(
+                "diff --git a/empty.txt b/empty.txt\nnew file mode 100644\n\
+                 index 0000000..e69de29\n\
+                 diff --git a/image.bin b/image.bin\nindex {}..{} 100644\n\
+                 Binary files a/image.bin and b/image.bin differ\n",
+                blob(&[0, 159, 146, 150]),
+                blob(&[0, 255])
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flush_merges_disjoint_disk_changes() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("lib.rs");
+        tokio::fs::write(&file, "one\ntwo\nthree\nfour\nfive\n").await?;
+
+        let vfs = OverlayFs::new();
+        let content = vfs.read_to_string(&file).await?;
+        vfs.write_string(&file, content.replace("one", "ONE"), "agent-a")
+            .await?;
+        tokio::fs::write(&file, "one\ntwo\nthree\nfour\nFIVE\n").await?;
+
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.merged_files, vec![file.clone()]);
+        assert_eq!(
+            tokio::fs::read_to_string(&file).await?,
+            "ONE\ntwo\nthree\nfour\nFIVE\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flush_reports_conflicts_until_rewritten() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("config.toml");
+        let other = tmp.path().join("other.txt");
+        tokio::fs::write(&file, "port = 1\n").await?;
+
+        let vfs = OverlayFs::new();
+        vfs.write_string(&file, "port = 2\n".to_string(), "agent-a")
+            .await?;
+        vfs.write_string(&other, "untouched\n".to_string(), "agent-a")
+            .await?;
+        tokio::fs::write(&file, "port = 3\n").await?;
+
+        let report = vfs.flush().await?;
+        assert_eq!(report.conflicts.len(), 1);
+        let hunk = &report.conflicts[0].hunks[0];
+        assert_eq!(
+            (hunk.base.as_str(), hunk.ours.as_str(), hunk.theirs.as_str()),
+            ("port = 1\n", "port = 2\n", "port = 3\n")
+        );
+        assert_eq!(report.errors[0].operation, "merge");
+        assert_eq!(tokio::fs::read_to_string(&file).await?, "port = 3\n");
+        assert!(!other.exists());
+        assert_eq!(vfs.flush().await?.conflicts.len(), 1);
+
+        vfs.write_string(&file, "port = 4\n".to_string(), "agent-a")
+            .await?;
+        let report = vfs.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(tokio::fs::read_to_string(&file).await?, "port = 4\n");
+        assert!(other.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recover_replays_journaled_changes() -> Result<()> {
+        let tmp = tempdir()?;
+        let kept = tmp.path().join("kept.txt");
+        let doomed = tmp.path().join("doomed.txt");
+        let merged = tmp.path().join("merged.txt");
+        tokio::fs::write(&doomed, "bye\n").await?;
+        tokio::fs::write(&merged, "a\nb\nc\n").await?;
+
+        let vfs = OverlayFs::recover(VfsJournal::for_agent(tmp.path(), "agent-a")).await?;
+        vfs.write_string(&kept, "hello\n".to_string(), "agent-a")
+            .await?;
+        vfs.write_string(&merged, "A\nb\nc\n".to_string(), "agent-a")
+            .await?;
+        vfs.remove_file(&doomed, "agent-a").await?;
+        vfs.acquire_lock(&tmp.path().join("locked.txt"), "agent-a")
+            .await?;
+        let pending = vfs.pending_changes().await;
+        drop(vfs);
+
+        // Disk moves on while the agent is down; the journaled base still merges it.
+        tokio::fs::write(&merged, "a\nb\nC\n").await?;
+        let recovered = OverlayFs::recover(VfsJournal::for_agent(tmp.path(), "agent-a")).await?;
+        assert_eq!(recovered.pending_changes().await, pending);
+        assert_eq!(
+            recovered
+                .acquire_lock(&tmp.path().join("locked.txt"), "agent-b")
+                .await?,
+            LockStatus::HeldByOther {
+                owner: "agent-a".to_string()
+            }
+        );
+
+        let report = recovered.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.merged_files, vec![merged.clone()]);
+        assert_eq!(tokio::fs::read_to_string(&merged).await?, "A\nb\nC\n");
+        assert_eq!(tokio::fs::read_to_string(&kept).await?, "hello\n");
+        assert!(!doomed.exists());
+
+        let mut journal = VfsJournal::for_agent(tmp.path(), "agent-a");
+        assert!(matches!(
+            journal.load().await?.as_slice(),
+            [JournalEntry::Checkpoint { version: 1, .. }]
+        ));
+        Ok(())
+    }
 }
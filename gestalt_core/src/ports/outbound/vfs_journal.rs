@@ -0,0 +1,422 @@
+//! Write-ahead journal for VFS overlays.
+//!
+//! Each agent's acknowledged overlay changes are appended as JSON lines to
+//! `<workspace>/.gestalt/vfs/<agent>.wal` before the caller sees success, so
+//! uncommitted edits survive a crash or restart and are replayed when the
+//! overlay is recovered. A successful flush or a discard compacts the journal
+//! down to a single checkpoint.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+use super::vfs::MergeBases;
+
+/// Journal directory, relative to the workspace root.
+pub const JOURNAL_DIR: &str = ".gestalt/vfs";
+
+const JOURNAL_EXTENSION: &str = "wal";
+
+/// One journaled overlay change.
+///
+/// Removals and renames of on-disk entries are replayed against the disk as
+/// it is at recovery time; `Base` entries restore the merge bases so the
+/// recovered overlay still three-way merges disk changes made meanwhile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalEntry {
+    /// Version and lock table after a flush or discard; starts a compacted journal.
+    Checkpoint {
+        version: u64,
+        locks: Vec<(PathBuf, String)>,
+    },
+    WriteText {
+        path: PathBuf,
+        owner: String,
+        content: String,
+    },
+    WriteBytes {
+        path: PathBuf,
+        owner: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    /// A unified diff applied to `path`; `content` is the result and is what gets replayed.
+    Patch {
+        path: PathBuf,
+        owner: String,
+        patch: String,
+        content: String,
+    },
+    CreateDir {
+        path: PathBuf,
+    },
+    RemoveFile {
+        path: PathBuf,
+        owner: String,
+    },
+    RemoveDir {
+        path: PathBuf,
+        owner: String,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        owner: String,
+    },
+    Lock {
+        path: PathBuf,
+        owner: String,
+    },
+    ReleaseLocks {
+        owner: String,
+    },
+    /// Release of a single lock, e.g. when a layer promotion is rolled back.
+    Unlock {
+        path: PathBuf,
+        owner: String,
+    },
+    /// Disk content `path` was derived from; `None` if it did not exist.
+    Base {
+        path: PathBuf,
+        #[serde(with = "base64_opt_bytes")]
+        content: Option<Vec<u8>>,
+    },
+}
+
+/// Append-only journal file of a single agent's overlay.
+#[derive(Debug)]
+pub struct VfsJournal {
+    path: PathBuf,
+    /// Paths whose merge base is already in the journal.
+    based: HashSet<PathBuf>,
+}
+
+impl VfsJournal {
+    /// Journal directory of `workspace`.
+    pub fn dir(workspace: &Path) -> PathBuf {
+        workspace.join(JOURNAL_DIR)
+    }
+
+    /// Journal of `agent_id` under `workspace`.
+    pub fn for_agent(workspace: &Path, agent_id: &str) -> Self {
+        let name: String = agent_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Self::open(Self::dir(workspace).join(format!("{name}.{JOURNAL_EXTENSION}")))
+    }
+
+    /// Journal stored at `path`; the file is created on the first append.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            based: HashSet::new(),
+        }
+    }
+
+    /// Every journal under `workspace`, sorted by agent.
+    pub async fn discover(workspace: &Path) -> Result<Vec<Self>> {
+        let dir = Self::dir(workspace);
+        let mut journals = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(journals),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == JOURNAL_EXTENSION) {
+                journals.push(Self::open(path));
+            }
+        }
+        journals.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(journals)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Agent the journal belongs to, as encoded in its file name.
+    pub fn agent(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Read every complete entry.
+    ///
+    /// A torn or corrupt tail (a crash mid-append) is cut off the file so later
+    /// appends start on a clean line.
+    pub async fn load(&mut self) -> Result<Vec<JournalEntry>> {
+        let Some(raw) = self.read_raw().await? else {
+            return Ok(Vec::new());
+        };
+        let (entries, valid) = self.parse(&raw);
+        if valid < raw.len() {
+            warn!(
+                "Truncating {} unreadable byte(s) from VFS journal {}",
+                raw.len() - valid,
+                self.path.display()
+            );
+            let file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(&self.path)
+                .await?;
+            file.set_len(valid as u64).await?;
+            file.sync_data().await?;
+        }
+
+        self.based = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Base { path, .. } => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Every complete entry, leaving the file untouched even if its tail is
+    /// torn; for inspecting a journal another process may still append to.
+    pub async fn read(&self) -> Result<Vec<JournalEntry>> {
+        Ok(match self.read_raw().await? {
+            Some(raw) => self.parse(&raw).0,
+            None => Vec::new(),
+        })
+    }
+
+    async fn read_raw(&self) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(raw) => Ok(Some(raw)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", self.path.display())),
+        }
+    }
+
+    /// Entries of `raw` up to the first unreadable line, and the length they span.
+    fn parse(&self, raw: &[u8]) -> (Vec<JournalEntry>, usize) {
+        let mut entries = Vec::new();
+        let mut valid = 0;
+        while let Some(end) = raw[valid..].iter().position(|b| *b == b'\n') {
+            let line = &raw[valid..valid + end];
+            match serde_json::from_slice::<JournalEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    warn!(
+                        "Corrupt VFS journal entry in {} at byte {}: {}",
+                        self.path.display(),
+                        valid,
+                        e
+                    );
+                    break;
+                }
+            }
+            valid += end + 1;
+        }
+        (entries, valid)
+    }
+
+    /// Durably append `entry`.
+    pub async fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("opening {}", self.path.display()))?;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
+        Ok(())
+    }
+
+    /// Append the merge base of `path`, once, if `bases` knows it.
+    pub async fn append_base(&mut self, bases: &MergeBases, path: &Path) -> Result<()> {
+        if self.based.contains(path) {
+            return Ok(());
+        }
+        let Some(content) = bases.base(path) else {
+            return Ok(());
+        };
+        self.append(&JournalEntry::Base {
+            path: path.to_path_buf(),
+            content: content.map(<[u8]>::to_vec),
+        })
+        .await?;
+        self.based.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Atomically replace the journal with a checkpoint of an empty overlay.
+    pub async fn checkpoint(
+        &mut self,
+        version: u64,
+        locks: &HashMap<PathBuf, String>,
+    ) -> Result<()> {
+        let mut locks: Vec<_> = locks
+            .iter()
+            .map(|(path, owner)| (path.clone(), owner.clone()))
+            .collect();
+        locks.sort();
+        let mut line = serde_json::to_vec(&JournalEntry::Checkpoint { version, locks })?;
+        line.push(b'\n');
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let staging = self.path.with_extension(format!("{JOURNAL_EXTENSION}.tmp"));
+        let mut file = tokio::fs::File::create(&staging).await?;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
+        tokio::fs::rename(&staging, &self.path).await?;
+        self.based.clear();
+        Ok(())
+    }
+
+    /// Delete the journal file.
+    pub async fn remove(self) -> Result<()> {
+        match tokio::fs::remove_file(&self.path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+mod base64_opt_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        data: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => serializer.serialize_some(&STANDARD.encode(data)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| STANDARD.decode(encoded).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn entries_roundtrip_through_the_journal_file() -> Result<()> {
+        let dir = tempdir()?;
+        let mut journal = VfsJournal::for_agent(dir.path(), "agent/1");
+        let entries = vec![
+            JournalEntry::WriteBytes {
+                path: PathBuf::from("a.bin"),
+                owner: "agent/1".into(),
+                data: vec![0, 159, 146, 150],
+            },
+            JournalEntry::Base {
+                path: PathBuf::from("a.bin"),
+                content: None,
+            },
+            JournalEntry::ReleaseLocks {
+                owner: "agent/1".into(),
+            },
+        ];
+        for entry in &entries {
+            journal.append(entry).await?;
+        }
+
+        assert_eq!(journal.agent(), "agent_1");
+        assert_eq!(journal.load().await?, entries);
+        let found = VfsJournal::discover(dir.path()).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path(), journal.path());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn load_truncates_a_torn_tail() -> Result<()> {
+        let dir = tempdir()?;
+        let mut journal = VfsJournal::for_agent(dir.path(), "agent");
+        let entry = JournalEntry::CreateDir {
+            path: PathBuf::from("src"),
+        };
+        journal.append(&entry).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(journal.path())
+            .await?;
+        file.write_all(br#"{"op":"write_text","path":"#).await?;
+        file.flush().await?;
+
+        let torn_len = tokio::fs::metadata(journal.path()).await?.len();
+        assert_eq!(journal.read().await?, vec![entry.clone()]);
+        assert_eq!(tokio::fs::metadata(journal.path()).await?.len(), torn_len);
+
+        assert_eq!(journal.load().await?, vec![entry.clone()]);
+        journal.append(&entry).await?;
+        assert_eq!(journal.load().await?, vec![entry.clone(), entry]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkpoint_compacts_the_journal() -> Result<()> {
+        let dir = tempdir()?;
+        let mut journal = VfsJournal::for_agent(dir.path(), "agent");
+        journal
+            .append(&JournalEntry::CreateDir {
+                path: PathBuf::from("src"),
+            })
+            .await?;
+        let locks = HashMap::from([(PathBuf::from("kept.rs"), "agent".to_string())]);
+        journal.checkpoint(4, &locks).await?;
+
+        assert_eq!(
+            journal.load().await?,
+            vec![JournalEntry::Checkpoint {
+                version: 4,
+                locks: vec![(PathBuf::from("kept.rs"), "agent".to_string())],
+            }]
+        );
+        journal.remove().await?;
+        assert!(VfsJournal::discover(dir.path()).await?.is_empty());
+        Ok(())
+    }
+}
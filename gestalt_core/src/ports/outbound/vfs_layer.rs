@@ -0,0 +1,804 @@
+//! Copy-on-write VFS layers.
+//!
+//! A [`LayerFs`] stacks one agent's (or task's) private changes over a parent
+//! file system, which is usually the shared overlay or another layer. Reads
+//! fall through to the parent until the layer writes a path; nothing reaches
+//! the parent until the layer is promoted. Named snapshots capture the layer
+//! and can be rolled back to, so an agent can branch off, try something and
+//! return.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::vfs::{
+    not_found, overlay_diff_against, rebase_keys, rebase_path, tree, EntryKind, FlushError,
+    FlushPlan, FlushReport, LockStatus, MergeBases, PendingChange, VirtualFileSystem, Whiteouts,
+};
+
+/// Pending contents of a layer. File contents are shared, so snapshots are cheap.
+#[derive(Debug, Clone, Default)]
+struct LayerContents {
+    files: HashMap<PathBuf, Arc<[u8]>>,
+    dirs: HashSet<PathBuf>,
+    whiteouts: Whiteouts,
+    bases: MergeBases,
+}
+
+impl LayerContents {
+    fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.dirs.is_empty() && self.whiteouts.is_empty()
+    }
+
+    fn has_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+            || self
+                .files
+                .keys()
+                .chain(self.dirs.iter())
+                .any(|p| p != path && p.starts_with(path))
+    }
+}
+
+#[derive(Debug, Default)]
+struct LayerState {
+    contents: LayerContents,
+    /// Oldest first; names are unique.
+    snapshots: Vec<(String, LayerContents)>,
+    version: u64,
+}
+
+/// A copy-on-write layer over `parent`.
+///
+/// Writes stay private to the layer and take no locks; sibling layers over
+/// the same parent coordinate through explicit locks, which live in the
+/// parent and are checked when a layer is promoted.
+/// [`VirtualFileSystem::flush`] promotes the layer.
+pub struct LayerFs {
+    name: String,
+    parent: Arc<dyn VirtualFileSystem>,
+    state: Mutex<LayerState>,
+}
+
+impl LayerFs {
+    /// Empty layer called `name` (the owner of its promoted changes) over `parent`.
+    pub fn new(name: impl Into<String>, parent: Arc<dyn VirtualFileSystem>) -> Self {
+        Self {
+            name: name.into(),
+            parent,
+            state: Mutex::new(LayerState::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> &Arc<dyn VirtualFileSystem> {
+        &self.parent
+    }
+
+    /// Record the current layer contents as snapshot `name`, replacing an older one.
+    pub async fn snapshot(&self, name: &str) {
+        let mut state = self.state.lock().await;
+        state.snapshots.retain(|(existing, _)| existing != name);
+        let contents = state.contents.clone();
+        state.snapshots.push((name.to_string(), contents));
+    }
+
+    /// Restore snapshot `name`, dropping every change and snapshot made after it.
+    pub async fn rollback(&self, name: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(index) = state
+            .snapshots
+            .iter()
+            .position(|(existing, _)| existing == name)
+        else {
+            anyhow::bail!("no snapshot named '{}' in layer '{}'", name, self.name);
+        };
+        state.snapshots.truncate(index + 1);
+        state.contents = state.snapshots[index].1.clone();
+        Ok(())
+    }
+
+    /// Snapshot names, oldest first.
+    pub async fn snapshots(&self) -> Vec<String> {
+        let state = self.state.lock().await;
+        state
+            .snapshots
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Merge the layer into its parent; same as [`VirtualFileSystem::flush`].
+    ///
+    /// Paths the parent changed since the layer first saw them are three-way
+    /// merged; conflicts, or locks held by another owner, leave the contents
+    /// of both the layer and the parent untouched. The change set is applied
+    /// all-or-nothing: if a step fails, the steps already applied are undone
+    /// and the locks taken for the promotion are given back. On success the
+    /// layer is empty, its snapshots are dropped and the locks it held in the
+    /// parent are released.
+    pub async fn promote(&self) -> Result<FlushReport> {
+        let mut state = self.state.lock().await;
+
+        let mut dirs: Vec<PathBuf> = state.contents.dirs.iter().cloned().collect();
+        dirs.sort();
+        let mut writes: Vec<(PathBuf, Vec<u8>)> = state
+            .contents
+            .files
+            .iter()
+            .map(|(path, data)| (path.clone(), data.to_vec()))
+            .collect();
+        writes.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut plan = FlushPlan {
+            removals: state.contents.whiteouts.removals(),
+            dirs,
+            writes,
+        };
+
+        let (merged_files, conflicts) = state
+            .contents
+            .bases
+            .reconcile_in(&mut plan, self.parent.as_ref())
+            .await;
+        if !conflicts.is_empty() {
+            return Ok(FlushReport::from_conflicts(conflicts));
+        }
+
+        let mut report = FlushReport::default();
+        let mut held = HashSet::new();
+        for path in plan
+            .removals
+            .iter()
+            .chain(plan.writes.iter().map(|(p, _)| p))
+        {
+            let error = match self.parent.acquire_lock(path, &self.name).await {
+                Ok(LockStatus::AlreadyHeldByOwner) => {
+                    held.insert(path.clone());
+                    continue;
+                }
+                Ok(LockStatus::Acquired) => continue,
+                Ok(LockStatus::HeldByOther { owner }) => format!("held by '{}'", owner),
+                Err(e) => e.to_string(),
+            };
+            report.errors.push(FlushError {
+                path: path.clone(),
+                operation: "lock",
+                error,
+            });
+        }
+        let mut touched: Vec<PathBuf> = plan
+            .removals
+            .iter()
+            .chain(plan.dirs.iter())
+            .chain(plan.writes.iter().map(|(p, _)| p))
+            .cloned()
+            .collect();
+        if report.has_errors() {
+            self.release_taken(&touched, &held).await;
+            return Ok(report);
+        }
+
+        let mut undo = Vec::new();
+        if let Err(error) = self.apply(plan, &mut report, &mut undo).await {
+            for step in undo.into_iter().rev() {
+                touched.extend(step.paths());
+                if let Err(e) = step.revert(self.parent.as_ref(), &self.name).await {
+                    warn!("Failed to undo promotion step of '{}': {}", self.name, e);
+                }
+            }
+            self.release_taken(&touched, &held).await;
+            return Ok(FlushReport {
+                errors: vec![error],
+                ..FlushReport::default()
+            });
+        }
+        report.merged_files = merged_files;
+
+        self.parent.release_locks(&self.name).await;
+        state.contents = LayerContents::default();
+        state.snapshots.clear();
+        if !report.written_files.is_empty()
+            || !report.created_dirs.is_empty()
+            || !report.removed_paths.is_empty()
+        {
+            state.version += 1;
+        }
+        Ok(report)
+    }
+
+    /// Apply `plan` to the parent step by step, stopping at the first failure.
+    /// Each applied step is recorded in `undo` with what it replaced.
+    async fn apply(
+        &self,
+        plan: FlushPlan,
+        report: &mut FlushReport,
+        undo: &mut Vec<UndoStep>,
+    ) -> std::result::Result<(), FlushError> {
+        let parent = self.parent.as_ref();
+        let fail = |path: &Path, operation, e: anyhow::Error| FlushError {
+            path: path.to_path_buf(),
+            operation,
+            error: e.to_string(),
+        };
+
+        for path in plan.removals {
+            let Some(kind) = parent.entry_kind(&path).await else {
+                continue;
+            };
+            let step = UndoStep::capture_removal(parent, &path, kind)
+                .await
+                .map_err(|e| fail(&path, "remove", e))?;
+            let removed = match kind {
+                EntryKind::Dir => parent.remove_dir_all(&path, &self.name).await,
+                EntryKind::File => parent.remove_file(&path, &self.name).await,
+            };
+            removed.map_err(|e| fail(&path, "remove", e))?;
+            undo.push(step);
+            report.removed_paths.push(path);
+        }
+        for path in plan.dirs {
+            let existed = parent.entry_kind(&path).await.is_some();
+            parent
+                .create_dir_all(&path)
+                .await
+                .map_err(|e| fail(&path, "create_dir", e))?;
+            if !existed {
+                undo.push(UndoStep::CreatedDir(path.clone()));
+            }
+            report.created_dirs.push(path);
+        }
+        for (path, data) in plan.writes {
+            let previous = match parent.entry_kind(&path).await {
+                Some(EntryKind::File) => Some(
+                    parent
+                        .read(&path)
+                        .await
+                        .map_err(|e| fail(&path, "write", e))?,
+                ),
+                _ => None,
+            };
+            parent
+                .write(&path, data, &self.name)
+                .await
+                .map_err(|e| fail(&path, "write", e))?;
+            undo.push(UndoStep::Wrote {
+                path: path.clone(),
+                previous,
+            });
+            report.written_files.push(path);
+        }
+        Ok(())
+    }
+
+    /// Give back the parent locks a failed promotion took on `touched` paths,
+    /// keeping the ones the layer already `held` before it started.
+    async fn release_taken(&self, touched: &[PathBuf], held: &HashSet<PathBuf>) {
+        for path in touched {
+            if !held.contains(path) {
+                self.parent.release_lock(path, &self.name).await;
+            }
+        }
+    }
+
+    /// Kind of the parent's entry at `path`, unless the layer hides it.
+    async fn parent_kind(&self, contents: &LayerContents, path: &Path) -> Option<EntryKind> {
+        if contents.whiteouts.hides(path) {
+            return None;
+        }
+        self.parent.entry_kind(path).await
+    }
+
+    /// Whether the layer has nothing to promote.
+    pub async fn is_clean(&self) -> bool {
+        self.state.lock().await.contents.is_empty()
+    }
+}
+
+/// A promotion step applied to the parent, with what it replaced.
+enum UndoStep {
+    Removed {
+        dirs: Vec<PathBuf>,
+        files: Vec<(PathBuf, Vec<u8>)>,
+    },
+    CreatedDir(PathBuf),
+    Wrote {
+        path: PathBuf,
+        previous: Option<Vec<u8>>,
+    },
+}
+
+impl UndoStep {
+    /// Contents of the parent entry at `path` that a removal is about to drop.
+    async fn capture_removal(
+        parent: &dyn VirtualFileSystem,
+        path: &Path,
+        kind: EntryKind,
+    ) -> Result<Self> {
+        let (mut dirs, paths) = match kind {
+            EntryKind::Dir => tree(parent, path).await?,
+            EntryKind::File => (Vec::new(), vec![path.to_path_buf()]),
+        };
+        if kind == EntryKind::Dir {
+            dirs.insert(0, path.to_path_buf());
+        }
+        let mut files = Vec::with_capacity(paths.len());
+        for file in paths {
+            let data = parent.read(&file).await?;
+            files.push((file, data));
+        }
+        Ok(UndoStep::Removed { dirs, files })
+    }
+
+    /// Paths the step or its revert may lock in the parent.
+    fn paths(&self) -> Vec<PathBuf> {
+        match self {
+            UndoStep::Removed { dirs, files } => dirs
+                .iter()
+                .cloned()
+                .chain(files.iter().map(|(path, _)| path.clone()))
+                .collect(),
+            UndoStep::CreatedDir(path) | UndoStep::Wrote { path, .. } => vec![path.clone()],
+        }
+    }
+
+    async fn revert(self, parent: &dyn VirtualFileSystem, owner: &str) -> Result<()> {
+        match self {
+            UndoStep::Removed { dirs, files } => {
+                for dir in dirs {
+                    parent.create_dir_all(&dir).await?;
+                }
+                for (path, data) in files {
+                    parent.write(&path, data, owner).await?;
+                }
+            }
+            UndoStep::CreatedDir(path) => parent.remove_dir_all(&path, owner).await?,
+            UndoStep::Wrote {
+                path,
+                previous: Some(data),
+            } => parent.write(&path, data, owner).await?,
+            UndoStep::Wrote {
+                path,
+                previous: None,
+            } => parent.remove_file(&path, owner).await?,
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VirtualFileSystem for LayerFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let mut state = self.state.lock().await;
+        if let Some(data) = state.contents.files.get(path) {
+            return Ok(data.to_vec());
+        }
+        if state.contents.whiteouts.hides(path) {
+            return Err(not_found(path));
+        }
+        let data = self.parent.read(path).await?;
+        state.contents.bases.observe(path, &data);
+        Ok(data)
+    }
+
+    async fn write(&self, path: &Path, data: Vec<u8>, _owner: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state
+            .contents
+            .bases
+            .track_in(path, self.parent.as_ref())
+            .await;
+        state.contents.files.insert(path.to_path_buf(), data.into());
+        state.contents.whiteouts.restore(path);
+        Ok(())
+    }
+
+    async fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let state = self.state.lock().await;
+        let mut entries: HashSet<PathBuf> = state
+            .contents
+            .files
+            .keys()
+            .chain(state.contents.dirs.iter())
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect();
+        if !state.contents.whiteouts.hides(path) && self.parent.exists(path).await? {
+            for entry in self.parent.list(path).await? {
+                if !state.contents.whiteouts.hides(&entry) {
+                    entries.insert(entry);
+                }
+            }
+        }
+        let mut result: Vec<_> = entries.into_iter().collect();
+        result.sort();
+        Ok(result)
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        let state = self.state.lock().await;
+        if state.contents.files.contains_key(path) || state.contents.has_dir(path) {
+            return Ok(true);
+        }
+        Ok(self.parent_kind(&state.contents, path).await.is_some())
+    }
+
+    async fn entry_kind(&self, path: &Path) -> Option<EntryKind> {
+        let state = self.state.lock().await;
+        if state.contents.files.contains_key(path) {
+            Some(EntryKind::File)
+        } else if state.contents.has_dir(path) {
+            Some(EntryKind::Dir)
+        } else {
+            self.parent_kind(&state.contents, path).await
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.contents.dirs.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path, _owner: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let below = self.parent_kind(&state.contents, path).await;
+        if state.contents.has_dir(path) || below == Some(EntryKind::Dir) {
+            anyhow::bail!("'{}' is a directory", path.display());
+        }
+
+        let in_layer = state.contents.files.remove(path).is_some();
+        match below {
+            Some(_) => {
+                state
+                    .contents
+                    .bases
+                    .track_in(path, self.parent.as_ref())
+                    .await;
+                state.contents.whiteouts.remove_file(path);
+            }
+            None if in_layer => state.contents.whiteouts.forget(path),
+            None => return Err(not_found(path)),
+        }
+        Ok(())
+    }
+
+    async fn remove_dir_all(&self, path: &Path, _owner: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let below = self.parent_kind(&state.contents, path).await;
+        if state.contents.files.contains_key(path) || below == Some(EntryKind::File) {
+            anyhow::bail!("'{}' is not a directory", path.display());
+        }
+
+        let in_layer = state.contents.has_dir(path);
+        state.contents.files.retain(|p, _| !p.starts_with(path));
+        state.contents.dirs.retain(|p| !p.starts_with(path));
+        match below {
+            Some(_) => state.contents.whiteouts.remove_dir(path),
+            None if in_layer => state.contents.whiteouts.forget(path),
+            None => return Err(not_found(path)),
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, _owner: &str) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        if to.starts_with(from) {
+            anyhow::bail!(
+                "cannot move '{}' into itself ('{}')",
+                from.display(),
+                to.display()
+            );
+        }
+        let mut state = self.state.lock().await;
+        let below = self.parent_kind(&state.contents, from).await;
+        let kind = if state.contents.files.contains_key(from) {
+            EntryKind::File
+        } else if state.contents.has_dir(from) {
+            EntryKind::Dir
+        } else {
+            below.ok_or_else(|| not_found(from))?
+        };
+        let dest_is_dir = state.contents.has_dir(to)
+            || (!state.contents.files.contains_key(to)
+                && self.parent_kind(&state.contents, to).await == Some(EntryKind::Dir));
+        if dest_is_dir {
+            anyhow::bail!("destination '{}' is a directory", to.display());
+        }
+
+        let parent = self.parent.as_ref();
+        match kind {
+            EntryKind::File => {
+                if below.is_some() {
+                    state.contents.bases.track_in(from, parent).await;
+                }
+                state.contents.bases.track_in(to, parent).await;
+                let data = match state.contents.files.remove(from) {
+                    Some(data) => data,
+                    None => parent.read(from).await?.into(),
+                };
+                state.contents.files.insert(to.to_path_buf(), data);
+            }
+            EntryKind::Dir => {
+                if state.contents.files.contains_key(to)
+                    || self.parent_kind(&state.contents, to).await.is_some()
+                {
+                    anyhow::bail!("destination '{}' already exists", to.display());
+                }
+                // Copy the visible parent subtree into the layer, then re-root everything.
+                if below == Some(EntryKind::Dir) {
+                    let (dirs, files) = tree(parent, from).await?;
+                    for file in files {
+                        if state.contents.whiteouts.hides(&file)
+                            || state.contents.files.contains_key(&file)
+                        {
+                            continue;
+                        }
+                        let data = parent.read(&file).await?;
+                        state.contents.files.insert(file, data.into());
+                    }
+                    let contents = &mut state.contents;
+                    contents.dirs.extend(
+                        dirs.into_iter()
+                            .filter(|dir| !contents.whiteouts.hides(dir)),
+                    );
+                }
+                state.contents.dirs.insert(from.to_path_buf());
+                rebase_keys(&mut state.contents.files, from, to);
+                let dirs: Vec<PathBuf> = state.contents.dirs.drain().collect();
+                state.contents.dirs = dirs
+                    .into_iter()
+                    .map(|dir| rebase_path(&dir, from, to))
+                    .collect();
+            }
+        }
+        state
+            .contents
+            .whiteouts
+            .rename(from, to, kind, below.is_some());
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<FlushReport> {
+        self.promote().await
+    }
+
+    async fn pending_changes(&self) -> Vec<PendingChange> {
+        let state = self.state.lock().await;
+        let mut dirs: Vec<PathBuf> = state.contents.dirs.iter().cloned().collect();
+        dirs.sort();
+        let mut files: Vec<(PathBuf, usize)> = state
+            .contents
+            .files
+            .iter()
+            .map(|(path, data)| (path.clone(), data.len()))
+            .collect();
+        files.sort();
+
+        let mut pending: Vec<PendingChange> = dirs
+            .into_iter()
+            .map(|path| PendingChange::CreateDir { path })
+            .collect();
+        pending.extend(
+            files
+                .into_iter()
+                .map(|(path, bytes)| PendingChange::WriteFile { path, bytes }),
+        );
+        pending.extend(state.contents.whiteouts.pending_changes());
+        pending
+    }
+
+    /// Diff against the parent rather than the disk.
+    async fn diff(&self) -> Result<String> {
+        overlay_diff_against(self, self.parent.as_ref(), &std::env::current_dir()?).await
+    }
+
+    async fn acquire_lock(&self, path: &Path, owner: &str) -> Result<LockStatus> {
+        self.parent.acquire_lock(path, owner).await
+    }
+
+    async fn release_lock(&self, path: &Path, owner: &str) {
+        self.parent.release_lock(path, owner).await
+    }
+
+    async fn release_locks(&self, owner: &str) {
+        self.parent.release_locks(owner).await
+    }
+
+    /// Drop the pending contents; snapshots are kept and can still be rolled back to.
+    async fn discard(&self) {
+        let mut state = self.state.lock().await;
+        state.contents = LayerContents::default();
+    }
+
+    async fn version(&self) -> u64 {
+        self.state.lock().await.version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::outbound::vfs::{OverlayFs, RealFileSystem};
+    use tempfile::tempdir;
+
+    fn layer_over(parent: &Arc<OverlayFs>, name: &str) -> LayerFs {
+        LayerFs::new(name, parent.clone() as Arc<dyn VirtualFileSystem>)
+    }
+
+    #[tokio::test]
+    async fn layers_are_isolated_until_promoted() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("lib.rs");
+        tokio::fs::write(&file, "base\n").await?;
+        let base = Arc::new(OverlayFs::new());
+        let a = layer_over(&base, "agent-a");
+        let b = layer_over(&base, "agent-b");
+
+        a.write_string(&file, "from a\n".to_string(), "agent-a")
+            .await?;
+        a.remove_file(&file, "agent-a").await?;
+        a.write_string(&file, "from a\n".to_string(), "agent-a")
+            .await?;
+        assert_eq!(b.read_to_string(&file).await?, "base\n");
+        assert_eq!(base.read_to_string(&file).await?, "base\n");
+
+        let report = a.promote().await?;
+        assert_eq!(report.written_files, vec![file.clone()]);
+        assert!(a.is_clean().await);
+        assert_eq!(base.read_to_string(&file).await?, "from a\n");
+        assert_eq!(b.read_to_string(&file).await?, "from a\n");
+        // Promotion only reached the parent overlay, not the disk.
+        assert_eq!(tokio::fs::read_to_string(&file).await?, "base\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rollback_restores_a_snapshot() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("main.rs");
+        let extra = tmp.path().join("extra.rs");
+        let base = Arc::new(OverlayFs::new());
+        let layer = layer_over(&base, "agent");
+
+        layer
+            .write_string(&file, "v1\n".to_string(), "agent")
+            .await?;
+        layer.snapshot("first").await;
+        layer
+            .write_string(&file, "v2\n".to_string(), "agent")
+            .await?;
+        layer
+            .write_string(&extra, "extra\n".to_string(), "agent")
+            .await?;
+        layer.snapshot("second").await;
+
+        layer.rollback("first").await?;
+        assert_eq!(layer.read_to_string(&file).await?, "v1\n");
+        assert!(!layer.exists(&extra).await?);
+        assert_eq!(layer.snapshots().await, vec!["first".to_string()]);
+        assert!(layer.rollback("second").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn promote_merges_sibling_changes_and_respects_locks() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("config.toml");
+        tokio::fs::write(&file, "a = 1\nb = 2\nc = 3\nd = 4\n").await?;
+        let base = Arc::new(OverlayFs::new());
+        let a = layer_over(&base, "agent-a");
+        let b = layer_over(&base, "agent-b");
+
+        a.write_string(
+            &file,
+            "a = 10\nb = 2\nc = 3\nd = 4\n".to_string(),
+            "agent-a",
+        )
+        .await?;
+        b.write_string(
+            &file,
+            "a = 1\nb = 2\nc = 3\nd = 40\n".to_string(),
+            "agent-b",
+        )
+        .await?;
+        assert!(a.promote().await?.errors.is_empty());
+
+        let report = b.promote().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.merged_files, vec![file.clone()]);
+        assert_eq!(
+            base.read_to_string(&file).await?,
+            "a = 10\nb = 2\nc = 3\nd = 40\n"
+        );
+
+        base.acquire_lock(&file, "someone-else").await?;
+        a.write_string(
+            &file,
+            "a = 11\nb = 2\nc = 3\nd = 40\n".to_string(),
+            "agent-a",
+        )
+        .await?;
+        let report = a.promote().await?;
+        assert_eq!(report.errors[0].operation, "lock");
+        assert!(!a.is_clean().await);
+        assert_eq!(
+            base.read_to_string(&file).await?,
+            "a = 10\nb = 2\nc = 3\nd = 40\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_promote_releases_locks_and_undoes_applied_steps() -> Result<()> {
+        let tmp = tempdir()?;
+        let first = tmp.path().join("a.txt");
+        let second = tmp.path().join("b.txt");
+        let base = Arc::new(OverlayFs::new());
+        let layer = layer_over(&base, "agent");
+        layer.write(&first, b"a".to_vec(), "agent").await?;
+        layer.write(&second, b"b".to_vec(), "agent").await?;
+
+        base.acquire_lock(&second, "someone-else").await?;
+        assert_eq!(layer.promote().await?.errors[0].operation, "lock");
+        assert_eq!(
+            base.acquire_lock(&first, "third").await?,
+            LockStatus::Acquired
+        );
+
+        // A parent write failing midway leaves the parent as it was.
+        tokio::fs::write(&first, "old").await?;
+        tokio::fs::write(tmp.path().join("blocker"), "file").await?;
+        let disk = Arc::new(RealFileSystem) as Arc<dyn VirtualFileSystem>;
+        let layer = LayerFs::new("agent", disk);
+        layer.write(&first, b"new".to_vec(), "agent").await?;
+        layer
+            .write(&tmp.path().join("blocker/c.txt"), b"c".to_vec(), "agent")
+            .await?;
+        let report = layer.promote().await?;
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.written_files.is_empty());
+        assert_eq!(tokio::fs::read_to_string(&first).await?, "old");
+        assert!(!layer.is_clean().await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn layers_stack_and_diff_against_their_parent() -> Result<()> {
+        let tmp = tempdir()?;
+        let dir = tmp.path().join("src");
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join("a.rs"), "a\n").await?;
+        let base = Arc::new(OverlayFs::new());
+        let parent = Arc::new(layer_over(&base, "parent"));
+        let child = LayerFs::new("child", parent.clone() as Arc<dyn VirtualFileSystem>);
+
+        parent
+            .write_string(&dir.join("b.rs"), "b\n".to_string(), "parent")
+            .await?;
+        child.rename(&dir, &tmp.path().join("lib"), "child").await?;
+        assert_eq!(
+            child.list(&tmp.path().join("lib")).await?,
+            vec![tmp.path().join("lib/a.rs"), tmp.path().join("lib/b.rs")]
+        );
+        assert!(!child.exists(&dir).await?);
+
+        let diff = overlay_diff_against(&child, parent.as_ref(), tmp.path()).await?;
+        assert!(diff.contains("rename from src/a.rs\nrename to lib/a.rs"));
+        assert!(diff.contains("rename from src/b.rs\nrename to lib/b.rs"));
+
+        child.promote().await?;
+        assert!(parent.exists(&tmp.path().join("lib/b.rs")).await?);
+        assert!(!parent.exists(&dir.join("a.rs")).await?);
+        Ok(())
+    }
+}
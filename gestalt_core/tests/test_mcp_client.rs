@@ -0,0 +1,253 @@
+use gestalt_core::mcp::{
+    GestaltMcpClient, McpClient, McpError, McpRegistry, McpTransport, StdioTransport,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Mutex};
+
+/// Minimal MCP echo server speaking newline-delimited JSON-RPC.
+///
+/// Tools: `echo` (returns `message`), `fail` (tool-level error) and `slow`
+/// (never answers in time). `tools/list` is split over two pages. Every
+/// notification method received is forwarded on `seen`.
+async fn run_echo_server<R, W>(reader: R, writer: W, seen: mpsc::UnboundedSender<Value>)
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let writer = Arc::new(Mutex::new(writer));
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let msg: Value = match serde_json::from_str(&line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let Some(id) = msg.get("id").cloned() else {
+            let _ = seen.send(msg);
+            continue;
+        };
+        let writer = writer.clone();
+        tokio::spawn(async move {
+            let reply = echo_reply(&msg, id).await;
+            let mut out = serde_json::to_string(&reply).unwrap();
+            out.push('\n');
+            let mut w = writer.lock().await;
+            let _ = w.write_all(out.as_bytes()).await;
+            let _ = w.flush().await;
+        });
+    }
+}
+
+async fn echo_reply(msg: &Value, id: Value) -> Value {
+    let params = msg.get("params").cloned().unwrap_or(Value::Null);
+    let result = match msg["method"].as_str().unwrap_or_default() {
+        "initialize" => json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": {"tools": {"listChanged": false}},
+            "serverInfo": {"name": "echo-server", "version": "0.1.0"}
+        }),
+        "tools/list" => match params.get("cursor").and_then(Value::as_str) {
+            None => json!({
+                "tools": [{"name": "echo", "description": "Echo back", "inputSchema": {"type": "object"}}],
+                "nextCursor": "page-2"
+            }),
+            Some(_) => json!({
+                "tools": [
+                    {"name": "fail", "inputSchema": {"type": "object"}},
+                    {"name": "slow", "inputSchema": {"type": "object"}}
+                ]
+            }),
+        },
+        "tools/call" => match params["name"].as_str().unwrap_or_default() {
+            "echo" => json!({
+                "content": [{"type": "text", "text": params["arguments"]["message"]}]
+            }),
+            "fail" => json!({
+                "content": [{"type": "text", "text": "boom"}],
+                "isError": true
+            }),
+            "slow" => {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                json!({"content": []})
+            }
+            other => {
+                return json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32602, "message": format!("Unknown tool: {}", other)}
+                })
+            }
+        },
+        _ => json!({}),
+    };
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+async fn connected_client() -> (GestaltMcpClient, mpsc::UnboundedReceiver<Value>) {
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    let (seen_tx, seen_rx) = mpsc::unbounded_channel();
+    tokio::spawn(run_echo_server(server_read, server_write, seen_tx));
+
+    let (client_read, client_write) = tokio::io::split(client_io);
+    let transport: Arc<dyn McpTransport> =
+        Arc::new(StdioTransport::from_streams(client_read, client_write));
+    let client = GestaltMcpClient::new().with_request_timeout(Duration::from_secs(5));
+    client.connect_transport(transport).await.unwrap();
+    (client, seen_rx)
+}
+
+#[tokio::test]
+async fn test_stdio_handshake_and_paginated_tools() {
+    let (client, mut seen) = connected_client().await;
+    assert!(client.is_connected());
+    assert_eq!(
+        client.server_info().unwrap().server_info.name,
+        "echo-server"
+    );
+
+    let initialized = seen.recv().await.unwrap();
+    assert_eq!(initialized["method"], "notifications/initialized");
+
+    let names: Vec<String> = client.cached_tools().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, vec!["echo", "fail", "slow"]);
+}
+
+#[tokio::test]
+async fn test_stdio_call_tool() {
+    let (client, _seen) = connected_client().await;
+
+    let ok = client
+        .call_tool("echo", json!({"message": "hi"}), None)
+        .await
+        .unwrap();
+    assert!(ok.success);
+    assert_eq!(ok.content[0]["text"], "hi");
+
+    let failed = client.call_tool("fail", json!({}), None).await.unwrap();
+    assert!(!failed.success);
+    assert_eq!(failed.error.as_deref(), Some("boom"));
+
+    let missing = client.call_tool("nope", json!({}), None).await;
+    assert!(matches!(missing, Err(McpError::ToolNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_stdio_timeout_sends_cancellation() {
+    let (client, mut seen) = connected_client().await;
+    let _initialized = seen.recv().await.unwrap();
+
+    let result = client
+        .call_tool("slow", json!({}), Some(Duration::from_millis(100)))
+        .await;
+    assert!(matches!(result, Err(McpError::Timeout)));
+
+    let cancelled = tokio::time::timeout(Duration::from_secs(2), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cancelled["method"], "notifications/cancelled");
+    assert!(cancelled["params"]["requestId"].is_number());
+}
+
+#[tokio::test]
+async fn test_registry_uses_live_client() {
+    let (client, _seen) = connected_client().await;
+    let registry = McpRegistry::new();
+    assert_eq!(registry.register_client(client).await, 3);
+
+    let result = registry
+        .execute_tool(
+            "echo",
+            json!({"message": "via registry"}),
+            &gestalt_core::mcp::DefaultToolContext::new(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.content[0]["text"], "via registry");
+
+    registry.disconnect_all().await;
+    assert!(registry.list_tools().await.is_empty());
+}
+
+/// Streamable HTTP server answering every request with a one-event SSE stream.
+async fn spawn_sse_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let (header_end, content_length) = loop {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let head = String::from_utf8_lossy(&buf[..pos]).to_lowercase();
+                        let len = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .and_then(|v| v.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        break (pos + 4, len);
+                    }
+                };
+                while buf.len() < header_end + content_length {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+
+                let msg: Value =
+                    serde_json::from_slice(&buf[header_end..header_end + content_length])
+                        .unwrap_or(Value::Null);
+                let response = match msg.get("id").cloned() {
+                    Some(id) => {
+                        let body = format!(
+                            ": ping\n\ndata: {}\n\n",
+                            serde_json::to_string(&echo_reply(&msg, id).await).unwrap()
+                        );
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                             Mcp-Session-Id: session-1\r\nContent-Length: {}\r\n\
+                             Connection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    None => {
+                        "HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+    format!("http://{}/mcp", addr)
+}
+
+#[tokio::test]
+async fn test_streamable_http_with_sse_responses() {
+    let url = spawn_sse_server().await;
+    let client = GestaltMcpClient::with_server(&url);
+    client.connect(&url).await.unwrap();
+    assert_eq!(client.cached_tools().len(), 3);
+
+    let result = client
+        .call_tool("echo", json!({"message": "over http"}), None)
+        .await
+        .unwrap();
+    assert_eq!(result.content[0]["text"], "over http");
+    client.disconnect().await.unwrap();
+}
@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use gestalt_core::mcp::protocol::{JsonRpcRequest, McpResource, RequestId, ResourceContents};
+use gestalt_core::mcp::{
+    GestaltMcpClient, McpClient, McpError, McpResourceProvider, McpServer, McpTransport,
+    StdioTransport,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use synapse_agentic::prelude::{Tool, ToolContext, ToolRegistry};
+
+struct EchoTool;
+
+#[async_trait]
+impl Tool for EchoTool {
+    fn name(&self) -> &str {
+        "echo"
+    }
+    fn description(&self) -> &str {
+        "Echo back the message"
+    }
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": { "message": { "type": "string" } } })
+    }
+    async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        Ok(args["message"].clone())
+    }
+}
+
+struct SleepTool;
+
+#[async_trait]
+impl Tool for SleepTool {
+    fn name(&self) -> &str {
+        "sleep"
+    }
+    fn description(&self) -> &str {
+        "Never finishes in time"
+    }
+    fn parameters(&self) -> Value {
+        json!({ "type": "object" })
+    }
+    async fn call(&self, _ctx: &dyn ToolContext, _args: Value) -> anyhow::Result<Value> {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        Ok(Value::Null)
+    }
+}
+
+struct NotesResources;
+
+#[async_trait]
+impl McpResourceProvider for NotesResources {
+    async fn list_resources(&self) -> Result<Vec<McpResource>, McpError> {
+        Ok(vec![McpResource {
+            uri: "notes://today".to_string(),
+            name: "Today".to_string(),
+            description: None,
+            mime_type: Some("application/json".to_string()),
+        }])
+    }
+
+    fn handles(&self, uri: &str) -> bool {
+        uri.starts_with("notes://")
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, McpError> {
+        Ok(vec![ResourceContents::json(
+            uri,
+            &json!({ "todo": ["ship"] }),
+        )])
+    }
+}
+
+/// Run an `McpServer` on one end of a duplex pipe and return a transport for the other.
+async fn serve_in_process() -> Arc<dyn McpTransport> {
+    let registry = ToolRegistry::new();
+    registry.register_tool(EchoTool).await;
+    registry.register_tool(SleepTool).await;
+    let server = Arc::new(
+        McpServer::new("gestalt-test", "0.0.0", registry)
+            .with_resource_provider(Arc::new(NotesResources)),
+    );
+
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    tokio::spawn(server.serve(server_read, server_write));
+
+    let (client_read, client_write) = tokio::io::split(client_io);
+    Arc::new(StdioTransport::from_streams(client_read, client_write))
+}
+
+#[tokio::test]
+async fn test_client_round_trip_against_server() {
+    let transport = serve_in_process().await;
+    let client = GestaltMcpClient::new().with_request_timeout(Duration::from_secs(5));
+    client.connect_transport(transport).await.unwrap();
+
+    let info = client.server_info().unwrap();
+    assert_eq!(info.server_info.name, "gestalt-test");
+    assert!(info.has_capability("resources"));
+
+    let names: Vec<String> = client.cached_tools().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, vec!["echo", "sleep"]);
+
+    let result = client
+        .call_tool("echo", json!({"message": "hello"}), None)
+        .await
+        .unwrap();
+    assert!(result.success);
+    assert_eq!(result.content[0]["text"], "hello");
+
+    let missing = client.call_tool("nope", json!({}), None).await;
+    assert!(matches!(missing, Err(McpError::ToolNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_cancelled_request_does_not_block_others() {
+    let transport = serve_in_process().await;
+    let client = GestaltMcpClient::new().with_request_timeout(Duration::from_secs(5));
+    client.connect_transport(transport).await.unwrap();
+
+    let slow = client
+        .call_tool("sleep", json!({}), Some(Duration::from_millis(100)))
+        .await;
+    assert!(matches!(slow, Err(McpError::Timeout)));
+
+    let ok = client
+        .call_tool("echo", json!({"message": "still alive"}), None)
+        .await
+        .unwrap();
+    assert_eq!(ok.content[0]["text"], "still alive");
+}
+
+#[tokio::test]
+async fn test_resources_list_and_read() {
+    let transport = serve_in_process().await;
+
+    let listed = transport
+        .request(JsonRpcRequest::new(
+            RequestId::Number(1),
+            "resources/list",
+            None,
+        ))
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(listed["resources"][0]["uri"], "notes://today");
+    assert_eq!(listed["resources"][0]["mimeType"], "application/json");
+
+    let read = transport
+        .request(JsonRpcRequest::new(
+            RequestId::Number(2),
+            "resources/read",
+            Some(json!({ "uri": "notes://today" })),
+        ))
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    let text = read["contents"][0]["text"].as_str().unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(text).unwrap()["todo"][0],
+        "ship"
+    );
+
+    let missing = transport
+        .request(JsonRpcRequest::new(
+            RequestId::Number(3),
+            "resources/read",
+            Some(json!({ "uri": "other://x" })),
+        ))
+        .await
+        .unwrap()
+        .into_result();
+    assert!(matches!(missing, Err(McpError::Rpc { code: -32002, .. })));
+}
@@ -38,25 +38,51 @@ async fn test_vector_search_integration() {
 }
 
 #[tokio::test]
-async fn test_lexical_fallback() {
+async fn test_lexical_search() {
     let adapter = SurrealDbAdapter::new().await.unwrap();
 
-    // Store something
     adapter
         .store_embedding(
             "code",
             "1",
             vec![1.0, 0.0, 0.0],
-            json!({"content": "item 1"}),
+            json!({"content": "fn load_user_config(path: &Path) -> Config"}),
+        )
+        .await
+        .unwrap();
+    adapter
+        .store_embedding(
+            "code",
+            "2",
+            vec![0.0, 1.0, 0.0],
+            json!({"content": "struct HttpClient;"}),
         )
         .await
         .unwrap();
 
-    // Search with a vector that might not match well but should return results via fallback
-    // (In our current impl, search_similar always returns something if table is not empty)
+    // camelCase queries match snake_case identifiers.
     let results = adapter
-        .search_similar("code", vec![0.0, 0.0, 1.0], 10)
+        .search_lexical("code", "loadUserConfig", 10)
         .await
         .unwrap();
-    assert!(!results.is_empty());
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].metadata["content"],
+        "fn load_user_config(path: &Path) -> Config"
+    );
+    assert!(results[0].score > 0.0);
+
+    // A record needs only one of the query terms to match.
+    let results = adapter
+        .search_lexical("code", "user client", 10)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+
+    // Unrelated terms no longer return arbitrary records.
+    let results = adapter
+        .search_lexical("code", "database migration", 10)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
 }
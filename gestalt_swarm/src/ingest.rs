@@ -84,6 +84,8 @@ fn convert_result(run_id: &str, result: &SwarmBridgeResult) -> ExecutionMetrics
         timestamp: FlexibleTimestamp::now(),
         project_id: None,
         output_lines: result.lines.as_ref().map(|l| l.len() as u32),
+        task_id: None,
+        llm_usage: Vec::new(),
         metadata: Default::default(),
     }
 }
@@ -15,7 +15,8 @@ mod shared;
 use gestalt_core::application::agent::tools::{AskAiTool, ExecuteShellTool, GitStatusTool};
 use health::{HealthChecker, HealthConfig, SwarmHealthMonitor};
 use synapse_agentic::prelude::{
-    GeminiProvider, GroqProvider, LLMProvider, MinimaxProvider, ToolRegistry,
+    GeminiProvider, LLMProvider, MinimaxProvider, OllamaProvider, OpenAiCompatibleProvider,
+    ToolRegistry,
 };
 
 // ============================================================================
@@ -88,6 +89,10 @@ struct RunArgs {
     /// Model to use. Defaults depend on provider.
     #[arg(long)]
     model: Option<String>,
+
+    /// Server URL for `openai` (e.g. a vLLM or llama.cpp server) and `ollama`
+    #[arg(long, value_hint = ValueHint::Url)]
+    base_url: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
@@ -95,6 +100,20 @@ enum LlmProviderKind {
     Gemini,
     Groq,
     Minimax,
+    /// Any OpenAI-compatible chat-completions server
+    #[value(name = "openai")]
+    OpenAi,
+    #[value(name = "openrouter")]
+    OpenRouter,
+    Ollama,
+}
+
+/// Provider selection shared by every agent of a run.
+#[derive(Debug, Clone)]
+struct LlmTarget {
+    provider: LlmProviderKind,
+    model: String,
+    base_url: Option<String>,
 }
 
 // ============================================================================
@@ -115,11 +134,15 @@ fn default_model(provider: LlmProviderKind) -> &'static str {
         LlmProviderKind::Gemini => "gemini-2.5-flash-lite",
         LlmProviderKind::Groq => "llama-3.3-70b-versatile",
         LlmProviderKind::Minimax => "MiniMax-Text-01",
+        LlmProviderKind::OpenAi => "gpt-4o-mini",
+        LlmProviderKind::OpenRouter => "openai/gpt-4o-mini",
+        LlmProviderKind::Ollama => "llama3.1",
     }
 }
 
-fn build_llm_provider(provider: LlmProviderKind, model: String) -> Result<Arc<dyn LLMProvider>> {
-    match provider {
+fn build_llm_provider(target: &LlmTarget) -> Result<Arc<dyn LLMProvider>> {
+    let model = target.model.clone();
+    match target.provider {
         LlmProviderKind::Gemini => {
             let api_key = std::env::var("GEMINI_API_KEY")
                 .map_err(|_| anyhow::anyhow!("GEMINI_API_KEY is required for --provider gemini"))?;
@@ -128,7 +151,7 @@ fn build_llm_provider(provider: LlmProviderKind, model: String) -> Result<Arc<dy
         LlmProviderKind::Groq => {
             let api_key = std::env::var("GROQ_API_KEY")
                 .map_err(|_| anyhow::anyhow!("GROQ_API_KEY is required for --provider groq"))?;
-            Ok(Arc::new(GroqProvider::new(api_key, model)))
+            Ok(Arc::new(OpenAiCompatibleProvider::groq(api_key, model)))
         }
         LlmProviderKind::Minimax => {
             let api_key = std::env::var("MINIMAX_API_KEY").map_err(|_| {
@@ -139,6 +162,30 @@ fn build_llm_provider(provider: LlmProviderKind, model: String) -> Result<Arc<dy
             })?;
             Ok(Arc::new(MinimaxProvider::new(api_key, group_id, model)))
         }
+        LlmProviderKind::OpenAi => {
+            let base_url = target
+                .base_url
+                .clone()
+                .or_else(|| std::env::var("OPENAI_BASE_URL").ok())
+                .unwrap_or_else(|| synapse_agentic::openai::OPENAI_BASE_URL.to_string());
+            // Local servers (vLLM, llama.cpp) usually run without a key.
+            let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
+            Ok(Arc::new(
+                OpenAiCompatibleProvider::new(base_url, model).with_api_key(api_key),
+            ))
+        }
+        LlmProviderKind::OpenRouter => {
+            let api_key = std::env::var("OPENROUTER_API_KEY").map_err(|_| {
+                anyhow::anyhow!("OPENROUTER_API_KEY is required for --provider openrouter")
+            })?;
+            Ok(Arc::new(OpenAiCompatibleProvider::openrouter(
+                api_key, model,
+            )))
+        }
+        LlmProviderKind::Ollama => Ok(Arc::new(match &target.base_url {
+            Some(url) => OllamaProvider::new(url, model),
+            None => OllamaProvider::from_env(model),
+        })),
     }
 }
 
@@ -146,17 +193,28 @@ fn build_llm_provider(provider: LlmProviderKind, model: String) -> Result<Arc<dy
 // Swarm Execution
 // ============================================================================
 
+/// State shared by all agents of a swarm run.
+#[derive(Clone)]
+struct SwarmContext {
+    semaphore: Arc<Semaphore>,
+    results: Arc<RwLock<Vec<AgentResult>>>,
+    monitor: Arc<SwarmHealthMonitor>,
+    quiet: bool,
+}
+
 async fn run_agent(
     agent_id: usize,
     goal: String,
     cwd: PathBuf,
-    provider: LlmProviderKind,
-    model: String,
-    semaphore: Arc<Semaphore>,
-    results: Arc<RwLock<Vec<AgentResult>>>,
-    quiet: bool,
-    monitor: Arc<SwarmHealthMonitor>,
+    target: LlmTarget,
+    swarm: SwarmContext,
 ) {
+    let SwarmContext {
+        semaphore,
+        results,
+        monitor,
+        quiet,
+    } = swarm;
     let start = Instant::now();
     let permit = match semaphore.acquire().await {
         Ok(p) => p,
@@ -188,7 +246,7 @@ async fn run_agent(
     let success;
     let output;
 
-    let llm = match build_llm_provider(provider, model.clone()) {
+    let llm = match build_llm_provider(&target) {
         Ok(provider) => provider,
         Err(e) => {
             success = false;
@@ -212,7 +270,7 @@ async fn run_agent(
 
     // Build minimal tool registry for this agent
     let registry = ToolRegistry::new();
-    registry.register_tool(ExecuteShellTool).await;
+    registry.register_tool(ExecuteShellTool::default()).await;
     registry.register_tool(GitStatusTool).await;
     registry
         .register_tool(AskAiTool {
@@ -228,7 +286,7 @@ async fn run_agent(
         Model: {}\n\
         Execute this task and report results concisely.\n\
         Use tools: execute_shell, git_status, ask_ai\n",
-        agent_id, goal, cwd, provider, model
+        agent_id, goal, cwd, target.provider, target.model
     );
 
     match llm.generate(&prompt).await {
@@ -290,8 +348,12 @@ async fn main() -> Result<()> {
     match args.command {
         Commands::Run(run_args) => run_swarm(run_args, args.quiet).await?,
         Commands::Ingest { run_id, file } => ingest::handle_ingest(&run_id, file).await?,
-        Commands::Priorities { agent_type } => ingest::show_priorities(agent_type.as_deref()).await?,
-        Commands::NextSteps { agent_type } => ingest::show_next_steps(agent_type.as_deref()).await?,
+        Commands::Priorities { agent_type } => {
+            ingest::show_priorities(agent_type.as_deref()).await?
+        }
+        Commands::NextSteps { agent_type } => {
+            ingest::show_next_steps(agent_type.as_deref()).await?
+        }
     }
 
     Ok(())
@@ -302,6 +364,11 @@ async fn run_swarm(args: RunArgs, quiet: bool) -> Result<()> {
         .model
         .clone()
         .unwrap_or_else(|| default_model(args.provider).to_string());
+    let target = LlmTarget {
+        provider: args.provider,
+        model: model.clone(),
+        base_url: args.base_url.clone(),
+    };
 
     let cwd = args
         .cwd
@@ -331,8 +398,12 @@ async fn run_swarm(args: RunArgs, quiet: bool) -> Result<()> {
     });
 
     // Shared state
-    let semaphore = Arc::new(Semaphore::new(args.max_concurrency));
-    let results: Arc<RwLock<Vec<AgentResult>>> = Arc::new(RwLock::new(Vec::new()));
+    let swarm = SwarmContext {
+        semaphore: Arc::new(Semaphore::new(args.max_concurrency)),
+        results: Arc::new(RwLock::new(Vec::new())),
+        monitor: monitor.clone(),
+        quiet,
+    };
 
     let start_time = Instant::now();
 
@@ -342,14 +413,11 @@ async fn run_swarm(args: RunArgs, quiet: bool) -> Result<()> {
     for agent_id in 0..args.agents {
         let goal = args.goal.clone();
         let cwd = cwd.clone();
-        let sem = semaphore.clone();
-        let res = results.clone();
-        let prov = args.provider;
-        let mdl = model.clone();
-        let mon = monitor.clone();
+        let target = target.clone();
+        let swarm = swarm.clone();
 
         let handle = tokio::spawn(async move {
-            run_agent(agent_id, goal, cwd, prov, mdl, sem, res, quiet, mon).await;
+            run_agent(agent_id, goal, cwd, target, swarm).await;
         });
 
         handles.push(handle);
@@ -368,7 +436,7 @@ async fn run_swarm(args: RunArgs, quiet: bool) -> Result<()> {
     monitor.shutdown();
 
     // Report summary
-    let all_results = results.read().await;
+    let all_results = swarm.results.read().await;
     let successes = all_results.iter().filter(|r| r.success).count();
     let failures = all_results.len() - successes;
 
@@ -51,6 +51,9 @@ pub enum Commands {
         project: String,
         /// Task description
         description: String,
+        /// IDs of tasks that must complete first (comma separated)
+        #[arg(long, value_delimiter = ',')]
+        depends_on: Vec<String>,
     },
 
     /// Update a task
@@ -64,6 +67,9 @@ pub enum Commands {
         /// New status (Todo, InProgress, Completed, Cancelled)
         #[arg(long)]
         status: Option<String>,
+        /// Replace the IDs of tasks that must complete first (comma separated, "" for none)
+        #[arg(long, value_delimiter = ',')]
+        depends_on: Option<Vec<String>>,
     },
 
     /// Delete a task
@@ -78,8 +84,12 @@ pub enum Commands {
     ScheduleTask {
         /// Task ID
         id: String,
-        /// Execution time (ISO 8601 or duration like "1h")
-        time: String,
+        /// Execution time (ISO 8601)
+        #[arg(required_unless_present = "cron")]
+        time: Option<String>,
+        /// Cron expression for recurring runs, e.g. "0 9 * * MON-FRI"
+        #[arg(long, conflicts_with = "time")]
+        cron: Option<String>,
     },
 
     /// Execute a task asynchronously
@@ -89,6 +99,27 @@ pub enum Commands {
         task_id: String,
     },
 
+    /// Cancel a task, stopping the agent running it and killing its jobs
+    #[command(name = "cancel-task")]
+    CancelTask {
+        /// Task ID to cancel
+        task_id: String,
+    },
+
+    /// Pause the agent running a task before its next step
+    #[command(name = "pause-task")]
+    PauseTask {
+        /// Task ID to pause
+        task_id: String,
+    },
+
+    /// Resume the paused agent of a task
+    #[command(name = "resume-task")]
+    ResumeTask {
+        /// Task ID to resume
+        task_id: String,
+    },
+
     /// List all projects
     #[command(name = "list-projects")]
     ListProjects,
@@ -107,6 +138,13 @@ pub enum Commands {
         project: String,
     },
 
+    /// Show the task dependency graph and its critical path
+    #[command(name = "graph")]
+    Graph {
+        /// Project name
+        project: String,
+    },
+
     /// Show timeline of events
     #[command(name = "timeline")]
     Timeline {
@@ -115,6 +153,17 @@ pub enum Commands {
         since: Option<String>,
     },
 
+    /// Show LLM token usage and spend of agent runs
+    #[command(name = "usage")]
+    Usage {
+        /// Group by "agent", "task", "project" or "provider"
+        #[arg(long, default_value = "provider")]
+        by: String,
+        /// Only count runs from the last N days
+        #[arg(long)]
+        days: Option<u32>,
+    },
+
     /// Watch timeline events in real-time (persistent process)
     #[command(name = "watch")]
     Watch {
@@ -215,6 +264,22 @@ pub enum Commands {
         port: u16,
     },
 
+    /// Serve the agent tool registry, VFS and timeline over MCP (stdio by default)
+    #[command(name = "mcp-serve")]
+    McpServe {
+        /// Serve streamable HTTP on --host/--port instead of stdio
+        #[arg(long)]
+        http: bool,
+
+        /// Host to bind to (with --http)
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on (with --http)
+        #[arg(long, default_value_t = 3000)]
+        port: u16,
+    },
+
     /// Authenticate with Google Gemini via OAuth2
     #[command(name = "login")]
     Login,
@@ -228,6 +293,10 @@ pub enum Commands {
     IndexRepo {
         /// Repository URL (GitHub, GitLab, or local path)
         url: String,
+
+        /// Keep re-indexing changed files of a local repository until interrupted
+        #[arg(long)]
+        watch: bool,
     },
 
     /// Start the Telegram Bot listener
@@ -247,16 +316,16 @@ pub enum Commands {
         /// Run as a background daemon
         #[arg(long)]
         daemon: bool,
+        /// Resume agent loops left in the Running phase by a previous process
+        #[arg(long)]
+        auto_resume: bool,
     },
 
-    /// Queue a task from the CLI for autonomous background execution
+    /// Queue tasks for autonomous background execution and manage the queue
     #[command(name = "queue")]
     Queue {
-        /// Natural language goal for an agent to work on
-        goal: String,
-        /// Priority 1-10 (default: 5)
-        #[arg(long, default_value_t = 5)]
-        priority: u8,
+        #[command(subcommand)]
+        action: QueueCommands,
     },
 
     /// Manage sub-agent CLI processes
@@ -265,6 +334,17 @@ pub enum Commands {
         #[command(subcommand)]
         action: AgentCommands,
     },
+
+    /// Inspect, flush or discard VFS overlays recovered from .gestalt/vfs journals
+    #[command(name = "vfs")]
+    Vfs {
+        /// Workspace holding the .gestalt/vfs journals
+        #[arg(long, default_value = ".")]
+        workspace: String,
+
+        #[command(subcommand)]
+        action: VfsCommands,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -281,4 +361,93 @@ pub enum AgentCommands {
     /// Lists currently running background agents managed by Gestalt
     #[command(name = "ps")]
     Ps,
+
+    /// Continue an interrupted agent loop from its persisted runtime state
+    #[command(name = "resume")]
+    Resume {
+        /// Agent ID of the interrupted runtime
+        id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QueueCommands {
+    /// Queue a natural language goal for an agent to work on
+    #[command(name = "add")]
+    Add {
+        /// Natural language goal for an agent to work on
+        goal: String,
+        /// Priority 1-10 (default: 5)
+        #[arg(long, default_value_t = 5)]
+        priority: u8,
+        /// Attempts before the task is dead-lettered
+        #[arg(long, default_value_t = 3)]
+        max_attempts: u32,
+    },
+
+    /// List queued tasks in dispatch order
+    #[command(name = "list")]
+    List {
+        /// Only show tasks in this state (pending, running, completed, dead_letter, cancelled)
+        #[arg(long)]
+        status: Option<String>,
+    },
+
+    /// Show a queued task, including its attempts and last error
+    #[command(name = "show")]
+    Show {
+        /// Queue entry ID
+        id: String,
+    },
+
+    /// Give a finished, cancelled or dead-lettered task a fresh set of attempts
+    #[command(name = "requeue")]
+    Requeue {
+        /// Queue entry ID
+        id: String,
+    },
+
+    /// Cancel a queued task
+    #[command(name = "cancel")]
+    Cancel {
+        /// Queue entry ID
+        id: String,
+    },
+}
+
+/// Journaled overlays are per agent; stop the agent before flushing or discarding its overlay.
+#[derive(Subcommand, Debug)]
+pub enum VfsCommands {
+    /// List agents with a journaled overlay and their pending changes
+    #[command(name = "list")]
+    List,
+
+    /// Show the pending changes and diff of an agent's overlay
+    #[command(name = "show")]
+    Show {
+        /// Agent ID
+        agent: String,
+    },
+
+    /// Apply an agent's recovered overlay to disk
+    #[command(name = "flush")]
+    Flush {
+        /// Agent ID
+        agent: String,
+
+        /// Proceed even though the agent's loop is recorded as running (e.g. its process died)
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Drop an agent's recovered overlay and its journal
+    #[command(name = "discard")]
+    Discard {
+        /// Agent ID
+        agent: String,
+
+        /// Proceed even though the agent's loop is recorded as running (e.g. its process died)
+        #[arg(long)]
+        force: bool,
+    },
 }
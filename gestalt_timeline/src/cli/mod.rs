@@ -3,4 +3,4 @@
 mod commands;
 pub mod repl;
 
-pub use commands::{AgentCommands, Cli, Commands};
+pub use commands::{AgentCommands, Cli, Commands, QueueCommands, VfsCommands};
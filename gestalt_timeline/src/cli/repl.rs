@@ -4,12 +4,18 @@ use rustyline::DefaultEditor;
 use std::sync::Arc;
 use tracing::warn;
 
+use crate::services::ApprovalService;
 use synapse_agentic::prelude::*;
 
 /// Runs the interactive REPL for the AI Chat.
-pub async fn run_repl(_agent_id: &str, engine: Arc<DecisionEngine>) -> Result<()> {
+pub async fn run_repl(
+    agent_id: &str,
+    engine: Arc<DecisionEngine>,
+    approvals: ApprovalService,
+) -> Result<()> {
     println!("🤖 Entering Interactive AI Chat (Synapse Decision Engine)");
     println!("📝 Type 'exit' or 'quit' to leave. 'clear' to reset context.");
+    println!("🛂 '/approvals' lists gated actions; '/approve <id>' or '/deny <id>' answers them.");
 
     let mut rl = DefaultEditor::new()?;
 
@@ -44,6 +50,11 @@ pub async fn run_repl(_agent_id: &str, engine: Arc<DecisionEngine>) -> Result<()
                     continue;
                 }
 
+                if input.starts_with('/') {
+                    handle_approval_command(input, agent_id, &approvals).await;
+                    continue;
+                }
+
                 // Add user message to history
                 // Note: The `chat` method currently takes a single message string.
                 // To support true context, we need to modify or use an overload of `chat`
@@ -113,3 +124,49 @@ pub async fn run_repl(_agent_id: &str, engine: Arc<DecisionEngine>) -> Result<()
 
     Ok(())
 }
+
+/// Handle `/approvals`, `/approve <id>` and `/deny <id>`.
+async fn handle_approval_command(input: &str, agent_id: &str, approvals: &ApprovalService) {
+    let mut parts = input.split_whitespace();
+    let command = parts.next().unwrap_or_default();
+    let id = parts.next();
+    let decided_by = format!("repl:{}", agent_id);
+
+    let result = match (command, id) {
+        ("/approvals", _) => approvals.list_pending().await.map(|pending| {
+            if pending.is_empty() {
+                println!("✅ No actions waiting for approval.");
+            }
+            for request in pending {
+                println!(
+                    "⏸️  {} {} by {} [{}]\n    {}",
+                    request.approval_id,
+                    request.action,
+                    request.agent_id,
+                    request.rule.as_deref().unwrap_or("default"),
+                    request.detail
+                );
+            }
+        }),
+        ("/approve", Some(id)) | ("/deny", Some(id)) => approvals
+            .decide(id, command == "/approve", &decided_by)
+            .await
+            .map(|decided| println!("👍 Approval {} {}.", decided.approval_id, decided.status)),
+        ("/approve", None) | ("/deny", None) => {
+            println!("Usage: {} <id>", command);
+            Ok(())
+        }
+        _ => {
+            println!(
+                "Unknown command '{}'. Try /approvals, /approve <id> or /deny <id>.",
+                command
+            );
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        println!("❌ Error: {}", e);
+        warn!("Approval command failed: {}", e);
+    }
+}
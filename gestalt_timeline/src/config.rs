@@ -9,6 +9,8 @@ pub struct Settings {
     pub cognition: CognitionSettings,
     pub agent: AgentSettings,
     pub telegram: Option<TelegramSettings>,
+    /// Embedding model for code search; the dummy model when unset
+    pub embedding: Option<gestalt_core::application::config::EmbeddingConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,10 +24,20 @@ pub struct DatabaseSettings {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct CognitionSettings {
+    /// `minimax`, `gemini`, `auto`, `openai`, `groq`, `openrouter` or `ollama`
     pub provider: String,
     pub model_id: String,
     pub gemini_api_key: Option<String>,
     pub minimax_api_key: Option<String>,
+    /// Key for the OpenAI-compatible providers (`openai`, `groq`, `openrouter`)
+    pub openai_api_key: Option<String>,
+    /// Server URL for `openai` (e.g. vLLM or llama.cpp) and `ollama`
+    pub base_url: Option<String>,
+    /// Extra headers sent by the OpenAI-compatible providers
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+    /// Price in USD per 1K tokens, used to report spend
+    pub cost_per_1k_tokens: Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -2,6 +2,7 @@
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use gestalt_core::adapters::persistence::lexical;
 use gestalt_core::ports::outbound::repo_manager::{ScoredResult, VectorDb};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -9,7 +10,7 @@ use std::sync::Arc;
 use surrealdb::engine::any::Any;
 use surrealdb::opt::auth::Root;
 use surrealdb::Surreal;
-use tracing::{debug, info, warn};
+use tracing::{debug, info};
 
 #[derive(Debug, serde::Deserialize)]
 struct SearchResult {
@@ -75,8 +76,8 @@ impl SurrealClient {
             DEFINE FIELD event_type ON timeline_events TYPE string;
             DEFINE FIELD project_id ON timeline_events TYPE option<string>;
             DEFINE FIELD task_id ON timeline_events TYPE option<string>;
-            DEFINE FIELD payload ON timeline_events TYPE option<object>;
-            DEFINE FIELD metadata ON timeline_events TYPE option<object>;
+            DEFINE FIELD OVERWRITE payload ON timeline_events FLEXIBLE TYPE option<object>;
+            DEFINE FIELD OVERWRITE metadata ON timeline_events FLEXIBLE TYPE option<object>;
             DEFINE INDEX idx_timestamp ON timeline_events FIELDS timestamp;
             DEFINE INDEX idx_project ON timeline_events FIELDS project_id;
             DEFINE INDEX idx_agent ON timeline_events FIELDS agent_id;
@@ -101,6 +102,9 @@ impl SurrealClient {
             DEFINE FIELD executed_by ON tasks TYPE option<string>;
             DEFINE FIELD duration_ms ON tasks TYPE option<int>;
             DEFINE FIELD external_id ON tasks TYPE option<string>;
+            DEFINE FIELD depends_on ON tasks TYPE option<array<string>>;
+            DEFINE FIELD scheduled_for ON tasks TYPE any;
+            DEFINE FIELD cron ON tasks TYPE option<string>;
             DEFINE INDEX idx_project_id ON tasks FIELDS project_id;
             DEFINE INDEX idx_status ON tasks FIELDS status;
             DEFINE INDEX idx_external_id ON tasks FIELDS external_id;
@@ -122,9 +126,18 @@ impl SurrealClient {
             DEFINE FIELD phase ON agent_runtime_states TYPE string;
             DEFINE FIELD current_step ON agent_runtime_states TYPE int;
             DEFINE FIELD max_steps ON agent_runtime_states TYPE int;
+            DEFINE FIELD task_id ON agent_runtime_states TYPE option<string>;
             DEFINE FIELD last_action ON agent_runtime_states TYPE option<string>;
             DEFINE FIELD last_observation ON agent_runtime_states TYPE option<string>;
             DEFINE FIELD history_tail ON agent_runtime_states TYPE array;
+            DEFINE FIELD summary ON agent_runtime_states TYPE option<string>;
+            DEFINE FIELD locked_paths ON agent_runtime_states TYPE array<string> DEFAULT [];
+            DEFINE FIELD llm_usage ON agent_runtime_states TYPE array<object> DEFAULT [];
+            DEFINE FIELD llm_usage.*.provider ON agent_runtime_states TYPE string;
+            DEFINE FIELD llm_usage.*.prompt_tokens ON agent_runtime_states TYPE int;
+            DEFINE FIELD llm_usage.*.completion_tokens ON agent_runtime_states TYPE int;
+            DEFINE FIELD llm_usage.*.cost_usd ON agent_runtime_states TYPE number;
+            DEFINE FIELD llm_usage.*.estimated ON agent_runtime_states TYPE bool;
             DEFINE FIELD error ON agent_runtime_states TYPE option<string>;
             DEFINE FIELD started_at ON agent_runtime_states TYPE any;
             DEFINE FIELD updated_at ON agent_runtime_states TYPE any;
@@ -136,6 +149,7 @@ impl SurrealClient {
             DEFINE FIELD name ON repositories TYPE string;
             DEFINE FIELD local_path ON repositories TYPE option<string>;
             DEFINE FIELD created_at ON repositories TYPE string;
+            DEFINE FIELD last_indexed_tree ON repositories TYPE option<string>;
             DEFINE INDEX idx_repo_url ON repositories FIELDS url UNIQUE;
 
             DEFINE TABLE documents SCHEMAFULL;
@@ -151,9 +165,15 @@ impl SurrealClient {
             DEFINE FIELD content ON chunks TYPE string;
             DEFINE FIELD chunk_index ON chunks TYPE int;
             DEFINE FIELD created_at ON chunks TYPE any;
-            DEFINE FIELD embedding ON chunks TYPE option<array<float, 384>>;
-            DEFINE FIELD metadata ON chunks TYPE option<object>;
+            DEFINE FIELD embedding ON chunks TYPE option<array<float>>;
+            DEFINE FIELD embedding_model ON chunks TYPE option<string>;
+            DEFINE FIELD embedding_dim ON chunks TYPE option<int>;
+            DEFINE FIELD metadata ON chunks FLEXIBLE TYPE option<object>;
+            DEFINE FIELD search_text ON chunks TYPE option<string>;
             DEFINE INDEX idx_chunk_doc ON chunks FIELDS doc_id;
+            DEFINE ANALYZER IF NOT EXISTS code_tokens TOKENIZERS blank FILTERS lowercase;
+            DEFINE INDEX IF NOT EXISTS chunks_search ON chunks FIELDS search_text
+                SEARCH ANALYZER code_tokens BM25(1.2,0.75);
 
             DEFINE TABLE execution_metrics SCHEMAFULL;
             DEFINE FIELD run_id ON execution_metrics TYPE string;
@@ -169,6 +189,13 @@ impl SurrealClient {
             DEFINE FIELD project_id ON execution_metrics TYPE option<string>;
             DEFINE FIELD output_lines ON execution_metrics TYPE option<int>;
             DEFINE FIELD metadata ON execution_metrics TYPE option<object>;
+            DEFINE FIELD task_id ON execution_metrics TYPE option<string>;
+            DEFINE FIELD llm_usage ON execution_metrics TYPE option<array<object>>;
+            DEFINE FIELD llm_usage.*.provider ON execution_metrics TYPE string;
+            DEFINE FIELD llm_usage.*.prompt_tokens ON execution_metrics TYPE int;
+            DEFINE FIELD llm_usage.*.completion_tokens ON execution_metrics TYPE int;
+            DEFINE FIELD llm_usage.*.cost_usd ON execution_metrics TYPE number;
+            DEFINE FIELD llm_usage.*.estimated ON execution_metrics TYPE bool;
             DEFINE INDEX idx_metrics_run ON execution_metrics FIELDS run_id;
             DEFINE INDEX idx_metrics_agent_type ON execution_metrics FIELDS agent_type;
             DEFINE INDEX idx_metrics_timestamp ON execution_metrics FIELDS timestamp;
@@ -181,22 +208,19 @@ impl SurrealClient {
             DEFINE FIELD triggered_by_run_id ON priority_updates TYPE string;
             DEFINE FIELD timestamp ON priority_updates TYPE any;
             DEFINE INDEX idx_priority_updates_timestamp ON priority_updates FIELDS timestamp;
+
+            DEFINE TABLE provider_cooldowns SCHEMAFULL;
+            DEFINE FIELD provider ON provider_cooldowns TYPE string;
+            DEFINE FIELD class ON provider_cooldowns TYPE string;
+            DEFINE FIELD failures ON provider_cooldowns TYPE int;
+            DEFINE FIELD until_ms ON provider_cooldowns TYPE int;
             "#,
         )
         .await
         .context("Failed to initialize schema")?;
 
-        // Optional vector index: some embedded/older Surreal engines do not support HNSW syntax.
-        if let Err(e) = db
-            .query(
-                r#"
-                DEFINE INDEX idx_chunk_embedding ON chunks FIELDS embedding TYPE HNSW DIMENSION 384 DISTANCE COSINE;
-                "#,
-            )
-            .await
-        {
-            warn!("Skipping optional HNSW index initialization: {}", e);
-        }
+        // No HNSW index on chunk embeddings: its dimension is fixed at definition
+        // time, while the vector length depends on the configured embedding model.
 
         debug!("Schema initialized successfully");
         Ok(())
@@ -236,7 +260,7 @@ impl SurrealClient {
         id: &str,
         data: &T,
     ) -> Result<T> {
-        let val = serde_json::to_value(data)?;
+        let val = record_content(data)?;
         let result: Option<T> = self.db.update((table, id)).content(val).await?;
         result.ok_or_else(|| anyhow::anyhow!("Failed to update record"))
     }
@@ -248,7 +272,7 @@ impl SurrealClient {
         id: &str,
         data: &T,
     ) -> Result<T> {
-        let val = serde_json::to_value(data)?;
+        let val = record_content(data)?;
         let table_name = table.to_string();
         let record_id = id.to_string();
         let mut response = self
@@ -297,12 +321,30 @@ impl SurrealClient {
         .await
     }
     /// Delete a record.
+    ///
+    /// Goes through a query that returns nothing: the deleted record carries
+    /// its record id, which does not deserialize into JSON.
     pub async fn delete(&self, table: &str, id: &str) -> Result<()> {
-        let _: Option<serde_json::Value> = self.db.delete((table, id)).await?;
+        self.db
+            .query("DELETE type::thing($table, $id)")
+            .bind(("table", table.to_string()))
+            .bind(("id", id.to_string()))
+            .await?
+            .check()?;
         Ok(())
     }
 }
 
+/// `data` as the content of a record addressed by its ID. A serialized `id`
+/// is dropped: it is not a record ID once converted to JSON.
+fn record_content<T: Serialize>(data: &T) -> Result<serde_json::Value> {
+    let mut val = serde_json::to_value(data)?;
+    if let Some(fields) = val.as_object_mut() {
+        fields.remove("id");
+    }
+    Ok(val)
+}
+
 #[async_trait]
 impl VectorDb for SurrealClient {
     async fn store_embedding(
@@ -312,16 +354,13 @@ impl VectorDb for SurrealClient {
         vector: Vec<f32>,
         metadata: serde_json::Value,
     ) -> anyhow::Result<()> {
-        let _: Option<serde_json::Value> = self
-            .db
-            .upsert((collection, id))
-            .content(serde_json::json!({
-                "embedding": vector,
-                "metadata": metadata,
-                "created_at": chrono::Utc::now()
-            }))
-            .await?;
-        Ok(())
+        let content = metadata["content"].as_str().unwrap_or_default().to_string();
+        let record = serde_json::json!({
+            "embedding": vector,
+            "metadata": metadata,
+            "created_at": chrono::Utc::now()
+        });
+        lexical::store(self.db.as_ref(), collection, id, &content, record).await
     }
 
     async fn search_similar(
@@ -332,7 +371,7 @@ impl VectorDb for SurrealClient {
     ) -> anyhow::Result<Vec<ScoredResult>> {
         let mut response = self
             .db
-            .query("SELECT id, metadata, vector::distance::cosine(embedding, $vector) AS score FROM type::table($table) WHERE embedding IS NOT NONE ORDER BY score ASC LIMIT $limit")
+            .query("SELECT id, metadata, vector::similarity::cosine(embedding, $vector) AS score FROM type::table($table) WHERE embedding IS NOT NONE AND array::len(embedding) = array::len($vector) ORDER BY score DESC LIMIT $limit")
             .bind(("vector", vector))
             .bind(("table", collection.to_string()))
             .bind(("limit", limit))
@@ -344,9 +383,18 @@ impl VectorDb for SurrealClient {
             .into_iter()
             .map(|r| ScoredResult {
                 id: r.id.to_string(),
-                score: 1.0 - r.score, // Convert distance to similarity
+                score: r.score,
                 metadata: r.metadata,
             })
             .collect())
     }
+
+    async fn search_lexical(
+        &self,
+        collection: &str,
+        query: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScoredResult>> {
+        lexical::search(self.db.as_ref(), collection, query, limit).await
+    }
 }
@@ -3,17 +3,19 @@ use chrono::{DateTime, Utc};
 use clap::Parser;
 use gestalt_core::application::agent::tools::{
     ExecuteShellTool, GitAddTool, GitBranchTool, GitCommitTool, GitLogTool, GitPushTool,
-    GitStatusTool, ReadFileTool, WriteFileTool,
+    GitStatusTool, ReadFileTool, ScanWorkspaceTool, WriteFileTool,
 };
-use gestalt_timeline::cli::{repl, AgentCommands, Cli, Commands};
+use gestalt_timeline::cli::{repl, AgentCommands, Cli, Commands, QueueCommands, VfsCommands};
 use gestalt_timeline::config::Settings;
 use gestalt_timeline::db::SurrealClient;
 #[cfg(feature = "telegram")]
 use gestalt_timeline::services::TelegramService;
 use gestalt_timeline::services::{
-    start_server, AgentRuntime, AgentService, AuthService, DispatcherService, IndexService,
-    MemoryService, ProjectService, ProtocolSyncService, QueuedTask, TaskQueue, TaskService,
-    TaskSource, TimelineService, WatchService,
+    build_mcp_server, is_worker_id, pending_change_json, serve_mcp_http, start_server,
+    AgentRuntime, AgentService, ApprovalService, AuthService, DispatcherService, FileManager,
+    IndexService, MemoryService, OverlayFs, PendingChange, PolicyEngine, ProjectService, ProtocolSyncService,
+    QueueStatus, QueuedTask, Readiness, SchedulerService, TaskQueue, TaskService, TaskSource,
+    TimelineService, UsageGroup, UsageService, VfsJournal, VirtualFs, WatchService,
 };
 use std::path::Path;
 
@@ -21,10 +23,11 @@ use gestalt_core::context::{detector, scanner};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 use tracing::{info, warn};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // Re-export specific models if needed for matching
-use gestalt_timeline::models::TaskStatus;
+use gestalt_timeline::models::{AgentRuntimeState, TaskStatus};
 
 /// Helper to convert Option<Thing> to String for display
 fn thing_to_string(thing: &Option<Thing>) -> String {
@@ -39,12 +42,17 @@ use synapse_agentic::prelude::*;
 /// Helper to initialize decision engine based on configuration
 async fn init_decision_engine(
     settings: &gestalt_timeline::config::CognitionSettings,
+    db: &SurrealClient,
 ) -> Result<Arc<DecisionEngine>> {
     let provider_name = settings.provider.to_lowercase();
     let model_id = &settings.model_id;
+    let cost = settings.cost_per_1k_tokens.unwrap_or(0.0);
 
-    // Use framework's resilience components for transparent failover
-    let store = Arc::new(InMemoryCooldownStore::new());
+    // Use framework's resilience components for transparent failover.
+    // Cooldowns live in the database so every gestalt process honours them.
+    let store = Arc::new(gestalt_timeline::services::SurrealCooldownStore::new(
+        db.clone(),
+    ));
     let mut rotator = StochasticRotator::new(store);
     let mut providers_added = 0usize;
 
@@ -57,7 +65,8 @@ async fn init_decision_engine(
         {
             info!("🚀 Initializing MiniMax resilient provider...");
             let group_id = std::env::var("MINIMAX_GROUP_ID").unwrap_or_default();
-            let provider = MinimaxProvider::new(api_key, group_id, model_id.clone());
+            let provider = MinimaxProvider::new(api_key, group_id, model_id.clone())
+                .with_cost_per_1k_tokens(cost);
             rotator.add_provider(ProviderId::new("minimax", model_id), Arc::new(provider));
             providers_added += 1;
         }
@@ -70,12 +79,65 @@ async fn init_decision_engine(
             .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         {
             info!("🚀 Initializing Gemini resilient provider...");
-            let provider = GeminiProvider::new(api_key, model_id.clone());
+            let provider =
+                GeminiProvider::new(api_key, model_id.clone()).with_cost_per_1k_tokens(cost);
             rotator.add_provider(ProviderId::new("gemini", model_id), Arc::new(provider));
             providers_added += 1;
         }
     }
 
+    // OpenAI-compatible servers: hosted APIs by name, or any server via base_url.
+    let openai_compatible = match provider_name.as_str() {
+        "openai" => Some((synapse_agentic::openai::OPENAI_BASE_URL, "OPENAI_API_KEY")),
+        "groq" => Some((synapse_agentic::openai::GROQ_BASE_URL, "GROQ_API_KEY")),
+        "openrouter" => Some((
+            synapse_agentic::openai::OPENROUTER_BASE_URL,
+            "OPENROUTER_API_KEY",
+        )),
+        _ => None,
+    };
+    if let Some((default_url, key_var)) = openai_compatible {
+        let base_url = settings
+            .base_url
+            .clone()
+            .or_else(|| {
+                std::env::var("OPENAI_BASE_URL")
+                    .ok()
+                    .filter(|_| provider_name == "openai")
+            })
+            .unwrap_or_else(|| default_url.to_string());
+        let api_key = settings
+            .openai_api_key
+            .clone()
+            .or_else(|| std::env::var(key_var).ok())
+            .unwrap_or_default();
+        info!(
+            "🚀 Initializing OpenAI-compatible provider at {}...",
+            base_url
+        );
+        let mut provider = OpenAiCompatibleProvider::new(base_url, model_id.clone())
+            .with_api_key(api_key)
+            .with_cost_per_1k_tokens(cost);
+        for (name, value) in &settings.headers {
+            provider = provider.with_header(name, value);
+        }
+        rotator.add_provider(
+            ProviderId::new(&provider_name, model_id),
+            Arc::new(provider),
+        );
+        providers_added += 1;
+    }
+
+    if provider_name == "ollama" {
+        let provider = match &settings.base_url {
+            Some(url) => OllamaProvider::new(url, model_id.clone()),
+            None => OllamaProvider::from_env(model_id.clone()),
+        };
+        info!("🚀 Initializing Ollama provider...");
+        rotator.add_provider(ProviderId::new("ollama", model_id), Arc::new(provider));
+        providers_added += 1;
+    }
+
     // Secondary fallback configurations (cross-registering)
     if provider_name == "gemini" {
         if let Some(api_key) = settings
@@ -84,7 +146,8 @@ async fn init_decision_engine(
             .or_else(|| std::env::var("MINIMAX_API_KEY").ok())
         {
             let group_id = std::env::var("MINIMAX_GROUP_ID").unwrap_or_default();
-            let provider = MinimaxProvider::new(api_key, group_id, model_id.clone());
+            let provider = MinimaxProvider::new(api_key, group_id, model_id.clone())
+                .with_cost_per_1k_tokens(cost);
             rotator.add_provider(
                 ProviderId::new("minimax-fallback", model_id),
                 Arc::new(provider),
@@ -97,7 +160,8 @@ async fn init_decision_engine(
             .clone()
             .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         {
-            let provider = GeminiProvider::new(api_key, model_id.clone());
+            let provider =
+                GeminiProvider::new(api_key, model_id.clone()).with_cost_per_1k_tokens(cost);
             rotator.add_provider(
                 ProviderId::new("gemini-fallback", model_id),
                 Arc::new(provider),
@@ -125,7 +189,8 @@ async fn init_tool_registry(
     embedding_model: Arc<dyn gestalt_core::domain::rag::embeddings::EmbeddingModel>,
 ) -> Arc<ToolRegistry> {
     let registry = Arc::new(ToolRegistry::new());
-    registry.register_tool(ExecuteShellTool).await;
+    registry.register_tool(ScanWorkspaceTool).await;
+    registry.register_tool(ExecuteShellTool::default()).await;
     registry.register_tool(ReadFileTool).await;
     registry.register_tool(WriteFileTool).await;
     registry.register_tool(GitStatusTool).await;
@@ -178,14 +243,334 @@ fn collect_context(root: &Path) -> String {
     context_str
 }
 
+/// Journal of `agent`, which must exist.
+fn agent_journal(workspace: &Path, agent: &str) -> anyhow::Result<VfsJournal> {
+    let journal = VfsJournal::for_agent(workspace, agent);
+    if !journal.path().exists() {
+        anyhow::bail!(
+            "No VFS journal for agent '{}' in {}",
+            agent,
+            VfsJournal::dir(workspace).display()
+        );
+    }
+    Ok(journal)
+}
+
+/// Recover the journaled overlay of `agent` for a flush or discard, which
+/// may repair and compact its journal; refused while the agent's loop is
+/// recorded as running or paused, unless `force`d.
+async fn recover_overlay(
+    db: &SurrealClient,
+    workspace: &Path,
+    agent: &str,
+    force: bool,
+) -> anyhow::Result<OverlayFs> {
+    let journal = agent_journal(workspace, agent)?;
+    if !force {
+        let state: Option<AgentRuntimeState> =
+            db.select_by_id("agent_runtime_states", agent).await?;
+        if let Some(state) = state.filter(|state| !state.phase.is_finished()) {
+            anyhow::bail!(
+                "Agent '{}' still owns its overlay (loop {:?} at step {}); cancel or resume it \
+                 first, or pass --force if its process is gone",
+                agent,
+                state.phase,
+                state.current_step
+            );
+        }
+    }
+    OverlayFs::recover(journal).await
+}
+
+fn describe_pending_change(change: &PendingChange) -> String {
+    match change {
+        PendingChange::CreateDir { path } => format!("mkdir  {}", path.display()),
+        PendingChange::WriteFile { path, bytes } => {
+            format!("write  {} ({} bytes)", path.display(), bytes)
+        }
+        PendingChange::RemoveFile { path } | PendingChange::RemoveDir { path } => {
+            format!("remove {}", path.display())
+        }
+        PendingChange::Rename { from, to } => {
+            format!("rename {} -> {}", from.display(), to.display())
+        }
+    }
+}
+
+/// `gestalt vfs`: list, inspect, flush or discard journaled agent overlays.
+async fn run_vfs_command(
+    db: &SurrealClient,
+    workspace: &Path,
+    action: VfsCommands,
+    json: bool,
+) -> anyhow::Result<()> {
+    match action {
+        VfsCommands::List => {
+            let mut overlays = Vec::new();
+            for journal in VfsJournal::discover(workspace).await? {
+                let agent = journal.agent();
+                let vfs = OverlayFs::inspect(&journal).await?;
+                overlays.push((agent, vfs.version().await, vfs.pending_changes().await));
+            }
+            if json {
+                let overlays: Vec<_> = overlays
+                    .iter()
+                    .map(|(agent, version, pending)| {
+                        serde_json::json!({
+                            "agent": agent,
+                            "version": version,
+                            "pending": pending.iter().map(pending_change_json).collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&overlays)?);
+            } else if overlays.is_empty() {
+                println!("📋 No journaled VFS overlays.");
+            } else {
+                println!("📋 Journaled VFS overlays:");
+                for (agent, version, pending) in overlays {
+                    println!(
+                        "  • {} - {} pending change(s), version {}",
+                        agent,
+                        pending.len(),
+                        version
+                    );
+                }
+            }
+        }
+
+        VfsCommands::Show { agent } => {
+            let vfs = OverlayFs::inspect(&agent_journal(workspace, &agent)?).await?;
+            let pending = vfs.pending_changes().await;
+            let diff = gestalt_core::ports::outbound::vfs::overlay_diff(&vfs, workspace).await?;
+            if json {
+                let overlay = serde_json::json!({
+                    "agent": agent,
+                    "version": vfs.version().await,
+                    "pending": pending.iter().map(pending_change_json).collect::<Vec<_>>(),
+                    "diff": diff,
+                });
+                println!("{}", serde_json::to_string_pretty(&overlay)?);
+            } else {
+                println!(
+                    "📂 Overlay of {} ({} pending change(s)):",
+                    agent,
+                    pending.len()
+                );
+                for change in &pending {
+                    println!("  • {}", describe_pending_change(change));
+                }
+                if !diff.is_empty() {
+                    println!("\n{}", diff);
+                }
+            }
+        }
+
+        VfsCommands::Flush { agent, force } => {
+            let vfs = recover_overlay(db, workspace, &agent, force).await?;
+            let report = vfs.flush().await?;
+            if json {
+                let errors: Vec<_> = report
+                    .errors
+                    .iter()
+                    .map(|e| {
+                        serde_json::json!({
+                            "path": e.path,
+                            "operation": e.operation,
+                            "error": e.error,
+                        })
+                    })
+                    .collect();
+                let flushed = serde_json::json!({
+                    "agent": agent,
+                    "version": vfs.version().await,
+                    "files": report.written_files,
+                    "dirs": report.created_dirs,
+                    "removed": report.removed_paths,
+                    "merged": report.merged_files,
+                    "conflicts": report.conflicts.iter().map(|c| &c.path).collect::<Vec<_>>(),
+                    "errors": errors,
+                });
+                println!("{}", serde_json::to_string_pretty(&flushed)?);
+            } else if report.has_errors() {
+                println!("❌ Flush of {} failed; nothing was written:", agent);
+                for error in &report.errors {
+                    println!(
+                        "  • {} {}: {}",
+                        error.operation,
+                        error.path.display(),
+                        error.error
+                    );
+                }
+            } else {
+                println!(
+                    "✅ Flushed {}: files={}, dirs={}, removed={}, merged={}",
+                    agent,
+                    report.written_files.len(),
+                    report.created_dirs.len(),
+                    report.removed_paths.len(),
+                    report.merged_files.len()
+                );
+            }
+            if report.has_errors() {
+                anyhow::bail!("VFS flush of agent '{}' failed", agent);
+            }
+        }
+
+        VfsCommands::Discard { agent, force } => {
+            let discarded = recover_overlay(db, workspace, &agent, force)
+                .await?
+                .pending_changes()
+                .await
+                .len();
+            VfsJournal::for_agent(workspace, &agent).remove().await?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "agent": agent, "discarded": discarded })
+                );
+            } else {
+                println!("🗑️ Discarded {} pending change(s) of {}", discarded, agent);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One-line summary of a queue entry for `gestalt queue list`.
+fn describe_queued_task(entry: &QueuedTask) -> String {
+    let mut line = format!(
+        "[{}] {} (p{}, attempt {}/{}) {}",
+        entry.status,
+        entry.key().unwrap_or_default(),
+        entry.priority,
+        entry.attempts,
+        entry.max_attempts,
+        entry.goal.chars().take(60).collect::<String>()
+    );
+    if let Some(at) = entry.available_at.filter(|at| *at > Utc::now()) {
+        line.push_str(&format!(" (retry at {})", at.format("%Y-%m-%d %H:%M:%S")));
+    }
+    line
+}
+
+/// `gestalt queue`: add, list, inspect, requeue or cancel queued tasks.
+async fn run_queue_command(queue: &TaskQueue, action: QueueCommands, json: bool) -> Result<()> {
+    match action {
+        QueueCommands::Add {
+            goal,
+            priority,
+            max_attempts,
+        } => {
+            let queued = QueuedTask::new(
+                goal.clone(),
+                TaskSource::Cli {
+                    invocation: format!(
+                        "gestalt queue add '{}'",
+                        goal.chars().take(60).collect::<String>()
+                    ),
+                },
+                priority,
+            )
+            .with_max_attempts(max_attempts);
+            let saved = queue.enqueue(queued).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&saved)?);
+            } else {
+                println!(
+                    "📥 Task queued: '{}' (id: {}, priority: {})",
+                    goal.chars().take(80).collect::<String>(),
+                    saved.key().unwrap_or_default(),
+                    saved.priority
+                );
+                println!("   Start the Nexus daemon to process: gestalt nexus");
+            }
+        }
+
+        QueueCommands::List { status } => {
+            let entries = queue.list(status.as_deref()).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("📋 The task queue is empty.");
+            } else {
+                println!("📋 Task queue ({} entries):", entries.len());
+                for entry in &entries {
+                    println!("  • {}", describe_queued_task(entry));
+                }
+            }
+        }
+
+        QueueCommands::Show { id } => {
+            let entry = queue
+                .get(&id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Queue entry '{}' not found", id))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("📄 {}", describe_queued_task(&entry));
+                println!("   Goal: {}", entry.goal);
+                println!("   Source: {:?}", entry.source);
+                println!(
+                    "   Queued at: {}",
+                    entry.queued_at.format("%Y-%m-%d %H:%M:%S")
+                );
+                if let Some(worker) = &entry.assigned_agent_id {
+                    println!("   Worker: {}", worker);
+                }
+                if let Some(task_id) = &entry.task_id {
+                    println!("   Task: {}", task_id);
+                }
+                match &entry.status {
+                    QueueStatus::Failed { reason } | QueueStatus::DeadLetter { reason } => {
+                        println!("   Reason: {}", reason)
+                    }
+                    _ => {}
+                }
+                if let Some(error) = &entry.last_error {
+                    println!("   Last error: {}", error);
+                }
+            }
+        }
+
+        QueueCommands::Requeue { id } => {
+            let entry = queue.requeue(&id).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("🔁 Requeued {}", describe_queued_task(&entry));
+            }
+        }
+
+        QueueCommands::Cancel { id } => {
+            let entry = queue.cancel(&id).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("🛑 Cancelled {}", describe_queued_task(&entry));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
+    // Parse CLI arguments
+    let cli = Cli::parse();
+
+    // Initialize logging. stdout carries the protocol for `mcp-serve` over stdio.
+    let log_writer = if matches!(cli.command, Some(Commands::McpServe { http: false, .. })) {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(log_writer))
         .init();
 
     // Load environment variables
@@ -194,17 +579,17 @@ async fn main() -> anyhow::Result<()> {
     // Load configuration
     let settings = Settings::new()?;
 
-    // Parse CLI arguments
-    let cli = Cli::parse();
-
     // Initialize database connection
     let db = SurrealClient::connect(&settings.database).await?;
     let vector_db: Arc<dyn gestalt_core::ports::outbound::repo_manager::VectorDb> =
         Arc::new(db.clone());
 
-    // Default lightweight embedding model — DummyEmbeddingModel for fast local builds.
+    // Configured embedding model, falling back to the dummy model for fast local builds.
     let embedding_model: Arc<dyn gestalt_core::domain::rag::embeddings::EmbeddingModel> =
-        Arc::new(gestalt_core::domain::rag::embeddings::DummyEmbeddingModel::new(384));
+        match &settings.embedding {
+            Some(config) => gestalt_core::adapters::embeddings::from_config(config).await?,
+            None => Arc::new(gestalt_core::domain::rag::embeddings::DummyEmbeddingModel::new(384)),
+        };
 
     // Initialize services
     let timeline_service = TimelineService::new(db.clone());
@@ -219,7 +604,7 @@ async fn main() -> anyhow::Result<()> {
     // Check if we have a direct prompt (Context Engine Mode)
     if let Some(prompt) = &cli.prompt {
         // Initialize decision engine
-        let engine = init_decision_engine(&settings.cognition).await?;
+        let engine = init_decision_engine(&settings.cognition, &db).await?;
 
         let mut final_prompt = prompt.clone();
         if cli.context {
@@ -275,10 +660,17 @@ async fn main() -> anyhow::Result<()> {
         Some(Commands::AddTask {
             project,
             description,
+            depends_on,
         }) => {
-            let task = task_service
+            let mut task = task_service
                 .create_task(&project, &description, &agent_id)
                 .await?;
+            if !depends_on.is_empty() {
+                let task_id = task.id.as_ref().map(|t| t.id.to_raw()).unwrap_or_default();
+                task = task_service
+                    .set_dependencies(&task_id, &depends_on, &agent_id)
+                    .await?;
+            }
             if cli.json {
                 println!("{}", serde_json::to_string_pretty(&task)?);
             } else {
@@ -297,6 +689,7 @@ async fn main() -> anyhow::Result<()> {
             id,
             description,
             status,
+            depends_on,
         }) => {
             let task_status = if let Some(s) = status {
                 match s.to_lowercase().as_str() {
@@ -316,9 +709,16 @@ async fn main() -> anyhow::Result<()> {
                 None
             };
 
-            let task = task_service
+            let mut task = task_service
                 .update_task(&id, description, task_status, &agent_id)
                 .await?;
+            if let Some(depends_on) = depends_on {
+                let depends_on: Vec<String> =
+                    depends_on.into_iter().filter(|d| !d.is_empty()).collect();
+                task = task_service
+                    .set_dependencies(&id, &depends_on, &agent_id)
+                    .await?;
+            }
             if cli.json {
                 println!("{}", serde_json::to_string_pretty(&task)?);
             } else {
@@ -335,26 +735,44 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
-        Some(Commands::ScheduleTask { id, time }) => {
-            // Simple parsing for now - assumes ISO 8601 or we can try flexible parsing if needed
-            // For MVP, strict ISO or fail
-            let execute_at = match DateTime::parse_from_rfc3339(&time) {
-                Ok(dt) => dt.with_timezone(&Utc),
-                Err(_) => {
-                    eprintln!(
-                        "Invalid time format. Please use ISO 8601 (e.g., 2023-10-27T10:00:00Z)"
-                    );
-                    return Ok(());
+        Some(Commands::ScheduleTask { id, time, cron }) => {
+            let task = match (cron, time) {
+                (Some(cron), _) => task_service.schedule_cron(&id, &cron, &agent_id).await?,
+                (None, Some(time)) => {
+                    // Strict ISO 8601 only
+                    let execute_at = match DateTime::parse_from_rfc3339(&time) {
+                        Ok(dt) => dt.with_timezone(&Utc),
+                        Err(_) => {
+                            eprintln!(
+                                "Invalid time format. Please use ISO 8601 (e.g., 2023-10-27T10:00:00Z)"
+                            );
+                            return Ok(());
+                        }
+                    };
+                    task_service
+                        .schedule_task(&id, execute_at, &agent_id)
+                        .await?
                 }
+                (None, None) => unreachable!("clap requires a time or --cron"),
             };
-
-            let task = task_service
-                .schedule_task(&id, execute_at, &agent_id)
-                .await?;
             if cli.json {
                 println!("{}", serde_json::to_string_pretty(&task)?);
             } else {
-                println!("⏰ Task scheduled: {} at {}", id, execute_at);
+                let next_run = task
+                    .scheduled_for
+                    .as_ref()
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default();
+                match &task.cron {
+                    Some(cron) => {
+                        println!(
+                            "⏰ Task scheduled: {} on '{}', next at {}",
+                            id, cron, next_run
+                        )
+                    }
+                    None => println!("⏰ Task scheduled: {} at {}", id, next_run),
+                }
+                println!("   Start the Nexus daemon to fire it: gestalt nexus");
             }
         }
 
@@ -367,6 +785,33 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
+        Some(Commands::CancelTask { task_id }) => {
+            let task = task_service.cancel_task(&task_id, &agent_id).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&task)?);
+            } else {
+                println!("🛑 Task {} cancelled: {}", task_id, task.description);
+            }
+        }
+
+        Some(Commands::PauseTask { task_id }) => {
+            let request = task_service.pause_task(&task_id, &agent_id).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&request)?);
+            } else {
+                println!("⏸️  Pause requested for task {}", task_id);
+            }
+        }
+
+        Some(Commands::ResumeTask { task_id }) => {
+            let request = task_service.resume_task(&task_id, &agent_id).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&request)?);
+            } else {
+                println!("▶️  Resume requested for task {}", task_id);
+            }
+        }
+
         Some(Commands::ListProjects) => {
             let projects = project_service.list_projects().await?;
             if cli.json {
@@ -402,6 +847,80 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
+        Some(Commands::Graph { project }) => {
+            let graph = task_service.graph(&project).await?;
+            let critical_path = graph.critical_path();
+            let cycle = graph.find_cycle();
+            let label = |id: &str| graph.get(id).map_or(id.to_string(), |n| n.label.clone());
+
+            if cli.json {
+                let tasks: Vec<serde_json::Value> = graph
+                    .nodes()
+                    .iter()
+                    .map(|node| {
+                        serde_json::json!({
+                            "task": node,
+                            "readiness": graph.readiness(&node.id),
+                        })
+                    })
+                    .collect();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "project": project,
+                        "tasks": tasks,
+                        "cycle": cycle,
+                        "critical_path": critical_path,
+                    }))?
+                );
+            } else if graph.nodes().is_empty() {
+                println!("📋 No tasks found.");
+            } else {
+                println!("🕸️ Task graph: {}", project);
+                for node in graph.topological_order() {
+                    let state = match (&node.status, graph.readiness(&node.id)) {
+                        (TaskStatus::Pending, Some(Readiness::Ready)) => "ready".to_string(),
+                        (TaskStatus::Pending, Some(Readiness::Blocked { .. })) => {
+                            "blocked".to_string()
+                        }
+                        (TaskStatus::Pending, Some(Readiness::DependencyFailed { .. })) => {
+                            "blocked: dependency failed".to_string()
+                        }
+                        (status, _) => status.to_string(),
+                    };
+                    let marker = if critical_path.tasks.contains(&node.id) {
+                        "🔥"
+                    } else {
+                        "  "
+                    };
+                    let duration = node
+                        .duration_ms
+                        .map(|ms| format!(" ({:.1}s)", ms as f64 / 1000.0))
+                        .unwrap_or_default();
+                    let after = if node.depends_on.is_empty() {
+                        String::new()
+                    } else {
+                        let deps: Vec<String> = node.depends_on.iter().map(|d| label(d)).collect();
+                        format!(" ← {}", deps.join(", "))
+                    };
+                    println!(
+                        "  {} [{}] {}{}{}",
+                        marker, state, node.label, duration, after
+                    );
+                }
+                if let Some(cycle) = cycle {
+                    let ids: Vec<String> = cycle.iter().map(|id| label(id)).collect();
+                    println!("⚠️ Dependency cycle: {}", ids.join(" → "));
+                }
+                let path: Vec<String> = critical_path.tasks.iter().map(|id| label(id)).collect();
+                println!(
+                    "🔥 Critical path ({:.1}s): {}",
+                    critical_path.duration_ms as f64 / 1000.0,
+                    path.join(" → ")
+                );
+            }
+        }
+
         Some(Commands::Status { project }) => {
             let status = project_service.get_status(&project).await?;
             if cli.json {
@@ -439,6 +958,44 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
+        Some(Commands::Usage { by, days }) => {
+            let group: UsageGroup = by.parse()?;
+            let since = days.map(|d| Utc::now() - chrono::Duration::days(d as i64));
+            let rows = UsageService::new(db.clone()).report(group, since).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else if rows.is_empty() {
+                println!("📊 No LLM usage recorded.");
+            } else {
+                println!("📊 LLM usage by {}:", group);
+                println!(
+                    "  {:<40} {:>5} {:>12} {:>12} {:>10}",
+                    group.to_string(),
+                    "runs",
+                    "prompt",
+                    "completion",
+                    "cost"
+                );
+                for row in &rows {
+                    println!(
+                        "  {:<40} {:>5} {:>12} {:>12} {:>10}",
+                        row.key,
+                        row.runs,
+                        row.prompt_tokens,
+                        row.completion_tokens,
+                        format!("${:.4}", row.cost_usd)
+                    );
+                }
+                println!(
+                    "  Total: {} tokens, ${:.4}",
+                    rows.iter()
+                        .map(|r| r.prompt_tokens + r.completion_tokens)
+                        .sum::<u64>(),
+                    rows.iter().map(|r| r.cost_usd).sum::<f64>()
+                );
+            }
+        }
+
         Some(Commands::Watch { project, events }) => {
             // Parse event filter
             let event_filter = events.map(|e| {
@@ -542,14 +1099,12 @@ async fn main() -> anyhow::Result<()> {
 
         Some(Commands::AiChat { message }) => {
             // Initialize decision engine
-            let engine = init_decision_engine(&settings.cognition).await?;
-
-            println!("🤖 Sending message to Decision Engine...");
-            let context = DecisionContext::new("chat").with_summary(&message);
-
-            let decision = engine.decide(&context).await?;
+            let engine = init_decision_engine(&settings.cognition, &db).await?;
 
             if cli.json {
+                let context = DecisionContext::new("chat").with_summary(&message);
+                let decision = engine.decide(&context).await?;
+
                 let json_resp = serde_json::json!({
                     "action": decision.action,
                     "reasoning": decision.reasoning,
@@ -558,9 +1113,19 @@ async fn main() -> anyhow::Result<()> {
                 });
                 println!("{}", serde_json::to_string_pretty(&json_resp)?);
             } else {
+                // Print the reply as it streams in so long generations show progress
+                use futures::StreamExt;
+                use std::io::Write;
+
+                println!("🤖 Sending message to Decision Engine...");
                 println!("\n💬 Decision Engine:");
-                println!("Action: {}", decision.action);
-                println!("Reasoning: {}", decision.reasoning);
+                let mut deltas = engine.stream_reply(&message).await?;
+                let mut stdout = std::io::stdout();
+                while let Some(delta) = deltas.next().await {
+                    write!(stdout, "{}", delta?)?;
+                    stdout.flush()?;
+                }
+                println!();
             }
         }
 
@@ -570,7 +1135,7 @@ async fn main() -> anyhow::Result<()> {
             dry_run: _,
         }) => {
             // Initialize decision engine
-            let engine = init_decision_engine(&settings.cognition).await?;
+            let engine = init_decision_engine(&settings.cognition, &db).await?;
             let registry = init_tool_registry(vector_db.clone(), embedding_model.clone()).await;
             let memory_service = MemoryService::new(db.clone());
 
@@ -585,7 +1150,8 @@ async fn main() -> anyhow::Result<()> {
                 watch_service.clone(),
                 agent_service.clone(),
                 memory_service,
-            );
+            )
+            .with_model(&settings.cognition.model_id);
 
             println!("🔄 Starting Autonomous Agent Loop: '{}'", workflow);
 
@@ -636,7 +1202,7 @@ async fn main() -> anyhow::Result<()> {
 
         Some(Commands::Server { port }) => {
             // Initialize decision engine
-            let engine = init_decision_engine(&settings.cognition).await?;
+            let engine = init_decision_engine(&settings.cognition, &db).await?;
             let registry = init_tool_registry(vector_db.clone(), embedding_model.clone()).await;
             let memory_service = MemoryService::new(db.clone());
 
@@ -651,7 +1217,8 @@ async fn main() -> anyhow::Result<()> {
                 watch_service.clone(),
                 agent_service.clone(),
                 memory_service,
-            );
+            )
+            .with_model(&settings.cognition.model_id);
 
             start_server(
                 runtime,
@@ -665,6 +1232,38 @@ async fn main() -> anyhow::Result<()> {
             .await?;
         }
 
+        Some(Commands::McpServe { http, host, port }) => {
+            let registry = init_tool_registry(vector_db.clone(), embedding_model.clone()).await;
+            let (vfs, actor) = FileManager::for_agent(&agent_id);
+            tokio::spawn(actor.run());
+
+            let policy = PolicyEngine::from_env().unwrap_or_else(|e| {
+                warn!("Policy rejected, denying every tool call: {:#}", e);
+                PolicyEngine::deny_all()
+            });
+            let approvals = ApprovalService::new(db.clone(), timeline_service.clone());
+
+            let server = Arc::new(
+                build_mcp_server(
+                    registry,
+                    Arc::new(vfs),
+                    timeline_service.clone(),
+                    Arc::new(policy),
+                    approvals,
+                    &agent_id,
+                )
+                .await,
+            );
+
+            if http {
+                println!("🔌 Gestalt MCP server: http://{}:{}/mcp", host, port);
+                serve_mcp_http(server, &host, port).await?;
+            } else {
+                info!("🔌 Gestalt MCP server on stdio");
+                server.serve_stdio().await?;
+            }
+        }
+
         Some(Commands::Login) => {
             let auth = AuthService::new()?;
 
@@ -689,13 +1288,14 @@ async fn main() -> anyhow::Result<()> {
 
         Some(Commands::Chat) => {
             // Initialize decision engine
-            let engine = init_decision_engine(&settings.cognition).await?;
+            let engine = init_decision_engine(&settings.cognition, &db).await?;
 
             // Run REPL
-            repl::run_repl(&agent_id, engine).await?;
+            let approvals = ApprovalService::new(db.clone(), timeline_service.clone());
+            repl::run_repl(&agent_id, engine, approvals).await?;
         }
 
-        Some(Commands::IndexRepo { url }) => {
+        Some(Commands::IndexRepo { url, watch }) => {
             // Check mode before write operation
             if cli.mode.to_lowercase() == "plan" {
                 eprintln!("❌ Cannot index repository in 'plan' mode. Use '--mode build' for write operations.");
@@ -705,11 +1305,32 @@ async fn main() -> anyhow::Result<()> {
             println!("📥 Indexing repository: {}", url);
             let index_service = IndexService::new(db.clone(), embedding_model.clone());
             match index_service.index_repo(&url).await {
-                Ok(_) => {
+                Ok(report) => {
                     if cli.json {
-                        println!(r#"{{"status": "completed", "url": "{}"}}"#, url);
+                        println!(
+                            r#"{{"status": "completed", "url": "{}", "incremental": {}, "indexed": {}, "unchanged": {}, "removed": {}}}"#,
+                            url,
+                            report.incremental,
+                            report.indexed,
+                            report.unchanged,
+                            report.removed
+                        );
                     } else {
-                        println!("✅ Repository indexed successfully: {}", url);
+                        println!(
+                            "✅ Repository indexed successfully: {} ({} indexed, {} unchanged, {} removed{})",
+                            url,
+                            report.indexed,
+                            report.unchanged,
+                            report.removed,
+                            if report.incremental { ", incremental" } else { "" }
+                        );
+                    }
+
+                    if watch {
+                        println!("👀 Watching {} for changes (Ctrl+C to stop)", url);
+                        index_service
+                            .watch(&url, &OverlayFs::new(), std::time::Duration::from_secs(1))
+                            .await?;
                     }
                 }
                 Err(e) => {
@@ -734,7 +1355,7 @@ async fn main() -> anyhow::Result<()> {
                     .ok_or_else(|| anyhow::anyhow!("Telegram settings not configured"))?;
 
                 // Initialize decision engine
-                let engine = init_decision_engine(&settings.cognition).await?;
+                let engine = init_decision_engine(&settings.cognition, &db).await?;
                 let watch_service =
                     Arc::new(WatchService::new(db.clone(), timeline_service.clone()));
 
@@ -760,6 +1381,7 @@ async fn main() -> anyhow::Result<()> {
             workers,
             port,
             daemon,
+            auto_resume,
         }) => {
             if daemon {
                 println!("🚀 Starting Gestalt Nexus in background...");
@@ -794,15 +1416,15 @@ async fn main() -> anyhow::Result<()> {
             info!("   Workers: {}, API Port: {}", workers, port);
 
             // Initialize cognition
-            let cognition = init_decision_engine(&settings.cognition).await?;
+            let cognition = init_decision_engine(&settings.cognition, &db).await?;
             let registry = init_tool_registry(vector_db.clone(), embedding_model.clone()).await;
 
             // Initialize memory service
             let _memory_service = Arc::new(MemoryService::new(db.clone()));
 
-            // Create TaskQueue (buffer = 256 tasks)
-            let (task_queue, task_receiver) = TaskQueue::new(db.clone(), 256);
-            let task_queue = Arc::new(task_queue);
+            // TaskQueue backed by SurrealDB; entries of a previous run are picked
+            // up again once their leases expire
+            let task_queue = Arc::new(TaskQueue::new(db.clone()));
 
             // Wire Telegram bot to TaskQueue if configured
             #[cfg(feature = "telegram")]
@@ -839,11 +1461,13 @@ async fn main() -> anyhow::Result<()> {
                 None
             };
 
-            // Recover any pending tasks from DB (handles restarts)
-            let pending = task_queue.recover_pending().await.unwrap_or_default();
-            for pending_task in pending {
-                let _ = task_queue.enqueue(pending_task).await;
-            }
+            // Fire one-shot and cron-scheduled tasks into the queue
+            let scheduler = SchedulerService::new(
+                db.clone(),
+                timeline_service.clone(),
+                task_queue.as_ref().clone(),
+            );
+            tokio::spawn(async move { scheduler.run().await });
 
             // Clone services for the factory closure
             let project_service_clone = project_service.clone();
@@ -865,7 +1489,8 @@ async fn main() -> anyhow::Result<()> {
                 watch_service.clone(),
                 agent_service.clone(),
                 memory_service.clone(),
-            );
+            )
+            .with_model(&settings.cognition.model_id);
             let api_handle = tokio::spawn(async move {
                 if let Err(e) = start_server(
                     api_runtime,
@@ -882,6 +1507,37 @@ async fn main() -> anyhow::Result<()> {
                 }
             });
 
+            // Continue agent loops a previous process left unfinished; the
+            // queue retries the entries of its own workers
+            if auto_resume {
+                for state in AgentRuntime::interrupted(&db).await? {
+                    if is_worker_id(&state.agent_id) {
+                        continue;
+                    }
+                    info!(
+                        "🔄 Resuming agent {} at step {}",
+                        state.agent_id, state.current_step
+                    );
+                    let runtime = AgentRuntime::new(
+                        state.agent_id.clone(),
+                        cognition.clone(),
+                        registry.clone(),
+                        project_service.clone(),
+                        task_service.clone(),
+                        timeline_service.clone(),
+                        watch_service.clone(),
+                        agent_service.clone(),
+                        memory_service.clone(),
+                    )
+                    .with_model(&settings.cognition.model_id);
+                    tokio::spawn(async move {
+                        if let Err(e) = runtime.resume().await {
+                            tracing::error!("Resumed agent failed: {}", e);
+                        }
+                    });
+                }
+            }
+
             // Launch the TaskQueue dispatch loop
             let tq_clone = Arc::clone(&task_queue);
             let tq_engine = cognition.clone();
@@ -896,8 +1552,9 @@ async fn main() -> anyhow::Result<()> {
                 workers
             );
             let tq_memory = memory_service.clone();
+            let tq_model = settings.cognition.model_id.clone();
             tq_clone
-                .run_dispatch_loop(task_receiver, workers, move |agent_id_str| {
+                .run_dispatch_loop(workers, move |agent_id_str| {
                     let engine = tq_engine.clone();
                     let registry = tq_registry.clone();
                     let project = tq_project.clone();
@@ -906,6 +1563,7 @@ async fn main() -> anyhow::Result<()> {
                     let agent = tq_agent.clone();
                     let memory = tq_memory.clone();
                     let timeline = timeline_clone.clone();
+                    let model = tq_model.clone();
                     async move {
                         Ok(AgentRuntime::new(
                             agent_id_str,
@@ -917,7 +1575,8 @@ async fn main() -> anyhow::Result<()> {
                             watch,
                             agent,
                             memory,
-                        ))
+                        )
+                        .with_model(&model))
                     }
                 })
                 .await;
@@ -931,30 +1590,8 @@ async fn main() -> anyhow::Result<()> {
             info!("🔴 Gestalt Nexus daemon shutting down.");
         }
 
-        Some(Commands::Queue { goal, priority }) => {
-            // Direct CLI task injection into the queue
-            let (task_queue, _receiver) = TaskQueue::new(db.clone(), 1);
-            let queued = QueuedTask::new(
-                goal.clone(),
-                TaskSource::Cli {
-                    invocation: format!("gestalt queue '{}'", &goal[..goal.len().min(60)]),
-                },
-                priority,
-            );
-            task_queue.enqueue(queued).await?;
-            if cli.json {
-                println!(
-                    r#"{{"status": "queued", "goal": "{}", "priority": {} }}"#,
-                    goal, priority
-                );
-            } else {
-                println!(
-                    "📥 Task queued: '{}' (priority: {})",
-                    &goal[..goal.len().min(80)],
-                    priority
-                );
-                println!("   Start the Nexus daemon to process: gestalt nexus");
-            }
+        Some(Commands::Queue { action }) => {
+            run_queue_command(&TaskQueue::new(db.clone()), action, cli.json).await?;
         }
 
         Some(Commands::Agent { action }) => {
@@ -979,19 +1616,61 @@ async fn main() -> anyhow::Result<()> {
                     // MVP implementation
                     println!("📋 Agent process list (Check timeline for outputs)");
                 }
+                AgentCommands::Resume { id } => {
+                    let engine = init_decision_engine(&settings.cognition, &db).await?;
+                    let registry =
+                        init_tool_registry(vector_db.clone(), embedding_model.clone()).await;
+                    let runtime = AgentRuntime::new(
+                        id.clone(),
+                        engine,
+                        registry,
+                        project_service.clone(),
+                        task_service.clone(),
+                        timeline_service.clone(),
+                        watch_service.clone(),
+                        agent_service.clone(),
+                        MemoryService::new(db.clone()),
+                    )
+                    .with_model(&settings.cognition.model_id);
+
+                    if !cli.json {
+                        println!("🔄 Resuming Autonomous Agent Loop: '{}'", id);
+                    }
+                    let result = runtime.resume().await;
+                    if cli.json {
+                        println!(
+                            "{}",
+                            serde_json::json!({
+                                "agent_id": id,
+                                "status": if result.is_ok() { "completed" } else { "failed" },
+                                "error": result.as_ref().err().map(|e| e.to_string()),
+                            })
+                        );
+                    } else {
+                        match result {
+                            Ok(_) => println!("\n✅ Autonomous Goal Completed."),
+                            Err(e) => println!("\n❌ Agent Error: {:?}", e),
+                        }
+                    }
+                }
             }
         }
 
+        Some(Commands::Vfs { workspace, action }) => {
+            run_vfs_command(&db, Path::new(&workspace), action, cli.json).await?;
+        }
+
         None => {
             // No command provided. If prompt is also None (checked above), show help or REPL
             // But we handled prompt above. So if we are here, prompt was None and command was None.
             // Start REPL in that case.
 
             // Initialize decision engine
-            let engine = init_decision_engine(&settings.cognition).await?;
+            let engine = init_decision_engine(&settings.cognition, &db).await?;
 
             // Run REPL
-            repl::run_repl(&agent_id, engine).await?;
+            let approvals = ApprovalService::new(db.clone(), timeline_service.clone());
+            repl::run_repl(&agent_id, engine, approvals).await?;
         }
     }
 
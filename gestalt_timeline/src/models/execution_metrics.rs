@@ -7,6 +7,7 @@ use super::FlexibleTimestamp;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use surrealdb::sql::Thing;
+use synapse_agentic::prelude::Usage;
 
 /// Aggregated execution metrics for a single agent run.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,22 +55,28 @@ pub struct ExecutionMetrics {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub output_lines: Option<u32>,
 
+    /// Associated task ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+
+    /// LLM tokens and spend, one entry per provider used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub llm_usage: Vec<Usage>,
+
     /// Additional metadata
     #[serde(default)]
     pub metadata: HashMap<String, String>,
 }
 
 impl ExecutionMetrics {
-    /// Create metrics from swarm agent result.
+    /// Create metrics from swarm agent result, timestamped now.
     pub fn from_agent_result(
         run_id: &str,
         agent_id: &str,
         agent_type: &str,
         success: bool,
         duration_ms: u64,
-        return_code: Option<i32>,
         error_message: Option<String>,
-        timestamp: FlexibleTimestamp,
     ) -> Self {
         let error_category = error_message.as_ref().and_then(|e| categorize_error(e));
         Self {
@@ -80,12 +87,14 @@ impl ExecutionMetrics {
             success,
             duration_ms,
             tools_used: 1,
-            return_code,
+            return_code: None,
             error_category,
             error_message: error_message.map(|e| e.chars().take(200).collect()),
-            timestamp,
+            timestamp: FlexibleTimestamp::now(),
             project_id: None,
             output_lines: None,
+            task_id: None,
+            llm_usage: Vec::new(),
             metadata: HashMap::new(),
         }
     }
@@ -96,6 +105,33 @@ impl ExecutionMetrics {
         self
     }
 
+    /// Set the return code.
+    pub fn with_return_code(mut self, return_code: Option<i32>) -> Self {
+        self.return_code = return_code;
+        self
+    }
+
+    /// Set the task ID.
+    pub fn with_task(mut self, task_id: &str) -> Self {
+        self.task_id = Some(task_id.to_string());
+        self
+    }
+
+    /// Set the LLM usage, one entry per provider.
+    pub fn with_usage(mut self, usage: Vec<Usage>) -> Self {
+        self.llm_usage = usage;
+        self
+    }
+
+    /// LLM usage summed over all providers.
+    pub fn total_usage(&self) -> Usage {
+        let mut total = Usage::default();
+        for usage in &self.llm_usage {
+            total.add(usage);
+        }
+        total
+    }
+
     /// Set output lines count.
     pub fn with_output_lines(mut self, lines: u32) -> Self {
         self.output_lines = Some(lines);
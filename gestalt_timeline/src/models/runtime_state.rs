@@ -3,6 +3,7 @@
 use super::FlexibleTimestamp;
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Thing;
+use synapse_agentic::prelude::Usage;
 
 /// Persisted state snapshot for a running autonomous agent loop.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +12,9 @@ pub struct AgentRuntimeState {
     pub id: Option<Thing>,
     pub agent_id: String,
     pub goal: String,
+    /// Timeline task the loop works on, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
     pub phase: RuntimePhase,
     pub current_step: usize,
     /// `0` means elastic (no fixed limit); otherwise hard safety cap.
@@ -20,6 +24,16 @@ pub struct AgentRuntimeState {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_observation: Option<String>,
     pub history_tail: Vec<String>,
+    /// Latest summary of the history compacted out of `history_tail`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Paths the agent holds VFS locks on, re-acquired on resume
+    #[serde(default)]
+    pub locked_paths: Vec<String>,
+    /// LLM usage of the run so far, one entry per provider; a resumed run
+    /// keeps counting it against its budget
+    #[serde(default)]
+    pub llm_usage: Vec<Usage>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     #[serde(with = "crate::models::timestamp")]
@@ -34,8 +48,22 @@ pub struct AgentRuntimeState {
 #[serde(rename_all = "snake_case")]
 pub enum RuntimePhase {
     Running,
+    /// Waiting for a resume, with the loop held between steps
+    Paused,
     Completed,
     Failed,
+    /// Stopped on request; jobs were killed and locks released
+    Cancelled,
+}
+
+impl RuntimePhase {
+    /// Whether the loop stopped for good; only unfinished runs can be resumed.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            RuntimePhase::Completed | RuntimePhase::Failed | RuntimePhase::Cancelled
+        )
+    }
 }
 
 impl AgentRuntimeState {
@@ -45,12 +73,16 @@ impl AgentRuntimeState {
             id: None,
             agent_id: agent_id.to_string(),
             goal: goal.to_string(),
+            task_id: None,
             phase: RuntimePhase::Running,
             current_step: 0,
             max_steps,
             last_action: None,
             last_observation: None,
             history_tail: Vec::new(),
+            summary: None,
+            locked_paths: Vec::new(),
+            llm_usage: Vec::new(),
             error: None,
             started_at: now.clone(),
             updated_at: now,
@@ -44,6 +44,18 @@ pub struct Task {
 
     /// External identifier for protocol synchronization (e.g. F1-01)
     pub external_id: Option<String>,
+
+    /// IDs of tasks that must complete before this one is ready
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    /// Next time the scheduler fires this task
+    #[serde(default, with = "super::timestamp::option")]
+    pub scheduled_for: Option<FlexibleTimestamp>,
+
+    /// Cron expression re-arming `scheduled_for` after each run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
 }
 
 impl Task {
@@ -67,6 +79,9 @@ impl Task {
             executed_by: None,
             duration_ms: None,
             external_id,
+            depends_on: Vec::new(),
+            scheduled_for: None,
+            cron: None,
         }
     }
 }
@@ -107,6 +107,10 @@ pub enum EventType {
     TaskUpdated,
     /// A task was deleted
     TaskDeleted,
+    /// A task was scheduled to run at a time or on a cron schedule
+    TaskScheduled,
+    /// The scheduler fired a scheduled run of a task into the queue
+    TaskFired,
     /// An agent connected to the system
     AgentConnected,
     /// An agent disconnected from the system
@@ -135,6 +139,26 @@ pub enum EventType {
     VfsFlushStarted,
     /// VFS Flush to disk completed
     VfsFlushCompleted,
+    /// VFS layer snapshot created
+    VfsSnapshotCreated,
+    /// VFS layer rolled back to a snapshot
+    VfsRolledBack,
+    /// VFS layer promoted into its parent
+    VfsLayerPromoted,
+    /// Policy engine allowed, denied or gated an action
+    PolicyDecision,
+    /// An action is waiting for human approval
+    ApprovalRequested,
+    /// A pending action was approved
+    ApprovalGranted,
+    /// A pending action was denied or its approval timed out
+    ApprovalDenied,
+    /// An agent loop paused on request
+    RunPaused,
+    /// A paused agent loop resumed
+    RunResumed,
+    /// An agent loop stopped on a cancel request
+    RunCancelled,
     /// A chat message from user or agent
     ChatMessage,
     /// Custom event type
@@ -166,6 +190,8 @@ impl<'de> Deserialize<'de> for EventType {
             "task_failed" => Ok(EventType::TaskFailed),
             "task_updated" => Ok(EventType::TaskUpdated),
             "task_deleted" => Ok(EventType::TaskDeleted),
+            "task_scheduled" => Ok(EventType::TaskScheduled),
+            "task_fired" => Ok(EventType::TaskFired),
             "agent_connected" => Ok(EventType::AgentConnected),
             "agent_disconnected" => Ok(EventType::AgentDisconnected),
             "command_executed" => Ok(EventType::CommandExecuted),
@@ -176,6 +202,16 @@ impl<'de> Deserialize<'de> for EventType {
             "vfs_lock_conflict" => Ok(EventType::VfsLockConflict),
             "vfs_flush_started" => Ok(EventType::VfsFlushStarted),
             "vfs_flush_completed" => Ok(EventType::VfsFlushCompleted),
+            "vfs_snapshot_created" => Ok(EventType::VfsSnapshotCreated),
+            "vfs_rolled_back" => Ok(EventType::VfsRolledBack),
+            "vfs_layer_promoted" => Ok(EventType::VfsLayerPromoted),
+            "policy_decision" => Ok(EventType::PolicyDecision),
+            "approval_requested" => Ok(EventType::ApprovalRequested),
+            "approval_granted" => Ok(EventType::ApprovalGranted),
+            "approval_denied" => Ok(EventType::ApprovalDenied),
+            "run_paused" => Ok(EventType::RunPaused),
+            "run_resumed" => Ok(EventType::RunResumed),
+            "run_cancelled" => Ok(EventType::RunCancelled),
             "chat_message" => Ok(EventType::ChatMessage),
             other => {
                 if let Some(agent) = other.strip_prefix("sub_agent_spawned:") {
@@ -212,6 +248,8 @@ impl fmt::Display for EventType {
             EventType::TaskFailed => write!(f, "task_failed"),
             EventType::TaskUpdated => write!(f, "task_updated"),
             EventType::TaskDeleted => write!(f, "task_deleted"),
+            EventType::TaskScheduled => write!(f, "task_scheduled"),
+            EventType::TaskFired => write!(f, "task_fired"),
             EventType::AgentConnected => write!(f, "agent_connected"),
             EventType::AgentDisconnected => write!(f, "agent_disconnected"),
             EventType::CommandExecuted => write!(f, "command_executed"),
@@ -222,6 +260,16 @@ impl fmt::Display for EventType {
             EventType::VfsLockConflict => write!(f, "vfs_lock_conflict"),
             EventType::VfsFlushStarted => write!(f, "vfs_flush_started"),
             EventType::VfsFlushCompleted => write!(f, "vfs_flush_completed"),
+            EventType::VfsSnapshotCreated => write!(f, "vfs_snapshot_created"),
+            EventType::VfsRolledBack => write!(f, "vfs_rolled_back"),
+            EventType::VfsLayerPromoted => write!(f, "vfs_layer_promoted"),
+            EventType::PolicyDecision => write!(f, "policy_decision"),
+            EventType::ApprovalRequested => write!(f, "approval_requested"),
+            EventType::ApprovalGranted => write!(f, "approval_granted"),
+            EventType::ApprovalDenied => write!(f, "approval_denied"),
+            EventType::RunPaused => write!(f, "run_paused"),
+            EventType::RunResumed => write!(f, "run_resumed"),
+            EventType::RunCancelled => write!(f, "run_cancelled"),
             EventType::ChatMessage => write!(f, "chat_message"),
             EventType::SubAgentSpawned(s) => write!(f, "sub_agent_spawned:{}", s),
             EventType::SubAgentOutput(s) => write!(f, "sub_agent_output:{}", s),
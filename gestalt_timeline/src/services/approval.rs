@@ -0,0 +1,313 @@
+//! Approval Service - human sign-off for actions gated by the policy engine.
+//!
+//! A runtime that hits a `require_approval` rule files an approval request and
+//! waits on it. Humans answer from the REPL, the HTTP server or Telegram; every
+//! transition is recorded on the timeline.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use tracing::info;
+
+use crate::db::SurrealClient;
+use crate::models::{EventType, TimelineEvent};
+use crate::services::policy::{ActionFacts, PolicyVerdict};
+use crate::services::TimelineService;
+
+const TABLE: &str = "approvals";
+
+/// State of an approval request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Denied,
+    /// Nobody answered within the policy's approval timeout.
+    Expired,
+}
+
+impl fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalStatus::Pending => write!(f, "pending"),
+            ApprovalStatus::Approved => write!(f, "approved"),
+            ApprovalStatus::Denied => write!(f, "denied"),
+            ApprovalStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// An action waiting for (or past) a human decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    /// Short identifier used by `/approve <id>` and the HTTP routes
+    pub approval_id: String,
+    pub agent_id: String,
+    /// Action kind, e.g. `git_push`
+    pub action: String,
+    /// Human-readable description of the concrete action
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub status: ApprovalStatus,
+    pub requested_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+}
+
+/// Stores approval requests and the decisions taken on them.
+#[derive(Clone)]
+pub struct ApprovalService {
+    db: SurrealClient,
+    timeline: TimelineService,
+    poll_interval: Duration,
+}
+
+impl ApprovalService {
+    pub fn new(db: SurrealClient, timeline: TimelineService) -> Self {
+        Self {
+            db,
+            timeline,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// File a pending approval for `facts`, as gated by `verdict`.
+    pub async fn request(
+        &self,
+        facts: &ActionFacts,
+        detail: &str,
+        verdict: &PolicyVerdict,
+    ) -> Result<ApprovalRequest> {
+        let ulid = ulid::Ulid::new().to_string().to_lowercase();
+        let request = ApprovalRequest {
+            approval_id: ulid[ulid.len() - 8..].to_string(),
+            agent_id: facts.agent_id.clone(),
+            action: facts.kind.clone(),
+            detail: detail.to_string(),
+            rule: verdict.rule.clone(),
+            reason: verdict.reason.clone(),
+            status: ApprovalStatus::Pending,
+            requested_at: Utc::now(),
+            decided_at: None,
+            decided_by: None,
+        };
+        let created: ApprovalRequest = self
+            .db
+            .upsert(TABLE, &request.approval_id, &request)
+            .await?;
+
+        info!(
+            "⏸️  Approval {} requested for {} by {}",
+            created.approval_id, created.action, created.agent_id
+        );
+        self.timeline
+            .record_event(
+                TimelineEvent::new(&created.agent_id, EventType::ApprovalRequested)
+                    .with_payload(serde_json::to_value(&created)?),
+            )
+            .await?;
+        Ok(created)
+    }
+
+    /// Get an approval request by ID.
+    pub async fn get(&self, approval_id: &str) -> Result<Option<ApprovalRequest>> {
+        self.db.select_by_id(TABLE, approval_id).await
+    }
+
+    /// All requests still waiting for a decision, oldest first.
+    pub async fn list_pending(&self) -> Result<Vec<ApprovalRequest>> {
+        self.db
+            .query_with(
+                "SELECT * FROM approvals WHERE status = 'pending' ORDER BY requested_at ASC",
+                serde_json::json!({}),
+            )
+            .await
+    }
+
+    /// Approve or deny a pending request on behalf of `decided_by`.
+    pub async fn decide(
+        &self,
+        approval_id: &str,
+        approve: bool,
+        decided_by: &str,
+    ) -> Result<ApprovalRequest> {
+        let status = if approve {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Denied
+        };
+        let decided = self.transition(approval_id, status, decided_by).await?;
+        info!(
+            "Approval {} {} by {}",
+            decided.approval_id, decided.status, decided_by
+        );
+        Ok(decided)
+    }
+
+    /// Wait until `approval_id` is decided, expiring it after `timeout`.
+    pub async fn wait(&self, approval_id: &str, timeout: Duration) -> Result<ApprovalStatus> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let current = self
+                .get(approval_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Approval '{}' not found", approval_id))?;
+            if current.status != ApprovalStatus::Pending {
+                return Ok(current.status);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+
+        match self
+            .transition(approval_id, ApprovalStatus::Expired, "timeout")
+            .await
+        {
+            Ok(expired) => Ok(expired.status),
+            // Decided between our last poll and the expiry.
+            Err(_) => Ok(self
+                .get(approval_id)
+                .await?
+                .map(|r| r.status)
+                .unwrap_or(ApprovalStatus::Expired)),
+        }
+    }
+
+    /// Expire a pending request nobody waits on any more, e.g. because the run
+    /// that asked for it was cancelled.
+    pub async fn expire(&self, approval_id: &str, expired_by: &str) -> Result<ApprovalRequest> {
+        self.transition(approval_id, ApprovalStatus::Expired, expired_by)
+            .await
+    }
+
+    /// Move a pending request to `status`. Fails if it is unknown or already decided.
+    async fn transition(
+        &self,
+        approval_id: &str,
+        status: ApprovalStatus,
+        decided_by: &str,
+    ) -> Result<ApprovalRequest> {
+        let updated: Vec<ApprovalRequest> = self
+            .db
+            .query_with(
+                "UPDATE type::thing('approvals', $id) \
+                 SET status = $status, decided_by = $by, decided_at = $at \
+                 WHERE status = 'pending' RETURN AFTER",
+                serde_json::json!({
+                    "id": approval_id,
+                    "status": status,
+                    "by": decided_by,
+                    "at": Utc::now(),
+                }),
+            )
+            .await?;
+
+        let Some(decided) = updated.into_iter().next() else {
+            return match self.get(approval_id).await? {
+                Some(existing) => Err(anyhow::anyhow!(
+                    "Approval '{}' is already {}",
+                    approval_id,
+                    existing.status
+                )),
+                None => Err(anyhow::anyhow!("Approval '{}' not found", approval_id)),
+            };
+        };
+
+        let event_type = if status == ApprovalStatus::Approved {
+            EventType::ApprovalGranted
+        } else {
+            EventType::ApprovalDenied
+        };
+        self.timeline
+            .record_event(
+                TimelineEvent::new(&decided.agent_id, event_type)
+                    .with_payload(serde_json::to_value(&decided)?),
+            )
+            .await?;
+        Ok(decided)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::policy::PolicyDecision;
+
+    async fn setup() -> (ApprovalService, ApprovalRequest) {
+        let db = SurrealClient::connect_mem().await.unwrap();
+        let mut service = ApprovalService::new(db.clone(), TimelineService::new(db));
+        service.poll_interval = Duration::from_millis(10);
+        let verdict = PolicyVerdict {
+            decision: PolicyDecision::RequireApproval,
+            rule: Some("pushes".to_string()),
+            reason: None,
+        };
+        let request = service
+            .request(
+                &ActionFacts::new("git_push", "agent-1"),
+                "git push origin main",
+                &verdict,
+            )
+            .await
+            .unwrap();
+        (service, request)
+    }
+
+    #[tokio::test]
+    async fn test_decide_pending_request() -> Result<()> {
+        let (service, request) = setup().await;
+        assert_eq!(request.status, ApprovalStatus::Pending);
+        assert_eq!(service.list_pending().await?.len(), 1);
+
+        let waiter = {
+            let service = service.clone();
+            let id = request.approval_id.clone();
+            tokio::spawn(async move { service.wait(&id, Duration::from_secs(5)).await })
+        };
+        let decided = service.decide(&request.approval_id, true, "alice").await?;
+        assert_eq!(decided.status, ApprovalStatus::Approved);
+        assert_eq!(decided.decided_by.as_deref(), Some("alice"));
+        assert_eq!(waiter.await??, ApprovalStatus::Approved);
+
+        assert!(service.list_pending().await?.is_empty());
+        assert!(service
+            .decide(&request.approval_id, false, "bob")
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_request_expires() -> Result<()> {
+        let (service, request) = setup().await;
+        let status = service
+            .wait(&request.approval_id, Duration::from_millis(30))
+            .await?;
+        assert_eq!(status, ApprovalStatus::Expired);
+        assert!(service.decide("missing", true, "alice").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expire_pending_request() -> Result<()> {
+        let (service, request) = setup().await;
+        let expired = service.expire(&request.approval_id, "cancelled").await?;
+        assert_eq!(expired.status, ApprovalStatus::Expired);
+        assert_eq!(expired.decided_by.as_deref(), Some("cancelled"));
+        assert!(service
+            .decide(&request.approval_id, true, "alice")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use synapse_agentic::prelude::{
     CompactionConfig, ContextOverflowRisk, LLMProvider, LLMSummarizer, MessageChunk,
-    SessionContext, SimpleTokenEstimator, TokenCounter,
+    SessionContext, TokenCounter, TokenizerRegistry,
 };
 
+/// Tokenizers shared by every compactor in the process, loaded from
+/// `GESTALT_TOKENIZER_DIR` (default `.gestalt/tokenizers`).
+fn tokenizers() -> &'static TokenizerRegistry {
+    static REGISTRY: OnceLock<TokenizerRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let dir = std::env::var("GESTALT_TOKENIZER_DIR")
+            .unwrap_or_else(|_| ".gestalt/tokenizers".to_string());
+        TokenizerRegistry::new(dir)
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct CompactionOutcome {
     pub compacted: bool,
@@ -13,20 +24,29 @@ pub struct CompactionOutcome {
 
 #[derive(Debug, Clone)]
 pub struct ContextCompactor {
-    estimator: SimpleTokenEstimator,
+    estimator: Arc<dyn TokenCounter>,
     config: CompactionConfig,
     summarizer: Arc<LLMSummarizer>,
 }
 
 impl ContextCompactor {
+    /// Count tokens with `model`'s tokenizer and compact at the thresholds
+    /// of its context window.
     pub fn new(provider: Arc<dyn LLMProvider>, model: &str) -> Self {
         Self {
-            estimator: SimpleTokenEstimator::new(model),
-            config: CompactionConfig::small_context(),
+            estimator: tokenizers().counter_for(model),
+            config: CompactionConfig::for_model(model),
             summarizer: Arc::new(LLMSummarizer::for_technical(provider)),
         }
     }
 
+    /// The same summarizer, sized for `model` instead.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.estimator = tokenizers().counter_for(model);
+        self.config = CompactionConfig::for_model(model);
+        self
+    }
+
     pub async fn compact(&self, session: &mut SessionContext) -> CompactionOutcome {
         // Update token counts for all messages using the estimator
         for msg in session.recent_messages_mut() {
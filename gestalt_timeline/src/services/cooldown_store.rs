@@ -0,0 +1,78 @@
+//! Provider cooldowns persisted in SurrealDB.
+//!
+//! Every gestalt process pointed at the same database sees the same
+//! rate-limit state, so a 429 seen by one worker keeps the others off that
+//! provider until it recovers.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use synapse_agentic::prelude::{CooldownEntry, CooldownStore};
+
+use crate::db::SurrealClient;
+
+const TABLE: &str = "provider_cooldowns";
+
+/// [`CooldownStore`] keyed by provider in the `provider_cooldowns` table.
+#[derive(Clone)]
+pub struct SurrealCooldownStore {
+    db: SurrealClient,
+}
+
+impl std::fmt::Debug for SurrealCooldownStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SurrealCooldownStore")
+            .field("table", &TABLE)
+            .finish()
+    }
+}
+
+impl SurrealCooldownStore {
+    pub fn new(db: SurrealClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl CooldownStore for SurrealCooldownStore {
+    async fn get(&self, provider: &str) -> Result<Option<CooldownEntry>> {
+        self.db.select_by_id(TABLE, provider).await
+    }
+
+    async fn put(&self, entry: &CooldownEntry) -> Result<()> {
+        self.db.upsert(TABLE, &entry.provider, entry).await?;
+        Ok(())
+    }
+
+    async fn clear(&self, provider: &str) -> Result<()> {
+        self.db.delete(TABLE, provider).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use synapse_agentic::prelude::ErrorClass;
+
+    #[tokio::test]
+    async fn test_cooldowns_shared_through_db() {
+        let db = SurrealClient::connect_mem().await.unwrap();
+        let first = SurrealCooldownStore::new(db.clone());
+        let second = SurrealCooldownStore::new(db);
+
+        let entry = CooldownEntry::new(
+            "gemini/gemini-2.0-flash",
+            ErrorClass::RateLimited,
+            2,
+            Duration::from_secs(30),
+        );
+        first.put(&entry).await.unwrap();
+
+        let seen = second.get("gemini/gemini-2.0-flash").await.unwrap();
+        assert_eq!(seen, Some(entry));
+        assert!(seen.unwrap().remaining().is_some());
+
+        second.clear("gemini/gemini-2.0-flash").await.unwrap();
+        assert_eq!(first.get("gemini/gemini-2.0-flash").await.unwrap(), None);
+    }
+}
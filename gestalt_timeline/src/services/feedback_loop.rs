@@ -97,10 +97,9 @@ impl FeedbackLoopService {
                 &result.agent_type,
                 result.success,
                 result.duration_ms,
-                result.return_code,
                 result.error.clone(),
-                crate::models::FlexibleTimestamp::now(),
             )
+            .with_return_code(result.return_code)
             .with_project(&result.project_id)
             .with_output_lines(result.output_lines.unwrap_or(0));
 
@@ -421,7 +420,7 @@ impl From<serde_json::Value> for SwarmAgentResult {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::execution_metrics::categorize_error;
+    use crate::models::execution_metrics::{categorize_error, ErrorCategory};
 
     #[test]
     fn test_categorize_error_timeout() {
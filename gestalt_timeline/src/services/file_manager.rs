@@ -2,33 +2,24 @@ use anyhow::{bail, Result};
 use async_trait::async_trait;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
-use tokio::task::JoinSet;
 use tokio::time::{Duration, Instant};
-use tracing::info;
+use tracing::{info, warn};
 
+pub use gestalt_core::ports::outbound::vfs::FileState;
 use gestalt_core::ports::outbound::vfs::{
-    FileEventType, FileWatchEvent, FileWatcher, FlushError, FlushReport, LockStatus, PendingChange,
-    VirtualFileSystem as VirtualFs,
+    FileEventType, FileWatchEvent, FileWatcher, FlushReport, LockStatus, OverlayState,
+    PendingChange, VirtualFileSystem as VirtualFs,
 };
+use gestalt_core::ports::outbound::vfs_journal::{JournalEntry, VfsJournal};
 
 /// Core FileManager Actor implementation.
 /// Handles in-memory file states and serialized patch application.
 pub struct FileManagerActor {
-    files: HashMap<PathBuf, FileState>,
-    binary_files: HashMap<PathBuf, Vec<u8>>,
-    dirs: HashSet<PathBuf>,
-    locks: HashMap<PathBuf, String>,
+    state: OverlayState,
     receiver: mpsc::Receiver<FileCommand>,
     last_modification: Option<Instant>,
-    global_version: u64,
-}
-
-#[derive(Clone, Debug, PartialEq)]
-pub struct FileState {
-    pub content: Arc<String>,
-    pub version: u64,
+    journal: Option<VfsJournal>,
 }
 
 pub enum FileCommand {
@@ -67,6 +58,22 @@ pub enum FileCommand {
         path: PathBuf,
         reply: oneshot::Sender<Result<()>>,
     },
+    RemoveFile {
+        path: PathBuf,
+        owner: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    RemoveDirAll {
+        path: PathBuf,
+        owner: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        owner: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
     Flush {
         reply: oneshot::Sender<Result<FlushReport>>,
     },
@@ -78,6 +85,10 @@ pub enum FileCommand {
         owner: String,
         reply: oneshot::Sender<Result<LockStatus>>,
     },
+    ReleaseLock {
+        path: PathBuf,
+        owner: String,
+    },
     ReleaseLocks {
         owner: String,
     },
@@ -94,15 +105,33 @@ pub struct FileManager {
 
 impl FileManager {
     pub fn new() -> (Self, FileManagerActor) {
+        Self::build(None)
+    }
+
+    /// Like [`FileManager::new`], but journaled: the actor first replays
+    /// `journal`, then records every acknowledged change to it.
+    pub fn with_journal(journal: VfsJournal) -> (Self, FileManagerActor) {
+        Self::build(Some(journal))
+    }
+
+    /// File manager of `agent_id`, journaled under `<workspace>/.gestalt/vfs/`
+    /// when `GESTALT_VFS_JOURNAL=<workspace>` is set.
+    pub fn for_agent(agent_id: &str) -> (Self, FileManagerActor) {
+        match std::env::var("GESTALT_VFS_JOURNAL") {
+            Ok(workspace) if !workspace.is_empty() => {
+                Self::with_journal(VfsJournal::for_agent(Path::new(&workspace), agent_id))
+            }
+            _ => Self::new(),
+        }
+    }
+
+    fn build(journal: Option<VfsJournal>) -> (Self, FileManagerActor) {
         let (sender, receiver) = mpsc::channel(100);
         let actor = FileManagerActor {
-            files: HashMap::new(),
-            binary_files: HashMap::new(),
-            dirs: HashSet::new(),
-            locks: HashMap::new(),
+            state: OverlayState::new(),
             receiver,
             last_modification: None,
-            global_version: 0,
+            journal,
         };
         (Self { sender }, actor)
     }
@@ -169,22 +198,24 @@ impl VirtualFs for FileManager {
 
         // This is a bit inefficient as it requires roundtrips to the actor for pending state
         let pending = self.pending_changes().await;
-        for change in pending {
+        for change in &pending {
             let p = match change {
-                PendingChange::CreateDir { path } => path,
-                PendingChange::WriteFile { path, .. } => path,
+                PendingChange::CreateDir { path } | PendingChange::WriteFile { path, .. } => path,
+                _ => continue,
             };
             if let Some(parent) = p.parent() {
                 if parent == path {
-                    entries.insert(p);
+                    entries.insert(p.clone());
                 }
             }
         }
 
-        if tokio::fs::metadata(path).await.is_ok() {
+        if !hidden_by_removal(&pending, path) && tokio::fs::metadata(path).await.is_ok() {
             let mut dir = tokio::fs::read_dir(path).await?;
             while let Some(entry) = dir.next_entry().await? {
-                entries.insert(entry.path());
+                if !hidden_by_removal(&pending, &entry.path()) {
+                    entries.insert(entry.path());
+                }
             }
         }
 
@@ -195,15 +226,18 @@ impl VirtualFs for FileManager {
 
     async fn exists(&self, path: &Path) -> Result<bool> {
         let pending = self.pending_changes().await;
-        for change in pending {
+        for change in &pending {
             let p = match change {
-                PendingChange::CreateDir { path } => path,
-                PendingChange::WriteFile { path, .. } => path,
+                PendingChange::CreateDir { path } | PendingChange::WriteFile { path, .. } => path,
+                _ => continue,
             };
-            if p == path {
+            if p.starts_with(path) {
                 return Ok(true);
             }
         }
+        if hidden_by_removal(&pending, path) {
+            return Ok(false);
+        }
         Ok(tokio::fs::metadata(path).await.is_ok())
     }
 
@@ -218,6 +252,43 @@ impl VirtualFs for FileManager {
         rx.await?
     }
 
+    async fn remove_file(&self, path: &Path, owner: &str) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.sender
+            .send(FileCommand::RemoveFile {
+                path: path.to_path_buf(),
+                owner: owner.to_string(),
+                reply,
+            })
+            .await?;
+        rx.await?
+    }
+
+    async fn remove_dir_all(&self, path: &Path, owner: &str) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.sender
+            .send(FileCommand::RemoveDirAll {
+                path: path.to_path_buf(),
+                owner: owner.to_string(),
+                reply,
+            })
+            .await?;
+        rx.await?
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, owner: &str) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.sender
+            .send(FileCommand::Rename {
+                from: from.to_path_buf(),
+                to: to.to_path_buf(),
+                owner: owner.to_string(),
+                reply,
+            })
+            .await?;
+        rx.await?
+    }
+
     async fn flush(&self) -> Result<FlushReport> {
         let (reply, rx) = oneshot::channel();
         self.sender.send(FileCommand::Flush { reply }).await?;
@@ -249,6 +320,16 @@ impl VirtualFs for FileManager {
         rx.await?
     }
 
+    async fn release_lock(&self, path: &Path, owner: &str) {
+        let _ = self
+            .sender
+            .send(FileCommand::ReleaseLock {
+                path: path.to_path_buf(),
+                owner: owner.to_string(),
+            })
+            .await;
+    }
+
     async fn release_locks(&self, owner: &str) {
         let _ = self
             .sender
@@ -276,6 +357,15 @@ impl VirtualFs for FileManager {
     }
 }
 
+/// Whether a pending `RemoveFile`/`RemoveDir` hides the on-disk entry at `path`.
+fn hidden_by_removal(pending: &[PendingChange], path: &Path) -> bool {
+    pending.iter().any(|change| match change {
+        PendingChange::RemoveFile { path: removed } => removed == path,
+        PendingChange::RemoveDir { path: removed } => path.starts_with(removed),
+        _ => false,
+    })
+}
+
 impl FileWatcher for FileManager {
     fn watch(&self, path: PathBuf, interval: Duration) -> mpsc::Receiver<FileWatchEvent> {
         let (tx, rx) = mpsc::channel(128);
@@ -372,6 +462,9 @@ impl FileWatcher for FileManager {
 
 impl FileManagerActor {
     pub async fn run(mut self) {
+        if let Some(journal) = self.journal.take() {
+            self.recover(journal).await;
+        }
         let mut interval = tokio::time::interval(Duration::from_millis(500));
 
         loop {
@@ -400,17 +493,18 @@ impl FileManagerActor {
         match command {
             FileCommand::ReadFile { path, reply } => {
                 let res = self
-                    .do_read_file_state(path)
+                    .state
+                    .read_text(&path)
                     .await
                     .map(|s| (*s.content).clone());
                 let _ = reply.send(res);
             }
             FileCommand::ReadFileState { path, reply } => {
-                let res = self.do_read_file_state(path).await;
+                let res = self.state.read_text(&path).await;
                 let _ = reply.send(res);
             }
             FileCommand::ReadBytes { path, reply } => {
-                let res = self.do_read_bytes(path).await;
+                let res = self.state.read(&path).await;
                 let _ = reply.send(res);
             }
             FileCommand::WriteString {
@@ -419,11 +513,8 @@ impl FileManagerActor {
                 owner,
                 reply,
             } => {
-                let res = self.do_write_string(path, content, owner).await;
-                if res.is_ok() {
-                    self.last_modification = Some(Instant::now());
-                }
-                let _ = reply.send(res);
+                let res = self.state.write_text(&path, content, &owner).await;
+                let _ = reply.send(self.touched(res).map(|_| ()));
             }
             FileCommand::WriteBytes {
                 path,
@@ -431,11 +522,8 @@ impl FileManagerActor {
                 owner,
                 reply,
             } => {
-                let res = self.do_write_bytes(path, data, owner).await;
-                if res.is_ok() {
-                    self.last_modification = Some(Instant::now());
-                }
-                let _ = reply.send(res);
+                let res = self.state.write_bytes(&path, data, &owner).await;
+                let _ = reply.send(self.touched(res));
             }
             FileCommand::ApplyPatch {
                 path,
@@ -445,14 +533,28 @@ impl FileManagerActor {
                 reply,
             } => {
                 let res = self.do_apply_patch(path, patch, base_version, owner).await;
-                if res.is_ok() {
-                    self.last_modification = Some(Instant::now());
-                }
-                let _ = reply.send(res);
+                let _ = reply.send(self.touched(res));
             }
             FileCommand::CreateDirAll { path, reply } => {
-                self.dirs.insert(path);
-                let _ = reply.send(Ok(()));
+                let res = self.state.create_dir_all(&path).await;
+                let _ = reply.send(res);
+            }
+            FileCommand::RemoveFile { path, owner, reply } => {
+                let res = self.state.remove_file(&path, &owner).await;
+                let _ = reply.send(self.touched(res));
+            }
+            FileCommand::RemoveDirAll { path, owner, reply } => {
+                let res = self.state.remove_dir_all(&path, &owner).await;
+                let _ = reply.send(self.touched(res));
+            }
+            FileCommand::Rename {
+                from,
+                to,
+                owner,
+                reply,
+            } => {
+                let res = self.state.rename(&from, &to, &owner).await;
+                let _ = reply.send(self.touched(res));
             }
             FileCommand::Flush { reply } => {
                 let res = self.perform_flush().await;
@@ -461,136 +563,29 @@ impl FileManagerActor {
                 let _ = reply.send(res);
             }
             FileCommand::GetPendingChanges { reply } => {
-                let mut pending = Vec::new();
-                for path in &self.dirs {
-                    pending.push(PendingChange::CreateDir { path: path.clone() });
-                }
-                for (path, state) in &self.files {
-                    pending.push(PendingChange::WriteFile {
-                        path: path.clone(),
-                        bytes: state.content.len(),
-                    });
-                }
-                for (path, data) in &self.binary_files {
-                    pending.push(PendingChange::WriteFile {
-                        path: path.clone(),
-                        bytes: data.len(),
-                    });
-                }
-                let _ = reply.send(pending);
+                let _ = reply.send(self.state.pending_changes());
             }
             FileCommand::AcquireLock { path, owner, reply } => {
-                let status = match self.locks.get(&path) {
-                    None => {
-                        self.locks.insert(path, owner);
-                        LockStatus::Acquired
-                    }
-                    Some(current_owner) if current_owner == &owner => {
-                        LockStatus::AlreadyHeldByOwner
-                    }
-                    Some(current_owner) => LockStatus::HeldByOther {
-                        owner: current_owner.clone(),
-                    },
-                };
-                let _ = reply.send(Ok(status));
-            }
-            FileCommand::ReleaseLocks { owner } => {
-                self.locks.retain(|_, v| v != &owner);
+                let res = self.state.acquire_lock(&path, &owner).await;
+                let _ = reply.send(res);
             }
-            FileCommand::Discard => {
-                self.files.clear();
-                self.binary_files.clear();
-                self.dirs.clear();
-                self.locks.clear();
+            FileCommand::ReleaseLock { path, owner } => {
+                self.state.release_lock(&path, &owner).await
             }
+            FileCommand::ReleaseLocks { owner } => self.state.release_locks(&owner).await,
+            FileCommand::Discard => self.state.discard().await,
             FileCommand::GetVersion { reply } => {
-                let _ = reply.send(self.global_version);
+                let _ = reply.send(self.state.version());
             }
         }
     }
 
-    async fn do_read_file_state(&mut self, path: PathBuf) -> Result<FileState> {
-        if let Some(state) = self.files.get(&path) {
-            return Ok(state.clone());
+    /// Arm the debounced flush when `res` records a successful change.
+    fn touched<T>(&mut self, res: Result<T>) -> Result<T> {
+        if res.is_ok() {
+            self.last_modification = Some(Instant::now());
         }
-        if let Some(bytes) = self.binary_files.get(&path) {
-            let content = String::from_utf8(bytes.clone())?;
-            let state = FileState {
-                content: Arc::new(content),
-                version: 0,
-            };
-            self.files.insert(path, state.clone());
-            return Ok(state);
-        }
-
-        // Load from disk if not in memory
-        let content = tokio::fs::read_to_string(&path).await?;
-        let state = FileState {
-            content: Arc::new(content),
-            version: 0,
-        };
-        self.files.insert(path, state.clone());
-        Ok(state)
-    }
-
-    async fn do_write_string(
-        &mut self,
-        path: PathBuf,
-        content: String,
-        owner: String,
-    ) -> Result<()> {
-        match self.locks.get(&path) {
-            Some(current_owner) if current_owner != &owner => {
-                bail!(
-                    "lock conflict for '{}': held by '{}'",
-                    path.display(),
-                    current_owner
-                );
-            }
-            _ => {
-                self.locks.insert(path.clone(), owner);
-            }
-        }
-
-        let current_version = self.files.get(&path).map(|s| s.version).unwrap_or(0);
-        self.files.insert(
-            path.clone(),
-            FileState {
-                content: Arc::new(content),
-                version: current_version + 1,
-            },
-        );
-        self.binary_files.remove(&path);
-        Ok(())
-    }
-
-    async fn do_read_bytes(&mut self, path: PathBuf) -> Result<Vec<u8>> {
-        if let Some(data) = self.binary_files.get(&path) {
-            return Ok(data.clone());
-        }
-        if let Some(state) = self.files.get(&path) {
-            return Ok(state.content.as_bytes().to_vec());
-        }
-        Ok(tokio::fs::read(path).await?)
-    }
-
-    async fn do_write_bytes(&mut self, path: PathBuf, data: Vec<u8>, owner: String) -> Result<()> {
-        match self.locks.get(&path) {
-            Some(current_owner) if current_owner != &owner => {
-                bail!(
-                    "lock conflict for '{}': held by '{}'",
-                    path.display(),
-                    current_owner
-                );
-            }
-            _ => {
-                self.locks.insert(path.clone(), owner);
-            }
-        }
-
-        self.files.remove(&path);
-        self.binary_files.insert(path, data);
-        Ok(())
+        res
     }
 
     async fn do_apply_patch(
@@ -600,7 +595,7 @@ impl FileManagerActor {
         base_version: u64,
         owner: String,
     ) -> Result<FileState> {
-        let current_state = self.do_read_file_state(path.clone()).await?;
+        let current_state = self.state.read_text(&path).await?;
 
         if current_state.version != base_version {
             bail!(
@@ -610,134 +605,38 @@ impl FileManagerActor {
             );
         }
 
-        match self.locks.get(&path) {
-            Some(current_owner) if current_owner != &owner => {
-                bail!(
-                    "lock conflict for '{}': held by '{}'",
-                    path.display(),
-                    current_owner
-                );
-            }
-            _ => {
-                self.locks.insert(path.clone(), owner.clone());
-            }
-        }
-
         let patch = UnifiedDiff::parse(&patch_str)?;
 
         // Strict single-base patching to avoid silent merges and non-determinism.
         let new_content = patch.apply(&current_state.content)?;
 
-        let new_state = FileState {
-            content: Arc::new(new_content),
-            version: current_state.version + 1,
+        let entry = || JournalEntry::Patch {
+            path: path.clone(),
+            owner: owner.clone(),
+            patch: patch_str.clone(),
+            content: new_content.clone(),
         };
+        self.state
+            .write_text_as(&path, new_content.clone(), &owner, entry)
+            .await
+    }
 
-        self.files.insert(path.clone(), new_state.clone());
-        self.binary_files.remove(&path);
-        Ok(new_state)
+    /// Rebuild the pending state from `journal`, then keep journaling to it.
+    async fn recover(&mut self, journal: VfsJournal) {
+        let path = journal.path().to_path_buf();
+        match OverlayState::recover(journal).await {
+            Ok(state) => self.state = state,
+            Err(e) => warn!(
+                "VFS journal {} unreadable, running without it: {}",
+                path.display(),
+                e
+            ),
+        }
     }
 
     async fn perform_flush(&mut self) -> Result<FlushReport> {
         info!("Flushing VFS state to physical SSD.");
-        let mut report = FlushReport::default();
-        let mut set = JoinSet::new();
-
-        enum FlushOp {
-            Dir(PathBuf),
-            File(PathBuf),
-            Error(FlushError),
-        }
-
-        for dir in self.dirs.iter().cloned() {
-            set.spawn(async move {
-                if let Err(err) = tokio::fs::create_dir_all(&dir).await {
-                    FlushOp::Error(FlushError {
-                        path: dir,
-                        operation: "create_dir_all",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::Dir(dir)
-                }
-            });
-        }
-
-        for (path, state) in self.files.iter() {
-            let path = path.clone();
-            let content = state.content.clone();
-            set.spawn(async move {
-                if let Some(parent) = path.parent() {
-                    if let Err(err) = tokio::fs::create_dir_all(parent).await {
-                        return FlushOp::Error(FlushError {
-                            path,
-                            operation: "create_dir_all_parent",
-                            error: err.to_string(),
-                        });
-                    }
-                }
-                if let Err(err) = tokio::fs::write(&path, content.as_str()).await {
-                    FlushOp::Error(FlushError {
-                        path,
-                        operation: "write",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::File(path)
-                }
-            });
-        }
-
-        for (path, data) in self.binary_files.iter() {
-            let path = path.clone();
-            let data = data.clone();
-            set.spawn(async move {
-                if let Some(parent) = path.parent() {
-                    if let Err(err) = tokio::fs::create_dir_all(parent).await {
-                        return FlushOp::Error(FlushError {
-                            path,
-                            operation: "create_dir_all_parent",
-                            error: err.to_string(),
-                        });
-                    }
-                }
-                if let Err(err) = tokio::fs::write(&path, data).await {
-                    FlushOp::Error(FlushError {
-                        path,
-                        operation: "write",
-                        error: err.to_string(),
-                    })
-                } else {
-                    FlushOp::File(path)
-                }
-            });
-        }
-
-        while let Some(res) = set.join_next().await {
-            match res? {
-                FlushOp::Dir(p) => report.created_dirs.push(p),
-                FlushOp::File(p) => report.written_files.push(p),
-                FlushOp::Error(e) => report.errors.push(e),
-            }
-        }
-
-        if !report.written_files.is_empty() || !report.created_dirs.is_empty() {
-            self.global_version += 1;
-        }
-
-        let created_dirs = report.created_dirs.clone();
-        for created in created_dirs {
-            self.dirs.remove(&created);
-        }
-
-        let written_files = report.written_files.clone();
-        for written in written_files {
-            self.files.remove(&written);
-            self.binary_files.remove(&written);
-            self.locks.remove(&written);
-        }
-
-        Ok(report)
+        self.state.flush().await
     }
 }
 
@@ -831,8 +730,9 @@ impl UnifiedDiff {
 
 #[cfg(test)]
 mod tests {
-    use super::{FileEventType, FileManager, FileWatcher, LockStatus, VirtualFs};
+    use super::{FileEventType, FileManager, FileWatcher, LockStatus, PendingChange, VirtualFs};
     use anyhow::Result;
+    use gestalt_core::ports::outbound::vfs_journal::VfsJournal;
     use tempfile::tempdir;
     use tokio::time::Duration;
 
@@ -1035,4 +935,170 @@ mod tests {
         assert!(seen);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_remove_and_rename_apply_on_flush() -> Result<()> {
+        let tmp = tempdir()?;
+        let stale = tmp.path().join("stale.txt");
+        let from = tmp.path().join("old").join("mod.rs");
+        let to = tmp.path().join("new").join("mod.rs");
+        tokio::fs::write(&stale, "bye").await?;
+        tokio::fs::create_dir_all(from.parent().unwrap()).await?;
+        tokio::fs::write(&from, "pub fn f() {}").await?;
+
+        let (manager, actor) = FileManager::new();
+        tokio::spawn(actor.run());
+
+        manager.remove_file(&stale, "agent-a").await?;
+        manager
+            .rename(from.parent().unwrap(), to.parent().unwrap(), "agent-a")
+            .await?;
+
+        assert!(!manager.exists(&stale).await?);
+        assert!(!manager.exists(&from).await?);
+        assert!(manager.exists(&to).await?);
+        assert!(!manager.list(tmp.path()).await?.contains(&stale));
+        assert_eq!(manager.read_to_string(&to).await?, "pub fn f() {}");
+        assert!(manager
+            .pending_changes()
+            .await
+            .contains(&PendingChange::RemoveDir {
+                path: from.parent().unwrap().to_path_buf()
+            }));
+
+        let report = manager.flush().await?;
+        assert!(report.errors.is_empty());
+        assert!(!stale.exists());
+        assert!(!from.parent().unwrap().exists());
+        assert_eq!(tokio::fs::read_to_string(&to).await?, "pub fn f() {}");
+        assert!(manager.pending_changes().await.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remove_respects_locks() -> Result<()> {
+        let tmp = tempdir()?;
+        let file_path = tmp.path().join("locked.txt");
+        tokio::fs::write(&file_path, "v1").await?;
+
+        let (manager, actor) = FileManager::new();
+        tokio::spawn(actor.run());
+
+        manager.acquire_lock(&file_path, "agent-a").await?;
+        let err = manager
+            .remove_dir_all(tmp.path(), "agent-b")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("lock conflict"));
+        assert!(manager.pending_changes().await.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_pending_state() -> Result<()> {
+        let tmp = tempdir()?;
+        let removed = tmp.path().join("removed.txt");
+        let blocker = tmp.path().join("blocker");
+        tokio::fs::write(&removed, "keep").await?;
+        tokio::fs::write(&blocker, "not a dir").await?;
+
+        let (manager, actor) = FileManager::new();
+        tokio::spawn(actor.run());
+
+        manager.remove_file(&removed, "agent-a").await?;
+        manager
+            .write_string(&blocker.join("child.txt"), "x".to_string(), "agent-a")
+            .await?;
+
+        let report = manager.flush().await?;
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(tokio::fs::read_to_string(&removed).await?, "keep");
+        assert_eq!(manager.pending_changes().await.len(), 2);
+        assert_eq!(manager.version().await, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_merges_or_reports_disk_changes() -> Result<()> {
+        let tmp = tempdir()?;
+        let merged = tmp.path().join("merged.txt");
+        let conflicted = tmp.path().join("conflicted.txt");
+        tokio::fs::write(&merged, "a\nb\nc\nd\ne").await?;
+        tokio::fs::write(&conflicted, "value\n").await?;
+
+        let (manager, actor) = FileManager::new();
+        tokio::spawn(actor.run());
+
+        manager
+            .apply_patch(
+                merged.clone(),
+                "@@ -1,1 +1,1 @@\n-a\n+A".to_string(),
+                0,
+                "agent-a".to_string(),
+            )
+            .await?;
+        tokio::fs::write(&merged, "a\nb\nc\nd\nE").await?;
+        manager
+            .write_string(&conflicted, "ours\n".to_string(), "agent-a")
+            .await?;
+        tokio::fs::write(&conflicted, "theirs\n").await?;
+
+        let report = manager.flush().await?;
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].path, conflicted);
+        assert_eq!(tokio::fs::read_to_string(&merged).await?, "a\nb\nc\nd\nE");
+
+        manager
+            .write_string(&conflicted, "resolved\n".to_string(), "agent-a")
+            .await?;
+        let report = manager.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(report.merged_files, vec![merged.clone()]);
+        assert_eq!(tokio::fs::read_to_string(&merged).await?, "A\nb\nc\nd\nE");
+        assert_eq!(tokio::fs::read_to_string(&conflicted).await?, "resolved\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_restores_pending_state_after_restart() -> Result<()> {
+        let tmp = tempdir()?;
+        let patched = tmp.path().join("patched.rs");
+        let added = tmp.path().join("added.bin");
+        tokio::fs::write(&patched, "a\nb").await?;
+
+        let (manager, actor) = FileManager::with_journal(VfsJournal::for_agent(tmp.path(), "a1"));
+        let actor = tokio::spawn(actor.run());
+        let state = manager.read_file_state(patched.clone()).await?;
+        let patch = "@@ -1,2 +1,2 @@\n-a\n+A\n b".to_string();
+        manager
+            .apply_patch(patched.clone(), patch, state.version, "a1".to_string())
+            .await?;
+        manager.write_bytes(&added, vec![0, 1, 2], "a1").await?;
+        drop(manager);
+        actor.await?;
+
+        let (manager, actor) = FileManager::with_journal(VfsJournal::for_agent(tmp.path(), "a1"));
+        tokio::spawn(actor.run());
+        let state = manager.read_file_state(patched.clone()).await?;
+        assert_eq!(state.content.as_str(), "A\nb");
+        assert_eq!(state.version, 1);
+        assert_eq!(manager.read_bytes(&added).await?, vec![0, 1, 2]);
+        assert_eq!(
+            manager.acquire_lock(&added, "a2").await?,
+            LockStatus::HeldByOther {
+                owner: "a1".to_string()
+            }
+        );
+
+        let report = manager.flush().await?;
+        assert!(report.errors.is_empty());
+        assert_eq!(tokio::fs::read_to_string(&patched).await?, "A\nb");
+        assert_eq!(manager.version().await, 1);
+
+        let (manager, actor) = FileManager::with_journal(VfsJournal::for_agent(tmp.path(), "a1"));
+        tokio::spawn(actor.run());
+        assert!(manager.pending_changes().await.is_empty());
+        assert_eq!(manager.version().await, 1);
+        Ok(())
+    }
 }
@@ -2,11 +2,17 @@ use crate::db::SurrealClient;
 use anyhow::{Context, Result};
 use chrono::Utc;
 use gestalt_core::application::indexer::{DocumentRecord, Indexer, RepositoryMetadata};
-use gestalt_core::domain::rag::embeddings::EmbeddingModel;
+use gestalt_core::adapters::persistence::lexical;
+use gestalt_core::domain::rag::embeddings::{EmbeddingModel, EmbeddingSpace};
+use gestalt_core::ports::outbound::vfs::{FileEventType, FileWatcher};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use surrealdb::sql::Thing;
-use tracing::info;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct RepoRecord {
@@ -15,6 +21,9 @@ struct RepoRecord {
     name: String,
     local_path: Option<String>,
     created_at: chrono::DateTime<Utc>,
+    /// Working-tree snapshot the index was last brought up to.
+    #[serde(default)]
+    last_indexed_tree: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,10 +42,32 @@ struct ChunkRecord {
     content: String,
     chunk_index: usize,
     created_at: chrono::DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     embedding: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding_dim: Option<usize>,
     metadata: serde_json::Value,
 }
 
+#[derive(Debug, Deserialize)]
+struct ChunkState {
+    embedding_model: Option<String>,
+    embedding_dim: Option<usize>,
+    searchable: bool,
+}
+
+/// What an indexing run did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct IndexReport {
+    /// Whether only files changed since the last indexing run were read.
+    pub incremental: bool,
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
 pub struct IndexService {
     db: SurrealClient,
     indexer: Indexer,
@@ -52,32 +83,163 @@ impl IndexService {
         }
     }
 
-    pub async fn index_repo(&self, url: &str) -> Result<()> {
+    /// Index `url`, reading only the files changed since the last run when
+    /// the repository is a git checkout that was indexed before.
+    pub async fn index_repo(&self, url: &str) -> Result<IndexReport> {
         let (repo_meta, path, _temp_dir) = self.indexer.ingest(url).await?;
 
         // 1. Ensure repository exists in DB
-        let repo_id = self.ensure_repo(&repo_meta).await?;
+        let (repo_id, last_tree) = self.ensure_repo(&repo_meta).await?;
+        // Taken before reading any file: edits made while indexing show up
+        // in the next diff instead of being lost.
+        let snapshot = Indexer::worktree_snapshot(&path);
 
-        // 2. Scan files
-        let files = self.indexer.scan(&path);
-        info!("Found {} files to index in {}", files.len(), url);
+        // 2. Diff against the last indexed working tree, or scan everything
+        let changes = match (&last_tree, &snapshot) {
+            (Some(last), Some(now)) => match self.indexer.changes_between(&path, last, now) {
+                Ok(changes) => Some(changes),
+                Err(e) => {
+                    warn!("Cannot diff {} against {}, re-indexing all files: {}", url, last, e);
+                    None
+                }
+            },
+            _ => None,
+        };
 
-        for file_path in files {
-            let record = self.indexer.process_file(&path, &file_path)?;
+        let mut report = IndexReport::default();
+        match changes {
+            Some(changes) => {
+                report.incremental = true;
+                info!(
+                    "{} changed and {} removed files since {} in {}",
+                    changes.changed.len(),
+                    changes.removed.len(),
+                    last_tree.as_deref().unwrap_or_default(),
+                    url
+                );
+                for removed in &changes.removed {
+                    report.removed += self.remove_doc(&repo_id, &removed.to_string_lossy()).await?;
+                }
+                for file_path in &changes.changed {
+                    self.index_file(&repo_id, &path, file_path, &mut report).await?;
+                }
+            }
+            None => {
+                let files = self.indexer.scan(&path);
+                info!("Found {} files to index in {}", files.len(), url);
+                for file_path in &files {
+                    self.index_file(&repo_id, &path, file_path, &mut report).await?;
+                }
+                report.removed += self.remove_missing_docs(&repo_id, &path, &files).await?;
+            }
+        }
 
-            // 3. Check checksum and update/skip
-            if let Some(doc_id) = self.should_update_doc(&repo_id, &record).await? {
-                info!("Indexing file: {}", record.metadata.path);
-                self.persist_doc(&doc_id, record).await?;
-            } else {
-                info!("Skipping unchanged file: {}", record.metadata.path);
+        if let Some(snapshot) = snapshot {
+            self.set_indexed_tree(&repo_id, &snapshot).await?;
+        }
+        Ok(report)
+    }
+
+    /// Keep the index of the local repository at `path` current, re-indexing
+    /// files as `watcher` reports them changed, until the watcher stops.
+    ///
+    /// Directories are watched individually, so directories created after the
+    /// watch starts are picked up by the next `index_repo` run.
+    pub async fn watch(
+        &self,
+        path: &str,
+        watcher: &dyn FileWatcher,
+        interval: Duration,
+    ) -> Result<()> {
+        let (repo_meta, root, temp_dir) = self.indexer.ingest(path).await?;
+        if temp_dir.is_some() {
+            anyhow::bail!("Watching needs a local repository path, not {}", path);
+        }
+        let (repo_id, _) = self.ensure_repo(&repo_meta).await?;
+
+        let (tx, mut rx) = mpsc::channel(256);
+        for dir in self.indexer.watch_dirs(&root) {
+            let mut events = watcher.watch(dir, interval);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while let Some(event) = events.recv().await {
+                    if tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        while let Some(event) = rx.recv().await {
+            // Coalesce the burst of events a single poll produces.
+            let mut batch: HashMap<PathBuf, FileEventType> = HashMap::new();
+            batch.insert(event.path, event.event_type);
+            while let Ok(Some(event)) = tokio::time::timeout(interval, rx.recv()).await {
+                batch.insert(event.path, event.event_type);
+            }
+
+            let snapshot = Indexer::worktree_snapshot(&root);
+            let mut report = IndexReport {
+                incremental: true,
+                ..Default::default()
+            };
+            for (file_path, event_type) in batch {
+                let result = match event_type {
+                    FileEventType::Deleted => match file_path.strip_prefix(&root) {
+                        Ok(relative) => self
+                            .remove_doc(&repo_id, &relative.to_string_lossy())
+                            .await
+                            .map(|removed| report.removed += removed),
+                        Err(_) => Ok(()),
+                    },
+                    _ if self.indexer.is_indexable(&root, &file_path) => {
+                        self.index_file(&repo_id, &root, &file_path, &mut report)
+                            .await
+                    }
+                    _ => Ok(()),
+                };
+                if let Err(e) = result {
+                    warn!("Failed to update index for {}: {}", file_path.display(), e);
+                }
+            }
+            if report.indexed + report.removed > 0 {
+                info!(
+                    "Index updated: {} indexed, {} removed",
+                    report.indexed, report.removed
+                );
+            }
+            if let Some(snapshot) = snapshot {
+                self.set_indexed_tree(&repo_id, &snapshot).await?;
             }
         }
+        Ok(())
+    }
+
+    async fn index_file(
+        &self,
+        repo_id: &str,
+        root: &Path,
+        file_path: &Path,
+        report: &mut IndexReport,
+    ) -> Result<()> {
+        let record = self.indexer.process_file(root, file_path)?;
 
+        // Check checksum and update/skip
+        if let Some(doc_id) = self.should_update_doc(repo_id, &record).await? {
+            info!("Indexing file: {}", record.metadata.path);
+            self.persist_doc(&doc_id, record).await?;
+            report.indexed += 1;
+        } else {
+            info!("Skipping unchanged file: {}", record.metadata.path);
+            report.unchanged += 1;
+        }
         Ok(())
     }
 
-    async fn ensure_repo(&self, meta: &RepositoryMetadata) -> Result<String> {
+    /// Returns the repository's record ID and the working-tree snapshot it was
+    /// last indexed at.
+    async fn ensure_repo(&self, meta: &RepositoryMetadata) -> Result<(String, Option<String>)> {
         let query = "SELECT * FROM repositories WHERE url = $url";
         let existing: Vec<RepoRecord> = self
             .db
@@ -85,9 +247,10 @@ impl IndexService {
             .await?;
 
         if let Some(repo) = existing.first() {
-            repo.id.as_ref()
+            let id = repo.id.as_ref()
                 .map(|t| t.to_string())
-                .context("Repository record has no ID")
+                .context("Repository record has no ID")?;
+            Ok((id, repo.last_indexed_tree.clone()))
         } else {
             let new_repo = RepoRecord {
                 id: None,
@@ -95,14 +258,79 @@ impl IndexService {
                 name: meta.name.clone(),
                 local_path: meta.local_path.clone(),
                 created_at: Utc::now(),
+                last_indexed_tree: None,
             };
             let created: RepoRecord = self.db.create("repositories", &new_repo).await?;
-            created.id.as_ref()
+            let id = created.id.as_ref()
                 .map(|t| t.to_string())
-                .context("Created repository has no ID")
+                .context("Created repository has no ID")?;
+            Ok((id, None))
         }
     }
 
+    async fn set_indexed_tree(&self, repo_id: &str, tree: &str) -> Result<()> {
+        let _: Vec<serde_json::Value> = self
+            .db
+            .query_with(
+                "UPDATE type::thing($repo_id) SET last_indexed_tree = $tree RETURN NONE",
+                serde_json::json!({ "repo_id": repo_id, "tree": tree }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Drop the document at `path` and its chunks; returns how many documents were removed.
+    async fn remove_doc(&self, repo_id: &str, path: &str) -> Result<usize> {
+        let docs: Vec<DocRecord> = self
+            .db
+            .query_with(
+                "SELECT * FROM documents WHERE repo_id = $repo_id AND path = $path",
+                serde_json::json!({ "repo_id": repo_id, "path": path }),
+            )
+            .await?;
+        for doc in &docs {
+            let doc_id = doc.id.as_ref()
+                .map(|t| t.to_string())
+                .context("Document record has no ID")?;
+            info!("Removing deleted file from index: {}", path);
+            self.delete_chunks(&doc_id).await?;
+            let _: Vec<serde_json::Value> = self
+                .db
+                .query_with(
+                    "DELETE type::thing($doc_id)",
+                    serde_json::json!({ "doc_id": doc_id }),
+                )
+                .await?;
+        }
+        Ok(docs.len())
+    }
+
+    /// Drop documents of `repo_id` whose files were not found by a full scan.
+    async fn remove_missing_docs(
+        &self,
+        repo_id: &str,
+        root: &Path,
+        files: &[PathBuf],
+    ) -> Result<usize> {
+        let present: HashSet<String> = files
+            .iter()
+            .filter_map(|f| f.strip_prefix(root).ok())
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let docs: Vec<DocRecord> = self
+            .db
+            .query_with(
+                "SELECT * FROM documents WHERE repo_id = $repo_id",
+                serde_json::json!({ "repo_id": repo_id }),
+            )
+            .await?;
+        let mut removed = 0;
+        for doc in docs.iter().filter(|d| !present.contains(&d.path)) {
+            removed += self.remove_doc(repo_id, &doc.path).await?;
+        }
+        Ok(removed)
+    }
+
     async fn should_update_doc(
         &self,
         repo_id: &str,
@@ -121,13 +349,15 @@ impl IndexService {
             .await?;
 
         if let Some(doc) = existing.first() {
-            if doc.checksum == record.metadata.checksum {
+            let doc_id = doc.id.as_ref()
+                .map(|t| t.to_string())
+                .context("Document record has no ID")?;
+            if doc.checksum == record.metadata.checksum
+                && self.chunks_are_current(&doc_id).await?
+            {
                 Ok(None) // No update needed
             } else {
-                doc.id.as_ref()
-                    .map(|t| t.to_string())
-                    .context("Document record has no ID")
-                    .map(Some)
+                Ok(Some(doc_id))
             }
         } else {
             // New document
@@ -148,12 +378,30 @@ impl IndexService {
         }
     }
 
+    /// Whether every chunk of `doc_id` was embedded by the configured model
+    /// and is in the BM25 index.
+    async fn chunks_are_current(&self, doc_id: &str) -> Result<bool> {
+        let current = EmbeddingSpace::of(self.embedding_model.as_ref());
+        let chunks: Vec<ChunkState> = self
+            .db
+            .query_with(
+                "SELECT embedding_model, embedding_dim, search_text IS NOT NONE AS searchable FROM chunks WHERE doc_id = $doc_id",
+                serde_json::json!({ "doc_id": doc_id }),
+            )
+            .await?;
+        Ok(chunks.iter().all(|c| {
+            c.searchable
+                && c.embedding_model.as_deref() == Some(current.model_id.as_str())
+                && c.embedding_dim == Some(current.dimension)
+        }))
+    }
+
     async fn persist_doc(&self, doc_id: &str, record: DocumentRecord) -> Result<()> {
         // 1. Update document checksum and updated_at
         let _: Vec<serde_json::Value> = self
             .db
             .query_with(
-                "UPDATE type::thing($doc_id) SET checksum = $checksum, updated_at = $now",
+                "UPDATE type::thing($doc_id) SET checksum = $checksum, updated_at = $now RETURN NONE",
                 serde_json::json!({
                     "doc_id": doc_id,
                     "checksum": record.metadata.checksum,
@@ -163,17 +411,23 @@ impl IndexService {
             .await?;
 
         // 2. Delete old chunks
-        let _: Vec<serde_json::Value> = self
-            .db
-            .query_with(
-                "DELETE chunks WHERE doc_id = $doc_id",
-                serde_json::json!({ "doc_id": doc_id }),
-            )
-            .await?;
+        self.delete_chunks(doc_id).await?;
 
-        // 3. Insert new chunks with embeddings and metadata for RAG
-        for chunk in record.chunks {
-            let embedding = self.embedding_model.embed(&chunk.content).await.ok();
+        // 3. Insert new chunks with embeddings, metadata and search tokens for RAG
+        let space = EmbeddingSpace::of(self.embedding_model.as_ref());
+        let texts: Vec<String> = record.chunks.iter().map(|c| c.content.clone()).collect();
+        let embeddings = match self.embedding_model.embed_batch(&texts).await {
+            Ok(vectors) => vectors.into_iter().map(Some).collect(),
+            Err(e) => {
+                warn!("Failed to embed {}: {}", record.metadata.path, e);
+                vec![None; texts.len()]
+            }
+        };
+        for (chunk, embedding) in record.chunks.into_iter().zip(embeddings) {
+            let (embedding_model, embedding_dim) = match embedding {
+                Some(_) => (Some(space.model_id.clone()), Some(space.dimension)),
+                None => (None, None),
+            };
             let chunk_record = ChunkRecord {
                 doc_id: doc_id.to_string(),
                 content: chunk.content.clone(),
@@ -184,12 +438,45 @@ impl IndexService {
                     "content": chunk.content,
                     "doc_id": doc_id,
                     "chunk_index": chunk.index,
-                    "path": record.metadata.path
+                    "path": record.metadata.path,
+                    "start_line": chunk.start_line,
+                    "end_line": chunk.end_line,
+                    "symbol": chunk.symbol,
+                    "kind": chunk.kind,
+                    "parent": chunk.parent,
+                    "embedding_model": embedding_model,
+                    "embedding_dim": embedding_dim
                 }),
+                embedding_model,
+                embedding_dim,
             };
-            self.db.create("chunks", &chunk_record).await?;
+            let id = format!("{}_{}", record_key(doc_id), chunk.index);
+            lexical::store(
+                self.db.client().as_ref(),
+                "chunks",
+                &id,
+                &chunk.content,
+                serde_json::to_value(&chunk_record)?,
+            )
+            .await?;
         }
 
         Ok(())
     }
+
+    /// Drop the chunks of `doc_id`, keeping the BM25 corpus stats in step.
+    async fn delete_chunks(&self, doc_id: &str) -> Result<()> {
+        lexical::delete_where(
+            self.db.client().as_ref(),
+            "chunks",
+            "doc_id",
+            serde_json::json!(doc_id),
+        )
+        .await
+    }
+}
+
+/// The key part of a record ID such as `documents:abc`.
+fn record_key(id: &str) -> &str {
+    id.split_once(':').map_or(id, |(_, key)| key)
 }
@@ -0,0 +1,866 @@
+//! MCP server for `gestalt mcp-serve`.
+//!
+//! Publishes the AgentRuntime tool registry over MCP (stdio or HTTP). File
+//! tools are routed through the VFS overlay, so writes stay pending until
+//! `flush_vfs` is called. Pending VFS changes and recent timeline events are
+//! exposed as MCP resources. Every `tools/call` is judged by the policy
+//! engine first, like the runtime's own actions.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Json, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use gestalt_core::mcp::protocol::{McpResource, ResourceContents};
+use gestalt_core::mcp::{McpError, McpResourceProvider, McpServer};
+use serde_json::{json, Value};
+use synapse_agentic::prelude::{Tool, ToolContext, ToolRegistry};
+use tokio::net::TcpListener;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::info;
+
+use crate::models::{EventType, TimelineEvent};
+use crate::services::server::{auth_middleware, require_api_token};
+use crate::services::{
+    ActionFacts, ApprovalService, ApprovalStatus, LockStatus, PendingChange, PolicyDecision,
+    PolicyEngine, TimelineService, VirtualFs,
+};
+
+const PENDING_URI: &str = "gestalt://vfs/pending";
+const VFS_FILE_PREFIX: &str = "gestalt://vfs/file/";
+const TIMELINE_URI: &str = "gestalt://timeline/events";
+
+const INSTRUCTIONS: &str = "Gestalt tools. read_file/write_file/remove_path/rename_path operate \
+on an isolated VFS overlay; diff_vfs shows them as a unified diff, flush_vfs persists them \
+(merging concurrent disk edits, reporting conflicts per hunk) and discard_vfs drops them. \
+Pending changes are listed at gestalt://vfs/pending.";
+
+/// Build the MCP server for `gestalt mcp-serve`.
+///
+/// `read_file`/`write_file` in `registry` are replaced by VFS-backed versions
+/// owned by `agent_id`, and `remove_path`/`rename_path`/`diff_vfs`/`flush_vfs`/
+/// `discard_vfs` are added. Each tool only runs once `policy` allows it, or
+/// once a human approved it through `approvals`.
+pub async fn build_mcp_server(
+    registry: Arc<ToolRegistry>,
+    vfs: Arc<dyn VirtualFs>,
+    timeline: TimelineService,
+    policy: Arc<PolicyEngine>,
+    approvals: ApprovalService,
+    agent_id: &str,
+) -> McpServer {
+    let vfs_tool = |vfs: &Arc<dyn VirtualFs>| VfsTool {
+        vfs: vfs.clone(),
+        timeline: timeline.clone(),
+        owner: agent_id.to_string(),
+    };
+    registry
+        .register_tool(VfsReadFileTool(vfs_tool(&vfs)))
+        .await;
+    registry
+        .register_tool(VfsWriteFileTool(vfs_tool(&vfs)))
+        .await;
+    registry
+        .register_tool(VfsRemovePathTool(vfs_tool(&vfs)))
+        .await;
+    registry
+        .register_tool(VfsRenamePathTool(vfs_tool(&vfs)))
+        .await;
+    registry.register_tool(DiffVfsTool(vfs_tool(&vfs))).await;
+    registry.register_tool(FlushVfsTool(vfs_tool(&vfs))).await;
+    registry.register_tool(DiscardVfsTool(vfs_tool(&vfs))).await;
+
+    let gate = Arc::new(PolicyGate {
+        policy,
+        approvals,
+        timeline: timeline.clone(),
+        agent_id: agent_id.to_string(),
+    });
+    let tools = ToolRegistry::new();
+    for inner in registry.tools().await {
+        tools
+            .register_tool(GatedTool {
+                inner,
+                gate: gate.clone(),
+            })
+            .await;
+    }
+
+    McpServer::new("gestalt", env!("CARGO_PKG_VERSION"), tools)
+        .with_instructions(INSTRUCTIONS)
+        .with_resource_provider(Arc::new(VfsResources { vfs }))
+        .with_resource_provider(Arc::new(TimelineResources { timeline }))
+}
+
+/// Serve MCP over streamable HTTP on `host:port` (`POST /mcp`).
+///
+/// `GET /tools` returns the plain tool list used by `gestalt_cli status/tools`.
+/// Every request needs `GESTALT_API_TOKEN`, so the server refuses to start
+/// without one. Browsers may only call it from the origins listed in
+/// `GESTALT_MCP_ALLOWED_ORIGINS` (comma-separated); by default none.
+pub async fn serve_mcp_http(server: Arc<McpServer>, host: &str, port: u16) -> anyhow::Result<()> {
+    if std::env::var("GESTALT_API_TOKEN")
+        .unwrap_or_default()
+        .is_empty()
+    {
+        anyhow::bail!(
+            "Set GESTALT_API_TOKEN before serving MCP over HTTP: its tools run shell commands and write files"
+        );
+    }
+
+    let mut app = Router::new()
+        .route("/mcp", post(mcp_endpoint).delete(end_session))
+        .route("/tools", get(list_tools))
+        .route("/health", get(|| async { "OK" }))
+        .layer(middleware::from_fn(auth_middleware))
+        .layer(middleware::from_fn(require_api_token))
+        .with_state(server);
+
+    let origins =
+        allowed_origins(&std::env::var("GESTALT_MCP_ALLOWED_ORIGINS").unwrap_or_default())?;
+    if !origins.is_empty() {
+        app = app.layer(
+            CorsLayer::new()
+                .allow_origin(AllowOrigin::list(origins))
+                .allow_methods([Method::GET, Method::POST, Method::DELETE])
+                .allow_headers([
+                    header::AUTHORIZATION,
+                    header::CONTENT_TYPE,
+                    header::ACCEPT,
+                    header::HeaderName::from_static("mcp-session-id"),
+                    header::HeaderName::from_static("mcp-protocol-version"),
+                ]),
+        );
+    }
+
+    let addr = format!("{}:{}", host, port);
+    info!("🔌 MCP server listening on http://{}/mcp", addr);
+
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Parse a comma-separated origin list such as `https://a.example,http://localhost:3000`.
+fn allowed_origins(list: &str) -> anyhow::Result<Vec<HeaderValue>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            anyhow::ensure!(
+                origin != "*",
+                "GESTALT_MCP_ALLOWED_ORIGINS must list origins, not '*'"
+            );
+            HeaderValue::from_str(origin)
+                .map_err(|e| anyhow::anyhow!("Invalid origin '{}': {}", origin, e))
+        })
+        .collect()
+}
+
+async fn mcp_endpoint(State(server): State<Arc<McpServer>>, Json(body): Json<Value>) -> Response {
+    match body {
+        Value::Array(batch) => {
+            let mut replies = Vec::new();
+            for message in batch {
+                if let Some(reply) = server.handle_value(message).await {
+                    replies.push(reply);
+                }
+            }
+            if replies.is_empty() {
+                StatusCode::ACCEPTED.into_response()
+            } else {
+                Json(Value::Array(replies)).into_response()
+            }
+        }
+        message => match server.handle_value(message).await {
+            Some(reply) => Json(reply).into_response(),
+            None => StatusCode::ACCEPTED.into_response(),
+        },
+    }
+}
+
+/// Sessions are stateless, so there is nothing to tear down.
+async fn end_session() -> StatusCode {
+    StatusCode::OK
+}
+
+async fn list_tools(State(server): State<Arc<McpServer>>) -> impl IntoResponse {
+    Json(server.tool_definitions().await)
+}
+
+/// Policy check shared by every published tool.
+struct PolicyGate {
+    policy: Arc<PolicyEngine>,
+    approvals: ApprovalService,
+    timeline: TimelineService,
+    agent_id: String,
+}
+
+impl PolicyGate {
+    /// Fail unless the policy allows `facts`, or a human approves them when
+    /// a rule asks for it.
+    async fn authorize(&self, facts: &ActionFacts, detail: &str) -> anyhow::Result<()> {
+        let verdict = self.policy.evaluate(facts);
+        self.timeline
+            .record_event(
+                TimelineEvent::new(&self.agent_id, EventType::PolicyDecision).with_payload(json!({
+                    "action": facts,
+                    "decision": verdict.decision,
+                    "rule": verdict.rule,
+                    "reason": verdict.reason,
+                    "source": "mcp",
+                })),
+            )
+            .await?;
+
+        let rule = verdict
+            .rule
+            .as_deref()
+            .map(|r| format!(" (rule '{}')", r))
+            .unwrap_or_default();
+        match verdict.decision {
+            PolicyDecision::Allow => Ok(()),
+            PolicyDecision::Deny => anyhow::bail!(
+                "Policy denied {}{}: {}",
+                facts.kind,
+                rule,
+                verdict.reason.as_deref().unwrap_or("not allowed")
+            ),
+            PolicyDecision::RequireApproval => {
+                let request = self.approvals.request(facts, detail, &verdict).await?;
+                info!(
+                    "Waiting for approval {} before running {}{}",
+                    request.approval_id, facts.kind, rule
+                );
+                let status = self
+                    .approvals
+                    .wait(&request.approval_id, self.policy.approval_timeout())
+                    .await?;
+                anyhow::ensure!(
+                    status == ApprovalStatus::Approved,
+                    "Approval {} for {}{} was {}; the tool did not run.",
+                    request.approval_id,
+                    facts.kind,
+                    rule,
+                    status
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What the policy engine is told about a `tools/call`. Kinds match the
+/// runtime's action kinds, so one policy file covers agents and MCP clients.
+fn tool_facts(name: &str, args: &Value, agent_id: &str) -> ActionFacts {
+    let str_arg = |key: &str| args.get(key).and_then(Value::as_str);
+    let kind = match name {
+        "remove_path" if args.get("recursive").and_then(Value::as_bool) == Some(true) => {
+            "remove_dir"
+        }
+        "remove_path" => "remove_file",
+        other => other,
+    };
+    let mut facts = ActionFacts::new(kind, agent_id);
+    if name == "git_push" {
+        facts = facts.with_command(format!(
+            "git push {} {}",
+            str_arg("remote").unwrap_or("origin"),
+            str_arg("branch").unwrap_or("main")
+        ));
+    } else if let Some(command) = str_arg("command") {
+        facts = facts.with_command(command);
+    }
+    for key in ["path", "from", "to"] {
+        if let Some(path) = str_arg(key) {
+            facts = facts.with_path(path);
+        }
+    }
+    for path in args
+        .get("paths")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        facts = facts.with_path(path);
+    }
+    facts
+}
+
+/// A published tool that runs only once the [`PolicyGate`] lets it.
+struct GatedTool {
+    inner: Arc<dyn Tool>,
+    gate: Arc<PolicyGate>,
+}
+
+#[async_trait]
+impl Tool for GatedTool {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+    fn parameters(&self) -> Value {
+        self.inner.parameters()
+    }
+
+    async fn call(&self, ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        let name = self.inner.name();
+        let facts = tool_facts(name, &args, &self.gate.agent_id);
+        self.gate
+            .authorize(&facts, &format!("{} {}", name, args))
+            .await?;
+        self.inner.call(ctx, args).await
+    }
+}
+
+/// Shared state of the VFS-backed tools.
+#[derive(Clone)]
+struct VfsTool {
+    vfs: Arc<dyn VirtualFs>,
+    timeline: TimelineService,
+    owner: String,
+}
+
+impl VfsTool {
+    async fn record(&self, event_type: EventType, payload: Value) {
+        let _ = self
+            .timeline
+            .record_event(TimelineEvent::new(&self.owner, event_type).with_payload(payload))
+            .await;
+    }
+
+    /// Record a remove/rename outcome and pass the error through.
+    async fn record_op(
+        &self,
+        mut payload: Value,
+        result: anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        payload["source"] = json!("mcp");
+        match result {
+            Ok(()) => {
+                payload["version"] = json!(self.vfs.version().await);
+                self.record(EventType::VfsPatchApplied, payload).await;
+                Ok(())
+            }
+            Err(e) => {
+                if e.to_string().contains("lock conflict") {
+                    payload["error"] = json!(e.to_string());
+                    self.record(EventType::VfsLockConflict, payload).await;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+fn path_arg(args: &Value) -> anyhow::Result<&str> {
+    args.get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter"))
+}
+
+struct VfsReadFileTool(VfsTool);
+
+#[async_trait]
+impl Tool for VfsReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+    fn description(&self) -> &str {
+        "Read a file, including pending (unflushed) VFS writes."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "The absolute or relative path to the file" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        let path = path_arg(&args)?;
+        match self.0.vfs.read_to_string(Path::new(path)).await {
+            Ok(content) => Ok(json!({ "content": content })),
+            Err(e) => Err(anyhow::anyhow!("Failed to read file '{}': {}", path, e)),
+        }
+    }
+}
+
+struct VfsWriteFileTool(VfsTool);
+
+#[async_trait]
+impl Tool for VfsWriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+    fn description(&self) -> &str {
+        "Write content to a file in the VFS overlay. Changes stay pending until flush_vfs."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "The path to the file to create or overwrite" },
+                "content": { "type": "string", "description": "The string content to write" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        let path = path_arg(&args)?;
+        let content = args
+            .get("content")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'content' parameter"))?;
+        let tool = &self.0;
+
+        if let Some(parent) = Path::new(path).parent() {
+            tool.vfs.create_dir_all(parent).await?;
+        }
+        if let LockStatus::HeldByOther { owner } =
+            tool.vfs.acquire_lock(Path::new(path), &tool.owner).await?
+        {
+            tool.record(
+                EventType::VfsLockConflict,
+                json!({ "path": path, "owner": owner }),
+            )
+            .await;
+            anyhow::bail!(
+                "Lock conflict for '{}': currently held by '{}'",
+                path,
+                owner
+            );
+        }
+
+        tool.vfs
+            .write_string(Path::new(path), content.to_string(), &tool.owner)
+            .await?;
+        let version = tool.vfs.version().await;
+        tool.record(
+            EventType::VfsPatchApplied,
+            json!({ "path": path, "version": version, "source": "mcp" }),
+        )
+        .await;
+
+        Ok(json!({ "success": true, "bytes_written": content.len(), "pending": true }))
+    }
+}
+
+struct VfsRemovePathTool(VfsTool);
+
+#[async_trait]
+impl Tool for VfsRemovePathTool {
+    fn name(&self) -> &str {
+        "remove_path"
+    }
+    fn description(&self) -> &str {
+        "Delete a file, or a directory with recursive=true, in the VFS overlay. Applied on flush_vfs."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "The file or directory to delete" },
+                "recursive": { "type": "boolean", "description": "Delete a directory and everything below it" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        let path = path_arg(&args)?;
+        let recursive = args
+            .get("recursive")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let tool = &self.0;
+
+        let result = if recursive {
+            tool.vfs.remove_dir_all(Path::new(path), &tool.owner).await
+        } else {
+            tool.vfs.remove_file(Path::new(path), &tool.owner).await
+        };
+        let op = if recursive {
+            "remove_dir"
+        } else {
+            "remove_file"
+        };
+        tool.record_op(json!({ "op": op, "path": path }), result)
+            .await?;
+
+        Ok(json!({ "success": true, "pending": true }))
+    }
+}
+
+struct VfsRenamePathTool(VfsTool);
+
+#[async_trait]
+impl Tool for VfsRenamePathTool {
+    fn name(&self) -> &str {
+        "rename_path"
+    }
+    fn description(&self) -> &str {
+        "Move a file or directory in the VFS overlay. Applied on flush_vfs."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "from": { "type": "string", "description": "The existing path" },
+                "to": { "type": "string", "description": "The new path" }
+            },
+            "required": ["from", "to"]
+        })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, args: Value) -> anyhow::Result<Value> {
+        let arg = |name: &str| {
+            args.get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing '{}' parameter", name))
+        };
+        let (from, to) = (arg("from")?, arg("to")?);
+        let tool = &self.0;
+
+        let result = tool
+            .vfs
+            .rename(Path::new(from), Path::new(to), &tool.owner)
+            .await;
+        tool.record_op(json!({ "op": "rename", "from": from, "to": to }), result)
+            .await?;
+
+        Ok(json!({ "success": true, "pending": true }))
+    }
+}
+
+struct DiffVfsTool(VfsTool);
+
+#[async_trait]
+impl Tool for DiffVfsTool {
+    fn name(&self) -> &str {
+        "diff_vfs"
+    }
+    fn description(&self) -> &str {
+        "Unified diff (git apply compatible) of all pending VFS changes against disk."
+    }
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, _args: Value) -> anyhow::Result<Value> {
+        Ok(json!({ "diff": self.0.vfs.diff().await? }))
+    }
+}
+
+struct FlushVfsTool(VfsTool);
+
+#[async_trait]
+impl Tool for FlushVfsTool {
+    fn name(&self) -> &str {
+        "flush_vfs"
+    }
+    fn description(&self) -> &str {
+        "Persist all pending VFS changes to disk."
+    }
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, _args: Value) -> anyhow::Result<Value> {
+        let tool = &self.0;
+        tool.record(EventType::VfsFlushStarted, json!({ "source": "mcp" }))
+            .await;
+        let report = tool.vfs.flush().await?;
+        let version = tool.vfs.version().await;
+        tool.record(
+            EventType::VfsFlushCompleted,
+            json!({
+                "version": version,
+                "files": report.written_files.len(),
+                "dirs": report.created_dirs.len(),
+                "removed": report.removed_paths.len(),
+                "merged": report.merged_files.len(),
+                "conflicts": report.conflicts.len(),
+                "errors": report.errors.len(),
+            }),
+        )
+        .await;
+        tool.vfs.release_locks(&tool.owner).await;
+
+        Ok(json!({
+            "version": version,
+            "written_files": report.written_files,
+            "created_dirs": report.created_dirs,
+            "removed_paths": report.removed_paths,
+            "merged_files": report.merged_files,
+            "conflicts": report
+                .conflicts
+                .iter()
+                .map(|c| json!({
+                    "path": c.path,
+                    "hunks": c
+                        .hunks
+                        .iter()
+                        .map(|h| json!({
+                            "base_start": h.base_start,
+                            "base": h.base,
+                            "ours": h.ours,
+                            "theirs": h.theirs,
+                        }))
+                        .collect::<Vec<_>>(),
+                }))
+                .collect::<Vec<_>>(),
+            "errors": report
+                .errors
+                .iter()
+                .map(|e| json!({ "path": e.path, "operation": e.operation, "error": e.error }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+}
+
+struct DiscardVfsTool(VfsTool);
+
+#[async_trait]
+impl Tool for DiscardVfsTool {
+    fn name(&self) -> &str {
+        "discard_vfs"
+    }
+    fn description(&self) -> &str {
+        "Drop all pending VFS changes without touching disk."
+    }
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn call(&self, _ctx: &dyn ToolContext, _args: Value) -> anyhow::Result<Value> {
+        let discarded = self.0.vfs.pending_changes().await.len();
+        self.0.vfs.discard().await;
+        Ok(json!({ "discarded": discarded }))
+    }
+}
+
+/// JSON form of a pending change, as reported by the VFS tools.
+pub fn pending_change_json(change: &PendingChange) -> Value {
+    match change {
+        PendingChange::CreateDir { path } => json!({ "kind": "create_dir", "path": path }),
+        PendingChange::WriteFile { path, bytes } => {
+            json!({ "kind": "write_file", "path": path, "bytes": bytes })
+        }
+        PendingChange::RemoveFile { path } => json!({ "kind": "remove_file", "path": path }),
+        PendingChange::RemoveDir { path } => json!({ "kind": "remove_dir", "path": path }),
+        PendingChange::Rename { from, to } => {
+            json!({ "kind": "rename", "from": from, "to": to })
+        }
+    }
+}
+
+/// Pending VFS changes: a summary plus one resource per pending file.
+struct VfsResources {
+    vfs: Arc<dyn VirtualFs>,
+}
+
+#[async_trait]
+impl McpResourceProvider for VfsResources {
+    async fn list_resources(&self) -> Result<Vec<McpResource>, McpError> {
+        let mut resources = vec![McpResource {
+            uri: PENDING_URI.to_string(),
+            name: "VFS pending changes".to_string(),
+            description: Some("Writes, removals and renames not yet flushed to disk".to_string()),
+            mime_type: Some("application/json".to_string()),
+        }];
+        for change in self.vfs.pending_changes().await {
+            if let PendingChange::WriteFile { path, bytes } = change {
+                resources.push(McpResource {
+                    uri: format!("{}{}", VFS_FILE_PREFIX, path.display()),
+                    name: path.display().to_string(),
+                    description: Some(format!("Pending write ({} bytes)", bytes)),
+                    mime_type: Some("text/plain".to_string()),
+                });
+            }
+        }
+        Ok(resources)
+    }
+
+    fn handles(&self, uri: &str) -> bool {
+        uri == PENDING_URI || uri.starts_with(VFS_FILE_PREFIX)
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, McpError> {
+        if uri == PENDING_URI {
+            let changes: Vec<Value> = self
+                .vfs
+                .pending_changes()
+                .await
+                .iter()
+                .map(pending_change_json)
+                .collect();
+            let summary = json!({ "version": self.vfs.version().await, "changes": changes });
+            return Ok(vec![ResourceContents::json(uri, &summary)]);
+        }
+
+        let path = PathBuf::from(uri.trim_start_matches(VFS_FILE_PREFIX));
+        let text = self
+            .vfs
+            .read_to_string(&path)
+            .await
+            .map_err(|e| McpError::Execution(format!("{}: {}", path.display(), e)))?;
+        Ok(vec![ResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some("text/plain".to_string()),
+            text,
+        }])
+    }
+}
+
+/// Recent timeline events (`?since=1h` narrows the window, default 24h).
+struct TimelineResources {
+    timeline: TimelineService,
+}
+
+#[async_trait]
+impl McpResourceProvider for TimelineResources {
+    async fn list_resources(&self) -> Result<Vec<McpResource>, McpError> {
+        Ok(vec![McpResource {
+            uri: TIMELINE_URI.to_string(),
+            name: "Timeline events".to_string(),
+            description: Some(
+                "Latest 100 timeline events (last 24h; append ?since=1h to narrow)".to_string(),
+            ),
+            mime_type: Some("application/json".to_string()),
+        }])
+    }
+
+    fn handles(&self, uri: &str) -> bool {
+        uri == TIMELINE_URI || uri.starts_with(&format!("{}?", TIMELINE_URI))
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, McpError> {
+        let since = uri
+            .split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|kv| kv.strip_prefix("since=")));
+        let events = self
+            .timeline
+            .get_timeline(since)
+            .await
+            .map_err(|e| McpError::Execution(e.to_string()))?;
+        let value =
+            serde_json::to_value(&events).map_err(|e| McpError::InvalidResponse(e.to_string()))?;
+        Ok(vec![ResourceContents::json(uri, &value)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SurrealClient;
+    use crate::services::FileManager;
+
+    const POLICY: &str = r#"
+default = "allow"
+
+[[rules]]
+name = "no-secrets"
+path = "secrets/**"
+decision = "deny"
+
+[[rules]]
+action = "remove_dir"
+decision = "deny"
+"#;
+
+    async fn server() -> McpServer {
+        let db = SurrealClient::connect_mem().await.unwrap();
+        let timeline = TimelineService::new(db.clone());
+        let (vfs, actor) = FileManager::for_agent("mcp-test");
+        tokio::spawn(actor.run());
+        build_mcp_server(
+            Arc::new(ToolRegistry::new()),
+            Arc::new(vfs),
+            timeline.clone(),
+            Arc::new(PolicyEngine::from_toml(POLICY).unwrap()),
+            ApprovalService::new(db, timeline),
+            "mcp-test",
+        )
+        .await
+    }
+
+    async fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        });
+        server.handle_value(request).await.unwrap()["result"].clone()
+    }
+
+    #[tokio::test]
+    async fn tool_calls_go_through_the_policy() {
+        let server = server().await;
+
+        let denied = call(
+            &server,
+            "write_file",
+            json!({ "path": "secrets/key.pem", "content": "x" }),
+        )
+        .await;
+        assert_eq!(denied["isError"], true);
+        let text = denied["content"][0]["text"].as_str().unwrap();
+        assert!(
+            text.contains("Policy denied write_file (rule 'no-secrets')"),
+            "{}",
+            text
+        );
+
+        let allowed = call(
+            &server,
+            "write_file",
+            json!({ "path": "notes.txt", "content": "x" }),
+        )
+        .await;
+        assert_eq!(allowed["isError"], false);
+
+        let recursive = call(
+            &server,
+            "remove_path",
+            json!({ "path": "src", "recursive": true }),
+        )
+        .await;
+        assert_eq!(recursive["isError"], true);
+    }
+
+    #[test]
+    fn tool_facts_use_runtime_action_kinds() {
+        let facts = tool_facts("git_push", &json!({ "branch": "dev" }), "a");
+        assert_eq!(facts.command.as_deref(), Some("git push origin dev"));
+        let facts = tool_facts("rename_path", &json!({ "from": "a", "to": "b" }), "a");
+        assert_eq!(facts.paths, ["a", "b"]);
+        let facts = tool_facts("remove_path", &json!({ "path": "x" }), "a");
+        assert_eq!(facts.kind, "remove_file");
+    }
+
+    #[test]
+    fn allowed_origins_must_be_explicit() {
+        assert!(allowed_origins("").unwrap().is_empty());
+        assert_eq!(
+            allowed_origins("https://a.example, http://localhost:3000")
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(allowed_origins("*").is_err());
+    }
+}
@@ -1,49 +1,71 @@
 //! Services module
 
 mod agent;
+pub mod approval;
 mod auth;
 pub mod context_compaction;
+mod cooldown_store;
 pub mod dispatcher;
 mod feedback_loop;
 pub mod file_manager;
 mod index;
+pub mod mcp_server;
 pub mod memory;
+pub mod policy;
 mod project;
 pub mod protocol_sync;
 pub mod reviewer_merge_agent;
+pub mod run_control;
 mod runtime;
+pub mod scheduler;
 mod server;
 mod task;
+pub mod task_graph;
 pub mod task_queue;
 #[cfg(feature = "telegram")]
 pub mod telegram;
 mod timeline;
+mod usage;
 mod watch;
 
 pub use feedback_loop::{FeedbackLoopService, SwarmAgentResult};
 
 pub use agent::{Agent, AgentService, AgentStatus, AgentType};
+pub use approval::{ApprovalRequest, ApprovalService, ApprovalStatus};
 pub use auth::AuthService;
 
 pub use context_compaction::{CompactionOutcome, ContextCompactor};
+pub use cooldown_store::SurrealCooldownStore;
 pub use dispatcher::DispatcherService;
 pub use file_manager::{FileManager, FileManagerActor, FileState};
+pub use gestalt_core::ports::outbound::sandbox::{
+    default_sandbox, default_sandbox_or_disabled, CommandSandbox, SandboxOutput, SandboxPolicy,
+    SandboxRequest, SandboxedProcess,
+};
 pub use gestalt_core::ports::outbound::vfs::{
     FileEventType, FileWatchEvent, FileWatcher, FlushError, FlushReport, LockStatus, OverlayFs,
     PendingChange, VirtualFileSystem as VirtualFs,
 };
+pub use gestalt_core::ports::outbound::vfs_journal::{JournalEntry, VfsJournal};
+pub use gestalt_core::ports::outbound::vfs_layer::LayerFs;
 pub use index::IndexService;
+pub use mcp_server::{build_mcp_server, pending_change_json, serve_mcp_http};
 pub use memory::{MemoryFragment, MemoryService};
+pub use policy::{ActionFacts, PolicyDecision, PolicyEngine, PolicyVerdict};
 pub use project::ProjectService;
 pub use protocol_sync::ProtocolSyncService;
 pub use reviewer_merge_agent::{
     spawn_reviewer_agent, ReviewResult, ReviewerMergeAgent, ReviewerMessage,
 };
+pub use run_control::{ControlRequest, ControlSignal, RunCancelled, RunControl, RunControlService};
 pub use runtime::{AgentRuntime, OrchestrationAction};
+pub use scheduler::{CatchUp, SchedulerService};
 pub use server::start_server;
 pub use task::TaskService;
-pub use task_queue::{QueuedTask, TaskQueue, TaskSource};
+pub use task_graph::{CriticalPath, Readiness, TaskGraph};
+pub use task_queue::{is_worker_id, QueueStatus, QueuedTask, TaskQueue, TaskSource};
 #[cfg(feature = "telegram")]
 pub use telegram::TelegramService;
 pub use timeline::TimelineService;
+pub use usage::{Budget, RunUsage, UsageGroup, UsageRow, UsageService};
 pub use watch::WatchService;
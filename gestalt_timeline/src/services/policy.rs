@@ -0,0 +1,941 @@
+//! Policy engine - declarative allow/deny/approval rules for agent actions.
+//!
+//! Sits between the LLM decision and `AgentRuntime::execute_action`. Rules are
+//! loaded from a TOML file (`GESTALT_POLICY`, or `.gestalt/policy.toml` in the
+//! working directory) and evaluated top to bottom; the first rule whose
+//! criteria all match decides. When no rule matches, `default` applies.
+//!
+//! ```toml
+//! default = "allow"
+//! approval_timeout_secs = 600
+//!
+//! [[rules]]
+//! name = "no-recursive-delete"
+//! action = "execute_shell"
+//! command = "rm -rf*"
+//! decision = "deny"
+//! reason = "Recursive deletes are never allowed"
+//!
+//! [[rules]]
+//! action = ["git_push", "call_agent"]
+//! agent = "swarm-*"
+//! decision = "require_approval"
+//!
+//! [[rules]]
+//! path = ["secrets/**", "**/*.pem"]
+//! decision = "deny"
+//! ```
+//!
+//! Globs: `command` and `agent` use `*` for any run of characters (including
+//! `/`); `path` treats `/` as a separator, so `*` stays within one component
+//! and `**` crosses directories.
+//!
+//! A `command` pattern that starts with a literal program name is also matched
+//! against every simple command of the line (split on `;`, `&&`, `|`, ...)
+//! after parsing: short flags are compared as a set, so `rm -rf*` catches
+//! `rm -fr /`, `rm -r -f /` and `x && /bin/rm -rvf /`. Without a trailing `*`
+//! the flags must match exactly; with one, extra flags and operands are fine.
+//! Commands run through wrappers (`sudo -u root rm ...`, `xargs -n 1 rm ...`)
+//! and the scripts given to `sh -c`, `bash -c` and `eval` are checked too.
+//! Paths are resolved lexically and made relative to the workspace, so
+//! `secrets/**` also covers `/repo/secrets/key` and `src/../secrets/key`.
+
+use anyhow::{Context, Result};
+use gestalt_core::ports::outbound::sandbox::{is_assignment, simple_commands};
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Default time an action waits for a human decision before it is denied.
+const DEFAULT_APPROVAL_TIMEOUT_SECS: u64 = 300;
+
+/// What a policy rule decides for a matching action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Allow,
+    Deny,
+    #[serde(alias = "approve", alias = "ask")]
+    RequireApproval,
+}
+
+impl fmt::Display for PolicyDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyDecision::Allow => write!(f, "allow"),
+            PolicyDecision::Deny => write!(f, "deny"),
+            PolicyDecision::RequireApproval => write!(f, "require_approval"),
+        }
+    }
+}
+
+/// A single pattern or a list of them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Patterns {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Patterns::One(p) => vec![p],
+            Patterns::Many(ps) => ps,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default = "default_decision")]
+    default: PolicyDecision,
+    #[serde(default = "default_approval_timeout")]
+    approval_timeout_secs: u64,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    name: Option<String>,
+    action: Option<Patterns>,
+    command: Option<Patterns>,
+    path: Option<Patterns>,
+    agent: Option<Patterns>,
+    decision: PolicyDecision,
+    reason: Option<String>,
+}
+
+fn default_decision() -> PolicyDecision {
+    PolicyDecision::Allow
+}
+
+fn default_approval_timeout() -> u64 {
+    DEFAULT_APPROVAL_TIMEOUT_SECS
+}
+
+/// What the policy engine knows about an action it is asked to judge.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ActionFacts {
+    /// Snake-case action kind, e.g. `execute_shell` or `git_push`.
+    pub kind: String,
+    /// Agent that wants to run the action.
+    pub agent_id: String,
+    /// Command line, for actions that run one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Paths the action reads or writes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+impl ActionFacts {
+    pub fn new(kind: impl Into<String>, agent_id: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            agent_id: agent_id.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+}
+
+/// Outcome of evaluating an action against the policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyVerdict {
+    pub decision: PolicyDecision,
+    /// Name of the rule that matched; `None` when the default applied.
+    pub rule: Option<String>,
+    pub reason: Option<String>,
+}
+
+struct Rule {
+    name: String,
+    actions: Option<Vec<String>>,
+    command: Option<CommandMatcher>,
+    path: Option<GlobSet>,
+    agent: Option<GlobSet>,
+    decision: PolicyDecision,
+    reason: Option<String>,
+}
+
+impl Rule {
+    fn matches(&self, facts: &ActionFacts, workspace: Option<&Path>) -> bool {
+        if let Some(actions) = &self.actions {
+            if !actions.iter().any(|a| a == "*" || *a == facts.kind) {
+                return false;
+            }
+        }
+        if let Some(command) = &self.command {
+            match &facts.command {
+                Some(cmd) if command.is_match(cmd) => {}
+                _ => return false,
+            }
+        }
+        if let Some(path) = &self.path {
+            if !facts
+                .paths
+                .iter()
+                .any(|p| path.is_match(normalize_path(p, workspace)))
+            {
+                return false;
+            }
+        }
+        if let Some(agent) = &self.agent {
+            if !agent.is_match(&facts.agent_id) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Command patterns: the raw globs over the whole line, plus the parsed form of
+/// those that name a program.
+struct CommandMatcher {
+    raw: GlobSet,
+    parsed: Vec<CommandPattern>,
+}
+
+impl CommandMatcher {
+    fn compile(patterns: Vec<String>) -> Result<Self> {
+        let parsed = patterns
+            .iter()
+            .map(|p| CommandPattern::parse(p))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            raw: compile(patterns, false)?,
+            parsed: parsed.into_iter().flatten().collect(),
+        })
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        let mut scripts = Vec::new();
+        let mut commands = Vec::new();
+        expand_script(line, &mut scripts, &mut commands);
+        scripts.iter().any(|script| self.raw.is_match(script))
+            || commands
+                .into_iter()
+                .filter_map(ParsedCommand::from_words)
+                .any(|cmd| self.parsed.iter().any(|p| p.matches(&cmd)))
+    }
+}
+
+/// A simple command: program name without its directory, flags (short flag
+/// clusters split up, `--name=value` reduced to `--name`) and operands.
+#[derive(Debug, PartialEq)]
+struct ParsedCommand {
+    program: String,
+    flags: BTreeSet<String>,
+    operands: Vec<String>,
+}
+
+impl ParsedCommand {
+    fn from_words(words: Vec<String>) -> Option<Self> {
+        let mut words = words.into_iter().skip_while(|w| is_assignment(w));
+        let program = words.next()?;
+        let program = program_name(&program).to_string();
+        let mut flags = BTreeSet::new();
+        let mut operands = Vec::new();
+        let mut options_done = false;
+        for word in words {
+            if options_done || word == "-" || !word.starts_with('-') {
+                operands.push(word);
+            } else if word == "--" {
+                options_done = true;
+            } else if let Some(long) = word.strip_prefix("--") {
+                let name = long.split('=').next().unwrap_or(long);
+                flags.insert(format!("--{}", name));
+            } else {
+                flags.extend(word.chars().skip(1).map(|c| format!("-{}", c)));
+            }
+        }
+        Some(Self {
+            program,
+            flags,
+            operands,
+        })
+    }
+}
+
+/// A program that runs the command given in its arguments.
+struct Wrapper {
+    name: &'static str,
+    /// Options whose value is the next word unless attached (`-u root`).
+    valued: &'static [&'static str],
+    /// Operands in front of the command, like the duration of `timeout`.
+    operands: usize,
+}
+
+const WRAPPERS: &[Wrapper] = &[
+    Wrapper::new("builtin", &[], 0),
+    Wrapper::new("chroot", &["--groups", "--userspec"], 1),
+    Wrapper::new("command", &[], 0),
+    Wrapper::new("doas", &["-C", "-u"], 0),
+    Wrapper::new("env", &["-C", "-u", "--chdir", "--unset"], 0),
+    Wrapper::new("exec", &["-a"], 0),
+    Wrapper::new("nice", &["-n", "--adjustment"], 0),
+    Wrapper::new("nohup", &[], 0),
+    Wrapper::new("setsid", &[], 0),
+    Wrapper::new(
+        "stdbuf",
+        &["-e", "-i", "-o", "--error", "--input", "--output"],
+        0,
+    ),
+    Wrapper::new(
+        "sudo",
+        &[
+            "-C",
+            "-D",
+            "-g",
+            "-p",
+            "-R",
+            "-r",
+            "-T",
+            "-t",
+            "-U",
+            "-u",
+            "--chdir",
+            "--chroot",
+            "--close-from",
+            "--command-timeout",
+            "--group",
+            "--other-user",
+            "--prompt",
+            "--role",
+            "--type",
+            "--user",
+        ],
+        0,
+    ),
+    Wrapper::new("time", &["-f", "-o", "--format", "--output"], 0),
+    Wrapper::new("timeout", &["-k", "-s", "--kill-after", "--signal"], 1),
+    Wrapper::new(
+        "xargs",
+        &[
+            "-a",
+            "-d",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "-P",
+            "-s",
+            "--arg-file",
+            "--delimiter",
+            "--max-args",
+            "--max-chars",
+            "--max-procs",
+            "--process-slot-var",
+        ],
+        0,
+    ),
+];
+
+impl Wrapper {
+    const fn new(name: &'static str, valued: &'static [&'static str], operands: usize) -> Self {
+        Self {
+            name,
+            valued,
+            operands,
+        }
+    }
+
+    /// Whether option word `arg` takes the next word as its value.
+    fn takes_value(&self, arg: &str) -> bool {
+        if arg.starts_with("--") {
+            return !arg.contains('=') && self.valued.contains(&arg);
+        }
+        // In a short option cluster the first option with a value takes the
+        // rest of the word, or the next word when nothing follows it.
+        let cluster = &arg[1..];
+        cluster
+            .char_indices()
+            .find(|(_, c)| self.valued.iter().any(|v| v.len() == 2 && v.ends_with(*c)))
+            .is_some_and(|(i, c)| i + c.len_utf8() == cluster.len())
+    }
+
+    /// The command in `args`, the words after the wrapper's name.
+    fn inner(&self, args: &[String]) -> Vec<String> {
+        let mut args = args.iter();
+        let mut inner = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                break;
+            }
+            if arg.len() > 1 && arg.starts_with('-') {
+                if self.takes_value(arg) {
+                    args.next();
+                }
+            } else if !is_assignment(arg) {
+                inner.push(arg.clone());
+                break;
+            }
+        }
+        inner.extend(args.cloned());
+        inner.into_iter().skip(self.operands).collect()
+    }
+}
+
+/// Shells whose `-c` script is parsed as a command line of its own.
+const SHELLS: &[&str] = &["ash", "bash", "dash", "ksh", "mksh", "sh", "zsh"];
+
+/// The script a shell runs with `-c`: its first operand, e.g. `rm -fr x` for
+/// `sh -ec 'rm -fr x'`.
+fn shell_script(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    let mut command_string = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "-o" | "+o" | "-O" | "+O" | "--init-file" | "--rcfile" => {
+                args.next();
+            }
+            a if a.starts_with("--") || a.starts_with('+') => {}
+            a if a.starts_with('-') => command_string |= a.contains('c'),
+            script => return command_string.then(|| script.to_string()),
+        }
+    }
+    args.next().filter(|_| command_string).cloned()
+}
+
+fn program_name(word: &str) -> &str {
+    Path::new(word)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(word)
+}
+
+/// Collect `script` and the scripts it passes to shells (`sh -c`) and `eval`
+/// into `scripts`, and the simple commands they run into `commands`: each
+/// one followed by the commands it runs through [`WRAPPERS`], e.g. `rm -fr`
+/// for `sudo -u root xargs -n 1 rm -fr`.
+///
+/// A script that does not parse only joins `scripts`; the sandbox refuses to
+/// run such command lines anyway.
+fn expand_script(script: &str, scripts: &mut Vec<String>, commands: &mut Vec<Vec<String>>) {
+    scripts.push(script.trim().to_string());
+    let Ok(simple) = simple_commands(script) else {
+        return;
+    };
+    for words in simple {
+        let mut current = words;
+        while let Some(start) = current.iter().position(|w| !is_assignment(w)) {
+            let name = program_name(&current[start]);
+            let args = &current[start + 1..];
+            let inner = if name == "eval" {
+                expand_script(&args.join(" "), scripts, commands);
+                None
+            } else if SHELLS.contains(&name) {
+                if let Some(script) = shell_script(args) {
+                    expand_script(&script, scripts, commands);
+                }
+                None
+            } else {
+                WRAPPERS
+                    .iter()
+                    .find(|w| w.name == name)
+                    .map(|w| w.inner(args))
+                    .filter(|inner| !inner.is_empty())
+            };
+            commands.push(std::mem::replace(&mut current, inner.unwrap_or_default()));
+            if current.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+/// The parsed form of a command pattern such as `rm -rf*`.
+struct CommandPattern {
+    program: String,
+    flags: BTreeSet<String>,
+    operands: Option<GlobMatcher>,
+    /// The pattern ended in `*`: extra flags and operands are allowed.
+    open: bool,
+}
+
+impl CommandPattern {
+    /// `None` for patterns that do not start with a literal program name or
+    /// span several commands; those only match as raw globs.
+    fn parse(pattern: &str) -> Result<Option<Self>> {
+        let Ok(mut commands) = simple_commands(pattern) else {
+            return Ok(None);
+        };
+        if commands.len() != 1 {
+            return Ok(None);
+        }
+        let mut words = commands.remove(0);
+        let open = words.last().is_some_and(|w| w.ends_with('*'));
+        if open {
+            let last = words.last_mut().expect("checked above");
+            last.pop();
+            if last.is_empty() {
+                words.pop();
+            }
+        }
+        let is_glob = |w: &str| w.contains(['*', '?', '[', ']', '{', '}']);
+        if words.first().is_none_or(|program| is_glob(program)) {
+            return Ok(None);
+        }
+        let Some(parsed) = ParsedCommand::from_words(words) else {
+            return Ok(None);
+        };
+        if parsed.flags.iter().any(|flag| is_glob(flag)) {
+            return Ok(None);
+        }
+        let operands = if parsed.operands.is_empty() {
+            None
+        } else {
+            let mut glob = parsed.operands.join(" ");
+            if open && !glob.ends_with('*') {
+                glob.push('*');
+            }
+            let matcher = GlobBuilder::new(&glob)
+                .backslash_escape(true)
+                .build()
+                .with_context(|| format!("invalid pattern '{}'", pattern))?
+                .compile_matcher();
+            Some(matcher)
+        };
+        Ok(Some(Self {
+            program: parsed.program,
+            flags: parsed.flags,
+            operands,
+            open,
+        }))
+    }
+
+    fn matches(&self, cmd: &ParsedCommand) -> bool {
+        let flags = if self.open {
+            self.flags.is_subset(&cmd.flags)
+        } else {
+            self.flags == cmd.flags
+        };
+        let operands = match &self.operands {
+            Some(glob) => glob.is_match(cmd.operands.join(" ")),
+            None => self.open || cmd.operands.is_empty(),
+        };
+        self.program == cmd.program && flags && operands
+    }
+}
+
+/// Resolve `.` and `..` lexically and make paths inside `workspace` relative
+/// to it, so `/repo/secrets/key` and `src/../secrets/key` both match
+/// `secrets/**`.
+fn normalize_path(path: &str, workspace: Option<&Path>) -> String {
+    let path = Path::new(path);
+    let path = match workspace {
+        Some(root) if path.is_relative() => clean_path(&root.join(path)),
+        _ => clean_path(path),
+    };
+    let relative = workspace
+        .map(clean_path)
+        .and_then(|root| path.strip_prefix(root).ok().map(Path::to_path_buf));
+    relative.unwrap_or(path).to_string_lossy().into_owned()
+}
+
+fn clean_path(path: &Path) -> PathBuf {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match clean.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    clean.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => clean.push(".."),
+            },
+            other => clean.push(other),
+        }
+    }
+    clean
+}
+
+fn compile(patterns: Vec<String>, literal_separator: bool) -> Result<GlobSet> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        let mut glob = pattern.as_str();
+        while let Some(rest) = glob.strip_prefix("./") {
+            glob = rest;
+        }
+        let glob = GlobBuilder::new(glob)
+            .literal_separator(literal_separator)
+            .backslash_escape(true)
+            .build()
+            .with_context(|| format!("invalid pattern '{}'", pattern))?;
+        set.add(glob);
+    }
+    Ok(set.build()?)
+}
+
+/// Evaluates actions against an ordered list of rules.
+pub struct PolicyEngine {
+    default: PolicyDecision,
+    approval_timeout: Duration,
+    rules: Vec<Rule>,
+    source: Option<PathBuf>,
+    workspace: Option<PathBuf>,
+}
+
+impl PolicyEngine {
+    /// A policy without rules that allows everything.
+    pub fn allow_all() -> Self {
+        Self {
+            default: PolicyDecision::Allow,
+            approval_timeout: Duration::from_secs(DEFAULT_APPROVAL_TIMEOUT_SECS),
+            rules: Vec::new(),
+            source: None,
+            workspace: None,
+        }
+    }
+
+    /// A policy without rules that denies everything (used when a policy file is broken).
+    pub fn deny_all() -> Self {
+        Self {
+            default: PolicyDecision::Deny,
+            ..Self::allow_all()
+        }
+    }
+
+    /// Parse a policy from TOML text.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let file: PolicyFile = toml::from_str(text).context("invalid policy file")?;
+        let mut rules = Vec::with_capacity(file.rules.len());
+        for (index, rule) in file.rules.into_iter().enumerate() {
+            let name = rule.name.unwrap_or_else(|| format!("rule #{}", index + 1));
+            let globs = |patterns: Option<Patterns>, literal_separator: bool| {
+                patterns
+                    .map(|p| compile(p.into_vec(), literal_separator))
+                    .transpose()
+                    .with_context(|| format!("in rule '{}'", name))
+            };
+            let command = rule
+                .command
+                .map(|p| CommandMatcher::compile(p.into_vec()))
+                .transpose()
+                .with_context(|| format!("in rule '{}'", name))?;
+            rules.push(Rule {
+                actions: rule.action.map(Patterns::into_vec),
+                command,
+                path: globs(rule.path, true)?,
+                agent: globs(rule.agent, false)?,
+                decision: rule.decision,
+                reason: rule.reason,
+                name,
+            });
+        }
+        Ok(Self {
+            default: file.default,
+            approval_timeout: Duration::from_secs(file.approval_timeout_secs),
+            rules,
+            source: None,
+            workspace: None,
+        })
+    }
+
+    /// Load a policy file from disk.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read policy file {}", path.display()))?;
+        let mut engine = Self::from_toml(&text)
+            .with_context(|| format!("failed to load policy file {}", path.display()))?;
+        engine.source = Some(path.to_path_buf());
+        Ok(engine)
+    }
+
+    /// Load the policy named by `GESTALT_POLICY`, else `.gestalt/policy.toml` if it
+    /// exists, else allow everything. The current directory is the workspace.
+    pub fn from_env() -> Result<Self> {
+        let engine = if let Ok(path) = std::env::var("GESTALT_POLICY") {
+            Self::load(Path::new(&path))?
+        } else {
+            let local = Path::new(".gestalt").join("policy.toml");
+            if local.is_file() {
+                Self::load(&local)?
+            } else {
+                Self::allow_all()
+            }
+        };
+        Ok(match std::env::current_dir() {
+            Ok(cwd) => engine.with_workspace(cwd),
+            Err(_) => engine,
+        })
+    }
+
+    /// Root that `path` rules are relative to; absolute paths below it are
+    /// matched relative to it.
+    pub fn with_workspace(mut self, root: impl Into<PathBuf>) -> Self {
+        self.workspace = Some(root.into());
+        self
+    }
+
+    /// Decide what to do with an action. The first matching rule wins.
+    pub fn evaluate(&self, facts: &ActionFacts) -> PolicyVerdict {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(facts, self.workspace.as_deref()))
+            .map(|rule| PolicyVerdict {
+                decision: rule.decision,
+                rule: Some(rule.name.clone()),
+                reason: rule.reason.clone(),
+            })
+            .unwrap_or(PolicyVerdict {
+                decision: self.default,
+                rule: None,
+                reason: None,
+            })
+    }
+
+    /// How long an action waits for approval before it counts as denied.
+    pub fn approval_timeout(&self) -> Duration {
+        self.approval_timeout
+    }
+
+    /// File the policy was loaded from, if any.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+default = "allow"
+approval_timeout_secs = 30
+
+[[rules]]
+name = "no-rm-rf"
+action = ["execute_shell", "start_job"]
+command = ["rm -rf*", "*&& rm -rf*"]
+decision = "deny"
+reason = "recursive delete"
+
+[[rules]]
+name = "secrets"
+path = "secrets/**"
+decision = "deny"
+
+[[rules]]
+name = "swarm-push"
+action = "git_push"
+agent = "swarm-*"
+decision = "require_approval"
+
+[[rules]]
+action = "call_agent"
+command = "kubectl *"
+decision = "approve"
+"#;
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let engine = PolicyEngine::from_toml(POLICY).unwrap();
+        assert_eq!(engine.approval_timeout(), Duration::from_secs(30));
+
+        let verdict = engine.evaluate(
+            &ActionFacts::new("execute_shell", "cli").with_command("cargo build && rm -rf /"),
+        );
+        assert_eq!(verdict.decision, PolicyDecision::Deny);
+        assert_eq!(verdict.rule.as_deref(), Some("no-rm-rf"));
+        assert_eq!(verdict.reason.as_deref(), Some("recursive delete"));
+
+        let verdict =
+            engine.evaluate(&ActionFacts::new("execute_shell", "cli").with_command("cargo test"));
+        assert_eq!(verdict.decision, PolicyDecision::Allow);
+        assert_eq!(verdict.rule, None);
+
+        let verdict = engine.evaluate(&ActionFacts::new("git_push", "swarm-7"));
+        assert_eq!(verdict.decision, PolicyDecision::RequireApproval);
+        assert_eq!(
+            engine
+                .evaluate(&ActionFacts::new("git_push", "cli"))
+                .decision,
+            PolicyDecision::Allow
+        );
+
+        let verdict = engine
+            .evaluate(&ActionFacts::new("call_agent", "cli").with_command("kubectl delete pod x"));
+        assert_eq!(verdict.decision, PolicyDecision::RequireApproval);
+        assert_eq!(verdict.rule.as_deref(), Some("rule #4"));
+    }
+
+    #[test]
+    fn path_globs_respect_separators() {
+        let engine = PolicyEngine::from_toml(
+            r#"
+default = "deny"
+
+[[rules]]
+action = "write_file"
+path = "src/*.rs"
+decision = "allow"
+"#,
+        )
+        .unwrap();
+
+        let write = |path: &str| {
+            engine
+                .evaluate(&ActionFacts::new("write_file", "a").with_path(path))
+                .decision
+        };
+        assert_eq!(write("./src/main.rs"), PolicyDecision::Allow);
+        assert_eq!(write("src/nested/mod.rs"), PolicyDecision::Deny);
+        assert_eq!(
+            engine
+                .evaluate(&ActionFacts::new("rename_path", "a").with_path("src/main.rs"))
+                .decision,
+            PolicyDecision::Deny
+        );
+
+        let secrets = PolicyEngine::from_toml(POLICY).unwrap();
+        let verdict = secrets.evaluate(
+            &ActionFacts::new("rename_path", "a")
+                .with_path("notes.txt")
+                .with_path("secrets/prod/key.pem"),
+        );
+        assert_eq!(verdict.rule.as_deref(), Some("secrets"));
+    }
+
+    #[test]
+    fn command_flags_match_in_any_order() {
+        let engine = PolicyEngine::from_toml(
+            r#"
+[[rules]]
+name = "no-rm-rf"
+command = "rm -rf*"
+decision = "deny"
+
+[[rules]]
+name = "exact"
+command = "git status"
+decision = "deny"
+"#,
+        )
+        .unwrap();
+        let decide = |cmd: &str| {
+            engine
+                .evaluate(&ActionFacts::new("execute_shell", "a").with_command(cmd))
+                .rule
+        };
+        for cmd in [
+            "rm -rf /",
+            "rm -fr /",
+            "rm -r -f /",
+            "rm -f -v -r build",
+            "/bin/rm -rvf .",
+            "LANG=C rm '-rf' x",
+            "cargo build;rm -fr target",
+            "echo $(rm -r -f ~)",
+            "ls | xargs rm -fr",
+            "sudo env -i rm -r -f /",
+        ] {
+            assert_eq!(decide(cmd).as_deref(), Some("no-rm-rf"), "{}", cmd);
+        }
+        for cmd in [
+            "rm -r build",
+            "rm -f a.txt",
+            "echo 'rm -fr /'",
+            "rmdir -p a",
+        ] {
+            assert_eq!(decide(cmd), None, "{}", cmd);
+        }
+        assert_eq!(decide("git  status").as_deref(), Some("exact"));
+        assert_eq!(decide("git status --short"), None);
+    }
+
+    #[test]
+    fn wrapped_commands_and_shell_scripts_are_checked() {
+        let engine = PolicyEngine::from_toml(
+            r#"
+[[rules]]
+name = "no-rm-rf"
+command = "rm -rf*"
+decision = "deny"
+"#,
+        )
+        .unwrap();
+        let decide = |cmd: &str| {
+            engine
+                .evaluate(&ActionFacts::new("execute_shell", "a").with_command(cmd))
+                .rule
+        };
+        for cmd in [
+            "sudo -u root rm -rf /",
+            "sudo -uroot -- rm -rf /",
+            "find . | xargs -n 1 rm -rf",
+            "xargs -I {} -P 4 rm -r -f {}",
+            "timeout -s KILL 10 nice -n 5 rm -rf build",
+            "bash -c \"rm -rf /\"",
+            "sh -ec 'rm -fr x'",
+            "bash -o pipefail -c 'ls && rm -rf /'",
+            "eval rm -rf /",
+            "sudo sh -c \"eval 'rm -r -f ~'\"",
+        ] {
+            assert_eq!(decide(cmd).as_deref(), Some("no-rm-rf"), "{}", cmd);
+        }
+        for cmd in [
+            "sudo -u rm ls -rf",
+            "xargs -n 1 echo rm -rf",
+            "bash script.sh rm -rf",
+            "sh -c 'echo rm -rf /'",
+        ] {
+            assert_eq!(decide(cmd), None, "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn absolute_paths_are_workspace_relative() {
+        let engine = PolicyEngine::from_toml(POLICY)
+            .unwrap()
+            .with_workspace("/repo");
+        let rule = |path: &str| {
+            engine
+                .evaluate(&ActionFacts::new("read_file", "a").with_path(path))
+                .rule
+        };
+        assert_eq!(rule("/repo/secrets/key").as_deref(), Some("secrets"));
+        assert_eq!(
+            rule("/repo/./src/../secrets/key").as_deref(),
+            Some("secrets")
+        );
+        assert_eq!(rule("src/../secrets/key").as_deref(), Some("secrets"));
+        assert_eq!(rule("/other/secrets/key"), None);
+        assert_eq!(rule("../repo2/secrets/key"), None);
+        assert_eq!(normalize_path("/repo", Some(Path::new("/repo"))), "");
+        assert_eq!(normalize_path("./a/./b", None), "a/b");
+        assert_eq!(normalize_path("../a", None), "../a");
+    }
+
+    #[test]
+    fn rejects_malformed_policies() {
+        assert!(PolicyEngine::from_toml("default = \"maybe\"").is_err());
+        assert!(PolicyEngine::from_toml("[[rules]]\naction = \"x\"").is_err());
+        assert!(PolicyEngine::from_toml("[[rules]]\npath = \"[\"\ndecision = \"deny\"").is_err());
+        assert!(PolicyEngine::from_toml("[[rules]]\nverb = \"x\"\ndecision = \"deny\"").is_err());
+        assert_eq!(
+            PolicyEngine::deny_all()
+                .evaluate(&ActionFacts::new("chat", "a"))
+                .decision,
+            PolicyDecision::Deny
+        );
+    }
+}
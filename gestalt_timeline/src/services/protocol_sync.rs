@@ -1,14 +1,20 @@
 //! Protocol Synchronization Service
 //!
 //! Synchronizes SurrealDB states with local markdown files (e.g. TASK.md).
+//!
+//! An optional `Depends On` column lists the IDs of the rows a task waits for
+//! (e.g. `F1-01, F1-02`); they become the task's `depends_on` edges.
 
 use crate::db::SurrealClient;
 use crate::models::EventType;
 use crate::models::{Task, TaskStatus};
+use crate::services::task_graph::TaskGraph;
 use crate::services::TimelineService;
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::path::Path;
 use tokio::fs;
+use tracing::warn;
 
 pub struct ProtocolSyncService {
     db: SurrealClient,
@@ -44,7 +50,13 @@ impl ProtocolSyncService {
             .map(|t| t.to_string())
             .unwrap_or_default();
 
-        for mut markdown_task in tasks {
+        // Dependencies by external ID, for rows of a table with a dependency column
+        let mut declared_deps: HashMap<String, Vec<String>> = HashMap::new();
+
+        for (mut markdown_task, deps) in tasks {
+            if let (Some(ext_id), Some(deps)) = (&markdown_task.external_id, deps) {
+                declared_deps.insert(ext_id.clone(), deps);
+            }
             markdown_task.project_id = project_id_str.clone();
             markdown_task.created_by = agent_id.to_string();
 
@@ -75,6 +87,11 @@ impl ProtocolSyncService {
                         _ => task_id.to_string(),
                     };
 
+                    // Keep what the file does not describe
+                    markdown_task.depends_on = existing_task.depends_on.clone();
+                    markdown_task.scheduled_for = existing_task.scheduled_for.clone();
+                    markdown_task.cron = existing_task.cron.clone();
+
                     self.db
                         .update("tasks", &task_id_str, &markdown_task)
                         .await?;
@@ -106,6 +123,72 @@ impl ProtocolSyncService {
             }
         }
 
+        if !declared_deps.is_empty() {
+            self.sync_dependencies(&project_id_str, &declared_deps)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Point `depends_on` of the project's tasks at the rows the file declares,
+    /// refusing to save a dependency cycle.
+    async fn sync_dependencies(
+        &self,
+        project_id: &str,
+        declared: &HashMap<String, Vec<String>>,
+    ) -> Result<()> {
+        let query = "SELECT * FROM tasks WHERE project_id = $proj_id";
+        let mut db_tasks: Vec<Task> = self.db.query_with(query, ("proj_id", project_id)).await?;
+        let by_external: HashMap<String, String> = db_tasks
+            .iter()
+            .filter_map(|t| Some((t.external_id.clone()?, t.id.as_ref()?.id.to_raw())))
+            .collect();
+
+        let mut changed = Vec::new();
+        for task in &mut db_tasks {
+            let Some(deps) = task.external_id.as_ref().and_then(|e| declared.get(e)) else {
+                continue;
+            };
+            let resolved: Vec<String> = deps
+                .iter()
+                .filter_map(|dep| {
+                    let id = by_external.get(dep).cloned();
+                    if id.is_none() {
+                        warn!(
+                            "Unknown dependency '{}' of task {:?}",
+                            dep, task.external_id
+                        );
+                    }
+                    id
+                })
+                .collect();
+            if resolved != task.depends_on {
+                task.depends_on = resolved;
+                if let Some(id) = &task.id {
+                    changed.push((id.id.to_raw(), task.depends_on.clone()));
+                }
+            }
+        }
+
+        let graph = TaskGraph::new(&db_tasks);
+        if let Some(cycle) = graph.find_cycle() {
+            let labels: Vec<&str> = cycle
+                .iter()
+                .map(|id| graph.get(id).map_or(id.as_str(), |n| n.label.as_str()))
+                .collect();
+            anyhow::bail!("Dependency cycle in markdown tasks: {}", labels.join(" → "));
+        }
+
+        for (id, depends_on) in changed {
+            let _: Vec<Task> = self
+                .db
+                .query_with(
+                    "UPDATE type::thing('tasks', $id) SET depends_on = $depends_on",
+                    serde_json::json!({ "id": id, "depends_on": depends_on }),
+                )
+                .await?;
+        }
         Ok(())
     }
 
@@ -142,7 +225,9 @@ impl ProtocolSyncService {
         Ok(())
     }
 
-    fn parse_tasks_from_markdown(&self, content: &str) -> Vec<Task> {
+    /// Tasks in the file's tables, with their declared dependencies when the
+    /// table has a dependency column.
+    fn parse_tasks_from_markdown(&self, content: &str) -> Vec<(Task, Option<Vec<String>>)> {
         let mut tasks = Vec::new();
         let mut in_table = false;
         let mut headers = Vec::new();
@@ -150,13 +235,9 @@ impl ProtocolSyncService {
         for line in content.lines() {
             let trimmed = line.trim();
             if trimmed.starts_with('|') {
-                let parts: Vec<String> = trimmed
-                    .split('|')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+                let parts = table_cells(trimmed);
 
-                if parts.is_empty() {
+                if parts.iter().all(|p| p.is_empty()) {
                     continue;
                 }
 
@@ -179,6 +260,7 @@ impl ProtocolSyncService {
                 let mut external_id = None;
                 let mut status = TaskStatus::Pending;
                 let mut description = String::new();
+                let mut depends_on = None;
 
                 for (i, part) in parts.iter().enumerate() {
                     if i >= headers.len() {
@@ -212,6 +294,11 @@ impl ProtocolSyncService {
                         };
                     } else if header == "task" || header == "description" {
                         description = part.clone();
+                    } else if matches!(
+                        header.as_str(),
+                        "depends on" | "depends" | "dependencies" | "deps"
+                    ) {
+                        depends_on = Some(parse_dependency_cell(part));
                     }
                 }
 
@@ -220,7 +307,7 @@ impl ProtocolSyncService {
                         let mut t = Task::new("", &description, "", Some(id.clone()));
                         t.status = status;
                         t.external_id = Some(id); // Ensure external_id is set
-                        tasks.push(t);
+                        tasks.push((t, depends_on));
                     }
                 }
             } else {
@@ -238,13 +325,9 @@ impl ProtocolSyncService {
         for line_slot in &mut lines {
             let line = line_slot.trim();
             if line.starts_with('|') {
-                let parts: Vec<String> = line
-                    .split('|')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+                let parts = table_cells(line);
 
-                if parts.is_empty() {
+                if parts.iter().all(|p| p.is_empty()) {
                     continue;
                 }
 
@@ -317,3 +400,56 @@ impl ProtocolSyncService {
         lines.join("\n")
     }
 }
+
+/// Cells of a markdown table row, keeping empty cells so columns stay aligned.
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|').map(|s| s.trim().to_string()).collect()
+}
+
+/// IDs in a dependency cell such as `F1-01, F1-02` or `F1-01 → F1-02`.
+fn parse_dependency_cell(cell: &str) -> Vec<String> {
+    cell.replace("→", ",")
+        .replace("->", ",")
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && *id != "-" && *id != "—")
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_dependency_column() -> Result<()> {
+        let db = SurrealClient::connect_mem().await?;
+        let service = ProtocolSyncService::new(db.clone(), TimelineService::new(db));
+        let content = "\
+| ID | Task | Depends On | Status |
+|----|------|------------|--------|
+| F1-01 | Scaffold |  | ✅ Done |
+| F1-02 | Parser | F1-01 | ⏳ Pending |
+| F1-03 | CLI | F1-01, F1-02 | 🔄 Running |
+";
+        let tasks = service.parse_tasks_from_markdown(content);
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].0.status, TaskStatus::Completed);
+        assert_eq!(tasks[0].1, Some(vec![]));
+        assert_eq!(tasks[1].0.description, "Parser");
+        assert_eq!(tasks[1].1, Some(vec!["F1-01".to_string()]));
+        assert_eq!(tasks[2].0.status, TaskStatus::Running);
+        assert_eq!(
+            tasks[2].1,
+            Some(vec!["F1-01".to_string(), "F1-02".to_string()])
+        );
+        assert_eq!(
+            parse_dependency_cell("F1-01 → F1-02"),
+            vec!["F1-01", "F1-02"]
+        );
+        Ok(())
+    }
+}
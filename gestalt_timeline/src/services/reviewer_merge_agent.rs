@@ -7,6 +7,8 @@ use synapse_agentic::prelude::{async_trait, Agent, AgentHandle, Hive};
 pub enum ReviewerMessage {
     ReviewAndMerge {
         goal: String,
+        /// Unified diff of the delegated agent's layer, when merging one.
+        diff: Option<String>,
         reply: oneshot::Sender<ReviewResult>,
     },
 }
@@ -30,20 +32,73 @@ impl Agent for ReviewerMergeAgent {
 
     async fn handle(&mut self, message: Self::Input) -> Result<()> {
         match message {
-            ReviewerMessage::ReviewAndMerge { goal, reply } => {
-                let approved = !goal.trim().is_empty();
-                let summary = if approved {
-                    format!("Review completed for goal '{}'. Merge approved.", goal)
-                } else {
-                    "Review failed: empty goal.".to_string()
-                };
-                let _ = reply.send(ReviewResult { approved, summary });
+            ReviewerMessage::ReviewAndMerge { goal, diff, reply } => {
+                let _ = reply.send(review(&goal, diff.as_deref()));
             }
         }
         Ok(())
     }
 }
 
+fn review(goal: &str, diff: Option<&str>) -> ReviewResult {
+    let rejected = |summary: String| ReviewResult {
+        approved: false,
+        summary,
+    };
+    if goal.trim().is_empty() {
+        return rejected("Review failed: empty goal.".to_string());
+    }
+    let Some(diff) = diff else {
+        return ReviewResult {
+            approved: true,
+            summary: format!("Review completed for goal '{}'. Merge approved.", goal),
+        };
+    };
+    if diff.trim().is_empty() {
+        return rejected(format!(
+            "Review failed for goal '{}': the layer has no changes.",
+            goal
+        ));
+    }
+    if diff.lines().any(|line| line.starts_with("+<<<<<<<")) {
+        return rejected(format!(
+            "Review failed for goal '{}': the diff contains conflict markers.",
+            goal
+        ));
+    }
+    let files = diff
+        .lines()
+        .filter(|line| line.starts_with("diff --git"))
+        .count();
+    ReviewResult {
+        approved: true,
+        summary: format!(
+            "Review completed for goal '{}' ({} file(s) changed). Merge approved.",
+            goal, files
+        ),
+    }
+}
+
 pub fn spawn_reviewer_agent(hive: &mut Hive) -> AgentHandle<ReviewerMessage> {
     hive.spawn(ReviewerMergeAgent)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn review_rejects_empty_or_conflicted_diffs() {
+        assert!(review("ship it", None).approved);
+        assert!(!review("  ", None).approved);
+        assert!(!review("ship it", Some("")).approved);
+
+        let conflicted = "diff --git a/a.rs b/a.rs\n+<<<<<<< ours\n+x\n";
+        assert!(!review("ship it", Some(conflicted)).approved);
+
+        let clean = "diff --git a/a.rs b/a.rs\n+x\ndiff --git a/b.rs b/b.rs\n-y\n";
+        let result = review("ship it", Some(clean));
+        assert!(result.approved);
+        assert!(result.summary.contains("2 file(s)"));
+    }
+}
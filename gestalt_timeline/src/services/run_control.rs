@@ -0,0 +1,294 @@
+//! Run Control - cooperative cancel, pause and resume of agent loops.
+//!
+//! Every `AgentRuntime` carries a `RunControl`: a cancellation token plus a
+//! pause flag that the loop checks between steps and races against engine
+//! calls and actions. Parent agents hold the controls of their sub-agents.
+//! The CLI, HTTP server and Telegram bot run in other processes, so they file
+//! a request in the `run_controls` table instead, keyed by task or agent ID,
+//! which a running loop polls and applies to its own control.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::db::SurrealClient;
+
+const TABLE: &str = "run_controls";
+
+/// What a running agent loop is asked to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlSignal {
+    Run,
+    Pause,
+    Cancel,
+}
+
+impl fmt::Display for ControlSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlSignal::Run => write!(f, "run"),
+            ControlSignal::Pause => write!(f, "pause"),
+            ControlSignal::Cancel => write!(f, "cancel"),
+        }
+    }
+}
+
+/// Error a loop stops with once it was cancelled; detect it with
+/// `err.is::<RunCancelled>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunCancelled;
+
+impl fmt::Display for RunCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "run cancelled")
+    }
+}
+
+impl std::error::Error for RunCancelled {}
+
+/// Cancellation token and pause flag shared by a runtime and whoever controls it.
+/// Cancelling is final until `reset`: a cancelled control can no longer be
+/// paused or resumed.
+#[derive(Clone)]
+pub struct RunControl {
+    signal: Arc<watch::Sender<ControlSignal>>,
+}
+
+impl Default for RunControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunControl {
+    pub fn new() -> Self {
+        Self {
+            signal: Arc::new(watch::Sender::new(ControlSignal::Run)),
+        }
+    }
+
+    pub fn signal(&self) -> ControlSignal {
+        *self.signal.borrow()
+    }
+
+    /// Apply `signal`; returns whether it changed anything.
+    pub fn set(&self, signal: ControlSignal) -> bool {
+        self.signal.send_if_modified(|current| {
+            if *current == signal || *current == ControlSignal::Cancel {
+                return false;
+            }
+            *current = signal;
+            true
+        })
+    }
+
+    pub fn cancel(&self) -> bool {
+        self.set(ControlSignal::Cancel)
+    }
+
+    pub fn pause(&self) -> bool {
+        self.set(ControlSignal::Pause)
+    }
+
+    pub fn resume(&self) -> bool {
+        self.set(ControlSignal::Run)
+    }
+
+    /// Clear a cancel or pause, e.g. to reuse a runtime after its run stopped.
+    pub fn reset(&self) {
+        self.signal.send_replace(ControlSignal::Run);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.signal() == ControlSignal::Cancel
+    }
+
+    /// Resolves once the control is cancelled.
+    pub async fn cancelled(&self) {
+        let mut rx = self.signal.subscribe();
+        let _ = rx.wait_for(|s| *s == ControlSignal::Cancel).await;
+    }
+
+    /// Wait while paused; returns `Run` once resumed, or `Cancel`.
+    pub async fn wait_while_paused(&self) -> ControlSignal {
+        let mut rx = self.signal.subscribe();
+        let resumed = rx
+            .wait_for(|s| *s != ControlSignal::Pause)
+            .await
+            .map(|signal| *signal);
+        resumed.unwrap_or_else(|_| self.signal())
+    }
+
+    /// Run `fut` unless the control is cancelled first, in which case `fut`
+    /// is dropped and the result is a `RunCancelled` error.
+    pub async fn interruptible<T>(
+        &self,
+        fut: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        tokio::select! {
+            result = fut => result,
+            _ = self.cancelled() => Err(RunCancelled.into()),
+        }
+    }
+}
+
+/// A control request filed for a task or agent from outside its process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlRequest {
+    /// Task ID (without the `tasks:` prefix) or agent ID
+    pub target: String,
+    pub signal: ControlSignal,
+    pub requested_by: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Stores control requests and relays them to running loops.
+#[derive(Clone)]
+pub struct RunControlService {
+    db: SurrealClient,
+    poll_interval: Duration,
+}
+
+impl RunControlService {
+    pub fn new(db: SurrealClient) -> Self {
+        Self {
+            db,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Ask whatever loop runs `target` to `signal`; replaces any earlier request.
+    pub async fn request(
+        &self,
+        target: &str,
+        signal: ControlSignal,
+        requested_by: &str,
+    ) -> Result<ControlRequest> {
+        let request = ControlRequest {
+            target: target.to_string(),
+            signal,
+            requested_by: requested_by.to_string(),
+            requested_at: Utc::now(),
+        };
+        let saved: ControlRequest = self.db.upsert(TABLE, target, &request).await?;
+        info!(
+            "Run control: {} requested for {} by {}",
+            signal, target, requested_by
+        );
+        Ok(saved)
+    }
+
+    /// The latest request filed for `target`, if any.
+    pub async fn get(&self, target: &str) -> Result<Option<ControlRequest>> {
+        self.db.select_by_id(TABLE, target).await
+    }
+
+    /// Forget the requests for `targets`, e.g. once their loop finished.
+    pub async fn clear(&self, targets: &[String]) -> Result<()> {
+        for target in targets {
+            self.db.delete(TABLE, target).await?;
+        }
+        Ok(())
+    }
+
+    /// Poll for requests filed for any of `targets` and apply them to
+    /// `control` until the returned handle is aborted. Requests filed before
+    /// the call are applied too, so a run paused before it started stays paused.
+    pub fn follow(&self, targets: Vec<String>, control: RunControl) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut seen: HashMap<String, DateTime<Utc>> = HashMap::new();
+            loop {
+                for target in &targets {
+                    match service.get(target).await {
+                        Ok(Some(request))
+                            if seen.get(target).is_none_or(|at| request.requested_at > *at) =>
+                        {
+                            seen.insert(target.clone(), request.requested_at);
+                            if control.set(request.signal) {
+                                debug!(
+                                    "Applied {} from {} to {}",
+                                    request.signal, request.requested_by, target
+                                );
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("Failed to read run control of {}: {}", target, e),
+                    }
+                }
+                if control.is_cancelled() {
+                    break;
+                }
+                tokio::time::sleep(service.poll_interval).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pause_resume_and_final_cancel() {
+        let control = RunControl::new();
+        assert!(control.pause());
+        assert!(!control.pause());
+
+        let waiter = tokio::spawn({
+            let control = control.clone();
+            async move { control.wait_while_paused().await }
+        });
+        assert!(control.resume());
+        assert_eq!(waiter.await.unwrap(), ControlSignal::Run);
+
+        assert!(control.cancel());
+        assert!(!control.resume());
+        assert!(control.is_cancelled());
+        let interrupted = control
+            .interruptible(std::future::pending::<Result<()>>())
+            .await;
+        assert!(interrupted.unwrap_err().is::<RunCancelled>());
+    }
+
+    #[tokio::test]
+    async fn test_follow_applies_stored_requests() -> Result<()> {
+        let mut service = RunControlService::new(SurrealClient::connect_mem().await?);
+        service.poll_interval = Duration::from_millis(10);
+        let control = RunControl::new();
+
+        service
+            .request("task-1", ControlSignal::Pause, "tester")
+            .await?;
+        let follower = service.follow(
+            vec!["agent-1".to_string(), "task-1".to_string()],
+            control.clone(),
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while control.signal() != ControlSignal::Pause {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await?;
+
+        service
+            .request("agent-1", ControlSignal::Cancel, "tester")
+            .await?;
+        tokio::time::timeout(Duration::from_secs(5), control.cancelled()).await?;
+        tokio::time::timeout(Duration::from_secs(5), follower).await??;
+
+        service
+            .clear(&["agent-1".to_string(), "task-1".to_string()])
+            .await?;
+        assert!(service.get("task-1").await?.is_none());
+        Ok(())
+    }
+}
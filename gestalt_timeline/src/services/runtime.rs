@@ -1,19 +1,27 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::{oneshot, Mutex};
 use tracing::{info, warn};
 
-use crate::models::{AgentRuntimeState, EventType, RuntimePhase, TimelineEvent};
+use crate::db::SurrealClient;
+use crate::models::{
+    AgentRuntimeState, EventType, ExecutionMetrics, FlexibleTimestamp, RuntimePhase, TimelineEvent,
+};
+use crate::services::task_graph::task_key;
 use crate::services::{
-    spawn_reviewer_agent, AgentService, ContextCompactor, FileManager, LockStatus, MemoryService,
-    ProjectService, ReviewerMessage, TaskService, TimelineService, VirtualFs, WatchService,
+    default_sandbox_or_disabled, spawn_reviewer_agent, ActionFacts, AgentService, ApprovalService,
+    ApprovalStatus, Budget, CommandSandbox, ContextCompactor, ControlSignal, FileManager,
+    FlushReport, LayerFs, LockStatus, MemoryService, PolicyDecision, PolicyEngine, ProjectService,
+    ReviewerMessage, RunCancelled, RunControl, RunControlService, RunUsage, SandboxOutput,
+    SandboxPolicy, SandboxRequest, SandboxedProcess, TaskService, TimelineService, UsageService,
+    VirtualFs, WatchService,
 };
 use synapse_agentic::prelude::{
-    CompactionConfig, Decision, DecisionContext, DecisionEngine, EmptyContext, Hive, Message,
-    MessageRole, SessionContext, ToolRegistry,
+    ChatRequest, CompactionConfig, DecisionEngine, EmptyContext, Hive, Message, MessageRole,
+    SessionContext, ToolCall, ToolRegistry, ToolSchema, Usage,
 };
 
 /// Orchestration action executed by AgentRuntime.
@@ -47,7 +55,24 @@ pub enum OrchestrationAction {
         path: String,
         content: String,
     },
+    RemoveFile {
+        path: String,
+    },
+    RemoveDir {
+        path: String,
+    },
+    RenamePath {
+        from: String,
+        to: String,
+    },
     FlushVfs,
+    SnapshotVfs {
+        name: String,
+    },
+    RollbackVfs {
+        name: String,
+    },
+    DiffVfs,
     ExecuteShell {
         command: String,
     },
@@ -92,9 +117,103 @@ pub enum OrchestrationAction {
     AwaitJob {
         job_id: String,
     },
+    /// Review a delegated agent's layer (or just the goal when `agent` is unset)
+    /// and promote the layer into ours if approved.
     ReviewAndMerge {
         goal: String,
+        agent: Option<String>,
     },
+    /// Pause, resume or cancel a delegated sub-agent.
+    ControlAgent {
+        agent: String,
+        signal: ControlSignal,
+    },
+}
+
+impl OrchestrationAction {
+    /// Snake-case name of the action, as used by policy rules.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OrchestrationAction::CreateProject { .. } => "create_project",
+            OrchestrationAction::CreateTask { .. } => "create_task",
+            OrchestrationAction::RunTask { .. } => "run_task",
+            OrchestrationAction::ListProjects => "list_projects",
+            OrchestrationAction::ListTasks { .. } => "list_tasks",
+            OrchestrationAction::GetStatus { .. } => "get_status",
+            OrchestrationAction::Chat { .. } => "chat",
+            OrchestrationAction::ReadFile { .. } => "read_file",
+            OrchestrationAction::WriteFile { .. } => "write_file",
+            OrchestrationAction::RemoveFile { .. } => "remove_file",
+            OrchestrationAction::RemoveDir { .. } => "remove_dir",
+            OrchestrationAction::RenamePath { .. } => "rename_path",
+            OrchestrationAction::FlushVfs => "flush_vfs",
+            OrchestrationAction::SnapshotVfs { .. } => "snapshot_vfs",
+            OrchestrationAction::RollbackVfs { .. } => "rollback_vfs",
+            OrchestrationAction::DiffVfs => "diff_vfs",
+            OrchestrationAction::ExecuteShell { .. } => "execute_shell",
+            OrchestrationAction::GitStatus => "git_status",
+            OrchestrationAction::GitLog { .. } => "git_log",
+            OrchestrationAction::GitBranchList => "git_branch_list",
+            OrchestrationAction::GitBranchCreate { .. } => "git_branch_create",
+            OrchestrationAction::GitCheckout { .. } => "git_checkout",
+            OrchestrationAction::GitAdd { .. } => "git_add",
+            OrchestrationAction::GitCommit { .. } => "git_commit",
+            OrchestrationAction::GitPush { .. } => "git_push",
+            OrchestrationAction::StartJob { .. } => "start_job",
+            OrchestrationAction::StopJob { .. } => "stop_job",
+            OrchestrationAction::ListJobs => "list_jobs",
+            OrchestrationAction::DelegateTask { .. } => "delegate_task",
+            OrchestrationAction::CallAgent { .. } => "call_agent",
+            OrchestrationAction::AwaitJob { .. } => "await_job",
+            OrchestrationAction::ReviewAndMerge { .. } => "review_and_merge",
+            OrchestrationAction::ControlAgent { signal, .. } => match signal {
+                ControlSignal::Run => "resume_agent",
+                ControlSignal::Pause => "pause_agent",
+                ControlSignal::Cancel => "cancel_agent",
+            },
+        }
+    }
+
+    /// What the policy engine gets to match on: the command line of actions
+    /// that run one and the paths of actions that touch files.
+    pub fn policy_facts(&self, agent_id: &str) -> ActionFacts {
+        let facts = ActionFacts::new(self.kind(), agent_id);
+        match self {
+            OrchestrationAction::ExecuteShell { command }
+            | OrchestrationAction::StartJob { command, .. } => facts.with_command(command.clone()),
+            OrchestrationAction::CallAgent { tool, args } => {
+                let args = match args {
+                    Value::Null => String::new(),
+                    Value::String(s) => s.clone(),
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|v| {
+                            v.as_str()
+                                .map(ToOwned::to_owned)
+                                .unwrap_or_else(|| v.to_string())
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    other => other.to_string(),
+                };
+                facts.with_command(format!("{} {}", tool, args).trim_end().to_string())
+            }
+            OrchestrationAction::GitPush { remote, branch } => {
+                facts.with_command(format!("git push {} {}", remote, branch))
+            }
+            OrchestrationAction::ReadFile { path }
+            | OrchestrationAction::WriteFile { path, .. }
+            | OrchestrationAction::RemoveFile { path }
+            | OrchestrationAction::RemoveDir { path } => facts.with_path(path.clone()),
+            OrchestrationAction::RenamePath { from, to } => {
+                facts.with_path(from.clone()).with_path(to.clone())
+            }
+            OrchestrationAction::GitAdd { paths } => paths
+                .iter()
+                .fold(facts, |facts, path| facts.with_path(path.clone())),
+            _ => facts,
+        }
+    }
 }
 
 /// The Autonomous Agent Runtime.
@@ -114,11 +233,206 @@ pub struct AgentRuntime {
     agent: AgentService,
     hard_step_cap: Option<usize>,
     max_retries: usize,
-    jobs: Arc<Mutex<HashMap<String, tokio::process::Child>>>,
+    jobs: Arc<Mutex<HashMap<String, SandboxedProcess>>>,
+    /// Runs `execute_shell` and `start_job` commands against `vfs`.
+    sandbox: Arc<dyn CommandSandbox>,
+    /// Rules consulted before every action
+    policy: Arc<PolicyEngine>,
+    approvals: ApprovalService,
     vfs: Arc<dyn VirtualFs>,
+    /// Set when `vfs` is a copy-on-write layer over the parent agent's VFS.
+    layer: Option<Arc<LayerFs>>,
+    /// Layers of delegated sub-agents, by sub-agent ID, awaiting review.
+    child_layers: Arc<Mutex<HashMap<String, Arc<LayerFs>>>>,
+    /// Cancellation token and pause flag checked by `run_loop`
+    control: RunControl,
+    /// Control requests filed from the CLI, HTTP server or Telegram
+    controls: RunControlService,
+    /// Controls of delegated sub-agents, by sub-agent ID
+    child_controls: Arc<Mutex<HashMap<String, RunControl>>>,
+    /// Paths this agent locked in the VFS, persisted so a resume can re-acquire them
+    held_locks: Arc<Mutex<BTreeSet<String>>>,
+    /// Latest summary written by the compactor, persisted for resumes
+    summary: Arc<Mutex<Option<String>>>,
+    /// Model whose tokenizer and context window size the session's token budget
+    model: String,
     compactor: ContextCompactor,
     hive: Arc<Mutex<Hive>>,
     session: Arc<Mutex<SessionContext>>,
+    usage: UsageService,
+    /// Spending limits checked after every engine call
+    budget: Budget,
+    /// Tokens and spend of the run in progress
+    run_usage: Arc<Mutex<RunUsage>>,
+}
+
+/// Tool parameters as `(name, type)` pairs; a trailing `?` marks an optional one.
+type ToolParams = &'static [(&'static str, &'static str)];
+
+/// Built-in actions offered to the model as tools, understood by
+/// `action_from_call`: name, description and parameters.
+const BUILTIN_TOOLS: &[(&str, &str, ToolParams)] = &[
+    (
+        "create_project",
+        "Create a new project.",
+        &[("name", "string"), ("description?", "string")],
+    ),
+    (
+        "create_task",
+        "Add a task to a project.",
+        &[("project", "string"), ("description", "string")],
+    ),
+    (
+        "run_task",
+        "Run an existing task.",
+        &[("task_id", "string")],
+    ),
+    ("list_projects", "List all projects.", &[]),
+    (
+        "read_file",
+        "Read a file from the workspace.",
+        &[("path", "string")],
+    ),
+    (
+        "write_file",
+        "Write a file in the workspace, replacing its content.",
+        &[("path", "string"), ("content", "string")],
+    ),
+    ("remove_file", "Delete a file.", &[("path", "string")]),
+    (
+        "remove_dir",
+        "Delete a directory and its content.",
+        &[("path", "string")],
+    ),
+    (
+        "rename",
+        "Move or rename a file or directory.",
+        &[("from", "string"), ("to", "string")],
+    ),
+    ("diff_vfs", "Show pending workspace changes.", &[]),
+    ("flush_vfs", "Write pending workspace changes to disk.", &[]),
+    (
+        "snapshot_vfs",
+        "Snapshot the workspace under a name.",
+        &[("name", "string")],
+    ),
+    (
+        "rollback_vfs",
+        "Roll the workspace back to a snapshot.",
+        &[("name", "string")],
+    ),
+    (
+        "execute_shell",
+        "Run a shell command in the sandbox.",
+        &[("command", "string")],
+    ),
+    ("git_status", "Show the git working tree status.", &[]),
+    ("git_log", "Show recent commits.", &[("count?", "integer")]),
+    ("git_branch_list", "List git branches.", &[]),
+    (
+        "git_branch_create",
+        "Create a git branch.",
+        &[("name", "string"), ("checkout?", "boolean")],
+    ),
+    (
+        "git_checkout",
+        "Check out a git branch.",
+        &[("name", "string")],
+    ),
+    (
+        "git_add",
+        "Stage paths for commit.",
+        &[("paths", "string[]")],
+    ),
+    (
+        "git_commit",
+        "Commit staged changes.",
+        &[("message", "string")],
+    ),
+    (
+        "git_push",
+        "Push a branch to a remote.",
+        &[("remote?", "string"), ("branch?", "string")],
+    ),
+    (
+        "start_job",
+        "Start a long-running background command.",
+        &[("name", "string"), ("command", "string")],
+    ),
+    ("stop_job", "Stop a background job.", &[("name", "string")]),
+    (
+        "await_job",
+        "Wait for a background job to finish.",
+        &[("job_id", "string")],
+    ),
+    ("list_jobs", "List background jobs.", &[]),
+    (
+        "delegate",
+        "Hand a sub-goal to a new sub-agent working in its own layer.",
+        &[("agent", "string"), ("goal", "string")],
+    ),
+    (
+        "review_merge",
+        "Review a sub-agent's changes and merge them if approved.",
+        &[("agent?", "string"), ("goal?", "string")],
+    ),
+    (
+        "pause_agent",
+        "Pause a delegated sub-agent between steps.",
+        &[("agent", "string")],
+    ),
+    (
+        "resume_agent",
+        "Resume a paused sub-agent.",
+        &[("agent", "string")],
+    ),
+    (
+        "cancel_agent",
+        "Stop a delegated sub-agent and kill its jobs.",
+        &[("agent", "string")],
+    ),
+];
+
+/// JSON Schemas for [`BUILTIN_TOOLS`].
+fn builtin_tools() -> Vec<ToolSchema> {
+    BUILTIN_TOOLS
+        .iter()
+        .map(|(name, description, params)| {
+            let mut properties = serde_json::Map::new();
+            let mut required = Vec::new();
+            for (param, kind) in params.iter() {
+                let (param, optional) = match param.strip_suffix('?') {
+                    Some(param) => (param, true),
+                    None => (*param, false),
+                };
+                let schema = match *kind {
+                    "string[]" => serde_json::json!({"type": "array", "items": {"type": "string"}}),
+                    kind => serde_json::json!({ "type": kind }),
+                };
+                properties.insert(param.to_string(), schema);
+                if !optional {
+                    required.push(param);
+                }
+            }
+            ToolSchema::new(
+                *name,
+                *description,
+                serde_json::json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                }),
+            )
+        })
+        .collect()
+}
+
+/// One reply from the engine, mapped onto actions.
+struct Turn {
+    /// Text that came with the reply; the final answer when `steps` is empty.
+    content: String,
+    /// Requested tool calls with the actions they map to, in order.
+    steps: Vec<(ToolCall, OrchestrationAction)>,
 }
 
 struct PersistStateInput<'a> {
@@ -139,11 +453,138 @@ struct ExecutionResult {
     is_success: bool,
 }
 
-fn spawn_sub_agent(sub_runtime: AgentRuntime, goal: String) {
+/// Observation for a sandboxed command, including resource usage and the
+/// workspace changes it left pending in the VFS.
+fn describe_command(label: &str, output: &SandboxOutput) -> String {
+    let mut observation = format!(
+        "{} (Exit: {})\nSTDOUT:\n{}\nSTDERR:\n{}",
+        label, output.exit_code, output.stdout, output.stderr
+    );
+    if output.timed_out {
+        observation.push_str("\nKilled: wall-clock timeout exceeded; changes were discarded.");
+    } else if output.killed {
+        observation.push_str("\nKilled on request; changes were discarded.");
+    } else if let Some(signal) = output.signal {
+        observation.push_str(&format!("\nTerminated by signal {}.", signal));
+    }
+    if output.truncated {
+        observation.push_str("\nOutput was truncated.");
+    }
+    observation.push_str(&format!(
+        "\nUsage: wall={}ms user={}ms sys={}ms max_rss={}KiB",
+        output.usage.wall_ms,
+        output.usage.user_ms,
+        output.usage.system_ms,
+        output.usage.max_rss_kib
+    ));
+    if !output.changed_files.is_empty() || !output.removed_files.is_empty() {
+        observation.push_str(&format!(
+            "\nPending VFS changes: {} written, {} removed (flush_vfs to persist).",
+            output.changed_files.len(),
+            output.removed_files.len()
+        ));
+    }
+    for error in &output.sync_errors {
+        observation.push_str(&format!("\nNot kept: {}", error));
+    }
+    observation
+}
+
+/// Observation for a flush or layer promotion, listing conflicting hunks.
+fn describe_flush(label: &str, report: &FlushReport) -> String {
+    let mut observation = format!(
+        "{} complete. dirs={}, files={}, removed={}, merged={}, errors={}",
+        label,
+        report.created_dirs.len(),
+        report.written_files.len(),
+        report.removed_paths.len(),
+        report.merged_files.len(),
+        report.errors.len()
+    );
+    for conflict in &report.conflicts {
+        observation.push_str(&format!(
+            "\nConflict in '{}' (nothing was flushed; write the file again to resolve):",
+            conflict.path.display()
+        ));
+        for hunk in &conflict.hunks {
+            observation.push_str(&format!(
+                "\n@ line {}\n<<<<<<< ours\n{}=======\n{}>>>>>>> disk",
+                hunk.base_start, hunk.ours, hunk.theirs
+            ));
+        }
+    }
+    for error in report.errors.iter().filter(|e| e.operation != "merge") {
+        observation.push_str(&format!(
+            "\n{} '{}' failed: {}",
+            error.operation,
+            error.path.display(),
+            error.error
+        ));
+    }
+    observation
+}
+
+/// Rebuild a session message from a `history_tail` line (`"{role:?}: {content}"`).
+/// Tool results lost the call they answered, so they come back as observations.
+fn restore_message(line: &str) -> Option<Message> {
+    let (role, content) = line.split_once(": ")?;
+    if content.trim().is_empty() {
+        return None;
+    }
+    let (role, content) = match role {
+        "System" => (MessageRole::System, content.to_string()),
+        "User" => (MessageRole::User, content.to_string()),
+        "Assistant" => (MessageRole::Assistant, content.to_string()),
+        "Tool" => (MessageRole::User, format!("Observation: {}", content)),
+        _ => return None,
+    };
+    Some(Message::new(role, content))
+}
+
+/// Session of a resumed loop: the goal and the compacted summary of earlier
+/// work, followed by the stored history tail.
+fn resume_messages(state: &AgentRuntimeState) -> Vec<Message> {
+    let mut intro = format!(
+        "GOAL: {}\n\nYour previous run was interrupted at step {}; continue working on this goal.",
+        state.goal, state.current_step
+    );
+    if let Some(summary) = &state.summary {
+        intro.push_str(&format!("\n\nSummary of earlier work:\n{}", summary));
+    }
+    let mut messages = vec![Message::new(MessageRole::User, intro)];
+    messages.extend(
+        state
+            .history_tail
+            .iter()
+            .map(String::as_str)
+            .filter_map(restore_message),
+    );
+    messages
+}
+
+/// Run a delegated sub-agent; once it finishes, its control and, unless it
+/// left changes to review, its layer are dropped from the parent's maps.
+fn spawn_sub_agent(
+    sub_runtime: AgentRuntime,
+    goal: String,
+    layers: Arc<Mutex<HashMap<String, Arc<LayerFs>>>>,
+    controls: Arc<Mutex<HashMap<String, RunControl>>>,
+) {
+    let sub_agent_id = sub_runtime.agent_id.clone();
+    let layer = sub_runtime.layer.clone();
     tokio::spawn(async move {
         if let Err(e) = sub_runtime.run_loop(&goal).await {
             tracing::error!("Sub-agent failed: {}", e);
         }
+        controls.lock().await.remove(&sub_agent_id);
+        let clean = match &layer {
+            Some(layer) => layer.is_clean().await,
+            None => true,
+        };
+        // Layers with changes stay until `ReviewAndMerge` promotes or rejects them.
+        if clean {
+            layers.lock().await.remove(&sub_agent_id);
+        }
     });
 }
 
@@ -169,10 +610,21 @@ impl AgentRuntime {
                 "gpt-4o".into(),
             ))
         });
+        // Providers report their model as their name; `with_model` corrects
+        // this for routers such as the StochasticRotator.
+        let model = compactor_provider.name().to_string();
 
-        let (vfs, actor) = FileManager::new();
+        let (vfs, actor) = FileManager::for_agent(&agent_id);
         tokio::spawn(actor.run());
 
+        let policy = PolicyEngine::from_env().unwrap_or_else(|e| {
+            warn!("Policy rejected, denying every action: {:#}", e);
+            PolicyEngine::deny_all()
+        });
+        let approvals = ApprovalService::new(watch.db(), timeline.clone());
+        let usage = UsageService::new(watch.db());
+        let controls = RunControlService::new(watch.db());
+
         Self {
             agent_id,
             task_id: None,
@@ -193,12 +645,26 @@ impl AgentRuntime {
                 .and_then(|v| v.parse::<usize>().ok())
                 .unwrap_or(3),
             jobs: Arc::new(Mutex::new(HashMap::new())),
+            sandbox: default_sandbox_or_disabled(SandboxPolicy::from_env()),
+            policy: Arc::new(policy),
+            approvals,
             vfs: Arc::new(vfs),
-            compactor: ContextCompactor::new(compactor_provider, "gpt-4o"),
+            layer: None,
+            child_layers: Arc::new(Mutex::new(HashMap::new())),
+            control: RunControl::new(),
+            controls,
+            child_controls: Arc::new(Mutex::new(HashMap::new())),
+            held_locks: Arc::new(Mutex::new(BTreeSet::new())),
+            summary: Arc::new(Mutex::new(None)),
+            compactor: ContextCompactor::new(compactor_provider, &model),
             hive: Arc::new(Mutex::new(Hive::new())),
             session: Arc::new(Mutex::new(SessionContext::new(
-                CompactionConfig::small_context(),
+                CompactionConfig::for_model(&model),
             ))),
+            model,
+            usage,
+            budget: Budget::from_env(),
+            run_usage: Arc::new(Mutex::new(RunUsage::default())),
         }
     }
 
@@ -214,14 +680,114 @@ impl AgentRuntime {
         self
     }
 
+    /// Work in a private copy-on-write layer over `parent` instead of a VFS of our own.
+    pub fn with_layer_over(mut self, parent: Arc<dyn VirtualFs>) -> Self {
+        let layer = Arc::new(LayerFs::new(self.agent_id.clone(), parent));
+        self.vfs = layer.clone();
+        self.layer = Some(layer);
+        self
+    }
+
+    /// Run shell commands and jobs in `sandbox` instead of the default one.
+    pub fn with_sandbox(mut self, sandbox: Arc<dyn CommandSandbox>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Judge actions with `policy` instead of the one loaded from the environment.
+    pub fn with_policy(mut self, policy: Arc<PolicyEngine>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Enforce `budget` instead of the limits loaded from the environment.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Size the session for `model`: count tokens with its tokenizer and
+    /// compact near its context window.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.compactor = self.compactor.with_model(model);
+        self.session = Arc::new(Mutex::new(SessionContext::new(
+            CompactionConfig::for_model(model),
+        )));
+        self.model = model.to_string();
+        self
+    }
+
+    /// Pause, resume or cancel this runtime through `control`.
+    pub fn with_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Handle to pause, resume or cancel this runtime's loop.
+    pub fn control(&self) -> RunControl {
+        self.control.clone()
+    }
+
+    /// Decision engine driving this runtime.
+    pub fn engine(&self) -> Arc<DecisionEngine> {
+        self.engine.clone()
+    }
+
+    /// Spending limits this runtime enforces.
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
     /// Override the loop hard cap for deterministic executions (e.g. tests).
     pub fn with_hard_step_cap(mut self, hard_step_cap: usize) -> Self {
         self.hard_step_cap = Some(hard_step_cap);
         self
     }
 
+    /// Runtime states of loops that were still running when their process
+    /// stopped; `resume` continues them.
+    pub async fn interrupted(db: &SurrealClient) -> Result<Vec<AgentRuntimeState>> {
+        db.query_with(
+            "SELECT * FROM agent_runtime_states WHERE phase = $phase",
+            ("phase", "running"),
+        )
+        .await
+    }
+
+    /// Continue the loop recorded in `agent_runtime_states` for this runtime's
+    /// agent ID, e.g. after a crash: the session is rebuilt from the stored
+    /// history and summary, VFS locks are re-acquired and the loop carries on
+    /// at the recorded step. The previous loop must no longer be running.
+    pub async fn resume(mut self) -> Result<()> {
+        let state: AgentRuntimeState = self
+            .watch
+            .db()
+            .select_by_id("agent_runtime_states", &self.agent_id)
+            .await?
+            .with_context(|| format!("No runtime state recorded for agent '{}'", self.agent_id))?;
+        if state.phase.is_finished() {
+            anyhow::bail!(
+                "Agent '{}' already stopped ({:?}); nothing to resume",
+                self.agent_id,
+                state.phase
+            );
+        }
+        if self.task_id.is_none() {
+            self.task_id = state.task_id.clone();
+        }
+        if self.hard_step_cap.is_none() && state.max_steps > 0 {
+            self.hard_step_cap = Some(state.max_steps);
+        }
+        let goal = state.goal.clone();
+        self.run(&goal, Some(state)).await
+    }
+
     /// Run the autonomous loop for a specific goal.
     pub async fn run_loop(&self, goal: &str) -> Result<()> {
+        self.run(goal, None).await
+    }
+
+    async fn run(&self, goal: &str, resumed: Option<AgentRuntimeState>) -> Result<()> {
         info!("Starting Autonomous Loop for Agent: {}", self.agent_id);
         info!("Goal: {}", goal);
 
@@ -235,29 +801,72 @@ impl AgentRuntime {
 
         {
             let mut session = self.session.lock().await;
-            session.add_message(Message::new(
-                MessageRole::User,
-                format!("GOAL: {}\n\nPlease start working on this goal.", goal),
-            ));
+            match &resumed {
+                Some(state) => {
+                    for message in resume_messages(state) {
+                        session.add_message(message);
+                    }
+                }
+                None => session.add_message(Message::new(
+                    MessageRole::User,
+                    format!("GOAL: {}\n\nPlease start working on this goal.", goal),
+                )),
+            }
         }
 
-        let started_at = crate::models::FlexibleTimestamp::now();
+        // A resumed run carries on from what it had spent when it stopped.
+        *self.run_usage.lock().await = resumed.as_ref().map_or_else(RunUsage::default, |state| {
+            RunUsage::from_providers(&state.llm_usage)
+        });
+
+        let started_at = resumed
+            .as_ref()
+            .map_or_else(FlexibleTimestamp::now, |state| state.started_at.clone());
+        let first_step = resumed.as_ref().map_or(0, |state| state.current_step);
+        if let Some(state) = &resumed {
+            info!(
+                "Resuming agent {} at step {}",
+                self.agent_id, state.current_step
+            );
+            *self.summary.lock().await = state.summary.clone();
+            self.reacquire_locks(&state.locked_paths).await;
+        }
         self.persist_state(PersistStateInput {
             goal,
-            step: 0,
+            step: first_step,
             phase: RuntimePhase::Running,
             last_action: None,
-            last_observation: None,
+            last_observation: resumed
+                .is_some()
+                .then_some("Resumed after an interruption."),
             history: self.get_history_strings().await,
             started_at: started_at.clone(),
             finished_at: None,
             error: None,
         })
         .await?;
+        if resumed.is_some() {
+            self.emit_run_event(EventType::RunResumed, first_step).await;
+        }
+
+        // Requests filed for our agent or task ID from other processes.
+        let mut control_targets = vec![self.agent_id.clone()];
+        if let Some(task_id) = &self.task_id {
+            control_targets.push(task_key(task_id).to_string());
+        }
+        let follower = self
+            .controls
+            .follow(control_targets.clone(), self.control.clone());
 
+        let mut step = first_step;
         let loop_result: Result<()> = async {
-            let mut step = 0usize;
-            let mut last_poll_time = started_at.0;
+            // A daily budget spent by earlier runs stops this one before it starts.
+            self.charge(None).await?;
+
+            // A resumed loop only catches up on what happened since it was interrupted.
+            let mut last_poll_time = resumed
+                .as_ref()
+                .map_or(started_at.0, |state| state.updated_at.0);
 
             loop {
                 // Fetch recent events from other agents to maintain context
@@ -278,6 +887,7 @@ impl AgentRuntime {
                         }
                     }
                 }
+                self.checkpoint(goal, step, &started_at).await?;
                 if let Some(limit) = self.hard_step_cap {
                     if step >= limit {
                         warn!("Hard safety cap reached at {} steps.", limit);
@@ -300,6 +910,10 @@ impl AgentRuntime {
                 let mut session = self.session.lock().await;
                 let outcome = self.compactor.compact(&mut session).await;
                 if outcome.compacted {
+                    *self.summary.lock().await = session
+                        .compactable_messages()
+                        .first()
+                        .map(|m| m.content.clone());
                     info!(
                         "Context compacted: {} -> {} tokens",
                         outcome.tokens_before, outcome.tokens_after
@@ -309,16 +923,36 @@ impl AgentRuntime {
 
                 step += 1;
                 info!("Elastic step {}", step);
-                let actions = self.next_actions(goal, None).await?;
+                let turn = self
+                    .control
+                    .interruptible(self.next_actions(goal, None))
+                    .await?;
 
-                if actions.is_empty() {
-                    info!("Agent decided to stop (no actions).");
+                if turn.steps.is_empty() {
+                    // A reply without tool calls is the agent's final answer.
+                    info!("Agent finished (no tool calls).");
+                    let reply = turn.content.trim().to_string();
+                    if !reply.is_empty() {
+                        self.session
+                            .lock()
+                            .await
+                            .add_message(Message::new(MessageRole::Assistant, reply.clone()));
+                        let _ = self
+                            .execute_authorized(&OrchestrationAction::Chat {
+                                response: reply.clone(),
+                            })
+                            .await;
+                    }
                     self.persist_state(PersistStateInput {
                         goal,
                         step,
                         phase: RuntimePhase::Completed,
                         last_action: None,
-                        last_observation: Some("Agent returned no actions; loop stopped."),
+                        last_observation: Some(if reply.is_empty() {
+                            "Agent returned no actions; loop stopped."
+                        } else {
+                            reply.as_str()
+                        }),
                         history: self.get_history_strings().await,
                         started_at: started_at.clone(),
                         finished_at: Some(crate::models::FlexibleTimestamp::now()),
@@ -328,21 +962,26 @@ impl AgentRuntime {
                     break;
                 }
 
-                for action in actions {
+                // Text sent along with the calls is attached to the first one.
+                let mut content = Some(turn.content);
+                for (call, action) in turn.steps {
+                    let mut current_call = call;
                     let mut current_action = action;
                     let mut retry_count = 0;
 
                     loop {
+                        self.checkpoint(goal, step, &started_at).await?;
                         {
                             let mut session = self.session.lock().await;
-                            session.add_message(Message::new(
-                                MessageRole::Assistant,
-                                format!("Action: {:?}", current_action),
+                            session.add_message(Message::assistant_tool_calls(
+                                content.take().unwrap_or_default(),
+                                vec![current_call.clone()],
                             ));
                         }
 
-                        let result = match self.execute_action(&current_action).await {
+                        let result = match self.execute_authorized(&current_action).await {
                             Ok(res) => res,
+                            Err(e) if e.is::<RunCancelled>() => return Err(e),
                             Err(e) => ExecutionResult {
                                 observation: format!("Error: {}", e),
                                 is_success: false,
@@ -351,9 +990,9 @@ impl AgentRuntime {
 
                         {
                             let mut session = self.session.lock().await;
-                            session.add_message(Message::new(
-                                MessageRole::User,
-                                format!("Observation: {}", result.observation),
+                            session.add_message(Message::tool_result(
+                                &current_call,
+                                result.observation.clone(),
                             ));
                         }
 
@@ -396,18 +1035,22 @@ impl AgentRuntime {
                             retry_count, self.max_retries
                         );
 
-                        let repair_actions =
-                            self.next_actions(goal, Some(&result.observation)).await?;
-                        if repair_actions.is_empty() {
+                        let repair = self
+                            .control
+                            .interruptible(self.next_actions(goal, Some(&result.observation)))
+                            .await?;
+                        let Some((call, action)) = repair.steps.into_iter().next() else {
                             warn!("Engine returned no repair actions. Failing loop.");
                             return Err(anyhow::anyhow!(
                                 "Action failed and engine gave no repair: {}",
                                 result.observation
                             ));
-                        }
+                        };
 
-                        // Take the first repair action and continue the retry loop
-                        current_action = repair_actions[0].clone();
+                        // Take the first repair call and continue the retry loop
+                        content = Some(repair.content);
+                        current_call = call;
+                        current_action = action;
                     }
                 }
             }
@@ -415,6 +1058,13 @@ impl AgentRuntime {
         }
         .await;
 
+        follower.abort();
+        if let Err(e) = self.controls.clear(&control_targets).await {
+            warn!("Failed to clear run controls of {}: {}", self.agent_id, e);
+        }
+
+        self.record_usage(&started_at, &loop_result).await;
+
         let _ = self
             .agent
             .set_status(&self.agent_id, AgentStatus::Idle)
@@ -442,31 +1092,151 @@ impl AgentRuntime {
         }
 
         if let Err(e) = loop_result {
-            let _ = self
-                .persist_state(PersistStateInput {
+            if e.is::<RunCancelled>() {
+                self.stop_cancelled(goal, step, started_at).await;
+                // The runtime may be reused (e.g. by the HTTP server) for another run.
+                self.control.reset();
+            } else {
+                let _ = self
+                    .persist_state(PersistStateInput {
+                        goal,
+                        step: 0,
+                        phase: RuntimePhase::Failed,
+                        last_action: None,
+                        last_observation: Some("Loop failed."),
+                        history: self.get_history_strings().await,
+                        started_at,
+                        finished_at: Some(crate::models::FlexibleTimestamp::now()),
+                        error: Some(e.to_string()),
+                    })
+                    .await;
+            }
+            self.release_locks().await;
+            return Err(e);
+        }
+
+        self.release_locks().await;
+
+        Ok(())
+    }
+
+    /// Take back the VFS locks a resumed loop held before it was interrupted.
+    async fn reacquire_locks(&self, paths: &[String]) {
+        for path in paths {
+            match self.vfs.acquire_lock(Path::new(path), &self.agent_id).await {
+                Ok(LockStatus::HeldByOther { owner }) => {
+                    warn!("Lock on '{}' is now held by '{}'", path, owner);
+                }
+                Ok(_) => {
+                    self.held_locks.lock().await.insert(path.clone());
+                }
+                Err(e) => warn!("Failed to re-acquire lock on '{}': {}", path, e),
+            }
+        }
+    }
+
+    async fn release_locks(&self) {
+        self.vfs.release_locks(&self.agent_id).await;
+        self.held_locks.lock().await.clear();
+    }
+
+    /// Hold the loop while paused and stop it once cancelled, recording each
+    /// transition in the runtime state and on the timeline.
+    async fn checkpoint(
+        &self,
+        goal: &str,
+        step: usize,
+        started_at: &FlexibleTimestamp,
+    ) -> Result<()> {
+        if self.control.signal() == ControlSignal::Pause {
+            info!("Agent {} paused at step {}", self.agent_id, step);
+            self.persist_state(PersistStateInput {
+                goal,
+                step,
+                phase: RuntimePhase::Paused,
+                last_action: None,
+                last_observation: Some("Paused on request."),
+                history: self.get_history_strings().await,
+                started_at: started_at.clone(),
+                finished_at: None,
+                error: None,
+            })
+            .await?;
+            self.emit_run_event(EventType::RunPaused, step).await;
+
+            if self.control.wait_while_paused().await == ControlSignal::Run {
+                info!("Agent {} resumed at step {}", self.agent_id, step);
+                self.persist_state(PersistStateInput {
                     goal,
-                    step: 0,
-                    phase: RuntimePhase::Failed,
+                    step,
+                    phase: RuntimePhase::Running,
                     last_action: None,
-                    last_observation: Some("Loop failed."),
+                    last_observation: Some("Resumed on request."),
                     history: self.get_history_strings().await,
-                    started_at,
-                    finished_at: Some(crate::models::FlexibleTimestamp::now()),
-                    error: Some(e.to_string()),
+                    started_at: started_at.clone(),
+                    finished_at: None,
+                    error: None,
                 })
-                .await;
-            self.vfs.release_locks(&self.agent_id).await;
-            return Err(e);
+                .await?;
+                self.emit_run_event(EventType::RunResumed, step).await;
+            }
         }
+        if self.control.is_cancelled() {
+            return Err(RunCancelled.into());
+        }
+        Ok(())
+    }
 
-        self.vfs.release_locks(&self.agent_id).await;
+    /// Wind down a cancelled loop: kill its background jobs, cancel the
+    /// sub-agents it delegated to and record the `Cancelled` phase.
+    async fn stop_cancelled(&self, goal: &str, step: usize, started_at: FlexibleTimestamp) {
+        info!("Agent {} cancelled at step {}", self.agent_id, step);
+        let jobs: Vec<(String, SandboxedProcess)> = self.jobs.lock().await.drain().collect();
+        for (name, job) in jobs {
+            if let Err(e) = job.kill().await {
+                warn!("Failed to kill job '{}' of {}: {}", name, self.agent_id, e);
+            }
+        }
+        for control in self.child_controls.lock().await.values() {
+            control.cancel();
+        }
 
-        Ok(())
+        let _ = self
+            .persist_state(PersistStateInput {
+                goal,
+                step,
+                phase: RuntimePhase::Cancelled,
+                last_action: None,
+                last_observation: Some("Cancelled on request."),
+                history: self.get_history_strings().await,
+                started_at,
+                finished_at: Some(FlexibleTimestamp::now()),
+                error: None,
+            })
+            .await;
+        self.emit_run_event(EventType::RunCancelled, step).await;
+    }
+
+    async fn emit_run_event(&self, event_type: EventType, step: usize) {
+        let mut event = TimelineEvent::new(&self.agent_id, event_type)
+            .with_payload(serde_json::json!({ "step": step }));
+        if let Some(task_id) = &self.task_id {
+            event = event.with_task(task_id);
+        }
+        if let Err(e) = self.timeline.record_event(event).await {
+            warn!("Failed to record run event of {}: {}", self.agent_id, e);
+        }
     }
 
     async fn persist_state(&self, input: PersistStateInput<'_>) -> Result<()> {
         let mut state =
             AgentRuntimeState::new(&self.agent_id, input.goal, self.hard_step_cap.unwrap_or(0));
+        state.task_id = self.task_id.clone();
+        state.summary = self.summary.lock().await.clone();
+        if !input.phase.is_finished() {
+            state.locked_paths = self.held_locks.lock().await.iter().cloned().collect();
+        }
+        state.llm_usage = self.run_usage.lock().await.by_provider();
         state.phase = input.phase;
         state.current_step = input.step;
         state.last_action = input.last_action.map(|a| format!("{:?}", a));
@@ -491,6 +1261,57 @@ impl AgentRuntime {
         Ok(())
     }
 
+    /// Add `usage` to the current run; fails with the reason once a budget
+    /// limit is exceeded. Today's spend is re-read every time, so runs that
+    /// finished meanwhile count against the daily limit.
+    async fn charge(&self, usage: Option<&Usage>) -> Result<()> {
+        let mut run_usage = self.run_usage.lock().await;
+        if let Some(usage) = usage {
+            run_usage.add(usage);
+        }
+        if self.budget.max_daily_usd.is_some() {
+            match self.usage.spent_today().await {
+                Ok(spent) => run_usage.spent_today = spent,
+                Err(e) => warn!("Could not read today's spend: {}", e),
+            }
+        }
+        match self
+            .budget
+            .exceeded(&run_usage.total(), run_usage.spent_today)
+        {
+            Some(reason) => {
+                warn!("Agent {} stopped: {}", self.agent_id, reason);
+                Err(anyhow::anyhow!(reason))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Store the outcome and LLM usage of the finished run as execution metrics.
+    async fn record_usage(&self, started_at: &FlexibleTimestamp, result: &Result<()>) {
+        let usage = self.run_usage.lock().await.by_provider();
+        let mut metrics = ExecutionMetrics::from_agent_result(
+            &format!("{}-{}", self.agent_id, started_at.0.timestamp_millis()),
+            &self.agent_id,
+            "agent_runtime",
+            result.is_ok(),
+            (chrono::Utc::now() - started_at.0)
+                .num_milliseconds()
+                .max(0) as u64,
+            result.as_ref().err().map(|e| e.to_string()),
+        )
+        .with_usage(usage);
+        if let Some(task_id) = &self.task_id {
+            metrics = metrics.with_task(task_id);
+            if let Ok(Some(task)) = self.task.get_by_id(task_id).await {
+                metrics = metrics.with_project(&task.project_id);
+            }
+        }
+        if let Err(e) = self.usage.record(&metrics).await {
+            warn!("Failed to record usage for {}: {}", self.agent_id, e);
+        }
+    }
+
     async fn get_history_strings(&self) -> Vec<String> {
         let session = self.session.lock().await;
         session
@@ -500,149 +1321,179 @@ impl AgentRuntime {
             .collect()
     }
 
-    async fn next_actions(
-        &self,
-        goal: &str,
-        error_feedback: Option<&str>,
-    ) -> Result<Vec<OrchestrationAction>> {
-        let history_summary = {
-            let session = self.session.lock().await;
-            session
-                .recent_messages()
-                .iter()
-                .rev()
-                .take(8)
-                .map(|m| format!("{:?}: {}", m.role, m.content))
-                .collect::<Vec<_>>()
-                .join("\n")
-        };
+    async fn next_actions(&self, goal: &str, error_feedback: Option<&str>) -> Result<Turn> {
+        let mut instructions = format!(
+            "You are agent {} working autonomously toward this goal:\n{}\n\n\
+             Call tools to make progress. Once the goal is achieved, reply in plain text \
+             without tool calls; that reply is your final answer.",
+            self.agent_id, goal
+        );
+        if let Some(err) = error_feedback {
+            instructions.push_str(&format!(
+                "\n\n⚠️ PREVIOUS ATTEMPT FAILED:\n{}\n\nPlease analyze the error and try a different approach to achieve the goal.",
+                err
+            ));
+        }
 
-        let summary = if let Some(err) = error_feedback {
-            format!(
-                "GOAL: {}\n\n⚠️ PREVIOUS ATTEMPT FAILED:\n{}\n\nPlease analyze the error and try a different approach to achieve the goal.\n\nhistory:\n{}",
-                goal, err, history_summary
-            )
-        } else {
-            format!("goal: {}\n\nhistory:\n{}", goal, history_summary)
-        };
+        let mut messages = vec![Message::new(MessageRole::System, instructions)];
+        messages.extend(self.session.lock().await.recent_messages().iter().cloned());
 
-        let context = DecisionContext::new("orchestration").with_summary(summary);
+        // Built-in actions take precedence over registry tools of the same name.
+        let mut tools = builtin_tools();
+        for schema in self.registry.schemas().await {
+            if !tools.iter().any(|t| t.name == schema.name) {
+                tools.push(schema);
+            }
+        }
 
         // Use native engine resilience (configured with StochasticRotator in main)
-        let decision = self.engine.decide(&context).await?;
-        Ok(Self::map_decision_to_actions(&decision))
+        let reply = self
+            .engine
+            .chat(&ChatRequest::new(messages).with_tools(tools))
+            .await?;
+        self.charge(reply.usage.as_ref()).await?;
+        let steps = reply
+            .tool_calls
+            .into_iter()
+            .map(|call| {
+                let action = Self::action_from_call(&call);
+                (call, action)
+            })
+            .collect();
+        Ok(Turn {
+            content: reply.content,
+            steps,
+        })
     }
 
-    fn map_decision_to_actions(decision: &Decision) -> Vec<OrchestrationAction> {
-        let action = decision.action.trim();
-        let params = decision.parameters.as_ref();
-
-        if let Some(tool_name) = action.strip_prefix("call:") {
-            return vec![OrchestrationAction::CallAgent {
-                tool: tool_name.to_string(),
-                args: decision.parameters.clone().unwrap_or(Value::Null),
-            }];
-        }
+    /// Map a tool call onto a built-in action; any other tool goes to the registry.
+    fn action_from_call(call: &ToolCall) -> OrchestrationAction {
+        let params = &call.arguments;
 
-        match action.to_lowercase().as_str() {
-            "stop" | "done" | "defer" | "final answer" => vec![],
+        match call.name.as_str() {
             "create_project" => {
                 let name = params
-                    .and_then(|p| p.get("name"))
+                    .get("name")
                     .and_then(|v| v.as_str())
                     .unwrap_or("unnamed")
                     .to_string();
                 let description = params
-                    .and_then(|p| p.get("description"))
+                    .get("description")
                     .and_then(|v| v.as_str())
                     .map(|s| s.to_string());
-                vec![OrchestrationAction::CreateProject { name, description }]
+                OrchestrationAction::CreateProject { name, description }
             }
             "create_task" => {
                 let project = params
-                    .and_then(|p| p.get("project"))
+                    .get("project")
                     .and_then(|v| v.as_str())
                     .unwrap_or("default")
                     .to_string();
                 let description = params
-                    .and_then(|p| p.get("description"))
+                    .get("description")
                     .and_then(|v| v.as_str())
                     .unwrap_or("no description")
                     .to_string();
-                vec![OrchestrationAction::CreateTask {
+                OrchestrationAction::CreateTask {
                     project,
                     description,
-                }]
+                }
             }
             "run_task" => {
                 let task_id = params
-                    .and_then(|p| p.get("task_id"))
+                    .get("task_id")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::RunTask { task_id }]
+                OrchestrationAction::RunTask { task_id }
             }
             "read_file" => {
                 let path = params
-                    .and_then(|p| p.get("path"))
+                    .get("path")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::ReadFile { path }]
+                OrchestrationAction::ReadFile { path }
             }
             "write_file" => {
                 let path = params
-                    .and_then(|p| p.get("path"))
+                    .get("path")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
                 let content = params
-                    .and_then(|p| p.get("content"))
+                    .get("content")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                OrchestrationAction::WriteFile { path, content }
+            }
+            "remove_file" => {
+                let path = params
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                OrchestrationAction::RemoveFile { path }
+            }
+            "remove_dir" => {
+                let path = params
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                OrchestrationAction::RemoveDir { path }
+            }
+            "rename" | "move_file" => {
+                let from = params
+                    .get("from")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::WriteFile { path, content }]
+                let to = params
+                    .get("to")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                OrchestrationAction::RenamePath { from, to }
             }
             "execute_shell" => {
                 let command = params
-                    .and_then(|p| p.get("command"))
+                    .get("command")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::ExecuteShell { command }]
+                OrchestrationAction::ExecuteShell { command }
             }
-            "git_status" => vec![OrchestrationAction::GitStatus],
+            "git_status" => OrchestrationAction::GitStatus,
             "git_log" => {
-                let count = params
-                    .and_then(|p| p.get("count"))
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(5) as usize;
-                vec![OrchestrationAction::GitLog { count }]
+                let count = params.get("count").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
+                OrchestrationAction::GitLog { count }
             }
-            "git_branch_list" => vec![OrchestrationAction::GitBranchList],
+            "git_branch_list" => OrchestrationAction::GitBranchList,
             "git_branch_create" => {
                 let name = params
-                    .and_then(|p| p.get("name"))
+                    .get("name")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
                 let checkout = params
-                    .and_then(|p| p.get("checkout"))
+                    .get("checkout")
                     .and_then(|v| v.as_bool())
                     .unwrap_or(false);
-                vec![OrchestrationAction::GitBranchCreate { name, checkout }]
+                OrchestrationAction::GitBranchCreate { name, checkout }
             }
             "git_checkout" => {
                 let name = params
-                    .and_then(|p| p.get("name"))
+                    .get("name")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::GitCheckout { name }]
+                OrchestrationAction::GitCheckout { name }
             }
             "git_add" => {
                 let paths = params
-                    .and_then(|p| p.get("paths"))
+                    .get("paths")
                     .and_then(|v| v.as_array())
                     .map(|arr| {
                         arr.iter()
@@ -650,83 +1501,119 @@ impl AgentRuntime {
                             .collect::<Vec<_>>()
                     })
                     .unwrap_or_default();
-                vec![OrchestrationAction::GitAdd { paths }]
+                OrchestrationAction::GitAdd { paths }
             }
             "git_commit" => {
                 let message = params
-                    .and_then(|p| p.get("message"))
+                    .get("message")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::GitCommit { message }]
+                OrchestrationAction::GitCommit { message }
             }
             "git_push" => {
                 let remote = params
-                    .and_then(|p| p.get("remote"))
+                    .get("remote")
                     .and_then(|v| v.as_str())
                     .unwrap_or("origin")
                     .to_string();
                 let branch = params
-                    .and_then(|p| p.get("branch"))
+                    .get("branch")
                     .and_then(|v| v.as_str())
                     .unwrap_or("main")
                     .to_string();
-                vec![OrchestrationAction::GitPush { remote, branch }]
-            }
-            "list_projects" => vec![OrchestrationAction::ListProjects],
-            "list_jobs" => vec![OrchestrationAction::ListJobs],
-            "flush_vfs" => vec![OrchestrationAction::FlushVfs],
-            "review_merge" => vec![OrchestrationAction::ReviewAndMerge {
-                goal: decision.reasoning.to_string(),
-            }],
+                OrchestrationAction::GitPush { remote, branch }
+            }
+            "list_projects" => OrchestrationAction::ListProjects,
+            "list_jobs" => OrchestrationAction::ListJobs,
+            "flush_vfs" => OrchestrationAction::FlushVfs,
+            "diff_vfs" => OrchestrationAction::DiffVfs,
+            "snapshot_vfs" => {
+                let name = params
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("default")
+                    .to_string();
+                OrchestrationAction::SnapshotVfs { name }
+            }
+            "rollback_vfs" => {
+                let name = params
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("default")
+                    .to_string();
+                OrchestrationAction::RollbackVfs { name }
+            }
+            "review_merge" => OrchestrationAction::ReviewAndMerge {
+                goal: params
+                    .get("goal")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                agent: params
+                    .get("agent")
+                    .and_then(|v| v.as_str())
+                    .map(ToOwned::to_owned),
+            },
             "delegate" | "delegate_task" => {
                 let agent = params
-                    .and_then(|p| p.get("agent"))
+                    .get("agent")
                     .and_then(|v| v.as_str())
                     .unwrap_or("subagent")
                     .to_string();
                 let goal = params
-                    .and_then(|p| p.get("goal"))
+                    .get("goal")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::DelegateTask { agent, goal }]
+                OrchestrationAction::DelegateTask { agent, goal }
+            }
+            "pause_agent" | "resume_agent" | "cancel_agent" => {
+                let agent = params
+                    .get("agent")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let signal = match call.name.as_str() {
+                    "pause_agent" => ControlSignal::Pause,
+                    "resume_agent" => ControlSignal::Run,
+                    _ => ControlSignal::Cancel,
+                };
+                OrchestrationAction::ControlAgent { agent, signal }
             }
             "start_job" => {
                 let name = params
-                    .and_then(|p| p.get("name"))
+                    .get("name")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
                 let command = params
-                    .and_then(|p| p.get("command"))
+                    .get("command")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::StartJob { name, command }]
+                OrchestrationAction::StartJob { name, command }
             }
             "stop_job" => {
                 let name = params
-                    .and_then(|p| p.get("name"))
+                    .get("name")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::StopJob { name }]
+                OrchestrationAction::StopJob { name }
             }
             "await_job" => {
                 let job_id = params
-                    .and_then(|p| p.get("job_id"))
+                    .get("job_id")
                     .and_then(|v| v.as_str())
                     .unwrap_or_default()
                     .to_string();
-                vec![OrchestrationAction::AwaitJob { job_id }]
-            }
-            "chat" => vec![OrchestrationAction::Chat {
-                response: decision.reasoning.to_string(),
-            }],
-            _ => vec![OrchestrationAction::Chat {
-                response: format!("Decision: {} | {}", decision.action, decision.reasoning),
-            }],
+                OrchestrationAction::AwaitJob { job_id }
+            }
+            _ => OrchestrationAction::CallAgent {
+                tool: call.name.clone(),
+                args: call.arguments.clone(),
+            },
         }
     }
 
@@ -756,6 +1643,11 @@ impl AgentRuntime {
         Ok(())
     }
 
+    /// Sandboxed `command` over this agent's view of the current directory.
+    fn sandbox_request(&self, command: &str) -> SandboxRequest {
+        SandboxRequest::new(command).with_workspace(self.vfs.clone(), ".", self.agent_id.clone())
+    }
+
     async fn execute_git(&self, args: &[String]) -> Result<ExecutionResult> {
         let output = tokio::process::Command::new("git")
             .args(args)
@@ -776,6 +1668,126 @@ impl AgentRuntime {
         })
     }
 
+    /// Record the outcome of a VFS remove/rename on the timeline.
+    async fn record_vfs_op(
+        &self,
+        payload: Value,
+        result: Result<()>,
+        success: String,
+    ) -> Result<ExecutionResult> {
+        match result {
+            Ok(()) => {
+                let mut payload = payload;
+                payload["version"] = Value::from(self.vfs.version().await);
+                let _ = self
+                    .timeline
+                    .record_event(
+                        TimelineEvent::new(&self.agent_id, EventType::VfsPatchApplied)
+                            .with_payload(payload),
+                    )
+                    .await;
+                Ok(ExecutionResult {
+                    observation: success,
+                    is_success: true,
+                })
+            }
+            Err(e) => {
+                if e.to_string().contains("lock conflict") {
+                    let mut payload = payload;
+                    payload["error"] = Value::from(e.to_string());
+                    let _ = self
+                        .timeline
+                        .record_event(
+                            TimelineEvent::new(&self.agent_id, EventType::VfsLockConflict)
+                                .with_payload(payload),
+                        )
+                        .await;
+                }
+                Ok(ExecutionResult {
+                    observation: format!("VFS operation failed: {}", e),
+                    is_success: false,
+                })
+            }
+        }
+    }
+
+    /// Run `action` if the policy allows it, waiting for a human when it asks for approval.
+    /// Fails with `RunCancelled` if the run is cancelled first; a pending
+    /// approval is then expired.
+    async fn execute_authorized(&self, action: &OrchestrationAction) -> Result<ExecutionResult> {
+        let facts = action.policy_facts(&self.agent_id);
+        let verdict = self.policy.evaluate(&facts);
+        self.timeline
+            .record_event(
+                TimelineEvent::new(&self.agent_id, EventType::PolicyDecision).with_payload(
+                    serde_json::json!({
+                        "action": facts,
+                        "decision": verdict.decision,
+                        "rule": verdict.rule,
+                        "reason": verdict.reason,
+                    }),
+                ),
+            )
+            .await?;
+
+        let rule = verdict
+            .rule
+            .as_deref()
+            .map(|r| format!(" (rule '{}')", r))
+            .unwrap_or_default();
+        match verdict.decision {
+            PolicyDecision::Allow => {
+                self.control
+                    .interruptible(self.execute_action(action))
+                    .await
+            }
+            PolicyDecision::Deny => Ok(ExecutionResult {
+                observation: format!(
+                    "Policy denied {}{}: {}",
+                    facts.kind,
+                    rule,
+                    verdict.reason.as_deref().unwrap_or("not allowed")
+                ),
+                is_success: false,
+            }),
+            PolicyDecision::RequireApproval => {
+                let request = self
+                    .approvals
+                    .request(&facts, &format!("{:?}", action), &verdict)
+                    .await?;
+                info!(
+                    "Waiting for approval {} before running {}{}",
+                    request.approval_id, facts.kind, rule
+                );
+                let status = tokio::select! {
+                    status = self
+                        .approvals
+                        .wait(&request.approval_id, self.policy.approval_timeout()) => status?,
+                    _ = self.control.cancelled() => {
+                        let expired = self.approvals.expire(&request.approval_id, "cancelled");
+                        if let Err(e) = expired.await {
+                            warn!("Failed to expire approval {}: {}", request.approval_id, e);
+                        }
+                        return Err(RunCancelled.into());
+                    }
+                };
+                if status == ApprovalStatus::Approved {
+                    return self
+                        .control
+                        .interruptible(self.execute_action(action))
+                        .await;
+                }
+                Ok(ExecutionResult {
+                    observation: format!(
+                        "Approval {} for {}{} was {}; the action did not run.",
+                        request.approval_id, facts.kind, rule, status
+                    ),
+                    is_success: false,
+                })
+            }
+        }
+    }
+
     async fn execute_action(&self, action: &OrchestrationAction) -> Result<ExecutionResult> {
         match action {
             OrchestrationAction::CreateProject {
@@ -905,6 +1917,7 @@ impl AgentRuntime {
                         });
                     }
                     LockStatus::Acquired => {
+                        self.held_locks.lock().await.insert(path.clone());
                         let _ = self
                             .timeline
                             .record_event(
@@ -946,6 +1959,39 @@ impl AgentRuntime {
                     }),
                 }
             }
+            OrchestrationAction::RemoveFile { path } => {
+                let result = self.vfs.remove_file(Path::new(path), &self.agent_id).await;
+                self.record_vfs_op(
+                    serde_json::json!({ "op": "remove_file", "path": path }),
+                    result,
+                    format!("Removed file '{}' (pending flush)", path),
+                )
+                .await
+            }
+            OrchestrationAction::RemoveDir { path } => {
+                let result = self
+                    .vfs
+                    .remove_dir_all(Path::new(path), &self.agent_id)
+                    .await;
+                self.record_vfs_op(
+                    serde_json::json!({ "op": "remove_dir", "path": path }),
+                    result,
+                    format!("Removed directory '{}' (pending flush)", path),
+                )
+                .await
+            }
+            OrchestrationAction::RenamePath { from, to } => {
+                let result = self
+                    .vfs
+                    .rename(Path::new(from), Path::new(to), &self.agent_id)
+                    .await;
+                self.record_vfs_op(
+                    serde_json::json!({ "op": "rename", "from": from, "to": to }),
+                    result,
+                    format!("Renamed '{}' to '{}' (pending flush)", from, to),
+                )
+                .await
+            }
             OrchestrationAction::FlushVfs => {
                 let _ = self
                     .timeline
@@ -962,19 +2008,16 @@ impl AgentRuntime {
                                         "version": version,
                                         "files": report.written_files.len(),
                                         "dirs": report.created_dirs.len(),
+                                        "removed": report.removed_paths.len(),
+                                        "merged": report.merged_files.len(),
+                                        "conflicts": report.conflicts.len(),
                                         "errors": report.errors.len(),
                                     })),
                             )
                             .await;
-                        let is_success = report.errors.is_empty();
                         Ok(ExecutionResult {
-                            observation: format!(
-                                "VFS flush complete. dirs={}, files={}, errors={}",
-                                report.created_dirs.len(),
-                                report.written_files.len(),
-                                report.errors.len()
-                            ),
-                            is_success,
+                            observation: describe_flush("VFS flush", &report),
+                            is_success: report.errors.is_empty(),
                         })
                     }
                     Err(e) => Ok(ExecutionResult {
@@ -983,30 +2026,78 @@ impl AgentRuntime {
                     }),
                 }
             }
-            OrchestrationAction::ExecuteShell { command } => {
-                #[cfg(target_os = "windows")]
-                let mut cmd = tokio::process::Command::new("powershell");
-                #[cfg(target_os = "windows")]
-                cmd.arg("-Command").arg(command);
-
-                #[cfg(not(target_os = "windows"))]
-                let mut cmd = tokio::process::Command::new("sh");
-                #[cfg(not(target_os = "windows"))]
-                cmd.arg("-c").arg(command);
-
-                match cmd.output().await {
-                    Ok(output) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        let exit_code = output.status.code().unwrap_or(-1);
+            OrchestrationAction::SnapshotVfs { name } => {
+                let Some(layer) = &self.layer else {
+                    return Ok(ExecutionResult {
+                        observation: "Snapshots need a VFS layer; only delegated agents have one."
+                            .to_string(),
+                        is_success: false,
+                    });
+                };
+                layer.snapshot(name).await;
+                let _ = self
+                    .timeline
+                    .record_event(
+                        TimelineEvent::new(&self.agent_id, EventType::VfsSnapshotCreated)
+                            .with_payload(serde_json::json!({
+                                "name": name,
+                                "pending": layer.pending_changes().await.len(),
+                            })),
+                    )
+                    .await;
+                Ok(ExecutionResult {
+                    observation: format!("VFS snapshot '{}' created.", name),
+                    is_success: true,
+                })
+            }
+            OrchestrationAction::RollbackVfs { name } => {
+                let Some(layer) = &self.layer else {
+                    return Ok(ExecutionResult {
+                        observation: "Snapshots need a VFS layer; only delegated agents have one."
+                            .to_string(),
+                        is_success: false,
+                    });
+                };
+                match layer.rollback(name).await {
+                    Ok(()) => {
+                        let _ = self
+                            .timeline
+                            .record_event(
+                                TimelineEvent::new(&self.agent_id, EventType::VfsRolledBack)
+                                    .with_payload(serde_json::json!({ "name": name })),
+                            )
+                            .await;
                         Ok(ExecutionResult {
-                            observation: format!(
-                                "Command executed (Exit: {})\nSTDOUT:\n{}\nSTDERR:\n{}",
-                                exit_code, stdout, stderr
-                            ),
-                            is_success: exit_code == 0,
+                            observation: format!("VFS rolled back to snapshot '{}'.", name),
+                            is_success: true,
                         })
                     }
+                    Err(e) => Ok(ExecutionResult {
+                        observation: format!("VFS rollback failed: {}", e),
+                        is_success: false,
+                    }),
+                }
+            }
+            OrchestrationAction::DiffVfs => match self.vfs.diff().await {
+                Ok(diff) if diff.is_empty() => Ok(ExecutionResult {
+                    observation: "No pending VFS changes.".to_string(),
+                    is_success: true,
+                }),
+                Ok(diff) => Ok(ExecutionResult {
+                    observation: format!("Pending VFS changes:\n{}", diff),
+                    is_success: true,
+                }),
+                Err(e) => Ok(ExecutionResult {
+                    observation: format!("VFS diff failed: {}", e),
+                    is_success: false,
+                }),
+            },
+            OrchestrationAction::ExecuteShell { command } => {
+                match self.sandbox.run(self.sandbox_request(command)).await {
+                    Ok(output) => Ok(ExecutionResult {
+                        observation: describe_command("Command executed", &output),
+                        is_success: output.success(),
+                    }),
                     Err(e) => Ok(ExecutionResult {
                         observation: format!("Failed to execute command '{}': {}", command, e),
                         is_success: false,
@@ -1082,20 +2173,8 @@ impl AgentRuntime {
                     .await
             }
             OrchestrationAction::StartJob { name, command } => {
-                #[cfg(target_os = "windows")]
-                let mut cmd = tokio::process::Command::new("powershell");
-                #[cfg(target_os = "windows")]
-                cmd.arg("-Command").arg(command);
-
-                #[cfg(not(target_os = "windows"))]
-                let mut cmd = tokio::process::Command::new("sh");
-                #[cfg(not(target_os = "windows"))]
-                cmd.arg("-c").arg(command);
-
-                cmd.stdout(std::process::Stdio::piped());
-                cmd.stderr(std::process::Stdio::piped());
-
-                match cmd.spawn() {
+                let request = self.sandbox_request(command).background();
+                match self.sandbox.spawn(request).await {
                     Ok(child) => {
                         let id = child.id().unwrap_or(0);
                         let mut jobs = self.jobs.lock().await;
@@ -1116,7 +2195,7 @@ impl AgentRuntime {
             }
             OrchestrationAction::StopJob { name } => {
                 let mut jobs = self.jobs.lock().await;
-                if let Some(mut child) = jobs.remove(name) {
+                if let Some(child) = jobs.remove(name) {
                     match child.kill().await {
                         Ok(_) => Ok(ExecutionResult {
                             observation: format!("Job '{}' stopped.", name),
@@ -1165,10 +2244,30 @@ impl AgentRuntime {
                     self.agent.clone(),
                     self.memory.clone(),
                 )
-                .with_parent(self.agent_id.clone());
+                .with_parent(self.agent_id.clone())
+                .with_sandbox(self.sandbox.clone())
+                .with_policy(self.policy.clone())
+                .with_budget(self.budget.clone())
+                .with_model(&self.model)
+                .with_layer_over(self.vfs.clone());
+                if let Some(layer) = &sub_runtime.layer {
+                    self.child_layers
+                        .lock()
+                        .await
+                        .insert(sub_agent_id.clone(), layer.clone());
+                }
+                self.child_controls
+                    .lock()
+                    .await
+                    .insert(sub_agent_id.clone(), sub_runtime.control());
 
                 let goal_clone = goal.clone();
-                spawn_sub_agent(sub_runtime, goal_clone);
+                spawn_sub_agent(
+                    sub_runtime,
+                    goal_clone,
+                    self.child_layers.clone(),
+                    self.child_controls.clone(),
+                );
 
                 let _ = self
                     .timeline
@@ -1241,17 +2340,45 @@ impl AgentRuntime {
                     }),
                 }
             }
+            OrchestrationAction::ControlAgent { agent, signal } => {
+                let controls = self.child_controls.lock().await;
+                let sub_agent_id = format!("{}-{}", self.agent_id, agent);
+                let Some(control) = controls.get(agent).or_else(|| controls.get(&sub_agent_id))
+                else {
+                    return Ok(ExecutionResult {
+                        observation: format!(
+                            "No delegated agent '{}'. Known: {:?}",
+                            agent,
+                            controls.keys().collect::<Vec<_>>()
+                        ),
+                        is_success: false,
+                    });
+                };
+                let observation = if control.set(*signal) {
+                    format!("Sent {} to sub-agent '{}'.", signal, agent)
+                } else {
+                    format!(
+                        "Sub-agent '{}' is already at {}; nothing changed.",
+                        agent,
+                        control.signal()
+                    )
+                };
+                Ok(ExecutionResult {
+                    observation,
+                    is_success: true,
+                })
+            }
             OrchestrationAction::AwaitJob { job_id } => {
                 let mut jobs = self.jobs.lock().await;
-                if let Some(mut child) = jobs.remove(job_id.as_str()) {
+                if let Some(child) = jobs.remove(job_id.as_str()) {
                     drop(jobs);
                     match child.wait().await {
-                        Ok(status) => Ok(ExecutionResult {
-                            observation: format!(
-                                "Job '{}' finished with status: {}",
-                                job_id, status
+                        Ok(output) => Ok(ExecutionResult {
+                            observation: describe_command(
+                                &format!("Job '{}' finished", job_id),
+                                &output,
                             ),
-                            is_success: status.success(),
+                            is_success: output.success(),
                         }),
                         Err(e) => Ok(ExecutionResult {
                             observation: format!("Error waiting for job '{}': {}", job_id, e),
@@ -1265,7 +2392,45 @@ impl AgentRuntime {
                     })
                 }
             }
-            OrchestrationAction::ReviewAndMerge { goal } => {
+            OrchestrationAction::ReviewAndMerge { goal, agent } => {
+                let layer = match agent {
+                    Some(agent) => {
+                        let layers = self.child_layers.lock().await;
+                        let sub_agent_id = format!("{}-{}", self.agent_id, agent);
+                        let key = if layers.contains_key(agent) {
+                            agent.clone()
+                        } else {
+                            sub_agent_id
+                        };
+                        match layers.get(&key) {
+                            Some(layer) => Some((key, layer.clone())),
+                            None => {
+                                return Ok(ExecutionResult {
+                                    observation: format!(
+                                        "No layer for delegated agent '{}'. Known: {:?}",
+                                        agent,
+                                        layers.keys().collect::<Vec<_>>()
+                                    ),
+                                    is_success: false,
+                                })
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                let diff = match &layer {
+                    Some((_, layer)) => match layer.diff().await {
+                        Ok(diff) => Some(diff),
+                        Err(e) => {
+                            return Ok(ExecutionResult {
+                                observation: format!("Layer diff failed: {}", e),
+                                is_success: false,
+                            })
+                        }
+                    },
+                    None => None,
+                };
+
                 let (reply_tx, reply_rx) = oneshot::channel();
                 let handle = {
                     let mut hive = self.hive.lock().await;
@@ -1274,6 +2439,7 @@ impl AgentRuntime {
                 if let Err(e) = handle
                     .send(ReviewerMessage::ReviewAndMerge {
                         goal: goal.clone(),
+                        diff,
                         reply: reply_tx,
                     })
                     .await
@@ -1285,13 +2451,65 @@ impl AgentRuntime {
                 }
 
                 match reply_rx.await {
-                    Ok(result) => Ok(ExecutionResult {
-                        observation: format!(
+                    Ok(result) => {
+                        let mut observation = format!(
                             "Reviewer decision: approved={} summary={}",
                             result.approved, result.summary
-                        ),
-                        is_success: result.approved,
-                    }),
+                        );
+                        let Some((key, layer)) = layer else {
+                            return Ok(ExecutionResult {
+                                observation,
+                                is_success: result.approved,
+                            });
+                        };
+                        if !result.approved {
+                            // A rejected layer is dropped; delegate again to retry.
+                            layer.discard().await;
+                            self.child_layers.lock().await.remove(&key);
+                            observation.push_str(&format!("\nLayer '{}' discarded.", layer.name()));
+                            return Ok(ExecutionResult {
+                                observation,
+                                is_success: false,
+                            });
+                        }
+                        let report = match layer.promote().await {
+                            Ok(report) => report,
+                            Err(e) => {
+                                observation.push_str(&format!("\nLayer promotion failed: {}", e));
+                                return Ok(ExecutionResult {
+                                    observation,
+                                    is_success: false,
+                                });
+                            }
+                        };
+                        let _ = self
+                            .timeline
+                            .record_event(
+                                TimelineEvent::new(&self.agent_id, EventType::VfsLayerPromoted)
+                                    .with_payload(serde_json::json!({
+                                        "layer": layer.name(),
+                                        "files": report.written_files.len(),
+                                        "dirs": report.created_dirs.len(),
+                                        "removed": report.removed_paths.len(),
+                                        "merged": report.merged_files.len(),
+                                        "conflicts": report.conflicts.len(),
+                                        "errors": report.errors.len(),
+                                    })),
+                            )
+                            .await;
+                        if report.errors.is_empty() && report.conflicts.is_empty() {
+                            self.child_layers.lock().await.remove(&key);
+                        }
+                        observation.push('\n');
+                        observation.push_str(&describe_flush(
+                            &format!("Layer '{}' promotion", layer.name()),
+                            &report,
+                        ));
+                        Ok(ExecutionResult {
+                            observation,
+                            is_success: report.errors.is_empty(),
+                        })
+                    }
                     Err(e) => Ok(ExecutionResult {
                         observation: format!("Reviewer did not respond: {}", e),
                         is_success: false,
@@ -1337,4 +2555,90 @@ mod tests {
 
         assert_eq!(runtime.max_retries, 3);
     }
+
+    #[test]
+    fn test_policy_facts_expose_commands_and_paths() {
+        let call = OrchestrationAction::CallAgent {
+            tool: "kubectl".to_string(),
+            args: serde_json::json!(["delete", "pod", "web-1"]),
+        };
+        let facts = call.policy_facts("agent-1");
+        assert_eq!(facts.kind, "call_agent");
+        assert_eq!(facts.command.as_deref(), Some("kubectl delete pod web-1"));
+
+        let rename = OrchestrationAction::RenamePath {
+            from: "src/a.rs".to_string(),
+            to: "secrets/a.rs".to_string(),
+        };
+        assert_eq!(
+            rename.policy_facts("agent-1").paths,
+            vec!["src/a.rs", "secrets/a.rs"]
+        );
+        assert!(OrchestrationAction::ListJobs
+            .policy_facts("agent-1")
+            .command
+            .is_none());
+    }
+
+    #[test]
+    fn test_tool_calls_map_to_actions() {
+        let call = |name: &str, arguments: Value| ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments,
+        };
+
+        match AgentRuntime::action_from_call(&call(
+            "git_add",
+            serde_json::json!({"paths": ["a.rs", "b.rs"]}),
+        )) {
+            OrchestrationAction::GitAdd { paths } => assert_eq!(paths, vec!["a.rs", "b.rs"]),
+            other => panic!("unexpected action: {:?}", other),
+        }
+        match AgentRuntime::action_from_call(&call("git_push", serde_json::json!({}))) {
+            OrchestrationAction::GitPush { remote, branch } => {
+                assert_eq!((remote.as_str(), branch.as_str()), ("origin", "main"))
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+        match AgentRuntime::action_from_call(&call("search_code", serde_json::json!({"q": "x"}))) {
+            OrchestrationAction::CallAgent { tool, args } => {
+                assert_eq!(tool, "search_code");
+                assert_eq!(args["q"], "x");
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+
+        let tools = builtin_tools();
+        assert_eq!(tools.len(), BUILTIN_TOOLS.len());
+        let push = tools.iter().find(|t| t.name == "git_push").unwrap();
+        assert_eq!(push.parameters["required"], serde_json::json!([]));
+        let add = tools.iter().find(|t| t.name == "git_add").unwrap();
+        assert_eq!(add.parameters["properties"]["paths"]["type"], "array");
+    }
+
+    #[test]
+    fn test_resume_messages_rebuild_session() {
+        let mut state = AgentRuntimeState::new("agent", "Ship it", 0);
+        state.current_step = 4;
+        state.summary = Some("Wrote the parser.".to_string());
+        state.history_tail = vec![
+            "User: GOAL: Ship it".to_string(),
+            "Assistant: ".to_string(),
+            "Tool: Successfully wrote to file 'src/lib.rs'".to_string(),
+            "Assistant: Next, the tests.".to_string(),
+        ];
+
+        let messages = resume_messages(&state);
+        assert_eq!(messages.len(), 4);
+        assert!(messages[0].content.contains("interrupted at step 4"));
+        assert!(messages[0].content.contains("Wrote the parser."));
+        assert!(matches!(messages[2].role, MessageRole::User));
+        assert_eq!(
+            messages[2].content,
+            "Observation: Successfully wrote to file 'src/lib.rs'"
+        );
+        assert!(matches!(messages[3].role, MessageRole::Assistant));
+        assert!(restore_message("no role here").is_none());
+    }
 }
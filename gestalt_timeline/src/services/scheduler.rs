@@ -0,0 +1,355 @@
+//! Scheduler - fires one-shot and cron-scheduled tasks into the `TaskQueue`.
+//!
+//! A task's next run is stored on the task itself (`scheduled_for`, plus a
+//! `cron` expression for recurring tasks), so schedules survive restarts. Each
+//! tick claims the tasks that are due, enqueues them with `TaskSource::Cron`
+//! and re-arms recurring ones. Runs that came due while the process was down
+//! are handled by the scheduler's [`CatchUp`] policy.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::db::SurrealClient;
+use crate::models::{EventType, FlexibleTimestamp, Task, TimelineEvent};
+use crate::services::task_queue::{QueuedTask, TaskQueue, TaskSource};
+use crate::services::TimelineService;
+
+/// Agent ID recorded on `TaskFired` events.
+const SCHEDULER_AGENT: &str = "scheduler";
+/// `TaskSource::Cron` schedule recorded for one-shot runs.
+pub const ONE_SHOT_SCHEDULE: &str = "@once";
+/// Most missed runs a single task is considered for when catching up.
+const MAX_CATCH_UP_RUNS: usize = 100;
+/// Most cron occurrences walked while looking for the next run.
+const MAX_SCANNED_OCCURRENCES: usize = 10_000;
+/// Priority of tasks fired by the scheduler.
+const SCHEDULED_PRIORITY: u8 = 5;
+
+/// What to do with runs that came due while the scheduler was not running.
+///
+/// A run is missed when it is more than the scheduler's grace period late;
+/// runs inside the grace period always fire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// Drop missed runs and wait for the next occurrence.
+    Skip,
+    /// Fire once in place of all missed runs.
+    #[default]
+    Latest,
+    /// Fire every missed run, oldest first.
+    All,
+}
+
+/// Parse a cron expression, with an optional leading seconds field.
+pub fn parse_cron(expression: &str) -> Result<Cron> {
+    Cron::new(expression)
+        .with_seconds_optional()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expression, e))
+}
+
+/// First occurrence of `cron` strictly after `after`.
+pub fn next_occurrence(cron: &Cron, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    cron.find_next_occurrence(&after, false)
+        .map_err(|e| anyhow::anyhow!("No cron occurrence after {}: {}", after, e))
+}
+
+/// The runs a task owes at some instant, and when it is due next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueRuns {
+    /// Occurrences to fire, oldest first.
+    pub fire: Vec<DateTime<Utc>>,
+    /// Occurrences dropped by the catch-up policy.
+    pub skipped: usize,
+    /// Next run, or `None` once a one-shot task has fired.
+    pub next: Option<DateTime<Utc>>,
+}
+
+/// Work out which runs of a task that was due at `due` fire at `now`.
+pub fn due_runs(
+    due: DateTime<Utc>,
+    cron: Option<&Cron>,
+    now: DateTime<Utc>,
+    catch_up: CatchUp,
+    grace: chrono::Duration,
+) -> Result<DueRuns> {
+    let mut owed = VecDeque::from([due]);
+    let mut total = 1;
+    let mut next = None;
+    if let Some(cron) = cron {
+        let mut at = due;
+        for _ in 0..MAX_SCANNED_OCCURRENCES {
+            at = next_occurrence(cron, at)?;
+            if at > now {
+                next = Some(at);
+                break;
+            }
+            owed.push_back(at);
+            total += 1;
+            if owed.len() > MAX_CATCH_UP_RUNS {
+                owed.pop_front();
+            }
+        }
+        if next.is_none() {
+            next = Some(next_occurrence(cron, now)?);
+        }
+    }
+
+    let fire: Vec<DateTime<Utc>> = match catch_up {
+        CatchUp::Skip => owed
+            .iter()
+            .rev()
+            .find(|at| now - **at <= grace)
+            .into_iter()
+            .copied()
+            .collect(),
+        CatchUp::Latest => owed.back().into_iter().copied().collect(),
+        CatchUp::All => owed.into_iter().collect(),
+    };
+    Ok(DueRuns {
+        skipped: total - fire.len(),
+        fire,
+        next,
+    })
+}
+
+/// Polls for due tasks and fires them into the `TaskQueue`.
+#[derive(Clone)]
+pub struct SchedulerService {
+    db: SurrealClient,
+    timeline: TimelineService,
+    queue: TaskQueue,
+    catch_up: CatchUp,
+    grace: chrono::Duration,
+    poll_interval: Duration,
+}
+
+impl SchedulerService {
+    pub fn new(db: SurrealClient, timeline: TimelineService, queue: TaskQueue) -> Self {
+        Self {
+            db,
+            timeline,
+            queue,
+            catch_up: CatchUp::default(),
+            grace: chrono::Duration::seconds(60),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Set how runs missed while the scheduler was down are handled.
+    pub fn with_catch_up(mut self, catch_up: CatchUp) -> Self {
+        self.catch_up = catch_up;
+        self
+    }
+
+    /// Set how often due tasks are polled for.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Fire due tasks until the process exits.
+    pub async fn run(&self) {
+        info!(
+            "⏰ [Scheduler] Started (catch-up: {:?}, poll every {:?})",
+            self.catch_up, self.poll_interval
+        );
+        loop {
+            match self.tick(Utc::now()).await {
+                Ok(0) => {}
+                Ok(fired) => info!("⏰ [Scheduler] Fired {} scheduled runs", fired),
+                Err(e) => warn!("⚠️ [Scheduler] Tick failed: {}", e),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Fire every task due at `now`; returns how many runs were enqueued.
+    pub async fn tick(&self, now: DateTime<Utc>) -> Result<usize> {
+        let tasks: Vec<Task> = self
+            .db
+            .query_with(
+                "SELECT * FROM tasks \
+                 WHERE scheduled_for != NONE AND scheduled_for != NULL AND status != 'cancelled'",
+                serde_json::json!({}),
+            )
+            .await?;
+
+        let mut fired = 0;
+        for task in tasks {
+            let Some(due) = task.scheduled_for.clone() else {
+                continue;
+            };
+            if due.0 > now {
+                continue;
+            }
+            match self.fire(&task, due.0, now).await {
+                Ok(runs) => fired += runs,
+                Err(e) => warn!(
+                    "⚠️ [Scheduler] Failed to fire task '{}': {}",
+                    task.description, e
+                ),
+            }
+        }
+        Ok(fired)
+    }
+
+    async fn fire(&self, task: &Task, due: DateTime<Utc>, now: DateTime<Utc>) -> Result<usize> {
+        let task_id = task
+            .id
+            .as_ref()
+            .map(|t| t.id.to_raw())
+            .context("Scheduled task has no ID")?;
+        let cron = task.cron.as_deref().map(parse_cron).transpose()?;
+        let runs = due_runs(due, cron.as_ref(), now, self.catch_up, self.grace)?;
+
+        // Re-arm before enqueueing, only if nobody else has, so a run fires once.
+        let claimed: Vec<Task> = self
+            .db
+            .query_with(
+                "UPDATE type::thing('tasks', $id) \
+                 SET scheduled_for = $next, updated_at = $now \
+                 WHERE scheduled_for = $due RETURN AFTER",
+                serde_json::json!({
+                    "id": task_id,
+                    "next": runs.next.map(FlexibleTimestamp::from),
+                    "now": FlexibleTimestamp::from(now),
+                    "due": FlexibleTimestamp::from(due),
+                }),
+            )
+            .await?;
+        if claimed.is_empty() {
+            return Ok(0);
+        }
+
+        if runs.skipped > 0 {
+            info!(
+                "⏭️ [Scheduler] Skipping {} missed runs of task {} ({:?})",
+                runs.skipped, task_id, self.catch_up
+            );
+        }
+        let schedule = task
+            .cron
+            .clone()
+            .unwrap_or_else(|| ONE_SHOT_SCHEDULE.to_string());
+        for at in &runs.fire {
+            self.queue
+                .enqueue(
+                    QueuedTask::new(
+                        task.description.clone(),
+                        TaskSource::Cron {
+                            schedule: schedule.clone(),
+                        },
+                        SCHEDULED_PRIORITY,
+                    )
+                    .with_task(&task_id),
+                )
+                .await?;
+            self.timeline
+                .record_event(
+                    TimelineEvent::new(SCHEDULER_AGENT, EventType::TaskFired)
+                        .with_project(&task.project_id)
+                        .with_task(&task_id)
+                        .with_payload(serde_json::json!({
+                            "schedule": schedule,
+                            "scheduled_for": at,
+                            "late_secs": (now - *at).num_seconds(),
+                            "skipped": runs.skipped,
+                            "next_run": runs.next,
+                        })),
+                )
+                .await?;
+        }
+        Ok(runs.fire.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_due_runs_catch_up_policies() -> Result<()> {
+        let hourly = parse_cron("0 * * * *")?;
+        let grace = chrono::Duration::seconds(60);
+        let now = at(13, 30);
+
+        let latest = due_runs(at(10, 0), Some(&hourly), now, CatchUp::Latest, grace)?;
+        assert_eq!(latest.fire, vec![at(13, 0)]);
+        assert_eq!(latest.skipped, 3);
+        assert_eq!(latest.next, Some(at(14, 0)));
+
+        let all = due_runs(at(10, 0), Some(&hourly), now, CatchUp::All, grace)?;
+        assert_eq!(all.fire, vec![at(10, 0), at(11, 0), at(12, 0), at(13, 0)]);
+        assert_eq!(all.skipped, 0);
+
+        let skip = due_runs(at(10, 0), Some(&hourly), now, CatchUp::Skip, grace)?;
+        assert!(skip.fire.is_empty());
+        assert_eq!(skip.skipped, 4);
+        assert_eq!(skip.next, Some(at(14, 0)));
+
+        // On time runs fire even when missed runs are skipped.
+        let on_time = due_runs(at(13, 0), Some(&hourly), at(13, 0), CatchUp::Skip, grace)?;
+        assert_eq!(on_time.fire, vec![at(13, 0)]);
+
+        let once = due_runs(at(10, 0), None, now, CatchUp::Latest, grace)?;
+        assert_eq!(once.fire, vec![at(10, 0)]);
+        assert_eq!(once.next, None);
+
+        assert!(parse_cron("not a schedule").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tick_fires_and_rearms_cron_task() -> Result<()> {
+        let db = SurrealClient::connect_mem().await?;
+        let timeline = TimelineService::new(db.clone());
+        let queue = TaskQueue::new(db.clone());
+        let scheduler = SchedulerService::new(db.clone(), timeline, queue.clone());
+
+        let now = Utc::now();
+        let mut task = Task::new("projects:demo", "Rotate logs", "tester", None);
+        task.scheduled_for = Some((now - chrono::Duration::minutes(90)).into());
+        task.cron = Some("*/15 * * * *".to_string());
+        let created: Task = db.create("tasks", &task).await?;
+        let task_id = created.id.as_ref().unwrap().id.to_raw();
+
+        assert_eq!(scheduler.tick(now).await?, 1);
+        let mut queued = queue.list(Some("pending")).await?;
+        assert_eq!(queued.len(), 1);
+        let queued = queued.remove(0);
+        assert_eq!(queued.goal, "Rotate logs");
+        assert_eq!(queued.task_id.as_deref(), Some(task_id.as_str()));
+        assert_eq!(
+            queued.source,
+            TaskSource::Cron {
+                schedule: "*/15 * * * *".to_string()
+            }
+        );
+
+        let rearmed: Task = db.select_by_id("tasks", &task_id).await?.unwrap();
+        assert!(rearmed.scheduled_for.unwrap().0 > now);
+        assert_eq!(scheduler.tick(now).await?, 0);
+
+        let fired: Vec<TimelineEvent> = db
+            .query_with(
+                "SELECT * FROM timeline_events WHERE event_type = 'task_fired'",
+                serde_json::json!({}),
+            )
+            .await?;
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].payload["skipped"], 6);
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Json, Path, State,
+        ConnectInfo, Json, Path, Query, State,
     },
     http::{HeaderMap, StatusCode},
     middleware::{self, Next},
@@ -10,14 +10,20 @@ use axum::{
     Router,
 };
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::models::TaskStatus;
+use synapse_agentic::prelude::Usage;
+
+use crate::models::{ExecutionMetrics, TaskStatus};
 use crate::services::{
-    AgentRuntime, AgentService, ProjectService, TaskService, TimelineService, WatchService,
+    AgentRuntime, AgentService, ApprovalRequest, ApprovalService, ControlRequest, ControlSignal,
+    ProjectService, RunUsage, TaskService, TimelineService, UsageGroup, UsageRow, UsageService,
+    WatchService,
 }; // Import TaskStatus
 
 #[derive(Clone)]
@@ -27,6 +33,8 @@ pub struct AppState {
     pub agent: AgentService,
     pub project: ProjectService,
     pub task: TaskService,
+    pub approvals: ApprovalService,
+    pub usage: UsageService,
     pub _watch: WatchService,
 }
 
@@ -65,9 +73,13 @@ pub struct UpdateTaskRequest {
     pub status: Option<String>, // "todo", "running", "completed", "cancelled"
 }
 
+/// One-shot `time` or recurring `cron` schedule; `cron` wins when both are set.
 #[derive(Deserialize)]
 pub struct ScheduleTaskRequest {
-    pub time: DateTime<Utc>,
+    #[serde(default)]
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub cron: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -75,6 +87,19 @@ pub struct SetModeRequest {
     pub mode: String, // "build" or "plan"
 }
 
+/// Prompt sent by a `/stream` client to get a streamed LLM reply.
+#[derive(Deserialize)]
+pub struct StreamPromptRequest {
+    pub prompt: String,
+}
+
+/// Query of `GET /usage`: grouping (default "provider") and window in days.
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    pub by: Option<String>,
+    pub days: Option<u32>,
+}
+
 #[derive(serde::Serialize)]
 pub struct ModeResponse {
     pub mode: String,
@@ -91,15 +116,25 @@ pub async fn start_server(
     watch: WatchService,
     port: u16,
 ) -> anyhow::Result<()> {
+    let approvals = ApprovalService::new(watch.db(), timeline.clone());
+    let usage = UsageService::new(watch.db());
     let state = AppState {
         runtime,
         timeline,
         agent,
         project,
         task,
+        approvals,
+        usage,
         _watch: watch,
     };
 
+    // Deciding approvals needs a token, so that the approver can be trusted.
+    let approvals = Router::new()
+        .route("/approvals/:id/approve", post(approve_endpoint))
+        .route("/approvals/:id/deny", post(deny_endpoint))
+        .route_layer(middleware::from_fn(require_api_token));
+
     let app = Router::new()
         .route("/orchestrate", post(run_orchestration))
         .route("/chat", post(chat_endpoint))
@@ -111,6 +146,12 @@ pub async fn start_server(
         .route("/tasks/:id", put(update_task).delete(delete_task))
         .route("/tasks/:id/run", post(run_task_endpoint))
         .route("/tasks/:id/schedule", post(schedule_task_endpoint))
+        .route("/tasks/:id/cancel", post(cancel_task_endpoint))
+        .route("/tasks/:id/pause", post(pause_task_endpoint))
+        .route("/tasks/:id/resume", post(resume_task_endpoint))
+        .route("/approvals", get(get_approvals))
+        .merge(approvals)
+        .route("/usage", get(get_usage))
         .route("/health", get(health_check))
         .route("/config/mode", get(get_agent_mode).post(set_agent_mode)) // Agent mode toggle
         .route("/stream", get(ws_handler))
@@ -122,13 +163,17 @@ pub async fn start_server(
     info!("🚀 Agent Server listening on {}", addr);
 
     let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
 /// Security Middleware that checks GESTALT_API_TOKEN
-async fn auth_middleware(
+pub(crate) async fn auth_middleware(
     headers: HeaderMap,
     req: axum::extract::Request,
     next: Next,
@@ -151,7 +196,8 @@ async fn auth_middleware(
 
     // Check Query Param
     if let Some(query) = req.uri().query() {
-        if query.contains(&format!("token={}", expected_token)) {
+        let expected = format!("token={}", expected_token);
+        if query.split('&').any(|pair| pair == expected) {
             authorized = true;
         }
     }
@@ -163,6 +209,26 @@ async fn auth_middleware(
     }
 }
 
+/// Refuses routes that must not be open when `GESTALT_API_TOKEN` is unset;
+/// [`auth_middleware`] checks the token itself.
+pub(crate) async fn require_api_token(
+    req: axum::extract::Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if std::env::var("GESTALT_API_TOKEN")
+        .unwrap_or_default()
+        .is_empty()
+    {
+        warn!(
+            "Refused {} {}: set GESTALT_API_TOKEN to enable it",
+            req.method(),
+            req.uri().path()
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(req).await)
+}
+
 /// WebSocket Handler for UI Real-Time Streaming
 async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
     ws.on_upgrade(move |socket| handle_socket(socket, state))
@@ -183,29 +249,133 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
         }
     }
 
-    // Stream loop
+    // Stream loop: push new timeline events, and answer `{"prompt": ...}`
+    // messages with a streamed LLM reply.
     loop {
-        // Send a ping to detect disconnections
-        if socket.send(Message::Ping(vec![])).await.is_err() {
-            break;
-        }
+        tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(request) = serde_json::from_str::<StreamPromptRequest>(&text) {
+                        if stream_reply(&mut socket, &state, &request.prompt).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            _ = tokio::time::sleep(poll_interval) => {
+                // Send a ping to detect disconnections
+                if socket.send(Message::Ping(vec![])).await.is_err() {
+                    break;
+                }
 
-        if let Ok(events) = state.timeline.get_events_since(last_check).await {
-            for event in events {
-                let ts_utc = event.timestamp.0;
-                if ts_utc > last_check {
-                    last_check = ts_utc;
+                if let Ok(events) = state.timeline.get_events_since(last_check).await {
+                    for event in events {
+                        let ts_utc = event.timestamp.0;
+                        if ts_utc > last_check {
+                            last_check = ts_utc;
+                        }
+
+                        if let Ok(json) = serde_json::to_string(&event) {
+                            if socket.send(Message::Text(json)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
                 }
+            }
+        }
+    }
+}
 
-                if let Ok(json) = serde_json::to_string(&event) {
-                    if socket.send(Message::Text(json)).await.is_err() {
-                        return;
+/// Forward an LLM reply to the socket as `llm_delta` frames, ending with
+/// `llm_done` (or `llm_error` if the provider fails midway).
+///
+/// Replies are metered like agent runs: the runtime's budget applies to each
+/// reply, and its usage, estimated from the text, is recorded under the
+/// `api-stream` agent.
+async fn stream_reply(
+    socket: &mut WebSocket,
+    state: &AppState,
+    prompt: &str,
+) -> Result<(), axum::Error> {
+    let frame = |value: serde_json::Value| Message::Text(value.to_string());
+    let error =
+        |message: String| frame(serde_json::json!({ "type": "llm_error", "message": message }));
+
+    let budget = state.runtime.budget();
+    let mut run_usage = RunUsage::default();
+    if budget.max_daily_usd.is_some() {
+        match state.usage.spent_today().await {
+            Ok(spent) => run_usage.spent_today = spent,
+            Err(e) => warn!("Could not read today's spend: {}", e),
+        }
+    }
+    if let Some(reason) = budget.exceeded(&run_usage.total(), run_usage.spent_today) {
+        return socket.send(error(reason)).await;
+    }
+
+    let engine = state.runtime.engine();
+    let (provider, cost_per_1k) = engine
+        .providers()
+        .first()
+        .map(|p| (p.name().to_string(), p.cost_per_1k_tokens()))
+        .unwrap_or_default();
+    let started_at = Utc::now();
+    let mut reply = String::new();
+    let mut failure = None;
+    let mut sent = Ok(());
+    match engine.stream_reply(prompt).await {
+        Ok(mut deltas) => {
+            while let Some(delta) = deltas.next().await {
+                let text = match delta {
+                    Ok(text) => text,
+                    Err(e) => {
+                        failure = Some(e.to_string());
+                        break;
                     }
+                };
+                reply.push_str(&text);
+                let message = serde_json::json!({ "type": "llm_delta", "text": text });
+                sent = socket.send(frame(message)).await;
+                if sent.is_err() {
+                    break;
+                }
+                let usage = Usage::estimate(&provider, prompt, &reply, cost_per_1k);
+                if let Some(reason) = budget.exceeded(&usage, run_usage.spent_today) {
+                    failure = Some(reason);
+                    break;
                 }
             }
         }
+        Err(e) => failure = Some(e.to_string()),
+    }
 
-        tokio::time::sleep(poll_interval).await;
+    if !reply.is_empty() {
+        run_usage.add(&Usage::estimate(&provider, prompt, &reply, cost_per_1k));
+    }
+    let metrics = ExecutionMetrics::from_agent_result(
+        &format!("api-stream-{}", started_at.timestamp_millis()),
+        "api-stream",
+        "stream",
+        failure.is_none() && sent.is_ok(),
+        (Utc::now() - started_at).num_milliseconds().max(0) as u64,
+        failure.clone(),
+    )
+    .with_usage(run_usage.by_provider());
+    if let Err(e) = state.usage.record(&metrics).await {
+        warn!("Failed to record stream usage: {}", e);
+    }
+
+    sent?;
+    match failure {
+        Some(message) => socket.send(error(message)).await,
+        None => {
+            socket
+                .send(frame(serde_json::json!({ "type": "llm_done" })))
+                .await
+        }
     }
 }
 
@@ -322,6 +492,91 @@ async fn delete_project(State(state): State<AppState>, Path(id): Path<String>) -
     }
 }
 
+/// Handler: List actions waiting for approval
+async fn get_approvals(State(state): State<AppState>) -> Json<Vec<ApprovalRequest>> {
+    let pending = state.approvals.list_pending().await.unwrap_or_default();
+    Json(pending)
+}
+
+/// Handler: LLM token usage and spend, grouped by agent, task, project or provider
+async fn get_usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> (StatusCode, Json<Vec<UsageRow>>) {
+    let by = query.by.as_deref().unwrap_or("provider");
+    let group = match by.parse::<UsageGroup>() {
+        Ok(group) => group,
+        Err(e) => {
+            info!("Rejected usage query: {}", e);
+            return (StatusCode::BAD_REQUEST, Json(Vec::new()));
+        }
+    };
+    let since = query
+        .days
+        .map(|d| Utc::now() - chrono::Duration::days(d as i64));
+    match state.usage.report(group, since).await {
+        Ok(rows) => (StatusCode::OK, Json(rows)),
+        Err(e) => {
+            info!("Failed to load usage: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(Vec::new()))
+        }
+    }
+}
+
+/// Handler: Approve a gated action
+async fn approve_endpoint(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Option<ApprovalRequest>>) {
+    decide_approval(&state, &id, true, &approver(&headers, addr)).await
+}
+
+/// Handler: Deny a gated action
+async fn deny_endpoint(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Option<ApprovalRequest>>) {
+    decide_approval(&state, &id, false, &approver(&headers, addr)).await
+}
+
+/// Who decided an approval: `api:<X-Gestalt-User>@<ip>`, or `api:<ip>` when
+/// the client does not name a user.
+fn approver(headers: &HeaderMap, addr: SocketAddr) -> String {
+    let user = headers
+        .get("X-Gestalt-User")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|u| !u.is_empty());
+    match user {
+        Some(user) => format!("api:{}@{}", user, addr.ip()),
+        None => format!("api:{}", addr.ip()),
+    }
+}
+
+async fn decide_approval(
+    state: &AppState,
+    id: &str,
+    approve: bool,
+    decided_by: &str,
+) -> (StatusCode, Json<Option<ApprovalRequest>>) {
+    match state.approvals.decide(id, approve, decided_by).await {
+        Ok(decided) => (StatusCode::OK, Json(Some(decided))),
+        Err(e) => {
+            info!("Failed to decide approval {}: {}", id, e);
+            let status = match state.approvals.get(id).await {
+                Ok(Some(_)) => StatusCode::CONFLICT,
+                Ok(None) => StatusCode::NOT_FOUND,
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(None))
+        }
+    }
+}
+
 /// Handler: Get all tasks (optionally filtered by project query param - simplified for now)
 async fn get_tasks(State(state): State<AppState>) -> Json<Vec<crate::models::Task>> {
     // List all tasks by iterating projects (inefficient but works for MVP) or adding list_all to TaskService
@@ -408,11 +663,12 @@ async fn schedule_task_endpoint(
     Path(id): Path<String>,
     Json(payload): Json<ScheduleTaskRequest>,
 ) -> StatusCode {
-    match state
-        .task
-        .schedule_task(&id, payload.time, "system-api")
-        .await
-    {
+    let scheduled = match (payload.cron, payload.time) {
+        (Some(cron), _) => state.task.schedule_cron(&id, &cron, "system-api").await,
+        (None, Some(time)) => state.task.schedule_task(&id, time, "system-api").await,
+        (None, None) => return StatusCode::BAD_REQUEST,
+    };
+    match scheduled {
         Ok(_) => StatusCode::OK,
         Err(e) => {
             info!("Failed to schedule task: {}", e);
@@ -421,6 +677,64 @@ async fn schedule_task_endpoint(
     }
 }
 
+/// Handler: Cancel a task and stop the agent loop running it
+async fn cancel_task_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Option<crate::models::Task>>) {
+    match state.task.cancel_task(&id, "system-api").await {
+        Ok(task) => (StatusCode::OK, Json(Some(task))),
+        Err(e) => {
+            info!("Failed to cancel task {}: {}", id, e);
+            (task_error_status(&state, &id).await, Json(None))
+        }
+    }
+}
+
+/// Handler: Pause the agent loop running a task
+async fn pause_task_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Option<ControlRequest>>) {
+    control_task(&state, &id, ControlSignal::Pause).await
+}
+
+/// Handler: Resume the paused agent loop of a task
+async fn resume_task_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Option<ControlRequest>>) {
+    control_task(&state, &id, ControlSignal::Run).await
+}
+
+async fn control_task(
+    state: &AppState,
+    id: &str,
+    signal: ControlSignal,
+) -> (StatusCode, Json<Option<ControlRequest>>) {
+    let requested = if signal == ControlSignal::Pause {
+        state.task.pause_task(id, "system-api").await
+    } else {
+        state.task.resume_task(id, "system-api").await
+    };
+    match requested {
+        Ok(request) => (StatusCode::ACCEPTED, Json(Some(request))),
+        Err(e) => {
+            info!("Failed to {} task {}: {}", signal, id, e);
+            (task_error_status(state, id).await, Json(None))
+        }
+    }
+}
+
+/// Status for a failed task control: unknown tasks are 404, finished ones 409.
+async fn task_error_status(state: &AppState, id: &str) -> StatusCode {
+    match state.task.get_by_id(id).await {
+        Ok(Some(_)) => StatusCode::CONFLICT,
+        Ok(None) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 /// Handler: Simple health check
 async fn health_check() -> StatusCode {
     StatusCode::OK
@@ -8,7 +8,10 @@ use surrealdb::sql::Thing;
 use tracing::{debug, info};
 
 use crate::db::SurrealClient;
-use crate::models::{EventType, Task, TaskResult, TaskStatus};
+use crate::models::{EventType, Task, TaskResult, TaskStatus, TimelineEvent};
+use crate::services::run_control::{ControlRequest, ControlSignal, RunControlService};
+use crate::services::scheduler::{next_occurrence, parse_cron};
+use crate::services::task_graph::{task_key, Readiness, TaskGraph};
 use crate::services::TimelineService;
 
 /// Helper to convert Option<Thing> to String
@@ -21,12 +24,18 @@ fn thing_to_string(thing: &Option<Thing>) -> String {
 pub struct TaskService {
     db: SurrealClient,
     timeline: TimelineService,
+    controls: RunControlService,
 }
 
 impl TaskService {
     /// Create a new TaskService.
     pub fn new(db: SurrealClient, timeline: TimelineService) -> Self {
-        Self { db, timeline }
+        let controls = RunControlService::new(db.clone());
+        Self {
+            db,
+            timeline,
+            controls,
+        }
     }
 
     /// Create a new task.
@@ -151,9 +160,12 @@ impl TaskService {
         Ok(result)
     }
 
-    /// Cancel a task.
+    /// Cancel a task, stopping the agent loop running it if there is one.
     pub async fn cancel_task(&self, task_id: &str, agent_id: &str) -> Result<Task> {
         let mut task = self.get_by_id(task_id).await?.context("Task not found")?;
+        self.controls
+            .request(task_key(task_id), ControlSignal::Cancel, agent_id)
+            .await?;
 
         task.status = TaskStatus::Cancelled;
         task.updated_at = crate::models::FlexibleTimestamp::now();
@@ -166,6 +178,36 @@ impl TaskService {
         Ok(updated)
     }
 
+    /// Pause the agent loop running a task between two steps.
+    pub async fn pause_task(&self, task_id: &str, agent_id: &str) -> Result<ControlRequest> {
+        self.control_task(task_id, ControlSignal::Pause, agent_id)
+            .await
+    }
+
+    /// Resume the paused agent loop of a task.
+    pub async fn resume_task(&self, task_id: &str, agent_id: &str) -> Result<ControlRequest> {
+        self.control_task(task_id, ControlSignal::Run, agent_id)
+            .await
+    }
+
+    async fn control_task(
+        &self,
+        task_id: &str,
+        signal: ControlSignal,
+        agent_id: &str,
+    ) -> Result<ControlRequest> {
+        let task = self.get_by_id(task_id).await?.context("Task not found")?;
+        if matches!(
+            task.status,
+            TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+        ) {
+            anyhow::bail!("Task '{}' is already {:?}", task_id, task.status);
+        }
+        self.controls
+            .request(task_key(task_id), signal, agent_id)
+            .await
+    }
+
     /// Update task details.
     pub async fn update_task(
         &self,
@@ -196,6 +238,63 @@ impl TaskService {
         Ok(updated)
     }
 
+    /// Make a task depend on `depends_on`, rejecting unknown tasks and cycles.
+    pub async fn set_dependencies(
+        &self,
+        task_id: &str,
+        depends_on: &[String],
+        agent_id: &str,
+    ) -> Result<Task> {
+        let task_id = task_key(task_id);
+        let mut task = self.get_by_id(task_id).await?.context("Task not found")?;
+        let depends_on: Vec<String> = depends_on.iter().map(|d| task_key(d).to_string()).collect();
+
+        let mut graph = TaskGraph::new(&self.project_tasks(&task.project_id).await?);
+        if let Some(unknown) = depends_on.iter().find(|d| graph.get(d).is_none()) {
+            anyhow::bail!("Dependency '{}' is not a task of the same project", unknown);
+        }
+        graph.set_dependencies(task_id, &depends_on);
+        if let Some(cycle) = graph.find_cycle() {
+            anyhow::bail!("Dependency cycle: {}", cycle.join(" → "));
+        }
+
+        task.depends_on = depends_on;
+        task.updated_at = crate::models::FlexibleTimestamp::now();
+        let updated = self.db.update("tasks", task_id, &task).await?;
+
+        self.timeline
+            .emit_task_event(agent_id, EventType::TaskUpdated, &task.project_id, task_id)
+            .await?;
+
+        Ok(updated)
+    }
+
+    /// Whether a task may run, given the status of its dependencies.
+    pub async fn readiness(&self, task_id: &str) -> Result<Readiness> {
+        let task = self
+            .get_by_id(task_key(task_id))
+            .await?
+            .context("Task not found")?;
+        TaskGraph::new(&self.project_tasks(&task.project_id).await?)
+            .readiness(task_id)
+            .context("Task not found")
+    }
+
+    /// Dependency graph of a project's tasks.
+    pub async fn graph(&self, project_name: &str) -> Result<TaskGraph> {
+        let tasks = self.list_tasks(Some(project_name)).await?;
+        Ok(TaskGraph::new(&tasks))
+    }
+
+    async fn project_tasks(&self, project_id: &str) -> Result<Vec<Task>> {
+        self.db
+            .query_with(
+                "SELECT * FROM tasks WHERE project_id = $project_id",
+                ("project_id", project_id),
+            )
+            .await
+    }
+
     /// Delete a task.
     pub async fn delete_task(&self, task_id: &str, agent_id: &str) -> Result<()> {
         let task = self.get_by_id(task_id).await?.context("Task not found")?;
@@ -209,28 +308,56 @@ impl TaskService {
         Ok(())
     }
 
-    /// Schedule a task.
+    /// Schedule a task to be fired once at `execute_at`.
     pub async fn schedule_task(
         &self,
         task_id: &str,
         execute_at: DateTime<Utc>,
         agent_id: &str,
     ) -> Result<Task> {
-        let mut task = self.get_by_id(task_id).await?.context("Task not found")?;
+        self.set_schedule(task_id, execute_at, None, agent_id).await
+    }
 
-        // In a real system, we would insert this into a scheduling queue.
-        // For now, we just update the metadata.
-        // Note: Task struct might need a `scheduled_for` field if we want to persist it.
-        // For this iteration, we'll just log it and update status.
+    /// Schedule a task to be fired on every occurrence of a cron `expression`.
+    pub async fn schedule_cron(
+        &self,
+        task_id: &str,
+        expression: &str,
+        agent_id: &str,
+    ) -> Result<Task> {
+        let cron = parse_cron(expression)?;
+        let first_run = next_occurrence(&cron, Utc::now())?;
+        self.set_schedule(task_id, first_run, Some(expression.to_string()), agent_id)
+            .await
+    }
+
+    /// Persist the next run of a task; `SchedulerService` fires it when due.
+    async fn set_schedule(
+        &self,
+        task_id: &str,
+        execute_at: DateTime<Utc>,
+        cron: Option<String>,
+        agent_id: &str,
+    ) -> Result<Task> {
+        let mut task = self.get_by_id(task_id).await?.context("Task not found")?;
 
         info!("Scheduling task {} for {}", task_id, execute_at);
+        task.scheduled_for = Some(execute_at.into());
+        task.cron = cron;
         task.updated_at = crate::models::FlexibleTimestamp::now();
 
         let updated = self.db.update("tasks", task_id, &task).await?;
 
-        // Emit scheduled event (might need a new EventType, using Updated for now)
         self.timeline
-            .emit_task_event(agent_id, EventType::TaskUpdated, &task.project_id, task_id)
+            .record_event(
+                TimelineEvent::new(agent_id, EventType::TaskScheduled)
+                    .with_project(&task.project_id)
+                    .with_task(task_id)
+                    .with_payload(serde_json::json!({
+                        "scheduled_for": execute_at,
+                        "cron": task.cron,
+                    })),
+            )
             .await?;
 
         Ok(updated)
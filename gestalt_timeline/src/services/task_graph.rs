@@ -0,0 +1,342 @@
+//! Task Graph - the dependency DAG between tasks of a project.
+//!
+//! Tasks declare `depends_on` (IDs of other tasks). A task is ready once every
+//! dependency is `Completed`; the graph also rejects cycles and finds the
+//! critical path through recorded `duration_ms` timings.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{Task, TaskStatus};
+
+/// Record key of a task ID, accepting both `abc` and `tasks:abc`.
+pub fn task_key(id: &str) -> &str {
+    id.strip_prefix("tasks:").unwrap_or(id)
+}
+
+/// Whether a task may run given the state of its dependencies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Readiness {
+    /// Every dependency is completed.
+    Ready,
+    /// Some dependencies have not completed yet.
+    Blocked { waiting_on: Vec<String> },
+    /// Some dependencies failed, were cancelled or no longer exist.
+    DependencyFailed { failed: Vec<String> },
+}
+
+impl Readiness {
+    /// Readiness of a task depending on `depends_on`, given known task statuses.
+    pub fn of(depends_on: &[String], statuses: &HashMap<String, TaskStatus>) -> Self {
+        let mut waiting_on = Vec::new();
+        let mut failed = Vec::new();
+        for dep in depends_on {
+            match statuses.get(task_key(dep)) {
+                Some(TaskStatus::Completed) => {}
+                Some(TaskStatus::Pending | TaskStatus::Running) => waiting_on.push(dep.clone()),
+                Some(TaskStatus::Failed | TaskStatus::Cancelled) | None => failed.push(dep.clone()),
+            }
+        }
+        if !failed.is_empty() {
+            Readiness::DependencyFailed { failed }
+        } else if !waiting_on.is_empty() {
+            Readiness::Blocked { waiting_on }
+        } else {
+            Readiness::Ready
+        }
+    }
+}
+
+/// A task as seen by the graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskNode {
+    pub id: String,
+    /// External ID when synced from a protocol file, else the description
+    pub label: String,
+    pub status: TaskStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    pub depends_on: Vec<String>,
+}
+
+/// Longest chain of dependent tasks by recorded duration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CriticalPath {
+    /// Task IDs, first dependency first
+    pub tasks: Vec<String>,
+    /// Sum of `duration_ms` along the path; tasks that never ran count as 0
+    pub duration_ms: u64,
+}
+
+/// Dependency graph over a set of tasks, in the order they were given.
+#[derive(Debug, Clone, Default)]
+pub struct TaskGraph {
+    nodes: Vec<TaskNode>,
+    index: HashMap<String, usize>,
+}
+
+impl TaskGraph {
+    pub fn new(tasks: &[Task]) -> Self {
+        let mut graph = Self::default();
+        for task in tasks {
+            let Some(id) = task.id.as_ref().map(|t| t.id.to_raw()) else {
+                continue;
+            };
+            graph.index.insert(id.clone(), graph.nodes.len());
+            graph.nodes.push(TaskNode {
+                id,
+                label: task
+                    .external_id
+                    .clone()
+                    .unwrap_or_else(|| task.description.clone()),
+                status: task.status.clone(),
+                duration_ms: task.duration_ms,
+                depends_on: task
+                    .depends_on
+                    .iter()
+                    .map(|d| task_key(d).to_string())
+                    .collect(),
+            });
+        }
+        graph
+    }
+
+    pub fn nodes(&self) -> &[TaskNode] {
+        &self.nodes
+    }
+
+    pub fn get(&self, id: &str) -> Option<&TaskNode> {
+        self.index.get(task_key(id)).map(|&i| &self.nodes[i])
+    }
+
+    /// Replace the dependencies of `id`, e.g. to validate an edit before saving it.
+    pub fn set_dependencies(&mut self, id: &str, depends_on: &[String]) {
+        if let Some(&i) = self.index.get(task_key(id)) {
+            self.nodes[i].depends_on = depends_on.iter().map(|d| task_key(d).to_string()).collect();
+        }
+    }
+
+    /// A dependency cycle, as the IDs along it with the first repeated at the end.
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+        let mut marks = vec![Mark::New; self.nodes.len()];
+        for start in 0..self.nodes.len() {
+            if marks[start] != Mark::New {
+                continue;
+            }
+            // Iterative DFS keeping the current path and each node's next edge.
+            let mut path: Vec<(usize, usize)> = vec![(start, 0)];
+            marks[start] = Mark::OnPath;
+            while let Some((node, edge)) = path.last_mut() {
+                let node = *node;
+                let Some(dep) = self.nodes[node].depends_on.get(*edge) else {
+                    marks[node] = Mark::Done;
+                    path.pop();
+                    continue;
+                };
+                *edge += 1;
+                let Some(&next) = self.index.get(dep) else {
+                    continue;
+                };
+                match marks[next] {
+                    Mark::New => {
+                        marks[next] = Mark::OnPath;
+                        path.push((next, 0));
+                    }
+                    Mark::OnPath => {
+                        let from = path.iter().position(|(n, _)| *n == next).unwrap_or(0);
+                        let mut cycle: Vec<String> = path[from..]
+                            .iter()
+                            .map(|(n, _)| self.nodes[*n].id.clone())
+                            .collect();
+                        cycle.push(self.nodes[next].id.clone());
+                        return Some(cycle);
+                    }
+                    Mark::Done => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// Readiness of `id` from the statuses of its dependencies in this graph.
+    pub fn readiness(&self, id: &str) -> Option<Readiness> {
+        let statuses: HashMap<String, TaskStatus> = self
+            .nodes
+            .iter()
+            .map(|n| (n.id.clone(), n.status.clone()))
+            .collect();
+        self.get(id)
+            .map(|n| Readiness::of(&n.depends_on, &statuses))
+    }
+
+    /// Nodes with every dependency before its dependents; nodes on a cycle are left out.
+    pub fn topological_order(&self) -> Vec<&TaskNode> {
+        let mut remaining: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| {
+                n.depends_on
+                    .iter()
+                    .filter(|d| self.index.contains_key(*d))
+                    .count()
+            })
+            .collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dep in &node.depends_on {
+                if let Some(&d) = self.index.get(dep) {
+                    dependents[d].push(i);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut emitted = HashSet::new();
+        let mut frontier: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| remaining[i] == 0)
+            .rev()
+            .collect();
+        while let Some(i) = frontier.pop() {
+            if !emitted.insert(i) {
+                continue;
+            }
+            order.push(&self.nodes[i]);
+            for &dependent in dependents[i].iter().rev() {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    frontier.push(dependent);
+                }
+            }
+        }
+        order
+    }
+
+    /// The chain of dependencies with the largest total `duration_ms`.
+    pub fn critical_path(&self) -> CriticalPath {
+        let mut best: HashMap<&str, (u64, Option<&str>)> = HashMap::new();
+        for node in self.topological_order() {
+            let (before, previous) = node
+                .depends_on
+                .iter()
+                .filter_map(|d| best.get(d.as_str()).map(|(total, _)| (*total, d.as_str())))
+                .max_by_key(|(total, _)| *total)
+                .map_or((0, None), |(total, dep)| (total, Some(dep)));
+            best.insert(
+                node.id.as_str(),
+                (before + node.duration_ms.unwrap_or(0), previous),
+            );
+        }
+
+        let Some((&end, &(duration_ms, _))) = self
+            .nodes
+            .iter()
+            .filter_map(|n| best.get_key_value(n.id.as_str()))
+            .max_by_key(|(_, (total, _))| *total)
+        else {
+            return CriticalPath::default();
+        };
+        let mut tasks = vec![end.to_string()];
+        let mut current = end;
+        while let Some(&(_, Some(previous))) = best.get(current) {
+            tasks.push(previous.to_string());
+            current = previous;
+        }
+        tasks.reverse();
+        CriticalPath { tasks, duration_ms }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use surrealdb::sql::Thing;
+
+    fn task(id: &str, status: TaskStatus, duration_ms: Option<u64>, deps: &[&str]) -> Task {
+        let mut task = Task::new("projects:demo", id, "tester", Some(id.to_uppercase()));
+        task.id = Some(Thing::from(("tasks", id)));
+        task.status = status;
+        task.duration_ms = duration_ms;
+        task.depends_on = deps.iter().map(|d| d.to_string()).collect();
+        task
+    }
+
+    #[test]
+    fn test_readiness_follows_dependency_status() {
+        let graph = TaskGraph::new(&[
+            task("f1", TaskStatus::Completed, Some(100), &[]),
+            task("f2", TaskStatus::Pending, None, &["f1"]),
+            task("f3", TaskStatus::Pending, None, &["tasks:f1", "f2"]),
+            task("f4", TaskStatus::Pending, None, &["gone"]),
+        ]);
+        assert_eq!(graph.readiness("f2"), Some(Readiness::Ready));
+        assert_eq!(
+            graph.readiness("tasks:f3"),
+            Some(Readiness::Blocked {
+                waiting_on: vec!["f2".to_string()]
+            })
+        );
+        assert_eq!(
+            graph.readiness("f4"),
+            Some(Readiness::DependencyFailed {
+                failed: vec!["gone".to_string()]
+            })
+        );
+        assert_eq!(graph.readiness("missing"), None);
+    }
+
+    #[test]
+    fn test_cycles_are_detected() {
+        let mut graph = TaskGraph::new(&[
+            task("a", TaskStatus::Pending, None, &[]),
+            task("b", TaskStatus::Pending, None, &["a"]),
+            task("c", TaskStatus::Pending, None, &["b"]),
+        ]);
+        assert_eq!(graph.find_cycle(), None);
+
+        graph.set_dependencies("a", &["c".to_string()]);
+        assert_eq!(
+            graph.find_cycle(),
+            Some(vec![
+                "a".to_string(),
+                "c".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ])
+        );
+
+        graph.set_dependencies("a", &["a".to_string()]);
+        assert_eq!(
+            graph.find_cycle(),
+            Some(vec!["a".to_string(), "a".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_critical_path_uses_durations() {
+        // a(100) -> b(500) -> d(50)
+        //        \-> c(200) -/
+        let graph = TaskGraph::new(&[
+            task("d", TaskStatus::Pending, Some(50), &["b", "c"]),
+            task("c", TaskStatus::Completed, Some(200), &["a"]),
+            task("b", TaskStatus::Completed, Some(500), &["a"]),
+            task("a", TaskStatus::Completed, Some(100), &[]),
+        ]);
+        let order: Vec<&str> = graph
+            .topological_order()
+            .iter()
+            .map(|n| n.id.as_str())
+            .collect();
+        assert_eq!(order, vec!["a", "c", "b", "d"]);
+
+        let path = graph.critical_path();
+        assert_eq!(path.tasks, vec!["a", "b", "d"]);
+        assert_eq!(path.duration_ms, 650);
+    }
+}
@@ -1,16 +1,37 @@
 //! TaskQueue - Multi-source task ingestion and dispatch.
 //!
 //! Accepts tasks from: Telegram, CLI, REST API, webhooks, Cron.
-//! Persists them to SurrealDB and dispatches to available AgentRuntime workers.
+//! SurrealDB is the queue: workers claim the highest-priority, oldest pending
+//! entry under a lease they renew by heartbeat, so entries of a crashed worker
+//! are requeued once the lease expires. Failed attempts are retried with
+//! exponential backoff until the entry's attempt budget is spent, after which
+//! it is parked as a dead letter until requeued or cancelled.
+//! Runs of timeline tasks are held back until the task's dependencies complete.
+//! Cancelling a running entry stops its agent loop through a run control request.
 
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::sync::Arc;
-use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, error, info, warn};
 
 use crate::db::SurrealClient;
+use crate::models::{FlexibleTimestamp, Task, TaskStatus};
+use crate::services::run_control::{ControlSignal, RunCancelled, RunControl, RunControlService};
+use crate::services::task_graph::{task_key, Readiness};
+use crate::services::{TaskService, TimelineService};
+
+/// Attempts an entry gets unless it asks for a different budget.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; doubles with each further attempt.
+const RETRY_BASE_SECS: i64 = 30;
+/// Longest delay between two attempts.
+const RETRY_MAX_SECS: i64 = 3600;
+/// Prefix of the agent IDs dispatch workers run under.
+const WORKER_PREFIX: &str = "worker-";
 
 /// Where a task originated from
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -36,8 +57,30 @@ pub struct QueuedTask {
     pub queued_at: chrono::DateTime<Utc>,
     /// Processing state
     pub status: QueueStatus,
-    /// Agent assigned to run this task (set when dispatched)
+    /// Worker holding (or last holding) the lease on this task
     pub assigned_agent_id: Option<String>,
+    /// Timeline task this run executes, if any; gates dispatch on its dependencies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// Attempts started so far
+    #[serde(default)]
+    pub attempts: u32,
+    /// Attempts allowed before the task is dead-lettered
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Not dispatched before this time, while backing off after a failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_at: Option<DateTime<Utc>>,
+    /// When the running worker's lease lapses unless renewed by a heartbeat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// Error of the most recent failed attempt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_MAX_ATTEMPTS
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -45,7 +88,28 @@ pub enum QueueStatus {
     Pending,
     Running,
     Completed,
-    Failed { reason: String },
+    /// Failed before retry budgets existed; requeue it like a dead letter
+    Failed {
+        reason: String,
+    },
+    /// Out of attempts; kept for inspection until requeued or cancelled
+    DeadLetter {
+        reason: String,
+    },
+    Cancelled,
+}
+
+impl fmt::Display for QueueStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueStatus::Pending => write!(f, "pending"),
+            QueueStatus::Running => write!(f, "running"),
+            QueueStatus::Completed => write!(f, "completed"),
+            QueueStatus::Failed { .. } => write!(f, "failed"),
+            QueueStatus::DeadLetter { .. } => write!(f, "dead_letter"),
+            QueueStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
 }
 
 impl QueuedTask {
@@ -58,156 +122,779 @@ impl QueuedTask {
             queued_at: Utc::now(),
             status: QueueStatus::Pending,
             assigned_agent_id: None,
+            task_id: None,
+            attempts: 0,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            available_at: None,
+            lease_expires_at: None,
+            last_error: None,
         }
     }
+
+    /// Run the timeline task `task_id`, once its dependencies have completed.
+    pub fn with_task(mut self, task_id: impl Into<String>) -> Self {
+        self.task_id = Some(task_id.into());
+        self
+    }
+
+    /// Allow `max_attempts` attempts before dead-lettering the task.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Record key of this entry, as accepted by `gestalt queue` subcommands.
+    pub fn key(&self) -> Option<String> {
+        self.id.as_ref().map(|t| t.id.to_raw())
+    }
+}
+
+/// Delay before retrying a task that has failed `attempts` times.
+pub fn retry_backoff(attempts: u32) -> chrono::Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    chrono::Duration::seconds((RETRY_BASE_SECS << exponent).min(RETRY_MAX_SECS))
+}
+
+/// Whether `agent_id` is a dispatch worker's. The queue retries the entry of an
+/// interrupted worker itself, so its runtime must not be resumed as well.
+pub fn is_worker_id(agent_id: &str) -> bool {
+    agent_id.starts_with(WORKER_PREFIX)
+}
+
+/// Record key of a queue entry ID, accepting both `abc` and `task_queue:abc`.
+fn entry_key(id: &str) -> &str {
+    id.strip_prefix("task_queue:").unwrap_or(id)
 }
 
 /// The TaskQueue receives tasks from all ingestion sources and dispatches them
-/// to `AgentRuntime` workers via mpsc channels.
+/// to `AgentRuntime` workers.
 #[derive(Clone)]
 pub struct TaskQueue {
     db: SurrealClient,
-    /// Channel sender — any ingestion source can clone and push tasks here
-    sender: mpsc::Sender<QueuedTask>,
+    /// Stops the agent loops of running entries that get cancelled
+    controls: RunControlService,
+    /// Decides whether the timeline task behind an entry may run
+    tasks: TaskService,
+    /// Wakes the dispatch loop when work is enqueued or a worker frees up
+    wake: Arc<Notify>,
+    lease: Duration,
+    poll_interval: Duration,
 }
 
 impl TaskQueue {
-    /// Create a new TaskQueue. Returns (queue, receiver).
-    /// The caller is responsible for running the dispatch loop with the receiver.
-    pub fn new(db: SurrealClient, buffer: usize) -> (Self, mpsc::Receiver<QueuedTask>) {
-        let (sender, receiver) = mpsc::channel(buffer);
-        let queue = Self { db, sender };
-        (queue, receiver)
+    pub fn new(db: SurrealClient) -> Self {
+        Self {
+            controls: RunControlService::new(db.clone()),
+            tasks: TaskService::new(db.clone(), TimelineService::new(db.clone())),
+            db,
+            wake: Arc::new(Notify::new()),
+            lease: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// Set how long a claim is valid without a heartbeat.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Set how often the dispatch loop looks for work it was not woken for.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
     }
 
     /// Enqueue a task — thread-safe, can be called from any ingestion source.
-    pub async fn enqueue(&self, task: QueuedTask) -> Result<()> {
+    pub async fn enqueue(&self, task: QueuedTask) -> Result<QueuedTask> {
         info!(
             "📥 [TaskQueue] Enqueuing task: '{}' (source: {:?}, priority: {})",
-            &task.goal[..task.goal.len().min(80)],
+            task.goal.chars().take(80).collect::<String>(),
             task.source,
             task.priority
         );
 
-        // Persist to SurrealDB for durability
-        let _saved = self
+        let saved = self
             .db
             .create("task_queue", &task)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to persist queued task: {}", e))?;
+        self.wake.notify_one();
+        Ok(saved)
+    }
 
-        // Send to dispatch channel
-        self.sender
-            .send(task)
-            .await
-            .map_err(|e| anyhow::anyhow!("TaskQueue channel closed: {}", e))?;
+    /// Get a queue entry by ID.
+    pub async fn get(&self, id: &str) -> Result<Option<QueuedTask>> {
+        self.db.select_by_id("task_queue", entry_key(id)).await
+    }
 
-        Ok(())
+    /// Queue entries in dispatch order, optionally only those whose status is
+    /// named `status` (e.g. `pending` or `dead_letter`).
+    pub async fn list(&self, status: Option<&str>) -> Result<Vec<QueuedTask>> {
+        let mut entries: Vec<QueuedTask> = self.db.select_all("task_queue").await?;
+        if let Some(status) = status {
+            entries.retain(|e| e.status.to_string() == status);
+        }
+        sort_for_dispatch(&mut entries);
+        Ok(entries)
     }
 
-    /// Create a clone of the sender for use in ingestion sources (Telegram, API, etc.)
-    pub fn sender(&self) -> mpsc::Sender<QueuedTask> {
-        self.sender.clone()
+    /// Stop a pending or running task, or drop a dead letter.
+    pub async fn cancel(&self, id: &str) -> Result<QueuedTask> {
+        let running = self
+            .get(id)
+            .await?
+            .filter(|e| e.status == QueueStatus::Running)
+            .and_then(|e| e.assigned_agent_id);
+        let cancelled = self
+            .transition(
+                id,
+                "SET status = 'Cancelled', lease_expires_at = NONE \
+                 WHERE status != 'Completed' AND status != 'Cancelled'",
+                "is already finished",
+            )
+            .await?;
+        if let Some(worker) = running {
+            self.controls
+                .request(&worker, ControlSignal::Cancel, "task-queue")
+                .await?;
+        }
+        Ok(cancelled)
     }
 
-    /// Load any pending tasks from SurrealDB (useful after restart).
-    pub async fn recover_pending(&self) -> Result<Vec<QueuedTask>> {
-        let pending: Vec<QueuedTask> = self.db
-            .query_with("SELECT * FROM task_queue WHERE status = 'Pending' ORDER BY priority DESC, queued_at ASC LIMIT 100", serde_json::json!({}))
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to recover pending tasks: {}", e))?;
+    /// Give a finished, cancelled or dead-lettered task a fresh attempt budget.
+    pub async fn requeue(&self, id: &str) -> Result<QueuedTask> {
+        let requeued = self
+            .transition(
+                id,
+                "SET status = 'Pending', attempts = 0, available_at = NONE, \
+                 lease_expires_at = NONE, assigned_agent_id = NONE \
+                 WHERE status != 'Pending' AND status != 'Running'",
+                "is still queued",
+            )
+            .await?;
+        self.wake.notify_one();
+        Ok(requeued)
+    }
 
-        info!(
-            "🔄 [TaskQueue] Recovered {} pending tasks from DB",
-            pending.len()
+    async fn transition(&self, id: &str, update: &str, refused: &str) -> Result<QueuedTask> {
+        let updated: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                &format!(
+                    "UPDATE type::thing('task_queue', $id) {} RETURN AFTER",
+                    update
+                ),
+                serde_json::json!({ "id": entry_key(id) }),
+            )
+            .await?;
+        if let Some(entry) = updated.into_iter().next() {
+            return Ok(entry);
+        }
+        match self.get(id).await? {
+            Some(entry) => Err(anyhow::anyhow!(
+                "Queue entry '{}' {} ({})",
+                id,
+                refused,
+                entry.status
+            )),
+            None => Err(anyhow::anyhow!("Queue entry '{}' not found", id)),
+        }
+    }
+
+    /// Claim the next ready task for `worker`: highest priority first, then
+    /// oldest. Returns `None` when nothing is ready.
+    pub async fn claim(&self, worker: &str) -> Result<Option<QueuedTask>> {
+        let now = Utc::now();
+        let mut pending: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                "SELECT * FROM task_queue WHERE status = 'Pending'",
+                serde_json::json!({}),
+            )
+            .await?;
+        pending.retain(|t| t.available_at.is_none_or(|at| at <= now));
+        sort_for_dispatch(&mut pending);
+
+        for candidate in pending {
+            let Some(key) = candidate.key() else {
+                continue;
+            };
+            if let Some(timeline_task) = &candidate.task_id {
+                match self.tasks.readiness(timeline_task).await {
+                    Ok(Readiness::Ready) => {}
+                    Ok(Readiness::Blocked { waiting_on }) => {
+                        debug!(
+                            "⏸️ [TaskQueue] Task {} waits on {}",
+                            timeline_task,
+                            waiting_on.join(", ")
+                        );
+                        continue;
+                    }
+                    Ok(Readiness::DependencyFailed { failed }) => {
+                        let reason =
+                            format!("Dependencies did not complete: {}", failed.join(", "));
+                        warn!("⛔ [TaskQueue] Dead-lettering {}: {}", key, reason);
+                        let _: Vec<QueuedTask> = self
+                            .db
+                            .query_with(
+                                "UPDATE type::thing('task_queue', $id) SET status = $status \
+                                 WHERE status = 'Pending'",
+                                serde_json::json!({
+                                    "id": key,
+                                    "status": QueueStatus::DeadLetter { reason },
+                                }),
+                            )
+                            .await?;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "⚠️ [TaskQueue] Cannot check dependencies of task {}: {}",
+                            timeline_task, e
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            // Only one worker wins the conditional update.
+            let claimed: Vec<QueuedTask> = self
+                .db
+                .query_with(
+                    "UPDATE type::thing('task_queue', $id) \
+                     SET status = 'Running', assigned_agent_id = $worker, \
+                     attempts = (attempts OR 0) + 1, lease_expires_at = $lease \
+                     WHERE status = 'Pending' RETURN AFTER",
+                    serde_json::json!({
+                        "id": key,
+                        "worker": worker,
+                        "lease": self.lease_deadline(),
+                    }),
+                )
+                .await?;
+            if let Some(entry) = claimed.into_iter().next() {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Renew the lease on a claimed task. Returns `false` once the lease was
+    /// lost, e.g. because the task expired, was cancelled or was requeued.
+    pub async fn heartbeat(&self, entry: &QueuedTask) -> Result<bool> {
+        let renewed: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                "UPDATE type::thing('task_queue', $id) SET lease_expires_at = $lease \
+                 WHERE status = 'Running' AND assigned_agent_id = $worker RETURN AFTER",
+                serde_json::json!({
+                    "id": entry.key().context("Queue entry has no ID")?,
+                    "worker": entry.assigned_agent_id,
+                    "lease": self.lease_deadline(),
+                }),
+            )
+            .await?;
+        Ok(!renewed.is_empty())
+    }
+
+    /// Mark a claimed task as completed. Returns `false` if the worker no
+    /// longer held the task, e.g. because it was requeued or cancelled.
+    pub async fn complete(&self, entry: &QueuedTask) -> Result<bool> {
+        let completed: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                "UPDATE type::thing('task_queue', $id) \
+                 SET status = 'Completed', lease_expires_at = NONE, last_error = NONE \
+                 WHERE status = 'Running' AND assigned_agent_id = $worker RETURN AFTER",
+                serde_json::json!({
+                    "id": entry.key().context("Queue entry has no ID")?,
+                    "worker": entry.assigned_agent_id,
+                }),
+            )
+            .await?;
+        Ok(!completed.is_empty())
+    }
+
+    /// Record a failed attempt of a claimed task: it is retried after a
+    /// backoff while attempts remain, and dead-lettered after that. Returns
+    /// the new status, or `None` if the worker no longer held the task.
+    pub async fn fail(&self, entry: &QueuedTask, error: &str) -> Result<Option<QueueStatus>> {
+        self.record_failure(entry, error, None).await
+    }
+
+    /// [`fail`](Self::fail), but only if the lease of `entry` lapsed before
+    /// `expired_before`, so that a heartbeat racing the requeue keeps the task.
+    async fn record_failure(
+        &self,
+        entry: &QueuedTask,
+        error: &str,
+        expired_before: Option<DateTime<Utc>>,
+    ) -> Result<Option<QueueStatus>> {
+        let (status, available_at) = if entry.attempts < entry.max_attempts {
+            (
+                QueueStatus::Pending,
+                Some(Utc::now() + retry_backoff(entry.attempts)),
+            )
+        } else {
+            (
+                QueueStatus::DeadLetter {
+                    reason: error.to_string(),
+                },
+                None,
+            )
+        };
+        let mut sql = String::from(
+            "UPDATE type::thing('task_queue', $id) \
+             SET status = $status, available_at = $available_at, last_error = $error, \
+             lease_expires_at = NONE \
+             WHERE status = 'Running' AND assigned_agent_id = $worker",
         );
-        Ok(pending)
+        if expired_before.is_some() {
+            sql.push_str(
+                " AND (lease_expires_at = NONE \
+                 OR type::datetime(lease_expires_at) < type::datetime($expired_before))",
+            );
+        }
+        sql.push_str(" RETURN AFTER");
+        let updated: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                &sql,
+                serde_json::json!({
+                    "id": entry.key().context("Queue entry has no ID")?,
+                    "worker": entry.assigned_agent_id,
+                    "status": status,
+                    "available_at": available_at,
+                    "error": error,
+                    "expired_before": expired_before,
+                }),
+            )
+            .await?;
+        if status == QueueStatus::Pending {
+            self.wake.notify_one();
+        }
+        Ok(updated.into_iter().next().map(|e| e.status))
+    }
+
+    /// Treat running tasks whose lease lapsed before `now` as failed attempts
+    /// of a crashed worker. Returns how many were released.
+    pub async fn requeue_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        let running: Vec<QueuedTask> = self
+            .db
+            .query_with(
+                "SELECT * FROM task_queue WHERE status = 'Running'",
+                serde_json::json!({}),
+            )
+            .await?;
+
+        let mut released = 0;
+        for entry in running
+            .iter()
+            .filter(|e| e.lease_expires_at.is_none_or(|at| at < now))
+        {
+            let worker = entry.assigned_agent_id.as_deref().unwrap_or("unknown");
+            warn!(
+                "⌛ [TaskQueue] Lease of {} held by {} expired",
+                entry.key().unwrap_or_default(),
+                worker
+            );
+            let reason = format!("Lease expired; worker {} stopped heartbeating", worker);
+            if self
+                .record_failure(entry, &reason, Some(now))
+                .await?
+                .is_some()
+            {
+                released += 1;
+            }
+        }
+        Ok(released)
     }
 
-    /// Start the dispatch loop. Reads tasks from the channel and runs AgentRuntime for each.
+    fn lease_deadline(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::milliseconds(self.lease.as_millis() as i64)
+    }
+
+    /// Record the final outcome of a run on the timeline task it executed, so
+    /// that dependents become ready once it completed.
+    async fn record_task_outcome(
+        &self,
+        task_id: &str,
+        status: TaskStatus,
+        agent_id: &str,
+        duration: Duration,
+    ) -> Result<()> {
+        let now = FlexibleTimestamp::now();
+        let _: Vec<Task> = self
+            .db
+            .query_with(
+                "UPDATE type::thing('tasks', $id) SET status = $status, \
+                 executed_by = $agent, duration_ms = $duration_ms, \
+                 updated_at = $now, completed_at = $now",
+                serde_json::json!({
+                    "id": task_key(task_id),
+                    "status": status,
+                    "agent": agent_id,
+                    "duration_ms": duration.as_millis() as u64,
+                    "now": now,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Start the dispatch loop. Claims ready tasks from SurrealDB while fewer
+    /// than `max_concurrent` workers are busy and runs AgentRuntime for each.
     /// This is the main always-on worker loop.
     ///
     /// `make_runtime` is a factory closure that creates a fresh `AgentRuntime` for each task.
-    pub async fn run_dispatch_loop<F, Fut>(
-        &self,
-        mut receiver: mpsc::Receiver<QueuedTask>,
-        max_concurrent: usize,
-        make_runtime: F,
-    ) where
+    pub async fn run_dispatch_loop<F, Fut>(&self, max_concurrent: usize, make_runtime: F)
+    where
         F: Fn(String) -> Fut + Send + Sync + Clone + 'static,
         Fut: std::future::Future<Output = Result<crate::services::AgentRuntime>> + Send + 'static,
     {
         info!(
-            "🚦 [TaskQueue] Dispatch loop started (max_concurrent={})",
-            max_concurrent
+            "🚦 [TaskQueue] Dispatch loop started (max_concurrent={}, lease={:?})",
+            max_concurrent, self.lease
         );
 
-        let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrent));
-        let db = self.db.clone();
-
-        while let Some(task) = receiver.recv().await {
-            let goal = task.goal.clone();
-            let task_id = task
-                .id
-                .as_ref()
-                .map(|t| t.to_string())
-                .unwrap_or_else(|| "unknown".to_string());
-            let sem = semaphore.clone();
-            let factory = make_runtime.clone();
-            let db_clone = db.clone();
-
-            info!(
-                "🎯 [TaskQueue] Dispatching task '{}' (id={})",
-                &goal[..goal.len().min(60)],
-                task_id
-            );
+        let semaphore = Arc::new(Semaphore::new(max_concurrent));
+        loop {
+            match self.requeue_expired(Utc::now()).await {
+                Ok(0) => {}
+                Ok(released) => info!("🔁 [TaskQueue] Requeued {} expired tasks", released),
+                Err(e) => warn!("⚠️ [TaskQueue] Failed to requeue expired tasks: {}", e),
+            }
 
-            tokio::spawn(async move {
-                // Acquire semaphore slot (limits concurrency)
-                let _permit = match sem.acquire().await {
-                    Ok(p) => p,
+            while let Ok(permit) = semaphore.clone().try_acquire_owned() {
+                let agent_id = format!(
+                    "{}{}",
+                    WORKER_PREFIX,
+                    ulid::Ulid::new().to_string().to_lowercase()
+                );
+                match self.claim(&agent_id).await {
+                    Ok(Some(entry)) => {
+                        self.spawn_worker(entry, agent_id, permit, make_runtime.clone())
+                    }
+                    Ok(None) => break,
                     Err(e) => {
-                        error!("Semaphore error: {}", e);
-                        return;
+                        error!("❌ [TaskQueue] Failed to claim a task: {}", e);
+                        break;
                     }
-                };
-
-                // Build agent runtime for this task
-                let agent_id = format!("worker-{}", chrono::Utc::now().timestamp_millis());
-                match factory(agent_id.clone()).await {
-                    Ok(runtime) => {
-                        info!(
-                            "🤖 [TaskQueue] Agent '{}' starting goal: {}",
-                            agent_id,
-                            &goal[..goal.len().min(60)]
-                        );
-                        let runtime = runtime.with_task_id(task_id.clone());
-                        match runtime.run_loop(&goal).await {
-                            Ok(_) => {
-                                info!("✅ [TaskQueue] Agent '{}' completed goal.", agent_id);
-                                let _ = db_clone
-                                    .query_with::<serde_json::Value>(
-                                        "UPDATE task_queue SET status = 'Completed' WHERE id = $id",
-                                        serde_json::json!({ "id": task_id }),
-                                    )
-                                    .await;
-                            }
-                            Err(e) => {
-                                warn!("❌ [TaskQueue] Agent '{}' failed: {}", agent_id, e);
-                                let _ = db_clone.query_with::<serde_json::Value>(
-                                    "UPDATE task_queue SET status = $status WHERE id = $id",
-                                    serde_json::json!({ "status": format!("{{\"Failed\":{{\"reason\":\"{}\"}}}}", e), "id": task_id }),
-                                ).await;
+                }
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(self.poll_interval) => {}
+            }
+        }
+    }
+
+    fn spawn_worker<F, Fut>(
+        &self,
+        entry: QueuedTask,
+        agent_id: String,
+        permit: OwnedSemaphorePermit,
+        make_runtime: F,
+    ) where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<crate::services::AgentRuntime>> + Send + 'static,
+    {
+        let queue = self.clone();
+        let goal = entry.goal.clone();
+        let entry_id = entry
+            .id
+            .as_ref()
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        info!(
+            "🎯 [TaskQueue] Dispatching task '{}' (id={}, attempt {}/{})",
+            goal.chars().take(60).collect::<String>(),
+            entry_id,
+            entry.attempts,
+            entry.max_attempts
+        );
+
+        tokio::spawn(async move {
+            let _permit = permit;
+
+            // Renew the lease for as long as the agent works on the task. Once
+            // it is lost the task may be claimed again, so the run is stopped.
+            let control = RunControl::new();
+            let heartbeat = {
+                let queue = queue.clone();
+                let entry = entry.clone();
+                let control = control.clone();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(queue.lease / 3);
+                    interval.tick().await;
+                    loop {
+                        interval.tick().await;
+                        match queue.heartbeat(&entry).await {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                warn!(
+                                    "⚠️ [TaskQueue] Lost the lease on {}",
+                                    entry.key().unwrap_or_default()
+                                );
+                                control.cancel();
+                                return true;
                             }
+                            Err(e) => warn!("⚠️ [TaskQueue] Heartbeat failed: {}", e),
                         }
                     }
-                    Err(e) => {
-                        error!("Failed to create AgentRuntime for task '{}': {}", goal, e);
+                })
+            };
+
+            let started = Instant::now();
+            let outcome = match make_runtime(agent_id.clone()).await {
+                Ok(runtime) => {
+                    info!(
+                        "🤖 [TaskQueue] Agent '{}' starting goal: {}",
+                        agent_id,
+                        goal.chars().take(60).collect::<String>()
+                    );
+                    // Run under the timeline task's ID so task controls reach this loop
+                    let run_id = entry.task_id.clone().unwrap_or_else(|| entry_id.clone());
+                    runtime
+                        .with_control(control)
+                        .with_task_id(run_id)
+                        .run_loop(&goal)
+                        .await
+                }
+                Err(e) => Err(e.context("Failed to create AgentRuntime")),
+            };
+            heartbeat.abort();
+            let lease_lost = matches!(heartbeat.await, Ok(true));
+
+            let task_status = match outcome {
+                Ok(()) => {
+                    info!("✅ [TaskQueue] Agent '{}' completed goal.", agent_id);
+                    // The outcome belongs to whoever holds the entry now
+                    match queue.complete(&entry).await {
+                        Ok(true) => Some(TaskStatus::Completed),
+                        Ok(false) => {
+                            warn!(
+                                "⚠️ [TaskQueue] Agent '{}' finished task {} after losing it",
+                                agent_id, entry_id
+                            );
+                            None
+                        }
+                        Err(e) => {
+                            error!("Failed to mark task {} completed: {}", entry_id, e);
+                            None
+                        }
+                    }
+                }
+                Err(e) if e.is::<RunCancelled>() && lease_lost => {
+                    // Unless the queue cancelled it, the entry was released for
+                    // another attempt and its outcome belongs to that attempt.
+                    match queue.get(&entry_id).await {
+                        Ok(Some(current)) if current.status == QueueStatus::Cancelled => {
+                            info!("🛑 [TaskQueue] Agent '{}' was cancelled.", agent_id);
+                            Some(TaskStatus::Cancelled)
+                        }
+                        _ => {
+                            warn!(
+                                "⚠️ [TaskQueue] Agent '{}' stopped after losing task {}",
+                                agent_id, entry_id
+                            );
+                            None
+                        }
+                    }
+                }
+                Err(e) if e.is::<RunCancelled>() => {
+                    info!("🛑 [TaskQueue] Agent '{}' was cancelled.", agent_id);
+                    // Already cancelled when the request came through the queue
+                    let _ = queue
+                        .transition(
+                            &entry_id,
+                            "SET status = 'Cancelled', lease_expires_at = NONE \
+                             WHERE status = 'Running'",
+                            "is no longer running",
+                        )
+                        .await;
+                    Some(TaskStatus::Cancelled)
+                }
+                Err(e) => {
+                    warn!("❌ [TaskQueue] Agent '{}' failed: {}", agent_id, e);
+                    match queue.fail(&entry, &e.to_string()).await {
+                        Ok(Some(QueueStatus::DeadLetter { .. })) => {
+                            warn!(
+                                "☠️ [TaskQueue] Task {} dead-lettered after {} attempts",
+                                entry_id, entry.attempts
+                            );
+                            Some(TaskStatus::Failed)
+                        }
+                        Ok(Some(_)) => {
+                            info!(
+                                "🔁 [TaskQueue] Task {} will be retried in {}s",
+                                entry_id,
+                                retry_backoff(entry.attempts).num_seconds()
+                            );
+                            None
+                        }
+                        Ok(None) => None,
+                        Err(e) => {
+                            error!("Failed to record failure of task {}: {}", entry_id, e);
+                            None
+                        }
                     }
                 }
-            });
+            };
+
+            if let (Some(timeline_task), Some(status)) = (&entry.task_id, task_status) {
+                if let Err(e) = queue
+                    .record_task_outcome(timeline_task, status, &agent_id, started.elapsed())
+                    .await
+                {
+                    warn!("Failed to record outcome on task {}: {}", timeline_task, e);
+                }
+            }
+            queue.wake.notify_one();
+        });
+    }
+}
+
+/// Highest priority first, oldest first within a priority.
+fn sort_for_dispatch(entries: &mut [QueuedTask]) {
+    entries.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.queued_at.cmp(&b.queued_at))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli_task(goal: &str, priority: u8) -> QueuedTask {
+        QueuedTask::new(
+            goal,
+            TaskSource::Cli {
+                invocation: "test".to_string(),
+            },
+            priority,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_claim_by_priority_then_age() -> Result<()> {
+        let queue = TaskQueue::new(SurrealClient::connect_mem().await?);
+        queue.enqueue(cli_task("low", 3)).await?;
+        queue.enqueue(cli_task("urgent", 9)).await?;
+        queue.enqueue(cli_task("urgent later", 9)).await?;
+
+        let mut order = Vec::new();
+        while let Some(entry) = queue.claim("worker-1").await? {
+            assert_eq!(entry.status, QueueStatus::Running);
+            assert_eq!(entry.attempts, 1);
+            order.push(entry.goal);
         }
+        assert_eq!(order, vec!["urgent", "urgent later", "low"]);
+        assert_eq!(queue.list(Some("running")).await?.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retries_back_off_then_dead_letter() -> Result<()> {
+        let queue = TaskQueue::new(SurrealClient::connect_mem().await?);
+        let saved = queue
+            .enqueue(cli_task("flaky", 5).with_max_attempts(2))
+            .await?;
+        let key = saved.key().unwrap();
+
+        let first = queue.claim("worker-1").await?.unwrap();
+        assert_eq!(
+            queue.fail(&first, "boom").await?,
+            Some(QueueStatus::Pending)
+        );
+        // Backing off: not claimable yet.
+        assert!(queue.claim("worker-1").await?.is_none());
+        let backing_off = queue.get(&key).await?.unwrap();
+        assert!(backing_off.available_at.unwrap() > Utc::now());
+        assert_eq!(backing_off.last_error.as_deref(), Some("boom"));
+
+        queue
+            .db
+            .query_with::<QueuedTask>(
+                "UPDATE type::thing('task_queue', $id) SET available_at = NONE",
+                serde_json::json!({ "id": key }),
+            )
+            .await?;
+        let second = queue.claim("worker-2").await?.unwrap();
+        assert_eq!(second.attempts, 2);
+        assert_eq!(
+            queue.fail(&second, "boom again").await?,
+            Some(QueueStatus::DeadLetter {
+                reason: "boom again".to_string()
+            })
+        );
+        assert_eq!(queue.list(Some("dead_letter")).await?.len(), 1);
+
+        let requeued = queue.requeue(&key).await?;
+        assert_eq!(requeued.status, QueueStatus::Pending);
+        assert_eq!(requeued.attempts, 0);
+        assert!(queue.requeue(&key).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_is_requeued() -> Result<()> {
+        let queue = TaskQueue::new(SurrealClient::connect_mem().await?);
+        queue.enqueue(cli_task("long job", 5)).await?;
+        let entry = queue.claim("worker-1").await?.unwrap();
+        assert!(queue.heartbeat(&entry).await?);
+
+        // Nothing expires while the lease holds.
+        assert_eq!(queue.requeue_expired(Utc::now()).await?, 0);
+        let later = Utc::now() + chrono::Duration::minutes(5);
+        assert_eq!(queue.requeue_expired(later).await?, 1);
+
+        let released = queue.get(&entry.key().unwrap()).await?.unwrap();
+        assert_eq!(released.status, QueueStatus::Pending);
+        assert!(released.last_error.unwrap().contains("worker-1"));
+        // The old worker can no longer renew or complete it.
+        assert!(!queue.heartbeat(&entry).await?);
+        assert!(!queue.complete(&entry).await?);
+
+        let cancelled = queue.cancel(&entry.key().unwrap()).await?;
+        assert_eq!(cancelled.status, QueueStatus::Cancelled);
+        assert!(queue.cancel(&entry.key().unwrap()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_renewed_lease_is_not_requeued() -> Result<()> {
+        let queue = TaskQueue::new(SurrealClient::connect_mem().await?);
+        queue.enqueue(cli_task("long job", 5)).await?;
+        let entry = queue.claim("worker-1").await?.unwrap();
+
+        // The worker renewed its lease after the entry was seen as expired.
+        let seen_expired = Utc::now() - chrono::Duration::minutes(5);
+        assert!(queue.heartbeat(&entry).await?);
+        let reason = "Lease expired";
+        assert_eq!(
+            queue
+                .record_failure(&entry, reason, Some(seen_expired))
+                .await?,
+            None
+        );
+        let current = queue.get(&entry.key().unwrap()).await?.unwrap();
+        assert_eq!(current.status, QueueStatus::Running);
+        assert_eq!(current.attempts, 1);
+        assert!(queue.complete(&entry).await?);
+        Ok(())
+    }
 
-        warn!("⚠️ [TaskQueue] Dispatch loop ended — receiver channel closed.");
+    #[test]
+    fn test_retry_backoff_doubles_up_to_a_cap() {
+        assert_eq!(retry_backoff(1).num_seconds(), 30);
+        assert_eq!(retry_backoff(2).num_seconds(), 60);
+        assert_eq!(retry_backoff(3).num_seconds(), 120);
+        assert_eq!(retry_backoff(30).num_seconds(), 3600);
     }
 }
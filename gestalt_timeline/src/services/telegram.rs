@@ -2,7 +2,7 @@ use crate::db::SurrealClient;
 use crate::models::{EventType, TimelineEvent};
 use crate::services::task_queue::{QueuedTask, TaskQueue, TaskSource};
 use crate::services::watch::WatchMessage;
-use crate::services::WatchService;
+use crate::services::{ApprovalService, ControlSignal, TaskService, TimelineService, WatchService};
 use std::sync::Arc;
 use synapse_agentic::prelude::*;
 use teloxide::prelude::*;
@@ -19,6 +19,8 @@ pub struct TelegramService {
     task_queue: Option<Arc<TaskQueue>>,
     watch: Arc<WatchService>,
     db: SurrealClient,
+    approvals: ApprovalService,
+    tasks: TaskService,
 }
 
 #[derive(BotCommands, Clone)]
@@ -37,6 +39,18 @@ enum Command {
     Task(String),
     #[command(description = "check system status and active agents.")]
     Status,
+    #[command(description = "list actions waiting for approval.")]
+    Approvals,
+    #[command(description = "approve a gated action by id.")]
+    Approve(String),
+    #[command(description = "deny a gated action by id.")]
+    Deny(String),
+    #[command(description = "cancel a task and stop its agent.")]
+    Cancel(String),
+    #[command(description = "pause the agent running a task.")]
+    Pause(String),
+    #[command(description = "resume the paused agent of a task.")]
+    Resume(String),
 }
 
 impl TelegramService {
@@ -47,6 +61,9 @@ impl TelegramService {
         watch: Arc<WatchService>,
         db: SurrealClient,
     ) -> Self {
+        let timeline = TimelineService::new(db.clone());
+        let approvals = ApprovalService::new(db.clone(), timeline.clone());
+        let tasks = TaskService::new(db.clone(), timeline);
         Self {
             token,
             engine,
@@ -54,6 +71,8 @@ impl TelegramService {
             task_queue: None,
             watch,
             db,
+            approvals,
+            tasks,
         }
     }
 
@@ -114,6 +133,18 @@ impl TelegramService {
             EventType::TaskStarted => Some(format!("🎯 Task Started: `{}`", event.agent_id)),
             EventType::TaskCompleted => Some(format!("✅ Task Completed: `{}`", event.agent_id)),
             EventType::TaskFailed => Some(format!("❌ Task Failed: `{}`", event.agent_id)),
+            EventType::RunPaused => Some(format!("⏸️ Agent Paused: `{}`", event.agent_id)),
+            EventType::RunResumed => Some(format!("▶️ Agent Resumed: `{}`", event.agent_id)),
+            EventType::RunCancelled => Some(format!("🛑 Agent Cancelled: `{}`", event.agent_id)),
+            EventType::ApprovalRequested => {
+                let id = event.payload["approval_id"].as_str().unwrap_or("?");
+                Some(format!(
+                    "⏸️ Approval needed `{id}`: {} by `{}`\n{}\nReply /approve {id} or /deny {id}",
+                    event.payload["action"].as_str().unwrap_or("?"),
+                    event.agent_id,
+                    event.payload["detail"].as_str().unwrap_or(""),
+                ))
+            }
             _ => None,
         };
 
@@ -146,7 +177,23 @@ impl TelegramService {
                 (None, None) => None,
             };
 
-            if let Some(cid) = chat_id {
+            // 3. Approval requests go to every known chat when nobody owns the agent
+            let chat_ids: Vec<String> = match chat_id {
+                Some(id) => vec![id],
+                None if matches!(event.event_type, EventType::ApprovalRequested) => self
+                    .db
+                    .query_with::<Value>(
+                        "SELECT chat_id FROM telegram_chats",
+                        serde_json::json!({}),
+                    )
+                    .await?
+                    .iter()
+                    .filter_map(|v| v["chat_id"].as_str().map(|s| s.to_string()))
+                    .collect(),
+                None => Vec::new(),
+            };
+
+            for cid in chat_ids {
                 if let Ok(id) = cid.parse::<i64>() {
                     // Escape for MarkdownV2?
                     // Let's defer robust escaping to step 5, but use it here as well
@@ -343,8 +390,111 @@ impl TelegramService {
                 .parse_mode(teloxide::types::ParseMode::MarkdownV2)
                 .await?;
             }
+            Command::Approvals => {
+                let text = match self.approvals.list_pending().await {
+                    Ok(pending) if pending.is_empty() => {
+                        "✅ No actions waiting for approval.".to_string()
+                    }
+                    Ok(pending) => pending
+                        .iter()
+                        .map(|r| {
+                            format!(
+                                "• `{}` {} by `{}`{}",
+                                r.approval_id,
+                                r.action,
+                                r.agent_id,
+                                r.rule
+                                    .as_deref()
+                                    .map(|rule| format!(" (rule: {})", rule))
+                                    .unwrap_or_default()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Err(e) => format!("❌ Failed to list approvals: {}", e),
+                };
+                bot.send_message(msg.chat.id, self.escape_markdown(&text))
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+            }
+            Command::Approve(id) | Command::Deny(id) if id.trim().is_empty() => {
+                bot.send_message(msg.chat.id, "Usage: /approve \\<id\\> or /deny \\<id\\>")
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+            }
+            Command::Approve(id) => self.decide(&bot, &msg, id.trim(), true).await?,
+            Command::Deny(id) => self.decide(&bot, &msg, id.trim(), false).await?,
+            Command::Cancel(id) | Command::Pause(id) | Command::Resume(id)
+                if id.trim().is_empty() =>
+            {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /cancel \\<task id\\>, /pause \\<task id\\> or /resume \\<task id\\>",
+                )
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            }
+            Command::Cancel(id) => {
+                self.control(&bot, &msg, id.trim(), ControlSignal::Cancel)
+                    .await?
+            }
+            Command::Pause(id) => {
+                self.control(&bot, &msg, id.trim(), ControlSignal::Pause)
+                    .await?
+            }
+            Command::Resume(id) => {
+                self.control(&bot, &msg, id.trim(), ControlSignal::Run)
+                    .await?
+            }
         }
 
         Ok(())
     }
+
+    async fn control(
+        &self,
+        bot: &Bot,
+        msg: &teloxide::types::Message,
+        task_id: &str,
+        signal: ControlSignal,
+    ) -> ResponseResult<()> {
+        let user = format!("telegram:{}", msg.chat.username().unwrap_or("unknown"));
+        let result = match signal {
+            ControlSignal::Cancel => self.tasks.cancel_task(task_id, &user).await.map(|_| ()),
+            ControlSignal::Pause => self.tasks.pause_task(task_id, &user).await.map(|_| ()),
+            ControlSignal::Run => self.tasks.resume_task(task_id, &user).await.map(|_| ()),
+        };
+        let text = match result {
+            Ok(()) => format!("👍 Sent {} to task `{}`.", signal, task_id),
+            Err(e) => {
+                warn!("Task control failed: {}", e);
+                format!("❌ {}", e)
+            }
+        };
+        bot.send_message(msg.chat.id, self.escape_markdown(&text))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        Ok(())
+    }
+
+    async fn decide(
+        &self,
+        bot: &Bot,
+        msg: &teloxide::types::Message,
+        approval_id: &str,
+        approve: bool,
+    ) -> ResponseResult<()> {
+        let user = format!("telegram:{}", msg.chat.username().unwrap_or("unknown"));
+        let text = match self.approvals.decide(approval_id, approve, &user).await {
+            Ok(decided) => format!("👍 Approval `{}` {}.", decided.approval_id, decided.status),
+            Err(e) => {
+                warn!("Approval decision failed: {}", e);
+                format!("❌ {}", e)
+            }
+        };
+        bot.send_message(msg.chat.id, self.escape_markdown(&text))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        Ok(())
+    }
 }
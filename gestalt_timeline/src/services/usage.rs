@@ -0,0 +1,374 @@
+//! Usage Service - LLM token and cost accounting.
+//!
+//! Each agent run stores its token counts and spend per provider on its
+//! `execution_metrics` record. This service reports them grouped by agent,
+//! task, project or provider, and enforces the per-run and per-day budgets
+//! that stop `AgentRuntime::run_loop`.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use synapse_agentic::prelude::Usage;
+
+use crate::db::SurrealClient;
+use crate::models::ExecutionMetrics;
+
+const TABLE: &str = "execution_metrics";
+
+/// Dimension a usage report is grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Agent,
+    Task,
+    Project,
+    Provider,
+}
+
+impl FromStr for UsageGroup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "agent" => Ok(UsageGroup::Agent),
+            "task" => Ok(UsageGroup::Task),
+            "project" => Ok(UsageGroup::Project),
+            "provider" => Ok(UsageGroup::Provider),
+            other => Err(anyhow::anyhow!(
+                "Unknown usage grouping '{}' (expected agent, task, project or provider)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UsageGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsageGroup::Agent => write!(f, "agent"),
+            UsageGroup::Task => write!(f, "task"),
+            UsageGroup::Project => write!(f, "project"),
+            UsageGroup::Provider => write!(f, "provider"),
+        }
+    }
+}
+
+/// Token counts and spend for one agent, task, project or provider.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageRow {
+    pub key: String,
+    pub runs: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Sum the usage of `runs` per `group`, most expensive first.
+pub fn summarize(runs: &[ExecutionMetrics], group: UsageGroup) -> Vec<UsageRow> {
+    let mut rows: BTreeMap<String, (UsageRow, HashSet<&str>)> = BTreeMap::new();
+    for run in runs {
+        for usage in &run.llm_usage {
+            let key = match group {
+                UsageGroup::Agent => Some(run.agent_id.as_str()),
+                UsageGroup::Task => run.task_id.as_deref(),
+                UsageGroup::Project => run.project_id.as_deref(),
+                UsageGroup::Provider => Some(usage.provider.as_str()),
+            }
+            .filter(|k| !k.is_empty())
+            .unwrap_or("-");
+            let (row, run_ids) = rows.entry(key.to_string()).or_default();
+            row.prompt_tokens += usage.prompt_tokens;
+            row.completion_tokens += usage.completion_tokens;
+            row.cost_usd += usage.cost_usd;
+            run_ids.insert(&run.run_id);
+        }
+    }
+
+    let mut rows: Vec<UsageRow> = rows
+        .into_iter()
+        .map(|(key, (row, run_ids))| UsageRow {
+            key,
+            runs: run_ids.len() as u64,
+            ..row
+        })
+        .collect();
+    rows.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+    rows
+}
+
+/// Spending limits for agent runs. Unset limits do not apply.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Budget {
+    /// Maximum spend of a single run, in USD.
+    pub max_run_usd: Option<f64>,
+    /// Maximum tokens of a single run.
+    pub max_run_tokens: Option<u64>,
+    /// Maximum spend across all runs in a UTC day, in USD.
+    pub max_daily_usd: Option<f64>,
+}
+
+impl Budget {
+    /// Limits from `GESTALT_MAX_RUN_USD`, `GESTALT_MAX_RUN_TOKENS` and
+    /// `GESTALT_MAX_DAILY_USD`.
+    pub fn from_env() -> Self {
+        fn var<T: FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+        Self {
+            max_run_usd: var("GESTALT_MAX_RUN_USD"),
+            max_run_tokens: var("GESTALT_MAX_RUN_TOKENS"),
+            max_daily_usd: var("GESTALT_MAX_DAILY_USD"),
+        }
+    }
+
+    /// Why a run that has used `run` so far must stop, given `spent_today`
+    /// by earlier runs; `None` while it is within budget. Every limit may be
+    /// reached but not passed.
+    pub fn exceeded(&self, run: &Usage, spent_today: f64) -> Option<String> {
+        if let Some(max) = self.max_run_usd.filter(|max| run.cost_usd > *max) {
+            return Some(format!(
+                "Run budget exceeded: spent ${:.4} of ${:.4}",
+                run.cost_usd, max
+            ));
+        }
+        if let Some(max) = self.max_run_tokens.filter(|max| run.total_tokens() > *max) {
+            return Some(format!(
+                "Run token budget exceeded: used {} of {} tokens",
+                run.total_tokens(),
+                max
+            ));
+        }
+        let today = spent_today + run.cost_usd;
+        if let Some(max) = self.max_daily_usd.filter(|max| today > *max) {
+            return Some(format!(
+                "Daily budget exceeded: spent ${:.4} of ${:.4} today",
+                today, max
+            ));
+        }
+        None
+    }
+}
+
+/// Usage of a run in progress, kept per provider.
+#[derive(Debug, Clone, Default)]
+pub struct RunUsage {
+    by_provider: Vec<Usage>,
+    /// Spent today by earlier runs, as of the last budget check.
+    pub spent_today: f64,
+}
+
+impl RunUsage {
+    /// Usage of a run that already used `by_provider`, e.g. before it was
+    /// interrupted.
+    pub fn from_providers(by_provider: &[Usage]) -> Self {
+        let mut run_usage = Self::default();
+        for usage in by_provider {
+            run_usage.add(usage);
+        }
+        run_usage
+    }
+
+    pub fn add(&mut self, usage: &Usage) {
+        match self
+            .by_provider
+            .iter_mut()
+            .find(|u| u.provider == usage.provider)
+        {
+            Some(existing) => existing.add(usage),
+            None => self.by_provider.push(usage.clone()),
+        }
+    }
+
+    pub fn total(&self) -> Usage {
+        let mut total = Usage::default();
+        for usage in &self.by_provider {
+            total.add(usage);
+        }
+        total
+    }
+
+    pub fn by_provider(&self) -> Vec<Usage> {
+        self.by_provider.clone()
+    }
+}
+
+/// Service for recording and reporting LLM usage.
+#[derive(Clone)]
+pub struct UsageService {
+    db: SurrealClient,
+}
+
+impl UsageService {
+    /// Create a new UsageService.
+    pub fn new(db: SurrealClient) -> Self {
+        Self { db }
+    }
+
+    /// Store the metrics of a finished run.
+    pub async fn record(&self, metrics: &ExecutionMetrics) -> Result<()> {
+        let _: ExecutionMetrics = self.db.create(TABLE, metrics).await?;
+        Ok(())
+    }
+
+    /// Usage since `since` (all time when `None`), grouped by `group`.
+    pub async fn report(
+        &self,
+        group: UsageGroup,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<UsageRow>> {
+        Ok(summarize(&self.runs_since(since).await?, group))
+    }
+
+    /// Total spend of runs recorded since the start of the current UTC day.
+    pub async fn spent_today(&self) -> Result<f64> {
+        let midnight = Utc::now()
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc();
+        let runs = self.runs_since(Some(midnight)).await?;
+        Ok(runs.iter().map(|run| run.total_usage().cost_usd).sum())
+    }
+
+    async fn runs_since(&self, since: Option<DateTime<Utc>>) -> Result<Vec<ExecutionMetrics>> {
+        let runs: Vec<ExecutionMetrics> = match since {
+            Some(since) => {
+                self.db
+                    .query_with(
+                        "SELECT * FROM execution_metrics WHERE timestamp >= $since",
+                        ("since", since),
+                    )
+                    .await?
+            }
+            None => self.db.select_all(TABLE).await?,
+        };
+        Ok(runs
+            .into_iter()
+            .filter(|run| !run.llm_usage.is_empty())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(run_id: &str, agent: &str, usage: Vec<Usage>) -> ExecutionMetrics {
+        ExecutionMetrics::from_agent_result(run_id, agent, "agent_runtime", true, 10, None)
+            .with_project("gestalt")
+            .with_usage(usage)
+    }
+
+    #[test]
+    fn test_summarize_groups_usage() {
+        let runs = vec![
+            run(
+                "r1",
+                "alice",
+                vec![
+                    Usage::new("openai/gpt-4o", 1000, 200, 0.01),
+                    Usage::new("ollama/llama3", 500, 100, 0.0),
+                ],
+            ),
+            run(
+                "r2",
+                "bob",
+                vec![Usage::new("openai/gpt-4o", 2000, 0, 0.01)],
+            ),
+        ];
+
+        let by_provider = summarize(&runs, UsageGroup::Provider);
+        assert_eq!(by_provider[0].key, "openai/gpt-4o");
+        assert_eq!(by_provider[0].runs, 2);
+        assert_eq!(by_provider[0].prompt_tokens, 3000);
+        assert!((by_provider[0].cost_usd - 0.032).abs() < 1e-9);
+
+        let by_agent = summarize(&runs, UsageGroup::Agent);
+        assert_eq!(by_agent.len(), 2);
+        assert_eq!(by_agent[0].key, "bob");
+
+        let by_task = summarize(&runs, UsageGroup::Task);
+        assert_eq!(by_task[0].key, "-");
+        assert_eq!(by_task[0].runs, 2);
+        assert_eq!(
+            summarize(&runs, UsageGroup::Project)[0].completion_tokens,
+            300
+        );
+    }
+
+    #[test]
+    fn test_budget_limits() {
+        let budget = Budget {
+            max_run_usd: Some(0.5),
+            max_run_tokens: Some(10_000),
+            max_daily_usd: Some(2.0),
+        };
+        assert_eq!(budget.exceeded(&Usage::new("p", 1000, 0, 0.1), 0.0), None);
+        assert!(budget
+            .exceeded(&Usage::new("p", 6000, 0, 0.1), 0.0)
+            .unwrap()
+            .starts_with("Run budget exceeded"));
+        assert!(budget
+            .exceeded(&Usage::new("p", 20_000, 0, 0.0), 0.0)
+            .unwrap()
+            .starts_with("Run token budget exceeded"));
+        assert!(budget
+            .exceeded(&Usage::new("p", 1000, 0, 0.01), 2.0)
+            .unwrap()
+            .starts_with("Daily budget exceeded"));
+        assert_eq!(
+            Budget::default().exceeded(&Usage::new("p", 1 << 40, 0, 1.0), 1e9),
+            None
+        );
+    }
+
+    #[test]
+    fn test_budget_limits_may_be_reached() {
+        let budget = Budget {
+            max_run_usd: Some(0.5),
+            max_run_tokens: Some(10_000),
+            max_daily_usd: Some(2.0),
+        };
+        let at_limit = Usage {
+            cost_usd: 0.5,
+            ..Usage::new("p", 10_000, 0, 0.0)
+        };
+        assert_eq!(budget.exceeded(&at_limit, 1.5), None);
+        assert!(budget
+            .exceeded(&Usage::new("p", 10_001, 0, 0.0), 0.0)
+            .is_some());
+        let over_cost = Usage {
+            cost_usd: 0.5001,
+            ..at_limit.clone()
+        };
+        assert!(budget.exceeded(&over_cost, 0.0).is_some());
+        assert!(budget
+            .exceeded(&Usage::new("p", 0, 0, 0.0), 2.0001)
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_report_reads_recorded_runs() {
+        let db = SurrealClient::connect_mem().await.unwrap();
+        let service = UsageService::new(db);
+        service
+            .record(
+                &run(
+                    "r1",
+                    "alice",
+                    vec![Usage::new("gemini/flash", 100, 50, 1.0)],
+                )
+                .with_task("t1"),
+            )
+            .await
+            .unwrap();
+
+        let rows = service.report(UsageGroup::Task, None).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, "t1");
+        assert_eq!(rows[0].completion_tokens, 50);
+        assert!((service.spent_today().await.unwrap() - 0.15).abs() < 1e-9);
+    }
+}
@@ -210,8 +210,8 @@ mod runtime_tests {
     use crate::db::SurrealClient;
     use crate::models::{AgentRuntimeState, RuntimePhase};
     use crate::services::{
-        AgentRuntime, AgentService, MemoryService, ProjectService, TaskService, TimelineService,
-        WatchService,
+        AgentRuntime, AgentService, MemoryService, ProjectService, RunCancelled, TaskService,
+        TimelineService, WatchService,
     };
     use std::sync::Arc;
     use synapse_agentic::prelude::*;
@@ -283,4 +283,91 @@ mod runtime_tests {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancelled_loop_stops_and_records_phase() -> anyhow::Result<()> {
+        tokio::time::timeout(std::time::Duration::from_secs(30), async {
+            let db = SurrealClient::connect_mem().await?;
+
+            let timeline = TimelineService::new(db.clone());
+            let runtime = AgentRuntime::new(
+                "cancel-agent".to_string(),
+                Arc::new(DecisionEngine::builder().with_provider(MockLlm).build()),
+                Arc::new(ToolRegistry::new()),
+                ProjectService::new(db.clone(), timeline.clone()),
+                TaskService::new(db.clone(), timeline.clone()),
+                timeline.clone(),
+                WatchService::new(db.clone(), timeline.clone()),
+                AgentService::new(db.clone(), timeline),
+                MemoryService::new(db.clone()),
+            )
+            .with_hard_step_cap(5);
+
+            runtime.control().cancel();
+            let err = runtime.run_loop("Test Goal").await.unwrap_err();
+            assert!(err.is::<RunCancelled>());
+
+            let state: AgentRuntimeState = db
+                .select_by_id("agent_runtime_states", "cancel-agent")
+                .await?
+                .expect("runtime state persisted");
+            assert_eq!(state.phase, RuntimePhase::Cancelled);
+            assert!(state.finished_at.is_some());
+
+            Ok::<(), anyhow::Error>(())
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("test_cancelled_loop_stops_and_records_phase timed out"))??;
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_resume_continues_interrupted_loop() -> anyhow::Result<()> {
+        tokio::time::timeout(std::time::Duration::from_secs(30), async {
+            let db = SurrealClient::connect_mem().await?;
+
+            // State left behind by a loop whose process died at step 1.
+            let mut interrupted = AgentRuntimeState::new("resume-agent", "Test Goal", 1);
+            interrupted.current_step = 1;
+            interrupted.task_id = Some("task-1".to_string());
+            interrupted.locked_paths = vec!["notes.md".to_string()];
+            interrupted.history_tail = vec!["User: GOAL: Test Goal".to_string()];
+            let _: AgentRuntimeState = db
+                .upsert("agent_runtime_states", "resume-agent", &interrupted)
+                .await?;
+            assert_eq!(AgentRuntime::interrupted(&db).await?.len(), 1);
+
+            let timeline = TimelineService::new(db.clone());
+            let runtime = AgentRuntime::new(
+                "resume-agent".to_string(),
+                Arc::new(DecisionEngine::builder().with_provider(MockLlm).build()),
+                Arc::new(ToolRegistry::new()),
+                ProjectService::new(db.clone(), timeline.clone()),
+                TaskService::new(db.clone(), timeline.clone()),
+                timeline.clone(),
+                WatchService::new(db.clone(), timeline.clone()),
+                AgentService::new(db.clone(), timeline),
+                MemoryService::new(db.clone()),
+            );
+            // The recorded cap of one step is already reached, so the loop stops.
+            runtime.resume().await?;
+
+            let state: AgentRuntimeState = db
+                .select_by_id("agent_runtime_states", "resume-agent")
+                .await?
+                .expect("runtime state persisted");
+            assert_eq!(state.phase, RuntimePhase::Completed);
+            assert_eq!(state.current_step, 1);
+            assert_eq!(state.task_id.as_deref(), Some("task-1"));
+            assert!(state.locked_paths.is_empty());
+            assert!(AgentRuntime::interrupted(&db).await?.is_empty());
+
+            Ok::<(), anyhow::Error>(())
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("test_resume_continues_interrupted_loop timed out"))??;
+
+        Ok(())
+    }
 }
@@ -5,16 +5,20 @@ use gestalt_core::application::agent::tools::{
 };
 use gestalt_timeline::config::DatabaseSettings;
 use gestalt_timeline::db::SurrealClient;
+use gestalt_timeline::models::{AgentRuntimeState, RuntimePhase};
 use gestalt_timeline::services::{
-    AgentRuntime, AgentService, MemoryService, ProjectService, TaskService, TimelineService,
-    WatchService,
+    AgentRuntime, AgentService, Budget, MemoryService, PolicyEngine, ProjectService, TaskService,
+    TimelineService, WatchService,
 };
 use std::sync::Arc;
-use synapse_agentic::prelude::{DecisionEngine, EmptyContext, ToolRegistry};
+use synapse_agentic::prelude::{
+    async_trait, Cassette, ChatRequest, ChatResponse, DecisionEngine, EmptyContext, LLMProvider,
+    MatchMode, MessageRole, RecordingProvider, ReplayProvider, ToolCall, ToolRegistry, Usage,
+};
 
 async fn init_tool_registry() -> Arc<ToolRegistry> {
     let registry = Arc::new(ToolRegistry::new());
-    registry.register_tool(ExecuteShellTool).await;
+    registry.register_tool(ExecuteShellTool::default()).await;
     registry.register_tool(ReadFileTool).await;
     registry.register_tool(WriteFileTool).await;
     registry.register_tool(GitStatusTool).await;
@@ -123,3 +127,177 @@ async fn test_runtime_loop_starts_with_registry() -> Result<()> {
     runtime.run_loop("Validate runtime bootstrap").await?;
     Ok(())
 }
+
+/// Stands in for a live model: creates a project, then a task in it, then
+/// reports back.
+#[derive(Debug)]
+struct ScriptedProvider;
+
+#[async_trait]
+impl LLMProvider for ScriptedProvider {
+    fn name(&self) -> &str {
+        "scripted"
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        0.0
+    }
+    async fn generate(&self, _prompt: &str) -> Result<String> {
+        Ok(String::new())
+    }
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let results = request
+            .messages
+            .iter()
+            .filter(|m| matches!(m.role, MessageRole::Tool))
+            .count();
+        let call = |name: &str, arguments: serde_json::Value| ChatResponse {
+            content: String::new(),
+            tool_calls: vec![ToolCall {
+                id: format!("call_{}", results),
+                name: name.to_string(),
+                arguments,
+            }],
+            usage: None,
+        };
+        Ok(match results {
+            0 => call(
+                "create_project",
+                serde_json::json!({ "name": "cassette-demo" }),
+            ),
+            1 => call(
+                "create_task",
+                serde_json::json!({ "project": "cassette-demo", "description": "Write the README" }),
+            ),
+            _ => ChatResponse::text("Project cassette-demo has its first task."),
+        })
+    }
+}
+
+async fn run_with_provider<P: LLMProvider + 'static>(
+    provider: P,
+    goal: &str,
+) -> Result<(Result<()>, ProjectService, TaskService)> {
+    let (project_service, task_service, watch_service, agent_service, memory_service, timeline) =
+        init_services().await?;
+    let engine = Arc::new(DecisionEngine::builder().with_provider(provider).build());
+    let runtime = AgentRuntime::new(
+        "cassette-agent".to_string(),
+        engine,
+        init_tool_registry().await,
+        project_service.clone(),
+        task_service.clone(),
+        timeline,
+        watch_service,
+        agent_service,
+        memory_service,
+    )
+    .with_policy(Arc::new(PolicyEngine::allow_all()))
+    .with_hard_step_cap(10);
+
+    let result = runtime.run_loop(goal).await;
+    Ok((result, project_service, task_service))
+}
+
+#[tokio::test]
+async fn test_runtime_replays_recorded_session_offline() -> Result<()> {
+    let goal = "Start the cassette-demo project";
+    let cassette = format!("target/cassettes/runtime-e2e-{}.json", uuid::Uuid::new_v4());
+
+    let recorder = RecordingProvider::new(Arc::new(ScriptedProvider), &cassette);
+    let (recorded, _, _) = run_with_provider(recorder, goal).await?;
+    recorded?;
+    assert_eq!(Cassette::load(&cassette)?.interactions.len(), 3);
+
+    // A fresh database gets new project and task IDs; normalized matching
+    // ignores them.
+    let replay = ReplayProvider::from_file(&cassette)?.with_match_mode(MatchMode::Normalized);
+    let (replayed, projects, tasks) = run_with_provider(replay, goal).await?;
+    replayed?;
+    assert!(projects.get_by_name("cassette-demo").await?.is_some());
+    let created = tasks.list_tasks(Some("cassette-demo")).await?;
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].description, "Write the README");
+
+    // A different goal changes the prompt, which the cassette cannot answer.
+    let replay = ReplayProvider::from_file(&cassette)?.with_match_mode(MatchMode::Normalized);
+    let (missed, _, _) = run_with_provider(replay, "Delete everything").await?;
+    let err = missed.unwrap_err().to_string();
+    assert!(err.contains("No recorded chat reply"), "{}", err);
+
+    std::fs::remove_file(&cassette)?;
+    Ok(())
+}
+
+/// Answers every chat with a final reply that costs $0.10.
+#[derive(Debug)]
+struct MeteredProvider;
+
+#[async_trait]
+impl LLMProvider for MeteredProvider {
+    fn name(&self) -> &str {
+        "metered"
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        1.0
+    }
+    async fn generate(&self, _prompt: &str) -> Result<String> {
+        Ok(String::new())
+    }
+    async fn chat(&self, _request: &ChatRequest) -> Result<ChatResponse> {
+        Ok(ChatResponse {
+            usage: Some(Usage::new("metered", 100, 0, 1.0)),
+            ..ChatResponse::text("Done.")
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_resumed_run_keeps_its_spend_against_the_budget() -> Result<()> {
+    let (project_service, task_service, watch_service, agent_service, memory_service, timeline) =
+        init_services().await?;
+    let db = watch_service.db();
+    let runtime = |agent_id: &str| {
+        AgentRuntime::new(
+            agent_id.to_string(),
+            Arc::new(
+                DecisionEngine::builder()
+                    .with_provider(MeteredProvider)
+                    .build(),
+            ),
+            Arc::new(ToolRegistry::new()),
+            project_service.clone(),
+            task_service.clone(),
+            timeline.clone(),
+            watch_service.clone(),
+            agent_service.clone(),
+            memory_service.clone(),
+        )
+        .with_policy(Arc::new(PolicyEngine::allow_all()))
+        .with_budget(Budget {
+            max_run_usd: Some(1.0),
+            ..Budget::default()
+        })
+    };
+
+    // A fresh run fits its $0.10 reply in the budget.
+    runtime("fresh-agent").run_loop("Say done").await?;
+
+    // Interrupted after spending $0.95; the next reply goes over $1.00.
+    let mut state = AgentRuntimeState::new("budget-agent", "Say done", 0);
+    state.current_step = 3;
+    state.llm_usage = vec![Usage::new("metered", 950, 0, 1.0)];
+    let _: AgentRuntimeState = db
+        .upsert("agent_runtime_states", "budget-agent", &state)
+        .await?;
+
+    let err = runtime("budget-agent").resume().await.unwrap_err();
+    assert!(err.to_string().contains("Run budget exceeded"), "{}", err);
+    let stopped: AgentRuntimeState = db
+        .select_by_id("agent_runtime_states", "budget-agent")
+        .await?
+        .unwrap();
+    assert_eq!(stopped.phase, RuntimePhase::Failed);
+    let spent: f64 = stopped.llm_usage.iter().map(|u| u.cost_usd).sum();
+    assert!((spent - 1.05).abs() < 1e-9, "{}", spent);
+    Ok(())
+}
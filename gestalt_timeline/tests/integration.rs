@@ -23,14 +23,24 @@ fn test_add_project_parsing() {
 
 #[test]
 fn test_add_task_parsing() {
-    let cli = Cli::try_parse_from(["gestalt", "add-task", "my-proj", "do work"]).unwrap();
+    let cli = Cli::try_parse_from([
+        "gestalt",
+        "add-task",
+        "my-proj",
+        "do work",
+        "--depends-on",
+        "a,b",
+    ])
+    .unwrap();
     match cli.command {
         Some(Commands::AddTask {
             project,
             description,
+            depends_on,
         }) => {
             assert_eq!(project, "my-proj");
             assert_eq!(description, "do work");
+            assert_eq!(depends_on, vec!["a", "b"]);
         }
         _ => panic!("Expected add-task command"),
     }
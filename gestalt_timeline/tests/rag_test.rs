@@ -1,9 +1,12 @@
 use anyhow::Result;
+use gestalt_core::adapters::persistence::lexical;
+use gestalt_core::application::hybrid_search::HybridSearch;
+use gestalt_core::domain::rag::embeddings::DummyEmbeddingModel;
 use gestalt_timeline::config::DatabaseSettings;
 use gestalt_timeline::db::SurrealClient;
 use gestalt_timeline::services::{
-    AgentRuntime, AgentService, MemoryService, ProjectService, TaskService, TimelineService,
-    WatchService,
+    AgentRuntime, AgentService, IndexService, MemoryService, ProjectService, TaskService,
+    TimelineService, WatchService,
 };
 use std::sync::Arc;
 use synapse_agentic::prelude::{DecisionEngine, ToolRegistry};
@@ -95,3 +98,46 @@ async fn test_rag_context_injection() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_indexed_files_are_found_by_hybrid_search() -> Result<()> {
+    let (_, _, watch_service, _, _, _) = init_services().await?;
+    let db = watch_service.db();
+    let model = Arc::new(DummyEmbeddingModel::new(8));
+
+    let repo = tempfile::tempdir()?;
+    std::fs::create_dir(repo.path().join("src"))?;
+    std::fs::write(
+        repo.path().join("src/manifest.rs"),
+        "pub fn parse_manifest(text: &str) -> Manifest {\n    Manifest::from_toml(text)\n}\n",
+    )?;
+    std::fs::write(
+        repo.path().join("src/render.rs"),
+        "pub fn render_page(page: &Page) -> String {\n    page.to_html()\n}\n",
+    )?;
+    let url = repo.path().to_string_lossy().to_string();
+
+    let index = IndexService::new(db.clone(), model.clone());
+    index.index_repo(&url).await?;
+    let stats = lexical::corpus_stats(db.client().as_ref(), "chunks").await?;
+    assert_eq!(stats.doc_count, 2);
+
+    let search = HybridSearch::new(Arc::new(db.clone()), model);
+    let hits = search.search("chunks", "parse manifest", 5).await?;
+    let lexical_hits: Vec<&str> = hits
+        .iter()
+        .filter(|hit| hit.scores.bm25.is_some())
+        .map(|hit| hit.path.as_str())
+        .collect();
+    assert_eq!(lexical_hits, ["src/manifest.rs"]);
+
+    // Removing the file takes its chunks out of the index and the stats.
+    std::fs::remove_file(repo.path().join("src/manifest.rs"))?;
+    let report = index.index_repo(&url).await?;
+    assert_eq!(report.removed, 1);
+    let stats = lexical::corpus_stats(db.client().as_ref(), "chunks").await?;
+    assert_eq!(stats.doc_count, 1);
+    let hits = search.search("chunks", "parse manifest", 5).await?;
+    assert!(hits.iter().all(|hit| hit.scores.bm25.is_none()));
+    Ok(())
+}
@@ -0,0 +1,405 @@
+//! Record and replay provider calls for deterministic tests.
+//!
+//! [`RecordingProvider`] wraps a live provider and appends every prompt and
+//! reply to a cassette file. [`ReplayProvider`] serves a cassette back
+//! without touching the network, so agent loops that need several model turns
+//! can run offline. Chat requests are keyed by their rendered prompt (see
+//! [`crate::chat::emulated_prompt`]), which keeps cassettes readable.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+
+use crate::chat::emulated_prompt;
+use crate::prelude::{
+    async_trait, ChatRequest, ChatResponse, Deserialize, LLMProvider, Serialize, Usage,
+};
+
+/// One recorded provider call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    Generate {
+        prompt: String,
+        reply: String,
+        #[serde(default)]
+        usage: Option<Usage>,
+    },
+    Chat {
+        prompt: String,
+        response: ChatResponse,
+    },
+}
+
+impl Interaction {
+    pub fn prompt(&self) -> &str {
+        match self {
+            Interaction::Generate { prompt, .. } | Interaction::Chat { prompt, .. } => prompt,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Interaction::Generate { .. } => "generate",
+            Interaction::Chat { .. } => "chat",
+        }
+    }
+}
+
+/// Recorded calls, in the order they were made.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse cassette {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write cassette {}", path.display()))
+    }
+}
+
+/// How a prompt is compared with the recorded ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Byte-for-byte equality.
+    #[default]
+    Exact,
+    /// Equality after [`normalize_prompt`], so whitespace, UUIDs, timestamps
+    /// and long numbers may differ between recording and replay.
+    Normalized,
+}
+
+impl MatchMode {
+    fn matches(self, recorded: &str, prompt: &str) -> bool {
+        match self {
+            MatchMode::Exact => recorded == prompt,
+            MatchMode::Normalized => normalize_prompt(recorded) == normalize_prompt(prompt),
+        }
+    }
+}
+
+/// Collapse whitespace and mask values that change from run to run: UUIDs
+/// become `<uuid>`, RFC 3339 timestamps `<timestamp>` and runs of six or more
+/// digits `<n>`.
+pub fn normalize_prompt(text: &str) -> String {
+    let text: Vec<char> = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let boundary = i == 0 || !text[i - 1].is_ascii_alphanumeric();
+        if boundary && is_uuid(rest) {
+            out.push_str("<uuid>");
+            i += 36;
+        } else if let Some(len) = timestamp_len(rest).filter(|_| boundary) {
+            out.push_str("<timestamp>");
+            i += len;
+        } else if rest[0].is_ascii_digit() {
+            let len = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            if len >= 6 {
+                out.push_str("<n>");
+            } else {
+                out.extend(&rest[..len]);
+            }
+            i += len;
+        } else {
+            out.push(rest[0]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Whether `s` matches `layout`, where `d` is a digit, `x` a hex digit and
+/// any other character itself.
+fn matches_layout(s: &[char], layout: &str) -> bool {
+    s.len() >= layout.len()
+        && layout.chars().zip(s).all(|(l, c)| match l {
+            'd' => c.is_ascii_digit(),
+            'x' => c.is_ascii_hexdigit(),
+            'T' => *c == 'T' || *c == ' ',
+            l => l == *c,
+        })
+}
+
+fn is_uuid(s: &[char]) -> bool {
+    matches_layout(s, "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx")
+        && s.get(36).is_none_or(|c| !c.is_ascii_alphanumeric())
+}
+
+/// Length of the RFC 3339 timestamp at the start of `s`, if there is one.
+fn timestamp_len(s: &[char]) -> Option<usize> {
+    if !matches_layout(s, "dddd-dd-ddTdd:dd:dd") {
+        return None;
+    }
+    let mut len = 19;
+    if s.get(len) == Some(&'.') {
+        len += 1 + s[len + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+    }
+    match s.get(len) {
+        Some('Z') => len += 1,
+        Some('+') | Some('-') if matches_layout(&s[len + 1..], "dd:dd") => len += 6,
+        _ => {}
+    }
+    Some(len)
+}
+
+/// Wraps a provider and records every call it serves to a cassette file.
+///
+/// The file is rewritten after each call, so a test that panics midway still
+/// leaves the calls made so far.
+#[derive(Debug)]
+pub struct RecordingProvider {
+    inner: std::sync::Arc<dyn LLMProvider>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingProvider {
+    /// Record calls to `inner` into a new cassette at `path`.
+    pub fn new(inner: std::sync::Arc<dyn LLMProvider>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Calls recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    fn record(&self, interaction: Interaction) -> anyhow::Result<()> {
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(interaction);
+        cassette.save(&self.path)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        self.inner.cost_per_1k_tokens()
+    }
+    async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+        Ok(self.generate_with_usage(prompt).await?.0)
+    }
+    async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+        let (reply, usage) = self.inner.generate_with_usage(prompt).await?;
+        self.record(Interaction::Generate {
+            prompt: prompt.to_string(),
+            reply: reply.clone(),
+            usage: Some(usage.clone()),
+        })?;
+        Ok((reply, usage))
+    }
+    async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+        let response = self.inner.chat(request).await?;
+        self.record(Interaction::Chat {
+            prompt: emulated_prompt(request),
+            response: response.clone(),
+        })?;
+        Ok(response)
+    }
+}
+
+/// Serves the replies of a recorded cassette instead of calling a provider.
+///
+/// Each call takes the first unused interaction of the same kind whose prompt
+/// matches, so a prompt asked twice gets its two recorded replies in order.
+/// A call with no match fails instead of guessing.
+#[derive(Debug)]
+pub struct ReplayProvider {
+    source: String,
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+    mode: MatchMode,
+}
+
+impl ReplayProvider {
+    pub fn new(cassette: Cassette) -> Self {
+        Self::with_source("in-memory cassette", cassette)
+    }
+
+    /// Replay the cassette stored at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self::with_source(
+            &path.display().to_string(),
+            Cassette::load(path)?,
+        ))
+    }
+
+    fn with_source(source: &str, cassette: Cassette) -> Self {
+        Self {
+            source: source.to_string(),
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+            mode: MatchMode::default(),
+        }
+    }
+
+    pub fn with_match_mode(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Number of recorded interactions not served yet.
+    pub fn remaining(&self) -> usize {
+        self.used
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|used| !**used)
+            .count()
+    }
+
+    fn take(&self, kind: &str, prompt: &str) -> anyhow::Result<Interaction> {
+        let mut used = self.used.lock().unwrap();
+        let found = self.interactions.iter().enumerate().find(|(i, recorded)| {
+            !used[*i] && recorded.kind() == kind && self.mode.matches(recorded.prompt(), prompt)
+        });
+        match found {
+            Some((i, recorded)) => {
+                used[i] = true;
+                Ok(recorded.clone())
+            }
+            None => {
+                let preview: String = prompt.chars().take(300).collect();
+                Err(anyhow::anyhow!(
+                    "No recorded {} reply in {} ({:?} match, {} of {} interactions unused) for prompt:\n{}",
+                    kind,
+                    self.source,
+                    self.mode,
+                    used.iter().filter(|u| !**u).count(),
+                    used.len(),
+                    preview
+                ))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        "replay"
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        0.0
+    }
+    async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+        Ok(self.generate_with_usage(prompt).await?.0)
+    }
+    async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+        match self.take("generate", prompt)? {
+            Interaction::Generate { reply, usage, .. } => {
+                let usage = usage.unwrap_or_else(|| Usage::estimate("replay", prompt, &reply, 0.0));
+                Ok((reply, usage))
+            }
+            Interaction::Chat { .. } => unreachable!("take matches the interaction kind"),
+        }
+    }
+    async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+        match self.take("chat", &emulated_prompt(request))? {
+            Interaction::Chat { response, .. } => Ok(response),
+            Interaction::Generate { .. } => unreachable!("take matches the interaction kind"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Message, MessageRole};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct CountingProvider;
+
+    #[async_trait]
+    impl LLMProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            0.0
+        }
+        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+            Ok(format!("{} chars", prompt.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = std::env::temp_dir()
+            .join(format!("synapse-cassette-{}", std::process::id()))
+            .join("session.json");
+        let recorder = RecordingProvider::new(Arc::new(CountingProvider), &path);
+        assert_eq!(recorder.generate("hello").await.unwrap(), "5 chars");
+        let request = ChatRequest::new(vec![Message::new(
+            MessageRole::User,
+            "hi there".to_string(),
+        )]);
+        let recorded = recorder.chat(&request).await.unwrap();
+        assert_eq!(recorder.cassette().interactions.len(), 2);
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        assert_eq!(replay.chat(&request).await.unwrap(), recorded);
+        assert_eq!(replay.generate("hello").await.unwrap(), "5 chars");
+        assert_eq!(replay.remaining(), 0);
+
+        let err = replay.generate("hello").await.unwrap_err().to_string();
+        assert!(err.contains("No recorded generate reply"), "{}", err);
+        assert!(err.contains("0 of 2 interactions unused"), "{}", err);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_normalized_matching() {
+        let cassette = Cassette {
+            interactions: vec![Interaction::Generate {
+                prompt: "Task 0b9c1d2e-3f40-4a5b-8c6d-7e8f9a0b1c2d created at 2026-01-02T03:04:05.123Z\n\n  (id 1700000000000)".to_string(),
+                reply: "ok".to_string(),
+                usage: None,
+            }],
+        };
+        let prompt = "Task 11111111-2222-4333-8444-555555555555 created at 2026-10-17T12:00:00+02:00 (id 1760000000000)";
+
+        let exact = ReplayProvider::new(cassette.clone());
+        assert!(exact.generate(prompt).await.is_err());
+
+        let normalized = ReplayProvider::new(cassette).with_match_mode(MatchMode::Normalized);
+        assert_eq!(normalized.generate(prompt).await.unwrap(), "ok");
+        assert_eq!(
+            normalize_prompt("step 12 of agent-1234567"),
+            "step 12 of agent-<n>"
+        );
+    }
+}
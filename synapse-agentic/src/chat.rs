@@ -0,0 +1,374 @@
+//! Chat-style requests with tool calling.
+//!
+//! A [`ChatRequest`] carries the conversation, the tools the model may call
+//! and the results of earlier calls. Providers with native function calling
+//! map it onto their own wire format; every other provider falls back to the
+//! prompt-based emulation in this module, which describes the tools in the
+//! prompt and parses calls back out of the reply with [`parse_emulated_reply`].
+
+use crate::decision::extract_json_object;
+use crate::prelude::{Deserialize, Message, MessageRole, Serialize, Usage, Value};
+
+/// A tool the model may call, with its arguments as a JSON Schema object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSchema {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl ToolSchema {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+        }
+    }
+}
+
+/// A tool invocation requested by the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Identifier echoed back by the matching tool result.
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Conversation plus the tools available for the next reply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub messages: Vec<Message>,
+    pub tools: Vec<ToolSchema>,
+}
+
+impl ChatRequest {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            tools: Vec::new(),
+        }
+    }
+    pub fn with_tools(mut self, tools: Vec<ToolSchema>) -> Self {
+        self.tools = tools;
+        self
+    }
+}
+
+/// The model's reply: text, tool calls, or both.
+///
+/// A reply without tool calls ends the model's turn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    /// Tokens the request used, when the provider reported or estimated them.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            tool_calls: Vec::new(),
+            usage: None,
+        }
+    }
+}
+
+/// Instructions appended to emulated prompts that offer tools.
+pub const TOOL_CALL_CONTRACT: &str = r#"To call tools, respond with a single JSON object and nothing else:
+{"tool_calls": [{"name": "<tool name>", "arguments": { <arguments matching its parameters> }}]}
+To finish, respond in plain text without tool calls; that text is your final answer."#;
+
+/// Render `request` as a single prompt for providers without native tool calling.
+pub fn emulated_prompt(request: &ChatRequest) -> String {
+    let mut prompt = String::new();
+
+    for message in &request.messages {
+        if matches!(message.role, MessageRole::System) {
+            prompt.push_str(&message.content);
+            prompt.push_str("\n\n");
+        }
+    }
+
+    if !request.tools.is_empty() {
+        prompt.push_str("TOOLS:\n");
+        for tool in &request.tools {
+            prompt.push_str(&format!(
+                "- {}: {}\n  parameters: {}\n",
+                tool.name, tool.description, tool.parameters
+            ));
+        }
+        prompt.push('\n');
+    }
+
+    prompt.push_str("CONVERSATION:\n");
+    for message in &request.messages {
+        match message.role {
+            MessageRole::System => {}
+            MessageRole::User => prompt.push_str(&format!("User: {}\n", message.content)),
+            MessageRole::Assistant => {
+                if !message.content.is_empty() {
+                    prompt.push_str(&format!("Assistant: {}\n", message.content));
+                }
+                for call in &message.tool_calls {
+                    prompt.push_str(&format!(
+                        "Assistant called {} with {}\n",
+                        call.name, call.arguments
+                    ));
+                }
+            }
+            MessageRole::Tool => prompt.push_str(&format!(
+                "Result of {}: {}\n",
+                message.name.as_deref().unwrap_or("tool"),
+                message.content
+            )),
+        }
+    }
+
+    if !request.tools.is_empty() {
+        prompt.push('\n');
+        prompt.push_str(TOOL_CALL_CONTRACT);
+    }
+    prompt
+}
+
+/// Parse a reply to an [`emulated_prompt`].
+///
+/// Accepts `{"tool_calls": [...]}` as well as a single `{"name"|"tool"|"action",
+/// "arguments"|"parameters"}` object, fenced or surrounded by prose. Calls to
+/// tools that were not offered are dropped; a reply without valid calls is
+/// returned as text.
+pub fn parse_emulated_reply(reply: &str, tools: &[ToolSchema]) -> ChatResponse {
+    let Some(value) = extract_json_object(reply).filter(|_| !tools.is_empty()) else {
+        return ChatResponse::text(reply.trim());
+    };
+
+    let offered = |name: &str| tools.iter().any(|t| t.name == name);
+    let candidates: Vec<ToolCall> = match value.get("tool_calls").and_then(Value::as_array) {
+        Some(items) => items.iter().filter_map(call_from_value).collect(),
+        None => call_from_value(&value).into_iter().collect(),
+    };
+    let calls: Vec<ToolCall> = candidates
+        .into_iter()
+        .filter(|call| offered(&call.name))
+        .enumerate()
+        .map(|(i, call)| ToolCall {
+            id: format!("call_{}", i + 1),
+            ..call
+        })
+        .collect();
+
+    if calls.is_empty() {
+        return ChatResponse::text(reply.trim());
+    }
+
+    let content = ["content", "reasoning"]
+        .iter()
+        .find_map(|k| value.get(*k).and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string();
+    ChatResponse {
+        content,
+        tool_calls: calls,
+        usage: None,
+    }
+}
+
+fn call_from_value(value: &Value) -> Option<ToolCall> {
+    // OpenAI-style entries nest the call under "function".
+    let value = value.get("function").unwrap_or(value);
+    let name = ["name", "tool", "action"]
+        .iter()
+        .find_map(|k| value.get(*k).and_then(Value::as_str))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())?;
+
+    let arguments = match ["arguments", "parameters", "args"]
+        .iter()
+        .find_map(|k| value.get(*k))
+    {
+        // Some models send the arguments as an encoded JSON string.
+        Some(Value::String(raw)) => serde_json::from_str(raw).unwrap_or(Value::Null),
+        Some(args) => args.clone(),
+        None => Value::Null,
+    };
+
+    Some(ToolCall {
+        id: String::new(),
+        name: name.to_string(),
+        arguments: if arguments.is_null() {
+            Value::Object(Default::default())
+        } else {
+            arguments
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DecisionEngine, LLMProvider};
+    use serde_json::json;
+
+    fn tools() -> Vec<ToolSchema> {
+        vec![
+            ToolSchema::new(
+                "read_file",
+                "Read a file.",
+                json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+            ),
+            ToolSchema::new("git_status", "Show the git status.", json!({})),
+        ]
+    }
+
+    #[test]
+    fn test_parse_emulated_tool_calls() {
+        let reply = "```json\n{\"tool_calls\": [{\"name\": \"read_file\", \"arguments\": {\"path\": \"a.rs\"}}, {\"name\": \"git_status\"}, {\"name\": \"rm_rf\"}]}\n```";
+        let response = parse_emulated_reply(reply, &tools());
+        assert_eq!(response.tool_calls.len(), 2);
+        assert_eq!(response.tool_calls[0].id, "call_1");
+        assert_eq!(response.tool_calls[0].arguments, json!({"path": "a.rs"}));
+        assert_eq!(response.tool_calls[1].name, "git_status");
+        assert_eq!(response.tool_calls[1].arguments, json!({}));
+
+        let single = parse_emulated_reply(
+            r#"{"reasoning": "look", "action": "read_file", "parameters": "{\"path\": \"b\"}"}"#,
+            &tools(),
+        );
+        assert_eq!(single.content, "look");
+        assert_eq!(single.tool_calls[0].arguments, json!({"path": "b"}));
+    }
+
+    #[test]
+    fn test_parse_emulated_text_reply() {
+        let response = parse_emulated_reply("  All done.  ", &tools());
+        assert_eq!(response, ChatResponse::text("All done."));
+
+        // JSON naming no offered tool is just text.
+        let reply = r#"The config is {"name": "demo"}."#;
+        assert!(parse_emulated_reply(reply, &tools()).tool_calls.is_empty());
+        assert!(parse_emulated_reply(r#"{"name": "read_file"}"#, &[])
+            .tool_calls
+            .is_empty());
+    }
+
+    #[test]
+    fn test_emulated_prompt_renders_tools_and_results() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({"path": "a.rs"}),
+        };
+        let request = ChatRequest::new(vec![
+            Message::new(MessageRole::System, "Be brief.".to_string()),
+            Message::new(MessageRole::User, "Open a.rs".to_string()),
+            Message::assistant_tool_calls(String::new(), vec![call.clone()]),
+            Message::tool_result(&call, "fn main() {}".to_string()),
+        ])
+        .with_tools(tools());
+
+        let prompt = emulated_prompt(&request);
+        assert!(prompt.starts_with("Be brief."));
+        assert!(prompt.contains("- read_file: Read a file."));
+        assert!(prompt.contains("Assistant called read_file with {\"path\":\"a.rs\"}"));
+        assert!(prompt.contains("Result of read_file: fn main() {}"));
+        assert!(prompt.ends_with(TOOL_CALL_CONTRACT));
+    }
+
+    #[test]
+    fn test_gemini_body_pairs_calls_and_results() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({"path": "a.rs"}),
+        };
+        let orphan = ToolCall {
+            id: "gone".to_string(),
+            name: "git_status".to_string(),
+            arguments: json!({}),
+        };
+        let request = ChatRequest::new(vec![
+            Message::new(MessageRole::System, "Be brief.".to_string()),
+            Message::tool_result(&orphan, "clean".to_string()),
+            Message::new(MessageRole::User, "Open a.rs".to_string()),
+            Message::assistant_tool_calls(String::new(), vec![call.clone()]),
+            Message::tool_result(&call, "fn main() {}".to_string()),
+        ])
+        .with_tools(tools());
+
+        let body = crate::prelude::gemini_chat_body(&request);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(
+            contents[0]["parts"][0]["text"],
+            "Result of git_status: clean"
+        );
+        assert_eq!(contents[0]["parts"][1]["text"], "Open a.rs");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["name"], "read_file");
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"]["response"],
+            json!({"content": "fn main() {}"})
+        );
+
+        let declarations = body["tools"][0]["functionDeclarations"].as_array().unwrap();
+        assert!(declarations[0].get("parameters").is_some());
+        assert!(declarations[1].get("parameters").is_none());
+    }
+
+    #[derive(Debug)]
+    struct EchoProvider;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            0.0
+        }
+        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+            Ok(if prompt.contains("Result of git_status") {
+                "Clean tree.".to_string()
+            } else {
+                r#"{"tool_calls": [{"name": "git_status", "arguments": {}}]}"#.to_string()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_engine_chat_emulates_tool_calls() {
+        let engine = DecisionEngine::builder()
+            .with_provider(EchoProvider)
+            .build();
+        let mut request = ChatRequest::new(vec![Message::new(
+            MessageRole::User,
+            "Is the tree clean?".to_string(),
+        )])
+        .with_tools(tools());
+
+        let first = engine.chat(&request).await.unwrap();
+        assert_eq!(first.tool_calls.len(), 1);
+        let call = first.tool_calls[0].clone();
+        request.messages.push(Message::assistant_tool_calls(
+            first.content,
+            vec![call.clone()],
+        ));
+        request
+            .messages
+            .push(Message::tool_result(&call, "nothing to commit".to_string()));
+
+        let second = engine.chat(&request).await.unwrap();
+        assert_eq!(second.content, "Clean tree.");
+        assert!(second.tool_calls.is_empty());
+        let usage = second.usage.unwrap();
+        assert!(usage.estimated && usage.prompt_tokens > 0);
+
+        let mock = DecisionEngine::new().chat(&request).await.unwrap();
+        assert!(mock.tool_calls.is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+//! Prompt contract and tolerant reply parser for [`DecisionEngine`].
+//!
+//! The engine asks the provider for a single JSON object:
+//!
+//! ```json
+//! {"reasoning": "...", "action": "...", "parameters": {...}, "confidence": 0.8}
+//! ```
+//!
+//! Models rarely comply perfectly, so [`parse_decision`] accepts fenced code
+//! blocks, prose around the object, trailing commas and truncated JSON.
+//!
+//! Decisions are for one-shot answers; agent loops that call tools use
+//! [`ChatRequest`] and the engine's native or emulated function calling.
+//!
+//! [`ChatRequest`]: crate::chat::ChatRequest
+//! [`DecisionEngine`]: crate::prelude::DecisionEngine
+
+use crate::prelude::{Decision, DecisionContext, Value};
+
+/// Instructions appended to every decision prompt.
+pub const DECISION_CONTRACT: &str = r#"Respond with a single JSON object and nothing else:
+{
+  "reasoning": "<short explanation of why this action is next>",
+  "action": "<action name>",
+  "parameters": { <arguments for the action, or null> },
+  "confidence": <number between 0 and 1>
+}
+Use "chat" to reply to the user, putting the reply in "reasoning".
+Use "done" when the goal is achieved and no further action is needed."#;
+
+/// Build the prompt sent to the provider for a decision.
+pub fn decision_prompt(ctx: &DecisionContext) -> String {
+    let mut prompt = format!(
+        "QUERY: {}\nSUMMARY: {}\n",
+        ctx.query,
+        ctx.summary.as_deref().unwrap_or("None")
+    );
+
+    if !ctx.metadata.is_empty() {
+        let mut keys: Vec<&String> = ctx.metadata.keys().collect();
+        keys.sort();
+        prompt.push_str("\nCONTEXT:\n");
+        for key in keys {
+            prompt.push_str(&format!("- {}: {}\n", key, ctx.metadata[key]));
+        }
+    }
+
+    if let Some(data) = &ctx.data {
+        prompt.push_str(&format!("\nDATA: {}\n", data));
+    }
+
+    prompt.push_str("\nBased on the above, decide the next action.\n\n");
+    prompt.push_str(DECISION_CONTRACT);
+    prompt
+}
+
+/// Build the follow-up prompt used when a reply could not be parsed.
+pub fn repair_prompt(original_prompt: &str, reply: &str, error: &anyhow::Error) -> String {
+    format!(
+        "{}\n\nYour previous reply could not be parsed ({}).\nPrevious reply:\n{}\n\n\
+         Reply again with ONLY the JSON object described above.",
+        original_prompt, error, reply
+    )
+}
+
+/// Parse a provider reply into a [`Decision`].
+///
+/// `providers_used` is left empty; the engine fills it in.
+pub fn parse_decision(reply: &str) -> anyhow::Result<Decision> {
+    let value = extract_json_object(reply)
+        .ok_or_else(|| anyhow::anyhow!("no JSON object found in reply"))?;
+    decision_from_value(&value)
+}
+
+fn decision_from_value(value: &Value) -> anyhow::Result<Decision> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("reply is not a JSON object"))?;
+
+    let parameters = ["parameters", "params", "arguments", "args"]
+        .iter()
+        .find_map(|k| obj.get(*k))
+        .filter(|v| !v.is_null())
+        .cloned();
+
+    let action = match obj.get("action").and_then(Value::as_str) {
+        Some(action) if !action.trim().is_empty() => action.trim().to_string(),
+        _ => match obj.get("tool").and_then(Value::as_str) {
+            Some(tool) if !tool.trim().is_empty() => format!("call:{}", tool.trim()),
+            _ => anyhow::bail!("missing \"action\" field"),
+        },
+    };
+
+    let reasoning = ["reasoning", "reason", "thought"]
+        .iter()
+        .find_map(|k| obj.get(*k))
+        .map(|v| match v {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_default();
+
+    let confidence = obj.get("confidence").map(parse_confidence).unwrap_or(0.5);
+
+    Ok(Decision {
+        reasoning,
+        action,
+        parameters,
+        confidence,
+        providers_used: Vec::new(),
+        usage: None,
+    })
+}
+
+fn parse_confidence(value: &Value) -> f32 {
+    let (raw, percent) = match value {
+        Value::Number(n) => (n.as_f64(), false),
+        Value::String(s) => {
+            let s = s.trim();
+            let percent = s.ends_with('%');
+            (s.trim_end_matches('%').trim().parse::<f64>().ok(), percent)
+        }
+        _ => (None, false),
+    };
+    let Some(raw) = raw else {
+        return 0.5;
+    };
+
+    // Some models answer on a 0-100 scale; a value just over 1 is an
+    // out-of-range fraction, not 1.2%.
+    let normalized = if percent || raw >= 2.0 {
+        raw / 100.0
+    } else {
+        raw
+    };
+    normalized.clamp(0.0, 1.0) as f32
+}
+
+/// Locate and decode the first JSON object in `text`.
+pub(crate) fn extract_json_object(text: &str) -> Option<Value> {
+    fenced_blocks(text)
+        .into_iter()
+        .chain(std::iter::once(text))
+        .find_map(object_from_text)
+}
+
+/// Contents of all ``` fenced blocks, in order of appearance.
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        // Skip the language tag (e.g. ```json).
+        let body_start = after.find('\n').map(|i| i + 1).unwrap_or(after.len());
+        let body = &after[body_start..];
+        match body.find("```") {
+            Some(end) => {
+                blocks.push(&body[..end]);
+                rest = &body[end + 3..];
+            }
+            None => {
+                // Unterminated fence: the reply was probably cut off.
+                blocks.push(body);
+                break;
+            }
+        }
+    }
+    blocks
+}
+
+fn object_from_text(text: &str) -> Option<Value> {
+    let start = text.find('{')?;
+    let scan = scan_object(&text[start..]);
+
+    if let Some(end) = scan.complete_at {
+        let candidate = &text[start..start + end];
+        return serde_json::from_str(candidate)
+            .ok()
+            .or_else(|| serde_json::from_str(&strip_trailing_commas(candidate)).ok());
+    }
+
+    // Partial JSON: close whatever is still open, then retry from the last
+    // clean separator backwards until something decodes.
+    let partial = &text[start..];
+    let mut attempts = vec![close_partial(partial, scan.in_string, &scan.stack)];
+    for (cut, stack) in scan.cut_points.iter().rev() {
+        attempts.push(close_partial(&partial[..*cut], false, stack));
+    }
+    attempts.into_iter().find_map(|candidate| {
+        serde_json::from_str::<Value>(&strip_trailing_commas(&candidate))
+            .ok()
+            .filter(Value::is_object)
+    })
+}
+
+struct ObjectScan {
+    /// Byte length of the object if its closing brace was found.
+    complete_at: Option<usize>,
+    /// Whether the scan ended inside a string literal.
+    in_string: bool,
+    /// Open brackets at the end of the scan.
+    stack: Vec<char>,
+    /// Positions of separators outside strings, with the open brackets there.
+    cut_points: Vec<(usize, Vec<char>)>,
+}
+
+fn scan_object(text: &str) -> ObjectScan {
+    let mut stack = Vec::new();
+    let mut cut_points = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => stack.push(c),
+            '}' | ']' => {
+                stack.pop();
+                if stack.is_empty() {
+                    return ObjectScan {
+                        complete_at: Some(i + 1),
+                        in_string: false,
+                        stack,
+                        cut_points,
+                    };
+                }
+            }
+            ',' => cut_points.push((i, stack.clone())),
+            _ => {}
+        }
+    }
+
+    ObjectScan {
+        complete_at: None,
+        in_string,
+        stack,
+        cut_points,
+    }
+}
+
+fn close_partial(prefix: &str, in_string: bool, stack: &[char]) -> String {
+    let mut out = prefix.to_string();
+    if in_string {
+        if out.ends_with('\\') {
+            out.pop();
+        }
+        out.push('"');
+    }
+    let trimmed_len = out.trim_end().len();
+    out.truncate(trimmed_len);
+    if out.ends_with(',') {
+        out.pop();
+    } else if out.ends_with(':') {
+        out.push_str("null");
+    }
+    for open in stack.iter().rev() {
+        out.push(if *open == '{' { '}' } else { ']' });
+    }
+    out
+}
+
+/// Remove commas directly followed by a closing bracket, outside strings.
+fn strip_trailing_commas(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|n| !n.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_plain_json() {
+        let d = parse_decision(
+            r#"{"reasoning":"need the file","action":"read_file","parameters":{"path":"a.rs"},"confidence":0.9}"#,
+        )
+        .unwrap();
+        assert_eq!(d.action, "read_file");
+        assert_eq!(d.reasoning, "need the file");
+        assert_eq!(d.parameters, Some(json!({"path": "a.rs"})));
+        assert!((d.confidence - 0.9).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_parse_fenced_json_with_prose() {
+        let reply = "Sure! Here is my decision:\n```json\n{\"reasoning\": \"scan first\", \"action\": \"call:scan_workspace\", \"parameters\": {}, \"confidence\": 80}\n```\nLet me know.";
+        let d = parse_decision(reply).unwrap();
+        assert_eq!(d.action, "call:scan_workspace");
+        assert!((d.confidence - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_confidence_scales() {
+        let confidence = |value: &str| {
+            let reply = format!(r#"{{"action": "chat", "confidence": {}}}"#, value);
+            parse_decision(&reply).unwrap().confidence
+        };
+        assert!((confidence("1.2") - 1.0).abs() < f32::EPSILON);
+        assert!((confidence("-0.3") - 0.0).abs() < f32::EPSILON);
+        assert!((confidence("0.7") - 0.7).abs() < 1e-6);
+        assert!((confidence("70") - 0.7).abs() < 1e-6);
+        assert!((confidence("\"1.5%\"") - 0.015).abs() < 1e-6);
+        assert!((confidence("\"85 %\"") - 0.85).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_trailing_prose_and_commas() {
+        let reply = r#"{"action": "create_task", "parameters": {"project": "p", "description": "d",},} I chose this because..."#;
+        let d = parse_decision(reply).unwrap();
+        assert_eq!(d.action, "create_task");
+        assert_eq!(d.parameters.unwrap()["project"], "p");
+        assert!((d.confidence - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_parse_partial_json() {
+        let reply = r#"{"reasoning": "write it", "action": "write_file", "parameters": {"path": "x.txt", "content": "hel"#;
+        let d = parse_decision(reply).unwrap();
+        assert_eq!(d.action, "write_file");
+        assert_eq!(d.parameters.unwrap()["content"], "hel");
+
+        let reply = r#"{"action": "git_status", "reasoning": "check", "confid"#;
+        let d = parse_decision(reply).unwrap();
+        assert_eq!(d.action, "git_status");
+    }
+
+    #[test]
+    fn test_parse_tool_alias() {
+        let d = parse_decision(r#"{"tool": "search_code", "arguments": {"query": "fn main"}}"#)
+            .unwrap();
+        assert_eq!(d.action, "call:search_code");
+        assert_eq!(d.parameters, Some(json!({"query": "fn main"})));
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        assert!(parse_decision("just some text").is_err());
+        assert!(parse_decision(r#"{"reasoning": "no action"}"#).is_err());
+    }
+
+    #[derive(Debug)]
+    struct ScriptedProvider {
+        replies: std::sync::Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::prelude::LLMProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            0.0
+        }
+        async fn generate(&self, _prompt: &str) -> anyhow::Result<String> {
+            Ok(self.replies.lock().unwrap().remove(0).to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decide_sends_repair_prompt() {
+        let engine = crate::prelude::DecisionEngine::builder()
+            .with_provider(ScriptedProvider {
+                replies: std::sync::Mutex::new(vec![
+                    "I think we should read the file.",
+                    r#"{"reasoning": "read", "action": "read_file", "parameters": {"path": "a"}, "confidence": 0.7}"#,
+                ]),
+            })
+            .build();
+        let d = engine.decide(&DecisionContext::new("q")).await.unwrap();
+        assert_eq!(d.action, "read_file");
+        assert_eq!(d.providers_used, vec!["scripted".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_decide_falls_back_to_chat() {
+        let engine = crate::prelude::DecisionEngine::builder()
+            .with_provider(ScriptedProvider {
+                replies: std::sync::Mutex::new(vec!["hello", "still prose"]),
+            })
+            .build();
+        let d = engine.decide(&DecisionContext::new("q")).await.unwrap();
+        assert_eq!(d.action, "chat");
+        assert_eq!(d.reasoning, "still prose");
+        assert_eq!(d.confidence, 0.0);
+    }
+
+    #[test]
+    fn test_decision_prompt_includes_contract_and_metadata() {
+        let ctx = DecisionContext::new("q")
+            .with_summary("s")
+            .with_metadata("repo_url", "https://example.com".to_string());
+        let prompt = decision_prompt(&ctx);
+        assert!(prompt.contains("QUERY: q"));
+        assert!(prompt.contains("- repo_url: https://example.com"));
+        assert!(prompt.contains("\"action\""));
+    }
+}
@@ -1,8 +1,37 @@
+pub mod cassette;
+pub mod chat;
+pub mod decision;
+#[cfg(test)]
+mod mock_http;
+pub mod ollama;
+pub mod openai;
+pub mod routing;
+pub mod stream;
+pub mod tokenizer;
+pub mod usage;
+
 pub mod prelude {
     pub use async_trait::async_trait;
     pub use serde::{Deserialize, Serialize};
     pub use serde_json::Value;
 
+    pub use crate::cassette::{
+        Cassette, Interaction, MatchMode, RecordingProvider, ReplayProvider,
+    };
+    pub use crate::chat::{ChatRequest, ChatResponse, ToolCall, ToolSchema};
+    pub use crate::decision::{decision_prompt, parse_decision};
+    pub use crate::ollama::OllamaProvider;
+    pub use crate::openai::OpenAiCompatibleProvider;
+    pub use crate::routing::{
+        CooldownEntry, CooldownPolicy, CooldownStore, ErrorClass, InMemoryCooldownStore,
+        ProviderError, ProviderStats,
+    };
+    pub use crate::stream::TokenStream;
+    pub use crate::tokenizer::{
+        context_window, HfTokenCounter, TiktokenCounter, TiktokenEncoding, TokenizerRegistry,
+    };
+    pub use crate::usage::Usage;
+
     use std::sync::Arc;
     use tokio::sync::mpsc;
 
@@ -41,6 +70,30 @@ pub mod prelude {
         fn name(&self) -> &str;
         fn cost_per_1k_tokens(&self) -> f64;
         async fn generate(&self, prompt: &str) -> anyhow::Result<String>;
+        /// Like [`generate`](Self::generate), also reporting the tokens used.
+        /// Providers whose APIs report counts override this; the default
+        /// estimates them from the text.
+        async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+            let reply = self.generate(prompt).await?;
+            let usage = Usage::estimate(self.name(), prompt, &reply, self.cost_per_1k_tokens());
+            Ok((reply, usage))
+        }
+        /// Stream the reply as it is generated. Providers that cannot stream
+        /// yield the full reply as a single delta.
+        async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+            let reply = self.generate(prompt).await?;
+            Ok(crate::stream::single_delta(reply))
+        }
+        /// Answer a chat request, possibly with tool calls. Providers without
+        /// native function calling use the prompt-based emulation in [`crate::chat`].
+        async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+            let (reply, usage) = self
+                .generate_with_usage(&crate::chat::emulated_prompt(request))
+                .await?;
+            let mut response = crate::chat::parse_emulated_reply(&reply, &request.tools);
+            response.usage = Some(usage);
+            Ok(response)
+        }
     }
 
     pub trait Provider: LLMProvider {}
@@ -50,85 +103,314 @@ pub mod prelude {
     pub struct GeminiProvider {
         api_key: String,
         model: String,
+        cost_per_1k: f64,
     }
     impl GeminiProvider {
         pub fn new(api_key: String, model: String) -> Self {
-            Self { api_key, model }
+            Self {
+                api_key,
+                model,
+                cost_per_1k: 0.0,
+            }
         }
-    }
-    #[async_trait]
-    impl LLMProvider for GeminiProvider {
-        fn name(&self) -> &str {
-            &self.model
+        /// Price used to report spend in [`Usage`].
+        pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+            self.cost_per_1k = cost;
+            self
         }
-        fn cost_per_1k_tokens(&self) -> f64 {
-            0.0
+
+        /// Reported token counts, estimated from the text when missing.
+        fn usage(&self, reported: Option<GeminiUsage>, prompt: &str, reply: &str) -> Usage {
+            match reported {
+                Some(u) => Usage::new(
+                    &self.model,
+                    u.prompt_token_count,
+                    u.candidates_token_count,
+                    self.cost_per_1k,
+                ),
+                None => Usage::estimate(&self.model, prompt, reply, self.cost_per_1k),
+            }
         }
-        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+
+        /// Request body for a single-turn text prompt.
+        fn prompt_body(prompt: &str) -> Value {
+            Self::request_body(serde_json::json!([{"parts": [{"text": prompt}]}]))
+        }
+
+        fn request_body(contents: Value) -> Value {
+            serde_json::json!({
+                "contents": contents,
+                "generationConfig": {
+                    "temperature": 0.7,
+                    "maxOutputTokens": 2048
+                }
+            })
+        }
+
+        /// POST `body` to the model's `method` endpoint and check the status.
+        async fn send(
+            &self,
+            method: &str,
+            body: &Value,
+            stream: bool,
+        ) -> anyhow::Result<reqwest::Response> {
             let api_key = std::env::var("GEMINI_API_KEY")
                 .or_else(|_| Ok(self.api_key.clone()))
                 .map_err(|_: std::env::VarError| anyhow::anyhow!("GEMINI_API_KEY not set"))?;
 
             let client = reqwest::Client::new();
             let url = format!(
-                "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-                self.model, api_key
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:{}?{}key={}",
+                self.model,
+                method,
+                if stream { "alt=sse&" } else { "" },
+                api_key
             );
 
-            let body = serde_json::json!({
-                "contents": [{"parts": [{"text": prompt}]}],
-                "generationConfig": {
-                    "temperature": 0.7,
-                    "maxOutputTokens": 2048
-                }
-            });
-
             let response = client
                 .post(&url)
-                .json(&body)
+                .json(body)
                 .send()
                 .await
-                .map_err(|e| anyhow::anyhow!("Gemini API request failed: {}", e))?;
+                .map_err(|e| crate::routing::request_error("Gemini API", e))?;
+            crate::routing::check_status("Gemini", response).await
+        }
+    }
 
-            let status = response.status();
-            if !status.is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                tracing::error!("Gemini API error ({}): {}", status, error_text);
-                return Err(anyhow::anyhow!("Gemini API returned error: {}", status));
-            }
+    #[derive(Deserialize)]
+    struct GeminiResponse {
+        candidates: Option<Vec<GeminiCandidate>>,
+        #[serde(rename = "usageMetadata")]
+        usage_metadata: Option<GeminiUsage>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiUsage {
+        #[serde(rename = "promptTokenCount", default)]
+        prompt_token_count: u64,
+        #[serde(rename = "candidatesTokenCount", default)]
+        candidates_token_count: u64,
+    }
+    #[derive(Deserialize)]
+    struct GeminiCandidate {
+        content: Option<GeminiContent>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiContent {
+        parts: Option<Vec<GeminiPart>>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiPart {
+        text: Option<String>,
+        #[serde(rename = "functionCall")]
+        function_call: Option<GeminiFunctionCall>,
+    }
+    #[derive(Deserialize)]
+    struct GeminiFunctionCall {
+        id: Option<String>,
+        name: String,
+        args: Option<Value>,
+    }
 
-            #[derive(Deserialize)]
-            struct GeminiResponse {
-                candidates: Option<Vec<Candidate>>,
-            }
-            #[derive(Deserialize)]
-            struct Candidate {
-                content: Option<Content>,
-            }
-            #[derive(Deserialize)]
-            struct Content {
-                parts: Option<Vec<Part>>,
+    /// Map a chat request onto Gemini's `contents`, `systemInstruction` and
+    /// `functionDeclarations`.
+    pub(crate) fn gemini_chat_body(request: &ChatRequest) -> Value {
+        let mut system = Vec::new();
+        let mut contents: Vec<(&str, Vec<Value>)> = Vec::new();
+        let mut calls: std::collections::HashMap<&str, &str> = std::collections::HashMap::new();
+
+        for message in &request.messages {
+            let (role, parts) = match message.role {
+                MessageRole::System => {
+                    system.push(serde_json::json!({"text": message.content}));
+                    continue;
+                }
+                MessageRole::User => ("user", vec![serde_json::json!({"text": message.content})]),
+                MessageRole::Assistant => {
+                    let mut parts = Vec::new();
+                    if !message.content.is_empty() {
+                        parts.push(serde_json::json!({"text": message.content}));
+                    }
+                    for call in &message.tool_calls {
+                        calls.insert(&call.id, &call.name);
+                        parts.push(serde_json::json!({
+                            "functionCall": {"name": call.name, "args": call.arguments}
+                        }));
+                    }
+                    ("model", parts)
+                }
+                MessageRole::Tool => {
+                    let name = message.tool_call_id.as_deref().and_then(|id| calls.get(id));
+                    let part = match name {
+                        Some(name) => {
+                            // Gemini wants an object; wrap anything else.
+                            let response = serde_json::from_str::<Value>(&message.content)
+                                .ok()
+                                .filter(Value::is_object)
+                                .unwrap_or_else(|| serde_json::json!({"content": message.content}));
+                            serde_json::json!({
+                                "functionResponse": {"name": name, "response": response}
+                            })
+                        }
+                        // The call was compacted away; keep the result as plain text.
+                        None => serde_json::json!({"text": format!(
+                            "Result of {}: {}",
+                            message.name.as_deref().unwrap_or("tool"),
+                            message.content
+                        )}),
+                    };
+                    ("user", vec![part])
+                }
+            };
+            if parts.is_empty() {
+                continue;
             }
-            #[derive(Deserialize)]
-            struct Part {
-                text: Option<String>,
+            // Gemini expects alternating turns; merge consecutive ones.
+            match contents.last_mut() {
+                Some((last, existing)) if *last == role => existing.extend(parts),
+                _ => contents.push((role, parts)),
             }
+        }
 
-            let resp: GeminiResponse = response
+        let contents: Vec<Value> = contents
+            .into_iter()
+            .map(|(role, parts)| serde_json::json!({"role": role, "parts": parts}))
+            .collect();
+        let mut body = GeminiProvider::request_body(Value::Array(contents));
+        if !system.is_empty() {
+            body["systemInstruction"] = serde_json::json!({"parts": system});
+        }
+        if !request.tools.is_empty() {
+            let declarations: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    let mut declaration = serde_json::json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                    });
+                    let parameters = gemini_schema(&tool.parameters);
+                    // Gemini rejects object schemas without properties.
+                    if parameters
+                        .get("properties")
+                        .and_then(Value::as_object)
+                        .is_some_and(|p| !p.is_empty())
+                    {
+                        declaration["parameters"] = parameters;
+                    }
+                    declaration
+                })
+                .collect();
+            body["tools"] = serde_json::json!([{"functionDeclarations": declarations}]);
+        }
+        body
+    }
+
+    /// Strip JSON Schema keywords outside the OpenAPI subset Gemini accepts.
+    fn gemini_schema(schema: &Value) -> Value {
+        match schema {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .filter(|(k, _)| {
+                        !matches!(
+                            k.as_str(),
+                            "$schema" | "$id" | "additionalProperties" | "default" | "examples"
+                        )
+                    })
+                    .map(|(k, v)| (k.clone(), gemini_schema(v)))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(gemini_schema).collect()),
+            other => other.clone(),
+        }
+    }
+    impl GeminiResponse {
+        fn into_parts(self) -> Vec<GeminiPart> {
+            self.candidates
+                .and_then(|c| c.into_iter().next())
+                .and_then(|c| c.content)
+                .and_then(|c| c.parts)
+                .unwrap_or_default()
+        }
+    }
+    #[async_trait]
+    impl LLMProvider for GeminiProvider {
+        fn name(&self) -> &str {
+            &self.model
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            self.cost_per_1k
+        }
+        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+            Ok(self.generate_with_usage(prompt).await?.0)
+        }
+        async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+            let response = self
+                .send("generateContent", &Self::prompt_body(prompt), false)
+                .await?;
+
+            let mut resp: GeminiResponse = response
                 .json()
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to parse Gemini response: {}", e))?;
 
+            let reported = resp.usage_metadata.take();
             let text = resp
-                .candidates
-                .and_then(|c| c.into_iter().next())
-                .and_then(|c| c.content)
-                .and_then(|c| c.parts)
-                .and_then(|p| p.into_iter().next())
+                .into_parts()
+                .into_iter()
+                .next()
                 .and_then(|p| p.text)
                 .ok_or_else(|| anyhow::anyhow!("No text in Gemini response"))?;
 
-            Ok(text)
+            let usage = self.usage(reported, prompt, &text);
+            Ok((text, usage))
+        }
+        async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+            let response = self
+                .send("streamGenerateContent", &Self::prompt_body(prompt), true)
+                .await?;
+
+            Ok(crate::stream::sse_deltas(
+                response.bytes_stream(),
+                |data: &str| {
+                    let chunk: GeminiResponse = serde_json::from_str(data).map_err(|e| {
+                        anyhow::anyhow!("Failed to parse Gemini stream chunk: {}", e)
+                    })?;
+                    let text: String = chunk
+                        .into_parts()
+                        .into_iter()
+                        .filter_map(|p| p.text)
+                        .collect();
+                    Ok((!text.is_empty()).then_some(text))
+                },
+            ))
+        }
+        async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+            let body = gemini_chat_body(request);
+            let response = self.send("generateContent", &body, false).await?;
+
+            let mut resp: GeminiResponse = response
+                .json()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to parse Gemini response: {}", e))?;
+
+            let reported = resp.usage_metadata.take();
+            let mut reply = ChatResponse::default();
+            for part in resp.into_parts() {
+                if let Some(text) = part.text {
+                    reply.content.push_str(&text);
+                }
+                if let Some(call) = part.function_call {
+                    reply.tool_calls.push(ToolCall {
+                        id: call
+                            .id
+                            .unwrap_or_else(|| format!("call_{}", reply.tool_calls.len() + 1)),
+                        name: call.name,
+                        arguments: call.args.unwrap_or_else(|| serde_json::json!({})),
+                    });
+                }
+            }
+            reply.usage = Some(self.usage(reported, &body.to_string(), &reply.content));
+            Ok(reply)
         }
     }
 
@@ -137,6 +419,7 @@ pub mod prelude {
         api_key: String,
         group_id: String,
         model: String,
+        cost_per_1k: f64,
     }
     impl MinimaxProvider {
         pub fn new(api_key: String, group_id: String, model: String) -> Self {
@@ -144,18 +427,17 @@ pub mod prelude {
                 api_key,
                 group_id,
                 model,
+                cost_per_1k: 0.0,
             }
         }
-    }
-    #[async_trait]
-    impl LLMProvider for MinimaxProvider {
-        fn name(&self) -> &str {
-            &self.model
-        }
-        fn cost_per_1k_tokens(&self) -> f64 {
-            0.0
+        /// Price used to report spend in [`Usage`].
+        pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+            self.cost_per_1k = cost;
+            self
         }
-        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+
+        /// POST a chat completion for `prompt` and check the status.
+        async fn send(&self, prompt: &str, stream: bool) -> anyhow::Result<reqwest::Response> {
             let api_key = std::env::var("MINIMAX_API_KEY")
                 .or_else(|_| Ok(self.api_key.clone()))
                 .map_err(|_: std::env::VarError| anyhow::anyhow!("MINIMAX_API_KEY not set"))?;
@@ -176,7 +458,8 @@ pub mod prelude {
                     {"role": "user", "content": prompt}
                 ],
                 "temperature": 0.7,
-                "max_tokens": 2048
+                "max_tokens": 2048,
+                "stream": stream
             });
 
             let response = client
@@ -186,27 +469,44 @@ pub mod prelude {
                 .json(&body)
                 .send()
                 .await
-                .map_err(|e| anyhow::anyhow!("MiniMax API request failed: {}", e))?;
-
-            let status = response.status();
-            if !status.is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                tracing::error!("MiniMax API error ({}): {}", status, error_text);
-                return Err(anyhow::anyhow!("MiniMax API returned error: {}", status));
-            }
+                .map_err(|e| crate::routing::request_error("MiniMax API", e))?;
+            crate::routing::check_status("MiniMax", response).await
+        }
+    }
 
-            #[derive(Deserialize)]
-            struct MinimaxResponse {
-                choices: Option<Vec<Choice>>,
-            }
-            #[derive(Deserialize)]
-            struct Choice {
-                messages: Option<Vec<Message>>,
-            }
-            #[derive(Deserialize)]
-            struct Message {
-                text: Option<String>,
-            }
+    #[derive(Deserialize)]
+    struct MinimaxResponse {
+        choices: Option<Vec<MinimaxChoice>>,
+        /// Only set on the final chunk of a stream, which repeats the whole reply.
+        reply: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct MinimaxChoice {
+        messages: Option<Vec<MinimaxMessage>>,
+    }
+    #[derive(Deserialize)]
+    struct MinimaxMessage {
+        text: Option<String>,
+    }
+    impl MinimaxResponse {
+        fn into_text(self) -> Option<String> {
+            self.choices
+                .and_then(|c| c.into_iter().next())
+                .and_then(|c| c.messages)
+                .and_then(|m| m.into_iter().next())
+                .and_then(|m| m.text)
+        }
+    }
+    #[async_trait]
+    impl LLMProvider for MinimaxProvider {
+        fn name(&self) -> &str {
+            &self.model
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            self.cost_per_1k
+        }
+        async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+            let response = self.send(prompt, false).await?;
 
             let resp: MinimaxResponse = response
                 .json()
@@ -214,27 +514,29 @@ pub mod prelude {
                 .map_err(|e| anyhow::anyhow!("Failed to parse MiniMax response: {}", e))?;
 
             let text = resp
-                .choices
-                .and_then(|c| c.into_iter().next())
-                .and_then(|c| c.messages)
-                .and_then(|m| m.into_iter().next())
-                .and_then(|m| m.text)
+                .into_text()
                 .ok_or_else(|| anyhow::anyhow!("No text in MiniMax response"))?;
 
             Ok(text)
         }
-    }
-
-    #[derive(Debug, Clone)]
-    pub struct InMemoryCooldownStore;
-    impl Default for InMemoryCooldownStore {
-        fn default() -> Self {
-            Self::new()
-        }
-    }
-    impl InMemoryCooldownStore {
-        pub fn new() -> Self {
-            Self
+        async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+            let response = self.send(prompt, true).await?;
+
+            Ok(crate::stream::sse_deltas(
+                response.bytes_stream(),
+                |data: &str| {
+                    if data.trim() == "[DONE]" {
+                        return Ok(None);
+                    }
+                    let chunk: MinimaxResponse = serde_json::from_str(data).map_err(|e| {
+                        anyhow::anyhow!("Failed to parse MiniMax stream chunk: {}", e)
+                    })?;
+                    if chunk.reply.is_some() {
+                        return Ok(None);
+                    }
+                    Ok(chunk.into_text().filter(|t| !t.is_empty()))
+                },
+            ))
         }
     }
 
@@ -250,27 +552,144 @@ pub mod prelude {
                 model: model.to_string(),
             }
         }
+        /// Key under which cooldowns and stats are tracked.
+        pub fn key(&self) -> String {
+            format!("{}/{}", self.provider, self.model)
+        }
     }
 
+    /// Routes each call across several providers.
+    ///
+    /// Providers cooling down in the [`CooldownStore`] are skipped; the rest
+    /// are tried in a random order weighted by [`ProviderStats::weight`], so
+    /// fast, reliable and cheap providers take most of the traffic.
     #[derive(Debug, Clone)]
     pub struct StochasticRotator {
-        providers: Vec<Arc<dyn LLMProvider>>,
-        counter: Arc<std::sync::atomic::AtomicUsize>,
+        providers: Vec<(ProviderId, Arc<dyn LLMProvider>)>,
+        store: Arc<dyn CooldownStore>,
+        policy: CooldownPolicy,
+        stats: Arc<std::sync::Mutex<std::collections::HashMap<String, ProviderStats>>>,
     }
     impl StochasticRotator {
-        pub fn new(_store: Arc<InMemoryCooldownStore>) -> Self {
-            // Use time as a simple seed for the initial counter
-            let initial = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs() as usize)
-                .unwrap_or(0);
+        pub fn new(store: Arc<dyn CooldownStore>) -> Self {
             Self {
                 providers: Vec::new(),
-                counter: Arc::new(std::sync::atomic::AtomicUsize::new(initial)),
+                store,
+                policy: CooldownPolicy::default(),
+                stats: Default::default(),
             }
         }
-        pub fn add_provider(&mut self, _id: ProviderId, provider: Arc<dyn LLMProvider>) {
-            self.providers.push(provider);
+        pub fn with_policy(mut self, policy: CooldownPolicy) -> Self {
+            self.policy = policy;
+            self
+        }
+        pub fn add_provider(&mut self, id: ProviderId, provider: Arc<dyn LLMProvider>) {
+            self.providers.push((id, provider));
+        }
+        /// Health observed by this process, keyed by [`ProviderId::key`].
+        pub fn stats(&self) -> std::collections::HashMap<String, ProviderStats> {
+            self.stats.lock().unwrap().clone()
+        }
+
+        /// Providers that are not cooling down, in the order this call should
+        /// try them. Fails with a rate-limit error carrying the shortest
+        /// remaining cooldown when every provider is out.
+        async fn rotation(&self) -> anyhow::Result<Vec<(String, Arc<dyn LLMProvider>)>> {
+            if self.providers.is_empty() {
+                return Err(anyhow::anyhow!("No providers available"));
+            }
+
+            let mut available = Vec::new();
+            let mut soonest: Option<CooldownEntry> = None;
+            for (id, provider) in &self.providers {
+                let key = id.key();
+                match self.store.get(&key).await {
+                    Ok(Some(entry)) if entry.remaining().is_some() => {
+                        if soonest.as_ref().is_none_or(|s| entry.until_ms < s.until_ms) {
+                            soonest = Some(entry);
+                        }
+                    }
+                    Ok(_) => available.push((key, provider.clone())),
+                    Err(e) => {
+                        tracing::warn!("Cooldown lookup for {} failed: {}", key, e);
+                        available.push((key, provider.clone()));
+                    }
+                }
+            }
+
+            if available.is_empty() {
+                let entry = soonest.expect("every provider is cooling down");
+                let wait = entry.remaining().unwrap_or_default();
+                return Err(ProviderError {
+                    class: ErrorClass::RateLimited,
+                    status: None,
+                    retry_after: Some(wait),
+                    message: format!(
+                        "All providers are cooling down; {} is available again in {:.1}s",
+                        entry.provider,
+                        wait.as_secs_f64()
+                    ),
+                }
+                .into());
+            }
+
+            let weights: Vec<f64> = {
+                let stats = self.stats.lock().unwrap();
+                available
+                    .iter()
+                    .map(|(key, provider)| {
+                        stats
+                            .get(key)
+                            .cloned()
+                            .unwrap_or_default()
+                            .weight(provider.cost_per_1k_tokens())
+                    })
+                    .collect()
+            };
+            let mut slots: Vec<_> = available.into_iter().map(Some).collect();
+            Ok(crate::routing::weighted_order(&weights)
+                .into_iter()
+                .filter_map(|i| slots[i].take())
+                .collect())
+        }
+
+        async fn record_success(&self, key: &str, latency: std::time::Duration) {
+            let recovered = {
+                let mut stats = self.stats.lock().unwrap();
+                let entry = stats.entry(key.to_string()).or_default();
+                let recovered = entry.consecutive_failures > 0;
+                entry.record_success(latency);
+                recovered
+            };
+            if recovered {
+                if let Err(e) = self.store.clear(key).await {
+                    tracing::warn!("Clearing cooldown for {} failed: {}", key, e);
+                }
+            }
+        }
+
+        async fn record_failure(&self, key: &str, err: &anyhow::Error) {
+            let failures = {
+                let mut stats = self.stats.lock().unwrap();
+                let entry = stats.entry(key.to_string()).or_default();
+                entry.record_failure();
+                entry.consecutive_failures
+            };
+            let (class, retry_after) = ProviderError::classify(err);
+            let Some(cooldown) = self.policy.cooldown(class, failures, retry_after) else {
+                return;
+            };
+            tracing::warn!(
+                "Provider {} cooling down for {:.1}s after {:?}: {}",
+                key,
+                cooldown.as_secs_f64(),
+                class,
+                err
+            );
+            let entry = CooldownEntry::new(key, class, failures, cooldown);
+            if let Err(e) = self.store.put(&entry).await {
+                tracing::warn!("Storing cooldown for {} failed: {}", key, e);
+            }
         }
     }
     #[async_trait]
@@ -282,25 +701,75 @@ pub mod prelude {
             0.0
         }
         async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
-            if self.providers.is_empty() {
-                return Err(anyhow::anyhow!("No providers available"));
+            Ok(self.generate_with_usage(prompt).await?.0)
+        }
+        async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+            let mut last_err = None;
+            for (key, provider) in self.rotation().await? {
+                let started = std::time::Instant::now();
+                match provider.generate_with_usage(prompt).await {
+                    Ok((reply, mut usage)) => {
+                        self.record_success(&key, started.elapsed()).await;
+                        usage.provider = key;
+                        return Ok((reply, usage));
+                    }
+                    Err(e) => {
+                        self.record_failure(&key, &e).await;
+                        last_err = Some(e);
+                    }
+                }
             }
+            Err(last_err.unwrap_or_else(|| anyhow::anyhow!("All providers failed")))
+        }
+        async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+            use futures::StreamExt;
 
-            let start_idx = self
-                .counter
-                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
-                % self.providers.len();
-
+            // Fall over to the next provider until one produces its first delta;
+            // after that the stream is committed to that provider.
             let mut last_err = None;
-            for i in 0..self.providers.len() {
-                let idx = (start_idx + i) % self.providers.len();
-                let provider = &self.providers[idx];
-                match provider.generate(prompt).await {
-                    Ok(res) => return Ok(res),
+            for (key, provider) in self.rotation().await? {
+                let started = std::time::Instant::now();
+                let mut stream = match provider.generate_stream(prompt).await {
+                    Ok(stream) => stream,
                     Err(e) => {
+                        self.record_failure(&key, &e).await;
                         last_err = Some(e);
                         continue;
                     }
+                };
+                match stream.next().await {
+                    Some(Ok(first)) => {
+                        self.record_success(&key, started.elapsed()).await;
+                        return Ok(crate::stream::single_delta(first).chain(stream).boxed());
+                    }
+                    Some(Err(e)) => {
+                        self.record_failure(&key, &e).await;
+                        last_err = Some(e);
+                    }
+                    None => {
+                        self.record_success(&key, started.elapsed()).await;
+                        return Ok(futures::stream::empty().boxed());
+                    }
+                }
+            }
+            Err(last_err.unwrap_or_else(|| anyhow::anyhow!("All providers failed")))
+        }
+        async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+            let mut last_err = None;
+            for (key, provider) in self.rotation().await? {
+                let started = std::time::Instant::now();
+                match provider.chat(request).await {
+                    Ok(mut res) => {
+                        self.record_success(&key, started.elapsed()).await;
+                        if let Some(usage) = &mut res.usage {
+                            usage.provider = key;
+                        }
+                        return Ok(res);
+                    }
+                    Err(e) => {
+                        self.record_failure(&key, &e).await;
+                        last_err = Some(e);
+                    }
                 }
             }
             Err(last_err.unwrap_or_else(|| anyhow::anyhow!("All providers failed")))
@@ -314,11 +783,18 @@ pub mod prelude {
         pub parameters: Option<Value>,
         pub confidence: f32,
         pub providers_used: Vec<String>,
+        /// Tokens spent reaching the decision, including repair prompts.
+        #[serde(default)]
+        pub usage: Option<Usage>,
     }
 
+    /// Number of repair prompts sent when a reply cannot be parsed.
+    const DEFAULT_REPAIR_ATTEMPTS: usize = 1;
+
     #[derive(Debug, Clone)]
     pub struct DecisionEngine {
         providers: Vec<Arc<dyn LLMProvider>>,
+        repair_attempts: usize,
     }
     impl Default for DecisionEngine {
         fn default() -> Self {
@@ -329,34 +805,78 @@ pub mod prelude {
         pub fn new() -> Self {
             Self {
                 providers: Vec::new(),
+                repair_attempts: DEFAULT_REPAIR_ATTEMPTS,
             }
         }
         pub fn builder() -> DecisionEngineBuilder {
             DecisionEngineBuilder {
                 providers: Vec::new(),
+                repair_attempts: DEFAULT_REPAIR_ATTEMPTS,
             }
         }
         pub fn providers(&self) -> &[Arc<dyn LLMProvider>] {
             &self.providers
         }
+        /// Stream a free-form reply to `prompt` from the primary provider.
+        pub async fn stream_reply(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+            match self.providers.first() {
+                Some(provider) => provider.generate_stream(prompt).await,
+                None => Ok(crate::stream::single_delta("mock".to_string())),
+            }
+        }
+        /// Answer a chat request with the primary provider, using its native
+        /// function calling when it has one.
+        pub async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+            match self.providers.first() {
+                Some(provider) => provider.chat(request).await,
+                None => Ok(ChatResponse::text("mock")),
+            }
+        }
         pub async fn decide(&self, ctx: &DecisionContext) -> anyhow::Result<Decision> {
             // Native resilience: try the first provider (StochasticRotator if configured)
             // or fallback if needed.
             if let Some(provider) = self.providers.first() {
-                let prompt = format!(
-                    "QUERY: {}\nSUMMARY: {}\n\nBased on the above, decide the next action.",
-                    ctx.query,
-                    ctx.summary.as_deref().unwrap_or("None")
-                );
-                let _resp = provider.generate(&prompt).await?;
+                let prompt = crate::decision::decision_prompt(ctx);
+                let (mut reply, mut usage) = provider.generate_with_usage(&prompt).await?;
 
-                Ok(Decision {
-                    reasoning: "Resilient decision via synapse-agentic".to_string(),
-                    action: "chat".to_string(),
-                    parameters: None,
-                    confidence: 1.0,
-                    providers_used: vec![provider.name().to_string()],
-                })
+                let mut attempt = 0;
+                loop {
+                    match crate::decision::parse_decision(&reply) {
+                        Ok(mut decision) => {
+                            decision.providers_used = vec![provider.name().to_string()];
+                            decision.usage = Some(usage);
+                            return Ok(decision);
+                        }
+                        Err(e) if attempt < self.repair_attempts => {
+                            attempt += 1;
+                            tracing::warn!(
+                                "Unparseable decision from {} ({}); sending repair prompt {}/{}",
+                                provider.name(),
+                                e,
+                                attempt,
+                                self.repair_attempts
+                            );
+                            let repair = crate::decision::repair_prompt(&prompt, &reply, &e);
+                            let (repaired, repair_usage) =
+                                provider.generate_with_usage(&repair).await?;
+                            reply = repaired;
+                            usage.add(&repair_usage);
+                        }
+                        Err(e) => {
+                            // The model answered in prose; treat it as a chat reply
+                            // with zero confidence rather than failing the caller.
+                            tracing::warn!("Falling back to chat decision: {}", e);
+                            return Ok(Decision {
+                                reasoning: reply,
+                                action: "chat".to_string(),
+                                parameters: None,
+                                confidence: 0.0,
+                                providers_used: vec![provider.name().to_string()],
+                                usage: Some(usage),
+                            });
+                        }
+                    }
+                }
             } else {
                 Ok(Decision {
                     reasoning: "mock".to_string(),
@@ -364,6 +884,7 @@ pub mod prelude {
                     parameters: None,
                     confidence: 1.0,
                     providers_used: vec!["mock".to_string()],
+                    usage: None,
                 })
             }
         }
@@ -372,15 +893,22 @@ pub mod prelude {
     #[derive(Debug, Clone)]
     pub struct DecisionEngineBuilder {
         providers: Vec<Arc<dyn LLMProvider>>,
+        repair_attempts: usize,
     }
     impl DecisionEngineBuilder {
         pub fn with_provider<P: Provider + 'static>(mut self, p: P) -> Self {
             self.providers.push(Arc::new(p));
             self
         }
+        /// Set how many repair prompts are sent for unparseable replies.
+        pub fn with_repair_attempts(mut self, attempts: usize) -> Self {
+            self.repair_attempts = attempts;
+            self
+        }
         pub fn build(self) -> DecisionEngine {
             DecisionEngine {
                 providers: self.providers,
+                repair_attempts: self.repair_attempts,
             }
         }
     }
@@ -416,6 +944,25 @@ pub mod prelude {
             let mut tools = self.tools.write().await;
             tools.insert(tool.name().to_string(), Arc::new(tool));
         }
+        /// Looks up a registered tool by name.
+        pub async fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+            self.tools.read().await.get(name).cloned()
+        }
+        /// Returns every registered tool, sorted by name.
+        pub async fn tools(&self) -> Vec<Arc<dyn Tool>> {
+            let tools = self.tools.read().await;
+            let mut list: Vec<Arc<dyn Tool>> = tools.values().cloned().collect();
+            list.sort_by(|a, b| a.name().cmp(b.name()));
+            list
+        }
+        /// Schemas of every registered tool, sorted by name, for chat requests.
+        pub async fn schemas(&self) -> Vec<ToolSchema> {
+            self.tools()
+                .await
+                .iter()
+                .map(|tool| ToolSchema::new(tool.name(), tool.description(), tool.parameters()))
+                .collect()
+        }
         pub async fn call(
             &self,
             name: &str,
@@ -481,6 +1028,8 @@ pub mod prelude {
         System,
         User,
         Assistant,
+        /// Result of a tool call requested by the assistant.
+        Tool,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -488,6 +1037,15 @@ pub mod prelude {
         pub role: MessageRole,
         pub content: String,
         pub token_count: Option<u32>,
+        /// Tool calls requested by an assistant message.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub tool_calls: Vec<ToolCall>,
+        /// For tool results, the ID of the call being answered.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tool_call_id: Option<String>,
+        /// For tool results, the name of the tool that produced it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub name: Option<String>,
     }
     impl Message {
         pub fn new(role: MessageRole, content: String) -> Self {
@@ -495,6 +1053,24 @@ pub mod prelude {
                 role,
                 content,
                 token_count: None,
+                tool_calls: Vec::new(),
+                tool_call_id: None,
+                name: None,
+            }
+        }
+        /// An assistant turn that requests `tool_calls`.
+        pub fn assistant_tool_calls(content: String, tool_calls: Vec<ToolCall>) -> Self {
+            Self {
+                tool_calls,
+                ..Self::new(MessageRole::Assistant, content)
+            }
+        }
+        /// The result of `call`, to be sent back to the model.
+        pub fn tool_result(call: &ToolCall, content: String) -> Self {
+            Self {
+                tool_call_id: Some(call.id.clone()),
+                name: Some(call.name.clone()),
+                ..Self::new(MessageRole::Tool, content)
             }
         }
     }
@@ -513,13 +1089,16 @@ pub mod prelude {
         }
     }
 
-    pub trait TokenCounter: Send + Sync {
+    /// Counts tokens the way a model's tokenizer does. See
+    /// [`crate::tokenizer`] for counters backed by real tokenizer files.
+    pub trait TokenCounter: Send + Sync + std::fmt::Debug {
         fn count_tokens(&self, text: &str) -> anyhow::Result<u32>;
         fn count_message(&self, message: &Message) -> anyhow::Result<u32> {
             self.count_tokens(&message.content)
         }
     }
 
+    /// Whitespace-based guess, used when no tokenizer file is available.
     #[derive(Debug, Clone)]
     pub struct SimpleTokenEstimator;
     impl SimpleTokenEstimator {
@@ -547,6 +1126,19 @@ pub mod prelude {
                 keep_recent: 10,
             }
         }
+        /// Warn at 60% and compact at 80% of a `window`-token context,
+        /// leaving room for the system prompt, tools and the reply.
+        pub fn for_context_window(window: u32) -> Self {
+            Self {
+                warning_tokens: window / 10 * 6,
+                critical_tokens: window / 10 * 8,
+                keep_recent: 10,
+            }
+        }
+        /// Preset for the context window of `model`; see [`crate::tokenizer::context_window`].
+        pub fn for_model(model: &str) -> Self {
+            Self::for_context_window(crate::tokenizer::context_window(model))
+        }
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -0,0 +1,305 @@
+//! Provider for Ollama's native `/api/chat` endpoint.
+
+use crate::chat::{ChatRequest, ChatResponse, ToolCall};
+use crate::prelude::{
+    async_trait, Deserialize, LLMProvider, MessageRole, TokenStream, Usage, Value,
+};
+
+pub const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    /// Provider for the Ollama server at `base_url` (e.g. `http://localhost:11434`).
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            api_key: None,
+            client: reqwest::Client::new(),
+        }
+    }
+    /// Server from `OLLAMA_HOST`, falling back to the local default.
+    pub fn from_env(model: impl Into<String>) -> Self {
+        let host = std::env::var("OLLAMA_HOST").unwrap_or_else(|_| OLLAMA_BASE_URL.to_string());
+        let host = if host.contains("://") {
+            host
+        } else {
+            format!("http://{}", host)
+        };
+        Self::new(host, model)
+    }
+    /// Bearer token for Ollama servers behind an authenticating proxy.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into()).filter(|k| !k.is_empty());
+        self
+    }
+
+    async fn send(&self, body: &Value) -> anyhow::Result<reqwest::Response> {
+        let mut request = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| crate::routing::request_error("Ollama", e))?;
+        crate::routing::check_status("Ollama", response).await
+    }
+
+    fn prompt_body(&self, prompt: &str, stream: bool) -> Value {
+        serde_json::json!({
+            "model": self.model,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": stream,
+        })
+    }
+
+    /// The reply and the tokens Ollama counted for it. Local models cost nothing.
+    async fn complete(&self, body: &Value) -> anyhow::Result<(OllamaMessage, Usage)> {
+        let response: OllamaResponse = self
+            .send(body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse Ollama response: {}", e))?;
+        let usage = Usage::new(
+            &self.model,
+            response.prompt_eval_count,
+            response.eval_count,
+            0.0,
+        );
+        let message = response
+            .message
+            .ok_or_else(|| anyhow::anyhow!("No message in Ollama response"))?;
+        Ok((message, usage))
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+#[derive(Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+#[derive(Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+#[derive(Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// Map a chat request onto Ollama's `messages` and `tools`.
+fn chat_body(model: &str, request: &ChatRequest) -> Value {
+    let messages: Vec<Value> = request
+        .messages
+        .iter()
+        .map(|message| match message.role {
+            MessageRole::System => {
+                serde_json::json!({"role": "system", "content": message.content})
+            }
+            MessageRole::User => serde_json::json!({"role": "user", "content": message.content}),
+            MessageRole::Assistant => {
+                let mut value =
+                    serde_json::json!({"role": "assistant", "content": message.content});
+                if !message.tool_calls.is_empty() {
+                    value["tool_calls"] = message
+                        .tool_calls
+                        .iter()
+                        .map(|call| {
+                            serde_json::json!({
+                                "function": {"name": call.name, "arguments": call.arguments}
+                            })
+                        })
+                        .collect();
+                }
+                value
+            }
+            // Ollama pairs results with calls by position, so no ID is sent.
+            MessageRole::Tool => serde_json::json!({
+                "role": "tool",
+                "tool_name": message.name,
+                "content": message.content,
+            }),
+        })
+        .collect();
+
+    let mut body = serde_json::json!({"model": model, "messages": messages, "stream": false});
+    if !request.tools.is_empty() {
+        body["tools"] = request
+            .tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    }
+                })
+            })
+            .collect();
+    }
+    body
+}
+
+#[async_trait]
+impl LLMProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        &self.model
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        0.0
+    }
+    async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+        Ok(self.generate_with_usage(prompt).await?.0)
+    }
+    async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+        let (message, usage) = self.complete(&self.prompt_body(prompt, false)).await?;
+        Ok((message.content, usage))
+    }
+    async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+        let response = self.send(&self.prompt_body(prompt, true)).await?;
+
+        Ok(crate::stream::ndjson_deltas(
+            response.bytes_stream(),
+            |line: &str| {
+                let chunk: Value = serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse Ollama stream chunk: {}", e))?;
+                if let Some(error) = chunk.get("error").and_then(Value::as_str) {
+                    anyhow::bail!("Ollama stream error: {}", error);
+                }
+                Ok(chunk["message"]["content"]
+                    .as_str()
+                    .filter(|text| !text.is_empty())
+                    .map(str::to_string))
+            },
+        ))
+    }
+    async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+        let (message, usage) = self.complete(&chat_body(&self.model, request)).await?;
+        Ok(ChatResponse {
+            content: message.content,
+            tool_calls: message
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, call)| ToolCall {
+                    id: format!("call_{}", i + 1),
+                    name: call.function.name,
+                    arguments: match call.function.arguments {
+                        Value::Null => serde_json::json!({}),
+                        args => args,
+                    },
+                })
+                .collect(),
+            usage: Some(usage),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ToolSchema;
+    use crate::mock_http::MockServer;
+    use crate::prelude::Message;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_chat_maps_tool_calls() {
+        let server = MockServer::start(vec![(
+            200,
+            "application/json",
+            json!({
+                "model": "llama3.1",
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{"function": {"name": "read_file", "arguments": {"path": "a.rs"}}}]
+                },
+                "done": true
+            })
+            .to_string(),
+        )])
+        .await;
+        let provider = OllamaProvider::new(&server.base_url, "llama3.1");
+
+        let request = ChatRequest::new(vec![Message::new(
+            MessageRole::User,
+            "open a.rs".to_string(),
+        )])
+        .with_tools(vec![ToolSchema::new(
+            "read_file",
+            "Read a file.",
+            json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+        )]);
+        let reply = provider.chat(&request).await.unwrap();
+        assert_eq!(reply.tool_calls[0].name, "read_file");
+        assert_eq!(reply.tool_calls[0].arguments, json!({"path": "a.rs"}));
+
+        let recorded = &server.requests()[0];
+        assert_eq!(recorded.path, "/api/chat");
+        let body = recorded.json();
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+    }
+
+    #[tokio::test]
+    async fn test_generate_and_stream() {
+        let lines = [
+            json!({"message": {"role": "assistant", "content": "Hel"}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "lo"}, "done": false}),
+            json!({"message": {"role": "assistant", "content": ""}, "done": true}),
+        ]
+        .iter()
+        .map(|l| format!("{}\n", l))
+        .collect::<String>();
+        let server = MockServer::start(vec![
+            (
+                200,
+                "application/json",
+                json!({"message": {"role": "assistant", "content": "Hello"}, "done": true})
+                    .to_string(),
+            ),
+            (200, "application/x-ndjson", lines),
+        ])
+        .await;
+        let provider = OllamaProvider::new(&server.base_url, "llama3.1");
+
+        assert_eq!(provider.generate("hi").await.unwrap(), "Hello");
+        let deltas: Vec<String> = provider
+            .generate_stream("hi")
+            .await
+            .unwrap()
+            .map(|d| d.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+    }
+}
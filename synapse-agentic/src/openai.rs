@@ -0,0 +1,448 @@
+//! Provider for OpenAI-compatible chat-completions APIs.
+//!
+//! One implementation covers OpenAI itself, Groq, OpenRouter, vLLM and
+//! llama.cpp servers: they differ only in base URL, key and extra headers.
+
+use crate::chat::{ChatRequest, ChatResponse, ToolCall};
+use crate::prelude::{
+    async_trait, Deserialize, LLMProvider, MessageRole, TokenStream, Usage, Value,
+};
+use std::collections::HashSet;
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+pub const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+pub const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleProvider {
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    headers: Vec<(String, String)>,
+    cost_per_1k: f64,
+    client: reqwest::Client,
+}
+
+impl OpenAiCompatibleProvider {
+    /// Provider for the server at `base_url` (the part before `/chat/completions`).
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            model: model.into(),
+            headers: Vec::new(),
+            cost_per_1k: 0.0,
+            client: reqwest::Client::new(),
+        }
+    }
+    pub fn openai(api_key: String, model: String) -> Self {
+        Self::new(OPENAI_BASE_URL, model).with_api_key(api_key)
+    }
+    pub fn groq(api_key: String, model: String) -> Self {
+        Self::new(GROQ_BASE_URL, model).with_api_key(api_key)
+    }
+    pub fn openrouter(api_key: String, model: String) -> Self {
+        Self::new(OPENROUTER_BASE_URL, model).with_api_key(api_key)
+    }
+    /// Send `key` as a bearer token. Local servers usually need none.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into()).filter(|k| !k.is_empty());
+        self
+    }
+    /// Add a header to every request (e.g. OpenRouter's `HTTP-Referer`).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+    /// Price used to report spend in [`Usage`].
+    pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+        self.cost_per_1k = cost;
+        self
+    }
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn send(&self, body: &Value) -> anyhow::Result<reqwest::Response> {
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| crate::routing::request_error(&self.base_url, e))?;
+        crate::routing::check_status(&self.base_url, response).await
+    }
+
+    fn prompt_body(&self, prompt: &str, stream: bool) -> Value {
+        serde_json::json!({
+            "model": self.model,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": stream,
+        })
+    }
+
+    /// The first choice and the tokens spent on it; counts are estimated
+    /// when the server reports none.
+    async fn complete(&self, body: &Value) -> anyhow::Result<(CompletionMessage, Usage)> {
+        let response: CompletionResponse = self
+            .send(body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse chat completion: {}", e))?;
+        let message = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message)
+            .ok_or_else(|| anyhow::anyhow!("No choices in chat completion"))?;
+        let usage = match response.usage {
+            Some(u) => Usage::new(
+                &self.model,
+                u.prompt_tokens,
+                u.completion_tokens,
+                self.cost_per_1k,
+            ),
+            None => Usage::estimate(
+                &self.model,
+                &body["messages"].to_string(),
+                message.content.as_deref().unwrap_or_default(),
+                self.cost_per_1k,
+            ),
+        };
+        Ok((message, usage))
+    }
+}
+
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Vec<CompletionChoice>,
+    usage: Option<CompletionUsage>,
+}
+#[derive(Deserialize)]
+struct CompletionUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+}
+#[derive(Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<CompletionToolCall>,
+}
+#[derive(Deserialize)]
+struct CompletionToolCall {
+    id: Option<String>,
+    function: CompletionFunction,
+}
+#[derive(Deserialize)]
+struct CompletionFunction {
+    name: String,
+    /// JSON-encoded arguments.
+    #[serde(default)]
+    arguments: String,
+}
+#[derive(Deserialize)]
+struct CompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+}
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: ChunkDelta,
+}
+#[derive(Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+/// Map a chat request onto the chat-completions `messages` and `tools`.
+fn chat_body(model: &str, request: &ChatRequest) -> Value {
+    let mut calls = HashSet::new();
+    let messages: Vec<Value> = request
+        .messages
+        .iter()
+        .map(|message| match message.role {
+            MessageRole::System => {
+                serde_json::json!({"role": "system", "content": message.content})
+            }
+            MessageRole::User => serde_json::json!({"role": "user", "content": message.content}),
+            MessageRole::Assistant if message.tool_calls.is_empty() => {
+                serde_json::json!({"role": "assistant", "content": message.content})
+            }
+            MessageRole::Assistant => {
+                let tool_calls: Vec<Value> = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        calls.insert(call.id.as_str());
+                        serde_json::json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": {
+                                "name": call.name,
+                                "arguments": call.arguments.to_string(),
+                            }
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "role": "assistant",
+                    "content": (!message.content.is_empty()).then_some(&message.content),
+                    "tool_calls": tool_calls,
+                })
+            }
+            MessageRole::Tool => match message.tool_call_id.as_deref() {
+                Some(id) if calls.contains(id) => serde_json::json!({
+                    "role": "tool",
+                    "tool_call_id": id,
+                    "content": message.content,
+                }),
+                // The call was compacted away; keep the result as plain text.
+                _ => serde_json::json!({
+                    "role": "user",
+                    "content": format!(
+                        "Result of {}: {}",
+                        message.name.as_deref().unwrap_or("tool"),
+                        message.content
+                    ),
+                }),
+            },
+        })
+        .collect();
+
+    let mut body = serde_json::json!({"model": model, "messages": messages});
+    if !request.tools.is_empty() {
+        body["tools"] = request
+            .tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    }
+                })
+            })
+            .collect();
+    }
+    body
+}
+
+#[async_trait]
+impl LLMProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.model
+    }
+    fn cost_per_1k_tokens(&self) -> f64 {
+        self.cost_per_1k
+    }
+    async fn generate(&self, prompt: &str) -> anyhow::Result<String> {
+        Ok(self.generate_with_usage(prompt).await?.0)
+    }
+    async fn generate_with_usage(&self, prompt: &str) -> anyhow::Result<(String, Usage)> {
+        let (message, usage) = self.complete(&self.prompt_body(prompt, false)).await?;
+        let text = message
+            .content
+            .ok_or_else(|| anyhow::anyhow!("No text in chat completion"))?;
+        Ok((text, usage))
+    }
+    async fn generate_stream(&self, prompt: &str) -> anyhow::Result<TokenStream> {
+        let response = self.send(&self.prompt_body(prompt, true)).await?;
+
+        Ok(crate::stream::sse_deltas(
+            response.bytes_stream(),
+            |data: &str| {
+                if data.trim() == "[DONE]" {
+                    return Ok(None);
+                }
+                let chunk: CompletionChunk = serde_json::from_str(data)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse completion chunk: {}", e))?;
+                let text: String = chunk
+                    .choices
+                    .into_iter()
+                    .filter_map(|c| c.delta.content)
+                    .collect();
+                Ok((!text.is_empty()).then_some(text))
+            },
+        ))
+    }
+    async fn chat(&self, request: &ChatRequest) -> anyhow::Result<ChatResponse> {
+        let (message, usage) = self.complete(&chat_body(&self.model, request)).await?;
+        Ok(ChatResponse {
+            content: message.content.unwrap_or_default(),
+            tool_calls: message
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, call)| ToolCall {
+                    id: call.id.unwrap_or_else(|| format!("call_{}", i + 1)),
+                    name: call.function.name,
+                    arguments: serde_json::from_str(&call.function.arguments)
+                        .unwrap_or_else(|_| serde_json::json!({})),
+                })
+                .collect(),
+            usage: Some(usage),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ToolSchema;
+    use crate::mock_http::MockServer;
+    use crate::prelude::Message;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_generate_sends_key_and_headers() {
+        let server = MockServer::start(vec![(
+            200,
+            "application/json",
+            json!({"choices": [{"message": {"role": "assistant", "content": "hi there"}}]})
+                .to_string(),
+        )])
+        .await;
+        let provider = OpenAiCompatibleProvider::new(format!("{}/v1/", server.base_url), "m1")
+            .with_api_key("sk-test")
+            .with_header("X-Title", "gestalt");
+
+        assert_eq!(provider.generate("hello").await.unwrap(), "hi there");
+
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/v1/chat/completions");
+        assert_eq!(request.header("authorization"), Some("Bearer sk-test"));
+        assert_eq!(request.header("x-title"), Some("gestalt"));
+        let body = request.json();
+        assert_eq!(body["model"], "m1");
+        assert_eq!(body["messages"][0]["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_usage_reported_and_priced() {
+        let server = MockServer::start(vec![(
+            200,
+            "application/json",
+            json!({
+                "choices": [{"message": {"role": "assistant", "content": "ok"}}],
+                "usage": {"prompt_tokens": 1200, "completion_tokens": 300, "total_tokens": 1500}
+            })
+            .to_string(),
+        )])
+        .await;
+        let mut rotator = crate::prelude::StochasticRotator::new(std::sync::Arc::new(
+            crate::prelude::InMemoryCooldownStore::new(),
+        ));
+        rotator.add_provider(
+            crate::prelude::ProviderId::new("openai", "m1"),
+            std::sync::Arc::new(
+                OpenAiCompatibleProvider::new(&server.base_url, "m1").with_cost_per_1k_tokens(0.5),
+            ),
+        );
+
+        let (reply, usage) = rotator.generate_with_usage("hello").await.unwrap();
+        assert_eq!(reply, "ok");
+        assert_eq!(usage.provider, "openai/m1");
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (1200, 300));
+        assert!((usage.cost_usd - 0.75).abs() < 1e-9);
+        assert!(!usage.estimated);
+    }
+
+    #[tokio::test]
+    async fn test_chat_maps_native_tool_calls() {
+        let server = MockServer::start(vec![(
+            200,
+            "application/json",
+            json!({"choices": [{"message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_abc",
+                    "type": "function",
+                    "function": {"name": "read_file", "arguments": "{\"path\":\"a.rs\"}"}
+                }]
+            }}]})
+            .to_string(),
+        )])
+        .await;
+        let provider = OpenAiCompatibleProvider::new(&server.base_url, "m1");
+
+        let earlier = ToolCall {
+            id: "call_1".to_string(),
+            name: "git_status".to_string(),
+            arguments: json!({}),
+        };
+        let request = ChatRequest::new(vec![
+            Message::new(MessageRole::System, "sys".to_string()),
+            Message::assistant_tool_calls(String::new(), vec![earlier.clone()]),
+            Message::tool_result(&earlier, "clean".to_string()),
+        ])
+        .with_tools(vec![ToolSchema::new(
+            "read_file",
+            "Read a file.",
+            json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+        )]);
+
+        let reply = provider.chat(&request).await.unwrap();
+        assert_eq!(reply.content, "");
+        assert_eq!(reply.tool_calls[0].id, "call_abc");
+        assert_eq!(reply.tool_calls[0].arguments, json!({"path": "a.rs"}));
+
+        let body = server.requests()[0].json();
+        assert!(server.requests()[0].header("authorization").is_none());
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+        assert_eq!(
+            body["messages"][1]["tool_calls"][0]["function"]["arguments"],
+            "{}"
+        );
+        assert_eq!(body["messages"][2]["role"], "tool");
+        assert_eq!(body["messages"][2]["tool_call_id"], "call_1");
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_errors() {
+        let sse = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+                   data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+                   data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n\
+                   data: [DONE]\n\n";
+        let server = MockServer::start(vec![
+            (200, "text/event-stream", sse.to_string()),
+            (429, "application/json", "{}".to_string()),
+        ])
+        .await;
+        let provider = OpenAiCompatibleProvider::new(&server.base_url, "m1");
+
+        let deltas: Vec<String> = provider
+            .generate_stream("hi")
+            .await
+            .unwrap()
+            .map(|d| d.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+        assert_eq!(server.requests()[0].json()["stream"], true);
+
+        let err = provider.generate("hi").await.unwrap_err();
+        assert!(err.to_string().contains("429"));
+    }
+}
@@ -0,0 +1,505 @@
+//! Error classification, cooldowns and provider health for [`StochasticRotator`].
+//!
+//! Providers report HTTP failures as [`ProviderError`]s. The rotator maps
+//! their [`ErrorClass`] through a [`CooldownPolicy`] and records the result
+//! in a [`CooldownStore`], which may be shared between processes.
+//!
+//! [`StochasticRotator`]: crate::prelude::StochasticRotator
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Why a provider call failed, as far as routing is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// 429 without a quota message.
+    RateLimited,
+    /// Billing or quota exhausted; retrying soon will not help.
+    QuotaExceeded,
+    /// 5xx or a failed connection.
+    ServerError,
+    /// The request or gateway timed out.
+    Timeout,
+    /// Rejected credentials.
+    Auth,
+    /// Anything else, such as a bad request or an unparseable reply.
+    Other,
+}
+
+impl ErrorClass {
+    /// Classify an HTTP error status and its response body.
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let body = body.to_lowercase();
+        let quota = [
+            "quota",
+            "resource_exhausted",
+            "billing",
+            "insufficient balance",
+        ]
+        .iter()
+        .any(|marker| body.contains(marker));
+        match status {
+            402 => Self::QuotaExceeded,
+            429 if quota => Self::QuotaExceeded,
+            429 => Self::RateLimited,
+            401 | 403 => Self::Auth,
+            408 | 504 => Self::Timeout,
+            500..=599 => Self::ServerError,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A failed provider call, carrying what the rotator needs to cool it down.
+#[derive(Debug, Clone)]
+pub struct ProviderError {
+    pub class: ErrorClass,
+    pub status: Option<u16>,
+    /// Parsed from the `Retry-After` header when the provider sent one.
+    pub retry_after: Option<Duration>,
+    pub message: String,
+}
+
+impl ProviderError {
+    pub fn new(class: ErrorClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            status: None,
+            retry_after: None,
+            message: message.into(),
+        }
+    }
+
+    /// Class and `Retry-After` of `err`; errors that are not a
+    /// [`ProviderError`] count as [`ErrorClass::Other`].
+    pub fn classify(err: &anyhow::Error) -> (ErrorClass, Option<Duration>) {
+        match err.downcast_ref::<ProviderError>() {
+            Some(e) => (e.class, e.retry_after),
+            None => (ErrorClass::Other, None),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// Pass successful responses through and turn the rest into a [`ProviderError`].
+pub(crate) async fn check_status(
+    label: &str,
+    response: reqwest::Response,
+) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let error_text = response.text().await.unwrap_or_default();
+    tracing::error!("{} API error ({}): {}", label, status, error_text);
+    Err(ProviderError {
+        class: ErrorClass::from_status(status.as_u16(), &error_text),
+        status: Some(status.as_u16()),
+        retry_after,
+        message: format!("{} API returned error: {}", label, status),
+    }
+    .into())
+}
+
+/// Wrap a transport failure so timeouts and refused connections cool down.
+pub(crate) fn request_error(label: &str, e: reqwest::Error) -> anyhow::Error {
+    let class = if e.is_timeout() {
+        ErrorClass::Timeout
+    } else if e.is_connect() {
+        ErrorClass::ServerError
+    } else {
+        ErrorClass::Other
+    };
+    ProviderError::new(class, format!("{} request failed: {}", label, e)).into()
+}
+
+/// Parse a `Retry-After` value, either delay-seconds or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// How long a provider sits out after a failure of each class.
+///
+/// The base duration doubles with each consecutive failure up to `max`.
+/// A `Retry-After` from the provider replaces the computed backoff.
+#[derive(Debug, Clone)]
+pub struct CooldownPolicy {
+    pub rate_limited: Duration,
+    pub quota_exceeded: Duration,
+    pub server_error: Duration,
+    pub timeout: Duration,
+    pub auth: Duration,
+    pub max: Duration,
+}
+
+impl Default for CooldownPolicy {
+    fn default() -> Self {
+        Self {
+            rate_limited: Duration::from_secs(5),
+            quota_exceeded: Duration::from_secs(5 * 60),
+            server_error: Duration::from_secs(2),
+            timeout: Duration::from_secs(5),
+            auth: Duration::from_secs(10 * 60),
+            max: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+impl CooldownPolicy {
+    /// Cooldown after the `failures`-th consecutive failure, or `None` when
+    /// the class does not warrant one.
+    pub fn cooldown(
+        &self,
+        class: ErrorClass,
+        failures: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        let base = match class {
+            ErrorClass::RateLimited => self.rate_limited,
+            ErrorClass::QuotaExceeded => self.quota_exceeded,
+            ErrorClass::ServerError => self.server_error,
+            ErrorClass::Timeout => self.timeout,
+            ErrorClass::Auth => self.auth,
+            ErrorClass::Other => return None,
+        };
+        let backoff = base.saturating_mul(1 << failures.saturating_sub(1).min(16));
+        Some(retry_after.unwrap_or(backoff).min(self.max))
+    }
+}
+
+/// A provider that is cooling down, as persisted by a [`CooldownStore`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CooldownEntry {
+    /// [`ProviderId::key`](crate::prelude::ProviderId::key) of the provider.
+    pub provider: String,
+    pub class: ErrorClass,
+    /// Consecutive failures that led to this cooldown.
+    pub failures: u32,
+    /// End of the cooldown in milliseconds since the Unix epoch.
+    pub until_ms: u64,
+}
+
+impl CooldownEntry {
+    pub fn new(
+        provider: impl Into<String>,
+        class: ErrorClass,
+        failures: u32,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            class,
+            failures,
+            until_ms: unix_ms(SystemTime::now() + duration),
+        }
+    }
+
+    /// Time left before the provider may be used again.
+    pub fn remaining(&self) -> Option<Duration> {
+        let until = UNIX_EPOCH + Duration::from_millis(self.until_ms);
+        until
+            .duration_since(SystemTime::now())
+            .ok()
+            .filter(|d| !d.is_zero())
+    }
+}
+
+fn unix_ms(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Where cooldowns live. Implementations backed by a database let several
+/// processes share rate-limit state for the same API keys.
+#[async_trait]
+pub trait CooldownStore: Send + Sync + std::fmt::Debug {
+    async fn get(&self, provider: &str) -> anyhow::Result<Option<CooldownEntry>>;
+    async fn put(&self, entry: &CooldownEntry) -> anyhow::Result<()>;
+    async fn clear(&self, provider: &str) -> anyhow::Result<()>;
+}
+
+/// Process-local cooldowns.
+#[derive(Debug, Default)]
+pub struct InMemoryCooldownStore {
+    entries: Mutex<HashMap<String, CooldownEntry>>,
+}
+
+impl InMemoryCooldownStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CooldownStore for InMemoryCooldownStore {
+    async fn get(&self, provider: &str) -> anyhow::Result<Option<CooldownEntry>> {
+        Ok(self.entries.lock().unwrap().get(provider).cloned())
+    }
+    async fn put(&self, entry: &CooldownEntry) -> anyhow::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(entry.provider.clone(), entry.clone());
+        Ok(())
+    }
+    async fn clear(&self, provider: &str) -> anyhow::Result<()> {
+        self.entries.lock().unwrap().remove(provider);
+        Ok(())
+    }
+}
+
+/// Smoothing factor for the latency and success-rate moving averages.
+const EWMA_ALPHA: f64 = 0.3;
+
+/// Latency assumed for a provider that has not answered yet.
+const DEFAULT_LATENCY: Duration = Duration::from_secs(1);
+
+/// Health observed by this process for one provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderStats {
+    pub calls: u64,
+    pub failures: u64,
+    /// Failures since the last success.
+    pub consecutive_failures: u32,
+    /// Moving average of successes, starting optimistic at 1.0.
+    pub success_rate: f64,
+    /// Moving average latency of successful calls.
+    pub latency: Option<Duration>,
+}
+
+impl Default for ProviderStats {
+    fn default() -> Self {
+        Self {
+            calls: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            success_rate: 1.0,
+            latency: None,
+        }
+    }
+}
+
+impl ProviderStats {
+    pub(crate) fn record_success(&mut self, latency: Duration) {
+        self.calls += 1;
+        self.consecutive_failures = 0;
+        self.success_rate += EWMA_ALPHA * (1.0 - self.success_rate);
+        self.latency = Some(match self.latency {
+            Some(avg) => avg.mul_f64(1.0 - EWMA_ALPHA) + latency.mul_f64(EWMA_ALPHA),
+            None => latency,
+        });
+    }
+
+    pub(crate) fn record_failure(&mut self) {
+        self.calls += 1;
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.success_rate -= EWMA_ALPHA * self.success_rate;
+    }
+
+    /// Selection weight: favours reliable, fast and cheap providers. Never
+    /// zero, so a recovering provider still gets the occasional call.
+    pub fn weight(&self, cost_per_1k_tokens: f64) -> f64 {
+        let latency = self.latency.unwrap_or(DEFAULT_LATENCY).as_secs_f64();
+        self.success_rate.max(0.05) / (1.0 + latency) / (1.0 + cost_per_1k_tokens.max(0.0))
+    }
+}
+
+/// Indices of `weights` in a random order where heavier entries tend to come
+/// first (weighted sampling without replacement).
+pub(crate) fn weighted_order(weights: &[f64]) -> Vec<usize> {
+    let mut remaining: Vec<(usize, f64)> = weights.iter().copied().enumerate().collect();
+    let mut order = Vec::with_capacity(weights.len());
+    while !remaining.is_empty() {
+        let total: f64 = remaining.iter().map(|(_, w)| w).sum();
+        let mut pick = fastrand::f64() * total;
+        let mut chosen = remaining.len() - 1;
+        for (i, (_, w)) in remaining.iter().enumerate() {
+            if pick < *w {
+                chosen = i;
+                break;
+            }
+            pick -= w;
+        }
+        order.push(remaining.remove(chosen).0);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_http::{MockResponse, MockServer};
+    use crate::prelude::{LLMProvider, OpenAiCompatibleProvider, ProviderId, StochasticRotator};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(ErrorClass::from_status(429, ""), ErrorClass::RateLimited);
+        assert_eq!(
+            ErrorClass::from_status(429, r#"{"error":{"status":"RESOURCE_EXHAUSTED"}}"#),
+            ErrorClass::QuotaExceeded
+        );
+        assert_eq!(
+            ErrorClass::from_status(429, r#"{"error":{"code":"insufficient_quota"}}"#),
+            ErrorClass::QuotaExceeded
+        );
+        assert_eq!(ErrorClass::from_status(503, ""), ErrorClass::ServerError);
+        assert_eq!(ErrorClass::from_status(504, ""), ErrorClass::Timeout);
+        assert_eq!(ErrorClass::from_status(401, ""), ErrorClass::Auth);
+        assert_eq!(ErrorClass::from_status(400, "quota"), ErrorClass::Other);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 12 "), Some(Duration::from_secs(12)));
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        let parsed = parse_retry_after(&later).unwrap();
+        assert!(parsed > Duration::from_secs(110) && parsed <= Duration::from_secs(120));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_cooldown_backoff() {
+        let policy = CooldownPolicy::default();
+        let rl = ErrorClass::RateLimited;
+        assert_eq!(policy.cooldown(rl, 1, None), Some(Duration::from_secs(5)));
+        assert_eq!(policy.cooldown(rl, 3, None), Some(Duration::from_secs(20)));
+        assert_eq!(policy.cooldown(rl, 40, None), Some(policy.max));
+        assert_eq!(
+            policy.cooldown(rl, 3, Some(Duration::from_secs(1))),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(policy.cooldown(ErrorClass::Other, 1, None), None);
+    }
+
+    #[test]
+    fn test_weights_prefer_healthy_cheap_providers() {
+        let mut fast = ProviderStats::default();
+        fast.record_success(Duration::from_millis(200));
+        let mut slow = ProviderStats::default();
+        slow.record_success(Duration::from_secs(4));
+        let mut flaky = fast.clone();
+        flaky.record_failure();
+        flaky.record_failure();
+
+        assert!(fast.weight(0.0) > slow.weight(0.0));
+        assert!(fast.weight(0.0) > flaky.weight(0.0));
+        assert!(fast.weight(0.0) > fast.weight(2.0));
+        assert!(flaky.weight(0.0) > 0.0);
+
+        let firsts = (0..500)
+            .filter(|_| weighted_order(&[fast.weight(0.0), slow.weight(0.0)])[0] == 0)
+            .count();
+        assert!(firsts > 300, "fast provider first only {firsts}/500 times");
+        let mut order = weighted_order(&[1.0, 0.0, 2.0]);
+        order.sort();
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    fn completion(text: &str) -> MockResponse {
+        MockResponse::new(
+            200,
+            "application/json",
+            serde_json::json!({"choices": [{"message": {"role": "assistant", "content": text}}]})
+                .to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_rotator_cools_down_rate_limited_provider() {
+        let limited = MockServer::start(vec![MockResponse::new(
+            429,
+            "application/json",
+            r#"{"error":{"message":"slow down"}}"#,
+        )
+        .with_header("retry-after", "60")])
+        .await;
+        let healthy = MockServer::start(vec![
+            completion("one"),
+            completion("two"),
+            completion("three"),
+        ])
+        .await;
+
+        let store = Arc::new(InMemoryCooldownStore::new());
+        let mut rotator = StochasticRotator::new(store.clone());
+        rotator.add_provider(
+            ProviderId::new("limited", "m"),
+            Arc::new(OpenAiCompatibleProvider::new(&limited.base_url, "m")),
+        );
+        rotator.add_provider(
+            ProviderId::new("healthy", "m"),
+            Arc::new(OpenAiCompatibleProvider::new(&healthy.base_url, "m")),
+        );
+
+        // Whichever provider is drawn first, the limited one is hit at most
+        // once and then sits out for its Retry-After.
+        let mut replies = Vec::new();
+        for _ in 0..3 {
+            replies.push(rotator.generate("hi").await.unwrap());
+        }
+        assert_eq!(replies, vec!["one", "two", "three"]);
+        assert!(limited.requests().len() <= 1);
+
+        if let Some(entry) = store.get("limited/m").await.unwrap() {
+            assert_eq!(entry.class, ErrorClass::RateLimited);
+            let left = entry.remaining().unwrap();
+            assert!(left > Duration::from_secs(55) && left <= Duration::from_secs(60));
+        }
+        assert_eq!(rotator.stats()["healthy/m"].calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_rotator_fails_fast_when_all_cooling_down() {
+        let store = Arc::new(InMemoryCooldownStore::new());
+        store
+            .put(&CooldownEntry::new(
+                "only/m",
+                ErrorClass::QuotaExceeded,
+                1,
+                Duration::from_secs(30),
+            ))
+            .await
+            .unwrap();
+        let server = MockServer::start(vec![completion("unused")]).await;
+        let mut rotator = StochasticRotator::new(store);
+        rotator.add_provider(
+            ProviderId::new("only", "m"),
+            Arc::new(OpenAiCompatibleProvider::new(&server.base_url, "m")),
+        );
+
+        let err = rotator.generate("hi").await.unwrap_err();
+        let (class, retry_after) = ProviderError::classify(&err);
+        assert_eq!(class, ErrorClass::RateLimited);
+        assert!(retry_after.unwrap() <= Duration::from_secs(30));
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+//! Streaming support for provider replies.
+//!
+//! Providers that talk server-sent events (SSE) feed the raw response bytes
+//! through [`sse_deltas`], which splits them into `data:` payloads and lets
+//! the provider turn each payload into a text delta. Providers that stream
+//! newline-delimited JSON (Ollama) use [`ndjson_deltas`] the same way.
+
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+
+/// Incremental reply from a provider: each item is the next piece of text.
+pub type TokenStream = BoxStream<'static, anyhow::Result<String>>;
+
+/// A stream that yields `text` as its only delta.
+pub fn single_delta(text: String) -> TokenStream {
+    stream::once(async move { Ok(text) }).boxed()
+}
+
+/// Splits a streamed body into complete events.
+trait EventDecoder: Send + 'static {
+    /// Feed the next chunk of the body; returns every event it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<String>;
+    /// Flush the event left open when the body ends.
+    fn finish(&mut self) -> Option<String>;
+}
+
+/// One event of a `text/event-stream` body.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// The `event:` field, when the server named the event.
+    pub event: Option<String>,
+    /// The `id:` field.
+    pub id: Option<String>,
+    /// The `data:` lines, joined with `\n`.
+    pub data: String,
+}
+
+/// Incremental decoder for `text/event-stream` bodies.
+///
+/// Keeps the `data:`, `event:` and `id:` fields; `retry:` and comment lines
+/// are ignored. Multi-line data is joined with `\n` as the SSE spec requires,
+/// and blocks without data are not events.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    pending: Vec<u8>,
+    event: Option<String>,
+    id: Option<String>,
+    data: Option<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next chunk of the body; returns every event it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.pending.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            if let Some(event) = self.line(&line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flush the event left open when the body ends without a blank line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let rest = std::mem::take(&mut self.pending);
+        if !rest.is_empty() {
+            self.line(&rest);
+        }
+        self.dispatch()
+    }
+
+    fn line(&mut self, raw: &[u8]) -> Option<SseEvent> {
+        let line = String::from_utf8_lossy(raw);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "event" => self.event = Some(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    /// End the current block; it is an event only if it carried data.
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let id = self.id.take();
+        self.data.take().map(|data| SseEvent { event, id, data })
+    }
+}
+
+impl EventDecoder for SseDecoder {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        SseDecoder::push(self, chunk)
+            .into_iter()
+            .map(|event| event.data)
+            .collect()
+    }
+    fn finish(&mut self) -> Option<String> {
+        SseDecoder::finish(self).map(|event| event.data)
+    }
+}
+
+/// Decoder for newline-delimited JSON: every non-blank line is an event.
+#[derive(Debug, Default)]
+struct LineDecoder {
+    pending: Vec<u8>,
+}
+
+impl EventDecoder for LineDecoder {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                events.push(line);
+            }
+        }
+        events
+    }
+    fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        let line = String::from_utf8_lossy(&rest).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+/// Turn an SSE body into a [`TokenStream`].
+///
+/// `parse` maps each event payload to a delta; returning `Ok(None)` skips the
+/// event (keep-alives, `[DONE]` markers, usage summaries).
+pub fn sse_deltas<S, B, E, F>(body: S, parse: F) -> TokenStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    F: FnMut(&str) -> anyhow::Result<Option<String>> + Send + 'static,
+{
+    deltas(body, SseDecoder::new(), parse)
+}
+
+/// Turn a newline-delimited JSON body into a [`TokenStream`].
+///
+/// `parse` receives each line, with the same contract as in [`sse_deltas`].
+pub fn ndjson_deltas<S, B, E, F>(body: S, parse: F) -> TokenStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    F: FnMut(&str) -> anyhow::Result<Option<String>> + Send + 'static,
+{
+    deltas(body, LineDecoder::default(), parse)
+}
+
+fn deltas<S, B, E, D, F>(body: S, decoder: D, mut parse: F) -> TokenStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    D: EventDecoder,
+    F: FnMut(&str) -> anyhow::Result<Option<String>> + Send + 'static,
+{
+    let events = stream::unfold(
+        (body.boxed(), decoder, false),
+        |(mut body, mut decoder, done)| async move {
+            if done {
+                return None;
+            }
+            let (events, done): (Vec<anyhow::Result<String>>, bool) = match body.next().await {
+                Some(Ok(chunk)) => (
+                    decoder.push(chunk.as_ref()).into_iter().map(Ok).collect(),
+                    false,
+                ),
+                Some(Err(e)) => (
+                    vec![Err(anyhow::anyhow!("Stream interrupted: {}", e))],
+                    true,
+                ),
+                None => (decoder.finish().into_iter().map(Ok).collect(), true),
+            };
+            Some((events, (body, decoder, done)))
+        },
+    );
+
+    events
+        .flat_map(stream::iter)
+        .filter_map(move |event| {
+            let delta = event.and_then(|data| parse(&data)).transpose();
+            async move { delta }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{InMemoryCooldownStore, LLMProvider, ProviderId, StochasticRotator};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_decoder_splits_events_across_chunks() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"event: message\r\ndata: {\"a\":").is_empty());
+        let events = decoder.push(b"1}\r\n\r\n: keep-alive\n\nda");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.as_deref(), Some("message"));
+        assert_eq!(events[0].data, "{\"a\":1}");
+        let events = decoder.push(b"ta: one\nevent: x\nid: 7\ndata:two\n\nid: 8\n\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: Some("x".into()),
+                id: Some("7".into()),
+                data: "one\ntwo".into(),
+            }]
+        );
+        assert!(decoder.push(b"data: [DONE]").is_empty());
+        assert_eq!(
+            decoder.finish().map(|event| event.data).as_deref(),
+            Some("[DONE]")
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sse_deltas_parse_and_skip() {
+        let body = stream::iter(vec![
+            Ok::<_, std::io::Error>(b"data: hel\n\ndata: lo\n".to_vec()),
+            Ok(b"\ndata: [DONE]\n\n".to_vec()),
+        ]);
+        let deltas: Vec<String> =
+            sse_deltas(
+                body,
+                |data| Ok((data != "[DONE]").then(|| data.to_string())),
+            )
+            .map(|d| d.unwrap())
+            .collect()
+            .await;
+        assert_eq!(deltas, vec!["hel", "lo"]);
+
+        let broken = stream::iter(vec![
+            Ok(b"data: a\n\n".to_vec()),
+            Err(std::io::Error::other("reset")),
+        ]);
+        let items: Vec<_> = sse_deltas(broken, |d| Ok(Some(d.to_string())))
+            .collect()
+            .await;
+        assert_eq!(items.len(), 2);
+        assert!(items[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_deltas_split_lines() {
+        let body = stream::iter(vec![
+            Ok::<_, std::io::Error>(b"{\"t\":\"a\"}\n\n{\"t\"".to_vec()),
+            Ok(b":\"b\"}\n{\"t\":\"c\"}".to_vec()),
+        ]);
+        let deltas: Vec<String> = ndjson_deltas(body, |line| {
+            let value: serde_json::Value = serde_json::from_str(line)?;
+            Ok(value["t"].as_str().map(str::to_string))
+        })
+        .map(|d| d.unwrap())
+        .collect()
+        .await;
+        assert_eq!(deltas, vec!["a", "b", "c"]);
+    }
+
+    #[derive(Debug)]
+    struct FixedProvider {
+        reply: Option<&'static str>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for FixedProvider {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+        fn cost_per_1k_tokens(&self) -> f64 {
+            0.0
+        }
+        async fn generate(&self, _prompt: &str) -> anyhow::Result<String> {
+            self.reply
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotator_streams_from_first_working_provider() {
+        let mut rotator = StochasticRotator::new(Arc::new(InMemoryCooldownStore::new()));
+        rotator.add_provider(
+            ProviderId::new("a", "down"),
+            Arc::new(FixedProvider { reply: None }),
+        );
+        rotator.add_provider(
+            ProviderId::new("b", "up"),
+            Arc::new(FixedProvider {
+                reply: Some("full reply"),
+            }),
+        );
+        for _ in 0..2 {
+            let deltas: Vec<String> = rotator
+                .generate_stream("hi")
+                .await
+                .unwrap()
+                .map(|d| d.unwrap())
+                .collect()
+                .await;
+            assert_eq!(deltas, vec!["full reply"]);
+        }
+    }
+}
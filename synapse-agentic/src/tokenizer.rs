@@ -0,0 +1,330 @@
+//! Token counting with the model's own tokenizer.
+//!
+//! [`TokenizerRegistry`] picks a [`TokenCounter`] per model id from a
+//! directory of local tokenizer files:
+//!
+//! - `<dir>/cl100k_base.tiktoken` and `<dir>/o200k_base.tiktoken`, the
+//!   OpenAI BPE rank files, for GPT and o-series models;
+//! - `<dir>/<model id>/tokenizer.json`, the Hugging Face format, for
+//!   everything else (BPE models and SentencePiece conversions alike).
+//!
+//! Models without a file fall back to [`SimpleTokenEstimator`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use base64::Engine;
+use tiktoken_rs::CoreBPE;
+
+use crate::prelude::{SimpleTokenEstimator, TokenCounter};
+
+const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+const O200K_PATTERN: &str = concat!(
+    r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+(?!\S)|\s+",
+);
+
+/// Model id lowercased without its `org/` prefix, dashes or underscores, so
+/// `openai/GPT-4o` and `gpt4o` compare equal.
+fn model_key(model: &str) -> String {
+    model
+        .rsplit('/')
+        .next()
+        .unwrap_or(model)
+        .to_lowercase()
+        .replace(['-', '_', ' '], "")
+}
+
+/// Context window of `model` in tokens; 8K for unknown models.
+pub fn context_window(model: &str) -> u32 {
+    let key = model_key(model);
+    let is = |prefix: &str| key.starts_with(prefix);
+    let has = |part: &str| key.contains(part);
+    if is("gpt4.1") {
+        1_047_576
+    } else if is("gpt4o") || is("gpt4turbo") || is("chatgpt") {
+        128_000
+    } else if is("o1") || is("o3") || is("o4") || has("claude") {
+        200_000
+    } else if is("gpt432k") {
+        32_768
+    } else if is("gpt4") {
+        8_192
+    } else if is("gpt3.5") {
+        16_385
+    } else if has("gemini1.5pro") {
+        2_097_152
+    } else if has("gemini") {
+        1_048_576
+    } else if has("llama3.1") || has("llama3.2") || has("llama3.3") || has("mistrallarge") {
+        131_072
+    } else if has("llama3") {
+        8_192
+    } else if has("llama2") {
+        4_096
+    } else if has("mistral") || has("mixtral") || has("qwen") {
+        32_768
+    } else if has("deepseek") {
+        65_536
+    } else if has("abab") || has("minimax") {
+        245_760
+    } else {
+        8_192
+    }
+}
+
+/// OpenAI BPE encodings read from `.tiktoken` rank files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiktokenEncoding {
+    Cl100kBase,
+    O200kBase,
+}
+
+impl TiktokenEncoding {
+    /// Encoding used by an OpenAI model, if `model` is one.
+    pub fn for_model(model: &str) -> Option<Self> {
+        let key = model_key(model);
+        let is = |prefix: &str| key.starts_with(prefix);
+        if is("gpt4o") || is("gpt4.1") || is("chatgpt") || is("o1") || is("o3") || is("o4") {
+            Some(Self::O200kBase)
+        } else if is("gpt4") || is("gpt3.5") || is("textembedding") {
+            Some(Self::Cl100kBase)
+        } else {
+            None
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Cl100kBase => "cl100k_base.tiktoken",
+            Self::O200kBase => "o200k_base.tiktoken",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::Cl100kBase => CL100K_PATTERN,
+            Self::O200kBase => O200K_PATTERN,
+        }
+    }
+}
+
+/// Counts tokens with an OpenAI BPE encoding.
+pub struct TiktokenCounter {
+    encoding: TiktokenEncoding,
+    bpe: CoreBPE,
+}
+
+impl TiktokenCounter {
+    /// Load a `.tiktoken` rank file: one base64 token and its rank per line.
+    pub fn from_file(path: impl AsRef<Path>, encoding: TiktokenEncoding) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let ranks = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tokenizer {}", path.display()))?;
+        Self::from_ranks(&ranks, encoding)
+            .with_context(|| format!("Failed to load tokenizer {}", path.display()))
+    }
+
+    fn from_ranks(ranks: &str, encoding: TiktokenEncoding) -> anyhow::Result<Self> {
+        let mut encoder = rustc_hash::FxHashMap::default();
+        for line in ranks.lines().filter(|l| !l.trim().is_empty()) {
+            let (token, rank) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("Malformed rank line: {}", line))?;
+            encoder.insert(
+                base64::engine::general_purpose::STANDARD.decode(token)?,
+                rank.trim().parse()?,
+            );
+        }
+        let bpe = CoreBPE::new(encoder, Default::default(), encoding.pattern())?;
+        Ok(Self { encoding, bpe })
+    }
+}
+
+impl std::fmt::Debug for TiktokenCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TiktokenCounter")
+            .field("encoding", &self.encoding)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenCounter for TiktokenCounter {
+    fn count_tokens(&self, text: &str) -> anyhow::Result<u32> {
+        Ok(self.bpe.encode_ordinary(text).len() as u32)
+    }
+}
+
+/// Counts tokens with a Hugging Face `tokenizer.json`.
+pub struct HfTokenCounter {
+    tokenizer: tokenizers::Tokenizer,
+}
+
+impl HfTokenCounter {
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let tokenizer = tokenizers::Tokenizer::from_file(path)
+            .map_err(|e| anyhow::anyhow!("Failed to load tokenizer {}: {}", path.display(), e))?;
+        Ok(Self { tokenizer })
+    }
+}
+
+impl std::fmt::Debug for HfTokenCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HfTokenCounter")
+            .field("vocab_size", &self.tokenizer.get_vocab_size(true))
+            .finish()
+    }
+}
+
+impl TokenCounter for HfTokenCounter {
+    fn count_tokens(&self, text: &str) -> anyhow::Result<u32> {
+        let encoding = self
+            .tokenizer
+            .encode(text, false)
+            .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
+        Ok(encoding.len() as u32)
+    }
+}
+
+/// Loads and caches one [`TokenCounter`] per model from a tokenizer directory.
+#[derive(Debug)]
+pub struct TokenizerRegistry {
+    dir: PathBuf,
+    counters: Mutex<HashMap<String, Arc<dyn TokenCounter>>>,
+}
+
+impl TokenizerRegistry {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counter for `model`: its tokenizer file when one is present, else
+    /// [`SimpleTokenEstimator`].
+    pub fn counter_for(&self, model: &str) -> Arc<dyn TokenCounter> {
+        if let Some(counter) = self.counters.lock().unwrap().get(model) {
+            return counter.clone();
+        }
+        let counter = self.load(model).unwrap_or_else(|e| {
+            tracing::warn!("Estimating tokens for {}: {:#}", model, e);
+            Arc::new(SimpleTokenEstimator::new(model))
+        });
+        self.counters
+            .lock()
+            .unwrap()
+            .insert(model.to_string(), counter.clone());
+        counter
+    }
+
+    fn load(&self, model: &str) -> anyhow::Result<Arc<dyn TokenCounter>> {
+        if let Some(encoding) = TiktokenEncoding::for_model(model) {
+            let path = self.dir.join(encoding.file_name());
+            if path.is_file() {
+                return Ok(Arc::new(TiktokenCounter::from_file(path, encoding)?));
+            }
+        }
+        let path = self.dir.join(model).join("tokenizer.json");
+        if path.is_file() {
+            return Ok(Arc::new(HfTokenCounter::from_file(path)?));
+        }
+        Err(anyhow::anyhow!(
+            "no tokenizer file for it in {}",
+            self.dir.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_windows_and_encodings() {
+        assert_eq!(context_window("gpt-4o-mini"), 128_000);
+        assert_eq!(context_window("openai/GPT-4.1"), 1_047_576);
+        assert_eq!(context_window("gpt-4"), 8_192);
+        assert_eq!(context_window("meta-llama/Llama-3.1-8B-Instruct"), 131_072);
+        assert_eq!(context_window("gemini-2.0-flash"), 1_048_576);
+        assert_eq!(context_window("unknown-model"), 8_192);
+
+        assert_eq!(
+            TiktokenEncoding::for_model("gpt-4o"),
+            Some(TiktokenEncoding::O200kBase)
+        );
+        assert_eq!(
+            TiktokenEncoding::for_model("gpt-3.5-turbo"),
+            Some(TiktokenEncoding::Cl100kBase)
+        );
+        assert_eq!(TiktokenEncoding::for_model("llama3"), None);
+    }
+
+    #[test]
+    fn test_registry_loads_files_and_falls_back() {
+        let dir = std::env::temp_dir().join(format!("synapse-tokenizers-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("tiny-bpe")).unwrap();
+
+        // A byte-level rank file with the merges "ab" and "abc".
+        let b64 = |s: &str| base64::engine::general_purpose::STANDARD.encode(s);
+        let mut ranks: Vec<String> = (0u8..=255)
+            .map(|b| {
+                format!(
+                    "{} {}",
+                    base64::engine::general_purpose::STANDARD.encode([b]),
+                    b
+                )
+            })
+            .collect();
+        ranks.push(format!("{} 256", b64("ab")));
+        ranks.push(format!("{} 257", b64("abc")));
+        std::fs::write(dir.join("cl100k_base.tiktoken"), ranks.join("\n")).unwrap();
+
+        std::fs::write(
+            dir.join("tiny-bpe").join("tokenizer.json"),
+            serde_json::json!({
+                "version": "1.0",
+                "truncation": null,
+                "padding": null,
+                "added_tokens": [],
+                "normalizer": null,
+                "pre_tokenizer": {"type": "Whitespace"},
+                "post_processor": null,
+                "decoder": null,
+                "model": {
+                    "type": "BPE",
+                    "vocab": {
+                        "h": 0, "e": 1, "l": 2, "o": 3, "w": 4, "r": 5, "d": 6,
+                        "he": 7, "ll": 8, "hell": 9, "hello": 10
+                    },
+                    "merges": ["h e", "l l", "he ll", "hell o"]
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let registry = TokenizerRegistry::new(&dir);
+        assert_eq!(
+            registry.counter_for("gpt-4").count_tokens("abcab").unwrap(),
+            2
+        );
+        assert_eq!(
+            registry
+                .counter_for("tiny-bpe")
+                .count_tokens("hello world")
+                .unwrap(),
+            6
+        );
+        let fallback = registry.counter_for("gpt-4o");
+        assert!(format!("{:?}", fallback).contains("SimpleTokenEstimator"));
+        assert!(Arc::ptr_eq(&fallback, &registry.counter_for("gpt-4o")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! Token counts and spend for a provider call.
+
+use serde::{Deserialize, Serialize};
+
+/// Tokens used by one or more calls to a provider, and what they cost.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Provider that served the calls. [`StochasticRotator`] reports the
+    /// [`ProviderId::key`] of the provider it routed to.
+    ///
+    /// [`StochasticRotator`]: crate::prelude::StochasticRotator
+    /// [`ProviderId::key`]: crate::prelude::ProviderId::key
+    #[serde(default)]
+    pub provider: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// Counts were estimated from text length because the provider reported none.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl Usage {
+    /// Usage as reported by a provider, priced at `cost_per_1k_tokens`.
+    pub fn new(
+        provider: impl Into<String>,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        cost_per_1k_tokens: f64,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            prompt_tokens,
+            completion_tokens,
+            cost_usd: (prompt_tokens + completion_tokens) as f64 / 1000.0 * cost_per_1k_tokens,
+            estimated: false,
+        }
+    }
+
+    /// Usage estimated from the prompt and reply text, for providers that
+    /// do not report token counts.
+    pub fn estimate(
+        provider: impl Into<String>,
+        prompt: &str,
+        completion: &str,
+        cost_per_1k_tokens: f64,
+    ) -> Self {
+        Self {
+            estimated: true,
+            ..Self::new(
+                provider,
+                estimate_tokens(prompt),
+                estimate_tokens(completion),
+                cost_per_1k_tokens,
+            )
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Add `other`'s counts and cost to these.
+    pub fn add(&mut self, other: &Usage) {
+        if self.provider.is_empty() {
+            self.provider = other.provider.clone();
+        }
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost_usd += other.cost_usd;
+        self.estimated |= other.estimated;
+    }
+}
+
+/// Rough token count for `text`, at about four characters per token.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_pricing_and_sum() {
+        let mut total = Usage::new("openai/gpt-4o", 1500, 500, 0.01);
+        assert_eq!(total.total_tokens(), 2000);
+        assert!((total.cost_usd - 0.02).abs() < 1e-9);
+
+        let estimated = Usage::estimate("openai/gpt-4o", "abcdefgh", "abc", 0.0);
+        assert_eq!(
+            (estimated.prompt_tokens, estimated.completion_tokens),
+            (2, 1)
+        );
+        assert!(estimated.estimated);
+
+        total.add(&estimated);
+        assert_eq!(total.total_tokens(), 2003);
+        assert!(total.estimated);
+        assert_eq!(total.provider, "openai/gpt-4o");
+    }
+}